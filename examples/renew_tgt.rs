@@ -0,0 +1,78 @@
+//! A long running service that authenticates once, and then keeps its TGT valid by
+//! renewing it shortly before it expires. Once the ticket can no longer be renewed,
+//! the service authenticates again.
+//!
+//! cargo run --example renew_tgt -- --kdc 127.0.0.1:55000 --realm EXAMPLE.COM testuser
+
+use clap::Parser;
+use libkrime::client::KerberosClient;
+use libkrime::error::KrbError;
+use libkrime::proto::AuthenticatedReply;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+
+/// Renew the TGT this long before it expires.
+const RENEW_MARGIN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Parser)]
+struct Opt {
+    /// The address of the KDC.
+    #[arg(long, default_value = "127.0.0.1:55000")]
+    kdc: String,
+    #[arg(long, default_value = "EXAMPLE.COM")]
+    realm: String,
+    /// How long each ticket is requested for, in seconds.
+    #[arg(long, default_value_t = 600)]
+    lifetime: u64,
+    principal: String,
+    #[arg(long, env = "KRB5_PASSWORD")]
+    password: String,
+}
+
+async fn authenticate(
+    client: &mut KerberosClient,
+    opt: &Opt,
+) -> Result<AuthenticatedReply, KrbError> {
+    let until = SystemTime::now() + Duration::from_secs(opt.lifetime);
+    let renew_until = until + Duration::from_secs(86400);
+    let tgs = format!("krbtgt/{}", opt.realm);
+
+    client
+        .authenticate_renewable(&opt.principal, &tgs, &opt.password, until, renew_until)
+        .await
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), KrbError> {
+    tracing_subscriber::fmt::init();
+
+    let opt = Opt::parse();
+
+    let mut client = KerberosClient::connect(opt.kdc.as_str(), &opt.realm).await?;
+    let mut tgt = authenticate(&mut client, &opt).await?;
+
+    loop {
+        info!(end_time = ?tgt.end_time(), renew_till = ?tgt.renew_till(), "Holding TGT");
+
+        let wake = tgt
+            .end_time()
+            .checked_sub(RENEW_MARGIN)
+            .and_then(|wake| wake.duration_since(SystemTime::now()).ok())
+            .unwrap_or_default();
+        tokio::time::sleep(wake).await;
+
+        let until = SystemTime::now() + Duration::from_secs(opt.lifetime);
+        tgt = match client.renew_ticket(&tgt, until).await {
+            Ok(renewed) => renewed,
+            Err(KrbError::TicketNotRenewable) | Err(KrbError::RenewTillExpired) => {
+                info!("TGT can no longer be renewed, authenticating again");
+                authenticate(&mut client, &opt).await?
+            }
+            Err(KrbError::RenewalFailed(code)) => {
+                error!(?code, "KDC refused to renew the TGT, authenticating again");
+                authenticate(&mut client, &opt).await?
+            }
+            Err(err) => return Err(err),
+        };
+    }
+}
@@ -0,0 +1,16 @@
+use der::flagset::flags;
+
+flags! {
+    /// ```text
+    /// APOptions       ::= KerberosFlags
+    ///         -- reserved(0),
+    ///         -- use-session-key(1),
+    ///         -- mutual-required(2)
+    /// ````
+    #[repr(u32)]
+    pub(crate) enum ApOptions: u32 {
+        Reserved       = 1 << 0,
+        UseSessionKey  = 1 << 1,
+        MutualRequired = 1 << 2,
+    }
+}
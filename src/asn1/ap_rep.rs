@@ -0,0 +1,45 @@
+use super::encrypted_data::EncryptedData;
+use der::{Decode, DecodeValue, Encode, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
+
+/// ```text
+/// AP-REP          ::= [APPLICATION 15] SEQUENCE {
+///         pvno            [0] INTEGER (5),
+///         msg-type        [1] INTEGER (15),
+///         enc-part        [2] EncryptedData -- EncAPRepPart
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct ApRep {
+    #[asn1(context_specific = "0")]
+    pub(crate) pvno: u8,
+    #[asn1(context_specific = "1")]
+    pub(crate) msg_type: u8,
+    #[asn1(context_specific = "2")]
+    pub(crate) enc_part: EncryptedData,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct TaggedApRep(pub(crate) ApRep);
+
+impl FixedTag for TaggedApRep {
+    const TAG: Tag = Tag::Application {
+        constructed: true,
+        number: TagNumber::N15,
+    };
+}
+
+impl<'a> DecodeValue<'a> for TaggedApRep {
+    fn decode_value<R: der::Reader<'a>>(reader: &mut R, _header: der::Header) -> der::Result<Self> {
+        let r: ApRep = ApRep::decode(reader)?;
+        Ok(Self(r))
+    }
+}
+
+impl EncodeValue for TaggedApRep {
+    fn value_len(&self) -> der::Result<der::Length> {
+        self.0.encoded_len()
+    }
+    fn encode_value(&self, encoder: &mut impl der::Writer) -> der::Result<()> {
+        self.0.encode(encoder)
+    }
+}
@@ -0,0 +1,106 @@
+use super::kerberos_time::KerberosTime;
+use super::microseconds::Microseconds;
+use der::asn1::{Any, BitString, ObjectIdentifier, OctetString, Uint};
+use der::Sequence;
+
+/// RFC 4556 3.2.1
+/// ```text
+/// AuthPack ::= SEQUENCE {
+///         pkAuthenticator         [0] PKAuthenticator,
+///         clientPublicValue       [1] SubjectPublicKeyInfo OPTIONAL,
+///                  -- Type SubjectPublicKeyInfo is defined in
+///                  -- [RFC3280].
+///                  -- Specifies Diffie-Hellman domain parameters
+///                  -- and the client's public key value [IEEE1363].
+///         supportedCMSTypes       [2] SEQUENCE OF AlgorithmIdentifier
+///                                     OPTIONAL,
+///         clientDHNonce           [3] DHNonce OPTIONAL,
+///         ...
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct AuthPack {
+    #[asn1(context_specific = "0")]
+    pub(crate) pk_authenticator: PkAuthenticator,
+    #[asn1(context_specific = "1", optional = "true")]
+    pub(crate) client_public_value: Option<SubjectPublicKeyInfo>,
+    #[asn1(context_specific = "2", optional = "true")]
+    pub(crate) supported_cms_types: Option<Vec<AlgorithmIdentifier>>,
+    #[asn1(context_specific = "3", optional = "true")]
+    pub(crate) client_dh_nonce: Option<OctetString>,
+}
+
+/// ```text
+/// PKAuthenticator ::= SEQUENCE {
+///         cusec                   [0] INTEGER (0..999999),
+///         ctime                   [1] KerberosTime,
+///                  -- cusec and ctime are used as in [RFC4120], for
+///                  -- replay prevention.
+///         nonce                   [2] INTEGER (0..4294967295),
+///                  -- Chosen randomly; this nonce does not need to
+///                  -- match with the nonce in the KDC-REQ-BODY.
+///         paChecksum              [3] OCTET STRING OPTIONAL,
+///                  -- MUST be present.
+///                  -- Contains the SHA1 checksum, performed over
+///                  -- KDC-REQ-BODY.
+///         ...,
+///         freshnessToken          [4] OCTET STRING OPTIONAL,
+///                  -- RFC 8070 3, the token from PA-AS-FRESHNESS.
+///         ...
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct PkAuthenticator {
+    #[asn1(context_specific = "0")]
+    pub(crate) cusec: Microseconds,
+    #[asn1(context_specific = "1")]
+    pub(crate) ctime: KerberosTime,
+    #[asn1(context_specific = "2")]
+    pub(crate) nonce: u32,
+    #[asn1(context_specific = "3", optional = "true")]
+    pub(crate) pa_checksum: Option<OctetString>,
+    #[asn1(context_specific = "4", optional = "true")]
+    pub(crate) freshness_token: Option<OctetString>,
+}
+
+/// RFC 5280 4.1
+/// ```text
+/// SubjectPublicKeyInfo  ::=  SEQUENCE  {
+///         algorithm            AlgorithmIdentifier,
+///         subjectPublicKey     BIT STRING
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct SubjectPublicKeyInfo {
+    pub(crate) algorithm: AlgorithmIdentifier,
+    pub(crate) subject_public_key: BitString,
+}
+
+/// ```text
+/// AlgorithmIdentifier  ::=  SEQUENCE  {
+///         algorithm               OBJECT IDENTIFIER,
+///         parameters              ANY DEFINED BY algorithm OPTIONAL
+/// }
+/// ````
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
+pub(crate) struct AlgorithmIdentifier {
+    pub(crate) algorithm: ObjectIdentifier,
+    pub(crate) parameters: Option<Any>,
+}
+
+/// RFC 3279 2.3.3, the parameters of a dhpublicnumber key.
+/// ```text
+/// DomainParameters ::= SEQUENCE {
+///         p       INTEGER, -- odd prime, p=jq +1
+///         g       INTEGER, -- generator, g
+///         q       INTEGER, -- factor of p-1
+///         j       INTEGER OPTIONAL, -- subgroup factor
+///         validationParms  ValidationParms OPTIONAL
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct DomainParameters {
+    pub(crate) p: Uint,
+    pub(crate) g: Uint,
+    pub(crate) q: Uint,
+}
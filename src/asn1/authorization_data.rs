@@ -7,10 +7,10 @@ use der::Sequence;
 ///        ad-data         [1] OCTET STRING
 ///}
 /// ````
-#[derive(Debug, Eq, PartialEq, Sequence)]
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
 pub(crate) struct AuthorizationData {
     #[asn1(context_specific = "0")]
-    ad_type: i32,
+    pub(crate) ad_type: i32,
     #[asn1(context_specific = "1")]
-    ad_data: OctetString,
+    pub(crate) ad_data: OctetString,
 }
@@ -0,0 +1,21 @@
+use super::principal_name::PrincipalName;
+use super::realm::Realm;
+use der::asn1::OctetString;
+use der::Sequence;
+
+/// ```text
+/// ChangePasswdData ::=  SEQUENCE {
+///         newpasswd[0]   OCTET STRING,
+///         targname[1]    PrincipalName OPTIONAL,
+///         targrealm[2]   Realm OPTIONAL
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct ChangePasswdData {
+    #[asn1(context_specific = "0")]
+    pub(crate) new_passwd: OctetString,
+    #[asn1(context_specific = "1", optional = "true")]
+    pub(crate) targ_name: Option<PrincipalName>,
+    #[asn1(context_specific = "2", optional = "true")]
+    pub(crate) targ_realm: Option<Realm>,
+}
@@ -1,7 +1,7 @@
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 #[allow(non_camel_case_types)]
-#[derive(Debug, TryFromPrimitive, IntoPrimitive, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, TryFromPrimitive, IntoPrimitive, PartialEq, Eq)]
 #[repr(i32)]
 pub enum EncryptionType {
     DES_CBC_CRC = 1,
@@ -28,3 +28,21 @@ pub enum EncryptionType {
     CAMELLIA128_CTS_CMAC = 25,
     CAMELLIA256_CTS_CMAC = 26,
 }
+
+impl EncryptionType {
+    /// Whether the etype is deprecated, which RFC 6649 did for single DES and RFC
+    /// 8429 for triple DES and RC4. These are only used when nothing else is on offer.
+    pub fn is_deprecated(self) -> bool {
+        matches!(
+            self,
+            EncryptionType::DES_CBC_CRC
+                | EncryptionType::DES_CBC_MD4
+                | EncryptionType::DES_CBC_MD5
+                | EncryptionType::DES3_CBC_MD5
+                | EncryptionType::DES3_CBC_SHA1
+                | EncryptionType::DES3_CBC_SHA1_KD
+                | EncryptionType::RC4_HMAC
+                | EncryptionType::RC4_HMAC_EXP
+        )
+    }
+}
@@ -0,0 +1,33 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+/// The key usage numbers (RFC 4120 7.5.1), which bind every use of a key to the one
+/// protocol element that it protects.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, TryFromPrimitive, IntoPrimitive, PartialEq, Eq)]
+#[repr(i32)]
+pub enum KeyUsage {
+    AS_REQ_PA_ENC_TIMESTAMP = 1,
+    KDC_REP_TICKET = 2,
+    AS_REP_ENC_PART = 3,
+    TGS_REQ_AUTH_DATA_SESSION = 4,
+    TGS_REQ_AUTH_DATA_SUBKEY = 5,
+    TGS_REQ_PA_TGS_REQ_CKSUM = 6,
+    TGS_REQ_PA_TGS_REQ_AUTHENTICATOR = 7,
+    TGS_REP_ENC_PART_SESSION = 8,
+    TGS_REP_ENC_PART_SUBKEY = 9,
+    AP_REQ_AUTHENTICATOR_CKSUM = 10,
+    AP_REQ_AUTHENTICATOR = 11,
+    AP_REP_ENC_PART = 12,
+    KRB_PRIV = 13,
+    KRB_CRED = 14,
+    KRB_SAFE_CKSUM = 15,
+    KERB_NON_KERB_CKSUM_SALT = 17, // MS-SFU 2.2.1, the checksum of PA-FOR-USER
+    PA_OTP_REQUEST = 45,           // RFC 6560 4.2
+    FAST_REQ_CHKSUM = 50,          // RFC 6113 5.4
+    FAST_ENC = 51,                 // RFC 6113 5.4
+    FAST_REP = 52,                 // RFC 6113 5.4
+    FAST_FINISHED = 53,            // RFC 6113 5.4
+    ENC_CHALLENGE_CLIENT = 54,     // RFC 6113 5.4.6
+    ENC_CHALLENGE_KDC = 55,        // RFC 6113 5.4.6
+    AS_REQ = 56,                   // RFC 6806 11, the checksum of PA-REQ-ENC-PA-REP
+}
@@ -1,5 +1,7 @@
+pub mod checksum_types;
 pub mod encryption_types;
 pub mod errors;
+pub mod key_usages;
 pub mod message_types;
 pub mod pa_data_types;
 
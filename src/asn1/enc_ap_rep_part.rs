@@ -0,0 +1,50 @@
+use super::encryption_key::EncryptionKey;
+use super::kerberos_time::KerberosTime;
+use super::microseconds::Microseconds;
+use der::{Decode, DecodeValue, Encode, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
+
+/// ```text
+/// EncAPRepPart    ::= [APPLICATION 27] SEQUENCE {
+///         ctime           [0] KerberosTime,
+///         cusec           [1] Microseconds,
+///         subkey          [2] EncryptionKey OPTIONAL,
+///         seq-number      [3] UInt32 OPTIONAL
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct EncApRepPart {
+    #[asn1(context_specific = "0")]
+    pub(crate) ctime: KerberosTime,
+    #[asn1(context_specific = "1")]
+    pub(crate) cusec: Microseconds,
+    #[asn1(context_specific = "2", optional = "true")]
+    pub(crate) subkey: Option<EncryptionKey>,
+    #[asn1(context_specific = "3", optional = "true")]
+    pub(crate) seq_number: Option<u32>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct TaggedEncApRepPart(pub(crate) EncApRepPart);
+
+impl FixedTag for TaggedEncApRepPart {
+    const TAG: Tag = Tag::Application {
+        constructed: true,
+        number: TagNumber::N27,
+    };
+}
+
+impl<'a> DecodeValue<'a> for TaggedEncApRepPart {
+    fn decode_value<R: der::Reader<'a>>(reader: &mut R, _header: der::Header) -> der::Result<Self> {
+        let p: EncApRepPart = EncApRepPart::decode(reader)?;
+        Ok(Self(p))
+    }
+}
+
+impl EncodeValue for TaggedEncApRepPart {
+    fn value_len(&self) -> der::Result<der::Length> {
+        self.0.encoded_len()
+    }
+    fn encode_value(&self, encoder: &mut impl der::Writer) -> der::Result<()> {
+        self.0.encode(encoder)
+    }
+}
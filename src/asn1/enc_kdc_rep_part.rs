@@ -0,0 +1,139 @@
+use super::encryption_key::EncryptionKey;
+use super::host_addresses::HostAddresses;
+use super::kerberos_flags::KerberosFlags;
+use super::kerberos_time::KerberosTime;
+use super::last_req::LastReq;
+use super::pa_data::PaData;
+use super::principal_name::PrincipalName;
+use super::realm::Realm;
+use super::ticket_flags::TicketFlags;
+use der::{Sequence, Tag, TagNumber};
+
+/// ```text
+/// EncKDCRepPart   ::= SEQUENCE {
+///         key             [0] EncryptionKey,
+///         last-req        [1] LastReq,
+///         nonce           [2] UInt32,
+///         key-expiration  [3] KerberosTime OPTIONAL,
+///         flags           [4] TicketFlags,
+///         authtime        [5] KerberosTime,
+///         starttime       [6] KerberosTime OPTIONAL,
+///         endtime         [7] KerberosTime,
+///         renew-till      [8] KerberosTime OPTIONAL,
+///         srealm          [9] Realm,
+///         sname           [10] PrincipalName,
+///         caddr           [11] HostAddresses OPTIONAL,
+///         encrypted-pa-data [12] SEQUENCE OF PA-DATA OPTIONAL
+/// }
+/// ````
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
+pub(crate) struct EncKdcRepPart {
+    #[asn1(context_specific = "0")]
+    pub(crate) key: EncryptionKey,
+    #[asn1(context_specific = "1")]
+    pub(crate) last_req: LastReq,
+    #[asn1(context_specific = "2")]
+    pub(crate) nonce: u32,
+    #[asn1(context_specific = "3", optional = "true")]
+    pub(crate) key_expiration: Option<KerberosTime>,
+    #[asn1(context_specific = "4")]
+    pub(crate) flags: KerberosFlags<TicketFlags>,
+    #[asn1(context_specific = "5")]
+    pub(crate) auth_time: KerberosTime,
+    #[asn1(context_specific = "6", optional = "true")]
+    pub(crate) start_time: Option<KerberosTime>,
+    #[asn1(context_specific = "7")]
+    pub(crate) end_time: KerberosTime,
+    #[asn1(context_specific = "8", optional = "true")]
+    pub(crate) renew_till: Option<KerberosTime>,
+    #[asn1(context_specific = "9")]
+    pub(crate) server_realm: Realm,
+    #[asn1(context_specific = "10")]
+    pub(crate) server_name: PrincipalName,
+    #[asn1(context_specific = "11", optional = "true")]
+    pub(crate) client_addresses: Option<HostAddresses>,
+    #[asn1(context_specific = "12", optional = "true")]
+    pub(crate) encrypted_pa_data: Option<Vec<PaData>>,
+}
+
+/// ```text
+/// EncASRepPart    ::= [APPLICATION 25] EncKDCRepPart
+/// EncTGSRepPart   ::= [APPLICATION 26] EncKDCRepPart
+/// ```
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum TaggedEncKdcRepPart {
+    EncAsRepPart(EncKdcRepPart),
+    EncTgsRepPart(EncKdcRepPart),
+}
+
+impl<'a> ::der::Decode<'a> for TaggedEncKdcRepPart {
+    fn decode<R: der::Reader<'a>>(decoder: &mut R) -> der::Result<Self> {
+        let tag: der::Tag = decoder.decode()?;
+        let _len: der::Length = decoder.decode()?;
+
+        match tag {
+            Tag::Application {
+                constructed: true,
+                number: TagNumber::N25,
+            } => {
+                let enc_part: EncKdcRepPart = decoder.decode()?;
+                Ok(TaggedEncKdcRepPart::EncAsRepPart(enc_part))
+            }
+            Tag::Application {
+                constructed: true,
+                number: TagNumber::N26,
+            } => {
+                let enc_part: EncKdcRepPart = decoder.decode()?;
+                Ok(TaggedEncKdcRepPart::EncTgsRepPart(enc_part))
+            }
+            _ => Err(der::Error::from(der::ErrorKind::TagUnexpected {
+                expected: None,
+                actual: tag,
+            })),
+        }
+    }
+}
+
+impl ::der::Encode for TaggedEncKdcRepPart {
+    fn encoded_len(&self) -> der::Result<der::Length> {
+        let (tag, enc_part) = self.tagged();
+        let len = enc_part.encoded_len()?;
+        der::Header::new(tag, len)?.encoded_len()? + len
+    }
+
+    fn encode(&self, encoder: &mut impl der::Writer) -> der::Result<()> {
+        let (tag, enc_part) = self.tagged();
+        der::Header::new(tag, enc_part.encoded_len()?)?.encode(encoder)?;
+        enc_part.encode(encoder)
+    }
+}
+
+impl TaggedEncKdcRepPart {
+    fn tagged(&self) -> (Tag, &EncKdcRepPart) {
+        match self {
+            TaggedEncKdcRepPart::EncAsRepPart(enc_part) => (
+                Tag::Application {
+                    constructed: true,
+                    number: TagNumber::N25,
+                },
+                enc_part,
+            ),
+            TaggedEncKdcRepPart::EncTgsRepPart(enc_part) => (
+                Tag::Application {
+                    constructed: true,
+                    number: TagNumber::N26,
+                },
+                enc_part,
+            ),
+        }
+    }
+
+    /// MIT KDCs are known to tag the enc-part of an AS-REP as an EncTGSRepPart,
+    /// so in most cases the tag has to be ignored.
+    pub(crate) fn into_inner(self) -> EncKdcRepPart {
+        match self {
+            TaggedEncKdcRepPart::EncAsRepPart(enc_part)
+            | TaggedEncKdcRepPart::EncTgsRepPart(enc_part) => enc_part,
+        }
+    }
+}
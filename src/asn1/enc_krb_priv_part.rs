@@ -0,0 +1,57 @@
+use super::host_address::HostAddress;
+use super::kerberos_time::KerberosTime;
+use super::microseconds::Microseconds;
+use der::asn1::OctetString;
+use der::{Decode, DecodeValue, Encode, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
+
+/// ```text
+/// EncKrbPrivPart  ::= [APPLICATION 28] SEQUENCE {
+///         user-data       [0] OCTET STRING,
+///         timestamp       [1] KerberosTime OPTIONAL,
+///         usec            [2] Microseconds OPTIONAL,
+///         seq-number      [3] UInt32 OPTIONAL,
+///         s-address       [4] HostAddress -- sender's addr --,
+///         r-address       [5] HostAddress OPTIONAL -- recip's addr
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct EncKrbPrivPart {
+    #[asn1(context_specific = "0")]
+    pub(crate) user_data: OctetString,
+    #[asn1(context_specific = "1", optional = "true")]
+    pub(crate) timestamp: Option<KerberosTime>,
+    #[asn1(context_specific = "2", optional = "true")]
+    pub(crate) usec: Option<Microseconds>,
+    #[asn1(context_specific = "3", optional = "true")]
+    pub(crate) seq_number: Option<u32>,
+    #[asn1(context_specific = "4")]
+    pub(crate) s_address: HostAddress,
+    #[asn1(context_specific = "5", optional = "true")]
+    pub(crate) r_address: Option<HostAddress>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct TaggedEncKrbPrivPart(pub(crate) EncKrbPrivPart);
+
+impl FixedTag for TaggedEncKrbPrivPart {
+    const TAG: Tag = Tag::Application {
+        constructed: true,
+        number: TagNumber::N28,
+    };
+}
+
+impl<'a> DecodeValue<'a> for TaggedEncKrbPrivPart {
+    fn decode_value<R: der::Reader<'a>>(reader: &mut R, _header: der::Header) -> der::Result<Self> {
+        let p: EncKrbPrivPart = EncKrbPrivPart::decode(reader)?;
+        Ok(Self(p))
+    }
+}
+
+impl EncodeValue for TaggedEncKrbPrivPart {
+    fn value_len(&self) -> der::Result<der::Length> {
+        self.0.encoded_len()
+    }
+    fn encode_value(&self, encoder: &mut impl der::Writer) -> der::Result<()> {
+        self.0.encode(encoder)
+    }
+}
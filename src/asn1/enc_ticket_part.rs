@@ -1,13 +1,13 @@
 use super::authorization_data::AuthorizationData;
 use super::encryption_key::EncryptionKey;
 use super::host_addresses::HostAddresses;
+use super::kerberos_flags::KerberosFlags;
 use super::kerberos_time::KerberosTime;
 use super::principal_name::PrincipalName;
 use super::realm::Realm;
 use super::ticket_flags::TicketFlags;
 use super::transited_encoding::TransitedEncoding;
-use der::flagset::FlagSet;
-use der::Sequence;
+use der::{Decode, DecodeValue, Encode, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
 
 /// ```text
 /// EncTicketPart   ::= [APPLICATION 3] SEQUENCE {
@@ -24,28 +24,55 @@ use der::Sequence;
 ///         authorization-data      [10] AuthorizationData OPTIONAL
 /// }
 /// ````
-#[derive(Debug, Eq, PartialEq, Sequence)]
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
 pub(crate) struct EncTicketPart {
     #[asn1(context_specific = "0")]
-    flags: FlagSet<TicketFlags>,
+    pub(crate) flags: KerberosFlags<TicketFlags>,
     #[asn1(context_specific = "1")]
-    key: EncryptionKey,
+    pub(crate) key: EncryptionKey,
     #[asn1(context_specific = "2")]
-    crealm: Realm,
+    pub(crate) crealm: Realm,
     #[asn1(context_specific = "3")]
-    cname: PrincipalName,
+    pub(crate) cname: PrincipalName,
     #[asn1(context_specific = "4")]
-    transited: TransitedEncoding,
+    pub(crate) transited: TransitedEncoding,
     #[asn1(context_specific = "5")]
-    authtime: KerberosTime,
+    pub(crate) authtime: KerberosTime,
     #[asn1(context_specific = "6", optional = "true")]
-    starttime: Option<KerberosTime>,
+    pub(crate) starttime: Option<KerberosTime>,
     #[asn1(context_specific = "7")]
-    endtime: KerberosTime,
+    pub(crate) endtime: KerberosTime,
     #[asn1(context_specific = "8", optional = "true")]
-    till: Option<KerberosTime>,
+    pub(crate) till: Option<KerberosTime>,
     #[asn1(context_specific = "9", optional = "true")]
-    cadr: Option<HostAddresses>,
+    pub(crate) cadr: Option<HostAddresses>,
     #[asn1(context_specific = "10", optional = "true")]
-    authorization_data: Option<Vec<AuthorizationData>>,
+    pub(crate) authorization_data: Option<Vec<AuthorizationData>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct TaggedEncTicketPart(pub(crate) EncTicketPart);
+
+impl FixedTag for TaggedEncTicketPart {
+    const TAG: Tag = Tag::Application {
+        constructed: true,
+        number: TagNumber::N3,
+    };
+}
+
+impl<'a> DecodeValue<'a> for TaggedEncTicketPart {
+    fn decode_value<R: der::Reader<'a>>(reader: &mut R, _header: der::Header) -> der::Result<Self> {
+        let enc_part: EncTicketPart = EncTicketPart::decode(reader)?;
+        Ok(Self(enc_part))
+    }
+}
+
+impl EncodeValue for TaggedEncTicketPart {
+    // The application tag wraps the whole SEQUENCE, not just its contents.
+    fn value_len(&self) -> der::Result<der::Length> {
+        self.0.encoded_len()
+    }
+    fn encode_value(&self, encoder: &mut impl der::Writer) -> der::Result<()> {
+        self.0.encode(encoder)
+    }
 }
@@ -8,7 +8,7 @@ use der::Sequence;
 ///         cipher  [2] OCTET STRING -- ciphertext
 /// }
 /// ````
-#[derive(Debug, Eq, PartialEq, Sequence)]
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
 pub(crate) struct EncryptedData {
     #[asn1(context_specific = "0")]
     pub(crate) etype: i32,
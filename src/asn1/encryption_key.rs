@@ -7,10 +7,10 @@ use der::Sequence;
 ///         keyvalue        [1] OCTET STRING
 /// }
 /// ````
-#[derive(Debug, Eq, PartialEq, Sequence)]
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
 pub(crate) struct EncryptionKey {
     #[asn1(context_specific = "0")]
-    key_type: i32,
-    #[asn1(context_specific = "0")]
-    key_value: OctetString,
+    pub(crate) key_type: i32,
+    #[asn1(context_specific = "1")]
+    pub(crate) key_value: OctetString,
 }
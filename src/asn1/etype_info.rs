@@ -0,0 +1,40 @@
+use der::asn1::OctetString;
+use der::Sequence;
+
+/// ```text
+/// ETYPE-INFO-ENTRY        ::= SEQUENCE {
+///         etype           [0] Int32,
+///         salt            [1] OCTET STRING OPTIONAL
+/// }
+/// ```
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct ETypeInfoEntry {
+    #[asn1(context_specific = "0")]
+    pub(crate) etype: i32,
+    #[asn1(context_specific = "1", optional = "true")]
+    pub(crate) salt: Option<OctetString>,
+}
+
+/// ```text
+/// ETYPE-INFO              ::= SEQUENCE OF ETYPE-INFO-ENTRY
+/// ```
+pub(crate) type ETypeInfo = Vec<ETypeInfoEntry>;
+
+#[cfg(test)]
+mod tests {
+    use crate::asn1::constants::EncryptionType;
+    use crate::asn1::etype_info::ETypeInfoEntry;
+    use der::Decode;
+
+    #[test]
+    fn etype_info_entry_parse() {
+        let blob = "301ca003020112a11504134558414d504c452e434f4d7465737475736572";
+        let blob = hex::decode(blob).expect("Failed to decode sample");
+        let info = ETypeInfoEntry::from_der(&blob).expect("Failed to decode");
+        assert_eq!(info.etype, EncryptionType::AES256_CTS_HMAC_SHA1_96 as i32);
+        assert_eq!(
+            info.salt.expect("Missing salt").as_bytes(),
+            b"EXAMPLE.COMtestuser"
+        );
+    }
+}
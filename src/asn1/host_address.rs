@@ -7,7 +7,7 @@ use der::Sequence;
 ///         address         [1] OCTET STRING
 /// }
 /// ````
-#[derive(Debug, Eq, PartialEq, Sequence)]
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
 pub(crate) struct HostAddress {
     #[asn1(context_specific = "0")]
     pub(crate) addr_type: i32,
@@ -1,7 +1,55 @@
-use super::kerberos_flags::KerberosFlags;
-use der::flagset::FlagSet;
+use der::flagset::flags;
 
-/// ```text
-/// KDCOptions      ::= KerberosFlags
-/// ````
-pub(crate) type KdcOptions = FlagSet<KerberosFlags>;
+flags! {
+    /// The options a client may request of the KDC, as defined by RFC 4120 5.4.1 and
+    /// RFC 6806.
+    ///
+    /// ```text
+    /// KDCOptions      ::= KerberosFlags
+    ///         -- reserved(0),
+    ///         -- forwardable(1),
+    ///         -- forwarded(2),
+    ///         -- proxiable(3),
+    ///         -- proxy(4),
+    ///         -- allow-postdate(5),
+    ///         -- postdated(6),
+    ///         -- unused7(7),
+    ///         -- renewable(8),
+    ///         -- unused9(9),
+    ///         -- unused10(10),
+    ///         -- opt-hardware-auth(11),
+    ///         -- unused12(12),
+    ///         -- unused13(13),
+    ///         -- cname-in-addl-tkt(14), MS-SFU constrained delegation
+    ///         -- 15 is reserved for canonicalize
+    ///         -- unused15(15),
+    ///         -- request-anonymous(16), RFC 8062
+    ///         -- 26 was unused in 1510
+    ///         -- disable-transited-check(26),
+    ///         --
+    ///         -- renewable-ok(27),
+    ///         -- enc-tkt-in-skey(28),
+    ///         -- renew(30),
+    ///         -- validate(31)
+    /// ````
+    #[repr(u32)]
+    pub enum KdcOptions: u32 {
+        Reserved              = 1 << 0,
+        Forwardable           = 1 << 1,
+        Forwarded             = 1 << 2,
+        Proxiable             = 1 << 3,
+        Proxy                 = 1 << 4,
+        AllowPostdate         = 1 << 5,
+        Postdated             = 1 << 6,
+        Renewable             = 1 << 8,
+        OptHardwareAuth       = 1 << 11,
+        ConstrainedDelegation = 1 << 14,
+        Canonicalize          = 1 << 15,
+        RequestAnonymous      = 1 << 16,
+        DisableTransitedCheck = 1 << 26,
+        RenewableOk           = 1 << 27,
+        EncTktInSkey          = 1 << 28,
+        Renew                 = 1 << 30,
+        Validate              = 1 << 31,
+    }
+}
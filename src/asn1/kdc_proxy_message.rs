@@ -0,0 +1,20 @@
+use super::realm::Realm;
+use der::asn1::OctetString;
+use der::Sequence;
+
+/// ```text
+/// KDC-PROXY-MESSAGE ::= SEQUENCE {
+///         kerb-message    [0] OCTET STRING,
+///         target-domain   [1] KERB-REALM OPTIONAL,
+///         dclocator-hint  [2] INTEGER OPTIONAL
+/// }
+/// ````
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
+pub(crate) struct KdcProxyMessage {
+    #[asn1(context_specific = "0")]
+    pub(crate) kerb_message: OctetString,
+    #[asn1(context_specific = "1", optional = "true")]
+    pub(crate) target_domain: Option<Realm>,
+    #[asn1(context_specific = "2", optional = "true")]
+    pub(crate) dclocator_hint: Option<u32>,
+}
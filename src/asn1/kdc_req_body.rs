@@ -1,6 +1,7 @@
 use super::encrypted_data::EncryptedData;
 use super::host_addresses::HostAddresses;
 use super::kdc_options::KdcOptions;
+use super::kerberos_flags::KerberosFlags;
 use super::kerberos_time::KerberosTime;
 use super::principal_name::PrincipalName;
 use super::realm::Realm;
@@ -32,7 +33,7 @@ use der::Sequence;
 #[derive(Debug, Eq, PartialEq, Sequence)]
 pub(crate) struct KdcReqBody {
     #[asn1(context_specific = "0")]
-    pub(crate) kdc_options: KdcOptions,
+    pub(crate) kdc_options: KerberosFlags<KdcOptions>,
     #[asn1(context_specific = "1", optional = "true")]
     pub(crate) cname: Option<PrincipalName>,
     #[asn1(context_specific = "2")]
@@ -1,45 +1,115 @@
-use der::flagset::flags;
-
-flags! {
-    /// ```text
-    /// KerberosFlags   ::= BIT STRING (SIZE (32..MAX))
-    ///                     -- minimum number of bits shall be sent,
-    ///                     -- but no fewer than 32
-    /// ````
-    #[repr(u32)]
-    pub(crate) enum KerberosFlags: u32 {
-        Reserved        = 1 << 0,
-        Forwardable     = 1 << 1,
-        Forwarded       = 1 << 2,
-        Proxiable       = 1 << 3,
-        Proxy           = 1 << 4,
-        AllowPostdate   = 1 << 5,
-        Postdated       = 1 << 6,
-        Unused7         = 1 << 7,
-        Renewable       = 1 << 8,
-        Unused9         = 1 << 9,
-        Unused10        = 1 << 10,
-        OptHardwareAuth = 1 << 11,
-        Unused12        = 1 << 12,
-        Unused13        = 1 << 13,
-        Unused14        = 1 << 14,
-        Canonicalize    = 1 << 15,
-        Unused16        = 1 << 16,
-        Unused17        = 1 << 17,
-        Unused18        = 1 << 18,
-        Unused19        = 1 << 19,
-        Unused20        = 1 << 20,
-        Unused21        = 1 << 21,
-        Unused22        = 1 << 22,
-        Unused23        = 1 << 23,
-        Unused24        = 1 << 24,
-        Unused25        = 1 << 25,
-        // -- 26 was unused in 1510
-        DisableTransitedCheck = 1 << 26,
-        RenewableOk     = 1 << 27,
-        EncTktInSkey    = 1 << 28,
-        Unused29        = 1 << 29,
-        Renew           = 1 << 30,
-        Validate        = 1 << 31
+use der::asn1::BitStringRef;
+use der::flagset::{FlagSet, Flags};
+use der::{DecodeValue, EncodeValue, FixedTag, Header, Length, Reader, Tag, Writer};
+
+/// ```text
+/// KerberosFlags   ::= BIT STRING (SIZE (32..MAX))
+///                     -- minimum number of bits shall be sent,
+///                     -- but no fewer than 32
+/// ````
+///
+/// Bit 0 is the most significant bit of the first octet. Unlike the DER encoding of a
+/// plain FlagSet, trailing zero bits are not trimmed since at least 32 bits must be sent.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct KerberosFlags<T: Flags<Type = u32>>(pub(crate) FlagSet<T>);
+
+impl<T: Flags<Type = u32>> From<FlagSet<T>> for KerberosFlags<T> {
+    fn from(flags: FlagSet<T>) -> Self {
+        Self(flags)
+    }
+}
+
+impl<T: Flags<Type = u32>> FixedTag for KerberosFlags<T> {
+    const TAG: Tag = Tag::BitString;
+}
+
+impl<'a, T: Flags<Type = u32>> DecodeValue<'a> for KerberosFlags<T> {
+    fn decode_value<R: Reader<'a>>(reader: &mut R, header: Header) -> der::Result<Self> {
+        let bit_string = BitStringRef::decode_value(reader, header)?;
+
+        // Senders may trim trailing zero bits, and only the first 32 bits have any
+        // meaning, so any further bits are ignored.
+        let mut bytes = [0u8; 4];
+        for (byte, raw) in bytes.iter_mut().zip(bit_string.raw_bytes()) {
+            *byte = *raw;
+        }
+
+        let bits = u32::from_be_bytes(bytes).reverse_bits();
+        Ok(Self(FlagSet::new_truncated(bits)))
+    }
+}
+
+impl<T: Flags<Type = u32>> EncodeValue for KerberosFlags<T> {
+    fn value_len(&self) -> der::Result<Length> {
+        // The leading octet is the count of unused bits, which is always zero.
+        Ok(Length::new(5))
+    }
+
+    fn encode_value(&self, writer: &mut impl Writer) -> der::Result<()> {
+        writer.write_byte(0)?;
+        writer.write(&self.0.bits().reverse_bits().to_be_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KerberosFlags;
+    use crate::asn1::kdc_options::KdcOptions;
+    use der::flagset::FlagSet;
+    use der::{Decode, Encode};
+
+    #[test]
+    fn kerberos_flags_bit_order() {
+        for flag in FlagSet::<KdcOptions>::full() {
+            let flags = KerberosFlags(FlagSet::from(flag));
+            let encoded = flags.to_der().expect("Failed to encode");
+
+            // Flag n is bit n of the BIT STRING, counting from the most significant
+            // bit of the first octet.
+            let bit = (flag as u32).trailing_zeros() as usize;
+            let mut expected = vec![0x03, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00];
+            expected[3 + bit / 8] = 0x80 >> (bit % 8);
+            assert_eq!(encoded, expected, "{:?}", flag);
+
+            let decoded =
+                KerberosFlags::<KdcOptions>::from_der(&encoded).expect("Failed to decode");
+            assert_eq!(decoded, flags);
+        }
+    }
+
+    #[test]
+    fn kerberos_flags_rfc4120_positions() {
+        let flags = KerberosFlags(
+            KdcOptions::Forwardable
+                | KdcOptions::Renewable
+                | KdcOptions::Canonicalize
+                | KdcOptions::RenewableOk,
+        );
+        assert_eq!(
+            flags.to_der().expect("Failed to encode"),
+            [0x03, 0x05, 0x00, 0x40, 0x81, 0x00, 0x10]
+        );
+
+        // The empty set is still sent as 32 bits.
+        let flags = KerberosFlags(FlagSet::<KdcOptions>::default());
+        assert_eq!(
+            flags.to_der().expect("Failed to encode"),
+            [0x03, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn kerberos_flags_decode_trimmed() {
+        // forwardable and proxiable with the trailing zero bits trimmed.
+        let decoded = KerberosFlags::<KdcOptions>::from_der(&[0x03, 0x02, 0x04, 0x50])
+            .expect("Failed to decode");
+        assert_eq!(decoded.0, KdcOptions::Forwardable | KdcOptions::Proxiable);
+
+        // Bits beyond the first 32 are ignored.
+        let decoded = KerberosFlags::<KdcOptions>::from_der(&[
+            0x03, 0x06, 0x00, 0x00, 0x80, 0x00, 0x00, 0xff,
+        ])
+        .expect("Failed to decode");
+        assert_eq!(decoded.0, KdcOptions::Renewable);
     }
 }
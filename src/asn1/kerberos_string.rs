@@ -7,7 +7,7 @@ use der::Tag;
 /// ```text
 /// KerberosString  ::= GeneralString (IA5String)
 /// ````
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub(crate) struct KerberosString(pub(crate) Ia5String);
 
 impl FixedTag for KerberosString {
@@ -5,7 +5,7 @@ use super::pa_data::PaData;
 use super::principal_name::PrincipalName;
 use super::realm::Realm;
 use der::asn1::OctetString;
-use der::{Decode, DecodeValue, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
+use der::{Decode, DecodeValue, Encode, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
 
 /// ```text
 /// KRB-ERROR       ::= [APPLICATION 30] SEQUENCE {
@@ -55,7 +55,7 @@ pub(crate) struct KrbError {
 }
 
 #[derive(Debug, Eq, PartialEq)]
-struct TaggedKrbError(KrbError);
+pub(crate) struct TaggedKrbError(pub(crate) KrbError);
 
 impl FixedTag for TaggedKrbError {
     const TAG: Tag = Tag::Application {
@@ -72,11 +72,12 @@ impl<'a> DecodeValue<'a> for TaggedKrbError {
 }
 
 impl<'a> EncodeValue for TaggedKrbError {
+    // The application tag wraps the whole SEQUENCE, not just its contents.
     fn value_len(&self) -> der::Result<der::Length> {
-        KrbError::value_len(&self.0)
+        self.0.encoded_len()
     }
     fn encode_value(&self, encoder: &mut impl der::Writer) -> der::Result<()> {
-        KrbError::encode_value(&self.0, encoder)
+        self.0.encode(encoder)
     }
 }
 
@@ -0,0 +1,20 @@
+use der::asn1::OctetString;
+use der::Sequence;
+
+/// RFC 6113 5.4.1
+/// ```text
+/// KrbFastArmor ::= SEQUENCE {
+///         armor-type   [0] Int32,
+///                 -- Type of the armor.
+///         armor-value  [1] OCTET STRING,
+///                 -- Value of the armor.
+///         ...
+/// }
+/// ````
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
+pub(crate) struct KrbFastArmor {
+    #[asn1(context_specific = "0")]
+    pub(crate) armor_type: i32,
+    #[asn1(context_specific = "1")]
+    pub(crate) armor_value: OctetString,
+}
@@ -0,0 +1,100 @@
+use super::checksum::Checksum;
+use super::encrypted_data::EncryptedData;
+use super::encryption_key::EncryptionKey;
+use super::kerberos_time::KerberosTime;
+use super::microseconds::Microseconds;
+use super::pa_data::PaData;
+use super::principal_name::PrincipalName;
+use super::realm::Realm;
+use der::{Decode, DecodeValue, Encode, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
+
+/// RFC 6113 5.4.3
+/// ```text
+/// KrbFastArmoredRep ::= SEQUENCE {
+///         enc-fast-rep      [0] EncryptedData, -- KrbFastResponse --
+///         ...
+/// }
+/// ````
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
+pub(crate) struct KrbFastArmoredRep {
+    #[asn1(context_specific = "0")]
+    pub(crate) enc_fast_rep: EncryptedData,
+}
+
+/// ```text
+/// PA-FX-FAST-REPLY ::= CHOICE {
+///         armored-data [0] KrbFastArmoredRep,
+///         ...
+/// }
+/// ````
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct PaFxFastReply(pub(crate) KrbFastArmoredRep);
+
+impl FixedTag for PaFxFastReply {
+    const TAG: Tag = Tag::ContextSpecific {
+        constructed: true,
+        number: TagNumber::N0,
+    };
+}
+
+impl<'a> DecodeValue<'a> for PaFxFastReply {
+    fn decode_value<R: der::Reader<'a>>(reader: &mut R, _header: der::Header) -> der::Result<Self> {
+        let r: KrbFastArmoredRep = KrbFastArmoredRep::decode(reader)?;
+        Ok(Self(r))
+    }
+}
+
+impl EncodeValue for PaFxFastReply {
+    // The choice is explicitly tagged, so the tag wraps the whole SEQUENCE.
+    fn value_len(&self) -> der::Result<der::Length> {
+        self.0.encoded_len()
+    }
+    fn encode_value(&self, encoder: &mut impl der::Writer) -> der::Result<()> {
+        self.0.encode(encoder)
+    }
+}
+
+/// ```text
+/// KrbFastResponse ::= SEQUENCE {
+///         padata         [0] SEQUENCE OF PA-DATA,
+///         strengthen-key [1] EncryptionKey OPTIONAL,
+///         finished       [2] KrbFastFinished OPTIONAL,
+///         nonce          [3] UInt32,
+///         ...
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct KrbFastResponse {
+    #[asn1(context_specific = "0")]
+    pub(crate) padata: Vec<PaData>,
+    #[asn1(context_specific = "1", optional = "true")]
+    pub(crate) strengthen_key: Option<EncryptionKey>,
+    #[asn1(context_specific = "2", optional = "true")]
+    pub(crate) finished: Option<KrbFastFinished>,
+    #[asn1(context_specific = "3")]
+    pub(crate) nonce: u32,
+}
+
+/// ```text
+/// KrbFastFinished ::= SEQUENCE {
+///         timestamp       [0] KerberosTime,
+///         usec            [1] Microseconds,
+///         crealm          [2] Realm,
+///         cname           [3] PrincipalName,
+///         ticket-checksum [4] Checksum,
+///         ...
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct KrbFastFinished {
+    #[asn1(context_specific = "0")]
+    pub(crate) timestamp: KerberosTime,
+    #[asn1(context_specific = "1")]
+    pub(crate) usec: Microseconds,
+    #[asn1(context_specific = "2")]
+    pub(crate) crealm: Realm,
+    #[asn1(context_specific = "3")]
+    pub(crate) cname: PrincipalName,
+    #[asn1(context_specific = "4")]
+    pub(crate) ticket_checksum: Checksum,
+}
@@ -0,0 +1,94 @@
+use super::checksum::Checksum;
+use super::encrypted_data::EncryptedData;
+use super::kdc_req_body::KdcReqBody;
+use super::kerberos_flags::KerberosFlags;
+use super::krb_fast_armor::KrbFastArmor;
+use super::pa_data::PaData;
+use der::flagset::flags;
+use der::{Decode, DecodeValue, Encode, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
+
+flags! {
+    /// The options of a FAST request, as defined by RFC 6113 5.4.1.
+    ///
+    /// ```text
+    /// FastOptions ::= KerberosFlags
+    ///         -- reserved(0),
+    ///         -- hide-client-names(1),
+    ///         -- kdc-follow-referrals(16)
+    /// ````
+    #[repr(u32)]
+    pub enum FastOptions: u32 {
+        Reserved           = 1 << 0,
+        HideClientNames    = 1 << 1,
+        KdcFollowReferrals = 1 << 16,
+    }
+}
+
+/// ```text
+/// KrbFastArmoredReq ::= SEQUENCE {
+///         armor        [0] KrbFastArmor OPTIONAL,
+///         req-checksum [1] Checksum,
+///         enc-fast-req [2] EncryptedData, -- KrbFastReq --
+///         ...
+/// }
+/// ````
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
+pub(crate) struct KrbFastArmoredReq {
+    #[asn1(context_specific = "0", optional = "true")]
+    pub(crate) armor: Option<KrbFastArmor>,
+    #[asn1(context_specific = "1")]
+    pub(crate) req_checksum: Checksum,
+    #[asn1(context_specific = "2")]
+    pub(crate) enc_fast_req: EncryptedData,
+}
+
+/// ```text
+/// PA-FX-FAST-REQUEST ::= CHOICE {
+///         armored-data [0] KrbFastArmoredReq,
+///         ...
+/// }
+/// ````
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct PaFxFastRequest(pub(crate) KrbFastArmoredReq);
+
+impl FixedTag for PaFxFastRequest {
+    const TAG: Tag = Tag::ContextSpecific {
+        constructed: true,
+        number: TagNumber::N0,
+    };
+}
+
+impl<'a> DecodeValue<'a> for PaFxFastRequest {
+    fn decode_value<R: der::Reader<'a>>(reader: &mut R, _header: der::Header) -> der::Result<Self> {
+        let r: KrbFastArmoredReq = KrbFastArmoredReq::decode(reader)?;
+        Ok(Self(r))
+    }
+}
+
+impl EncodeValue for PaFxFastRequest {
+    // The choice is explicitly tagged, so the tag wraps the whole SEQUENCE.
+    fn value_len(&self) -> der::Result<der::Length> {
+        self.0.encoded_len()
+    }
+    fn encode_value(&self, encoder: &mut impl der::Writer) -> der::Result<()> {
+        self.0.encode(encoder)
+    }
+}
+
+/// ```text
+/// KrbFastReq ::= SEQUENCE {
+///         fast-options [0] FastOptions,
+///         padata       [1] SEQUENCE OF PA-DATA,
+///         req-body     [2] KDC-REQ-BODY,
+///         ...
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct KrbFastReq {
+    #[asn1(context_specific = "0")]
+    pub(crate) fast_options: KerberosFlags<FastOptions>,
+    #[asn1(context_specific = "1")]
+    pub(crate) padata: Vec<PaData>,
+    #[asn1(context_specific = "2")]
+    pub(crate) req_body: KdcReqBody,
+}
@@ -91,8 +91,8 @@ impl ::der::Encode for KrbKdcReq {
 #[cfg(test)]
 mod tests {
     use crate::asn1::constants::{EncryptionType, KrbMessageType, PaDataType};
+    use crate::asn1::kdc_options::KdcOptions;
     use crate::asn1::kdc_req::KdcReq;
-    use crate::asn1::kerberos_flags::KerberosFlags;
     use crate::asn1::kerberos_time::KerberosTime;
     use crate::asn1::krb_kdc_req::KrbKdcReq;
     use core::iter::zip;
@@ -111,7 +111,7 @@ mod tests {
         principal: String,
         realm: String,
         padata: Vec<TestPaData>,
-        kdc_options: FlagSet<KerberosFlags>,
+        kdc_options: FlagSet<KdcOptions>,
         from: Option<KerberosTime>,
         till: KerberosTime,
         rtime: Option<KerberosTime>,
@@ -132,7 +132,7 @@ mod tests {
             assert_eq!(pa.padata_value.as_bytes(), tpa.padata_value);
         }
 
-        let bits = asreq.req_body.kdc_options;
+        let bits = asreq.req_body.kdc_options.0;
         assert_eq!(bits, tasreq.kdc_options);
 
         let ref cname = &asreq.req_body.cname.as_ref().unwrap();
@@ -200,7 +200,7 @@ mod tests {
                         padata_value: vec![],
                     }
                 ],
-                kdc_options: KerberosFlags::RenewableOk.into(),
+                kdc_options: KdcOptions::RenewableOk.into(),
                 from: None,
                 till: KerberosTime::from_date_time(DateTime::new(2024, 04, 17, 04, 15, 49).expect("Failed to build DateTime")),
                 rtime: None,
@@ -235,7 +235,7 @@ mod tests {
                         padata_value: vec![0x30, 0x05, 0xa0, 0x03, 0x01, 0x01, 0xff],
                     }
                 ],
-                kdc_options: KerberosFlags::RenewableOk | KerberosFlags::Forwardable | KerberosFlags::Canonicalize | KerberosFlags::Proxiable,
+                kdc_options: KdcOptions::RenewableOk | KdcOptions::Forwardable | KdcOptions::Canonicalize | KdcOptions::Proxiable,
                 from: None,
                 till: KerberosTime::from_date_time(DateTime::new(2024, 06, 12, 14, 51, 09).expect("Failed to build DateTime")),
                 rtime: None,
@@ -269,7 +269,7 @@ mod tests {
                         padata_value: vec![],
                     }
                 ],
-                kdc_options: KerberosFlags::Renewable.into(),
+                kdc_options: KdcOptions::Renewable.into(),
                 from: None,
                 till: KerberosTime::from_date_time(DateTime::new(2024, 06, 16, 05, 27, 01).expect("Failed to build DateTime")),
                 rtime: Some(KerberosTime::from_date_time(DateTime::new(2024, 06, 22, 05, 27, 01).expect("Failed to build DateTime"))),
@@ -0,0 +1,46 @@
+use super::encrypted_data::EncryptedData;
+use der::{Decode, DecodeValue, Encode, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
+
+/// ```text
+/// KRB-PRIV        ::= [APPLICATION 21] SEQUENCE {
+///         pvno            [0] INTEGER (5),
+///         msg-type        [1] INTEGER (21),
+///                         -- NOTE: there is no [2] tag
+///         enc-part        [3] EncryptedData -- EncKrbPrivPart
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct KrbPriv {
+    #[asn1(context_specific = "0")]
+    pub(crate) pvno: u8,
+    #[asn1(context_specific = "1")]
+    pub(crate) msg_type: u8,
+    #[asn1(context_specific = "3")]
+    pub(crate) enc_part: EncryptedData,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct TaggedKrbPriv(pub(crate) KrbPriv);
+
+impl FixedTag for TaggedKrbPriv {
+    const TAG: Tag = Tag::Application {
+        constructed: true,
+        number: TagNumber::N21,
+    };
+}
+
+impl<'a> DecodeValue<'a> for TaggedKrbPriv {
+    fn decode_value<R: der::Reader<'a>>(reader: &mut R, _header: der::Header) -> der::Result<Self> {
+        let p: KrbPriv = KrbPriv::decode(reader)?;
+        Ok(Self(p))
+    }
+}
+
+impl EncodeValue for TaggedKrbPriv {
+    fn value_len(&self) -> der::Result<der::Length> {
+        self.0.encoded_len()
+    }
+    fn encode_value(&self, encoder: &mut impl der::Writer) -> der::Result<()> {
+        self.0.encode(encoder)
+    }
+}
@@ -1,12 +1,24 @@
+pub mod ap_options;
+pub mod ap_rep;
+pub mod ap_req;
+pub mod auth_pack;
+pub mod authenticator;
 pub mod authorization_data;
+pub mod change_passwd_data;
+pub mod checksum;
 pub mod constants;
+pub mod enc_ap_rep_part;
+pub mod enc_kdc_rep_part;
+pub mod enc_krb_priv_part;
 pub mod enc_ticket_part;
 pub mod encrypted_data;
 pub mod encryption_key;
+pub mod etype_info;
 pub mod etype_info2;
 pub mod host_address;
 pub mod host_addresses;
 pub mod kdc_options;
+pub mod kdc_proxy_message;
 pub mod kdc_rep;
 pub mod kdc_req;
 pub mod kdc_req_body;
@@ -14,11 +26,22 @@ pub mod kerberos_flags;
 pub mod kerberos_string;
 pub mod kerberos_time;
 pub mod krb_error;
+pub mod krb_fast_armor;
+pub mod krb_fast_rep;
+pub mod krb_fast_req;
 pub mod krb_kdc_rep;
 pub mod krb_kdc_req;
+pub mod krb_priv;
+pub mod last_req;
 pub mod microseconds;
 pub mod pa_data;
 pub mod pa_enc_ts_enc;
+pub mod pa_for_user;
+pub mod pa_otp_challenge;
+pub mod pa_otp_request;
+pub mod pa_pac_options;
+pub mod pa_pk_as_rep;
+pub mod pa_pk_as_req;
 pub mod principal_name;
 pub mod realm;
 pub mod tagged_ticket;
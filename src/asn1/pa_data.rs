@@ -8,7 +8,7 @@ use der::Sequence;
 ///         padata-value    [2] OCTET STRING -- might be encoded AP-REQ
 /// }
 /// ````
-#[derive(Debug, Eq, PartialEq, Sequence)]
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
 pub(crate) struct PaData {
     #[asn1(context_specific = "1")]
     pub(crate) padata_type: u32,
@@ -0,0 +1,121 @@
+use super::auth_pack::AlgorithmIdentifier;
+use super::encrypted_data::EncryptedData;
+use super::kerberos_flags::KerberosFlags;
+use super::kerberos_time::KerberosTime;
+use super::pa_otp_challenge::OtpFlags;
+use der::asn1::OctetString;
+use der::Sequence;
+
+/// RFC 6560 4.2
+/// ```text
+/// PA-OTP-REQUEST ::= SEQUENCE {
+///         flags          [0]  OTPFlags,
+///         nonce          [1]  OCTET STRING                OPTIONAL,
+///         encData        [2]  EncryptedData,
+///                            -- PA-OTP-ENC-REQUEST or PA-ENC-TS-ENC
+///                            -- Key usage of KEY_USAGE_OTP_REQUEST
+///         hashAlg        [3]  AlgorithmIdentifier         OPTIONAL,
+///         iterationCount [4]  Int32                       OPTIONAL,
+///         otp-value      [5]  OCTET STRING                OPTIONAL,
+///         otp-pin        [6]  UTF8String                  OPTIONAL,
+///         otp-challenge  [7]  OCTET STRING (SIZE(1..MAX)) OPTIONAL,
+///         otp-time       [8]  KerberosTime                OPTIONAL,
+///         otp-counter    [9]  OCTET STRING                OPTIONAL,
+///         otp-format     [10] OTPFormat                   OPTIONAL,
+///         otp-tokenID    [11] OCTET STRING                OPTIONAL,
+///         otp-algID      [12] AnyURI                      OPTIONAL,
+///         otp-vendor     [13] UTF8String                  OPTIONAL,
+///         ...
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct PaOtpRequest {
+    #[asn1(context_specific = "0")]
+    pub(crate) flags: KerberosFlags<OtpFlags>,
+    #[asn1(context_specific = "1", optional = "true")]
+    pub(crate) nonce: Option<OctetString>,
+    #[asn1(context_specific = "2")]
+    pub(crate) enc_data: EncryptedData,
+    #[asn1(context_specific = "3", optional = "true")]
+    pub(crate) hash_alg: Option<AlgorithmIdentifier>,
+    #[asn1(context_specific = "4", optional = "true")]
+    pub(crate) iteration_count: Option<i32>,
+    #[asn1(context_specific = "5", optional = "true")]
+    pub(crate) otp_value: Option<OctetString>,
+    #[asn1(context_specific = "6", optional = "true")]
+    pub(crate) otp_pin: Option<String>,
+    #[asn1(context_specific = "7", optional = "true")]
+    pub(crate) otp_challenge: Option<OctetString>,
+    #[asn1(context_specific = "8", optional = "true")]
+    pub(crate) otp_time: Option<KerberosTime>,
+    #[asn1(context_specific = "9", optional = "true")]
+    pub(crate) otp_counter: Option<OctetString>,
+    #[asn1(context_specific = "10", optional = "true")]
+    pub(crate) otp_format: Option<i32>,
+    #[asn1(context_specific = "11", optional = "true")]
+    pub(crate) otp_token_id: Option<OctetString>,
+    #[asn1(context_specific = "12", optional = "true")]
+    pub(crate) otp_alg_id: Option<String>,
+    #[asn1(context_specific = "13", optional = "true")]
+    pub(crate) otp_vendor: Option<String>,
+}
+
+/// ```text
+/// PA-OTP-ENC-REQUEST ::= SEQUENCE {
+///         nonce     [0] OCTET STRING,
+///         ...
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct PaOtpEncRequest {
+    #[asn1(context_specific = "0")]
+    pub(crate) nonce: OctetString,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PaOtpEncRequest, PaOtpRequest};
+    use crate::asn1::encrypted_data::EncryptedData;
+    use crate::asn1::kerberos_flags::KerberosFlags;
+    use der::asn1::OctetString;
+    use der::flagset::FlagSet;
+    use der::{Decode, Encode};
+
+    #[test]
+    fn pa_otp_request_round_trip() {
+        let enc_request = PaOtpEncRequest {
+            nonce: OctetString::new(vec![0x01; 16]).expect("Invalid nonce"),
+        };
+        let der_bytes = enc_request.to_der().expect("Failed to encode");
+        assert_eq!(
+            PaOtpEncRequest::from_der(&der_bytes).expect("Failed to decode"),
+            enc_request
+        );
+
+        let request = PaOtpRequest {
+            flags: KerberosFlags(FlagSet::default()),
+            nonce: None,
+            enc_data: EncryptedData {
+                etype: 18,
+                kvno: None,
+                cipher: OctetString::new(vec![0xab; 48]).expect("Invalid cipher"),
+            },
+            hash_alg: None,
+            iteration_count: None,
+            otp_value: Some(OctetString::new(b"123456".as_slice()).expect("Invalid value")),
+            otp_pin: Some("1234".to_string()),
+            otp_challenge: None,
+            otp_time: None,
+            otp_counter: None,
+            otp_format: None,
+            otp_token_id: Some(OctetString::new(b"a1b2c3d4".as_slice()).expect("Invalid id")),
+            otp_alg_id: None,
+            otp_vendor: Some("FreeIPA".to_string()),
+        };
+        let der_bytes = request.to_der().expect("Failed to encode");
+        assert_eq!(
+            PaOtpRequest::from_der(&der_bytes).expect("Failed to decode"),
+            request
+        );
+    }
+}
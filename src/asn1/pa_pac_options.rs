@@ -0,0 +1,90 @@
+use super::kerberos_flags::KerberosFlags;
+use der::flagset::flags;
+use der::Sequence;
+
+flags! {
+    /// The options a client may request of the PAC, as defined by MS-KILE 2.2.10.
+    ///
+    /// ```text
+    /// PA-PAC-OPTIONS ::= SEQUENCE {
+    ///         KerberosFlags
+    ///         -- Claims (0)
+    ///         -- Branch Aware (1)
+    ///         -- Forward to Full DC (2)
+    ///         -- Resource-based Constrained Delegation (3)
+    /// }
+    /// ````
+    #[repr(u32)]
+    pub enum PacOptions: u32 {
+        Claims                             = 1 << 0,
+        BranchAware                        = 1 << 1,
+        ForwardToFullDc                    = 1 << 2,
+        ResourceBasedConstrainedDelegation = 1 << 3,
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
+pub(crate) struct PaPacOptions {
+    #[asn1(context_specific = "0")]
+    pub(crate) flags: KerberosFlags<PacOptions>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PaPacOptions, PacOptions};
+    use crate::asn1::kerberos_flags::KerberosFlags;
+    use der::flagset::FlagSet;
+    use der::{Decode, Encode};
+
+    #[test]
+    fn pa_pac_options_encoding() {
+        // Windows expects the options as a full 32 bit KerberosFlags.
+        let cases: [(FlagSet<PacOptions>, [u8; 11]); 4] = [
+            (
+                PacOptions::Claims.into(),
+                [
+                    0x30, 0x09, 0xa0, 0x07, 0x03, 0x05, 0x00, 0x80, 0x00, 0x00, 0x00,
+                ],
+            ),
+            (
+                PacOptions::ResourceBasedConstrainedDelegation.into(),
+                [
+                    0x30, 0x09, 0xa0, 0x07, 0x03, 0x05, 0x00, 0x10, 0x00, 0x00, 0x00,
+                ],
+            ),
+            (
+                PacOptions::Claims | PacOptions::ResourceBasedConstrainedDelegation,
+                [
+                    0x30, 0x09, 0xa0, 0x07, 0x03, 0x05, 0x00, 0x90, 0x00, 0x00, 0x00,
+                ],
+            ),
+            (
+                FlagSet::full(),
+                [
+                    0x30, 0x09, 0xa0, 0x07, 0x03, 0x05, 0x00, 0xf0, 0x00, 0x00, 0x00,
+                ],
+            ),
+        ];
+
+        for (flags, expected) in cases {
+            let pac_options = PaPacOptions {
+                flags: KerberosFlags(flags),
+            };
+            assert_eq!(pac_options.to_der().expect("Failed to encode"), expected);
+
+            let decoded = PaPacOptions::from_der(&expected).expect("Failed to decode");
+            assert_eq!(decoded, pac_options);
+        }
+    }
+
+    #[test]
+    fn pa_pac_options_decode_trimmed() {
+        // Claims and branch aware, with the unused bits trimmed.
+        let decoded = PaPacOptions::from_der(&[0x30, 0x06, 0xa0, 0x04, 0x03, 0x02, 0x06, 0xc0])
+            .expect("Failed to decode");
+        assert_eq!(
+            decoded.flags.0,
+            PacOptions::Claims | PacOptions::BranchAware
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use super::kerberos_time::KerberosTime;
+use der::asn1::{Any, BitString, OctetString};
+use der::{Decode, DecodeValue, Encode, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
+
+/// RFC 4556 3.2.3
+/// ```text
+/// PA-PK-AS-REP ::= CHOICE {
+///         dhInfo                  [0] DHRepInfo,
+///                  -- Selected when Diffie-Hellman key exchange is
+///                  -- used.
+///         encKeyPack              [1] IMPLICIT OCTET STRING,
+///                  -- Selected when public key encryption is used.
+///         ...
+/// }
+/// ````
+///
+/// Only the Diffie-Hellman key exchange is supported, so this is the dhInfo.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct PaPkAsRep(pub(crate) DhRepInfo);
+
+impl FixedTag for PaPkAsRep {
+    const TAG: Tag = Tag::ContextSpecific {
+        constructed: true,
+        number: TagNumber::N0,
+    };
+}
+
+impl<'a> DecodeValue<'a> for PaPkAsRep {
+    fn decode_value<R: der::Reader<'a>>(reader: &mut R, _header: der::Header) -> der::Result<Self> {
+        let r: DhRepInfo = DhRepInfo::decode(reader)?;
+        Ok(Self(r))
+    }
+}
+
+impl EncodeValue for PaPkAsRep {
+    // The choice is explicitly tagged, so the tag wraps the whole SEQUENCE.
+    fn value_len(&self) -> der::Result<der::Length> {
+        self.0.encoded_len()
+    }
+    fn encode_value(&self, encoder: &mut impl der::Writer) -> der::Result<()> {
+        self.0.encode(encoder)
+    }
+}
+
+/// ```text
+/// DHRepInfo ::= SEQUENCE {
+///         dhSignedData            [0] IMPLICIT OCTET STRING,
+///                  -- Contains a CMS type ContentInfo encoded according
+///                  -- to [RFC3852].
+///                  -- The contentType field of the type ContentInfo is
+///                  -- id-signedData (1.2.840.113549.1.7.2), and the
+///                  -- content field is a SignedData.
+///                  -- The eContentType field for the type SignedData is
+///                  -- id-pkinit-DHKeyData (1.3.6.1.5.2.3.2), and the
+///                  -- eContent field contains the DER encoding of the
+///                  -- type KDCDHKeyInfo.
+///         serverDHNonce           [1] DHNonce OPTIONAL,
+///         ...
+/// }
+/// ````
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
+pub(crate) struct DhRepInfo {
+    #[asn1(context_specific = "0", tag_mode = "IMPLICIT")]
+    pub(crate) dh_signed_data: OctetString,
+    #[asn1(context_specific = "1", optional = "true")]
+    pub(crate) server_dh_nonce: Option<OctetString>,
+    // RFC 8636 The KDF is only selected if the client offered any.
+    #[asn1(context_specific = "2", optional = "true")]
+    pub(crate) kdf: Option<Any>,
+}
+
+/// ```text
+/// KDCDHKeyInfo ::= SEQUENCE {
+///         subjectPublicKey        [0] BIT STRING,
+///                  -- The KDC's DH public key.
+///                  -- The DH public key value is encoded as a BIT
+///                  -- STRING according to [RFC3279].
+///         nonce                   [1] INTEGER (0..4294967295),
+///                  -- Contains the nonce in the pkAuthenticator field
+///                  -- in the request if the DH keys are NOT reused,
+///                  -- 0 otherwise.
+///         dhKeyExpiration         [2] KerberosTime OPTIONAL,
+///                  -- Expiration time for KDC's key pair,
+///                  -- present if and only if the DH keys are reused.
+///         ...
+/// }
+/// ````
+#[derive(Debug, Eq, PartialEq, Sequence)]
+pub(crate) struct KdcDhKeyInfo {
+    #[asn1(context_specific = "0")]
+    pub(crate) subject_public_key: BitString,
+    #[asn1(context_specific = "1")]
+    pub(crate) nonce: u32,
+    #[asn1(context_specific = "2", optional = "true")]
+    pub(crate) dh_key_expiration: Option<KerberosTime>,
+}
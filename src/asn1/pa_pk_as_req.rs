@@ -0,0 +1,32 @@
+use der::asn1::{Any, OctetString};
+use der::Sequence;
+
+/// RFC 4556 3.2.1
+/// ```text
+/// PA-PK-AS-REQ ::= SEQUENCE {
+///         signedAuthPack          [0] IMPLICIT OCTET STRING,
+///                  -- Contains a CMS type ContentInfo encoded
+///                  -- according to [RFC3852].
+///                  -- The contentType field of the type ContentInfo
+///                  -- is id-signedData (1.2.840.113549.1.7.2),
+///                  -- and the content field is a SignedData.
+///                  -- The eContentType field for the type SignedData is
+///                  -- id-pkinit-authData (1.3.6.1.5.2.3.1), and the
+///                  -- eContent field contains the DER encoding of the
+///                  -- type AuthPack.
+///         trustedCertifiers       [1] SEQUENCE OF
+///                     ExternalPrincipalIdentifier OPTIONAL,
+///         kdcPkId                 [2] IMPLICIT OCTET STRING
+///                                     OPTIONAL,
+///         ...
+/// }
+/// ````
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
+pub(crate) struct PaPkAsReq {
+    #[asn1(context_specific = "0", tag_mode = "IMPLICIT")]
+    pub(crate) signed_auth_pack: OctetString,
+    #[asn1(context_specific = "1", optional = "true")]
+    pub(crate) trusted_certifiers: Option<Vec<Any>>,
+    #[asn1(context_specific = "2", tag_mode = "IMPLICIT", optional = "true")]
+    pub(crate) kdc_pk_id: Option<OctetString>,
+}
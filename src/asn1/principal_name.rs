@@ -7,7 +7,7 @@ use der::Sequence;
 ///           name-string     [1] SEQUENCE OF KerberosString
 ///   }
 /// ````
-#[derive(Debug, Eq, PartialEq, Sequence)]
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
 pub(crate) struct PrincipalName {
     #[asn1(context_specific = "0")]
     pub(crate) name_type: i32,
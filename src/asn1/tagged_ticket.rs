@@ -1,7 +1,7 @@
 use super::encrypted_data::EncryptedData;
 use super::principal_name::PrincipalName;
 use super::realm::Realm;
-use der::{Decode, DecodeValue, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
+use der::{Decode, DecodeValue, Encode, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
 
 /// ```text
 /// Ticket          ::= [APPLICATION 1] SEQUENCE {
@@ -11,7 +11,7 @@ use der::{Decode, DecodeValue, EncodeValue, FixedTag, Sequence, Tag, TagNumber};
 ///         enc-part        [3] EncryptedData -- EncTicketPart
 /// }
 /// ````
-#[derive(Debug, Eq, PartialEq, Sequence)]
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
 pub(crate) struct Ticket {
     #[asn1(context_specific = "0")]
     pub(crate) tkt_vno: i8,
@@ -23,8 +23,8 @@ pub(crate) struct Ticket {
     pub(crate) enc_part: EncryptedData,
 }
 
-#[derive(Debug, Eq, PartialEq)]
-pub(crate) struct TaggedTicket(Ticket);
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct TaggedTicket(pub(crate) Ticket);
 
 impl TaggedTicket {
     pub fn new(tkt: Ticket) -> Self {
@@ -47,10 +47,11 @@ impl<'a> DecodeValue<'a> for TaggedTicket {
 }
 
 impl<'a> EncodeValue for TaggedTicket {
+    // The application tag wraps the whole SEQUENCE, not just its contents.
     fn value_len(&self) -> der::Result<der::Length> {
-        Ticket::value_len(&self.0)
+        self.0.encoded_len()
     }
     fn encode_value(&self, encoder: &mut impl der::Writer) -> der::Result<()> {
-        Ticket::encode_value(&self.0, encoder)
+        self.0.encode(encoder)
     }
 }
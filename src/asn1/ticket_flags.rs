@@ -16,10 +16,12 @@ flags! {
     ///         -- pre-authent(10),
     ///         -- hw-authent(11),
     ///         -- transited-policy-checked(12),
-    ///         -- ok-as-delegate(13)
+    ///         -- ok-as-delegate(13),
+    ///         -- anonymous(14) RFC 8062,
+    ///         -- enc-pa-rep(15) RFC 6806
     /// ````
     #[repr(u32)]
-    pub(crate) enum TicketFlags: u32 {
+    pub enum TicketFlags: u32 {
         Reserved               = 1 << 0,
         Forwardable            = 1 << 1,
         Forwarded              = 1 << 2,
@@ -34,5 +36,58 @@ flags! {
         HwAuthent              = 1 << 11,
         TransitedPolicyChecked = 1 << 12,
         OkAsDelegate           = 1 << 13,
+        Anonymous              = 1 << 14,
+        EncPaRep               = 1 << 15,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TicketFlags;
+    use crate::asn1::kerberos_flags::KerberosFlags;
+    use der::flagset::FlagSet;
+    use der::{Decode, Encode};
+
+    #[test]
+    fn ticket_flags_bit_positions() {
+        for flag in FlagSet::<TicketFlags>::full() {
+            let flags = KerberosFlags(FlagSet::from(flag));
+            let encoded = flags.to_der().expect("Failed to encode");
+
+            // Flag n is bit n of the BIT STRING, counting from the most significant
+            // bit of the first octet.
+            let bit = (flag as u32).trailing_zeros() as usize;
+            let mut expected = vec![0x03, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00];
+            expected[3 + bit / 8] = 0x80 >> (bit % 8);
+            assert_eq!(encoded, expected, "{:?}", flag);
+
+            let decoded =
+                KerberosFlags::<TicketFlags>::from_der(&encoded).expect("Failed to decode");
+            assert_eq!(decoded, flags);
+        }
+    }
+
+    #[test]
+    fn ticket_flags_tgt() {
+        // The flags of a TGT from an MIT KDC, 0x40e10000.
+        let flags = KerberosFlags(
+            TicketFlags::Forwardable
+                | TicketFlags::Renewable
+                | TicketFlags::Initial
+                | TicketFlags::PreAuthent
+                | TicketFlags::EncPaRep,
+        );
+        let encoded = [0x03, 0x05, 0x00, 0x40, 0xe1, 0x00, 0x00];
+        assert_eq!(flags.to_der().expect("Failed to encode"), encoded);
+        assert_eq!(
+            KerberosFlags::<TicketFlags>::from_der(&encoded).expect("Failed to decode"),
+            flags
+        );
+
+        // Bits that aren't ticket flags, which Active Directory sets, are ignored.
+        let decoded =
+            KerberosFlags::<TicketFlags>::from_der(&[0x03, 0x05, 0x00, 0x40, 0xe1, 0x80, 0x00])
+                .expect("Failed to decode");
+        assert_eq!(decoded, flags);
     }
 }
@@ -7,10 +7,10 @@ use der::Sequence;
 ///        contents        [1] OCTET STRING
 ///}
 /// ````
-#[derive(Debug, Eq, PartialEq, Sequence)]
+#[derive(Debug, Clone, Eq, PartialEq, Sequence)]
 pub(crate) struct TransitedEncoding {
     #[asn1(context_specific = "0")]
-    tr_type: i32,
+    pub(crate) tr_type: i32,
     #[asn1(context_specific = "1")]
-    contents: OctetString,
+    pub(crate) contents: OctetString,
 }
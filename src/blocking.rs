@@ -0,0 +1,137 @@
+use crate::constants::DEFAULT_IO_MAX_SIZE;
+use crate::error::KrbError;
+use crate::framing::{decode_record, decode_response, encode_request};
+use crate::proto::{KerberosRequest, KerberosResponse};
+use bytes::BytesMut;
+use std::io::{self, Read, Write};
+use tracing::debug;
+
+/// Send `request` to the KDC on `stream`, and wait for its response. This uses the same
+/// record framing as [KerberosTcpCodec](crate::KerberosTcpCodec) so `stream` is normally
+/// a [std::net::TcpStream], with any timeouts already configured on it.
+pub fn send_recv<S: Read + Write>(
+    stream: &mut S,
+    request: &KerberosRequest,
+) -> Result<KerberosResponse, KrbError> {
+    let mut buf = BytesMut::new();
+    encode_request(request, DEFAULT_IO_MAX_SIZE, &mut buf).map_err(|err| {
+        debug!(?err, "Failed to encode request");
+        KrbError::IoError(err.kind())
+    })?;
+
+    stream
+        .write_all(&buf)
+        .and_then(|()| stream.flush())
+        .map_err(|err| {
+            debug!(?err, "Failed to send request to KDC");
+            KrbError::IoError(err.kind())
+        })?;
+
+    buf.clear();
+    let mut chunk = [0u8; 4096];
+
+    loop {
+        let record = decode_record(&mut buf, DEFAULT_IO_MAX_SIZE).map_err(|err| {
+            debug!(?err, "Failed to decode response from KDC");
+            KrbError::IoError(err.kind())
+        })?;
+
+        if let Some(record) = record {
+            let response = decode_response(&record).map_err(|err| {
+                debug!(?err, "Failed to decode response from KDC");
+                KrbError::IoError(err.kind())
+            })?;
+            return Ok(response);
+        }
+
+        match stream.read(&mut chunk) {
+            Ok(0) => return Err(KrbError::ConnectionClosed),
+            Ok(len) => buf.extend_from_slice(&chunk[..len]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => {
+                debug!(?err, "Failed to receive response from KDC");
+                return Err(KrbError::IoError(err.kind()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::send_recv;
+    use crate::error::KrbError;
+    use crate::proto::{KerberosRequest, KerberosResponse};
+    use crate::tests::{as_rep_record, as_req};
+    use std::io::{self, Cursor, Read, Write};
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    /// A stream that replies with canned bytes, and records what was written to it.
+    struct CannedStream {
+        reply: Cursor<Vec<u8>>,
+        sent: Vec<u8>,
+    }
+
+    impl CannedStream {
+        fn new(reply: Vec<u8>) -> Self {
+            CannedStream {
+                reply: Cursor::new(reply),
+                sent: Vec::with_capacity(1024),
+            }
+        }
+    }
+
+    impl Read for CannedStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            // Trickle the reply out, so that records span multiple reads.
+            let len = buf.len().min(7);
+            self.reply.read(&mut buf[..len])
+        }
+    }
+
+    impl Write for CannedStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.sent.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_send_recv() {
+        let mut stream = CannedStream::new(as_rep_record());
+
+        let response = send_recv(&mut stream, &as_req()).expect("Exchange failed");
+        assert!(matches!(response, KerberosResponse::AsRep(_)));
+
+        let (len, der_bytes) = stream.sent.split_at(4);
+        assert_eq!(len, (der_bytes.len() as u32).to_be_bytes());
+        assert!(KerberosRequest::from_der(der_bytes).is_ok());
+    }
+
+    #[test]
+    fn test_send_recv_truncated_reply() {
+        let mut record = as_rep_record();
+        record.truncate(record.len() / 2);
+        let mut stream = CannedStream::new(record);
+
+        let err = send_recv(&mut stream, &as_req()).expect_err("Exchange succeeded");
+        assert!(matches!(err, KrbError::ConnectionClosed));
+    }
+
+    #[test]
+    fn test_localhost_kdc_blocking() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut stream =
+            TcpStream::connect("127.0.0.1:55000").expect("Unable to connect to localhost:55000");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .expect("Unable to set read timeout");
+
+        let response = send_recv(&mut stream, &as_req()).expect("Exchange failed");
+        assert!(matches!(response, KerberosResponse::AsRep(_)));
+    }
+}
@@ -0,0 +1,249 @@
+//! Keyed checksums (RFC 3961 4), for the protocol elements that need integrity
+//! rather than encryption, such as the checksum of an authenticator, the checksum
+//! of PA-FOR-USER and the req-checksum of FAST.
+
+use crate::asn1::checksum::Checksum;
+use crate::asn1::constants::checksum_types::ChecksumType;
+use crate::crypto::{checksum_hmac_md5, cipher_for};
+use crate::error::KrbError;
+use crate::proto::{BaseKey, KeyUsage};
+use der::asn1::OctetString;
+use subtle::ConstantTimeEq;
+
+impl Checksum {
+    /// Compute the checksum of `checksum_type` over `data`, keyed with `key` for
+    /// `key_usage`. The hmac-sha1-96 and hmac-sha2 checksums must be those of the
+    /// etype of the key, while hmac-md5 (RFC 4757) can be keyed with any key.
+    pub(crate) fn compute(
+        checksum_type: ChecksumType,
+        key: &BaseKey,
+        key_usage: KeyUsage,
+        data: &[u8],
+    ) -> Result<Self, KrbError> {
+        let checksum = match checksum_type {
+            ChecksumType::HMAC_MD5 => checksum_hmac_md5(key.as_slice(), data, key_usage)?.to_vec(),
+            _ => {
+                let cipher = cipher_for(key.etype())?;
+                if cipher.checksum_type() != checksum_type {
+                    return Err(KrbError::UnsupportedChecksumType(checksum_type.into()));
+                }
+                cipher.checksum(key.as_slice(), key_usage, data)?
+            }
+        };
+
+        Ok(Checksum {
+            checksum_type: checksum_type.into(),
+            checksum: OctetString::new(checksum).map_err(|_| KrbError::DerEncodeChecksum)?,
+        })
+    }
+
+    /// Verify that this is the checksum of `data`, keyed with `key` for `key_usage`.
+    /// The checksum must be of the mandatory checksum type of the etype of `key`, so
+    /// that a peer can't downgrade it to hmac-md5. Any other type is reported as
+    /// [KrbError::UnsupportedChecksumType].
+    pub(crate) fn verify(
+        &self,
+        key: &BaseKey,
+        key_usage: KeyUsage,
+        data: &[u8],
+    ) -> Result<(), KrbError> {
+        self.verify_as(key.mandatory_checksum_type()?, key, key_usage, data)
+    }
+
+    /// Verify that this is the hmac-md5 checksum (RFC 4757) of `data`, keyed with
+    /// `key` for `key_usage`. This is only for PA-FOR-USER, whose checksum is
+    /// hmac-md5 whatever the etype of the session key is.
+    pub(crate) fn verify_hmac_md5(
+        &self,
+        key: &BaseKey,
+        key_usage: KeyUsage,
+        data: &[u8],
+    ) -> Result<(), KrbError> {
+        self.verify_as(ChecksumType::HMAC_MD5, key, key_usage, data)
+    }
+
+    fn verify_as(
+        &self,
+        checksum_type: ChecksumType,
+        key: &BaseKey,
+        key_usage: KeyUsage,
+        data: &[u8],
+    ) -> Result<(), KrbError> {
+        if self.checksum_type != i32::from(checksum_type) {
+            return Err(KrbError::UnsupportedChecksumType(self.checksum_type));
+        }
+
+        // A plain comparison stops at the first byte that differs, which would let
+        // a peer time its way to a valid checksum.
+        let computed = Checksum::compute(checksum_type, key, key_usage, data)?;
+        if bool::from(computed.checksum.as_bytes().ct_eq(self.checksum.as_bytes())) {
+            Ok(())
+        } else {
+            Err(KrbError::MessageAuthenticationFailed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Checksum;
+    use crate::asn1::constants::checksum_types::ChecksumType;
+    use crate::error::KrbError;
+    use crate::proto::{BaseKey, EncryptionType, KeyUsage};
+    use der::asn1::OctetString;
+
+    #[test]
+    fn test_checksum_hmac_sha1_96_aes256() {
+        // From the MIT krb5 checksum test vectors, t_cksums.c
+        let key = hex::decode("b1ae4cd8462aff1677053cc9279aac30b796fb81ce21474dd3ddbcfea4ec76d7")
+            .expect("Invalid key");
+        let key = BaseKey::from_bytes(EncryptionType::AES256_CTS_HMAC_SHA1_96, &key)
+            .expect("Invalid key");
+
+        let cksum = Checksum::compute(
+            ChecksumType::HMAC_SHA1_96_AES256,
+            &key,
+            KeyUsage::TGS_REQ_AUTH_DATA_SESSION,
+            b"fourteen",
+        )
+        .expect("Failed to compute checksum");
+        assert_eq!(cksum.checksum_type, 16);
+        assert_eq!(
+            cksum.checksum.as_bytes(),
+            hex::decode("e08739e3279e2903ec8e3836").expect("Invalid checksum")
+        );
+
+        assert!(cksum
+            .verify(&key, KeyUsage::TGS_REQ_AUTH_DATA_SESSION, b"fourteen")
+            .is_ok());
+        assert!(matches!(
+            cksum.verify(&key, KeyUsage::TGS_REQ_AUTH_DATA_SESSION, b"fifteen"),
+            Err(KrbError::MessageAuthenticationFailed)
+        ));
+        assert!(matches!(
+            cksum.verify(&key, KeyUsage::TGS_REQ_AUTH_DATA_SUBKEY, b"fourteen"),
+            Err(KrbError::MessageAuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_checksum_hmac_sha1_96_aes128() {
+        // Computed with an independent implementation of the RFC 3961 DK and n-fold,
+        // which reproduces the aes256 vector of MIT krb5 above.
+        let key = hex::decode("9062430c8cda3388922e6d6a509f5b7a").expect("Invalid key");
+        let key = BaseKey::from_bytes(EncryptionType::AES128_CTS_HMAC_SHA1_96, &key)
+            .expect("Invalid key");
+
+        let cksum = Checksum::compute(
+            ChecksumType::HMAC_SHA1_96_AES128,
+            &key,
+            KeyUsage::AS_REP_ENC_PART,
+            b"fourteen",
+        )
+        .expect("Failed to compute checksum");
+        assert_eq!(cksum.checksum_type, 15);
+        assert_eq!(
+            cksum.checksum.as_bytes(),
+            hex::decode("c6c304c031fecb7107d2b91b").expect("Invalid checksum")
+        );
+        assert!(cksum
+            .verify(&key, KeyUsage::AS_REP_ENC_PART, b"fourteen")
+            .is_ok());
+
+        // The checksum type must be that of the etype of the key.
+        assert!(matches!(
+            Checksum::compute(
+                ChecksumType::HMAC_SHA1_96_AES256,
+                &key,
+                KeyUsage::AS_REP_ENC_PART,
+                b"fourteen"
+            ),
+            Err(KrbError::UnsupportedChecksumType(16))
+        ));
+        let cksum = Checksum {
+            checksum_type: ChecksumType::HMAC_SHA1_96_AES256.into(),
+            checksum: cksum.checksum,
+        };
+        assert!(matches!(
+            cksum.verify(&key, KeyUsage::AS_REP_ENC_PART, b"fourteen"),
+            Err(KrbError::UnsupportedChecksumType(16))
+        ));
+    }
+
+    #[test]
+    fn test_checksum_hmac_md5_any_key() {
+        let key = BaseKey::from_bytes(EncryptionType::AES256_CTS_HMAC_SHA1_96, &[0x42; 32])
+            .expect("Invalid key");
+
+        let cksum = Checksum::compute(
+            ChecksumType::HMAC_MD5,
+            &key,
+            KeyUsage::KERB_NON_KERB_CKSUM_SALT,
+            b"fourteen",
+        )
+        .expect("Failed to compute checksum");
+        assert_eq!(cksum.checksum.as_bytes().len(), 16);
+        assert!(cksum
+            .verify_hmac_md5(&key, KeyUsage::KERB_NON_KERB_CKSUM_SALT, b"fourteen")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_checksum_downgrade_rejected() {
+        let key = BaseKey::from_bytes(EncryptionType::AES256_CTS_HMAC_SHA1_96, &[0x42; 32])
+            .expect("Invalid key");
+
+        // A valid hmac-md5 checksum still isn't accepted in place of hmac-sha1-96-aes256.
+        let cksum = Checksum::compute(ChecksumType::HMAC_MD5, &key, KeyUsage::AS_REQ, b"fourteen")
+            .expect("Failed to compute checksum");
+        assert!(matches!(
+            cksum.verify(&key, KeyUsage::AS_REQ, b"fourteen"),
+            Err(KrbError::UnsupportedChecksumType(-138))
+        ));
+
+        // Nor is the mandatory checksum accepted where hmac-md5 is expected.
+        let cksum = Checksum::compute(
+            ChecksumType::HMAC_SHA1_96_AES256,
+            &key,
+            KeyUsage::AS_REQ,
+            b"fourteen",
+        )
+        .expect("Failed to compute checksum");
+        assert!(matches!(
+            cksum.verify_hmac_md5(&key, KeyUsage::AS_REQ, b"fourteen"),
+            Err(KrbError::UnsupportedChecksumType(16))
+        ));
+    }
+
+    #[test]
+    fn test_checksum_bit_flips() {
+        let key = BaseKey::from_bytes(EncryptionType::AES256_CTS_HMAC_SHA1_96, &[0x42; 32])
+            .expect("Invalid key");
+
+        for checksum_type in [ChecksumType::HMAC_SHA1_96_AES256, ChecksumType::HMAC_MD5] {
+            let cksum = Checksum::compute(checksum_type, &key, KeyUsage::AS_REQ, b"fourteen")
+                .expect("Failed to compute checksum");
+
+            // Any single bit flipped anywhere in the checksum fails to verify.
+            let bytes = cksum.checksum.as_bytes();
+            for bit in 0..bytes.len() * 8 {
+                let mut forged = bytes.to_vec();
+                forged[bit / 8] ^= 1 << (bit % 8);
+                let forged = Checksum {
+                    checksum_type: cksum.checksum_type,
+                    checksum: OctetString::new(forged).expect("Invalid checksum"),
+                };
+                let verified = match checksum_type {
+                    ChecksumType::HMAC_MD5 => {
+                        forged.verify_hmac_md5(&key, KeyUsage::AS_REQ, b"fourteen")
+                    }
+                    _ => forged.verify(&key, KeyUsage::AS_REQ, b"fourteen"),
+                };
+                assert!(matches!(
+                    verified,
+                    Err(KrbError::MessageAuthenticationFailed)
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,1999 @@
+use crate::constants::{
+    DEFAULT_CONNECT_TIMEOUT, DEFAULT_RESPONSE_TIMEOUT, DEFAULT_RETRIES, DEFAULT_RETRY_BACKOFF,
+    MAX_CROSS_REALM_HOPS,
+};
+use crate::error::KrbError;
+use crate::exchange::{AsExchange, AsStep, Credentials};
+#[cfg(feature = "pkinit")]
+use crate::pkinit::{KdcCertValidator, PkinitSigner};
+#[cfg(feature = "pkinit")]
+use crate::proto::KerberosAsReq;
+use crate::proto::{
+    split_principal, AuthenticatedReply, BaseKey, ClockOffset, FastArmor, FlagSet, KdcOptions,
+    KerberosRequest, KerberosResponse, KrbErrorCode, TicketFlags,
+};
+use crate::{KerberosTcpCodec, KerberosUdpCodec};
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, SystemTime};
+use tokio::net::{lookup_host, TcpStream, ToSocketAddrs, UdpSocket};
+use tokio_util::codec::Framed;
+use tokio_util::udp::UdpFramed;
+use tracing::debug;
+use zeroize::Zeroizing;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdcTransport {
+    Tcp,
+    Udp,
+}
+
+/// The address of a KDC and the transport to reach it with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdcAddress {
+    pub(crate) transport: KdcTransport,
+    pub(crate) addr: SocketAddr,
+}
+
+impl KdcAddress {
+    pub fn new(transport: KdcTransport, addr: SocketAddr) -> Self {
+        KdcAddress { transport, addr }
+    }
+
+    pub fn transport(&self) -> KdcTransport {
+        self.transport
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+/// The timeouts and retries applied to each exchange with a KDC.
+#[derive(Debug, Clone)]
+pub struct ExchangeOptions {
+    connect_timeout: Duration,
+    response_timeout: Duration,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl Default for ExchangeOptions {
+    fn default() -> Self {
+        ExchangeOptions {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            response_timeout: DEFAULT_RESPONSE_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+            backoff: DEFAULT_RETRY_BACKOFF,
+        }
+    }
+}
+
+impl ExchangeOptions {
+    /// How long to wait for a TCP connection to a KDC to be established.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// How long to wait for a KDC to respond to a request.
+    pub fn response_timeout(mut self, timeout: Duration) -> Self {
+        self.response_timeout = timeout;
+        self
+    }
+
+    /// How many times to retry a KDC that failed to respond before moving on to the
+    /// next one. The delay between retries starts at `backoff` and doubles each time.
+    pub fn retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.backoff = backoff;
+        self
+    }
+
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        self.backoff.saturating_mul(2u32.saturating_pow(attempt))
+    }
+}
+
+/// Find the KDCs that serve a realm, so that a client can follow a cross-realm path
+/// to a realm other than its own.
+pub trait KdcLocator {
+    fn locate(&self, realm: &str)
+        -> impl Future<Output = Result<Vec<KdcAddress>, KrbError>> + Send;
+}
+
+/// A static mapping of realms to their KDCs, as in the `[realms]` section of krb5.conf.
+impl KdcLocator for HashMap<String, Vec<KdcAddress>> {
+    async fn locate(&self, realm: &str) -> Result<Vec<KdcAddress>, KrbError> {
+        self.get(realm).cloned().ok_or(KrbError::NoKdcFound)
+    }
+}
+
+/// Locate the KDCs of a realm from DNS.
+#[cfg(feature = "dns")]
+pub struct DnsKdcLocator;
+
+#[cfg(feature = "dns")]
+impl KdcLocator for DnsKdcLocator {
+    async fn locate(&self, realm: &str) -> Result<Vec<KdcAddress>, KrbError> {
+        crate::resolver::resolve_kdcs(realm).await
+    }
+}
+
+enum Transport {
+    Tcp(Framed<TcpStream, KerberosTcpCodec>),
+    Udp(UdpFramed<KerberosUdpCodec>),
+}
+
+/// A client connected to a KDC, which performs whole kerberos exchanges on behalf
+/// of the caller. If the KDC stops responding, the client fails over to the next
+/// KDC it was given.
+pub struct KerberosClient {
+    realm: String,
+    kdc: SocketAddr,
+    transport: Transport,
+    /// The KDCs that haven't been tried yet, in the order to try them.
+    candidates: VecDeque<KdcAddress>,
+    options: ExchangeOptions,
+    // How far the clock of the KDC is from ours, once it refused our clock as skewed.
+    clock_offset: ClockOffset,
+}
+
+impl KerberosClient {
+    /// Connect to the KDC at `addr` which serves `realm`. If `addr` resolves to
+    /// more than one address, each is tried in turn.
+    pub async fn connect<A: ToSocketAddrs>(addr: A, realm: &str) -> Result<Self, KrbError> {
+        let kdcs = resolve(addr)
+            .await?
+            .map(|addr| KdcAddress::new(KdcTransport::Tcp, addr))
+            .collect();
+
+        Self::connect_kdcs(kdcs, realm, ExchangeOptions::default()).await
+    }
+
+    /// Communicate with the KDC at `addr` which serves `realm` over UDP. If a response
+    /// is too large for a datagram, the request is transparently retried over TCP, and
+    /// TCP is used for the remainder of the client's lifetime.
+    pub async fn connect_udp<A: ToSocketAddrs>(addr: A, realm: &str) -> Result<Self, KrbError> {
+        let kdcs = resolve(addr)
+            .await?
+            .map(|addr| KdcAddress::new(KdcTransport::Udp, addr))
+            .collect();
+
+        Self::connect_kdcs(kdcs, realm, ExchangeOptions::default()).await
+    }
+
+    /// Connect to the first reachable KDC of `kdcs`, which all serve `realm`. If a KDC
+    /// fails to connect or respond within the limits of `options`, the next one is tried.
+    /// A KDC that responds with an error is not failed over, since it was reachable.
+    pub async fn connect_kdcs(
+        kdcs: Vec<KdcAddress>,
+        realm: &str,
+        options: ExchangeOptions,
+    ) -> Result<Self, KrbError> {
+        let mut candidates = VecDeque::from(kdcs);
+        let (kdc, transport) = connect_next(
+            &mut candidates,
+            options.connect_timeout,
+            KrbError::NoKdcFound,
+        )
+        .await?;
+
+        Ok(KerberosClient {
+            realm: realm.to_string(),
+            kdc,
+            transport,
+            candidates,
+            options,
+            clock_offset: ClockOffset::default(),
+        })
+    }
+
+    /// Discover the KDCs of `realm` from DNS, and connect to the first one that is reachable.
+    #[cfg(feature = "dns")]
+    pub async fn connect_realm(realm: &str) -> Result<Self, KrbError> {
+        let kdcs = crate::resolver::resolve_kdcs(realm).await?;
+        Self::connect_kdcs(kdcs, realm, ExchangeOptions::default()).await
+    }
+
+    /// The address of the KDC that the client is currently using, which is the KDC that
+    /// served the most recent exchange.
+    pub fn kdc(&self) -> SocketAddr {
+        self.kdc
+    }
+
+    /// How far the clock of the KDC is from ours. This is learnt when the KDC refuses
+    /// a request for clock skew, and applied to the times of later requests.
+    pub fn clock_offset(&self) -> ClockOffset {
+        self.clock_offset
+    }
+
+    /// Perform an AS exchange for `client_name`, requesting a ticket for `service_name`
+    /// that is valid until `until`. If the KDC requires pre-authentication, the exchange
+    /// is retried with an encrypted timestamp derived from `password`.
+    pub async fn authenticate(
+        &mut self,
+        client_name: &str,
+        service_name: &str,
+        password: &str,
+        until: SystemTime,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        self.authenticate_inner(client_name, service_name, password, until, None, None)
+            .await
+    }
+
+    /// Perform an AS exchange as [Self::authenticate], requesting a renewable ticket
+    /// that may be renewed with [Self::renew_ticket] until `renew_until`.
+    pub async fn authenticate_renewable(
+        &mut self,
+        client_name: &str,
+        service_name: &str,
+        password: &str,
+        until: SystemTime,
+        renew_until: SystemTime,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        self.authenticate_inner(
+            client_name,
+            service_name,
+            password,
+            until,
+            Some(renew_until),
+            None,
+        )
+        .await
+    }
+
+    /// Perform an AS exchange as [Self::authenticate], armored with FAST (RFC 6113).
+    /// The pre-authentication is then an encrypted challenge, which unlike an
+    /// encrypted timestamp can't be attacked offline by an observer of the exchange.
+    pub async fn authenticate_armored(
+        &mut self,
+        client_name: &str,
+        service_name: &str,
+        password: &str,
+        until: SystemTime,
+        armor: &FastArmor,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        self.authenticate_inner(
+            client_name,
+            service_name,
+            password,
+            until,
+            None,
+            Some(armor),
+        )
+        .await
+    }
+
+    async fn authenticate_inner(
+        &mut self,
+        client_name: &str,
+        service_name: &str,
+        password: &str,
+        until: SystemTime,
+        renew_until: Option<SystemTime>,
+        armor: Option<&FastArmor>,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        let credentials = Credentials::Password(Zeroizing::new(password.to_string()));
+        let mut corrected = false;
+
+        loop {
+            let mut exchange =
+                AsExchange::new(client_name, service_name, self.realm.as_str(), until)
+                    .renew_until(renew_until)
+                    .clock_offset(self.clock_offset);
+            if let Some(armor) = armor {
+                exchange = exchange.armor(armor);
+            }
+
+            let mut request = exchange.start()?;
+
+            let err = loop {
+                let response = self.exchange(request).await?;
+                match exchange.step_async(response, &credentials).await {
+                    AsStep::SendNext(next) => request = next,
+                    AsStep::Done(reply) => return Ok(reply),
+                    // The caller has to connect to the KDC of the referred realm.
+                    AsStep::Referral { realm } => return Err(KrbError::ClientReferral(realm)),
+                    AsStep::Failed(err) => break err,
+                }
+            };
+
+            // As kinit does, start again once with the clock of the KDC when it
+            // refused ours as skewed.
+            match exchange.kdc_clock_offset() {
+                Some(clock_offset) if !corrected => {
+                    debug!(?clock_offset, "Retrying with the clock of the KDC");
+                    self.clock_offset = clock_offset;
+                    corrected = true;
+                }
+                _ => return Err(err),
+            }
+        }
+    }
+
+    /// Perform an AS exchange for `client_name` that is preauthenticated with PKINIT
+    /// (RFC 4556), proving the identity of the client with the certificate of
+    /// `signer` rather than a password.
+    #[cfg(feature = "pkinit")]
+    pub async fn authenticate_pkinit<S, V>(
+        &mut self,
+        client_name: &str,
+        service_name: &str,
+        until: SystemTime,
+        signer: S,
+        kdc_cert_validator: V,
+    ) -> Result<AuthenticatedReply, KrbError>
+    where
+        S: PkinitSigner + 'static,
+        V: KdcCertValidator + 'static,
+    {
+        let as_req = KerberosRequest::build_asreq(client_name, service_name, &self.realm, until)
+            .preauth_pkinit(signer, kdc_cert_validator)
+            .build()?;
+
+        // The reply key is agreed with the Diffie-Hellman key of the request, and is
+        // bound to its nonce.
+        let KerberosRequest::AsReq(KerberosAsReq {
+            pkinit: Some(pkinit),
+            nonce,
+            ..
+        }) = &as_req
+        else {
+            unreachable!();
+        };
+        let (pkinit, nonce) = (pkinit.clone(), *nonce);
+
+        match self.exchange(as_req).await? {
+            KerberosResponse::AsRep(as_rep) => {
+                let reply_key = as_rep.pkinit_reply_key(&pkinit, nonce)?;
+                as_rep.into_authenticated_reply(&reply_key)
+            }
+            // The KDC didn't accept the certificate as preauthentication.
+            KerberosResponse::PaRep(_) => {
+                Err(KrbError::KdcError(KrbErrorCode::KdcErrPreauthRequired))
+            }
+            KerberosResponse::Err(err_rep) => Err(KrbError::KdcError(err_rep.error_code())),
+            KerberosResponse::TgsRep(_) => Err(KrbError::UnexpectedResponse),
+        }
+    }
+
+    /// Perform a TGS exchange, presenting the TGT in `tgt` to request a ticket for
+    /// `service_name` that is valid until `until`.
+    pub async fn get_service_ticket(
+        &mut self,
+        tgt: &AuthenticatedReply,
+        service_name: &str,
+        until: SystemTime,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        self.request_service_ticket(tgt, service_name, until, FlagSet::default())
+            .await
+    }
+
+    async fn request_service_ticket(
+        &mut self,
+        tgt: &AuthenticatedReply,
+        service_name: &str,
+        until: SystemTime,
+        kdc_options: FlagSet<KdcOptions>,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        // A fresh subkey in the authenticator keeps the reply, and with it the new
+        // session key, out of the session key of the TGT.
+        let subkey = BaseKey::generate_session_key(tgt.session_key().etype())?;
+        let tgs_req = KerberosRequest::build_tgsreq(service_name, until)
+            .kdc_options(kdc_options)
+            .clock_offset(self.clock_offset)
+            .subkey(Some(subkey.clone()))
+            .build(tgt)?;
+
+        match self.exchange(tgs_req.clone()).await? {
+            KerberosResponse::TgsRep(tgs_rep) => tgs_rep.into_authenticated_reply_for_request(
+                &tgs_req,
+                tgt.session_key(),
+                Some(&subkey),
+            ),
+            KerberosResponse::Err(err_rep) => Err(KrbError::KdcError(err_rep.error_code())),
+            KerberosResponse::AsRep(_) | KerberosResponse::PaRep(_) => {
+                Err(KrbError::UnexpectedResponse)
+            }
+        }
+    }
+
+    /// Renew the renewable ticket `tgt` so that it is valid until `until`, returning the
+    /// same ticket with new times. A ticket can't be renewed after it expires or past its
+    /// renew-till, which is checked before the KDC is asked.
+    pub async fn renew_ticket(
+        &mut self,
+        tgt: &AuthenticatedReply,
+        until: SystemTime,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        let Some(renew_till) = tgt.renew_till() else {
+            return Err(KrbError::TicketNotRenewable);
+        };
+        if !tgt.ticket_flags().contains(TicketFlags::Renewable) {
+            return Err(KrbError::TicketNotRenewable);
+        }
+
+        let now = SystemTime::now();
+        if renew_till <= now || tgt.end_time() <= now {
+            return Err(KrbError::RenewTillExpired);
+        }
+
+        let renew_req = KerberosRequest::build_renew(tgt, until)?;
+
+        match self.exchange(renew_req.clone()).await? {
+            KerberosResponse::TgsRep(tgs_rep) => {
+                tgs_rep.into_authenticated_reply_for_request(&renew_req, tgt.session_key(), None)
+            }
+            KerberosResponse::Err(err_rep) => Err(KrbError::RenewalFailed(err_rep.error_code())),
+            KerberosResponse::AsRep(_) | KerberosResponse::PaRep(_) => {
+                Err(KrbError::UnexpectedResponse)
+            }
+        }
+    }
+
+    /// Validate the postdated ticket `tgt` once its start time has arrived. The KDC
+    /// replies with the same ticket, which is then no longer flagged invalid and may
+    /// be used.
+    pub async fn validate_ticket(
+        &mut self,
+        tgt: &AuthenticatedReply,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        let validate_req = KerberosRequest::build_validate(tgt)?;
+
+        let reply = match self.exchange(validate_req.clone()).await? {
+            KerberosResponse::TgsRep(tgs_rep) => tgs_rep.into_authenticated_reply_for_request(
+                &validate_req,
+                tgt.session_key(),
+                None,
+            )?,
+            KerberosResponse::Err(err_rep) => return Err(KrbError::KdcError(err_rep.error_code())),
+            KerberosResponse::AsRep(_) | KerberosResponse::PaRep(_) => {
+                return Err(KrbError::UnexpectedResponse)
+            }
+        };
+
+        if reply.ticket_flags().contains(TicketFlags::Invalid) {
+            return Err(KrbError::TicketStillInvalid);
+        }
+
+        Ok(reply)
+    }
+
+    /// Request a ticket for `service_name`, which is qualified with the realm of the
+    /// service as in `host/server.remote.example.com@REMOTE.EXAMPLE.COM`. When the
+    /// service is in another realm, cross-realm TGTs are requested along the path
+    /// to that realm, and the KDCs of each realm on the path are found with `locator`.
+    pub async fn get_cross_realm_ticket<L: KdcLocator>(
+        &mut self,
+        tgt: &AuthenticatedReply,
+        service_name: &str,
+        until: SystemTime,
+        locator: &L,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        let (name, service_realm) = match split_principal(service_name) {
+            (name, Some(service_realm)) if service_realm != self.realm => (name, service_realm),
+            _ => return self.get_service_ticket(tgt, service_name, until).await,
+        };
+
+        // The KDC answers with a TGT for the next realm on its path to the service
+        // realm, which is the service realm itself when the realms trust each other.
+        // That TGT names another service than the one requested, which the reply is
+        // only allowed to when the request is canonicalized.
+        let cross_realm_tgs = format!("krbtgt/{}", service_realm);
+        let hop_options = FlagSet::from(KdcOptions::Canonicalize);
+        let mut path = CrossRealmPath::new(&self.realm);
+        let mut cross_realm_tgt = self
+            .request_service_ticket(tgt, &cross_realm_tgs, until, hop_options)
+            .await?;
+
+        loop {
+            let realm = cross_realm_tgt
+                .ticket()
+                .tgs_realm()
+                .ok_or(KrbError::UnexpectedResponse)?;
+            path.visit(&realm)?;
+
+            debug!(%realm, "Following cross-realm path");
+            let kdcs = locator.locate(&realm).await?;
+            let mut client = Self::connect_kdcs(kdcs, &realm, self.options.clone()).await?;
+            // Our clock is as far off for the KDCs along the path as for our own.
+            client.clock_offset = self.clock_offset;
+
+            if realm == service_realm {
+                return client
+                    .get_service_ticket(&cross_realm_tgt, name, until)
+                    .await;
+            }
+
+            cross_realm_tgt = client
+                .request_service_ticket(&cross_realm_tgt, &cross_realm_tgs, until, hop_options)
+                .await?;
+        }
+    }
+
+    async fn exchange(&mut self, request: KerberosRequest) -> Result<KerberosResponse, KrbError> {
+        loop {
+            let err = match self.exchange_with_retries(&request).await {
+                Ok(response) => {
+                    debug!(kdc = %self.kdc, "Exchange completed");
+                    return Ok(response);
+                }
+                Err(err) if is_transport_error(&err) => err,
+                Err(err) => return Err(err),
+            };
+
+            debug!(?err, kdc = %self.kdc, "Exchange with KDC failed, trying the next KDC");
+            let (kdc, transport) =
+                connect_next(&mut self.candidates, self.options.connect_timeout, err).await?;
+            self.kdc = kdc;
+            self.transport = transport;
+        }
+    }
+
+    async fn exchange_with_retries(
+        &mut self,
+        request: &KerberosRequest,
+    ) -> Result<KerberosResponse, KrbError> {
+        let mut attempt = 0;
+        loop {
+            match self.attempt_exchange(request, attempt > 0).await {
+                Err(err) if is_transport_error(&err) && attempt < self.options.retries => {
+                    let delay = self.options.backoff_delay(attempt);
+                    debug!(?err, kdc = %self.kdc, %attempt, ?delay, "Exchange failed, retrying");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn attempt_exchange(
+        &mut self,
+        request: &KerberosRequest,
+        reconnect: bool,
+    ) -> Result<KerberosResponse, KrbError> {
+        // A TCP stream may have been left part way through a record, so start afresh.
+        if reconnect && matches!(self.transport, Transport::Tcp(_)) {
+            let stream = with_timeout(self.options.connect_timeout, connect_tcp(self.kdc)).await?;
+            self.transport = Transport::Tcp(stream);
+        }
+
+        self.exchange_current(request).await
+    }
+
+    async fn exchange_current(
+        &mut self,
+        request: &KerberosRequest,
+    ) -> Result<KerberosResponse, KrbError> {
+        let response = match &mut self.transport {
+            Transport::Tcp(stream) => {
+                with_timeout(self.options.response_timeout, exchange_tcp(stream, request)).await?
+            }
+            Transport::Udp(socket) => {
+                with_timeout(
+                    self.options.response_timeout,
+                    exchange_udp(socket, self.kdc, request),
+                )
+                .await?
+            }
+        };
+
+        if matches!(self.transport, Transport::Udp(_))
+            && response.error_code() == Some(KrbErrorCode::KrbErrResponseTooBig)
+        {
+            // RFC4120 7.2.1 - the client must retry the request over TCP. We resend the
+            // same request so that the nonce still matches.
+            debug!("Response too big for UDP, retrying over TCP");
+            let mut stream =
+                with_timeout(self.options.connect_timeout, connect_tcp(self.kdc)).await?;
+            let response = with_timeout(
+                self.options.response_timeout,
+                exchange_tcp(&mut stream, request),
+            )
+            .await?;
+
+            self.transport = Transport::Tcp(stream);
+            return Ok(response);
+        }
+
+        Ok(response)
+    }
+}
+
+/// The realms visited while following a cross-realm path, so that a misconfigured
+/// path can neither loop nor go on forever.
+struct CrossRealmPath {
+    realms: Vec<String>,
+}
+
+impl CrossRealmPath {
+    fn new(realm: &str) -> Self {
+        CrossRealmPath {
+            realms: vec![realm.to_string()],
+        }
+    }
+
+    fn visit(&mut self, realm: &str) -> Result<(), KrbError> {
+        if self.realms.iter().any(|visited| visited == realm) {
+            return Err(KrbError::CrossRealmLoop(realm.to_string()));
+        }
+        if self.realms.len() > MAX_CROSS_REALM_HOPS {
+            return Err(KrbError::CrossRealmHopLimit);
+        }
+        self.realms.push(realm.to_string());
+        Ok(())
+    }
+}
+
+/// Only failures to reach the KDC are worth trying another KDC for. If the KDC
+/// answered, even with something we couldn't decode, another KDC won't do better.
+fn is_transport_error(err: &KrbError) -> bool {
+    match err {
+        KrbError::ConnectionClosed | KrbError::Timeout => true,
+        KrbError::IoError(kind) => !matches!(
+            kind,
+            io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput | io::ErrorKind::Unsupported
+        ),
+        _ => false,
+    }
+}
+
+async fn with_timeout<T, F>(timeout: Duration, fut: F) -> Result<T, KrbError>
+where
+    F: Future<Output = Result<T, KrbError>>,
+{
+    tokio::time::timeout(timeout, fut).await.map_err(|_| {
+        debug!(?timeout, "Timed out waiting for KDC");
+        KrbError::Timeout
+    })?
+}
+
+async fn resolve<A: ToSocketAddrs>(addr: A) -> Result<impl Iterator<Item = SocketAddr>, KrbError> {
+    lookup_host(addr).await.map_err(|err| {
+        debug!(?err, "Unable to resolve KDC address");
+        KrbError::IoError(err.kind())
+    })
+}
+
+/// Connect to the next reachable KDC of `candidates`. If none are reachable, the error
+/// from the last attempt is returned, or `last_err` if there were no candidates left.
+async fn connect_next(
+    candidates: &mut VecDeque<KdcAddress>,
+    timeout: Duration,
+    mut last_err: KrbError,
+) -> Result<(SocketAddr, Transport), KrbError> {
+    while let Some(kdc) = candidates.pop_front() {
+        let transport = match kdc.transport {
+            KdcTransport::Tcp => with_timeout(timeout, connect_tcp(kdc.addr))
+                .await
+                .map(Transport::Tcp),
+            KdcTransport::Udp => bind_udp(kdc.addr).await.map(Transport::Udp),
+        };
+
+        match transport {
+            Ok(transport) => return Ok((kdc.addr, transport)),
+            Err(err) => {
+                debug!(?err, addr = %kdc.addr, "Unable to connect to KDC");
+                last_err = err;
+            }
+        }
+    }
+
+    Err(last_err)
+}
+
+async fn connect_tcp(kdc: SocketAddr) -> Result<Framed<TcpStream, KerberosTcpCodec>, KrbError> {
+    let stream = TcpStream::connect(kdc).await.map_err(|err| {
+        debug!(?err, %kdc, "Unable to connect to KDC");
+        KrbError::IoError(err.kind())
+    })?;
+
+    Ok(Framed::new(stream, KerberosTcpCodec::default()))
+}
+
+async fn bind_udp(kdc: SocketAddr) -> Result<UdpFramed<KerberosUdpCodec>, KrbError> {
+    let local_addr: SocketAddr = if kdc.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+
+    let socket = UdpSocket::bind(local_addr).await.map_err(|err| {
+        debug!(?err, "Unable to bind UDP socket");
+        KrbError::IoError(err.kind())
+    })?;
+
+    Ok(UdpFramed::new(socket, KerberosUdpCodec::default()))
+}
+
+async fn exchange_tcp(
+    stream: &mut Framed<TcpStream, KerberosTcpCodec>,
+    request: &KerberosRequest,
+) -> Result<KerberosResponse, KrbError> {
+    stream.send(request).await.map_err(|err| {
+        debug!(?err, "Failed to send request to KDC");
+        KrbError::IoError(err.kind())
+    })?;
+
+    stream
+        .next()
+        .await
+        .ok_or(KrbError::ConnectionClosed)?
+        .map_err(|err| {
+            debug!(?err, "Failed to receive response from KDC");
+            KrbError::IoError(err.kind())
+        })
+}
+
+async fn exchange_udp(
+    socket: &mut UdpFramed<KerberosUdpCodec>,
+    kdc: SocketAddr,
+    request: &KerberosRequest,
+) -> Result<KerberosResponse, KrbError> {
+    socket.send((request, kdc)).await.map_err(|err| {
+        debug!(?err, "Failed to send request to KDC");
+        KrbError::IoError(err.kind())
+    })?;
+
+    loop {
+        let (response, from) = socket
+            .next()
+            .await
+            .ok_or(KrbError::ConnectionClosed)?
+            .map_err(|err| {
+                debug!(?err, "Failed to receive response from KDC");
+                KrbError::IoError(err.kind())
+            })?;
+
+        // The socket isn't connected, so anyone can send us a datagram.
+        if from == kdc {
+            return Ok(response);
+        }
+
+        debug!(%from, "Ignoring datagram that was not from the KDC");
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::{CrossRealmPath, ExchangeOptions, KdcAddress, KdcTransport, KerberosClient};
+    use crate::asn1::{
+        enc_kdc_rep_part::{EncKdcRepPart, TaggedEncKdcRepPart},
+        encryption_key::EncryptionKey as KdcEncryptionKey,
+        kerberos_flags::KerberosFlags,
+        kerberos_string::KerberosString,
+        kerberos_time::KerberosTime,
+        principal_name::PrincipalName,
+        Ia5String,
+    };
+    use crate::constants::{AES_256_KEY_LEN, MAX_CROSS_REALM_HOPS};
+    use crate::error::KrbError;
+    use crate::proto::{
+        AuthenticatedReply, BaseKey, EncryptionType, FastArmor, FlagSet, KdcOptions,
+        KerberosRequest, KerberosResponse, KerberosTgsRep, KeyUsage, KrbErrorCode, TicketFlags,
+        ANONYMOUS_PRINCIPAL, ANONYMOUS_REALM,
+    };
+    use crate::tests::{AS_REP_SAMPLE, RESPONSE_TOO_BIG_SAMPLE};
+    use crate::KdcTcpCodec;
+    use der::Decode;
+    use futures::{SinkExt, StreamExt};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, UdpSocket};
+    use tokio_util::codec::Framed;
+
+    #[tokio::test]
+    async fn test_authenticate_krb_error() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+        let addr = listener.local_addr().expect("Unable to get local address");
+
+        let kdc = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("Failed to accept");
+
+            // Consume the AS-REQ, we don't care what it is.
+            let len = stream.read_u32().await.expect("Failed to read record mark");
+            let mut req = vec![0; len as usize];
+            stream
+                .read_exact(&mut req)
+                .await
+                .expect("Failed to read request");
+
+            let rep = hex::decode(RESPONSE_TOO_BIG_SAMPLE).expect("Failed to decode sample");
+            stream
+                .write_u32(rep.len() as u32)
+                .await
+                .expect("Failed to write record mark");
+            stream.write_all(&rep).await.expect("Failed to write reply");
+        });
+
+        let mut client = KerberosClient::connect(addr, "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect");
+
+        let err = client
+            .authenticate(
+                "testuser",
+                "krbtgt/EXAMPLE.COM",
+                "password",
+                SystemTime::now() + Duration::from_secs(3600),
+            )
+            .await
+            .expect_err("Authentication succeeded");
+
+        assert!(matches!(
+            err,
+            KrbError::KdcError(KrbErrorCode::KrbErrResponseTooBig)
+        ));
+
+        kdc.await.expect("KDC task failed");
+    }
+
+    #[tokio::test]
+    async fn test_renew_ticket_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+        let addr = listener.local_addr().expect("Unable to get local address");
+
+        let mut client = KerberosClient::connect(addr, "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect");
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let now = SystemTime::now();
+        let mut tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: now + Duration::from_secs(3600),
+            renew_till: Some(now + Duration::from_secs(86400)),
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+        let until = now + Duration::from_secs(7200);
+
+        // Neither of these may reach the KDC, which never answers.
+        let err = client
+            .renew_ticket(&tgt, until)
+            .await
+            .expect_err("Renewed a ticket that isn't renewable");
+        assert!(matches!(err, KrbError::TicketNotRenewable));
+
+        tgt.flags = FlagSet::from(TicketFlags::Renewable);
+        tgt.renew_till = Some(now - Duration::from_secs(60));
+        let err = client
+            .renew_ticket(&tgt, until)
+            .await
+            .expect_err("Renewed a ticket past its renew-till");
+        assert!(matches!(err, KrbError::RenewTillExpired));
+    }
+
+    /// Accept a single connection and answer each TGS-REQ on it in turn, with the
+    /// nonce of the request moved by the offset for that turn. The reply is
+    /// encrypted in the subkey of the authenticator if it has one, else in the
+    /// session key of the TGT.
+    async fn serve_tgs_reps(listener: TcpListener, session_key: BaseKey, nonce_offsets: Vec<u32>) {
+        let (mut stream, _) = listener.accept().await.expect("Failed to accept");
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        for nonce_offset in nonce_offsets {
+            let len = stream.read_u32().await.expect("Failed to read record mark");
+            let mut req = vec![0; len as usize];
+            stream
+                .read_exact(&mut req)
+                .await
+                .expect("Failed to read request");
+
+            let request = KerberosRequest::from_der(&req).expect("Failed to decode request");
+            let KerberosRequest::TgsReq(tgs_req) = &request else {
+                unreachable!();
+            };
+            let authenticator = tgs_req
+                .verify_authenticator(&session_key)
+                .expect("Failed to verify authenticator");
+            let (key, key_usage) = match authenticator.subkey() {
+                Some(subkey) => (subkey.clone(), KeyUsage::TGS_REP_ENC_PART_SUBKEY),
+                None => (session_key.clone(), KeyUsage::TGS_REP_ENC_PART_SESSION),
+            };
+
+            let now = KerberosTime::from_system_time(SystemTime::now()).expect("Invalid time");
+            let enc_part = TaggedEncKdcRepPart::EncTgsRepPart(EncKdcRepPart {
+                key: KdcEncryptionKey::try_from(
+                    &BaseKey::generate_session_key(EncryptionType::AES256_CTS_HMAC_SHA1_96)
+                        .expect("Failed to generate session key"),
+                )
+                .expect("Failed to encode session key"),
+                last_req: Vec::new(),
+                nonce: request.nonce().wrapping_add(nonce_offset),
+                key_expiration: None,
+                flags: KerberosFlags(FlagSet::default()),
+                auth_time: now,
+                start_time: None,
+                end_time: KerberosTime::from_system_time(request.until()).expect("Invalid time"),
+                renew_till: None,
+                server_realm: KerberosString(
+                    Ia5String::new(request.realm()).expect("Invalid realm"),
+                ),
+                server_name: PrincipalName {
+                    name_type: 2,
+                    name_string: request
+                        .service_name()
+                        .split('/')
+                        .map(|part| {
+                            Ia5String::new(part)
+                                .map(KerberosString)
+                                .expect("Invalid name")
+                        })
+                        .collect(),
+                },
+                client_addresses: None,
+                encrypted_pa_data: None,
+            })
+            .to_der()
+            .expect("Failed to encode enc-part");
+
+            let rep = KerberosResponse::TgsRep(KerberosTgsRep {
+                client_realm: as_rep.client_realm.clone(),
+                client_name: as_rep.client_name.clone(),
+                client_name_type: as_rep.client_name_type,
+                ticket: as_rep.ticket.clone(),
+                enc_part: key
+                    .encrypt_data(&enc_part, key_usage)
+                    .expect("Failed to encrypt enc-part"),
+                other_padata: Vec::new(),
+            })
+            .to_der()
+            .expect("Failed to encode TGS-REP");
+
+            stream
+                .write_u32(rep.len() as u32)
+                .await
+                .expect("Failed to write record mark");
+            stream.write_all(&rep).await.expect("Failed to write reply");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tgs_reply_nonce_mismatch() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+        let addr = listener.local_addr().expect("Unable to get local address");
+
+        let session_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x42; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+
+        // Each exchange is first answered with a reply to some other request under
+        // the same session key, and then with the reply to it.
+        let kdc = tokio::spawn(serve_tgs_reps(
+            listener,
+            session_key.clone(),
+            vec![1, 0, 1, 0, 1, 0],
+        ));
+
+        let mut client = KerberosClient::connect(addr, "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect");
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let now = SystemTime::now();
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key,
+            flags: FlagSet::from(TicketFlags::Renewable),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: now + Duration::from_secs(3600),
+            renew_till: Some(now + Duration::from_secs(86400)),
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+        let until = now + Duration::from_secs(7200);
+
+        let err = client
+            .get_service_ticket(&tgt, "host/localhost", until)
+            .await
+            .expect_err("Accepted a service ticket for another request");
+        assert!(matches!(err, KrbError::ReplyNonceMismatch));
+        client
+            .get_service_ticket(&tgt, "host/localhost", until)
+            .await
+            .expect("Failed to get service ticket");
+
+        let err = client
+            .renew_ticket(&tgt, until)
+            .await
+            .expect_err("Accepted a renewed ticket for another request");
+        assert!(matches!(err, KrbError::ReplyNonceMismatch));
+        client
+            .renew_ticket(&tgt, until)
+            .await
+            .expect("Failed to renew ticket");
+
+        let err = client
+            .validate_ticket(&tgt)
+            .await
+            .expect_err("Accepted a validated ticket for another request");
+        assert!(matches!(err, KrbError::ReplyNonceMismatch));
+        client
+            .validate_ticket(&tgt)
+            .await
+            .expect("Failed to validate ticket");
+
+        kdc.await.expect("KDC task failed");
+    }
+
+    #[test]
+    fn test_cross_realm_path() {
+        let mut path = CrossRealmPath::new("A.EXAMPLE.COM");
+        assert!(path.visit("B.EXAMPLE.COM").is_ok());
+        assert!(path.visit("C.EXAMPLE.COM").is_ok());
+
+        // A KDC that refers us back to a realm we've already been through.
+        assert!(matches!(
+            path.visit("A.EXAMPLE.COM"),
+            Err(KrbError::CrossRealmLoop(realm)) if realm == "A.EXAMPLE.COM"
+        ));
+
+        let mut path = CrossRealmPath::new("REALM0");
+        for hop in 1..=MAX_CROSS_REALM_HOPS {
+            assert!(path.visit(&format!("REALM{}", hop)).is_ok());
+        }
+        assert!(matches!(
+            path.visit("ONE.TOO.MANY"),
+            Err(KrbError::CrossRealmHopLimit)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_udp_response_too_big_fallback() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        // The KDC listens on the same port for both UDP and TCP.
+        let udp_kdc = UdpSocket::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind UDP socket");
+        let addr = udp_kdc.local_addr().expect("Unable to get local address");
+        let tcp_kdc = TcpListener::bind(addr)
+            .await
+            .expect("Unable to bind listener");
+
+        let kdc = tokio::spawn(async move {
+            // Every UDP reply is too big.
+            let mut buf = vec![0; 4096];
+            let (len, client_addr) = udp_kdc
+                .recv_from(&mut buf)
+                .await
+                .expect("Failed to receive request");
+            let KerberosRequest::AsReq(udp_as_req) =
+                KerberosRequest::from_der(&buf[..len]).expect("Failed to decode request")
+            else {
+                unreachable!();
+            };
+
+            let too_big = hex::decode(RESPONSE_TOO_BIG_SAMPLE).expect("Failed to decode sample");
+            udp_kdc
+                .send_to(&too_big, client_addr)
+                .await
+                .expect("Failed to send reply");
+
+            // So the same request must now arrive over TCP.
+            let (stream, _) = tcp_kdc.accept().await.expect("Failed to accept");
+            let mut kdc_stream = Framed::new(stream, KdcTcpCodec::default());
+
+            let KerberosRequest::AsReq(tcp_as_req) = kdc_stream
+                .next()
+                .await
+                .expect("Stream closed")
+                .expect("Failed to decode request")
+            else {
+                unreachable!();
+            };
+            assert_eq!(udp_as_req.nonce, tcp_as_req.nonce);
+            assert_eq!(udp_as_req.client_name, tcp_as_req.client_name);
+
+            let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+            let response = KerberosResponse::from_der(&as_rep).expect("Failed to decode sample");
+            kdc_stream
+                .send(response)
+                .await
+                .expect("Failed to send reply");
+        });
+
+        let mut client = KerberosClient::connect_udp(addr, "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect");
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build()
+        .expect("Failed to build request");
+
+        let response = client.exchange(as_req).await.expect("Exchange failed");
+        assert!(matches!(response, KerberosResponse::AsRep(_)));
+
+        kdc.await.expect("KDC task failed");
+    }
+
+    /// Accept a single connection, consume the request and reply with `rep`.
+    async fn serve_once(listener: TcpListener, rep: &str) {
+        let (mut stream, _) = listener.accept().await.expect("Failed to accept");
+
+        let len = stream.read_u32().await.expect("Failed to read record mark");
+        let mut req = vec![0; len as usize];
+        stream
+            .read_exact(&mut req)
+            .await
+            .expect("Failed to read request");
+
+        let rep = hex::decode(rep).expect("Failed to decode sample");
+        stream
+            .write_u32(rep.len() as u32)
+            .await
+            .expect("Failed to write record mark");
+        stream.write_all(&rep).await.expect("Failed to write reply");
+    }
+
+    #[tokio::test]
+    async fn test_failover_unreachable_kdc() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        // Nothing is listening here once the listener is dropped.
+        let unreachable = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener")
+            .local_addr()
+            .expect("Unable to get local address");
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+        let reachable = listener.local_addr().expect("Unable to get local address");
+        let kdc = tokio::spawn(serve_once(listener, RESPONSE_TOO_BIG_SAMPLE));
+
+        let unused = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+
+        let kdcs = [
+            unreachable,
+            reachable,
+            unused.local_addr().expect("No address"),
+        ]
+        .into_iter()
+        .map(|addr| KdcAddress::new(KdcTransport::Tcp, addr))
+        .collect();
+
+        let mut client = KerberosClient::connect_kdcs(
+            kdcs,
+            "EXAMPLE.COM",
+            ExchangeOptions::default().retries(0, Duration::ZERO),
+        )
+        .await
+        .expect("Unable to connect");
+        assert_eq!(client.kdc(), reachable);
+
+        let err = client
+            .authenticate(
+                "testuser",
+                "krbtgt/EXAMPLE.COM",
+                "password",
+                SystemTime::now() + Duration::from_secs(3600),
+            )
+            .await
+            .expect_err("Authentication succeeded");
+
+        // The KDC answered, so the error is returned rather than failing over.
+        assert!(matches!(
+            err,
+            KrbError::KdcError(KrbErrorCode::KrbErrResponseTooBig)
+        ));
+        assert_eq!(client.kdc(), reachable);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), unused.accept())
+                .await
+                .is_err()
+        );
+
+        kdc.await.expect("KDC task failed");
+    }
+
+    #[tokio::test]
+    async fn test_failover_connection_closed() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let first = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+        let first_addr = first.local_addr().expect("Unable to get local address");
+
+        // The first KDC hangs up as soon as the request arrives.
+        let first_kdc = tokio::spawn(async move {
+            let (mut stream, _) = first.accept().await.expect("Failed to accept");
+            let _ = stream.read_u32().await;
+        });
+
+        let second = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+        let second_addr = second.local_addr().expect("Unable to get local address");
+        let second_kdc = tokio::spawn(serve_once(second, AS_REP_SAMPLE));
+
+        let kdcs = vec![
+            KdcAddress::new(KdcTransport::Tcp, first_addr),
+            KdcAddress::new(KdcTransport::Tcp, second_addr),
+        ];
+
+        let mut client = KerberosClient::connect_kdcs(
+            kdcs,
+            "EXAMPLE.COM",
+            ExchangeOptions::default().retries(0, Duration::ZERO),
+        )
+        .await
+        .expect("Unable to connect");
+        assert_eq!(client.kdc(), first_addr);
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build()
+        .expect("Failed to build request");
+
+        let response = client.exchange(as_req).await.expect("Exchange failed");
+        assert!(matches!(response, KerberosResponse::AsRep(_)));
+        assert_eq!(client.kdc(), second_addr);
+
+        first_kdc.await.expect("KDC task failed");
+        second_kdc.await.expect("KDC task failed");
+    }
+
+    #[tokio::test]
+    async fn test_exchange_timeout_retries() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        // The KDC accepts every connection but never replies.
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+        let addr = listener.local_addr().expect("Unable to get local address");
+        let kdc = tokio::spawn(async move {
+            let mut streams = Vec::with_capacity(2);
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().await.expect("Failed to accept");
+                streams.push(stream);
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            streams.len()
+        });
+
+        let options = ExchangeOptions::default()
+            .response_timeout(Duration::from_millis(100))
+            .retries(1, Duration::from_millis(10));
+
+        let kdcs = vec![KdcAddress::new(KdcTransport::Tcp, addr)];
+        let mut client = KerberosClient::connect_kdcs(kdcs, "EXAMPLE.COM", options)
+            .await
+            .expect("Unable to connect");
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build()
+        .expect("Failed to build request");
+
+        let err = client
+            .exchange(as_req)
+            .await
+            .expect_err("Exchange succeeded");
+        assert!(matches!(err, KrbError::Timeout));
+
+        // The retry is made on a fresh connection.
+        assert_eq!(kdc.await.expect("KDC task failed"), 2);
+    }
+
+    #[test]
+    fn test_exchange_options_backoff() {
+        let options = ExchangeOptions::default().retries(4, Duration::from_millis(100));
+
+        assert_eq!(options.backoff_delay(0), Duration::from_millis(100));
+        assert_eq!(options.backoff_delay(1), Duration::from_millis(200));
+        assert_eq!(options.backoff_delay(3), Duration::from_millis(800));
+        // The delay saturates rather than overflowing.
+        assert_eq!(
+            options.backoff_delay(u32::MAX),
+            Duration::from_millis(100) * u32::MAX
+        );
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_authenticate() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let reply = client
+            .authenticate(
+                "testuser",
+                "krbtgt/EXAMPLE.COM",
+                "password",
+                SystemTime::now() + Duration::from_secs(3600),
+            )
+            .await
+            .expect("Failed to authenticate");
+
+        assert_eq!(reply.client_name(), "testuser");
+        assert_eq!(reply.client_realm(), "EXAMPLE.COM");
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_authenticate_preauth() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let reply = client
+            .authenticate(
+                "testuser_preauth",
+                "krbtgt/EXAMPLE.COM",
+                "password",
+                SystemTime::now() + Duration::from_secs(3600),
+            )
+            .await
+            .expect("Failed to authenticate");
+
+        assert_eq!(reply.client_name(), "testuser_preauth");
+        // The KDC returns a checksum of the request that we sent.
+        assert!(reply.request_verified());
+
+        // A wrong password must be rejected by the KDC, not fail in the client.
+        let err = client
+            .authenticate(
+                "testuser_preauth",
+                "krbtgt/EXAMPLE.COM",
+                "wrong password",
+                SystemTime::now() + Duration::from_secs(3600),
+            )
+            .await
+            .expect_err("Authenticated with the wrong password");
+
+        assert!(matches!(
+            err,
+            KrbError::KdcError(KrbErrorCode::KdcErrPreauthFailed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_authenticate_armored() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let until = SystemTime::now() + Duration::from_secs(3600);
+
+        // Any TGT will do as the armor, normally that of the host.
+        let tgt = client
+            .authenticate("testuser", "krbtgt/EXAMPLE.COM", "password", until)
+            .await
+            .expect("Failed to authenticate");
+
+        let armor = FastArmor::new(
+            &tgt,
+            BaseKey::generate_session_key(tgt.session_key().etype())
+                .expect("Failed to generate subkey"),
+        )
+        .expect("Failed to build armor");
+
+        let reply = client
+            .authenticate_armored(
+                "testuser_preauth",
+                "krbtgt/EXAMPLE.COM",
+                "password",
+                until,
+                &armor,
+            )
+            .await
+            .expect("Failed to authenticate");
+
+        assert_eq!(reply.client_name(), "testuser_preauth");
+        assert_eq!(reply.client_realm(), "EXAMPLE.COM");
+    }
+
+    #[cfg(feature = "pkinit")]
+    #[tokio::test]
+    async fn test_localhost_kdc_authenticate_pkinit() {
+        use crate::pkinit::{KdcCertValidator, PkinitSigner};
+        use cms::signed_data::SignedData;
+        use rsa::pkcs1v15::SigningKey;
+        use rsa::pkcs8::DecodePrivateKey;
+        use rsa::sha2::Sha256;
+        use rsa::signature::{SignatureEncoding, Signer};
+        use rsa::RsaPrivateKey;
+        use x509_cert::der::Decode as _;
+        use x509_cert::spki::AlgorithmIdentifierOwned;
+        use x509_cert::Certificate;
+
+        // A software key standing in for a smartcard.
+        struct RsaSigner {
+            certificate: Certificate,
+            signing_key: SigningKey<Sha256>,
+        }
+
+        impl PkinitSigner for RsaSigner {
+            fn certificate(&self) -> &Certificate {
+                &self.certificate
+            }
+
+            fn signature_algorithm(&self) -> AlgorithmIdentifierOwned {
+                AlgorithmIdentifierOwned {
+                    // sha256WithRSAEncryption
+                    oid: "1.2.840.113549.1.1.11".parse().expect("Invalid oid"),
+                    parameters: None,
+                }
+            }
+
+            fn sign(&self, data: &[u8]) -> Result<Vec<u8>, KrbError> {
+                Ok(self.signing_key.sign(data).to_vec())
+            }
+        }
+
+        // The KDC of the test realm is trusted as long as it signed the reply.
+        struct TestRealmValidator;
+
+        impl KdcCertValidator for TestRealmValidator {
+            fn validate(&self, signed_data: &SignedData) -> Result<(), KrbError> {
+                if signed_data.signer_infos.0.is_empty() {
+                    return Err(KrbError::PkinitKdcCertInvalid(
+                        "The reply is not signed".to_string(),
+                    ));
+                }
+                Ok(())
+            }
+        }
+
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let signer = RsaSigner {
+            certificate: Certificate::from_der(include_bytes!(
+                "../kdc_test/pkinit/testuser_pkinit.der"
+            ))
+            .expect("Failed to decode certificate"),
+            signing_key: RsaPrivateKey::from_pkcs8_der(include_bytes!(
+                "../kdc_test/pkinit/testuser_pkinit.key.der"
+            ))
+            .map(SigningKey::new)
+            .expect("Failed to decode private key"),
+        };
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let reply = client
+            .authenticate_pkinit(
+                "testuser_pkinit",
+                "krbtgt/EXAMPLE.COM",
+                SystemTime::now() + Duration::from_secs(3600),
+                signer,
+                TestRealmValidator,
+            )
+            .await
+            .expect("Failed to authenticate");
+
+        assert_eq!(reply.client_name(), "testuser_pkinit");
+        assert_eq!(reply.client_realm(), "EXAMPLE.COM");
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_principal_unknown() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let as_req = KerberosRequest::build_asreq(
+            "nosuchuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build()
+        .expect("Failed to build request");
+
+        let response = client.exchange(as_req).await.expect("Exchange failed");
+
+        let KerberosResponse::Err(err_rep) = response else {
+            unreachable!();
+        };
+        assert_eq!(err_rep.error_code(), KrbErrorCode::KdcErrCPrincipalUnknown);
+        assert_eq!(err_rep.service_realm(), "EXAMPLE.COM");
+        assert_eq!(err_rep.service_name(), "krbtgt/EXAMPLE.COM");
+
+        // The same error surfaces through authenticate.
+        let err = client
+            .authenticate(
+                "nosuchuser",
+                "krbtgt/EXAMPLE.COM",
+                "password",
+                SystemTime::now() + Duration::from_secs(3600),
+            )
+            .await
+            .expect_err("Authenticated a nonexistent principal");
+
+        assert!(matches!(
+            err,
+            KrbError::KdcError(KrbErrorCode::KdcErrCPrincipalUnknown)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_service_ticket() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let until = SystemTime::now() + Duration::from_secs(3600);
+
+        let tgt = client
+            .authenticate("testuser", "krbtgt/EXAMPLE.COM", "password", until)
+            .await
+            .expect("Failed to authenticate");
+
+        let service_ticket = client
+            .get_service_ticket(&tgt, "host/localhost", until)
+            .await
+            .expect("Failed to get service ticket");
+
+        assert_eq!(service_ticket.client_name(), "testuser");
+        assert_eq!(service_ticket.client_realm(), "EXAMPLE.COM");
+        let sname: String = service_ticket.ticket.0.sname.clone().into();
+        assert_eq!(sname, "host/localhost");
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_tgs_subkey() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let until = SystemTime::now() + Duration::from_secs(3600);
+
+        let tgt = client
+            .authenticate("testuser", "krbtgt/EXAMPLE.COM", "password", until)
+            .await
+            .expect("Failed to authenticate");
+
+        // Without a subkey the reply is in the session key of the TGT.
+        let tgs_req = KerberosRequest::build_tgsreq("host/localhost", until)
+            .build(&tgt)
+            .expect("Failed to build request");
+        let KerberosResponse::TgsRep(tgs_rep) =
+            client.exchange(tgs_req).await.expect("Exchange failed")
+        else {
+            unreachable!();
+        };
+        tgs_rep
+            .into_authenticated_reply(tgt.session_key())
+            .expect("Failed to decrypt reply");
+
+        // With a subkey the reply is in the subkey, with key usage 9.
+        let subkey = BaseKey::generate_session_key(tgt.session_key().etype())
+            .expect("Failed to generate subkey");
+        let tgs_req = KerberosRequest::build_tgsreq("host/localhost", until)
+            .subkey(Some(subkey.clone()))
+            .build(&tgt)
+            .expect("Failed to build request");
+        let KerberosResponse::TgsRep(tgs_rep) =
+            client.exchange(tgs_req).await.expect("Exchange failed")
+        else {
+            unreachable!();
+        };
+        assert!(tgs_rep.decrypt_enc_part(tgt.session_key()).is_err());
+        let service_ticket = tgs_rep
+            .into_authenticated_reply_with_subkey(&subkey)
+            .expect("Failed to decrypt reply");
+
+        assert_eq!(service_ticket.client_name(), "testuser");
+        let sname: String = service_ticket.ticket.0.sname.clone().into();
+        assert_eq!(sname, "host/localhost");
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_s4u2self() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let until = SystemTime::now() + Duration::from_secs(3600);
+
+        let tgt = client
+            .authenticate("testuser", "krbtgt/EXAMPLE.COM", "password", until)
+            .await
+            .expect("Failed to authenticate");
+
+        // testuser asks for a ticket to itself on behalf of testuser_preauth.
+        let tgs_req = KerberosRequest::build_tgsreq("testuser", until)
+            .s4u2self("testuser_preauth".to_string(), "EXAMPLE.COM".to_string())
+            .build(&tgt)
+            .expect("Failed to build request");
+
+        let KerberosResponse::TgsRep(tgs_rep) =
+            client.exchange(tgs_req).await.expect("Exchange failed")
+        else {
+            unreachable!();
+        };
+
+        let service_ticket = tgs_rep
+            .into_authenticated_reply(tgt.session_key())
+            .expect("Failed to decrypt reply");
+
+        assert_eq!(service_ticket.client_name(), "testuser_preauth");
+        assert_eq!(service_ticket.client_realm(), "EXAMPLE.COM");
+        let sname: String = service_ticket.ticket.0.sname.clone().into();
+        assert_eq!(sname, "testuser");
+    }
+
+    // Constrained delegation needs an Active Directory KDC such as Samba, with a
+    // service account that may delegate to the target service. This only runs when
+    // LIBKRIMES_S4U_KDC is set to the address of the KDC, and the remaining
+    // LIBKRIMES_S4U_ variables describe the realm, service account and target.
+    #[tokio::test]
+    async fn test_samba_kdc_s4u2proxy() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let Ok(kdc) = std::env::var("LIBKRIMES_S4U_KDC") else {
+            return;
+        };
+        let var = |name: &str| std::env::var(name).expect("Missing LIBKRIMES_S4U_ variable");
+        let realm = var("LIBKRIMES_S4U_REALM");
+        let service = var("LIBKRIMES_S4U_SERVICE");
+        let password = var("LIBKRIMES_S4U_PASSWORD");
+        let user = var("LIBKRIMES_S4U_USER");
+        let target = var("LIBKRIMES_S4U_TARGET");
+
+        let mut client = KerberosClient::connect(&kdc, &realm)
+            .await
+            .expect("Unable to connect to the KDC");
+
+        let until = SystemTime::now() + Duration::from_secs(3600);
+
+        let tgt = client
+            .authenticate(&service, &format!("krbtgt/{}", realm), &password, until)
+            .await
+            .expect("Failed to authenticate");
+
+        // S4U2Self provides the evidence of the user to S4U2Proxy.
+        let tgs_req = KerberosRequest::build_tgsreq(service.clone(), until)
+            .kdc_options(KdcOptions::Forwardable.into())
+            .s4u2self(user.clone(), realm.clone())
+            .build(&tgt)
+            .expect("Failed to build request");
+
+        let KerberosResponse::TgsRep(tgs_rep) =
+            client.exchange(tgs_req).await.expect("Exchange failed")
+        else {
+            unreachable!();
+        };
+        let evidence = tgs_rep
+            .into_authenticated_reply(tgt.session_key())
+            .expect("Failed to decrypt reply");
+        assert_eq!(evidence.client_name(), user);
+
+        let tgs_req = KerberosRequest::build_tgsreq(service, until)
+            .kdc_options(KdcOptions::Forwardable.into())
+            .delegate(evidence.ticket(), target.clone())
+            .build(&tgt)
+            .expect("Failed to build request");
+
+        let KerberosResponse::TgsRep(tgs_rep) =
+            client.exchange(tgs_req).await.expect("Exchange failed")
+        else {
+            unreachable!();
+        };
+        let service_ticket = tgs_rep
+            .into_authenticated_reply(tgt.session_key())
+            .expect("Failed to decrypt reply");
+
+        assert_eq!(service_ticket.client_name(), user);
+        assert_eq!(service_ticket.ticket().service_name(), target);
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_ticket_flags() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let until = SystemTime::now() + Duration::from_secs(3600);
+        let renew = until + Duration::from_secs(86400);
+
+        let as_req =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .renew(Some(renew))
+                .forwardable(true)
+                .proxiable(true)
+                .renewable(true)
+                .build()
+                .expect("Failed to build request");
+
+        let KerberosResponse::AsRep(as_rep) =
+            client.exchange(as_req).await.expect("Exchange failed")
+        else {
+            unreachable!();
+        };
+
+        let base_key = as_rep
+            .enc_part
+            .derive_key(b"password", b"EXAMPLE.COM", b"testuser")
+            .expect("Failed to derive key");
+        let reply = as_rep
+            .into_authenticated_reply(&base_key)
+            .expect("Failed to decrypt reply");
+
+        let flags = reply.ticket_flags();
+        assert!(flags.contains(TicketFlags::Forwardable));
+        assert!(flags.contains(TicketFlags::Proxiable));
+        assert!(flags.contains(TicketFlags::Renewable));
+
+        // Without the options, the KDC must not grant them.
+        let reply = client
+            .authenticate("testuser", "krbtgt/EXAMPLE.COM", "password", until)
+            .await
+            .expect("Failed to authenticate");
+
+        let flags = reply.ticket_flags();
+        assert!(!flags.contains(TicketFlags::Forwardable));
+        assert!(!flags.contains(TicketFlags::Proxiable));
+        assert!(!flags.contains(TicketFlags::Renewable));
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_password_expiry() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let until = SystemTime::now() + Duration::from_secs(3600);
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser_pwexpire",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            until,
+        )
+        .build()
+        .expect("Failed to build request");
+
+        let KerberosResponse::AsRep(as_rep) =
+            client.exchange(as_req).await.expect("Exchange failed")
+        else {
+            unreachable!();
+        };
+
+        let base_key = as_rep
+            .enc_part
+            .derive_key(b"password", b"EXAMPLE.COM", b"testuser_pwexpire")
+            .expect("Failed to derive key");
+        let enc_part = as_rep
+            .decrypt_enc_part(&base_key)
+            .expect("Failed to decrypt reply");
+
+        // The password of the test principal expires at the start of 2099.
+        let expiry = UNIX_EPOCH + Duration::from_secs(4_070_908_800);
+        let now = SystemTime::now();
+        let remaining = expiry
+            .duration_since(now)
+            .expect("Password already expired");
+
+        assert!(enc_part
+            .password_expiry_warning(now, Duration::from_secs(86400 * 14))
+            .is_none());
+        assert_eq!(
+            enc_part.password_expiry_warning(now, remaining + Duration::from_secs(86400)),
+            Some(remaining)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_renew_ticket() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let until = SystemTime::now() + Duration::from_secs(600);
+        let renew = until + Duration::from_secs(86400);
+
+        let as_req =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .renew(Some(renew))
+                .renewable(true)
+                .build()
+                .expect("Failed to build request");
+
+        let KerberosResponse::AsRep(as_rep) =
+            client.exchange(as_req).await.expect("Exchange failed")
+        else {
+            unreachable!();
+        };
+
+        let base_key = as_rep
+            .enc_part
+            .derive_key(b"password", b"EXAMPLE.COM", b"testuser")
+            .expect("Failed to derive key");
+        let tgt = as_rep
+            .into_authenticated_reply(&base_key)
+            .expect("Failed to decrypt reply");
+
+        let renewed = client
+            .renew_ticket(&tgt, SystemTime::now() + Duration::from_secs(3600))
+            .await
+            .expect("Failed to renew ticket");
+
+        assert_eq!(renewed.client_name(), tgt.client_name());
+        assert_eq!(renewed.ticket().service_name(), tgt.ticket().service_name());
+        assert_eq!(renewed.auth_time(), tgt.auth_time());
+        assert!(renewed.end_time() > tgt.end_time());
+        assert!(renewed.ticket_flags().contains(TicketFlags::Renewable));
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_anonymous_tgs() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let until = SystemTime::now() + Duration::from_secs(3600);
+
+        let tgt = client
+            .authenticate("testuser", "krbtgt/EXAMPLE.COM", "password", until)
+            .await
+            .expect("Failed to authenticate");
+
+        let tgs_req = KerberosRequest::build_tgsreq("host/localhost", until)
+            .anonymous(true)
+            .build(&tgt)
+            .expect("Failed to build request");
+
+        let KerberosResponse::TgsRep(tgs_rep) =
+            client.exchange(tgs_req).await.expect("Exchange failed")
+        else {
+            unreachable!();
+        };
+
+        let service_ticket = tgs_rep
+            .into_authenticated_reply(tgt.session_key())
+            .expect("Failed to decrypt reply");
+
+        // The ticket hides both the client and its realm.
+        assert_eq!(service_ticket.client_name(), ANONYMOUS_PRINCIPAL);
+        assert_eq!(service_ticket.client_realm(), ANONYMOUS_REALM);
+        assert!(service_ticket.is_anonymous());
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_validate_ticket() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let mut client = KerberosClient::connect("127.0.0.1:55000", "EXAMPLE.COM")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let from = SystemTime::now() + Duration::from_secs(2);
+        let until = from + Duration::from_secs(3600);
+
+        let as_req =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .from(Some(from))
+                .build()
+                .expect("Failed to build request");
+
+        let KerberosResponse::AsRep(as_rep) =
+            client.exchange(as_req).await.expect("Exchange failed")
+        else {
+            unreachable!();
+        };
+
+        let base_key = as_rep
+            .enc_part
+            .derive_key(b"password", b"EXAMPLE.COM", b"testuser")
+            .expect("Failed to derive key");
+        let tgt = as_rep
+            .into_authenticated_reply(&base_key)
+            .expect("Failed to decrypt reply");
+
+        // A start time in the future is enough to request a postdated ticket.
+        assert!(tgt.ticket_flags().contains(TicketFlags::Postdated));
+        assert!(tgt.requires_validation());
+        assert!(tgt.may_postdate());
+
+        // The KDC refuses to validate a ticket that hasn't started yet.
+        let err = client
+            .validate_ticket(&tgt)
+            .await
+            .expect_err("Validated a ticket before its start time");
+        assert!(matches!(
+            err,
+            KrbError::KdcError(KrbErrorCode::KrbApErrTktNyv)
+        ));
+
+        tokio::time::sleep(Duration::from_secs(3)).await;
+
+        let validated = client
+            .validate_ticket(&tgt)
+            .await
+            .expect("Failed to validate ticket");
+
+        assert!(!validated.requires_validation());
+        assert_eq!(validated.client_name(), tgt.client_name());
+    }
+}
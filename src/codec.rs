@@ -0,0 +1,789 @@
+use crate::constants::DEFAULT_IO_MAX_SIZE;
+use crate::framing::{
+    decode_record, decode_request, decode_response, encode_record, encode_request, request_to_der,
+    response_to_der, RECORD_MAX_LEN,
+};
+use crate::proto::{KerberosRequest, KerberosResponse};
+use bytes::{BufMut, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+pub struct KerberosTcpCodec {
+    max_size: usize,
+}
+
+impl KerberosTcpCodec {
+    /// Create a codec that rejects messages larger than `max_size` bytes, in either
+    /// direction. Since the TCP record mark can't describe a message larger than
+    /// 2^31 - 1 bytes, a larger `max_size` is limited to that.
+    pub fn new(max_size: usize) -> Self {
+        KerberosTcpCodec {
+            max_size: max_size.min(RECORD_MAX_LEN),
+        }
+    }
+
+    /// Change the largest message that the codec will accept, as for [KerberosTcpCodec::new].
+    pub fn with_max_size(self, max_size: usize) -> Self {
+        Self::new(max_size)
+    }
+}
+
+impl Default for KerberosTcpCodec {
+    fn default() -> Self {
+        KerberosTcpCodec {
+            max_size: DEFAULT_IO_MAX_SIZE,
+        }
+    }
+}
+
+impl Decoder for KerberosTcpCodec {
+    type Item = KerberosResponse;
+    type Error = io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(record) = decode_record(buf, self.max_size)? else {
+            return Ok(None);
+        };
+
+        decode_response(&record).map(Some)
+    }
+}
+
+impl Encoder<KerberosRequest> for KerberosTcpCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, msg: KerberosRequest, buf: &mut BytesMut) -> io::Result<()> {
+        Encoder::<&KerberosRequest>::encode(self, &msg, buf)
+    }
+}
+
+/// Encoding a borrowed request allows the identical request to be sent again,
+/// such as when retrying over a different transport.
+impl Encoder<&KerberosRequest> for KerberosTcpCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, msg: &KerberosRequest, buf: &mut BytesMut) -> io::Result<()> {
+        encode_request(msg, self.max_size, buf)
+    }
+}
+
+/// The server side of [KerberosTcpCodec] for use by a KDC. This decodes requests
+/// from clients, and encodes responses to them.
+pub struct KdcTcpCodec {
+    max_size: usize,
+}
+
+impl KdcTcpCodec {
+    /// Create a codec that rejects messages larger than `max_size` bytes, which is
+    /// limited in the same way as [KerberosTcpCodec::new].
+    pub fn new(max_size: usize) -> Self {
+        KdcTcpCodec {
+            max_size: max_size.min(RECORD_MAX_LEN),
+        }
+    }
+}
+
+impl Default for KdcTcpCodec {
+    fn default() -> Self {
+        KdcTcpCodec {
+            max_size: DEFAULT_IO_MAX_SIZE,
+        }
+    }
+}
+
+impl Decoder for KdcTcpCodec {
+    type Item = KerberosRequest;
+    type Error = io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(record) = decode_record(buf, self.max_size)? else {
+            return Ok(None);
+        };
+
+        decode_request(&record).map(Some)
+    }
+}
+
+impl Encoder<KerberosResponse> for KdcTcpCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, msg: KerberosResponse, buf: &mut BytesMut) -> io::Result<()> {
+        let der_bytes = response_to_der(&msg, self.max_size)?;
+        encode_record(&der_bytes, buf)
+    }
+}
+
+/// A codec for kerberos over UDP, suitable for use with `UdpFramed`. Each datagram
+/// contains exactly one message, so unlike TCP there is no record marking.
+///
+/// If a reply is too large for a datagram the KDC will respond with
+/// [KrbErrorCode::KrbErrResponseTooBig](crate::proto::KrbErrorCode::KrbErrResponseTooBig)
+/// and the request must be retried over TCP.
+pub struct KerberosUdpCodec {
+    max_size: usize,
+}
+
+impl Default for KerberosUdpCodec {
+    fn default() -> Self {
+        KerberosUdpCodec {
+            max_size: DEFAULT_IO_MAX_SIZE,
+        }
+    }
+}
+
+impl Decoder for KerberosUdpCodec {
+    type Item = KerberosResponse;
+    type Error = io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if buf.is_empty() {
+            return Ok(None);
+        }
+
+        // The whole datagram is the message.
+        let record = buf.split();
+
+        if record.len() > self.max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Datagram length {} exceeds maximum of {}",
+                    record.len(),
+                    self.max_size
+                ),
+            ));
+        }
+
+        decode_response(&record).map(Some)
+    }
+}
+
+impl Encoder<KerberosRequest> for KerberosUdpCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, msg: KerberosRequest, buf: &mut BytesMut) -> io::Result<()> {
+        Encoder::<&KerberosRequest>::encode(self, &msg, buf)
+    }
+}
+
+impl Encoder<&KerberosRequest> for KerberosUdpCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, msg: &KerberosRequest, buf: &mut BytesMut) -> io::Result<()> {
+        let der_bytes = request_to_der(msg, self.max_size)?;
+        buf.put_slice(&der_bytes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KerberosResponse;
+    use bytes::{BufMut, BytesMut};
+    use futures::SinkExt;
+    use std::io;
+    use tokio::net::{TcpListener, TcpStream, UdpSocket};
+    use tokio_util::codec::{Decoder, Encoder, Framed};
+    use tokio_util::udp::UdpFramed;
+
+    use std::time::{Duration, SystemTime};
+
+    use super::{KdcTcpCodec, KerberosTcpCodec, KerberosUdpCodec};
+    use crate::asn1::constants::errors::KrbErrorCode;
+    use crate::asn1::constants::PaDataType;
+    use crate::asn1::krb_kdc_req::KrbKdcReq;
+    use crate::asn1::OctetString;
+    use crate::proto::{KerberosRequest, KeyUsage, PreAuth, PreAuthEntry};
+    use crate::tests::{AS_REP_SAMPLE, RESPONSE_TOO_BIG_SAMPLE};
+    use der::{Decode, Encode};
+    use futures::StreamExt;
+    use tracing::trace;
+
+    fn frame(payload: &[u8]) -> BytesMut {
+        let mut buf = BytesMut::with_capacity(4 + payload.len());
+        buf.put_u32(payload.len() as u32);
+        buf.put_slice(payload);
+        buf
+    }
+
+    #[test]
+    fn test_decode_corrupted_der() {
+        let mut codec = KerberosTcpCodec::default();
+
+        // An AS-REP tag that claims far more data than is present.
+        let mut buf = frame(&[0x6b, 0x82, 0x03, 0x51, 0x30, 0x82, 0x03]);
+        let err = codec
+            .decode(&mut buf)
+            .expect_err("Corrupt DER was accepted");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Garbage that isn't DER at all.
+        let mut buf = frame(&[0xff, 0xff, 0xff, 0xff]);
+        let err = codec.decode(&mut buf).expect_err("Garbage was accepted");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decode_empty_record() {
+        let mut codec = KerberosTcpCodec::default();
+
+        let mut buf = frame(&[]);
+        let err = codec
+            .decode(&mut buf)
+            .expect_err("Empty record was accepted");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decode_unexpected_message_type() {
+        let mut codec = KerberosTcpCodec::default();
+
+        // SEQUENCE { INTEGER 5 } is valid DER, but not a kerberos reply.
+        let mut buf = frame(&[0x30, 0x03, 0x02, 0x01, 0x05]);
+        let err = codec
+            .decode(&mut buf)
+            .expect_err("Non-kerberos message was accepted");
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn test_decode_incomplete_frames() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let complete = frame(&as_rep);
+
+        for offset in 0..complete.len() {
+            let mut codec = KerberosTcpCodec::default();
+            let mut buf = BytesMut::from(&complete[..offset]);
+
+            // Nothing must be consumed until the frame is complete.
+            let response = codec.decode(&mut buf).expect("Failed to decode");
+            assert!(response.is_none());
+            assert_eq!(buf.len(), offset);
+
+            buf.extend_from_slice(&complete[offset..]);
+
+            let response = codec.decode(&mut buf).expect("Failed to decode");
+            assert!(matches!(response, Some(KerberosResponse::AsRep(_))));
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_decode_oversized_record() {
+        let mut codec = KerberosTcpCodec::default();
+
+        // A record mark claiming 2^31 - 1 bytes follow.
+        let mut buf = BytesMut::new();
+        buf.put_u32(0x7fff_ffff);
+        buf.put_slice(&[0x6b, 0x82]);
+
+        let err = codec
+            .decode(&mut buf)
+            .expect_err("Oversized record was accepted");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // We must not have tried to make space for the claimed record.
+        assert!(buf.capacity() < 1024 * 1024);
+
+        let mut codec = KdcTcpCodec::default();
+        let mut buf = BytesMut::new();
+        buf.put_u32(0x7fff_ffff);
+
+        let err = codec
+            .decode(&mut buf)
+            .expect_err("Oversized record was accepted");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    /// An AS-REP with a ticket that is padded out to `ticket_len` bytes of ciphertext,
+    /// like an AD ticket carrying a large PAC.
+    fn large_as_rep(ticket_len: usize) -> KerberosResponse {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(mut as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode sample")
+        else {
+            unreachable!();
+        };
+
+        as_rep.ticket.0.enc_part.cipher =
+            OctetString::new(vec![0xaa; ticket_len]).expect("Failed to build ciphertext");
+        KerberosResponse::AsRep(as_rep)
+    }
+
+    #[test]
+    fn test_decode_max_size_too_small() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let mut buf = BytesMut::new();
+        buf.put_u32(as_rep.len() as u32);
+        buf.put_slice(&as_rep);
+
+        let mut codec = KerberosTcpCodec::new(as_rep.len() - 1);
+        let err = codec
+            .decode(&mut buf.clone())
+            .expect_err("Oversized record was accepted");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // The limit is inclusive.
+        let mut codec = KerberosTcpCodec::default().with_max_size(as_rep.len());
+        let response = codec
+            .decode(&mut buf)
+            .expect("Failed to decode")
+            .expect("Incomplete record");
+        assert!(matches!(response, KerberosResponse::AsRep(_)));
+    }
+
+    #[test]
+    fn test_decode_max_size_enlarged() {
+        let max_size = 128 * 1024;
+
+        let mut buf = BytesMut::new();
+        KdcTcpCodec::new(max_size)
+            .encode(large_as_rep(96 * 1024), &mut buf)
+            .expect("Failed to encode");
+        assert!(buf.len() > 96 * 1024);
+
+        let err = KerberosTcpCodec::default()
+            .decode(&mut buf.clone())
+            .expect_err("Oversized record was accepted");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let response = KerberosTcpCodec::new(max_size)
+            .decode(&mut buf)
+            .expect("Failed to decode")
+            .expect("Incomplete record");
+        let KerberosResponse::AsRep(as_rep) = response else {
+            unreachable!();
+        };
+        assert_eq!(as_rep.ticket.0.enc_part.cipher.as_bytes().len(), 96 * 1024);
+    }
+
+    #[test]
+    fn test_max_size_limited_by_record_mark() {
+        let codec = KerberosTcpCodec::new(usize::MAX);
+        assert_eq!(codec.max_size, 0x7fff_ffff);
+
+        let codec = KdcTcpCodec::new(usize::MAX);
+        assert_eq!(codec.max_size, 0x7fff_ffff);
+    }
+
+    #[test]
+    fn test_decode_reserved_bit() {
+        let mut codec = KerberosTcpCodec::default();
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let mut buf = BytesMut::new();
+        buf.put_u32(as_rep.len() as u32 | 0x8000_0000);
+        buf.put_slice(&as_rep);
+
+        let err = codec
+            .decode(&mut buf)
+            .expect_err("Record with reserved bit was accepted");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decode_multi_fragment_record() {
+        let mut codec = KerberosTcpCodec::default();
+
+        // Split the AS-REP over two RPC record marking fragments, where only
+        // the last fragment has the high bit set.
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let (first, last) = as_rep.split_at(128);
+
+        let mut buf = BytesMut::new();
+        buf.put_u32(first.len() as u32);
+        buf.put_slice(first);
+        buf.put_u32(last.len() as u32 | 0x8000_0000);
+        buf.put_slice(last);
+
+        let err = codec
+            .decode(&mut buf)
+            .expect_err("Multi-fragment record was accepted");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("multi-fragment"));
+    }
+
+    #[test]
+    fn test_encode_record_mark() {
+        let mut codec = KdcTcpCodec::default();
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let response = KerberosResponse::from_der(&as_rep).expect("Failed to decode sample");
+        let der_bytes = response.to_der().expect("Failed to encode response");
+
+        let mut buf = BytesMut::new();
+        codec
+            .encode(response, &mut buf)
+            .expect("Failed to encode response");
+
+        // A plain big endian length with the high bit clear, then the message.
+        assert_eq!(buf[..4], (der_bytes.len() as u32).to_be_bytes());
+        assert_eq!(buf[4..], der_bytes[..]);
+
+        // And our own decoder must round trip it.
+        let mut codec = KerberosTcpCodec::default();
+        let decoded = codec
+            .decode(&mut buf)
+            .expect("Failed to decode record")
+            .expect("Record was incomplete");
+        assert!(matches!(decoded, KerberosResponse::AsRep(_)));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_encode_request_retry() {
+        let mut preauth = PreAuth::default();
+        preauth.push(PreAuthEntry::FxCookie(b"cookie".to_vec()));
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .add_preauthentication(preauth)
+        .build()
+        .expect("Failed to build request");
+
+        // A reply of RESPONSE_TOO_BIG over UDP is retried over TCP with the same
+        // request, so that the reply still matches its nonce.
+        let mut udp_buf = BytesMut::new();
+        KerberosUdpCodec::default()
+            .encode(&as_req, &mut udp_buf)
+            .expect("Failed to encode request");
+
+        let mut tcp_buf = BytesMut::new();
+        KerberosTcpCodec::default()
+            .encode(as_req.clone(), &mut tcp_buf)
+            .expect("Failed to encode request");
+
+        assert_eq!(tcp_buf[4..], udp_buf[..]);
+
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&udp_buf).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(kdc_req.req_body.nonce, as_req.nonce());
+        assert_eq!(
+            kdc_req
+                .padata
+                .unwrap_or_default()
+                .iter()
+                .map(|padata| padata.padata_type)
+                .collect::<Vec<_>>(),
+            vec![PaDataType::PaFxCookie as u32]
+        );
+    }
+
+    #[test]
+    fn test_encode_oversized_message() {
+        let mut codec = KerberosTcpCodec { max_size: 16 };
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build()
+        .expect("Failed to build request");
+
+        let mut buf = BytesMut::new();
+        let err = codec
+            .encode(as_req, &mut buf)
+            .expect_err("Oversized message was encoded");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+
+        let mut codec = KdcTcpCodec { max_size: 16 };
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let response = KerberosResponse::from_der(&as_rep).expect("Failed to decode sample");
+
+        let err = codec
+            .encode(response, &mut buf)
+            .expect_err("Oversized message was encoded");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_udp_loopback() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let server = UdpSocket::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind server socket");
+        let server_addr = server.local_addr().expect("Unable to get server address");
+
+        let client = UdpSocket::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind client socket");
+        let mut krb_stream = UdpFramed::new(client, KerberosUdpCodec::default());
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build()
+        .expect("Failed to build request");
+
+        krb_stream
+            .send((as_req, server_addr))
+            .await
+            .expect("Failed to transmit request");
+
+        // The datagram is the bare DER of the request.
+        let mut buf = [0u8; 4096];
+        let (len, client_addr) = server
+            .recv_from(&mut buf)
+            .await
+            .expect("Failed to receive request");
+        let request = KrbKdcReq::from_der(&buf[..len]).expect("Failed to decode request");
+        assert!(matches!(request, KrbKdcReq::AsReq(_)));
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        server
+            .send_to(&as_rep, client_addr)
+            .await
+            .expect("Failed to send reply");
+
+        let (response, _) = krb_stream
+            .next()
+            .await
+            .expect("No response")
+            .expect("Failed to decode response");
+        assert!(matches!(response, KerberosResponse::AsRep(_)));
+
+        // When the reply is too large, the KDC tells us to retry via TCP.
+        let too_big = hex::decode(RESPONSE_TOO_BIG_SAMPLE).expect("Failed to decode sample");
+        server
+            .send_to(&too_big, client_addr)
+            .await
+            .expect("Failed to send reply");
+
+        let (response, _) = krb_stream
+            .next()
+            .await
+            .expect("No response")
+            .expect("Failed to decode response");
+        assert_eq!(
+            response.error_code(),
+            Some(KrbErrorCode::KrbErrResponseTooBig)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kdc_codec_loopback() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+        let kdc_addr = listener.local_addr().expect("Unable to get kdc address");
+
+        let kdc = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.expect("Failed to accept");
+            let mut kdc_stream = Framed::new(stream, KdcTcpCodec::default());
+
+            let request = kdc_stream
+                .next()
+                .await
+                .expect("No request")
+                .expect("Failed to decode request");
+
+            let KerberosRequest::AsReq(as_req) = request else {
+                unreachable!();
+            };
+            assert_eq!(as_req.client_name, "testuser");
+
+            let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+            let response = KerberosResponse::from_der(&as_rep).expect("Failed to decode sample");
+
+            kdc_stream
+                .send(response)
+                .await
+                .expect("Failed to transmit response");
+        });
+
+        let stream = TcpStream::connect(kdc_addr)
+            .await
+            .expect("Unable to connect to kdc");
+        let mut krb_stream = Framed::new(stream, KerberosTcpCodec::default());
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build()
+        .expect("Failed to build request");
+
+        krb_stream
+            .send(as_req)
+            .await
+            .expect("Failed to transmit request");
+
+        let response = krb_stream
+            .next()
+            .await
+            .expect("No response")
+            .expect("Failed to decode response");
+
+        let asrep = match response {
+            KerberosResponse::AsRep(asrep) => asrep,
+            _ => unreachable!(),
+        };
+        assert_eq!(asrep.client_name, "testuser");
+        assert_eq!(asrep.client_realm, "EXAMPLE.COM");
+
+        kdc.await.expect("KDC task failed");
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let stream = TcpStream::connect("127.0.0.1:55000")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let mut krb_stream = Framed::new(stream, KerberosTcpCodec::default());
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build()
+        .expect("Failed to build request");
+
+        // Write a request
+        krb_stream
+            .send(as_req)
+            .await
+            .expect("Failed to transmit request");
+
+        let response = krb_stream.next().await;
+
+        trace!(?response);
+        assert!(response.is_some());
+        let response = response.unwrap();
+        assert!(response.is_ok());
+        let response = response.unwrap();
+        let asrep = match response {
+            KerberosResponse::AsRep(asrep) => asrep,
+            _ => unreachable!(),
+        };
+
+        let base_key = asrep
+            .enc_part
+            .derive_key(b"password", b"EXAMPLE.COM", b"testuser")
+            .unwrap();
+
+        // RFC 4120 The key usage value for encrypting this field is 3 in an AS-REP
+        // message, using the client's long-term key or another key selected
+        // via pre-authentication mechanisms.
+        let cleartext = asrep
+            .enc_part
+            .decrypt_data(&base_key, KeyUsage::AS_REP_ENC_PART)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_localhost_kdc_preauth() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let stream = TcpStream::connect("127.0.0.1:55000")
+            .await
+            .expect("Unable to connect to localhost:55000");
+
+        let mut krb_stream = Framed::new(stream, KerberosTcpCodec::default());
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser_preauth",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build()
+        .expect("Failed to build request");
+
+        // Write a request
+        krb_stream
+            .send(as_req)
+            .await
+            .expect("Failed to transmit request");
+
+        let response = krb_stream.next().await;
+
+        trace!(?response);
+        assert!(response.is_some());
+        let response = response.unwrap();
+        assert!(response.is_ok());
+        let response = response.unwrap();
+        let pa_rep = match response {
+            KerberosResponse::PaRep(pa_rep) => pa_rep,
+            _ => unreachable!(),
+        };
+
+        // The PA-ENC-TIMESTAMP method MUST be supported by
+        // clients, but whether it is enabled by default MAY be determined on
+        // a realm-by-realm basis.
+        // If the method is not used in the initial request and the error
+        // KDC_ERR_PREAUTH_REQUIRED is returned specifying PA-ENC-TIMESTAMP
+        // as an acceptable method, the client SHOULD retry the initial
+        // request using the PA-ENC-TIMESTAMP pre- authentication method.
+        //
+        // The ETYPE-INFO2 method MUST be supported; this method is used to
+        // communicate the set of supported encryption types, and
+        // corresponding salt and string to key parameters.
+
+        // Assert returned preauth data contains PA-ENC-TIMESTAMP and PA-ETYPE-INFO2
+        assert!(pa_rep.enc_timestamp);
+
+        // Assert returned preauth data contains PA-ETYPE-INFO2
+        assert!(!pa_rep.etype_info2.is_empty());
+
+        // The KDC names the client, which the default salt is built from.
+        assert_eq!(pa_rep.client_name(), Some("testuser_preauth"));
+        assert_eq!(pa_rep.client_realm(), Some("EXAMPLE.COM"));
+
+        // Compute the pre-authentication.
+        let now = SystemTime::now();
+        let password = "password";
+        let seconds_since_epoch = now.duration_since(SystemTime::UNIX_EPOCH).unwrap();
+
+        let pre_auth = pa_rep
+            .perform_enc_timestamp(
+                password,
+                "EXAMPLE.COM",
+                "testuser_preauth",
+                seconds_since_epoch,
+            )
+            .unwrap();
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser_preauth",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            now + Duration::from_secs(3600),
+        )
+        .add_preauthentication(pre_auth)
+        .build()
+        .expect("Failed to build request");
+
+        // Write a request
+        krb_stream
+            .send(as_req)
+            .await
+            .expect("Failed to transmit request");
+
+        let response = krb_stream.next().await;
+
+        trace!(?response);
+    }
+}
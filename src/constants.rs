@@ -1,24 +1,83 @@
+#[cfg(feature = "tokio-codec")]
+use std::time::Duration;
+
+#[cfg(any(feature = "tokio-codec", feature = "blocking", feature = "kkdcp"))]
 pub const DEFAULT_IO_MAX_SIZE: usize = 32 * 1024;
 
+#[cfg(feature = "tokio-codec")]
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+#[cfg(feature = "tokio-codec")]
+pub const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+#[cfg(feature = "tokio-codec")]
+pub const DEFAULT_RETRIES: u32 = 2;
+#[cfg(feature = "tokio-codec")]
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+#[cfg(feature = "tokio-codec")]
+pub const MAX_CROSS_REALM_HOPS: usize = 8;
+
+#[cfg(feature = "dns")]
+pub const DEFAULT_KDC_PORT: u16 = 88;
+
+// The initial request, the one with preauthentication, and one more should the KDC
+// continue the conversation with a new cookie.
+pub const DEFAULT_AS_MAX_ROUNDS: usize = 3;
+
 pub const AES_BLOCK_SIZE: usize = 16;
+pub const AES_128_KEY_LEN: usize = 16;
 pub const AES_256_KEY_LEN: usize = 32;
 pub const SHA1_HMAC_LEN: usize = 12;
+pub const SHA256_LEN: usize = 32;
+pub const SHA256_HMAC_LEN: usize = 16;
+pub const SHA384_LEN: usize = 48;
+pub const SHA384_HMAC_LEN: usize = 24;
+pub const MD5_HMAC_LEN: usize = 16;
+pub const SHA1_LEN: usize = 20;
+pub const CAMELLIA_BLOCK_SIZE: usize = 16;
+pub const CAMELLIA_128_KEY_LEN: usize = 16;
+pub const CAMELLIA_256_KEY_LEN: usize = 32;
+pub const DES3_BLOCK_SIZE: usize = 8;
+pub const DES3_KEY_LEN: usize = 24;
+// The key of des3 without its parity bits.
+pub const DES3_SEED_LEN: usize = 21;
+// The default iteration counts of the string-to-key, when the KDC sends no s2kparams:
+// 4096 for aes-cts-hmac-sha1-96 (RFC 3962 4) and 32768 for the sha2 enctypes
+// (RFC 8009 4) and camellia (RFC 6803 3).
 pub const PKBDF2_SHA1_ITER: u32 = 0x1000;
+pub const PKBDF2_SHA256_ITER: u32 = 0x8000;
+pub const PKBDF2_SHA384_ITER: u32 = 0x8000;
+pub const PKBDF2_CAMELLIA_ITER: u32 = 0x8000;
 
-pub const IV_ZERO: [u8; AES_BLOCK_SIZE] = [0u8; AES_BLOCK_SIZE];
+// RFC 8009 4 The string-to-key of the sha2 enctypes prefixes the salt with the name
+// of the enctype.
+pub const AES128_CTS_HMAC_SHA256_128_NAME: &[u8] = b"aes128-cts-hmac-sha256-128";
+pub const AES256_CTS_HMAC_SHA384_192_NAME: &[u8] = b"aes256-cts-hmac-sha384-192";
 
-// In the DK phase of key derivation, a pub constant is used with the nfold algorithm. Since this
-// is a pub constant, we don't need to derive it at run time, we can just staticly define it.
-// pub const DK_CONSTANT: &[u8] = b"kerberos";
-pub const N_FOLD_KERBEROS_16: [u8; 16] = [
-    0x6b, 0x65, 0x72, 0x62, 0x65, 0x72, 0x6f, 0x73, 0x7b, 0x9b, 0x5b, 0x2b, 0x93, 0x13, 0x2b, 0x93,
-];
-// pub const N_FOLD_KERBEROS_32: [u8; 32] = [
-//     0x6b, 0x65, 0x72, 0x62, 0x65, 0x72, 0x6f, 0x73, 0x7b, 0x9b, 0x5b, 0x2b, 0x93, 0x13, 0x2b, 0x93,
-//     0x5c, 0x9b, 0xdc, 0xda, 0xd9, 0x5c, 0x98, 0x99, 0xc4, 0xca, 0xe4, 0xde, 0xe6, 0xd6, 0xca, 0xe4
-// ];
+// The prime of the 2048-bit MODP group (RFC 3526 3), which is the smallest group
+// that MIT KRB5 accepts for PKINIT. Its generator is 2.
+#[cfg(feature = "pkinit")]
+pub const PKINIT_DH_MODP_2048_P: [u8; 256] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xc9, 0x0f, 0xda, 0xa2, 0x21, 0x68, 0xc2, 0x34,
+    0xc4, 0xc6, 0x62, 0x8b, 0x80, 0xdc, 0x1c, 0xd1, 0x29, 0x02, 0x4e, 0x08, 0x8a, 0x67, 0xcc, 0x74,
+    0x02, 0x0b, 0xbe, 0xa6, 0x3b, 0x13, 0x9b, 0x22, 0x51, 0x4a, 0x08, 0x79, 0x8e, 0x34, 0x04, 0xdd,
+    0xef, 0x95, 0x19, 0xb3, 0xcd, 0x3a, 0x43, 0x1b, 0x30, 0x2b, 0x0a, 0x6d, 0xf2, 0x5f, 0x14, 0x37,
+    0x4f, 0xe1, 0x35, 0x6d, 0x6d, 0x51, 0xc2, 0x45, 0xe4, 0x85, 0xb5, 0x76, 0x62, 0x5e, 0x7e, 0xc6,
+    0xf4, 0x4c, 0x42, 0xe9, 0xa6, 0x37, 0xed, 0x6b, 0x0b, 0xff, 0x5c, 0xb6, 0xf4, 0x06, 0xb7, 0xed,
+    0xee, 0x38, 0x6b, 0xfb, 0x5a, 0x89, 0x9f, 0xa5, 0xae, 0x9f, 0x24, 0x11, 0x7c, 0x4b, 0x1f, 0xe6,
+    0x49, 0x28, 0x66, 0x51, 0xec, 0xe4, 0x5b, 0x3d, 0xc2, 0x00, 0x7c, 0xb8, 0xa1, 0x63, 0xbf, 0x05,
+    0x98, 0xda, 0x48, 0x36, 0x1c, 0x55, 0xd3, 0x9a, 0x69, 0x16, 0x3f, 0xa8, 0xfd, 0x24, 0xcf, 0x5f,
+    0x83, 0x65, 0x5d, 0x23, 0xdc, 0xa3, 0xad, 0x96, 0x1c, 0x62, 0xf3, 0x56, 0x20, 0x85, 0x52, 0xbb,
+    0x9e, 0xd5, 0x29, 0x07, 0x70, 0x96, 0x96, 0x6d, 0x67, 0x0c, 0x35, 0x4e, 0x4a, 0xbc, 0x98, 0x04,
+    0xf1, 0x74, 0x6c, 0x08, 0xca, 0x18, 0x21, 0x7c, 0x32, 0x90, 0x5e, 0x46, 0x2e, 0x36, 0xce, 0x3b,
+    0xe3, 0x9e, 0x77, 0x2c, 0x18, 0x0e, 0x86, 0x03, 0x9b, 0x27, 0x83, 0xa2, 0xec, 0x07, 0xa2, 0x8f,
+    0xb5, 0xc5, 0x5d, 0xf0, 0x6f, 0x4c, 0x52, 0xc9, 0xde, 0x2b, 0xcb, 0xf6, 0x95, 0x58, 0x17, 0x18,
+    0x39, 0x95, 0x49, 0x7c, 0xea, 0x95, 0x6a, 0xe5, 0x15, 0xd2, 0x26, 0x18, 0x98, 0xfa, 0x05, 0x10,
+    0x15, 0x72, 0x8e, 0x5a, 0x8a, 0xac, 0xaa, 0x68, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+];
+#[cfg(feature = "pkinit")]
+pub const PKINIT_DH_MODP_2048_G: u8 = 2;
 
-// This is a table of all the pre-calculated key usage values.
+// This is a table of all the pre-calculated key usage values, each the n-fold of the
+// key usage and the constant of Kc (0x99), Ke (0xAA) or Ki (0x55) to the block size.
 pub const N_FOLD_KEY_USAGE_KI_00: [u8; 16] = [
     0x52, 0xd3, 0xe9, 0xf4, 0xfa, 0x52, 0xa8, 0x1, 0x54, 0xaa, 0x55, 0x2a, 0x95, 0x40, 0xa, 0xa5,
 ];
@@ -211,3 +270,159 @@ pub const N_FOLD_KEY_USAGE_KI_31: [u8; 16] = [
 pub const N_FOLD_KEY_USAGE_KE_31: [u8; 16] = [
     0x9e, 0xa4, 0xd2, 0xe9, 0xe5, 0xac, 0x57, 0x81, 0xe8, 0x73, 0xb9, 0xdc, 0xea, 0x83, 0xf7, 0x3b,
 ];
+
+// RFC 6113 FAST key usages.
+pub const N_FOLD_KEY_USAGE_KI_50: [u8; 16] = [
+    0xe3, 0x9c, 0xce, 0x67, 0x1a, 0xfb, 0xb4, 0xcf, 0xbb, 0xdd, 0x6e, 0x37, 0x15, 0x46, 0x4d, 0xc6,
+];
+pub const N_FOLD_KEY_USAGE_KE_50: [u8; 16] = [
+    0x36, 0x70, 0xb8, 0x5c, 0x15, 0x4e, 0x5c, 0xd1, 0x10, 0x87, 0xc3, 0x61, 0xaa, 0x86, 0x58, 0x6c,
+];
+pub const N_FOLD_KEY_USAGE_KI_51: [u8; 16] = [
+    0xec, 0x21, 0x10, 0x88, 0x2b, 0x3, 0xb4, 0xd3, 0xbd, 0xde, 0x6e, 0xb7, 0x55, 0x46, 0x6d, 0xd6,
+];
+pub const N_FOLD_KEY_USAGE_KE_51: [u8; 16] = [
+    0x3e, 0xf4, 0xfa, 0x7d, 0x25, 0x56, 0x5c, 0xd5, 0x12, 0x88, 0xc3, 0xe1, 0xea, 0x86, 0x78, 0x7c,
+];
+pub const N_FOLD_KEY_USAGE_KI_52: [u8; 16] = [
+    0xf3, 0xa4, 0x52, 0xa9, 0x3b, 0xc, 0xb5, 0xd7, 0xbf, 0xdf, 0x6f, 0x37, 0x95, 0x46, 0x8d, 0xe6,
+];
+pub const N_FOLD_KEY_USAGE_KE_52: [u8; 16] = [
+    0x46, 0x78, 0x3c, 0x9e, 0x35, 0x5f, 0x5d, 0xd9, 0x14, 0x89, 0xc4, 0x62, 0x2a, 0x86, 0x98, 0x8c,
+];
+pub const N_FOLD_KEY_USAGE_KI_53: [u8; 16] = [
+    0xfc, 0x28, 0x94, 0xca, 0x4b, 0x14, 0xb5, 0xdb, 0xc1, 0xe0, 0x6f, 0xb7, 0xd5, 0x46, 0xad, 0xf6,
+];
+pub const N_FOLD_KEY_USAGE_KE_53: [u8; 16] = [
+    0x4e, 0xfc, 0x7e, 0xbf, 0x45, 0x67, 0x5d, 0xdd, 0x16, 0x8a, 0xc4, 0xe2, 0x6a, 0x86, 0xb8, 0x9c,
+];
+pub const N_FOLD_KEY_USAGE_KI_54: [u8; 16] = [
+    0x3, 0xac, 0xd6, 0xeb, 0x5b, 0x1d, 0xb5, 0xdf, 0xc3, 0xe1, 0x70, 0x38, 0x15, 0x46, 0xce, 0x7,
+];
+pub const N_FOLD_KEY_USAGE_KE_54: [u8; 16] = [
+    0x56, 0x80, 0xc0, 0xe0, 0x55, 0x70, 0x5d, 0xe1, 0x18, 0x8b, 0xc5, 0x62, 0xaa, 0x86, 0xd8, 0xac,
+];
+pub const N_FOLD_KEY_USAGE_KI_55: [u8; 16] = [
+    0xc, 0x31, 0x19, 0xc, 0x6b, 0x25, 0xb5, 0xe3, 0xc5, 0xe2, 0x70, 0xb8, 0x55, 0x46, 0xee, 0x17,
+];
+pub const N_FOLD_KEY_USAGE_KE_55: [u8; 16] = [
+    0x5f, 0x5, 0x3, 0x1, 0x65, 0x78, 0x5d, 0xe5, 0x1a, 0x8c, 0xc5, 0xe2, 0xea, 0x86, 0xf8, 0xbc,
+];
+
+// The checksum key usage values, used to derive Kc for keyed checksums.
+pub const N_FOLD_KEY_USAGE_KC_00: [u8; 16] = [
+    0x95, 0x17, 0xb, 0x85, 0xc2, 0x94, 0xc8, 0x2, 0x65, 0x32, 0x99, 0x4c, 0xa6, 0x40, 0x13, 0x29,
+];
+pub const N_FOLD_KEY_USAGE_KC_01: [u8; 16] = [
+    0x9d, 0x9b, 0x4d, 0xa6, 0xd2, 0x9c, 0xc8, 0x6, 0x67, 0x33, 0x99, 0xcc, 0xe6, 0x40, 0x33, 0x39,
+];
+pub const N_FOLD_KEY_USAGE_KC_02: [u8; 16] = [
+    0xa5, 0x1f, 0x8f, 0xc7, 0xe2, 0xa5, 0xc8, 0xa, 0x69, 0x34, 0x9a, 0x4d, 0x26, 0x40, 0x53, 0x49,
+];
+pub const N_FOLD_KEY_USAGE_KC_03: [u8; 16] = [
+    0xad, 0xa3, 0xd1, 0xe8, 0xf2, 0xad, 0xc8, 0xe, 0x6b, 0x35, 0x9a, 0xcd, 0x66, 0x40, 0x73, 0x59,
+];
+pub const N_FOLD_KEY_USAGE_KC_04: [u8; 16] = [
+    0xb5, 0x27, 0x14, 0xa, 0x2, 0xb6, 0xc9, 0x12, 0x6d, 0x36, 0x9b, 0x4d, 0xa6, 0x40, 0x93, 0x69,
+];
+pub const N_FOLD_KEY_USAGE_KC_05: [u8; 16] = [
+    0xbd, 0xab, 0x56, 0x2b, 0x12, 0xbe, 0xc9, 0x16, 0x6f, 0x37, 0x9b, 0xcd, 0xe6, 0x40, 0xb3, 0x79,
+];
+pub const N_FOLD_KEY_USAGE_KC_06: [u8; 16] = [
+    0xc5, 0x2f, 0x98, 0x4c, 0x22, 0xc7, 0xc9, 0x1a, 0x71, 0x38, 0x9c, 0x4e, 0x26, 0x40, 0xd3, 0x89,
+];
+pub const N_FOLD_KEY_USAGE_KC_07: [u8; 16] = [
+    0xcd, 0xb3, 0xda, 0x6d, 0x32, 0xcf, 0xc9, 0x1e, 0x73, 0x39, 0x9c, 0xce, 0x66, 0x40, 0xf3, 0x99,
+];
+pub const N_FOLD_KEY_USAGE_KC_08: [u8; 16] = [
+    0xd5, 0x37, 0x1b, 0x8e, 0x42, 0xd8, 0xca, 0x23, 0x75, 0x3a, 0x9d, 0x4e, 0xa6, 0x41, 0x13, 0xa9,
+];
+pub const N_FOLD_KEY_USAGE_KC_09: [u8; 16] = [
+    0xdd, 0xbb, 0x5d, 0xaf, 0x52, 0xe0, 0xca, 0x27, 0x77, 0x3b, 0x9d, 0xce, 0xe6, 0x41, 0x33, 0xb9,
+];
+pub const N_FOLD_KEY_USAGE_KC_10: [u8; 16] = [
+    0xe5, 0x3f, 0x9f, 0xd0, 0x62, 0xe9, 0xca, 0x2b, 0x79, 0x3c, 0x9e, 0x4f, 0x26, 0x41, 0x53, 0xc9,
+];
+pub const N_FOLD_KEY_USAGE_KC_11: [u8; 16] = [
+    0xed, 0xc3, 0xe1, 0xf1, 0x72, 0xf1, 0xca, 0x2f, 0x7b, 0x3d, 0x9e, 0xcf, 0x66, 0x41, 0x73, 0xd9,
+];
+pub const N_FOLD_KEY_USAGE_KC_12: [u8; 16] = [
+    0xf5, 0x47, 0x24, 0x12, 0x82, 0xfa, 0xcb, 0x33, 0x7d, 0x3e, 0x9f, 0x4f, 0xa6, 0x41, 0x93, 0xe9,
+];
+pub const N_FOLD_KEY_USAGE_KC_13: [u8; 16] = [
+    0xfd, 0xcb, 0x66, 0x33, 0x93, 0x2, 0xcb, 0x37, 0x7f, 0x3f, 0x9f, 0xcf, 0xe6, 0x41, 0xb3, 0xf9,
+];
+pub const N_FOLD_KEY_USAGE_KC_14: [u8; 16] = [
+    0x5, 0x4f, 0xa8, 0x54, 0xa3, 0xb, 0xcb, 0x3b, 0x81, 0x40, 0xa0, 0x50, 0x26, 0x41, 0xd4, 0xa,
+];
+pub const N_FOLD_KEY_USAGE_KC_15: [u8; 16] = [
+    0xd, 0xd3, 0xea, 0x75, 0xb3, 0x13, 0xcb, 0x3f, 0x83, 0x41, 0xa0, 0xd0, 0x66, 0x41, 0xf4, 0x1a,
+];
+pub const N_FOLD_KEY_USAGE_KC_16: [u8; 16] = [
+    0x15, 0x57, 0x2b, 0x95, 0xc3, 0x1c, 0xcc, 0x44, 0x86, 0x42, 0xa1, 0x50, 0xa6, 0x42, 0x14, 0x2a,
+];
+pub const N_FOLD_KEY_USAGE_KC_17: [u8; 16] = [
+    0x1d, 0xdb, 0x6d, 0xb6, 0xd3, 0x24, 0xcc, 0x48, 0x88, 0x43, 0xa1, 0xd0, 0xe6, 0x42, 0x34, 0x3a,
+];
+pub const N_FOLD_KEY_USAGE_KC_18: [u8; 16] = [
+    0x25, 0x5f, 0xaf, 0xd7, 0xe3, 0x2d, 0xcc, 0x4c, 0x8a, 0x44, 0xa2, 0x51, 0x26, 0x42, 0x54, 0x4a,
+];
+pub const N_FOLD_KEY_USAGE_KC_19: [u8; 16] = [
+    0x2d, 0xe3, 0xf1, 0xf8, 0xf3, 0x35, 0xcc, 0x50, 0x8c, 0x45, 0xa2, 0xd1, 0x66, 0x42, 0x74, 0x5a,
+];
+pub const N_FOLD_KEY_USAGE_KC_20: [u8; 16] = [
+    0x35, 0x67, 0x34, 0x1a, 0x3, 0x3e, 0xcd, 0x54, 0x8e, 0x46, 0xa3, 0x51, 0xa6, 0x42, 0x94, 0x6a,
+];
+pub const N_FOLD_KEY_USAGE_KC_21: [u8; 16] = [
+    0x3d, 0xeb, 0x76, 0x3b, 0x13, 0x46, 0xcd, 0x58, 0x90, 0x47, 0xa3, 0xd1, 0xe6, 0x42, 0xb4, 0x7a,
+];
+pub const N_FOLD_KEY_USAGE_KC_22: [u8; 16] = [
+    0x45, 0x6f, 0xb8, 0x5c, 0x23, 0x4f, 0xcd, 0x5c, 0x92, 0x48, 0xa4, 0x52, 0x26, 0x42, 0xd4, 0x8a,
+];
+pub const N_FOLD_KEY_USAGE_KC_23: [u8; 16] = [
+    0x4d, 0xf3, 0xfa, 0x7d, 0x33, 0x57, 0xcd, 0x60, 0x94, 0x49, 0xa4, 0xd2, 0x66, 0x42, 0xf4, 0x9a,
+];
+pub const N_FOLD_KEY_USAGE_KC_24: [u8; 16] = [
+    0x55, 0x77, 0x3b, 0x9e, 0x43, 0x60, 0xce, 0x65, 0x96, 0x4a, 0xa5, 0x52, 0xa6, 0x43, 0x14, 0xaa,
+];
+pub const N_FOLD_KEY_USAGE_KC_25: [u8; 16] = [
+    0x5d, 0xfb, 0x7d, 0xbf, 0x53, 0x68, 0xce, 0x69, 0x98, 0x4b, 0xa5, 0xd2, 0xe6, 0x43, 0x34, 0xba,
+];
+pub const N_FOLD_KEY_USAGE_KC_26: [u8; 16] = [
+    0x65, 0x7f, 0xbf, 0xe0, 0x63, 0x71, 0xce, 0x6d, 0x9a, 0x4c, 0xa6, 0x53, 0x26, 0x43, 0x54, 0xca,
+];
+pub const N_FOLD_KEY_USAGE_KC_27: [u8; 16] = [
+    0x6e, 0x4, 0x2, 0x1, 0x73, 0x79, 0xce, 0x71, 0x9c, 0x4d, 0xa6, 0xd3, 0x66, 0x43, 0x74, 0xda,
+];
+pub const N_FOLD_KEY_USAGE_KC_28: [u8; 16] = [
+    0x75, 0x87, 0x44, 0x22, 0x83, 0x82, 0xcf, 0x75, 0x9e, 0x4e, 0xa7, 0x53, 0xa6, 0x43, 0x94, 0xea,
+];
+pub const N_FOLD_KEY_USAGE_KC_29: [u8; 16] = [
+    0x7e, 0xb, 0x86, 0x43, 0x93, 0x8a, 0xcf, 0x79, 0xa0, 0x4f, 0xa7, 0xd3, 0xe6, 0x43, 0xb4, 0xfa,
+];
+pub const N_FOLD_KEY_USAGE_KC_30: [u8; 16] = [
+    0x85, 0x8f, 0xc8, 0x64, 0xa3, 0x93, 0xcf, 0x7d, 0xa2, 0x50, 0xa8, 0x54, 0x26, 0x43, 0xd5, 0xa,
+];
+pub const N_FOLD_KEY_USAGE_KC_31: [u8; 16] = [
+    0x8e, 0x14, 0xa, 0x85, 0xb3, 0x9b, 0xcf, 0x81, 0xa4, 0x51, 0xa8, 0xd4, 0x66, 0x43, 0xf5, 0x1a,
+];
+
+// RFC 6113 FAST key usages.
+pub const N_FOLD_KEY_USAGE_KC_50: [u8; 16] = [
+    0x25, 0xdf, 0xef, 0xf7, 0xe3, 0x3d, 0xd4, 0xd0, 0xcc, 0x65, 0xb2, 0x59, 0x26, 0x46, 0x56, 0x4b,
+];
+pub const N_FOLD_KEY_USAGE_KC_51: [u8; 16] = [
+    0x2e, 0x64, 0x32, 0x18, 0xf3, 0x45, 0xd4, 0xd4, 0xce, 0x66, 0xb2, 0xd9, 0x66, 0x46, 0x76, 0x5b,
+];
+pub const N_FOLD_KEY_USAGE_KC_52: [u8; 16] = [
+    0x35, 0xe7, 0x74, 0x3a, 0x3, 0x4e, 0xd5, 0xd8, 0xd0, 0x67, 0xb3, 0x59, 0xa6, 0x46, 0x96, 0x6b,
+];
+pub const N_FOLD_KEY_USAGE_KC_53: [u8; 16] = [
+    0x3e, 0x6b, 0xb6, 0x5b, 0x13, 0x56, 0xd5, 0xdc, 0xd2, 0x68, 0xb3, 0xd9, 0xe6, 0x46, 0xb6, 0x7b,
+];
+pub const N_FOLD_KEY_USAGE_KC_54: [u8; 16] = [
+    0x45, 0xef, 0xf8, 0x7c, 0x23, 0x5f, 0xd5, 0xe0, 0xd4, 0x69, 0xb4, 0x5a, 0x26, 0x46, 0xd6, 0x8b,
+];
+pub const N_FOLD_KEY_USAGE_KC_55: [u8; 16] = [
+    0x4e, 0x74, 0x3a, 0x9d, 0x33, 0x67, 0xd5, 0xe4, 0xd6, 0x6a, 0xb4, 0xda, 0x66, 0x46, 0xf6, 0x9b,
+];
@@ -1,21 +1,81 @@
+use crate::asn1::constants::checksum_types::ChecksumType;
 use crate::constants::*;
 use crate::error::KrbError;
+use crate::proto::{EncryptionType, KeyUsage};
 
 use aes::cipher::generic_array::GenericArray;
-use aes::cipher::{BlockDecryptMut, BlockEncryptMut};
-use aes::Aes256;
+use aes::cipher::{BlockDecrypt, BlockEncrypt};
+use aes::{Aes128, Aes256};
 use hmac::{digest::FixedOutput, Hmac, Mac};
+use md5::{Digest, Md5};
 use pbkdf2::pbkdf2_hmac;
 use rand::{thread_rng, Rng};
 use sha1::Sha1;
+use sha2::{Sha256, Sha384};
+use zeroize::{Zeroize, Zeroizing};
 
-type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
-type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+type AesBlock = GenericArray<u8, <aes::Aes256 as aes::cipher::BlockSizeUser>::BlockSize>;
 
-type Aes256Block = GenericArray<u8, <aes::Aes256 as aes::cipher::BlockSizeUser>::BlockSize>;
-type Aes256Key = GenericArray<u8, <aes::Aes256 as aes::cipher::KeySizeUser>::KeySize>;
+#[cfg(feature = "camellia")]
+mod camellia;
+#[cfg(feature = "legacy-crypto")]
+mod des3;
 
 type HmacSha1 = Hmac<Sha1>;
+type HmacMd5 = Hmac<Md5>;
+type HmacSha256 = Hmac<Sha256>;
+type HmacSha384 = Hmac<Sha384>;
+
+/// The block cipher of aes-cts-hmac-sha1-96. RFC 3962 defines the aes128 and aes256
+/// enctypes identically apart from the size of the key, so everything past the
+/// choice of cipher is shared between them.
+enum Aes {
+    Aes128(Aes128),
+    Aes256(Aes256),
+}
+
+/// A block cipher with the 128 bit blocks of aes, which is all that CTS needs to know
+/// of it. Camellia (RFC 6803) shares the CTS mode of aes.
+trait CtsCipher {
+    fn encrypt_block(&self, block: &mut AesBlock);
+
+    fn decrypt_block(&self, block: &mut AesBlock);
+}
+
+impl CtsCipher for Aes {
+    fn encrypt_block(&self, block: &mut AesBlock) {
+        match self {
+            Aes::Aes128(cipher) => cipher.encrypt_block(block),
+            Aes::Aes256(cipher) => cipher.encrypt_block(block),
+        }
+    }
+
+    fn decrypt_block(&self, block: &mut AesBlock) {
+        match self {
+            Aes::Aes128(cipher) => cipher.decrypt_block(block),
+            Aes::Aes256(cipher) => cipher.decrypt_block(block),
+        }
+    }
+}
+
+/// A key of aes-cts-hmac-sha1-96, which is either 128 or 256 bits.
+trait AesKey {
+    fn cipher(&self) -> Aes;
+}
+
+impl AesKey for [u8; AES_128_KEY_LEN] {
+    fn cipher(&self) -> Aes {
+        use aes::cipher::KeyInit;
+        Aes::Aes128(Aes128::new(self.into()))
+    }
+}
+
+impl AesKey for [u8; AES_256_KEY_LEN] {
+    fn cipher(&self) -> Aes {
+        use aes::cipher::KeyInit;
+        Aes::Aes256(Aes256::new(self.into()))
+    }
+}
 
 /// Given the users passphrase, the kerberos realm, the client name and the iteration
 /// count then the users base key is derived. The iteration count is an optional value
@@ -27,22 +87,33 @@ pub(crate) fn derive_key_aes256_cts_hmac_sha1_96(
     cname: &[u8],
     iter_count: Option<u32>,
 ) -> Result<[u8; AES_256_KEY_LEN], KrbError> {
-    // Salt is the concatenation of realm + cname.
     // NOTE: Salt may come in AS-REP padata ETYPE-INFO2
-    let mut salt = Vec::with_capacity(realm.len() + cname.len());
-    salt.extend_from_slice(realm);
-    salt.extend_from_slice(cname);
-
-    let iter_count = iter_count.unwrap_or(PKBDF2_SHA1_ITER);
-
-    let mut buf = [0u8; AES_256_KEY_LEN];
-    pbkdf2_hmac::<Sha1>(passphrase, &salt, iter_count, &mut buf);
+    let salt = default_salt(realm, cname);
+    derive_key_aes_cts_hmac_sha1_96(passphrase, &salt, iter_count)
+}
 
-    // It's unclear what this achieves cryptographically ...
-    let mut dk_buf = [0u8; AES_256_KEY_LEN];
-    dk_aes_256(&mut dk_buf, &buf);
+/// As [derive_key_aes256_cts_hmac_sha1_96], for the 128 bit base key of
+/// aes128-cts-hmac-sha1-96.
+pub(crate) fn derive_key_aes128_cts_hmac_sha1_96(
+    passphrase: &[u8],
+    realm: &[u8],
+    cname: &[u8],
+    iter_count: Option<u32>,
+) -> Result<[u8; AES_128_KEY_LEN], KrbError> {
+    let salt = default_salt(realm, cname);
+    derive_key_aes_cts_hmac_sha1_96(passphrase, &salt, iter_count)
+}
 
-    Ok(dk_buf)
+/// The default salt of a principal, RFC 4120 4: the realm followed by each
+/// component of the name, without separators. `host/node1.example.com@EXAMPLE.COM`
+/// is salted with `EXAMPLE.COMhostnode1.example.com`.
+pub(crate) fn default_salt(realm: &[u8], cname: &[u8]) -> Vec<u8> {
+    let mut salt = Vec::with_capacity(realm.len() + cname.len());
+    salt.extend_from_slice(realm);
+    cname
+        .split(|b| *b == b'/')
+        .for_each(|component| salt.extend_from_slice(component));
+    salt
 }
 
 /// Given the users passphrase, an external salt and the iteration
@@ -54,31 +125,120 @@ pub(crate) fn derive_key_external_salt_aes256_cts_hmac_sha1_96(
     external_salt: &[u8],
     iter_count: Option<u32>,
 ) -> Result<[u8; AES_256_KEY_LEN], KrbError> {
-    // Salt is the concatenation of realm + cname.
-    // NOTE: Salt may come in AS-REP padata ETYPE-INFO2
+    derive_key_aes_cts_hmac_sha1_96(passphrase, external_salt, iter_count)
+}
+
+/// As [derive_key_external_salt_aes256_cts_hmac_sha1_96], for the 128 bit base key
+/// of aes128-cts-hmac-sha1-96.
+pub(crate) fn derive_key_external_salt_aes128_cts_hmac_sha1_96(
+    passphrase: &[u8],
+    external_salt: &[u8],
+    iter_count: Option<u32>,
+) -> Result<[u8; AES_128_KEY_LEN], KrbError> {
+    derive_key_aes_cts_hmac_sha1_96(passphrase, external_salt, iter_count)
+}
+
+#[cfg(test)]
+thread_local! {
+    // How many times PBKDF2 ran on this thread, which tests check to see that a key
+    // isn't derived again when it is already known.
+    pub(crate) static PBKDF2_RUNS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+fn count_pbkdf2_run() {
+    #[cfg(test)]
+    PBKDF2_RUNS.with(|runs| runs.set(runs.get() + 1));
+}
+
+fn derive_key_aes_cts_hmac_sha1_96<const N: usize>(
+    passphrase: &[u8],
+    salt: &[u8],
+    iter_count: Option<u32>,
+) -> Result<[u8; N], KrbError>
+where
+    [u8; N]: AesKey,
+{
     let iter_count = iter_count.unwrap_or(PKBDF2_SHA1_ITER);
 
-    let mut buf = [0u8; AES_256_KEY_LEN];
-    pbkdf2_hmac::<Sha1>(passphrase, external_salt, iter_count, &mut buf);
+    let mut buf = Zeroizing::new([0u8; N]);
+    pbkdf2_hmac::<Sha1>(passphrase, salt, iter_count, buf.as_mut_slice());
+    count_pbkdf2_run();
 
     // It's unclear what this achieves cryptographically ...
-    let mut dk_buf = [0u8; AES_256_KEY_LEN];
-    dk_aes_256(&mut dk_buf, &buf);
+    Ok(dk_aes(&buf, &dk_constant(b"kerberos")))
+}
+
+/// RFC 3961 5.1 n-fold, which stretches or shrinks `input` to `out_len` bytes. It is
+/// how the well-known constants of DK, such as "kerberos", are made to fit the block
+/// size.
+pub(crate) fn nfold(input: &[u8], out_len: usize) -> Vec<u8> {
+    let mut out = vec![0u8; out_len];
+    let in_len = input.len();
+    if in_len == 0 || out_len == 0 {
+        return out;
+    }
+
+    // The input is repeated until it is the lcm of both lengths, each repetition
+    // rotated right by 13 bits, and the result is added in chunks of out_len with
+    // ones' complement addition. This walks the bytes of the repetitions from the
+    // last, so that the carry moves towards the most significant byte.
+    let in_bits = in_len * 8;
+    let lcm = in_len / gcd(in_len, out_len) * out_len;
+    let mut byte: usize = 0;
+    for i in (0..lcm).rev() {
+        // The most significant bit of the input that lands in this byte.
+        let msbit = ((in_bits - 1) + (in_bits + 13) * (i / in_len) + ((in_len - i % in_len) << 3))
+            % in_bits;
+        let hi = usize::from(input[(in_len - 1 - (msbit >> 3)) % in_len]);
+        let lo = usize::from(input[(in_len - (msbit >> 3)) % in_len]);
+        byte += (((hi << 8) | lo) >> ((msbit & 7) + 1)) & 0xff;
+        byte += usize::from(out[i % out_len]);
+        out[i % out_len] = (byte & 0xff) as u8;
+        byte >>= 8;
+    }
+
+    // The end around carry.
+    if byte != 0 {
+        for o in out.iter_mut().rev() {
+            byte += usize::from(*o);
+            *o = (byte & 0xff) as u8;
+            byte >>= 8;
+        }
+    }
 
-    Ok(dk_buf)
+    out
 }
 
-fn dk_aes_256(out_buf: &mut [u8; AES_256_KEY_LEN], buf: &[u8; AES_256_KEY_LEN]) {
-    let (lower, upper) = out_buf.split_at_mut(AES_BLOCK_SIZE);
-    debug_assert!(lower.len() == AES_BLOCK_SIZE);
-    debug_assert!(upper.len() == AES_BLOCK_SIZE);
-    dk_encrypt_aes_256_cbc(buf.into(), &N_FOLD_KERBEROS_16.into(), lower.into());
-    dk_encrypt_aes_256_cbc(buf.into(), (&*lower).into(), upper.into());
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// The n-fold of `input` to the block size, the constant of DK.
+fn dk_constant(input: &[u8]) -> [u8; AES_BLOCK_SIZE] {
+    let mut constant = [0u8; AES_BLOCK_SIZE];
+    constant.copy_from_slice(&nfold(input, AES_BLOCK_SIZE));
+    constant
 }
 
-fn dk_encrypt_aes_256_cbc(key: &Aes256Key, plaintext: &Aes256Block, out_buf: &mut Aes256Block) {
-    use aes::cipher::KeyIvInit;
-    Aes256CbcEnc::new(key, &IV_ZERO.into()).encrypt_block_b2b_mut(plaintext, out_buf)
+/// RFC 3961 DK. The n-folded constant is encrypted repeatedly, each output block
+/// being the input of the next, until there are enough bits for a key of the same
+/// size as the base key. random-to-key is the identity for aes.
+fn dk_aes<const N: usize>(key: &[u8; N], constant: &[u8; AES_BLOCK_SIZE]) -> [u8; N]
+where
+    [u8; N]: AesKey,
+{
+    let cipher = key.cipher();
+    let mut block: AesBlock = (*constant).into();
+
+    let mut out = [0u8; N];
+    for chunk in out.chunks_mut(AES_BLOCK_SIZE) {
+        cipher.encrypt_block(&mut block);
+        chunk.copy_from_slice(&block);
+    }
+    out
 }
 
 /// Given the [base key](derive_key_aes256_cts_hmac_sha1_96) and the key_usage value
@@ -86,40 +246,54 @@ fn dk_encrypt_aes_256_cbc(key: &Aes256Key, plaintext: &Aes256Block, out_buf: &mu
 pub(crate) fn decrypt_aes256_cts_hmac_sha1_96(
     key: &[u8; AES_256_KEY_LEN],
     ciphertext: &[u8],
-    key_usage: i32,
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError> {
+    decrypt_aes_cts_hmac_sha1_96(key, ciphertext, key_usage)
+}
+
+/// Given the [base key](derive_key_aes128_cts_hmac_sha1_96) and the key_usage value
+/// decrypt and authenticate the provided ciphertext.
+pub(crate) fn decrypt_aes128_cts_hmac_sha1_96(
+    key: &[u8; AES_128_KEY_LEN],
+    ciphertext: &[u8],
+    key_usage: KeyUsage,
 ) -> Result<Vec<u8>, KrbError> {
+    decrypt_aes_cts_hmac_sha1_96(key, ciphertext, key_usage)
+}
+
+fn decrypt_aes_cts_hmac_sha1_96<const N: usize>(
+    key: &[u8; N],
+    ciphertext: &[u8],
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError>
+where
+    [u8; N]: AesKey,
+{
     // Split to get the mac.
     if let Some((ciphertext, msg_hmac)) = ciphertext.split_last_chunk::<SHA1_HMAC_LEN>() {
         // Check the ciphertext length.
-        assert!(ciphertext.len() > 0);
         if ciphertext.is_empty() {
             return Err(KrbError::MessageEmpty);
         };
 
         // More key derivation ...
-        let (ki, ke) = dk_ki_ke_aes_256(key, key_usage);
+        let (ki, ke) = dk_ki_ke_aes(key, key_usage)?;
 
-        let mut plaintext = decrypt_aes256_cts(&ke, ciphertext)?;
+        let mut plaintext = decrypt_aes_cts(&ke, ciphertext)?;
 
         // let mut mac = HmacSha1::new(&ki.into());
-        let mut mac = HmacSha1::new_from_slice(&ki).map_err(|_| KrbError::InvalidHmacSha1Key)?;
+        let mut mac =
+            HmacSha1::new_from_slice(ki.as_slice()).map_err(|_| KrbError::InvalidHmacSha1Key)?;
         mac.update(&plaintext);
 
-        let mut buf = [0u8; 20];
-        mac.finalize_into((&mut buf).into());
-
-        // Truncate to 96 bits.
-        let my_hmac = &buf[0..SHA1_HMAC_LEN];
+        // Compare the 96 bit truncation in constant time, so that the time to fail
+        // tells nothing about how much of a forged tag was right.
+        mac.verify_truncated_left(msg_hmac)
+            .map_err(|_| KrbError::MessageAuthenticationFailed)?;
 
         // The first block is a "confounder" or a random block that exists to setup
         // the IV for the next block. Ignore it.
-        let plaintext = plaintext.split_off(AES_BLOCK_SIZE);
-
-        if my_hmac == msg_hmac {
-            Ok(plaintext)
-        } else {
-            Err(KrbError::MessageAuthenticationFailed)
-        }
+        Ok(plaintext.split_off(AES_BLOCK_SIZE))
     } else {
         // Not enough data
         Err(KrbError::InsufficientData)
@@ -131,20 +305,48 @@ pub(crate) fn decrypt_aes256_cts_hmac_sha1_96(
 pub(crate) fn encrypt_aes256_cts_hmac_sha1_96(
     key: &[u8; AES_256_KEY_LEN],
     plaintext: &[u8],
-    key_usage: i32,
+    key_usage: KeyUsage,
 ) -> Result<Vec<u8>, KrbError> {
-    if plaintext.is_empty() {
-        return Err(KrbError::PlaintextEmpty);
-    };
-    let (ki, ke) = dk_ki_ke_aes_256(key, key_usage);
+    let mut confuzzler = [0u8; AES_BLOCK_SIZE];
+    thread_rng().fill(&mut confuzzler);
+
+    encrypt_aes_cts_hmac_sha1_96(key, &confuzzler, plaintext, key_usage)
+}
 
+/// Given the [base key](derive_key_aes128_cts_hmac_sha1_96) and the key_usage value
+/// encrypt and authenticate the provided plaintext.
+pub(crate) fn encrypt_aes128_cts_hmac_sha1_96(
+    key: &[u8; AES_128_KEY_LEN],
+    plaintext: &[u8],
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError> {
     let mut confuzzler = [0u8; AES_BLOCK_SIZE];
     thread_rng().fill(&mut confuzzler);
 
+    encrypt_aes_cts_hmac_sha1_96(key, &confuzzler, plaintext, key_usage)
+}
+
+// The confounder is only ever chosen by the caller in tests, where a fixed one makes
+// the ciphertext reproducible.
+fn encrypt_aes_cts_hmac_sha1_96<const N: usize>(
+    key: &[u8; N],
+    confuzzler: &[u8; AES_BLOCK_SIZE],
+    plaintext: &[u8],
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError>
+where
+    [u8; N]: AesKey,
+{
+    if plaintext.is_empty() {
+        return Err(KrbError::PlaintextEmpty);
+    };
+    let (ki, ke) = dk_ki_ke_aes(key, key_usage)?;
+
     // let mut mac = HmacSha1::new(ki.into());
-    let mut mac = HmacSha1::new_from_slice(&ki).map_err(|_| KrbError::InvalidHmacSha1Key)?;
+    let mut mac =
+        HmacSha1::new_from_slice(ki.as_slice()).map_err(|_| KrbError::InvalidHmacSha1Key)?;
 
-    mac.update(&confuzzler);
+    mac.update(confuzzler);
     mac.update(&plaintext);
 
     let mut buf = [0u8; 20];
@@ -157,17 +359,565 @@ pub(crate) fn encrypt_aes256_cts_hmac_sha1_96(
     ciphertext.resize(ciphertext.capacity(), 0);
     let (cipher, hmac) = ciphertext.split_at_mut(AES_BLOCK_SIZE + plaintext.len());
 
-    encrypt_aes256_cts(&ke, &confuzzler, plaintext, cipher)?;
+    encrypt_aes_cts(&ke, confuzzler, plaintext, cipher)?;
     hmac.copy_from_slice(&my_hmac);
 
     Ok(ciphertext)
 }
 
-fn dk_ki_ke_aes_256(
-    buf: &[u8; AES_256_KEY_LEN],
-    key_usage: i32,
-) -> ([u8; AES_256_KEY_LEN], [u8; AES_256_KEY_LEN]) {
-    let (ki_const, ke_const) = match key_usage {
+/// Given the [base key](derive_key_aes256_cts_hmac_sha1_96) and the key_usage value
+/// compute the hmac-sha1-96-aes256 checksum of the provided data.
+pub(crate) fn checksum_hmac_sha1_96_aes256(
+    key: &[u8; AES_256_KEY_LEN],
+    data: &[u8],
+    key_usage: KeyUsage,
+) -> Result<[u8; SHA1_HMAC_LEN], KrbError> {
+    checksum_hmac_sha1_96_aes(key, data, key_usage)
+}
+
+/// Given the [base key](derive_key_aes128_cts_hmac_sha1_96) and the key_usage value
+/// compute the hmac-sha1-96-aes128 checksum of the provided data.
+pub(crate) fn checksum_hmac_sha1_96_aes128(
+    key: &[u8; AES_128_KEY_LEN],
+    data: &[u8],
+    key_usage: KeyUsage,
+) -> Result<[u8; SHA1_HMAC_LEN], KrbError> {
+    checksum_hmac_sha1_96_aes(key, data, key_usage)
+}
+
+fn checksum_hmac_sha1_96_aes<const N: usize>(
+    key: &[u8; N],
+    data: &[u8],
+    key_usage: KeyUsage,
+) -> Result<[u8; SHA1_HMAC_LEN], KrbError>
+where
+    [u8; N]: AesKey,
+{
+    let kc = dk_kc_aes(key, key_usage)?;
+
+    let mut mac =
+        HmacSha1::new_from_slice(kc.as_slice()).map_err(|_| KrbError::InvalidHmacSha1Key)?;
+    mac.update(data);
+
+    let mut buf = [0u8; 20];
+    mac.finalize_into((&mut buf).into());
+
+    // Truncate to 96 bits.
+    let mut checksum = [0u8; SHA1_HMAC_LEN];
+    checksum.copy_from_slice(&buf[0..SHA1_HMAC_LEN]);
+
+    Ok(checksum)
+}
+
+/// The HMAC of the aes-cts-hmac-sha2 enctypes. RFC 8009 pairs aes128 with SHA-256
+/// and aes256 with SHA-384, and otherwise defines both enctypes identically.
+enum Sha2Hmac {
+    Sha256(HmacSha256),
+    Sha384(HmacSha384),
+}
+
+impl Sha2Hmac {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Sha2Hmac::Sha256(mac) => mac.update(data),
+            Sha2Hmac::Sha384(mac) => mac.update(data),
+        }
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Sha2Hmac::Sha256(mac) => mac.finalize().into_bytes().to_vec(),
+            Sha2Hmac::Sha384(mac) => mac.finalize().into_bytes().to_vec(),
+        }
+    }
+
+    fn verify_truncated_left(self, tag: &[u8]) -> Result<(), KrbError> {
+        match self {
+            Sha2Hmac::Sha256(mac) => mac.verify_truncated_left(tag),
+            Sha2Hmac::Sha384(mac) => mac.verify_truncated_left(tag),
+        }
+        .map_err(|_| KrbError::MessageAuthenticationFailed)
+    }
+}
+
+/// A key of aes-cts-hmac-sha2, which is either 128 or 256 bits.
+trait Sha2Key: Zeroize {
+    /// The name of the enctype, which prefixes the string-to-key salt.
+    const NAME: &'static [u8];
+    /// The default string-to-key iteration count.
+    const ITER: u32;
+
+    /// The HMAC of the enctype, keyed with `key`.
+    fn hmac(key: &[u8]) -> Result<Sha2Hmac, KrbError>;
+
+    /// PBKDF2 with the HMAC of the enctype.
+    fn pbkdf2(passphrase: &[u8], salt: &[u8], iter_count: u32) -> Zeroizing<Self>;
+}
+
+impl Sha2Key for [u8; AES_128_KEY_LEN] {
+    const NAME: &'static [u8] = AES128_CTS_HMAC_SHA256_128_NAME;
+    const ITER: u32 = PKBDF2_SHA256_ITER;
+
+    fn hmac(key: &[u8]) -> Result<Sha2Hmac, KrbError> {
+        HmacSha256::new_from_slice(key)
+            .map(Sha2Hmac::Sha256)
+            .map_err(|_| KrbError::InvalidHmacSha256Key)
+    }
+
+    fn pbkdf2(passphrase: &[u8], salt: &[u8], iter_count: u32) -> Zeroizing<Self> {
+        let mut tkey = Zeroizing::new([0u8; AES_128_KEY_LEN]);
+        pbkdf2_hmac::<Sha256>(passphrase, salt, iter_count, tkey.as_mut_slice());
+        count_pbkdf2_run();
+        tkey
+    }
+}
+
+impl Sha2Key for [u8; AES_256_KEY_LEN] {
+    const NAME: &'static [u8] = AES256_CTS_HMAC_SHA384_192_NAME;
+    const ITER: u32 = PKBDF2_SHA384_ITER;
+
+    fn hmac(key: &[u8]) -> Result<Sha2Hmac, KrbError> {
+        HmacSha384::new_from_slice(key)
+            .map(Sha2Hmac::Sha384)
+            .map_err(|_| KrbError::InvalidHmacSha384Key)
+    }
+
+    fn pbkdf2(passphrase: &[u8], salt: &[u8], iter_count: u32) -> Zeroizing<Self> {
+        let mut tkey = Zeroizing::new([0u8; AES_256_KEY_LEN]);
+        pbkdf2_hmac::<Sha384>(passphrase, salt, iter_count, tkey.as_mut_slice());
+        count_pbkdf2_run();
+        tkey
+    }
+}
+
+/// RFC 8009 4 Given the users passphrase, the kerberos realm, the client name and the
+/// iteration count then the users aes256-cts-hmac-sha384-192 base key is derived. The
+/// iteration count defaults to the RFC 8009 value of 0x8000 (32768).
+pub(crate) fn derive_key_aes256_cts_hmac_sha384_192(
+    passphrase: &[u8],
+    realm: &[u8],
+    cname: &[u8],
+    iter_count: Option<u32>,
+) -> Result<[u8; AES_256_KEY_LEN], KrbError> {
+    let salt = default_salt(realm, cname);
+    derive_key_aes_cts_hmac_sha2(passphrase, &salt, iter_count)
+}
+
+/// As [derive_key_aes256_cts_hmac_sha384_192], for the 128 bit base key of
+/// aes128-cts-hmac-sha256-128.
+pub(crate) fn derive_key_aes128_cts_hmac_sha256_128(
+    passphrase: &[u8],
+    realm: &[u8],
+    cname: &[u8],
+    iter_count: Option<u32>,
+) -> Result<[u8; AES_128_KEY_LEN], KrbError> {
+    let salt = default_salt(realm, cname);
+    derive_key_aes_cts_hmac_sha2(passphrase, &salt, iter_count)
+}
+
+/// As [derive_key_aes256_cts_hmac_sha384_192], with an external salt such as the one
+/// of ETYPE-INFO2.
+pub(crate) fn derive_key_external_salt_aes256_cts_hmac_sha384_192(
+    passphrase: &[u8],
+    external_salt: &[u8],
+    iter_count: Option<u32>,
+) -> Result<[u8; AES_256_KEY_LEN], KrbError> {
+    derive_key_aes_cts_hmac_sha2(passphrase, external_salt, iter_count)
+}
+
+/// As [derive_key_aes128_cts_hmac_sha256_128], with an external salt such as the one
+/// of ETYPE-INFO2.
+pub(crate) fn derive_key_external_salt_aes128_cts_hmac_sha256_128(
+    passphrase: &[u8],
+    external_salt: &[u8],
+    iter_count: Option<u32>,
+) -> Result<[u8; AES_128_KEY_LEN], KrbError> {
+    derive_key_aes_cts_hmac_sha2(passphrase, external_salt, iter_count)
+}
+
+fn derive_key_aes_cts_hmac_sha2<const N: usize>(
+    passphrase: &[u8],
+    external_salt: &[u8],
+    iter_count: Option<u32>,
+) -> Result<[u8; N], KrbError>
+where
+    [u8; N]: Sha2Key,
+{
+    let iter_count = iter_count.unwrap_or(<[u8; N] as Sha2Key>::ITER);
+
+    // saltp = enctype-name | 0x00 | salt
+    let name = <[u8; N] as Sha2Key>::NAME;
+    let mut saltp = Vec::with_capacity(name.len() + 1 + external_salt.len());
+    saltp.extend_from_slice(name);
+    saltp.push(0);
+    saltp.extend_from_slice(external_salt);
+
+    let tkey = <[u8; N] as Sha2Key>::pbkdf2(passphrase, &saltp, iter_count);
+
+    kdf_hmac_sha2(&tkey, b"kerberos", &[])
+}
+
+/// RFC 8009 3 KDF-HMAC-SHA2, the key derivation function of the sha2 enctypes. It
+/// replaces DK, and no output of it is longer than a single HMAC.
+fn kdf_hmac_sha2<const N: usize, const K: usize>(
+    key: &[u8; N],
+    label: &[u8],
+    context: &[u8],
+) -> Result<[u8; K], KrbError>
+where
+    [u8; N]: Sha2Key,
+{
+    // K1 = HMAC-SHA(key, 0x00000001 | label | 0x00 | context | k)
+    let bits = u32::try_from(K * 8).map_err(|_| KrbError::InvalidEncryptionKey)?;
+
+    let mut mac = <[u8; N] as Sha2Key>::hmac(key)?;
+    mac.update(&1u32.to_be_bytes());
+    mac.update(label);
+    mac.update(&[0]);
+    mac.update(context);
+    mac.update(&bits.to_be_bytes());
+    let k1 = mac.finalize();
+
+    // k-truncate(K1)
+    let k1 = k1.get(..K).ok_or(KrbError::InvalidEncryptionKey)?;
+    let mut out = [0u8; K];
+    out.copy_from_slice(k1);
+    Ok(out)
+}
+
+/// RFC 8009 5 the keys of a key usage, derived from the base key with the constant
+/// 0x99 for Kc, 0xAA for Ke and 0x55 for Ki.
+fn kdf_key_usage_aes_sha2<const N: usize, const K: usize>(
+    key: &[u8; N],
+    key_usage: KeyUsage,
+    constant: u8,
+) -> Result<[u8; K], KrbError>
+where
+    [u8; N]: Sha2Key,
+{
+    kdf_hmac_sha2(key, &key_usage_label(key_usage, constant)?, &[])
+}
+
+/// The key usage as four big endian octets followed by the constant of the key, as
+/// DK and KDF-HMAC-SHA2 both derive the keys of a key usage from.
+fn key_usage_label(key_usage: KeyUsage, constant: u8) -> Result<[u8; 5], KrbError> {
+    let key_usage = i32::from(key_usage);
+    let usage = u32::try_from(key_usage).map_err(|_| KrbError::InvalidKeyUsage(key_usage))?;
+
+    let mut label = [0u8; 5];
+    label[..4].copy_from_slice(&usage.to_be_bytes());
+    label[4] = constant;
+    Ok(label)
+}
+
+/// The HMAC of the ciphertext of RFC 8009, which unlike RFC 3962 authenticates the
+/// ciphertext following the IV rather than the plaintext.
+fn hmac_sha2_ciphertext<const N: usize>(ki: &[u8], ciphertext: &[u8]) -> Result<Sha2Hmac, KrbError>
+where
+    [u8; N]: Sha2Key,
+{
+    let mut mac = <[u8; N] as Sha2Key>::hmac(ki)?;
+    mac.update(&[0u8; AES_BLOCK_SIZE]);
+    mac.update(ciphertext);
+    Ok(mac)
+}
+
+/// Given the [base key](derive_key_aes256_cts_hmac_sha384_192) and the key_usage value
+/// decrypt and authenticate the provided ciphertext.
+pub(crate) fn decrypt_aes256_cts_hmac_sha384_192(
+    key: &[u8; AES_256_KEY_LEN],
+    ciphertext: &[u8],
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError> {
+    decrypt_aes_cts_hmac_sha2::<AES_256_KEY_LEN, SHA384_HMAC_LEN>(key, ciphertext, key_usage)
+}
+
+/// Given the [base key](derive_key_aes128_cts_hmac_sha256_128) and the key_usage value
+/// decrypt and authenticate the provided ciphertext.
+pub(crate) fn decrypt_aes128_cts_hmac_sha256_128(
+    key: &[u8; AES_128_KEY_LEN],
+    ciphertext: &[u8],
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError> {
+    decrypt_aes_cts_hmac_sha2::<AES_128_KEY_LEN, SHA256_HMAC_LEN>(key, ciphertext, key_usage)
+}
+
+fn decrypt_aes_cts_hmac_sha2<const N: usize, const T: usize>(
+    key: &[u8; N],
+    ciphertext: &[u8],
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError>
+where
+    [u8; N]: AesKey + Sha2Key,
+{
+    let Some((ciphertext, msg_hmac)) = ciphertext.split_last_chunk::<T>() else {
+        // Not enough data
+        return Err(KrbError::InsufficientData);
+    };
+
+    if ciphertext.is_empty() {
+        return Err(KrbError::MessageEmpty);
+    };
+
+    let ke: Zeroizing<[u8; N]> = Zeroizing::new(kdf_key_usage_aes_sha2(key, key_usage, 0xAA)?);
+    let ki: Zeroizing<[u8; T]> = Zeroizing::new(kdf_key_usage_aes_sha2(key, key_usage, 0x55)?);
+
+    // The ciphertext is authenticated before anything is decrypted.
+    hmac_sha2_ciphertext::<N>(ki.as_slice(), ciphertext)?.verify_truncated_left(msg_hmac)?;
+
+    let mut plaintext = decrypt_aes_cts(&ke, ciphertext)?;
+
+    // Drop the confounder.
+    Ok(plaintext.split_off(AES_BLOCK_SIZE))
+}
+
+/// Given the [base key](derive_key_aes256_cts_hmac_sha384_192) and the key_usage value
+/// encrypt and authenticate the provided plaintext.
+pub(crate) fn encrypt_aes256_cts_hmac_sha384_192(
+    key: &[u8; AES_256_KEY_LEN],
+    plaintext: &[u8],
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError> {
+    let mut confuzzler = [0u8; AES_BLOCK_SIZE];
+    thread_rng().fill(&mut confuzzler);
+
+    encrypt_aes_cts_hmac_sha2::<AES_256_KEY_LEN, SHA384_HMAC_LEN>(
+        key,
+        &confuzzler,
+        plaintext,
+        key_usage,
+    )
+}
+
+/// Given the [base key](derive_key_aes128_cts_hmac_sha256_128) and the key_usage value
+/// encrypt and authenticate the provided plaintext.
+pub(crate) fn encrypt_aes128_cts_hmac_sha256_128(
+    key: &[u8; AES_128_KEY_LEN],
+    plaintext: &[u8],
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError> {
+    let mut confuzzler = [0u8; AES_BLOCK_SIZE];
+    thread_rng().fill(&mut confuzzler);
+
+    encrypt_aes_cts_hmac_sha2::<AES_128_KEY_LEN, SHA256_HMAC_LEN>(
+        key,
+        &confuzzler,
+        plaintext,
+        key_usage,
+    )
+}
+
+fn encrypt_aes_cts_hmac_sha2<const N: usize, const T: usize>(
+    key: &[u8; N],
+    confuzzler: &[u8; AES_BLOCK_SIZE],
+    plaintext: &[u8],
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError>
+where
+    [u8; N]: AesKey + Sha2Key,
+{
+    let ke: Zeroizing<[u8; N]> = Zeroizing::new(kdf_key_usage_aes_sha2(key, key_usage, 0xAA)?);
+    let ki: Zeroizing<[u8; T]> = Zeroizing::new(kdf_key_usage_aes_sha2(key, key_usage, 0x55)?);
+
+    let mut ciphertext = vec![0u8; AES_BLOCK_SIZE + plaintext.len() + T];
+    let (cipher, hmac) = ciphertext.split_at_mut(AES_BLOCK_SIZE + plaintext.len());
+
+    encrypt_aes_cts(&ke, confuzzler, plaintext, cipher)?;
+
+    // Truncate to the length of the tag.
+    let my_hmac = hmac_sha2_ciphertext::<N>(ki.as_slice(), cipher)?.finalize();
+    hmac.copy_from_slice(&my_hmac[..T]);
+
+    Ok(ciphertext)
+}
+
+/// Given the [base key](derive_key_aes256_cts_hmac_sha384_192) and the key_usage value
+/// compute the hmac-sha384-192-aes256 checksum of the provided data.
+pub(crate) fn checksum_hmac_sha384_192_aes256(
+    key: &[u8; AES_256_KEY_LEN],
+    data: &[u8],
+    key_usage: KeyUsage,
+) -> Result<[u8; SHA384_HMAC_LEN], KrbError> {
+    checksum_hmac_sha2_aes(key, data, key_usage)
+}
+
+/// Given the [base key](derive_key_aes128_cts_hmac_sha256_128) and the key_usage value
+/// compute the hmac-sha256-128-aes128 checksum of the provided data.
+pub(crate) fn checksum_hmac_sha256_128_aes128(
+    key: &[u8; AES_128_KEY_LEN],
+    data: &[u8],
+    key_usage: KeyUsage,
+) -> Result<[u8; SHA256_HMAC_LEN], KrbError> {
+    checksum_hmac_sha2_aes(key, data, key_usage)
+}
+
+fn checksum_hmac_sha2_aes<const N: usize, const T: usize>(
+    key: &[u8; N],
+    data: &[u8],
+    key_usage: KeyUsage,
+) -> Result<[u8; T], KrbError>
+where
+    [u8; N]: Sha2Key,
+{
+    let kc: Zeroizing<[u8; T]> = Zeroizing::new(kdf_key_usage_aes_sha2(key, key_usage, 0x99)?);
+
+    let mut mac = <[u8; N] as Sha2Key>::hmac(kc.as_slice())?;
+    mac.update(data);
+    let buf = mac.finalize();
+
+    // Truncate to the length of the tag.
+    let mut checksum = [0u8; T];
+    checksum.copy_from_slice(&buf[..T]);
+
+    Ok(checksum)
+}
+
+/// RFC 8009 5 the pseudo-random function of the sha2 enctypes, which is KDF-HMAC-SHA2
+/// with the label "prf" and the input as the context. Its output is as long as the
+/// hash, `P`.
+fn prf_aes_cts_hmac_sha2<const N: usize, const P: usize>(
+    key: &[u8; N],
+    data: &[u8],
+) -> Result<[u8; P], KrbError>
+where
+    [u8; N]: Sha2Key,
+{
+    kdf_hmac_sha2(key, b"prf", data)
+}
+
+/// RFC 4757 compute the keyed hmac-md5 checksum of the provided data. Unlike the
+/// other checksums this is not bound to an enctype, and is keyed directly with the
+/// bytes of the key. MS-SFU requires it for PA-FOR-USER whatever the session key.
+pub(crate) fn checksum_hmac_md5(
+    key: &[u8],
+    data: &[u8],
+    key_usage: KeyUsage,
+) -> Result<[u8; MD5_HMAC_LEN], KrbError> {
+    let key_usage = i32::from(key_usage);
+    let usage = u32::try_from(key_usage)
+        .map_err(|_| KrbError::InvalidKeyUsage(key_usage))?
+        .to_le_bytes();
+
+    // Ksign = HMAC-MD5(Key, "signaturekey\0")
+    let mut mac = HmacMd5::new_from_slice(key).map_err(|_| KrbError::InvalidHmacMd5Key)?;
+    mac.update(b"signaturekey\0");
+    let ksign = mac.finalize().into_bytes();
+
+    // tmp = MD5(usage | data)
+    let tmp = Md5::new().chain_update(usage).chain_update(data).finalize();
+
+    // CHKSUM = HMAC-MD5(Ksign, tmp)
+    let mut mac = HmacMd5::new_from_slice(&ksign).map_err(|_| KrbError::InvalidHmacMd5Key)?;
+    mac.update(&tmp);
+
+    let mut checksum = [0u8; MD5_HMAC_LEN];
+    mac.finalize_into((&mut checksum).into());
+
+    Ok(checksum)
+}
+
+/// RFC 3962 the pseudo-random function of aes-cts-hmac-sha1-96. This encrypts
+/// the truncated SHA-1 of the input with a key derived from the base key.
+fn prf_aes_cts_hmac_sha1_96<const N: usize>(key: &[u8; N], data: &[u8]) -> [u8; AES_BLOCK_SIZE]
+where
+    [u8; N]: AesKey,
+{
+    let kp = Zeroizing::new(dk_aes(key, &dk_constant(b"prf")));
+
+    let digest = Sha1::new().chain_update(data).finalize();
+
+    let mut out = [0u8; AES_BLOCK_SIZE];
+    out.copy_from_slice(&digest[..AES_BLOCK_SIZE]);
+
+    // A single block, so CBC with a zero IV is just the block cipher.
+    kp.cipher().encrypt_block((&mut out).into());
+    out
+}
+
+/// RFC 4556 3.2.3.1 octetstring2key, which derives the aes-cts-hmac-sha1-96 reply
+/// key of PKINIT, of `N` bytes, from the Diffie-Hellman shared secret `x`.
+#[cfg(feature = "pkinit")]
+pub(crate) fn octetstring2key_aes_cts_hmac_sha1_96<const N: usize>(x: &[u8]) -> [u8; N] {
+    // k-truncate(SHA1(0x00 | x) | SHA1(0x01 | x) | ...)
+    let mut out = [0u8; N];
+    for (counter, chunk) in out.chunks_mut(20).enumerate() {
+        let digest = Sha1::new()
+            .chain_update([counter as u8])
+            .chain_update(x)
+            .finalize();
+        chunk.copy_from_slice(&digest[..chunk.len()]);
+    }
+
+    // random-to-key is the identity for aes.
+    out
+}
+
+fn dk_kc_aes<const N: usize>(
+    key: &[u8; N],
+    key_usage: KeyUsage,
+) -> Result<Zeroizing<[u8; N]>, KrbError>
+where
+    [u8; N]: AesKey,
+{
+    // The constants of most key usages are precomputed, and the rest are n-folded.
+    let computed;
+    let kc_const = match i32::from(key_usage) {
+        0 => &N_FOLD_KEY_USAGE_KC_00,
+        1 => &N_FOLD_KEY_USAGE_KC_01,
+        2 => &N_FOLD_KEY_USAGE_KC_02,
+        3 => &N_FOLD_KEY_USAGE_KC_03,
+        4 => &N_FOLD_KEY_USAGE_KC_04,
+        5 => &N_FOLD_KEY_USAGE_KC_05,
+        6 => &N_FOLD_KEY_USAGE_KC_06,
+        7 => &N_FOLD_KEY_USAGE_KC_07,
+        8 => &N_FOLD_KEY_USAGE_KC_08,
+        9 => &N_FOLD_KEY_USAGE_KC_09,
+        10 => &N_FOLD_KEY_USAGE_KC_10,
+        11 => &N_FOLD_KEY_USAGE_KC_11,
+        12 => &N_FOLD_KEY_USAGE_KC_12,
+        13 => &N_FOLD_KEY_USAGE_KC_13,
+        14 => &N_FOLD_KEY_USAGE_KC_14,
+        15 => &N_FOLD_KEY_USAGE_KC_15,
+        16 => &N_FOLD_KEY_USAGE_KC_16,
+        17 => &N_FOLD_KEY_USAGE_KC_17,
+        18 => &N_FOLD_KEY_USAGE_KC_18,
+        19 => &N_FOLD_KEY_USAGE_KC_19,
+        20 => &N_FOLD_KEY_USAGE_KC_20,
+        21 => &N_FOLD_KEY_USAGE_KC_21,
+        22 => &N_FOLD_KEY_USAGE_KC_22,
+        23 => &N_FOLD_KEY_USAGE_KC_23,
+        24 => &N_FOLD_KEY_USAGE_KC_24,
+        25 => &N_FOLD_KEY_USAGE_KC_25,
+        26 => &N_FOLD_KEY_USAGE_KC_26,
+        27 => &N_FOLD_KEY_USAGE_KC_27,
+        28 => &N_FOLD_KEY_USAGE_KC_28,
+        29 => &N_FOLD_KEY_USAGE_KC_29,
+        30 => &N_FOLD_KEY_USAGE_KC_30,
+        31 => &N_FOLD_KEY_USAGE_KC_31,
+        50 => &N_FOLD_KEY_USAGE_KC_50,
+        51 => &N_FOLD_KEY_USAGE_KC_51,
+        52 => &N_FOLD_KEY_USAGE_KC_52,
+        53 => &N_FOLD_KEY_USAGE_KC_53,
+        54 => &N_FOLD_KEY_USAGE_KC_54,
+        55 => &N_FOLD_KEY_USAGE_KC_55,
+        _ => {
+            computed = dk_constant(&key_usage_label(key_usage, 0x99)?);
+            &computed
+        }
+    };
+
+    Ok(Zeroizing::new(dk_aes(key, kc_const)))
+}
+
+fn dk_ki_ke_aes<const N: usize>(
+    key: &[u8; N],
+    key_usage: KeyUsage,
+) -> Result<(Zeroizing<[u8; N]>, Zeroizing<[u8; N]>), KrbError>
+where
+    [u8; N]: AesKey,
+{
+    let computed;
+    let (ki_const, ke_const) = match i32::from(key_usage) {
         0 => (&N_FOLD_KEY_USAGE_KI_00, &N_FOLD_KEY_USAGE_KE_00),
         1 => (&N_FOLD_KEY_USAGE_KI_01, &N_FOLD_KEY_USAGE_KE_01),
         2 => (&N_FOLD_KEY_USAGE_KI_02, &N_FOLD_KEY_USAGE_KE_02),
@@ -200,35 +950,45 @@ fn dk_ki_ke_aes_256(
         29 => (&N_FOLD_KEY_USAGE_KI_29, &N_FOLD_KEY_USAGE_KE_29),
         30 => (&N_FOLD_KEY_USAGE_KI_30, &N_FOLD_KEY_USAGE_KE_30),
         31 => (&N_FOLD_KEY_USAGE_KI_31, &N_FOLD_KEY_USAGE_KE_31),
-        _ => todo!(),
+        50 => (&N_FOLD_KEY_USAGE_KI_50, &N_FOLD_KEY_USAGE_KE_50),
+        51 => (&N_FOLD_KEY_USAGE_KI_51, &N_FOLD_KEY_USAGE_KE_51),
+        52 => (&N_FOLD_KEY_USAGE_KI_52, &N_FOLD_KEY_USAGE_KE_52),
+        53 => (&N_FOLD_KEY_USAGE_KI_53, &N_FOLD_KEY_USAGE_KE_53),
+        54 => (&N_FOLD_KEY_USAGE_KI_54, &N_FOLD_KEY_USAGE_KE_54),
+        55 => (&N_FOLD_KEY_USAGE_KI_55, &N_FOLD_KEY_USAGE_KE_55),
+        _ => {
+            computed = (
+                dk_constant(&key_usage_label(key_usage, 0x55)?),
+                dk_constant(&key_usage_label(key_usage, 0xAA)?),
+            );
+            (&computed.0, &computed.1)
+        }
     };
 
-    let mut ki = [0u8; AES_256_KEY_LEN];
-
-    let (lower, upper) = ki.split_at_mut(AES_BLOCK_SIZE);
-    debug_assert!(lower.len() == AES_BLOCK_SIZE);
-    debug_assert!(upper.len() == AES_BLOCK_SIZE);
-    dk_encrypt_aes_256_cbc(buf.into(), ki_const.into(), lower.into());
-    dk_encrypt_aes_256_cbc(buf.into(), (&*lower).into(), upper.into());
-
-    let mut ke = [0u8; AES_256_KEY_LEN];
-    let (lower, upper) = ke.split_at_mut(AES_BLOCK_SIZE);
-    debug_assert!(lower.len() == AES_BLOCK_SIZE);
-    debug_assert!(upper.len() == AES_BLOCK_SIZE);
-    dk_encrypt_aes_256_cbc(buf.into(), ke_const.into(), lower.into());
-    dk_encrypt_aes_256_cbc(buf.into(), (&*lower).into(), upper.into());
+    Ok((
+        Zeroizing::new(dk_aes(key, ki_const)),
+        Zeroizing::new(dk_aes(key, ke_const)),
+    ))
+}
 
-    (ki, ke)
+fn encrypt_aes_cts<const N: usize>(
+    key: &[u8; N],
+    confuzzler: &[u8],
+    plaintext: &[u8],
+    ciphertext: &mut [u8],
+) -> Result<(), KrbError>
+where
+    [u8; N]: AesKey,
+{
+    encrypt_cts(&key.cipher(), confuzzler, plaintext, ciphertext)
 }
 
-fn encrypt_aes256_cts(
-    key: &[u8; AES_256_KEY_LEN],
+fn encrypt_cts(
+    cipher: &impl CtsCipher,
     confuzzler: &[u8],
     plaintext: &[u8],
     ciphertext: &mut [u8],
 ) -> Result<(), KrbError> {
-    use aes::cipher::{KeyInit, KeyIvInit};
-
     // Need at lesat one block for the confuzzler.
     debug_assert!(ciphertext.len() == plaintext.len() + AES_BLOCK_SIZE);
 
@@ -251,32 +1011,36 @@ fn encrypt_aes256_cts(
     let mut chunks = std::iter::zip(ciphertext_chunks, plaintext_chunks);
     // Get the last chunk, this is the only one that may not be block_size
     // and needs special handling.
-    let (c_n_chunk, p_n_star_chunk) = chunks
-        .next_back()
-        // Should be impossible
-        .ok_or(KrbError::InsufficientData)?;
+    let last_chunk = chunks.next_back();
 
     // All remaining chunks are to be directly encrypted.
 
-    // Setup the CBC encipher.
-    let mut cipher = Aes256CbcEnc::new(key.into(), &IV_ZERO.into());
-
-    // Setup the initial block that contains the confuzzler
+    // Setup the initial block that contains the confuzzler. CBC starts from an IV
+    // of zero, so the xor with the IV is skipped.
     let mut previous_block = [0u8; AES_BLOCK_SIZE];
     previous_block.copy_from_slice(confuzzler);
 
     // Initially encipher the confuzzler
-    cipher.encrypt_block_mut((&mut previous_block).into());
+    cipher.encrypt_block((&mut previous_block).into());
     previous_chunk.copy_from_slice(&previous_block);
 
-    // Now for each chunk, encrypt.
+    // Now for each chunk, xor with the previous ciphertext block and encrypt.
     for (cipher_chunk, plain_chunk) in chunks {
-        previous_block.copy_from_slice(plain_chunk);
-        cipher.encrypt_block_mut((&mut previous_block).into());
+        previous_block
+            .iter_mut()
+            .zip(plain_chunk)
+            .for_each(|(c, p)| *c ^= p);
+        cipher.encrypt_block((&mut previous_block).into());
         cipher_chunk.copy_from_slice(&previous_block);
         previous_chunk = cipher_chunk;
     }
 
+    // Without any plaintext there is only the confuzzler, and a single block is
+    // left as is by CTS.
+    let Some((c_n_chunk, p_n_star_chunk)) = last_chunk else {
+        return Ok(());
+    };
+
     // Now we are positioned. previous_chunk + previous_block both have Cn-1.
 
     // We have c_n and p_n already positioned from the start.
@@ -288,7 +1052,7 @@ fn encrypt_aes256_cts(
 
     debug_assert!(*c_n1_chunk == c_n1_block);
 
-    let mut c_n_block: Aes256Block = [0u8; AES_BLOCK_SIZE].into();
+    let mut c_n_block: AesBlock = [0u8; AES_BLOCK_SIZE].into();
 
     let (p_n_star, c_n_star_2) = c_n_block.split_at_mut(p_n_star_len);
     p_n_star.copy_from_slice(p_n_star_chunk);
@@ -300,8 +1064,7 @@ fn encrypt_aes256_cts(
         p_n_star[i] = p_n_star[i] ^ c_n1_star[i];
     }
 
-    let mut raw_cipher = Aes256::new(key.into());
-    raw_cipher.encrypt_block_mut(&mut c_n_block);
+    cipher.encrypt_block(&mut c_n_block);
 
     // We now have c_n_block and c_n1_star. This is where we apply the CS3 / CTS
     // swap.
@@ -311,16 +1074,20 @@ fn encrypt_aes256_cts(
     Ok(())
 }
 
-fn decrypt_aes256_cts(key: &[u8; AES_256_KEY_LEN], ciphertext: &[u8]) -> Result<Vec<u8>, KrbError> {
-    use aes::cipher::{KeyInit, KeyIvInit};
+fn decrypt_aes_cts<const N: usize>(key: &[u8; N], ciphertext: &[u8]) -> Result<Vec<u8>, KrbError>
+where
+    [u8; N]: AesKey,
+{
+    decrypt_cts(&key.cipher(), ciphertext)
+}
 
+fn decrypt_cts(cipher: &impl CtsCipher, ciphertext: &[u8]) -> Result<Vec<u8>, KrbError> {
     // Should not be possible
     debug_assert!(ciphertext.len() > 0);
 
     let ctxt_len = ciphertext.len();
 
     let num_blocks = ctxt_len / AES_BLOCK_SIZE;
-    let mut cipher = Aes256CbcDec::new(key.into(), &IV_ZERO.into());
 
     if num_blocks == 0 {
         // Impossible in krb because the first block is always the confounder.
@@ -339,14 +1106,28 @@ fn decrypt_aes256_cts(key: &[u8; AES_256_KEY_LEN], ciphertext: &[u8]) -> Result<
     // Remove the last two blocks from the right. These are "special" in CTS.
     let (c_n1_chunk, p_n_chunk) = chunks.next_back().ok_or(KrbError::InsufficientData)?;
     // Penultimate chunk
-    let (c_n_chunk, p_n1_chunk) = chunks.next_back().ok_or(KrbError::InsufficientData)?;
+    let Some((c_n_chunk, p_n1_chunk)) = chunks.next_back() else {
+        // A single block is left as is by CTS, so this is just the confuzzler.
+        let mut block = AesBlock::clone_from_slice(c_n1_chunk);
+        cipher.decrypt_block(&mut block);
+        p_n_chunk.copy_from_slice(&block);
+        return Ok(plaintext);
+    };
 
     // Now process the other chunks as normal. CTS aka CS3 is just CBC with
     // bad vibes at the end.
-    // .encrypt_block_b2b_mut(plaintext, out_buf)
+    //
+    // The previous ciphertext block, which starts as the IV of zero.
+    let mut previous_block = [0u8; AES_BLOCK_SIZE];
 
     for (cipher_chunk, plain_chunk) in chunks {
-        cipher.decrypt_block_b2b_mut(cipher_chunk.into(), plain_chunk.into())
+        let mut block = AesBlock::clone_from_slice(cipher_chunk);
+        cipher.decrypt_block(&mut block);
+        plain_chunk
+            .iter_mut()
+            .zip(block.iter().zip(previous_block))
+            .for_each(|(p, (z, c))| *p = z ^ c);
+        previous_block.copy_from_slice(cipher_chunk);
     }
 
     // Now we have to process the last two blocks. To understand why we need
@@ -384,13 +1165,12 @@ fn decrypt_aes256_cts(key: &[u8; AES_256_KEY_LEN], ciphertext: &[u8]) -> Result<
     // the last two blocks swapped basicly.
 
     // We need a scratch block.
-    let mut z: Aes256Block = [0u8; AES_BLOCK_SIZE].into();
-    let mut raw_cipher = Aes256::new(key.into());
+    let mut z = AesBlock::clone_from_slice(c_n_chunk);
 
     let z_star_len = c_n1_chunk.len();
 
     // Decrypt Cn
-    raw_cipher.decrypt_block_b2b_mut(c_n_chunk.into(), &mut z);
+    cipher.decrypt_block(&mut z);
 
     // Block is now Z.
     let (z_star, z_star_2) = z.split_at(z_star_len);
@@ -404,20 +1184,378 @@ fn decrypt_aes256_cts(key: &[u8; AES_256_KEY_LEN], ciphertext: &[u8]) -> Result<
     }
 
     // Pn is complete.
-    let mut cn1_block: Aes256Block = [0u8; AES_BLOCK_SIZE].into();
+    let mut cn1_block: AesBlock = [0u8; AES_BLOCK_SIZE].into();
 
     // We concat the two slices here.
     let (cn1_block_star, cn1_block_star_2) = cn1_block.split_at_mut(c_n1_chunk.len());
     cn1_block_star.copy_from_slice(c_n1_chunk);
     cn1_block_star_2.copy_from_slice(z_star_2);
 
-    // We can re-use the existing cbc cipher as it has the correct state
-    // of the cbc mode.
-    cipher.decrypt_block_b2b_mut(&cn1_block, p_n1_chunk.into());
+    // Finish the cbc mode, xoring with Cn-2 which is still the previous block.
+    cipher.decrypt_block(&mut cn1_block);
+    p_n1_chunk
+        .iter_mut()
+        .zip(cn1_block.iter().zip(previous_block))
+        .for_each(|(p, (z, c))| *p = z ^ c);
 
     Ok(plaintext)
 }
 
+/// The operations of an enctype (RFC 3961 3). Callers look the enctype of a key up
+/// with [cipher_for] rather than matching on it, so that a new enctype is added here
+/// and nowhere else.
+pub(crate) trait EtypeCipher: Sync {
+    fn etype(&self) -> EncryptionType;
+
+    /// The checksum type that is keyed with keys of this enctype.
+    fn checksum_type(&self) -> ChecksumType;
+
+    /// The length of a key in bytes.
+    fn key_size(&self) -> usize;
+
+    /// The number of random bytes that random-to-key takes. Only des3 differs from
+    /// the key size, as its keys carry parity bits.
+    fn seed_size(&self) -> usize {
+        self.key_size()
+    }
+
+    /// The iteration count of the string-to-key when the s2kparams don't give one.
+    fn default_iter_count(&self) -> u32;
+
+    /// The iteration count of the string-to-key for the s2kparams of ETYPE-INFO2.
+    /// Those of the aes, sha2 and camellia enctypes are all a 4-octet big-endian
+    /// count (RFC 3962 4, RFC 8009 4, RFC 6803 3), and anything else is refused.
+    fn iter_count(&self, s2kparams: Option<&[u8]>) -> Result<u32, KrbError> {
+        s2kparams_iter_count(s2kparams)
+            .map(|iter_count| iter_count.unwrap_or_else(|| self.default_iter_count()))
+    }
+
+    /// Derive the base key from a passphrase, with the salt and the iteration count
+    /// of the s2kparams. Without an iteration count the default of the enctype is
+    /// used.
+    fn string_to_key(
+        &self,
+        passphrase: &[u8],
+        salt: &[u8],
+        iter_count: Option<u32>,
+    ) -> Result<Zeroizing<Vec<u8>>, KrbError>;
+
+    /// Make a key of random bytes of the key size, such as the output of PRF+.
+    /// random-to-key is the identity for every aes enctype.
+    fn random_to_key(&self, random: &[u8]) -> Result<Zeroizing<Vec<u8>>, KrbError> {
+        if random.len() != self.seed_size() {
+            return Err(KrbError::InvalidEncryptionKey);
+        }
+        Ok(Zeroizing::new(random.to_vec()))
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8],
+        key_usage: KeyUsage,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, KrbError>;
+
+    fn decrypt(
+        &self,
+        key: &[u8],
+        key_usage: KeyUsage,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, KrbError>;
+
+    fn checksum(&self, key: &[u8], key_usage: KeyUsage, data: &[u8]) -> Result<Vec<u8>, KrbError>;
+
+    /// The RFC 3961 pseudo-random function of the key. The length of its output
+    /// depends on the enctype.
+    fn prf(&self, key: &[u8], input: &[u8]) -> Result<Vec<u8>, KrbError>;
+
+    /// RFC 6113 PRF+ of the key, which repeats the pseudo-random function with a
+    /// counter octet before the pepper until `out` is filled.
+    fn prf_plus(&self, key: &[u8], pepper: &[u8], out: &mut [u8]) -> Result<(), KrbError> {
+        let mut stream = Vec::with_capacity(out.len());
+        for counter in 1..=u8::MAX {
+            if stream.len() >= out.len() {
+                break;
+            }
+            let mut input = Vec::with_capacity(1 + pepper.len());
+            input.push(counter);
+            input.extend_from_slice(pepper);
+            stream.extend(self.prf(key, &input)?);
+        }
+
+        let stream = stream
+            .get(..out.len())
+            .ok_or(KrbError::InvalidEncryptionKey)?;
+        out.copy_from_slice(stream);
+        Ok(())
+    }
+}
+
+/// The cipher of `etype`, if it is one that we support.
+pub(crate) fn cipher_for(etype: EncryptionType) -> Result<&'static dyn EtypeCipher, KrbError> {
+    match etype {
+        EncryptionType::AES128_CTS_HMAC_SHA1_96 => Ok(&Aes128CtsHmacSha196),
+        EncryptionType::AES256_CTS_HMAC_SHA1_96 => Ok(&Aes256CtsHmacSha196),
+        EncryptionType::AES128_CTS_HMAC_SHA256_128 => Ok(&Aes128CtsHmacSha256128),
+        EncryptionType::AES256_CTS_HMAC_SHA384_192 => Ok(&Aes256CtsHmacSha384192),
+        #[cfg(feature = "camellia")]
+        EncryptionType::CAMELLIA128_CTS_CMAC => Ok(&camellia::Camellia128CtsCmac),
+        #[cfg(feature = "camellia")]
+        EncryptionType::CAMELLIA256_CTS_CMAC => Ok(&camellia::Camellia256CtsCmac),
+        #[cfg(feature = "legacy-crypto")]
+        EncryptionType::DES3_CBC_SHA1_KD => Ok(&des3::Des3CbcSha1Kd),
+        _ => Err(KrbError::UnsupportedEncryption),
+    }
+}
+
+/// The iteration count of the PBKDF2 string-to-key. The parameter is a 4-octet
+/// big-endian count, but some KDCs send an empty string to mean the default, in
+/// which case `None` is returned. A count of zero stands for 2^32 iterations, which
+/// is refused rather than spent.
+fn s2kparams_iter_count(s2kparams: Option<&[u8]>) -> Result<Option<u32>, KrbError> {
+    match s2kparams {
+        None | Some([]) => Ok(None),
+        Some(s2kparams @ [0, 0, 0, 0]) => {
+            Err(KrbError::PreAuthInvalidS2KParams(s2kparams.to_vec()))
+        }
+        Some(&[a, b, c, d]) => Ok(Some(u32::from_be_bytes([a, b, c, d]))),
+        Some(s2kparams) => Err(KrbError::PreAuthInvalidS2KParams(s2kparams.to_vec())),
+    }
+}
+
+/// The key as the array of its enctype.
+fn key_array<const N: usize>(key: &[u8]) -> Result<&[u8; N], KrbError> {
+    key.try_into().map_err(|_| KrbError::InvalidEncryptionKey)
+}
+
+struct Aes128CtsHmacSha196;
+
+impl EtypeCipher for Aes128CtsHmacSha196 {
+    fn etype(&self) -> EncryptionType {
+        EncryptionType::AES128_CTS_HMAC_SHA1_96
+    }
+
+    fn checksum_type(&self) -> ChecksumType {
+        ChecksumType::HMAC_SHA1_96_AES128
+    }
+
+    fn key_size(&self) -> usize {
+        AES_128_KEY_LEN
+    }
+
+    fn default_iter_count(&self) -> u32 {
+        PKBDF2_SHA1_ITER
+    }
+
+    fn string_to_key(
+        &self,
+        passphrase: &[u8],
+        salt: &[u8],
+        iter_count: Option<u32>,
+    ) -> Result<Zeroizing<Vec<u8>>, KrbError> {
+        derive_key_external_salt_aes128_cts_hmac_sha1_96(passphrase, salt, iter_count)
+            .map(|k| Zeroizing::new(k.to_vec()))
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8],
+        key_usage: KeyUsage,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, KrbError> {
+        encrypt_aes128_cts_hmac_sha1_96(key_array(key)?, plaintext, key_usage)
+    }
+
+    fn decrypt(
+        &self,
+        key: &[u8],
+        key_usage: KeyUsage,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, KrbError> {
+        decrypt_aes128_cts_hmac_sha1_96(key_array(key)?, ciphertext, key_usage)
+    }
+
+    fn checksum(&self, key: &[u8], key_usage: KeyUsage, data: &[u8]) -> Result<Vec<u8>, KrbError> {
+        checksum_hmac_sha1_96_aes128(key_array(key)?, data, key_usage).map(|c| c.to_vec())
+    }
+
+    fn prf(&self, key: &[u8], input: &[u8]) -> Result<Vec<u8>, KrbError> {
+        let key: &[u8; AES_128_KEY_LEN] = key_array(key)?;
+        Ok(prf_aes_cts_hmac_sha1_96(key, input).to_vec())
+    }
+}
+
+struct Aes256CtsHmacSha196;
+
+impl EtypeCipher for Aes256CtsHmacSha196 {
+    fn etype(&self) -> EncryptionType {
+        EncryptionType::AES256_CTS_HMAC_SHA1_96
+    }
+
+    fn checksum_type(&self) -> ChecksumType {
+        ChecksumType::HMAC_SHA1_96_AES256
+    }
+
+    fn key_size(&self) -> usize {
+        AES_256_KEY_LEN
+    }
+
+    fn default_iter_count(&self) -> u32 {
+        PKBDF2_SHA1_ITER
+    }
+
+    fn string_to_key(
+        &self,
+        passphrase: &[u8],
+        salt: &[u8],
+        iter_count: Option<u32>,
+    ) -> Result<Zeroizing<Vec<u8>>, KrbError> {
+        derive_key_external_salt_aes256_cts_hmac_sha1_96(passphrase, salt, iter_count)
+            .map(|k| Zeroizing::new(k.to_vec()))
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8],
+        key_usage: KeyUsage,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, KrbError> {
+        encrypt_aes256_cts_hmac_sha1_96(key_array(key)?, plaintext, key_usage)
+    }
+
+    fn decrypt(
+        &self,
+        key: &[u8],
+        key_usage: KeyUsage,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, KrbError> {
+        decrypt_aes256_cts_hmac_sha1_96(key_array(key)?, ciphertext, key_usage)
+    }
+
+    fn checksum(&self, key: &[u8], key_usage: KeyUsage, data: &[u8]) -> Result<Vec<u8>, KrbError> {
+        checksum_hmac_sha1_96_aes256(key_array(key)?, data, key_usage).map(|c| c.to_vec())
+    }
+
+    fn prf(&self, key: &[u8], input: &[u8]) -> Result<Vec<u8>, KrbError> {
+        let key: &[u8; AES_256_KEY_LEN] = key_array(key)?;
+        Ok(prf_aes_cts_hmac_sha1_96(key, input).to_vec())
+    }
+}
+
+struct Aes128CtsHmacSha256128;
+
+impl EtypeCipher for Aes128CtsHmacSha256128 {
+    fn etype(&self) -> EncryptionType {
+        EncryptionType::AES128_CTS_HMAC_SHA256_128
+    }
+
+    fn checksum_type(&self) -> ChecksumType {
+        ChecksumType::HMAC_SHA256_128_AES128
+    }
+
+    fn key_size(&self) -> usize {
+        AES_128_KEY_LEN
+    }
+
+    fn default_iter_count(&self) -> u32 {
+        PKBDF2_SHA256_ITER
+    }
+
+    fn string_to_key(
+        &self,
+        passphrase: &[u8],
+        salt: &[u8],
+        iter_count: Option<u32>,
+    ) -> Result<Zeroizing<Vec<u8>>, KrbError> {
+        derive_key_external_salt_aes128_cts_hmac_sha256_128(passphrase, salt, iter_count)
+            .map(|k| Zeroizing::new(k.to_vec()))
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8],
+        key_usage: KeyUsage,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, KrbError> {
+        encrypt_aes128_cts_hmac_sha256_128(key_array(key)?, plaintext, key_usage)
+    }
+
+    fn decrypt(
+        &self,
+        key: &[u8],
+        key_usage: KeyUsage,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, KrbError> {
+        decrypt_aes128_cts_hmac_sha256_128(key_array(key)?, ciphertext, key_usage)
+    }
+
+    fn checksum(&self, key: &[u8], key_usage: KeyUsage, data: &[u8]) -> Result<Vec<u8>, KrbError> {
+        checksum_hmac_sha256_128_aes128(key_array(key)?, data, key_usage).map(|c| c.to_vec())
+    }
+
+    fn prf(&self, key: &[u8], input: &[u8]) -> Result<Vec<u8>, KrbError> {
+        prf_aes_cts_hmac_sha2::<AES_128_KEY_LEN, SHA256_LEN>(key_array(key)?, input)
+            .map(|prf| prf.to_vec())
+    }
+}
+
+struct Aes256CtsHmacSha384192;
+
+impl EtypeCipher for Aes256CtsHmacSha384192 {
+    fn etype(&self) -> EncryptionType {
+        EncryptionType::AES256_CTS_HMAC_SHA384_192
+    }
+
+    fn checksum_type(&self) -> ChecksumType {
+        ChecksumType::HMAC_SHA384_192_AES256
+    }
+
+    fn key_size(&self) -> usize {
+        AES_256_KEY_LEN
+    }
+
+    fn default_iter_count(&self) -> u32 {
+        PKBDF2_SHA384_ITER
+    }
+
+    fn string_to_key(
+        &self,
+        passphrase: &[u8],
+        salt: &[u8],
+        iter_count: Option<u32>,
+    ) -> Result<Zeroizing<Vec<u8>>, KrbError> {
+        derive_key_external_salt_aes256_cts_hmac_sha384_192(passphrase, salt, iter_count)
+            .map(|k| Zeroizing::new(k.to_vec()))
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8],
+        key_usage: KeyUsage,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, KrbError> {
+        encrypt_aes256_cts_hmac_sha384_192(key_array(key)?, plaintext, key_usage)
+    }
+
+    fn decrypt(
+        &self,
+        key: &[u8],
+        key_usage: KeyUsage,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, KrbError> {
+        decrypt_aes256_cts_hmac_sha384_192(key_array(key)?, ciphertext, key_usage)
+    }
+
+    fn checksum(&self, key: &[u8], key_usage: KeyUsage, data: &[u8]) -> Result<Vec<u8>, KrbError> {
+        checksum_hmac_sha384_192_aes256(key_array(key)?, data, key_usage).map(|c| c.to_vec())
+    }
+
+    fn prf(&self, key: &[u8], input: &[u8]) -> Result<Vec<u8>, KrbError> {
+        prf_aes_cts_hmac_sha2::<AES_256_KEY_LEN, SHA384_LEN>(key_array(key)?, input)
+            .map(|prf| prf.to_vec())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -486,6 +1624,160 @@ mod tests {
         )
     }
 
+    // RFC 3962 Appendix B, the 128-bit keys of the string-to-key vectors.
+    #[test]
+    fn test_aes128_cts_hmac_sha1_96_rfc3962_vectors() {
+        for (iter_count, expected) in [
+            (1, "42263c6e89f4fc28b8df68ee09799f15"),
+            (2, "c651bf29e2300ac27fa469d693bdda13"),
+            (1200, "4c01cd46d632d01e6dbe230a01ed642a"),
+        ] {
+            let out_key = derive_key_aes128_cts_hmac_sha1_96(
+                "password".as_bytes(),
+                "ATHENA.MIT.EDU".as_bytes(),
+                "raeburn".as_bytes(),
+                Some(iter_count),
+            )
+            .unwrap();
+
+            assert_eq!(out_key.as_slice(), hex::decode(expected).unwrap());
+        }
+
+        let out_key = derive_key_external_salt_aes128_cts_hmac_sha1_96(
+            "password".as_bytes(),
+            &hex::decode("1234567878563412").unwrap(),
+            Some(5),
+        )
+        .unwrap();
+
+        assert_eq!(
+            out_key.as_slice(),
+            hex::decode("e9b23d52273747dd5c35cb55be619d8e").unwrap()
+        );
+    }
+
+    // RFC 3962 Appendix B, every string-to-key vector with both key sizes. The
+    // passphrase is given as a str, so the g-clef is derived from its UTF-8 encoding.
+    #[test]
+    fn test_string_to_key_rfc3962_vectors() {
+        let vectors = [
+            // (passphrase, salt, iteration count, aes128 key, aes256 key)
+            (
+                "password".to_string(),
+                b"ATHENA.MIT.EDUraeburn".to_vec(),
+                1,
+                "42263c6e89f4fc28b8df68ee09799f15",
+                "fe697b52bc0d3ce14432ba036a92e65bbb52280990a2fa27883998d72af30161",
+            ),
+            (
+                "password".to_string(),
+                b"ATHENA.MIT.EDUraeburn".to_vec(),
+                2,
+                "c651bf29e2300ac27fa469d693bdda13",
+                "a2e16d16b36069c135d5e9d2e25f896102685618b95914b467c67622225824ff",
+            ),
+            (
+                "password".to_string(),
+                b"ATHENA.MIT.EDUraeburn".to_vec(),
+                1200,
+                "4c01cd46d632d01e6dbe230a01ed642a",
+                "55a6ac740ad17b4846941051e1e8b0a7548d93b0ab30a8bc3ff16280382b8c2a",
+            ),
+            (
+                "password".to_string(),
+                hex::decode("1234567878563412").unwrap(),
+                5,
+                "e9b23d52273747dd5c35cb55be619d8e",
+                "97a4e786be20d81a382d5ebc96d5909cabcdadc87ca48f574504159f16c36e31",
+            ),
+            (
+                "X".repeat(64),
+                b"pass phrase equals block size".to_vec(),
+                1200,
+                "59d1bb789a828b1aa54ef9c2883f69ed",
+                "89adee3608db8bc71f1bfbfe459486b05618b70cbae22092534e56c553ba4b34",
+            ),
+            (
+                "X".repeat(65),
+                b"pass phrase exceeds block size".to_vec(),
+                1200,
+                "cb8005dc5f90179a7f02104c0018751d",
+                "d78c5c9cb872a8c9dad4697f0bb5b2d21496c82beb2caeda2112fceea057401b",
+            ),
+            (
+                "\u{1D11E}".to_string(),
+                b"EXAMPLE.COMpianist".to_vec(),
+                50,
+                "f149c1f2e154a73452d43e7fe62a56e5",
+                "4b6d9839f84406df1f09cc166db4b83c571848b784a3d6bdc346589a3e393f9e",
+            ),
+        ];
+
+        for (passphrase, salt, iter_count, aes128, aes256) in vectors {
+            let out_key = derive_key_external_salt_aes128_cts_hmac_sha1_96(
+                passphrase.as_bytes(),
+                &salt,
+                Some(iter_count),
+            )
+            .unwrap();
+            assert_eq!(out_key.as_slice(), hex::decode(aes128).unwrap());
+
+            let out_key = derive_key_external_salt_aes256_cts_hmac_sha1_96(
+                passphrase.as_bytes(),
+                &salt,
+                Some(iter_count),
+            )
+            .unwrap();
+            assert_eq!(out_key.as_slice(), hex::decode(aes256).unwrap());
+        }
+
+        // The g-clef is four bytes of UTF-8, not one codepoint or two UTF-16 units.
+        assert_eq!("\u{1D11E}".as_bytes(), [0xf0, 0x9d, 0x84, 0x9e]);
+
+        // The salt of the first vectors is the default salt of the principal.
+        let out_key =
+            derive_key_aes256_cts_hmac_sha1_96(b"password", b"ATHENA.MIT.EDU", b"raeburn", Some(2))
+                .unwrap();
+        assert_eq!(
+            out_key.as_slice(),
+            hex::decode("a2e16d16b36069c135d5e9d2e25f896102685618b95914b467c67622225824ff")
+                .unwrap()
+        );
+    }
+
+    // RFC 3962 Appendix B, CTS mode of aes128 on its own. The first block of the
+    // plaintext takes the place of the confounder.
+    #[test]
+    fn test_aes128_cts_rfc3962_vectors() {
+        let key = b"chicken teriyaki";
+
+        for (plaintext, expected) in [
+            ("I would like the ", "c6353568f2bf8cb4d8a580362da7ff7f97"),
+            (
+                "I would like the General Gau's ",
+                "fc00783e0efdb2c1d445d4c8eff7ed2297687268d6ecccc0c07b25e25ecfe5",
+            ),
+            (
+                "I would like the General Gau's C",
+                "39312523a78662d5be7fcbcc98ebf5a897687268d6ecccc0c07b25e25ecfe584",
+            ),
+            (
+                "I would like the General Gau's Chicken, please,",
+                "97687268d6ecccc0c07b25e25ecfe584b3fffd940c16a18c1b5549d2f838029e39312523a78662d5be7fcbcc98ebf5",
+            ),
+        ] {
+            let (confuzzler, plaintext) = plaintext.as_bytes().split_at(AES_BLOCK_SIZE);
+
+            let mut ciphertext = vec![0u8; AES_BLOCK_SIZE + plaintext.len()];
+            encrypt_aes_cts(key, confuzzler, plaintext, &mut ciphertext).unwrap();
+            assert_eq!(ciphertext, hex::decode(expected).unwrap());
+
+            let data = decrypt_aes_cts(key, &ciphertext).unwrap();
+            assert_eq!(data[..AES_BLOCK_SIZE], *confuzzler);
+            assert_eq!(data[AES_BLOCK_SIZE..], *plaintext);
+        }
+    }
+
     #[test]
     fn test_aes256_cts_hmac_sha1_96_decrypt_1() {
         let out_key = derive_key_aes256_cts_hmac_sha1_96(
@@ -503,7 +1795,7 @@ mod tests {
             0x16, 0x5e, 0xbb, 0x27, 0xc0, 0xd7, 0xce, 0x9b, 0x5a, 0xec, 0x7a,
         ];
 
-        let key_usage = 1;
+        let key_usage = KeyUsage::AS_REQ_PA_ENC_TIMESTAMP;
 
         let data = decrypt_aes256_cts_hmac_sha1_96(&out_key, &input_data, key_usage).unwrap();
 
@@ -516,6 +1808,101 @@ mod tests {
         );
     }
 
+    // RFC 3962 only has vectors of CTS on its own, so these were computed with an
+    // independent implementation, using the keys of its string-to-key vectors with an
+    // iteration count of 1.
+    #[test]
+    fn test_aes_cts_hmac_sha1_96_fixed_confounder() {
+        let confounder: [u8; AES_BLOCK_SIZE] = core::array::from_fn(|i| i as u8);
+
+        let key: [u8; AES_128_KEY_LEN] = hex::decode("42263c6e89f4fc28b8df68ee09799f15")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        for (len, expected) in [
+            (6, "b71ed798564c18919e05e046ed86883d7e8ebc774f65f319ead2178f1941c8814f88"),
+            (16, "81a746f7f6a7f2356dea4f625e8abba97e8ebc774f65b0a38c5b7eb2ad0bc5912b9ffca03e043a7ce89f3e4a"),
+            (21, "7e8ebc774f65b0a38c5b7eb2ad0bc591727de25f4288ff18c9dd41b5a1f7d36681a746f7f6e15feca0f64ed0391a62b3aa"),
+        ] {
+            let plaintext: Vec<u8> = (0..len).collect();
+            let ciphertext = encrypt_aes_cts_hmac_sha1_96(
+                &key,
+                &confounder,
+                &plaintext,
+                KeyUsage::KDC_REP_TICKET,
+            )
+            .unwrap();
+            assert_eq!(ciphertext, hex::decode(expected).unwrap());
+
+            let data =
+                decrypt_aes128_cts_hmac_sha1_96(&key, &ciphertext, KeyUsage::KDC_REP_TICKET)
+                    .unwrap();
+            assert_eq!(data, plaintext);
+        }
+
+        let key: [u8; AES_256_KEY_LEN] =
+            hex::decode("fe697b52bc0d3ce14432ba036a92e65bbb52280990a2fa27883998d72af30161")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        for (len, expected) in [
+            (6, "4ba53cc71daa181c660ec50a872821f7d5847651823eee4f4c5094b032bed7ca6844"),
+            (16, "1cdc17bd5270bd30f986d01d5d1feb5bd5847651823e0b17775f29e097ff79537022c0887d2d7c4643d1e922"),
+            (21, "d5847651823e0b17775f29e097ff795362f3cfcc6030746d9442e72485b7406b1cdc17bd52e7be3f1c3a37845f46b6988c"),
+        ] {
+            let plaintext: Vec<u8> = (0..len).collect();
+            let ciphertext = encrypt_aes_cts_hmac_sha1_96(
+                &key,
+                &confounder,
+                &plaintext,
+                KeyUsage::KDC_REP_TICKET,
+            )
+            .unwrap();
+            assert_eq!(ciphertext, hex::decode(expected).unwrap());
+
+            let data =
+                decrypt_aes256_cts_hmac_sha1_96(&key, &ciphertext, KeyUsage::KDC_REP_TICKET)
+                    .unwrap();
+            assert_eq!(data, plaintext);
+        }
+    }
+
+    // The capture of test_aes256_cts_hmac_sha1_96_decrypt_1 was sent with a confounder
+    // of zeros, so it can be encrypted again bit for bit.
+    #[test]
+    fn test_aes256_cts_hmac_sha1_96_reencrypt_capture() {
+        let out_key = derive_key_aes256_cts_hmac_sha1_96(
+            "admin".as_bytes(),
+            "admin".as_bytes(),
+            "1234".as_bytes(),
+            None,
+        )
+        .unwrap();
+
+        let plaintext = [
+            0x33, 0x61, 0x68, 0x77, 0x7a, 0x74, 0x39, 0x4d, 0x47, 0x39, 0x57, 0x56, 0x45, 0x75,
+            0x42, 0x56, 0x43, 0x35, 0x6a, 0x30, 0x6f, 0x69, 0x36, 0x73, 0x49,
+        ];
+
+        let ciphertext = encrypt_aes_cts_hmac_sha1_96(
+            &out_key,
+            &[0u8; AES_BLOCK_SIZE],
+            &plaintext,
+            KeyUsage::AS_REQ_PA_ENC_TIMESTAMP,
+        )
+        .unwrap();
+
+        assert_eq!(
+            ciphertext,
+            [
+                0x29, 0x73, 0x7f, 0x3d, 0xb6, 0xbc, 0xdf, 0xe9, 0x99, 0x0f, 0xb2, 0x13, 0x6d, 0x3e,
+                0xfe, 0x6f, 0x21, 0x00, 0xe6, 0xc4, 0xac, 0x75, 0x82, 0x42, 0x99, 0xd8, 0xd3, 0x70,
+                0x2f, 0x5a, 0x2e, 0x31, 0xc7, 0xa3, 0x36, 0x74, 0x7d, 0xfd, 0x73, 0x4a, 0x1e, 0xa0,
+                0x16, 0x5e, 0xbb, 0x27, 0xc0, 0xd7, 0xce, 0x9b, 0x5a, 0xec, 0x7a,
+            ]
+        );
+    }
+
     #[test]
     fn test_aes256_cts_hmac_sha1_96_decrypt_2() {
         let out_key = derive_key_aes256_cts_hmac_sha1_96(
@@ -533,7 +1920,7 @@ mod tests {
             0xd3,
         ];
 
-        let key_usage = 2;
+        let key_usage = KeyUsage::KDC_REP_TICKET;
 
         let data = decrypt_aes256_cts_hmac_sha1_96(&out_key, &input_data, key_usage).unwrap();
 
@@ -558,7 +1945,7 @@ mod tests {
 
         let input_data = [0xffu8; 32];
 
-        let key_usage = 2;
+        let key_usage = KeyUsage::KDC_REP_TICKET;
 
         let enc_data = encrypt_aes256_cts_hmac_sha1_96(&out_key, &input_data, key_usage).unwrap();
 
@@ -580,7 +1967,7 @@ mod tests {
         // Half an aes block size
         let input_data = [0xaau8; 8];
 
-        let key_usage = 3;
+        let key_usage = KeyUsage::AS_REP_ENC_PART;
 
         let enc_data = encrypt_aes256_cts_hmac_sha1_96(&out_key, &input_data, key_usage).unwrap();
 
@@ -602,7 +1989,7 @@ mod tests {
         // Exactly one block size
         let input_data = [0x55u8; 16];
 
-        let key_usage = 4;
+        let key_usage = KeyUsage::TGS_REQ_AUTH_DATA_SESSION;
 
         let enc_data = encrypt_aes256_cts_hmac_sha1_96(&out_key, &input_data, key_usage).unwrap();
 
@@ -624,7 +2011,7 @@ mod tests {
         // Multiple blocks, not aligned
         let input_data = [0xbbu8; 49];
 
-        let key_usage = 5;
+        let key_usage = KeyUsage::TGS_REQ_AUTH_DATA_SUBKEY;
 
         let enc_data = encrypt_aes256_cts_hmac_sha1_96(&out_key, &input_data, key_usage).unwrap();
 
@@ -633,6 +2020,581 @@ mod tests {
         assert_eq!(data, input_data);
     }
 
+    #[test]
+    fn test_aes128_cts_hmac_sha1_96_reflexive() {
+        let out_key = derive_key_aes128_cts_hmac_sha1_96(
+            "test".as_bytes(),
+            "test".as_bytes(),
+            "1234".as_bytes(),
+            None,
+        )
+        .unwrap();
+
+        // Multiple blocks, not aligned
+        let input_data = [0xbbu8; 49];
+
+        let key_usage = KeyUsage::TGS_REQ_AUTH_DATA_SUBKEY;
+
+        let enc_data = encrypt_aes128_cts_hmac_sha1_96(&out_key, &input_data, key_usage).unwrap();
+
+        let data = decrypt_aes128_cts_hmac_sha1_96(&out_key, &enc_data, key_usage).unwrap();
+
+        assert_eq!(data, input_data);
+    }
+
+    #[test]
+    fn test_nfold_rfc3961() {
+        // RFC 3961 A.1
+        for (input, out_len, expect) in [
+            (b"012345".as_slice(), 8, "be072631276b1955"),
+            (b"password", 7, "78a07b6caf85fa"),
+            (b"Rough Consensus, and Running Code", 8, "bb6ed30870b7f0e0"),
+            (
+                b"password",
+                21,
+                "59e4a8ca7c0385c3c37b3f6d2000247cb6e6bd5b3e",
+            ),
+            (
+                b"MASSACHVSETTS INSTITVTE OF TECHNOLOGY",
+                24,
+                "db3b0d8f0b061e603282b308a50841229ad798fab9540c1b",
+            ),
+            (b"Q", 21, "518a54a215a8452a518a54a215a8452a518a54a215"),
+            (b"ba", 21, "fb25d531ae8974499f52fd92ea9857c4ba24cf297e"),
+            (b"kerberos", 8, "6b65726265726f73"),
+            (b"kerberos", 16, "6b65726265726f737b9b5b2b93132b93"),
+            (
+                b"kerberos",
+                21,
+                "8372c236344e5f1550cd0747e15d62ca7a5a3bcea4",
+            ),
+            (
+                b"kerberos",
+                32,
+                "6b65726265726f737b9b5b2b93132b935c9bdcdad95c9899c4cae4dee6d6cae4",
+            ),
+        ] {
+            assert_eq!(hex::encode(nfold(input, out_len)), expect);
+        }
+
+        // The precomputed key usage constants are the n-fold of the usage and the
+        // constant of the key.
+        assert_eq!(dk_constant(&[0, 0, 0, 4, 0x99]), N_FOLD_KEY_USAGE_KC_04);
+        assert_eq!(dk_constant(&[0, 0, 0, 3, 0xAA]), N_FOLD_KEY_USAGE_KE_03);
+        assert_eq!(dk_constant(&[0, 0, 0, 3, 0x55]), N_FOLD_KEY_USAGE_KI_03);
+        assert_eq!(
+            hex::encode(dk_constant(b"prf")),
+            "c08be52122f8f023398908fdbc08ce21"
+        );
+    }
+
+    // From the MIT krb5 checksum test vectors, t_cksums.c
+    #[test]
+    fn test_checksum_hmac_sha1_96_aes256() {
+        let key = hex::decode("b1ae4cd8462aff1677053cc9279aac30b796fb81ce21474dd3ddbcfea4ec76d7")
+            .unwrap();
+        let key: [u8; AES_256_KEY_LEN] = key.try_into().unwrap();
+
+        let checksum = checksum_hmac_sha1_96_aes256(
+            &key,
+            "fourteen".as_bytes(),
+            KeyUsage::TGS_REQ_AUTH_DATA_SESSION,
+        )
+        .unwrap();
+
+        assert_eq!(
+            checksum.as_slice(),
+            hex::decode("e08739e3279e2903ec8e3836").unwrap()
+        );
+
+        // A key usage without a precomputed constant is n-folded.
+        let checksum =
+            checksum_hmac_sha1_96_aes256(&key, "fourteen".as_bytes(), KeyUsage::AS_REQ).unwrap();
+
+        assert_eq!(
+            checksum.as_slice(),
+            hex::decode("ea9d7db61c555114a2a07700").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_checksum_hmac_md5() {
+        // Computed with the RFC 4757 construction, keyed with the AES256 key above as
+        // it would be for a PA-FOR-USER with an AES session key.
+        let key = hex::decode("b1ae4cd8462aff1677053cc9279aac30b796fb81ce21474dd3ddbcfea4ec76d7")
+            .unwrap();
+
+        let checksum = checksum_hmac_md5(
+            &key,
+            "fourteen".as_bytes(),
+            KeyUsage::KERB_NON_KERB_CKSUM_SALT,
+        )
+        .unwrap();
+
+        assert_eq!(
+            checksum.as_slice(),
+            hex::decode("14f4053b9ecf7e92d34cc03a662e86f5").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_checksum_fast_key_usage() {
+        // KEY_USAGE_FAST_REQ_CHKSUM
+        let key: [u8; AES_256_KEY_LEN] =
+            hex::decode("b1ae4cd8462aff1677053cc9279aac30b796fb81ce21474dd3ddbcfea4ec76d7")
+                .unwrap()
+                .try_into()
+                .unwrap();
+
+        let checksum =
+            checksum_hmac_sha1_96_aes256(&key, "fourteen".as_bytes(), KeyUsage::FAST_REQ_CHKSUM)
+                .unwrap();
+
+        assert_eq!(
+            checksum.as_slice(),
+            hex::decode("4049b91229c96fa8c6ee8b66").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_krb_fx_cf2_aes256_cts_hmac_sha1_96() {
+        // From the MIT KRB5 t_cf2 tests, where each key is derived with its password
+        // as the salt.
+        let key1 =
+            derive_key_external_salt_aes256_cts_hmac_sha1_96(b"key1", b"key1", None).unwrap();
+        let key2 =
+            derive_key_external_salt_aes256_cts_hmac_sha1_96(b"key2", b"key2", None).unwrap();
+
+        // KRB-FX-CF2 is the xor of PRF+ of each key.
+        let cipher = cipher_for(EncryptionType::AES256_CTS_HMAC_SHA1_96).unwrap();
+        let mut out = [0u8; AES_256_KEY_LEN];
+        let mut prf2 = [0u8; AES_256_KEY_LEN];
+        cipher.prf_plus(&key1, b"a", &mut out).unwrap();
+        cipher.prf_plus(&key2, b"b", &mut prf2).unwrap();
+        out.iter_mut().zip(prf2).for_each(|(a, b)| *a ^= b);
+
+        assert_eq!(
+            out.as_slice(),
+            hex::decode("4d6ca4e629785c1f01baf55e2e548566b9617ae3a96868c337cb93b5e72b1c7b")
+                .unwrap()
+        );
+    }
+
+    // RFC 8009 Appendix A
+    #[test]
+    fn test_aes256_cts_hmac_sha384_192_rfc8009_string_to_key() {
+        let mut salt = hex::decode("10DF9DD783E5BC8ACEA1730E74355F61").unwrap();
+        salt.extend_from_slice(b"ATHENA.MIT.EDUraeburn");
+
+        let key =
+            derive_key_external_salt_aes256_cts_hmac_sha384_192(b"password", &salt, Some(32768))
+                .unwrap();
+
+        assert_eq!(
+            key.as_slice(),
+            hex::decode("45BD806DBF6A833A9CFFC1C94589A222367A79BC21C413718906E9F578A78467")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_aes256_cts_hmac_sha384_192_rfc8009_key_derivation() {
+        let key: [u8; AES_256_KEY_LEN] =
+            hex::decode("6D404D37FAF79F9DF0D33568D320669800EB4836472EA8A026D16B7182460C52")
+                .unwrap()
+                .try_into()
+                .unwrap();
+
+        let kc: [u8; SHA384_HMAC_LEN] =
+            kdf_key_usage_aes_sha2(&key, KeyUsage::KDC_REP_TICKET, 0x99).unwrap();
+        let ke: [u8; AES_256_KEY_LEN] =
+            kdf_key_usage_aes_sha2(&key, KeyUsage::KDC_REP_TICKET, 0xAA).unwrap();
+        let ki: [u8; SHA384_HMAC_LEN] =
+            kdf_key_usage_aes_sha2(&key, KeyUsage::KDC_REP_TICKET, 0x55).unwrap();
+
+        assert_eq!(
+            kc.as_slice(),
+            hex::decode("EF5718BE86CC84963D8BBB5031E9F5C4BA41F28FAF69E73D").unwrap()
+        );
+        assert_eq!(
+            ke.as_slice(),
+            hex::decode("56AB22BEE63D82D7BC5227F6773F8EA7A5EB1C825160C38312980C442E5C7E49")
+                .unwrap()
+        );
+        assert_eq!(
+            ki.as_slice(),
+            hex::decode("69B16514E3CD8E56B82010D5C73012B622C4D00FFC23ED1F").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_aes256_cts_hmac_sha384_192_rfc8009_vectors() {
+        let key: [u8; AES_256_KEY_LEN] =
+            hex::decode("6D404D37FAF79F9DF0D33568D320669800EB4836472EA8A026D16B7182460C52")
+                .unwrap()
+                .try_into()
+                .unwrap();
+
+        // (plaintext length, confounder, ciphertext)
+        let vectors = [
+            (
+                0,
+                "F764E9FA15C276478B2C7D0C4E5F58E4",
+                "41F53FA5BFE7026D91FAF9BE959195A058707273A96A40F0A01960621AC612748B9BBFBE7EB4CE3C",
+            ),
+            (
+                6,
+                "B80D3251C1F6471494256FFE712D0B9A",
+                "4ED7B37C2BCAC8F74F23C1CF07E62BC7B75FB3F637B9F559C7F664F69EAB7B6092237526EA0D1F61CB20D69D10F2",
+            ),
+            (
+                16,
+                "53BF8A0D105265D4E276428624CE5E63",
+                "BC47FFEC7998EB91E8115CF8D19DAC4BBBE2E163E87DD37F49BECA92027764F68CF51F14D798C2273F35DF574D1F932E40C4FF255B36A266",
+            ),
+            (
+                21,
+                "763E65367E864F02F55153C7E3B58AF1",
+                "40013E2DF58E8751957D2878BCD2D6FE101CCFD556CB1EAE79DB3C3EE86429F2B2A602AC86FEF6ECB647D6295FAE077A1FEB517508D2C16B4192E01F62",
+            ),
+        ];
+
+        for (len, confounder, expected) in vectors {
+            let plaintext: Vec<u8> = (0..len).collect();
+            let confounder: [u8; AES_BLOCK_SIZE] =
+                hex::decode(confounder).unwrap().try_into().unwrap();
+            let expected = hex::decode(expected).unwrap();
+
+            let ciphertext = encrypt_aes_cts_hmac_sha2::<AES_256_KEY_LEN, SHA384_HMAC_LEN>(
+                &key,
+                &confounder,
+                &plaintext,
+                KeyUsage::KDC_REP_TICKET,
+            )
+            .unwrap();
+            assert_eq!(ciphertext, expected);
+
+            let data =
+                decrypt_aes256_cts_hmac_sha384_192(&key, &ciphertext, KeyUsage::KDC_REP_TICKET)
+                    .unwrap();
+            assert_eq!(data, plaintext);
+        }
+
+        // A different key usage must not authenticate.
+        let ciphertext =
+            encrypt_aes256_cts_hmac_sha384_192(&key, b"fourteen", KeyUsage::KDC_REP_TICKET)
+                .unwrap();
+        assert!(matches!(
+            decrypt_aes256_cts_hmac_sha384_192(&key, &ciphertext, KeyUsage::AS_REP_ENC_PART),
+            Err(KrbError::MessageAuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_checksum_hmac_sha384_192_aes256() {
+        let key: [u8; AES_256_KEY_LEN] =
+            hex::decode("6D404D37FAF79F9DF0D33568D320669800EB4836472EA8A026D16B7182460C52")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let data: Vec<u8> = (0..21).collect();
+
+        let checksum =
+            checksum_hmac_sha384_192_aes256(&key, &data, KeyUsage::KDC_REP_TICKET).unwrap();
+
+        assert_eq!(
+            checksum.as_slice(),
+            hex::decode("45EE791567EEFCA37F4AC1E0222DE80D43C3BFA06699672A").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_prf_aes256_cts_hmac_sha384_192() {
+        let key: [u8; AES_256_KEY_LEN] =
+            hex::decode("6D404D37FAF79F9DF0D33568D320669800EB4836472EA8A026D16B7182460C52")
+                .unwrap()
+                .try_into()
+                .unwrap();
+
+        let prf: [u8; SHA384_LEN] = prf_aes_cts_hmac_sha2(&key, b"test").unwrap();
+
+        assert_eq!(
+            prf.as_slice(),
+            hex::decode(
+                "9801F69A368C2BF675E59521E177D9A07F67EFE1CFDE8D3C8D6F6A0256E3B17DB3C1B62AD1B8553360D17367EB1514D2"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_aes128_cts_hmac_sha256_128_rfc8009_string_to_key() {
+        let mut salt = hex::decode("10DF9DD783E5BC8ACEA1730E74355F61").unwrap();
+        salt.extend_from_slice(b"ATHENA.MIT.EDUraeburn");
+
+        let key =
+            derive_key_external_salt_aes128_cts_hmac_sha256_128(b"password", &salt, Some(32768))
+                .unwrap();
+
+        assert_eq!(
+            key.as_slice(),
+            hex::decode("089BCA48B105EA6EA77CA5D2F39DC5E7").unwrap()
+        );
+
+        // The iteration count defaults to 32768 rather than the 4096 of RFC 3962.
+        assert_eq!(
+            derive_key_external_salt_aes128_cts_hmac_sha256_128(b"password", &salt, None).unwrap(),
+            key
+        );
+    }
+
+    #[test]
+    fn test_aes128_cts_hmac_sha256_128_rfc8009_key_derivation() {
+        let key: [u8; AES_128_KEY_LEN] = hex::decode("3705D96080C17728A0E800EAB6E0D23C")
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        let kc: [u8; SHA256_HMAC_LEN] =
+            kdf_key_usage_aes_sha2(&key, KeyUsage::KDC_REP_TICKET, 0x99).unwrap();
+        let ke: [u8; AES_128_KEY_LEN] =
+            kdf_key_usage_aes_sha2(&key, KeyUsage::KDC_REP_TICKET, 0xAA).unwrap();
+        let ki: [u8; SHA256_HMAC_LEN] =
+            kdf_key_usage_aes_sha2(&key, KeyUsage::KDC_REP_TICKET, 0x55).unwrap();
+
+        assert_eq!(
+            kc.as_slice(),
+            hex::decode("B31A018A48F54776F403E9A396325DC3").unwrap()
+        );
+        assert_eq!(
+            ke.as_slice(),
+            hex::decode("9B197DD1E8C5609D6E67C3E37C62C72E").unwrap()
+        );
+        assert_eq!(
+            ki.as_slice(),
+            hex::decode("9FDA0E56AB2D85E1569A688696C26A6C").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_aes128_cts_hmac_sha256_128_rfc8009_vectors() {
+        let key: [u8; AES_128_KEY_LEN] = hex::decode("3705D96080C17728A0E800EAB6E0D23C")
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        // (plaintext length, confounder, ciphertext)
+        let vectors = [
+            (
+                0,
+                "7E5895EAF2672435BAD817F545A37148",
+                "EF85FB890BB8472F4DAB20394DCA781DAD877EDA39D50C870C0D5A0A8E48C718",
+            ),
+            (
+                6,
+                "7BCA285E2FD4130FB55B1A5C83BC5B24",
+                "84D7F30754ED987BAB0BF3506BEB09CFB55402CEF7E6877CE99E247E52D16ED4421DFDF8976C",
+            ),
+            (
+                16,
+                "56AB21713FF62C0A1457200F6FA9948F",
+                "3517D640F50DDC8AD3628722B3569D2AE07493FA8263254080EA65C1008E8FC295FB4852E7D83E1E7C48C37EEBE6B0D3",
+            ),
+            (
+                21,
+                "A7A4E29A4728CE10664FB64E49AD3FAC",
+                "720F73B18D9859CD6CCB4346115CD336C70F58EDC0C4437C5573544C31C813BCE1E6D072C186B39A413C2F92CA9B8334A287FFCBFC",
+            ),
+        ];
+
+        for (len, confounder, expected) in vectors {
+            let plaintext: Vec<u8> = (0..len).collect();
+            let confounder: [u8; AES_BLOCK_SIZE] =
+                hex::decode(confounder).unwrap().try_into().unwrap();
+            let expected = hex::decode(expected).unwrap();
+
+            let ciphertext = encrypt_aes_cts_hmac_sha2::<AES_128_KEY_LEN, SHA256_HMAC_LEN>(
+                &key,
+                &confounder,
+                &plaintext,
+                KeyUsage::KDC_REP_TICKET,
+            )
+            .unwrap();
+            assert_eq!(ciphertext, expected);
+
+            let data =
+                decrypt_aes128_cts_hmac_sha256_128(&key, &ciphertext, KeyUsage::KDC_REP_TICKET)
+                    .unwrap();
+            assert_eq!(data, plaintext);
+        }
+
+        let ciphertext =
+            encrypt_aes128_cts_hmac_sha256_128(&key, b"fourteen", KeyUsage::KDC_REP_TICKET)
+                .unwrap();
+        assert!(matches!(
+            decrypt_aes128_cts_hmac_sha256_128(&key, &ciphertext, KeyUsage::AS_REP_ENC_PART),
+            Err(KrbError::MessageAuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_checksum_hmac_sha256_128_aes128() {
+        let key: [u8; AES_128_KEY_LEN] = hex::decode("3705D96080C17728A0E800EAB6E0D23C")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let data: Vec<u8> = (0..21).collect();
+
+        let checksum =
+            checksum_hmac_sha256_128_aes128(&key, &data, KeyUsage::KDC_REP_TICKET).unwrap();
+
+        assert_eq!(
+            checksum.as_slice(),
+            hex::decode("D78367186643D67B411CBA9139FC1DEE").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_prf_aes128_cts_hmac_sha256_128() {
+        let key: [u8; AES_128_KEY_LEN] = hex::decode("3705D96080C17728A0E800EAB6E0D23C")
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        let prf: [u8; SHA256_LEN] = prf_aes_cts_hmac_sha2(&key, b"test").unwrap();
+
+        assert_eq!(
+            prf.as_slice(),
+            hex::decode("9D188616F63852FE86915BB840B4A886FF3E6BB0F819B49B893393D393854295")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_etype_cipher_conformance() {
+        for (etype, key_size, checksum_len, iter_count) in [
+            (EncryptionType::AES128_CTS_HMAC_SHA1_96, 16, 12, 4096),
+            (EncryptionType::AES256_CTS_HMAC_SHA1_96, 32, 12, 4096),
+            (EncryptionType::AES128_CTS_HMAC_SHA256_128, 16, 16, 32768),
+            (EncryptionType::AES256_CTS_HMAC_SHA384_192, 32, 24, 32768),
+        ] {
+            let cipher = cipher_for(etype).unwrap();
+            assert_eq!(cipher.etype(), etype);
+            assert_eq!(cipher.key_size(), key_size);
+            assert_eq!(cipher.default_iter_count(), iter_count);
+
+            let key = cipher
+                .string_to_key(b"password", b"EXAMPLE.COMtestuser", Some(1))
+                .unwrap();
+            assert_eq!(key.len(), key_size);
+
+            // Every etype takes a confounder and appends its checksum.
+            let ciphertext = cipher
+                .encrypt(&key, KeyUsage::AS_REP_ENC_PART, b"fourteen")
+                .unwrap();
+            assert_eq!(
+                ciphertext.len(),
+                AES_BLOCK_SIZE + b"fourteen".len() + checksum_len
+            );
+            assert_eq!(
+                cipher
+                    .decrypt(&key, KeyUsage::AS_REP_ENC_PART, &ciphertext)
+                    .unwrap(),
+                b"fourteen"
+            );
+            assert!(matches!(
+                cipher.decrypt(&key, KeyUsage::TGS_REQ_AUTH_DATA_SESSION, &ciphertext),
+                Err(KrbError::MessageAuthenticationFailed)
+            ));
+
+            // Any single bit flipped in the tag fails to authenticate.
+            for bit in (ciphertext.len() - checksum_len) * 8..ciphertext.len() * 8 {
+                let mut forged = ciphertext.clone();
+                forged[bit / 8] ^= 1 << (bit % 8);
+                assert!(matches!(
+                    cipher.decrypt(&key, KeyUsage::AS_REP_ENC_PART, &forged),
+                    Err(KrbError::MessageAuthenticationFailed)
+                ));
+            }
+
+            let checksum = cipher
+                .checksum(&key, KeyUsage::TGS_REQ_AUTH_DATA_SESSION, b"fourteen")
+                .unwrap();
+            assert_eq!(checksum.len(), checksum_len);
+
+            assert_eq!(cipher.random_to_key(&key).unwrap(), key);
+            assert!(matches!(
+                cipher.random_to_key(&key[1..]),
+                Err(KrbError::InvalidEncryptionKey)
+            ));
+            assert!(matches!(
+                cipher.encrypt(&key[1..], KeyUsage::AS_REP_ENC_PART, b"fourteen"),
+                Err(KrbError::InvalidEncryptionKey)
+            ));
+        }
+
+        assert!(matches!(
+            cipher_for(EncryptionType::DES_CBC_MD5),
+            Err(KrbError::UnsupportedEncryption)
+        ));
+    }
+
+    #[test]
+    fn test_etype_cipher_aes256_cts_hmac_sha1_96() {
+        // The cipher gives the same results as the aes256 functions it wraps.
+        let cipher = cipher_for(EncryptionType::AES256_CTS_HMAC_SHA1_96).unwrap();
+
+        let key = cipher
+            .string_to_key(b"admin", &default_salt(b"admin", b"1234"), None)
+            .unwrap();
+        let out_key =
+            derive_key_aes256_cts_hmac_sha1_96(b"admin", b"admin", b"1234", None).unwrap();
+        assert_eq!(key.as_slice(), out_key);
+
+        let input_data = [
+            0x29, 0x73, 0x7f, 0x3d, 0xb6, 0xbc, 0xdf, 0xe9, 0x99, 0x0f, 0xb2, 0x13, 0x6d, 0x3e,
+            0xfe, 0x6f, 0x21, 0x00, 0xe6, 0xc4, 0xac, 0x75, 0x82, 0x42, 0x99, 0xd8, 0xd3, 0x70,
+            0x2f, 0x5a, 0x2e, 0x31, 0xc7, 0xa3, 0x36, 0x74, 0x7d, 0xfd, 0x73, 0x4a, 0x1e, 0xa0,
+            0x16, 0x5e, 0xbb, 0x27, 0xc0, 0xd7, 0xce, 0x9b, 0x5a, 0xec, 0x7a,
+        ];
+        assert_eq!(
+            cipher
+                .decrypt(&key, KeyUsage::AS_REQ_PA_ENC_TIMESTAMP, &input_data)
+                .unwrap(),
+            decrypt_aes256_cts_hmac_sha1_96(
+                &out_key,
+                &input_data,
+                KeyUsage::AS_REQ_PA_ENC_TIMESTAMP
+            )
+            .unwrap()
+        );
+
+        // From the MIT krb5 checksum test vectors, t_cksums.c
+        let key = hex::decode("b1ae4cd8462aff1677053cc9279aac30b796fb81ce21474dd3ddbcfea4ec76d7")
+            .unwrap();
+        assert_eq!(cipher.checksum_type(), ChecksumType::HMAC_SHA1_96_AES256);
+        assert_eq!(
+            cipher
+                .checksum(&key, KeyUsage::TGS_REQ_AUTH_DATA_SESSION, b"fourteen")
+                .unwrap(),
+            hex::decode("e08739e3279e2903ec8e3836").unwrap()
+        );
+    }
+
+    #[cfg(feature = "pkinit")]
+    #[test]
+    fn test_octetstring2key_aes256_cts_hmac_sha1_96() {
+        let x: Vec<u8> = (0..=255).collect();
+
+        let out: [u8; AES_256_KEY_LEN] = octetstring2key_aes_cts_hmac_sha1_96(&x);
+
+        assert_eq!(
+            out.as_slice(),
+            hex::decode("2ec6e9558abab677324cc5df9467dbfd127d18944032d3974735d24163c65292")
+                .unwrap()
+        );
+    }
+
     #[test]
     fn test_aes256_cts_hmac_sha1_pa_enc_timestamp_decrypt() {
         let enc_data = hex::decode("b736f4dba847718b9f634b7ac94d5d691663164d877a0d875b94f786222ae9dca8cf68a972cfe6b5bec1c29682ec3c507307e7c32eedc032")
@@ -645,7 +2607,7 @@ mod tests {
         )
         .unwrap();
 
-        let key_usage = 1;
+        let key_usage = KeyUsage::AS_REQ_PA_ENC_TIMESTAMP;
 
         let data = decrypt_aes256_cts_hmac_sha1_96(&out_key, &enc_data, key_usage).unwrap();
 
@@ -655,4 +2617,62 @@ mod tests {
 
         eprintln!("{:?}", pa_enc_ts_enc);
     }
+
+    #[test]
+    fn test_s2kparams_iter_count() {
+        assert!(matches!(s2kparams_iter_count(None), Ok(None)));
+        // An empty parameter means the default iteration count.
+        assert!(matches!(s2kparams_iter_count(Some(&[])), Ok(None)));
+        assert!(matches!(
+            s2kparams_iter_count(Some(&[0x00, 0x00, 0x08, 0x00])),
+            Ok(Some(2048))
+        ));
+        assert!(matches!(
+            s2kparams_iter_count(Some(&[0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x01])),
+            Err(KrbError::PreAuthInvalidS2KParams(params)) if params.len() == 7
+        ));
+        // Zero is 2^32 iterations.
+        assert!(matches!(
+            s2kparams_iter_count(Some(&[0x00, 0x00, 0x00, 0x00])),
+            Err(KrbError::PreAuthInvalidS2KParams(_))
+        ));
+    }
+
+    #[test]
+    fn test_default_salt() {
+        assert_eq!(
+            default_salt(b"EXAMPLE.COM", b"testuser"),
+            b"EXAMPLE.COMtestuser"
+        );
+        assert_eq!(
+            default_salt(b"EXAMPLE.COM", b"host/node1.example.com"),
+            b"EXAMPLE.COMhostnode1.example.com"
+        );
+        assert_eq!(
+            default_salt(b"EXAMPLE.COM", b"HTTP/web.example.com/extra"),
+            b"EXAMPLE.COMHTTPweb.example.comextra"
+        );
+        // The realm is used as it is, the caller has the canonical case.
+        assert_eq!(
+            default_salt(b"example.com", b"testuser"),
+            b"example.comtestuser"
+        );
+
+        // A multi component principal derives the key of its default salt, not of
+        // the name with its separators.
+        let out_key = derive_key_aes256_cts_hmac_sha1_96(
+            b"password",
+            b"EXAMPLE.COM",
+            b"host/node1.example.com",
+            Some(1),
+        )
+        .unwrap();
+        let expected = derive_key_external_salt_aes256_cts_hmac_sha1_96(
+            b"password",
+            b"EXAMPLE.COMhostnode1.example.com",
+            Some(1),
+        )
+        .unwrap();
+        assert_eq!(out_key, expected);
+    }
 }
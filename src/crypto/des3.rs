@@ -0,0 +1,438 @@
+//! des3-cbc-sha1-kd (RFC 3961 6.3). Triple DES is deprecated by RFC 8429, and is only
+//! here to talk to systems that have no newer keys.
+
+use super::{key_array, key_usage_label, nfold, EtypeCipher, HmacSha1};
+use crate::asn1::constants::checksum_types::ChecksumType;
+use crate::constants::*;
+use crate::error::KrbError;
+use crate::proto::{EncryptionType, KeyUsage};
+
+use der::{Encode, Reader, SliceReader};
+use des::cipher::generic_array::GenericArray;
+use des::cipher::{BlockDecrypt, BlockEncrypt, BlockSizeUser, KeyInit};
+use des::TdesEde3;
+use hmac::Mac;
+use rand::{thread_rng, Rng};
+use sha1::{Digest, Sha1};
+use zeroize::Zeroizing;
+
+type Des3Block = GenericArray<u8, <TdesEde3 as BlockSizeUser>::BlockSize>;
+
+/// random-to-key, which spreads the 168 bits of `random` over the three DES keys.
+/// The low bit of every byte is replaced by odd parity, so the low bits of the first
+/// seven bytes of each key are moved to its eighth byte.
+fn random_to_key(random: &[u8; DES3_SEED_LEN]) -> [u8; DES3_KEY_LEN] {
+    let mut key = [0u8; DES3_KEY_LEN];
+    for (seed, des_key) in random.chunks_exact(7).zip(key.chunks_exact_mut(8)) {
+        des_key[..7].copy_from_slice(seed);
+        des_key[7] = seed
+            .iter()
+            .enumerate()
+            .fold(0, |low_bits, (i, b)| low_bits | ((b & 1) << (i + 1)));
+
+        for b in des_key.iter_mut() {
+            *b = (*b & 0xfe) | u8::from((*b & 0xfe).count_ones() % 2 == 0);
+        }
+    }
+    key
+}
+
+/// RFC 3961 DK with triple DES. Unlike aes the output of DR is one block short of a
+/// multiple of the block size, and random-to-key adds the parity bits.
+fn dk(key: &[u8; DES3_KEY_LEN], constant: &[u8]) -> [u8; DES3_KEY_LEN] {
+    let cipher = TdesEde3::new(key.into());
+    let mut block = Des3Block::default();
+    block.copy_from_slice(&nfold(constant, DES3_BLOCK_SIZE));
+
+    let mut random = Zeroizing::new([0u8; DES3_SEED_LEN]);
+    for chunk in random.chunks_mut(DES3_BLOCK_SIZE) {
+        cipher.encrypt_block(&mut block);
+        chunk.copy_from_slice(&block[..chunk.len()]);
+    }
+    random_to_key(&random)
+}
+
+// CBC with an IV of zeros over whole blocks, as there's no ciphertext stealing.
+fn cbc_encrypt(key: &[u8; DES3_KEY_LEN], data: &mut [u8]) {
+    let cipher = TdesEde3::new(key.into());
+    let mut iv = Des3Block::default();
+    for chunk in data.chunks_exact_mut(DES3_BLOCK_SIZE) {
+        let mut block = Des3Block::default();
+        block.copy_from_slice(chunk);
+        block.iter_mut().zip(iv.iter()).for_each(|(b, v)| *b ^= v);
+        cipher.encrypt_block(&mut block);
+        chunk.copy_from_slice(&block);
+        iv = block;
+    }
+}
+
+fn cbc_decrypt(key: &[u8; DES3_KEY_LEN], data: &mut [u8]) {
+    let cipher = TdesEde3::new(key.into());
+    let mut iv = Des3Block::default();
+    for chunk in data.chunks_exact_mut(DES3_BLOCK_SIZE) {
+        let mut block = Des3Block::default();
+        block.copy_from_slice(chunk);
+        let next_iv = block;
+        cipher.decrypt_block(&mut block);
+        block.iter_mut().zip(iv.iter()).for_each(|(b, v)| *b ^= v);
+        chunk.copy_from_slice(&block);
+        iv = next_iv;
+    }
+}
+
+/// The string-to-key of des3-cbc-sha1-kd, which has no iteration count. The
+/// passphrase and salt are n-folded to the seed of a key, which is then put through
+/// DK with "kerberos".
+pub(crate) fn derive_key_external_salt_des3_cbc_sha1_kd(
+    passphrase: &[u8],
+    external_salt: &[u8],
+) -> [u8; DES3_KEY_LEN] {
+    let mut input = Zeroizing::new(Vec::with_capacity(passphrase.len() + external_salt.len()));
+    input.extend_from_slice(passphrase);
+    input.extend_from_slice(external_salt);
+
+    let mut seed = Zeroizing::new([0u8; DES3_SEED_LEN]);
+    seed.copy_from_slice(&Zeroizing::new(nfold(&input, DES3_SEED_LEN)));
+
+    let tmp_key = Zeroizing::new(random_to_key(&seed));
+    dk(&tmp_key, b"kerberos")
+}
+
+/// Given the base key and the key_usage value encrypt and authenticate the provided
+/// plaintext.
+pub(crate) fn encrypt_des3_cbc_sha1_kd(
+    key: &[u8; DES3_KEY_LEN],
+    plaintext: &[u8],
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError> {
+    let mut confuzzler = [0u8; DES3_BLOCK_SIZE];
+    thread_rng().fill(&mut confuzzler);
+
+    encrypt_des3_cbc_sha1_kd_with_confounder(key, &confuzzler, plaintext, key_usage)
+}
+
+fn encrypt_des3_cbc_sha1_kd_with_confounder(
+    key: &[u8; DES3_KEY_LEN],
+    confuzzler: &[u8; DES3_BLOCK_SIZE],
+    plaintext: &[u8],
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError> {
+    if plaintext.is_empty() {
+        return Err(KrbError::PlaintextEmpty);
+    };
+    let ke = Zeroizing::new(dk(key, &key_usage_label(key_usage, 0xAA)?));
+    let ki = Zeroizing::new(dk(key, &key_usage_label(key_usage, 0x55)?));
+
+    // The plaintext is padded with zeros to a whole number of blocks, and the HMAC
+    // covers the padding too.
+    let len = (DES3_BLOCK_SIZE + plaintext.len()).next_multiple_of(DES3_BLOCK_SIZE);
+    let mut ciphertext = Vec::with_capacity(len + SHA1_LEN);
+    ciphertext.extend_from_slice(confuzzler);
+    ciphertext.extend_from_slice(plaintext);
+    ciphertext.resize(len, 0);
+
+    let mut mac =
+        HmacSha1::new_from_slice(ki.as_slice()).map_err(|_| KrbError::InvalidHmacSha1Key)?;
+    mac.update(&ciphertext);
+
+    cbc_encrypt(&ke, &mut ciphertext);
+    ciphertext.extend_from_slice(&mac.finalize().into_bytes());
+    Ok(ciphertext)
+}
+
+/// Given the base key and the key_usage value decrypt and authenticate the provided
+/// ciphertext. The plaintext keeps the zeros that it was padded with.
+pub(crate) fn decrypt_des3_cbc_sha1_kd(
+    key: &[u8; DES3_KEY_LEN],
+    ciphertext: &[u8],
+    key_usage: KeyUsage,
+) -> Result<Vec<u8>, KrbError> {
+    let Some((ciphertext, msg_hmac)) = ciphertext.split_last_chunk::<SHA1_LEN>() else {
+        return Err(KrbError::InsufficientData);
+    };
+
+    if ciphertext.is_empty() {
+        return Err(KrbError::MessageEmpty);
+    };
+
+    if ciphertext.len() % DES3_BLOCK_SIZE != 0 {
+        return Err(KrbError::InsufficientData);
+    }
+
+    let ke = Zeroizing::new(dk(key, &key_usage_label(key_usage, 0xAA)?));
+    let ki = Zeroizing::new(dk(key, &key_usage_label(key_usage, 0x55)?));
+
+    let mut plaintext = ciphertext.to_vec();
+    cbc_decrypt(&ke, &mut plaintext);
+
+    let mut mac =
+        HmacSha1::new_from_slice(ki.as_slice()).map_err(|_| KrbError::InvalidHmacSha1Key)?;
+    mac.update(&plaintext);
+    mac.verify_slice(msg_hmac)
+        .map_err(|_| KrbError::MessageAuthenticationFailed)?;
+
+    // Drop the confounder.
+    Ok(plaintext.split_off(DES3_BLOCK_SIZE))
+}
+
+/// Given the base key and the key_usage value compute the hmac-sha1-des3-kd checksum
+/// of the provided data, which is the whole HMAC.
+pub(crate) fn checksum_hmac_sha1_des3_kd(
+    key: &[u8; DES3_KEY_LEN],
+    data: &[u8],
+    key_usage: KeyUsage,
+) -> Result<[u8; SHA1_LEN], KrbError> {
+    let kc = Zeroizing::new(dk(key, &key_usage_label(key_usage, 0x99)?));
+
+    let mut mac =
+        HmacSha1::new_from_slice(kc.as_slice()).map_err(|_| KrbError::InvalidHmacSha1Key)?;
+    mac.update(data);
+
+    let mut checksum = [0u8; SHA1_LEN];
+    checksum.copy_from_slice(&mac.finalize().into_bytes());
+    Ok(checksum)
+}
+
+/// RFC 3961 6.3 the pseudo-random function of des3, which encrypts the SHA-1 of the
+/// input truncated to whole blocks.
+fn prf_des3_cbc_sha1_kd(key: &[u8; DES3_KEY_LEN], data: &[u8]) -> [u8; 2 * DES3_BLOCK_SIZE] {
+    let kp = Zeroizing::new(dk(key, b"prf"));
+
+    let digest = Sha1::new().chain_update(data).finalize();
+
+    let mut out = [0u8; 2 * DES3_BLOCK_SIZE];
+    out.copy_from_slice(&digest[..2 * DES3_BLOCK_SIZE]);
+    cbc_encrypt(&kp, &mut out);
+    out
+}
+
+/// The plaintext without the zeros that it was padded with. des3 doesn't keep the
+/// length of the plaintext, so it is taken from the DER encoding of the message.
+/// Anything that isn't DER is returned as is.
+fn trim_padding(mut plaintext: Vec<u8>) -> Vec<u8> {
+    let len = SliceReader::new(&plaintext)
+        .and_then(|reader| reader.peek_header())
+        .and_then(|header| header.encoded_len().and_then(|len| len + header.length))
+        .ok()
+        .and_then(|len| usize::try_from(len).ok());
+
+    if let Some(len) = len.filter(|len| *len <= plaintext.len()) {
+        plaintext.truncate(len);
+    }
+    plaintext
+}
+
+pub(crate) struct Des3CbcSha1Kd;
+
+impl EtypeCipher for Des3CbcSha1Kd {
+    fn etype(&self) -> EncryptionType {
+        EncryptionType::DES3_CBC_SHA1_KD
+    }
+
+    fn checksum_type(&self) -> ChecksumType {
+        ChecksumType::HMAC_SHA1_DES3_KD
+    }
+
+    fn key_size(&self) -> usize {
+        DES3_KEY_LEN
+    }
+
+    fn seed_size(&self) -> usize {
+        DES3_SEED_LEN
+    }
+
+    // There are no iterations, so any floor on the count refuses des3.
+    fn default_iter_count(&self) -> u32 {
+        0
+    }
+
+    // RFC 3961 6.3.1 The s2kparams of des3 are empty, and any that a KDC sends
+    // anyway have nothing to set.
+    fn iter_count(&self, _s2kparams: Option<&[u8]>) -> Result<u32, KrbError> {
+        Ok(0)
+    }
+
+    fn string_to_key(
+        &self,
+        passphrase: &[u8],
+        salt: &[u8],
+        _iter_count: Option<u32>,
+    ) -> Result<Zeroizing<Vec<u8>>, KrbError> {
+        let key = Zeroizing::new(derive_key_external_salt_des3_cbc_sha1_kd(passphrase, salt));
+        Ok(Zeroizing::new(key.to_vec()))
+    }
+
+    fn random_to_key(&self, random: &[u8]) -> Result<Zeroizing<Vec<u8>>, KrbError> {
+        let random: &[u8; DES3_SEED_LEN] = random
+            .try_into()
+            .map_err(|_| KrbError::InvalidEncryptionKey)?;
+        Ok(Zeroizing::new(random_to_key(random).to_vec()))
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8],
+        key_usage: KeyUsage,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, KrbError> {
+        encrypt_des3_cbc_sha1_kd(key_array(key)?, plaintext, key_usage)
+    }
+
+    fn decrypt(
+        &self,
+        key: &[u8],
+        key_usage: KeyUsage,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, KrbError> {
+        decrypt_des3_cbc_sha1_kd(key_array(key)?, ciphertext, key_usage).map(trim_padding)
+    }
+
+    fn checksum(&self, key: &[u8], key_usage: KeyUsage, data: &[u8]) -> Result<Vec<u8>, KrbError> {
+        checksum_hmac_sha1_des3_kd(key_array(key)?, data, key_usage).map(|c| c.to_vec())
+    }
+
+    fn prf(&self, key: &[u8], input: &[u8]) -> Result<Vec<u8>, KrbError> {
+        Ok(prf_des3_cbc_sha1_kd(key_array(key)?, input).to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::cipher_for;
+
+    // RFC 3961 A.3, DR and DK of des3.
+    #[test]
+    fn test_des3_dk_rfc3961() {
+        let key: [u8; DES3_KEY_LEN] =
+            hex::decode("dce06b1f64c857a11c3db57c51899b2cc1791008ce973b92")
+                .unwrap()
+                .try_into()
+                .unwrap();
+
+        assert_eq!(
+            dk(&key, &hex::decode("0000000155").unwrap()).as_slice(),
+            hex::decode("925179d04591a79b5d3192c4a7e9c289b049c71f6ee604cd").unwrap()
+        );
+    }
+
+    // RFC 3961 A.4, the string-to-key of des3.
+    #[test]
+    fn test_des3_string_to_key_rfc3961() {
+        for (passphrase, salt, expected) in [
+            (
+                "password",
+                "ATHENA.MIT.EDUraeburn",
+                "850bb51358548cd05e86768c313e3bfef7511937dcf72c3e",
+            ),
+            (
+                "potatoe",
+                "WHITEHOUSE.GOVdanny",
+                "dfcd233dd0a43204ea6dc437fb15e061b02979c1f74f377a",
+            ),
+            (
+                "penny",
+                "EXAMPLE.COMbuckaroo",
+                "6d2fcdf2d6fbbc3ddcadb5da5710a23489b0d3b69d5d9d4a",
+            ),
+            (
+                "\u{00DF}",
+                "ATHENA.MIT.EDUJuri\u{0161}i\u{0107}",
+                "16d5a40e1ce3bacb61b9dce00470324c831973a7b952feb0",
+            ),
+            (
+                "\u{1D11E}",
+                "EXAMPLE.COMpianist",
+                "85763726585dbc1cce6ec43e1f751f07f1c4cbb098f40b19",
+            ),
+        ] {
+            let key =
+                derive_key_external_salt_des3_cbc_sha1_kd(passphrase.as_bytes(), salt.as_bytes());
+            assert_eq!(key.as_slice(), hex::decode(expected).unwrap());
+        }
+    }
+
+    // These were computed with an independent implementation, with the key of the
+    // first string-to-key vector.
+    #[test]
+    fn test_des3_cbc_sha1_kd_fixed_confounder() {
+        let key = derive_key_external_salt_des3_cbc_sha1_kd(b"password", b"ATHENA.MIT.EDUraeburn");
+        let confounder: [u8; DES3_BLOCK_SIZE] = core::array::from_fn(|i| i as u8);
+
+        for (len, expected) in [
+            (6, "c18690ba486875c8a85d12f71bfa4b8f022862ceea927822ccf224338bb9de2d1d036948"),
+            (8, "c18690ba486875c86d1fc843a406a8cf27bc59ab8ece3bef2ccac39a53d4455f25bde3f2"),
+            (21, "c18690ba486875c86d1fc843a406a8cf41e0b85cb5b6c016a8025aa4943aeaa50730950cb8fab38e062d0ad394f416ab1dfe0d33"),
+        ] {
+            let plaintext: Vec<u8> = (0..len).collect();
+            let ciphertext = encrypt_des3_cbc_sha1_kd_with_confounder(
+                &key,
+                &confounder,
+                &plaintext,
+                KeyUsage::KDC_REP_TICKET,
+            )
+            .unwrap();
+            assert_eq!(ciphertext, hex::decode(expected).unwrap());
+
+            // The zeros of the padding are part of the plaintext.
+            let data = decrypt_des3_cbc_sha1_kd(&key, &ciphertext, KeyUsage::KDC_REP_TICKET)
+                .unwrap();
+            assert_eq!(data[..plaintext.len()], plaintext);
+            assert!(data[plaintext.len()..].iter().all(|b| *b == 0));
+
+            assert!(matches!(
+                decrypt_des3_cbc_sha1_kd(&key, &ciphertext, KeyUsage::AS_REP_ENC_PART),
+                Err(KrbError::MessageAuthenticationFailed)
+            ));
+        }
+
+        assert_eq!(
+            checksum_hmac_sha1_des3_kd(&key, b"fourteen", KeyUsage::KDC_REP_TICKET)
+                .unwrap()
+                .as_slice(),
+            hex::decode("04250c9019c095b693331019944d3408a6fb5723").unwrap()
+        );
+        assert_eq!(
+            prf_des3_cbc_sha1_kd(&key, b"test").as_slice(),
+            hex::decode("32eb2c6930987d312ac7e6fa6791387e").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_des3_cipher() {
+        let cipher = cipher_for(EncryptionType::DES3_CBC_SHA1_KD).unwrap();
+        let key = cipher
+            .string_to_key(b"password", b"ATHENA.MIT.EDUraeburn", None)
+            .unwrap();
+
+        // Every byte of a key has odd parity.
+        assert!(key.iter().all(|b| b.count_ones() % 2 == 1));
+        let random: Vec<u8> = (0..DES3_SEED_LEN as u8).collect();
+        let random_key = cipher.random_to_key(&random).unwrap();
+        assert_eq!(random_key.len(), DES3_KEY_LEN);
+        assert!(random_key.iter().all(|b| b.count_ones() % 2 == 1));
+
+        // DER has its padding taken off again.
+        let plaintext = [0x30, 0x03, 0x02, 0x01, 0x05];
+        let ciphertext = cipher
+            .encrypt(&key, KeyUsage::AS_REP_ENC_PART, &plaintext)
+            .unwrap();
+        assert_eq!(ciphertext.len(), 2 * DES3_BLOCK_SIZE + SHA1_LEN);
+        assert_eq!(
+            cipher
+                .decrypt(&key, KeyUsage::AS_REP_ENC_PART, &ciphertext)
+                .unwrap(),
+            plaintext
+        );
+
+        // Any single bit flipped in the HMAC fails to authenticate.
+        for bit in (ciphertext.len() - SHA1_LEN) * 8..ciphertext.len() * 8 {
+            let mut forged = ciphertext.clone();
+            forged[bit / 8] ^= 1 << (bit % 8);
+            assert!(matches!(
+                cipher.decrypt(&key, KeyUsage::AS_REP_ENC_PART, &forged),
+                Err(KrbError::MessageAuthenticationFailed)
+            ));
+        }
+    }
+}
@@ -1,6 +1,13 @@
+#[cfg(feature = "tokio-codec")]
+use crate::kpasswd::KpasswdResultCode;
+use crate::proto::{EncryptionType, KrbErrorCode};
+
 #[derive(Debug, Clone)]
 pub enum KrbError {
     InvalidHmacSha1Key,
+    InvalidHmacMd5Key,
+    InvalidHmacSha256Key,
+    InvalidHmacSha384Key,
     MessageAuthenticationFailed,
     MessageEmpty,
     InsufficientData,
@@ -9,13 +16,101 @@ pub enum KrbError {
     UnsupportedEncryption,
     MissingPaData,
     DerDecodePaData,
+    DerDecodeEtypeInfo,
     DerDecodeEtypeInfo2,
     DerEncodePaEncTsEnc,
     PreAuthUnsupported,
     PreAuthMissingEtypeInfo2,
     PreAuthInvalidUnixTs,
-    PreAuthInvalidS2KParams,
+    PreAuthInvalidS2KParams(Vec<u8>),
+    PreAuthIterCountTooLow(u32, u32),
+    PreAuthRoundLimit,
+    PreAuthKeyEtypeNotOffered(EncryptionType),
+    StringToKeyTaskFailed,
+    MissingClientName,
+    MissingServiceName,
+    MissingRenewTime,
+    MissingStartTime,
+    InvalidStartTime,
+    EndTimeInPast,
+    RenewTimeBeforeEndTime,
+    DerDecodeEncKdcRepPart,
+    DerDecodeReqEncPaRep,
+    ReqEncPaRepMissing,
+    ReqEncPaRepMismatch,
+    ReplyNonceMismatch,
+    ReplyServiceMismatch(String),
+    ReplyEndTimeExceedsRequest,
+    InvalidEncryptionKey,
+    InvalidKeyLength(EncryptionType, usize),
+    InvalidKeyUsage(i32),
+    KeyVersionMismatch(u32, u32),
+    InvalidHostAddress,
+    InvalidTime,
+    InvalidName(String),
+    DerEncodeKdcReq,
+    DerEncodeKdcReqBody,
+    DerDecodeApReq,
+    DerEncodeTicket,
+    DerDecodeTicket,
+    DerDecodeEncTicketPart,
+    DerEncodePaForUser,
+    DerDecodePaForUser,
+    DerDecodePaPacOptions,
+    UnsupportedChecksumType(i32),
+    InvalidAuthPackage(String),
+    DerEncodeFastReq,
+    DerDecodeFastReq,
+    DerDecodeFastRep,
+    DerDecodePaOtpChallenge,
+    DerEncodePaOtpRequest,
+    FastMissingReply,
+    FastMissingFinished,
+    FastNonceMismatch,
+    FastReplyArmored,
+    DerEncodePaPkAsReq,
+    DerDecodePaPkAsRep,
+    PkinitSigningFailed(String),
+    PkinitInvalidSignedData,
+    PkinitKdcCertInvalid(String),
+    PkinitNonceMismatch,
+    PkinitInvalidDhKey,
+    PkinitMissingReply,
+
+    IoError(std::io::ErrorKind),
+    ConnectionClosed,
+    Timeout,
+    UnexpectedResponse,
+    KdcError(KrbErrorCode),
+    EtypeDowngrade(Vec<EncryptionType>, i32),
+    ClientReferral(String),
+    TicketNotRenewable,
+    RenewTillExpired,
+    RenewalFailed(KrbErrorCode),
+    TicketStillInvalid,
+    DnsLookupFailed(String),
+    NoKdcFound,
+    CrossRealmLoop(String),
+    CrossRealmHopLimit,
+    DerEncodeKdcProxyMessage,
+    DerDecodeKdcProxyMessage,
+    HttpRequestFailed(String),
+    DerEncodeAuthenticator,
+    DerDecodeAuthenticator,
+    DerEncodeChecksum,
+    MissingAuthenticatorChecksum,
+    DerEncodeApReq,
+    DerDecodeApRep,
+    DerEncodeKrbPriv,
+    DerDecodeKrbPriv,
+    DerEncodeEncryptedData,
+    DerDecodeEncryptedData,
+    KpasswdInvalidReply,
+    #[cfg(feature = "tokio-codec")]
+    PasswordChangeFailed(KpasswdResultCode, String),
 
+    InvalidPvno(u8),
     InvalidMessageType(i32, i32),
+    WrongPayloadTag(u8, u8),
     InvalidEnumValue(String, i32),
 }
@@ -0,0 +1,985 @@
+use crate::constants::DEFAULT_AS_MAX_ROUNDS;
+use crate::error::KrbError;
+use crate::proto::{
+    AuthenticatedReply, BaseKey, ClockOffset, EncryptionType, FastArmor, KerberosAsRep,
+    KerberosKey, KerberosPaRep, KerberosRequest, KerberosResponse, KrbErrorCode, PreAuth,
+    PreAuthEntry, StringToKeyOptions, StringToKeyParams,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+/// The long-term secret of the client, which the reply key of an AS exchange is
+/// derived from.
+pub enum Credentials<'a> {
+    /// A password, which the key is derived from with the parameters of the KDC. It
+    /// is wiped from memory when the credentials are dropped.
+    Password(Zeroizing<String>),
+    /// A key that was already derived, such as one from a keytab.
+    Key(&'a BaseKey),
+    /// A key along with its version. A reply that is encrypted in another version
+    /// of the key fails with [KrbError::KeyVersionMismatch].
+    VersionedKey(&'a KerberosKey),
+}
+
+impl Credentials<'_> {
+    // The version of the key, which the preauthentication and reply are checked
+    // against.
+    fn kvno(&self) -> Option<u32> {
+        match self {
+            Credentials::VersionedKey(key) => Some(key.kvno()),
+            Credentials::Password(_) | Credentials::Key(_) => None,
+        }
+    }
+
+    fn key(&self) -> Option<&BaseKey> {
+        match self {
+            Credentials::Key(key) => Some(key),
+            Credentials::VersionedKey(key) => Some(key.key()),
+            Credentials::Password(_) => None,
+        }
+    }
+}
+
+// A response that was checked against the exchange, before any key is derived for it.
+enum Received {
+    AsRep(KerberosAsRep),
+    PaRep(KerberosPaRep),
+    Referral(String),
+}
+
+/// What to do after a response was given to [AsExchange::step].
+#[derive(Debug)]
+pub enum AsStep {
+    /// Send this request to the KDC, and step with its response.
+    SendNext(KerberosRequest),
+    /// The exchange succeeded with this ticket.
+    Done(AuthenticatedReply),
+    /// The client is in another realm, and the exchange has to be started again
+    /// against the KDC of `realm`.
+    Referral { realm: String },
+    /// The exchange failed, and can't be continued.
+    Failed(KrbError),
+}
+
+/// The client side of an AS exchange, which drives the rounds of preauthentication
+/// without any transport. Each request is sent by the caller, and its response is
+/// given back to [Self::step], which says what to do next.
+///
+/// ```no_run
+/// use libkrime::exchange::{AsExchange, AsStep, Credentials};
+/// use libkrime::proto::{KerberosRequest, KerberosResponse};
+/// use std::time::{Duration, SystemTime};
+/// use zeroize::Zeroizing;
+///
+/// # fn send(request: KerberosRequest) -> KerberosResponse { unimplemented!() }
+/// let until = SystemTime::now() + Duration::from_secs(3600);
+///
+/// let mut exchange = AsExchange::new("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until);
+/// let mut request = exchange.start().expect("Failed to build request");
+/// let credentials = Credentials::Password(Zeroizing::new("password".to_string()));
+///
+/// let tgt = loop {
+///     match exchange.step(send(request), &credentials) {
+///         AsStep::SendNext(next) => request = next,
+///         AsStep::Done(tgt) => break tgt,
+///         AsStep::Referral { realm } => panic!("The client is in {}", realm),
+///         AsStep::Failed(err) => panic!("{:?}", err),
+///     }
+/// };
+/// ```
+#[derive(Debug)]
+pub struct AsExchange {
+    client_name: String,
+    service_name: String,
+    realm: String,
+    until: SystemTime,
+    renew_until: Option<SystemTime>,
+    armor: Option<FastArmor>,
+    max_rounds: usize,
+    rounds: usize,
+    allow_etype_downgrade: bool,
+    string_to_key: StringToKeyOptions,
+    clock_offset: ClockOffset,
+    // The offset of the clock of the KDC, when it refused our clock as skewed.
+    kdc_clock_offset: Option<ClockOffset>,
+    // The request in flight, which its reply is checked against.
+    nonce: u32,
+    etypes: Vec<EncryptionType>,
+    request: Vec<u8>,
+    // The reply key, once preauthentication was sent with it.
+    reply_key: Option<BaseKey>,
+    // The parameters that the reply key was derived from a password with.
+    reply_key_params: Option<StringToKeyParams>,
+    finished: bool,
+}
+
+impl AsExchange {
+    /// Start an exchange for `client_name` in `realm`, requesting a ticket for
+    /// `service_name` that is valid until `until`. A client name with an @ in it is
+    /// sent as an enterprise name.
+    pub fn new(
+        client_name: impl Into<String>,
+        service_name: impl Into<String>,
+        realm: impl Into<String>,
+        until: SystemTime,
+    ) -> Self {
+        AsExchange {
+            client_name: client_name.into(),
+            service_name: service_name.into(),
+            realm: realm.into(),
+            until,
+            renew_until: None,
+            armor: None,
+            max_rounds: DEFAULT_AS_MAX_ROUNDS,
+            rounds: 0,
+            allow_etype_downgrade: false,
+            string_to_key: StringToKeyOptions::default(),
+            clock_offset: ClockOffset::default(),
+            kdc_clock_offset: None,
+            nonce: 0,
+            etypes: Vec::new(),
+            request: Vec::new(),
+            reply_key: None,
+            reply_key_params: None,
+            finished: false,
+        }
+    }
+
+    /// Request a renewable ticket that may be renewed until `renew_until`.
+    pub fn renew_until(mut self, renew_until: Option<SystemTime>) -> Self {
+        self.renew_until = renew_until;
+        self
+    }
+
+    /// Armor every request with FAST (RFC 6113), so that the preauthentication is
+    /// an encrypted challenge rather than an encrypted timestamp.
+    pub fn armor(mut self, armor: &FastArmor) -> Self {
+        self.armor = Some(armor.clone());
+        self
+    }
+
+    /// Limit how many requests are sent before the exchange fails, so that a KDC
+    /// can't keep the client asking forever.
+    pub fn max_rounds(mut self, max_rounds: usize) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    /// Accept a reply encrypted in an etype that the request didn't offer. This is
+    /// only meant for debugging a KDC that doesn't interoperate, as it leaves the
+    /// client open to a downgrade of the reply.
+    pub fn allow_etype_downgrade(mut self, allow_etype_downgrade: bool) -> Self {
+        self.allow_etype_downgrade = allow_etype_downgrade;
+        self
+    }
+
+    /// How the key is derived from a password, such as the lowest iteration count
+    /// that the KDC may ask for.
+    pub fn string_to_key_options(mut self, string_to_key: StringToKeyOptions) -> Self {
+        self.string_to_key = string_to_key;
+        self
+    }
+
+    /// Move the timestamp of the preauthentication by `clock_offset`, so that the
+    /// KDC accepts it when our clock is off.
+    pub fn clock_offset(mut self, clock_offset: ClockOffset) -> Self {
+        self.clock_offset = clock_offset;
+        self
+    }
+
+    /// How far the clock of the KDC is from ours, when the exchange failed with
+    /// KRB_AP_ERR_SKEW. A new exchange with this [offset](Self::clock_offset) is
+    /// then expected to succeed.
+    pub fn kdc_clock_offset(&self) -> Option<ClockOffset> {
+        self.kdc_clock_offset
+    }
+
+    /// Build the first request of the exchange.
+    pub fn start(&mut self) -> Result<KerberosRequest, KrbError> {
+        // RFC 8070 2 Ask the KDC for a freshness token, which is echoed along with
+        // the preauthentication.
+        let mut freshness = PreAuth::default();
+        freshness.push(PreAuthEntry::AsFreshness(Vec::new()));
+
+        self.next_request(freshness)
+    }
+
+    /// Continue the exchange with the `response` of the KDC to the last request,
+    /// deriving the preauthentication from `credentials` when it is required.
+    pub fn step(&mut self, response: KerberosResponse, credentials: &Credentials) -> AsStep {
+        if self.finished {
+            return AsStep::Failed(KrbError::UnexpectedResponse);
+        }
+
+        let step = self
+            .try_step(response, credentials)
+            .unwrap_or_else(AsStep::Failed);
+        self.finish_step(step)
+    }
+
+    /// Continue the exchange as [Self::step], deriving the key from a password on
+    /// the blocking thread pool of tokio so that the string-to-key doesn't stall the
+    /// executor.
+    #[cfg(feature = "tokio-codec")]
+    pub async fn step_async(
+        &mut self,
+        response: KerberosResponse,
+        credentials: &Credentials<'_>,
+    ) -> AsStep {
+        if self.finished {
+            return AsStep::Failed(KrbError::UnexpectedResponse);
+        }
+
+        let step = self
+            .try_step_async(response, credentials)
+            .await
+            .unwrap_or_else(AsStep::Failed);
+        self.finish_step(step)
+    }
+
+    fn finish_step(&mut self, step: AsStep) -> AsStep {
+        if !matches!(step, AsStep::SendNext(_)) {
+            self.finished = true;
+        }
+        step
+    }
+
+    fn try_step(
+        &mut self,
+        response: KerberosResponse,
+        credentials: &Credentials,
+    ) -> Result<AsStep, KrbError> {
+        let received = self.receive(response)?;
+
+        let password_key = match credentials {
+            Credentials::Password(password) => match self.password_key_params(&received)? {
+                Some(params) => {
+                    let key = params.derive_key(password, &self.string_to_key)?;
+                    self.reply_key_params = Some(params);
+                    Some(key)
+                }
+                None => None,
+            },
+            Credentials::Key(_) | Credentials::VersionedKey(_) => None,
+        };
+
+        self.complete(received, credentials, password_key)
+    }
+
+    // The same as try_step, with the key derived off the executor.
+    #[cfg(feature = "tokio-codec")]
+    async fn try_step_async(
+        &mut self,
+        response: KerberosResponse,
+        credentials: &Credentials<'_>,
+    ) -> Result<AsStep, KrbError> {
+        let received = self.receive(response)?;
+
+        let password_key = match credentials {
+            Credentials::Password(password) => match self.password_key_params(&received)? {
+                Some(params) => {
+                    let key = params
+                        .derive_key_async(password, &self.string_to_key)
+                        .await?;
+                    self.reply_key_params = Some(params);
+                    Some(key)
+                }
+                None => None,
+            },
+            Credentials::Key(_) | Credentials::VersionedKey(_) => None,
+        };
+
+        self.complete(received, credentials, password_key)
+    }
+
+    // The parameters that a key has to be derived from the password with for the
+    // response, or None when the key we already have is the one to use.
+    fn password_key_params(
+        &self,
+        received: &Received,
+    ) -> Result<Option<StringToKeyParams>, KrbError> {
+        match received {
+            // A salt that the KDC confirms in the reply is the one that the reply key
+            // was derived with, even if the error asking for preauthentication named
+            // another. When it confirms the parameters of the key we sent the
+            // preauthentication with, that key is the reply key.
+            Received::AsRep(as_rep) => match as_rep.reply_string_to_key_params().transpose()? {
+                Some(params) if self.reply_key_params.as_ref() == Some(&params) => Ok(None),
+                Some(params) => Ok(Some(params)),
+                // The KDC didn't require preauthentication, so we fall back to the
+                // default salt and iteration count.
+                None if self.reply_key.is_none() => as_rep.default_string_to_key_params().map(Some),
+                None => Ok(None),
+            },
+            // RFC 6806 11 The default salt is built from the client principal that the
+            // KDC replied with, which is the canonical one if it was canonicalized. When
+            // the KDC continues the conversation with the parameters of the key we
+            // already sent, that key is used again.
+            Received::PaRep(pa_rep) => {
+                match pa_rep.preferred_etype_info(&[])?.string_to_key_params(
+                    pa_rep.client_realm().unwrap_or(&self.realm),
+                    pa_rep.client_name().unwrap_or(&self.client_name),
+                )? {
+                    params if self.reply_key_params.as_ref() == Some(&params) => Ok(None),
+                    params => Ok(Some(params)),
+                }
+            }
+            Received::Referral(_) => Ok(None),
+        }
+    }
+
+    // Check the response against the request in flight, before any key is derived
+    // for it.
+    fn receive(&mut self, response: KerberosResponse) -> Result<Received, KrbError> {
+        match response {
+            KerberosResponse::AsRep(as_rep) => {
+                let as_rep = match &self.armor {
+                    Some(armor) => as_rep.unarmor(armor, self.nonce)?,
+                    None => as_rep,
+                };
+
+                if !self.allow_etype_downgrade {
+                    as_rep.check_etype(&self.etypes)?;
+                }
+                Ok(Received::AsRep(as_rep))
+            }
+            KerberosResponse::PaRep(pa_rep) => {
+                let pa_rep = match &self.armor {
+                    Some(armor) => pa_rep.unarmor(armor, self.nonce)?,
+                    None => pa_rep,
+                };
+
+                // Being asked again after we sent our preauthentication means the KDC
+                // didn't accept it, unless it continues the conversation with a cookie.
+                if self.reply_key.is_some() && pa_rep.pa_fx_cookie.is_none() {
+                    return Err(KrbError::KdcError(KrbErrorCode::KdcErrPreauthRequired));
+                }
+                Ok(Received::PaRep(pa_rep))
+            }
+            KerberosResponse::Err(err_rep) => {
+                // A referral back to the realm we asked in would only loop.
+                if let Some(realm) = err_rep
+                    .referral_realm()
+                    .filter(|realm| *realm != self.realm)
+                {
+                    return Ok(Received::Referral(realm.to_string()));
+                }
+
+                if err_rep.error_code() == KrbErrorCode::KrbApErrSkew {
+                    self.kdc_clock_offset = Some(err_rep.clock_offset());
+                }
+                Err(KrbError::KdcError(err_rep.error_code()))
+            }
+            KerberosResponse::TgsRep(_) => Err(KrbError::UnexpectedResponse),
+        }
+    }
+
+    // Continue with the key derived from a password, or otherwise the key that was
+    // already sent in preauthentication or given in the credentials.
+    fn complete(
+        &mut self,
+        received: Received,
+        credentials: &Credentials,
+        password_key: Option<BaseKey>,
+    ) -> Result<AsStep, KrbError> {
+        match received {
+            Received::AsRep(as_rep) => {
+                let reply_key = password_key
+                    .or_else(|| self.reply_key.take())
+                    .or_else(|| credentials.key().cloned())
+                    .ok_or(KrbError::InvalidEncryptionKey)?;
+
+                as_rep
+                    .decrypt_reply(
+                        &reply_key,
+                        credentials.kvno(),
+                        Some(&self.request),
+                        Some(self.nonce),
+                    )
+                    .map(AsStep::Done)
+            }
+            Received::PaRep(pa_rep) => {
+                let reply_key = match password_key {
+                    Some(reply_key) => reply_key,
+                    None => {
+                        let key = self
+                            .reply_key
+                            .as_ref()
+                            .or_else(|| credentials.key())
+                            .ok_or(KrbError::InvalidEncryptionKey)?;
+                        pa_rep.check_key_etype(key)?;
+                        key.clone()
+                    }
+                };
+
+                let epoch_seconds = self
+                    .clock_offset
+                    .now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(|_| KrbError::PreAuthInvalidUnixTs)?;
+
+                // This also echoes the fx cookie and freshness token of the KDC.
+                let preauth = match &self.armor {
+                    Some(armor) => {
+                        pa_rep.encrypted_challenge_with_key(armor, &reply_key, epoch_seconds)?
+                    }
+                    None => pa_rep.enc_timestamp_with_key(
+                        &reply_key,
+                        credentials.kvno(),
+                        epoch_seconds,
+                    )?,
+                };
+
+                let request = self.next_request(preauth)?;
+                self.reply_key = Some(reply_key);
+                Ok(AsStep::SendNext(request))
+            }
+            Received::Referral(realm) => Ok(AsStep::Referral { realm }),
+        }
+    }
+
+    fn next_request(&mut self, preauth: PreAuth) -> Result<KerberosRequest, KrbError> {
+        if self.rounds >= self.max_rounds {
+            return Err(KrbError::PreAuthRoundLimit);
+        }
+
+        // A client name can only contain an @ if it is an enterprise name, such as the
+        // UPN of an Active Directory user. The KDC must then tell us who that is.
+        let enterprise = self.client_name.contains('@');
+
+        let builder = KerberosRequest::build_asreq(
+            self.client_name.as_str(),
+            self.service_name.as_str(),
+            self.realm.as_str(),
+            self.until,
+        )
+        .renew(self.renew_until)
+        .enterprise(enterprise)
+        .canonicalize(enterprise)
+        .renewable(self.renew_until.is_some())
+        .req_enc_pa_rep(true)
+        .add_preauthentication(preauth);
+
+        let request = match &self.armor {
+            Some(armor) => builder.armor(armor),
+            None => builder,
+        }
+        .build()?;
+
+        let KerberosRequest::AsReq(as_req) = &request else {
+            unreachable!();
+        };
+        self.nonce = as_req.nonce();
+        self.etypes = request.etypes().to_vec();
+        // The KDC checksums the request exactly as it was sent.
+        self.request = request.to_der().map_err(|_| KrbError::DerEncodeKdcReq)?;
+        self.rounds += 1;
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AsExchange, AsStep, Credentials};
+    use crate::asn1::enc_kdc_rep_part::{EncKdcRepPart, TaggedEncKdcRepPart};
+    use crate::asn1::kerberos_flags::KerberosFlags;
+    use crate::asn1::kerberos_string::KerberosString;
+    use crate::asn1::kerberos_time::KerberosTime;
+    use crate::asn1::krb_error::MethodData;
+    use crate::asn1::pa_data::PaData;
+    use crate::asn1::pa_enc_ts_enc::PaEncTsEnc;
+    use crate::asn1::{Ia5String, OctetString};
+    use crate::constants::AES_256_KEY_LEN;
+    use crate::crypto::{derive_key_external_salt_aes256_cts_hmac_sha1_96, PBKDF2_RUNS};
+    use crate::error::KrbError;
+    use crate::proto::{
+        etype_info2_from_der, principal_name_from_str, BaseKey, EncryptedData, EncryptionType,
+        KdcEncryptionKey, KerberosAsRep, KerberosErrorRep, KerberosKey, KerberosPaRep,
+        KerberosRequest, KerberosResponse, KeyUsage, KrbErrorCode, PreAuthEntry, TicketFlags,
+    };
+    use crate::tests::AS_REP_SAMPLE;
+    use der::{Decode, Encode};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use zeroize::Zeroizing;
+
+    // METHOD-DATA with PA-ENC-TIMESTAMP, a PA-FX-COOKIE and ETYPE-INFO2.
+    const PA_REP_COOKIE: &str = "30483009a103020102a20204003010a10402020085a2080406636f6f6b69653029a103020113a2220420301e301ca003020112a1151b134558414d504c452e434f4d7465737475736572";
+    // The same without the cookie.
+    const PA_REP: &str = "30363009a103020102a20204003029a103020113a2220420301e301ca003020112a1151b134558414d504c452e434f4d7465737475736572";
+
+    fn pa_rep(method_data: &str) -> KerberosResponse {
+        let method_data = hex::decode(method_data).expect("Failed to decode sample");
+        let padata = MethodData::from_der(&method_data).expect("Failed to decode METHOD-DATA");
+        KerberosResponse::PaRep(KerberosPaRep::try_from(padata).expect("Failed to parse padata"))
+    }
+
+    fn reply_key() -> BaseKey {
+        let KerberosResponse::PaRep(pa_rep) = pa_rep(PA_REP) else {
+            unreachable!();
+        };
+        pa_rep
+            .derive_key("password", "EXAMPLE.COM", "testuser")
+            .expect("Failed to derive key")
+    }
+
+    // An AS-REP to `request`, encrypted in `reply_key` the way the KDC would.
+    fn as_rep(reply_key: &BaseKey, request: &KerberosRequest, nonce: u32) -> KerberosResponse {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let request = request.to_der().expect("Failed to encode request");
+        let cksum = reply_key
+            .checksum(&request, KeyUsage::AS_REQ)
+            .expect("Failed to checksum request")
+            .to_der()
+            .expect("Failed to encode checksum");
+
+        let time = KerberosTime::from_unix_duration(Duration::from_secs(1_700_000_000))
+            .expect("Invalid time");
+        let enc_part = TaggedEncKdcRepPart::EncAsRepPart(EncKdcRepPart {
+            key: KdcEncryptionKey::try_from(
+                &BaseKey::from_bytes(
+                    EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                    &[0x33; AES_256_KEY_LEN],
+                )
+                .expect("Invalid key"),
+            )
+            .expect("Failed to encode session key"),
+            last_req: Vec::new(),
+            nonce,
+            key_expiration: None,
+            flags: KerberosFlags(TicketFlags::EncPaRep.into()),
+            auth_time: time,
+            start_time: None,
+            end_time: time,
+            renew_till: None,
+            server_realm: KerberosString(Ia5String::new("EXAMPLE.COM").expect("Invalid realm")),
+            server_name: principal_name_from_str(2, "krbtgt/EXAMPLE.COM").expect("Invalid name"),
+            client_addresses: None,
+            encrypted_pa_data: Some(vec![PaData {
+                padata_type: 149,
+                padata_value: OctetString::new(cksum).expect("Invalid checksum"),
+            }]),
+        })
+        .to_der()
+        .expect("Failed to encode enc-part");
+
+        KerberosResponse::AsRep(KerberosAsRep {
+            enc_part: reply_key
+                .encrypt_data(&enc_part, KeyUsage::AS_REP_ENC_PART)
+                .expect("Failed to encrypt enc-part"),
+            ..as_rep
+        })
+    }
+
+    fn new_exchange() -> AsExchange {
+        AsExchange::new(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+    }
+
+    fn nonce(request: &KerberosRequest) -> u32 {
+        let KerberosRequest::AsReq(as_req) = request else {
+            unreachable!();
+        };
+        as_req.nonce()
+    }
+
+    #[test]
+    fn test_as_exchange_preauth() {
+        let mut exchange = new_exchange();
+        let credentials = Credentials::Password(Zeroizing::new("password".to_string()));
+
+        let request = exchange.start().expect("Failed to build request");
+        let KerberosRequest::AsReq(as_req) = &request else {
+            unreachable!();
+        };
+        assert!(as_req.req_enc_pa_rep());
+        let entries = as_req.preauth().expect("Missing preauth").entries();
+        assert!(matches!(entries, [PreAuthEntry::AsFreshness(token)] if token.is_empty()));
+
+        // The preauthentication is sent with the cookie of the KDC echoed.
+        let AsStep::SendNext(request) = exchange.step(pa_rep(PA_REP_COOKIE), &credentials) else {
+            unreachable!();
+        };
+        let KerberosRequest::AsReq(as_req) = &request else {
+            unreachable!();
+        };
+        let entries = as_req.preauth().expect("Missing preauth").entries();
+        assert!(matches!(
+            entries,
+            [PreAuthEntry::EncTimestamp(_), PreAuthEntry::FxCookie(cookie)] if cookie == b"cookie"
+        ));
+
+        let AsStep::Done(tgt) = exchange.step(
+            as_rep(&reply_key(), &request, nonce(&request)),
+            &credentials,
+        ) else {
+            unreachable!();
+        };
+        assert!(tgt.request_verified());
+
+        // There's nothing left to do once the exchange is done.
+        let step = exchange.step(pa_rep(PA_REP), &credentials);
+        assert!(matches!(step, AsStep::Failed(KrbError::UnexpectedResponse)));
+    }
+
+    #[test]
+    fn test_as_exchange_nonce_mismatch() {
+        let reply_key = reply_key();
+        let mut exchange = new_exchange();
+        let credentials = Credentials::Key(&reply_key);
+
+        exchange.start().expect("Failed to build request");
+        let AsStep::SendNext(request) = exchange.step(pa_rep(PA_REP), &credentials) else {
+            unreachable!();
+        };
+
+        let step = exchange.step(
+            as_rep(&reply_key, &request, nonce(&request) ^ 1),
+            &credentials,
+        );
+        assert!(matches!(step, AsStep::Failed(KrbError::ReplyNonceMismatch)));
+    }
+
+    #[test]
+    fn test_as_exchange_kvno_mismatch() {
+        let reply_key = KerberosKey::new(reply_key(), 2);
+        let mut exchange = new_exchange();
+        let credentials = Credentials::VersionedKey(&reply_key);
+
+        exchange.start().expect("Failed to build request");
+        let AsStep::SendNext(request) = exchange.step(pa_rep(PA_REP), &credentials) else {
+            unreachable!();
+        };
+
+        // The timestamp names the version of the key it is encrypted in.
+        let KerberosRequest::AsReq(as_req) = &request else {
+            unreachable!();
+        };
+        let entries = as_req.preauth().expect("Missing preauth").entries();
+        assert!(matches!(
+            entries,
+            [PreAuthEntry::EncTimestamp(enc_data)] if enc_data.kvno() == Some(2)
+        ));
+
+        // The KDC has since rotated the key, and replies in the new version of it.
+        let new_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x24; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        let KerberosResponse::AsRep(as_rep) = as_rep(&new_key, &request, nonce(&request)) else {
+            unreachable!();
+        };
+        let as_rep = KerberosAsRep {
+            enc_part: as_rep.enc_part.clone().with_kvno(Some(3)),
+            ..as_rep
+        };
+
+        let step = exchange.step(KerberosResponse::AsRep(as_rep), &credentials);
+        assert!(matches!(
+            step,
+            AsStep::Failed(KrbError::KeyVersionMismatch(3, 2))
+        ));
+    }
+
+    #[test]
+    fn test_as_exchange_request_tampered() {
+        let reply_key = reply_key();
+        let mut exchange = new_exchange();
+        let credentials = Credentials::Key(&reply_key);
+
+        exchange.start().expect("Failed to build request");
+        let AsStep::SendNext(request) = exchange.step(pa_rep(PA_REP), &credentials) else {
+            unreachable!();
+        };
+
+        // The KDC checksummed a request that differs from the one we sent by a byte.
+        let last = exchange.request.len() - 1;
+        exchange.request[last] ^= 0x01;
+
+        let step = exchange.step(as_rep(&reply_key, &request, nonce(&request)), &credentials);
+        assert!(matches!(
+            step,
+            AsStep::Failed(KrbError::ReqEncPaRepMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_as_exchange_rounds() {
+        let credentials = Credentials::Password(Zeroizing::new("password".to_string()));
+
+        // Without a cookie, being asked again means the preauthentication failed.
+        let mut exchange = new_exchange();
+        exchange.start().expect("Failed to build request");
+        assert!(matches!(
+            exchange.step(pa_rep(PA_REP), &credentials),
+            AsStep::SendNext(_)
+        ));
+        assert!(matches!(
+            exchange.step(pa_rep(PA_REP), &credentials),
+            AsStep::Failed(KrbError::KdcError(KrbErrorCode::KdcErrPreauthRequired))
+        ));
+
+        // With one, the conversation continues until the rounds run out.
+        let mut exchange = new_exchange().max_rounds(2);
+        exchange.start().expect("Failed to build request");
+        assert!(matches!(
+            exchange.step(pa_rep(PA_REP_COOKIE), &credentials),
+            AsStep::SendNext(_)
+        ));
+        assert!(matches!(
+            exchange.step(pa_rep(PA_REP_COOKIE), &credentials),
+            AsStep::Failed(KrbError::PreAuthRoundLimit)
+        ));
+    }
+
+    #[test]
+    fn test_as_exchange_clock_skew() {
+        let credentials = Credentials::Password(Zeroizing::new("password".to_string()));
+
+        // Our clock is an hour ahead of the KDC, which refuses the timestamp.
+        let kdc_now = SystemTime::now() - Duration::from_secs(3600);
+        let skew = KerberosResponse::Err(KerberosErrorRep {
+            error_code: KrbErrorCode::KrbApErrSkew,
+            ctime: None,
+            stime: kdc_now,
+            client_realm: None,
+            client_name: None,
+            service_realm: "EXAMPLE.COM".to_string(),
+            service_name: "krbtgt/EXAMPLE.COM".to_string(),
+            error_text: None,
+            error_data: None,
+            client_name_type: 1,
+            service_name_type: 2,
+        });
+
+        let mut exchange = new_exchange();
+        exchange.start().expect("Failed to build request");
+        assert!(matches!(
+            exchange.step(pa_rep(PA_REP), &credentials),
+            AsStep::SendNext(_)
+        ));
+        assert!(matches!(
+            exchange.step(skew, &credentials),
+            AsStep::Failed(KrbError::KdcError(KrbErrorCode::KrbApErrSkew))
+        ));
+        let clock_offset = exchange.kdc_clock_offset().expect("Missing clock offset");
+
+        // Starting again, the timestamp is in the time of the KDC.
+        let mut exchange = new_exchange().clock_offset(clock_offset);
+        exchange.start().expect("Failed to build request");
+        let AsStep::SendNext(KerberosRequest::AsReq(as_req)) =
+            exchange.step(pa_rep(PA_REP), &credentials)
+        else {
+            unreachable!();
+        };
+        let Some([PreAuthEntry::EncTimestamp(enc_data)]) =
+            as_req.preauth().map(|preauth| preauth.entries())
+        else {
+            unreachable!();
+        };
+
+        let data = enc_data
+            .decrypt_data(&reply_key(), KeyUsage::AS_REQ_PA_ENC_TIMESTAMP)
+            .expect("Failed to decrypt timestamp");
+        let timestamp = UNIX_EPOCH
+            + PaEncTsEnc::from_der(&data)
+                .expect("Failed to decode timestamp")
+                .patimestamp
+                .to_unix_duration();
+        let skew = timestamp
+            .duration_since(kdc_now)
+            .unwrap_or_else(|err| err.duration());
+        assert!(skew < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_as_exchange_referral() {
+        let credentials = Credentials::Password(Zeroizing::new("password".to_string()));
+        let referral = |realm: &str| {
+            KerberosResponse::Err(KerberosErrorRep {
+                error_code: KrbErrorCode::KdcErrWrongRealm,
+                ctime: None,
+                stime: SystemTime::now(),
+                client_realm: Some(realm.to_string()),
+                client_name: Some("testuser".to_string()),
+                service_realm: "EXAMPLE.COM".to_string(),
+                service_name: "krbtgt/EXAMPLE.COM".to_string(),
+                error_text: None,
+                error_data: None,
+                client_name_type: 1,
+                service_name_type: 2,
+            })
+        };
+
+        let mut exchange = new_exchange();
+        exchange.start().expect("Failed to build request");
+        let AsStep::Referral { realm } = exchange.step(referral("CHILD.EXAMPLE.COM"), &credentials)
+        else {
+            unreachable!();
+        };
+        assert_eq!(realm, "CHILD.EXAMPLE.COM");
+
+        // A referral to the realm that was asked is an error, not a loop.
+        let mut exchange = new_exchange();
+        exchange.start().expect("Failed to build request");
+        assert!(matches!(
+            exchange.step(referral("EXAMPLE.COM"), &credentials),
+            AsStep::Failed(KrbError::KdcError(KrbErrorCode::KdcErrWrongRealm))
+        ));
+    }
+
+    #[test]
+    fn test_as_exchange_etype_downgrade() {
+        let credentials = Credentials::Password(Zeroizing::new("password".to_string()));
+
+        // An AS-REP to our AES request whose enc-part is in RC4.
+        let downgraded = |request: &KerberosRequest| {
+            let KerberosResponse::AsRep(as_rep) = as_rep(&reply_key(), request, nonce(request))
+            else {
+                unreachable!();
+            };
+            KerberosResponse::AsRep(KerberosAsRep {
+                enc_part: EncryptedData::Unsupported {
+                    etype: 23,
+                    kvno: None,
+                    data: vec![0xab; 64],
+                },
+                ..as_rep
+            })
+        };
+
+        let mut exchange = new_exchange();
+        exchange.start().expect("Failed to build request");
+        let AsStep::SendNext(request) = exchange.step(pa_rep(PA_REP), &credentials) else {
+            unreachable!();
+        };
+        let AsStep::Failed(KrbError::EtypeDowngrade(requested, 23)) =
+            exchange.step(downgraded(&request), &credentials)
+        else {
+            unreachable!();
+        };
+        assert_eq!(requested, request.etypes());
+
+        // Allowing it, the reply is decrypted as it is and fails on its etype.
+        let mut exchange = new_exchange().allow_etype_downgrade(true);
+        exchange.start().expect("Failed to build request");
+        let AsStep::SendNext(request) = exchange.step(pa_rep(PA_REP), &credentials) else {
+            unreachable!();
+        };
+        assert!(matches!(
+            exchange.step(downgraded(&request), &credentials),
+            AsStep::Failed(KrbError::UnsupportedEncryption)
+        ));
+    }
+
+    #[test]
+    fn test_as_exchange_reply_salt() {
+        let credentials = Credentials::Password(Zeroizing::new("password".to_string()));
+
+        let mut exchange = new_exchange();
+        exchange.start().expect("Failed to build request");
+        let AsStep::SendNext(request) = exchange.step(pa_rep(PA_REP), &credentials) else {
+            unreachable!();
+        };
+
+        // The KDC asked for preauthentication with the salt EXAMPLE.COMtestuser, but
+        // the reply is encrypted in a key with the salt that it confirms instead.
+        let confirmed_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &derive_key_external_salt_aes256_cts_hmac_sha1_96(
+                b"password",
+                b"EXAMPLE.COMTestUser",
+                None,
+            )
+            .expect("Failed to derive key"),
+        )
+        .expect("Invalid key");
+        let KerberosResponse::AsRep(mut as_rep) = as_rep(&confirmed_key, &request, nonce(&request))
+        else {
+            unreachable!();
+        };
+        let etype_info2 =
+            hex::decode("301e301ca003020112a1151b134558414d504c452e434f4d5465737455736572")
+                .expect("Failed to decode sample");
+        as_rep.etype_info2 =
+            etype_info2_from_der(&etype_info2).expect("Failed to parse ETYPE-INFO2");
+
+        let AsStep::Done(tgt) = exchange.step(KerberosResponse::AsRep(as_rep), &credentials) else {
+            unreachable!();
+        };
+        assert!(tgt.request_verified());
+    }
+
+    #[test]
+    fn test_as_exchange_derives_key_once() {
+        let credentials = Credentials::Password(Zeroizing::new("password".to_string()));
+        let reply_key = reply_key();
+        PBKDF2_RUNS.with(|runs| runs.set(0));
+
+        let mut exchange = new_exchange();
+        exchange.start().expect("Failed to build request");
+        let AsStep::SendNext(request) = exchange.step(pa_rep(PA_REP), &credentials) else {
+            unreachable!();
+        };
+        assert_eq!(PBKDF2_RUNS.with(|runs| runs.get()), 1);
+
+        // The KDC confirms the salt that the preauthentication was sent with, so the
+        // key of the preauthentication decrypts the reply.
+        let KerberosResponse::AsRep(mut as_rep) = as_rep(&reply_key, &request, nonce(&request))
+        else {
+            unreachable!();
+        };
+        let etype_info2 =
+            hex::decode("301e301ca003020112a1151b134558414d504c452e434f4d7465737475736572")
+                .expect("Failed to decode sample");
+        as_rep.etype_info2 =
+            etype_info2_from_der(&etype_info2).expect("Failed to parse ETYPE-INFO2");
+
+        let AsStep::Done(tgt) = exchange.step(KerberosResponse::AsRep(as_rep), &credentials) else {
+            unreachable!();
+        };
+        assert!(tgt.request_verified());
+        assert_eq!(PBKDF2_RUNS.with(|runs| runs.get()), 1);
+    }
+
+    #[test]
+    fn test_as_exchange_derives_key_once_per_conversation() {
+        let credentials = Credentials::Password(Zeroizing::new("password".to_string()));
+        let reply_key = reply_key();
+        PBKDF2_RUNS.with(|runs| runs.set(0));
+
+        let mut exchange = new_exchange();
+        exchange.start().expect("Failed to build request");
+        assert!(matches!(
+            exchange.step(pa_rep(PA_REP_COOKIE), &credentials),
+            AsStep::SendNext(_)
+        ));
+        assert_eq!(PBKDF2_RUNS.with(|runs| runs.get()), 1);
+
+        // The KDC continues the conversation with the same salt, so the key of the
+        // previous round is sent again rather than derived again.
+        let AsStep::SendNext(request) = exchange.step(pa_rep(PA_REP_COOKIE), &credentials) else {
+            unreachable!();
+        };
+        assert_eq!(PBKDF2_RUNS.with(|runs| runs.get()), 1);
+
+        let AsStep::Done(tgt) =
+            exchange.step(as_rep(&reply_key, &request, nonce(&request)), &credentials)
+        else {
+            unreachable!();
+        };
+        assert!(tgt.request_verified());
+        assert_eq!(PBKDF2_RUNS.with(|runs| runs.get()), 1);
+    }
+}
@@ -0,0 +1,356 @@
+use crate::proto::{KerberosRequest, KerberosResponse};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use der::{Decode, Encode, Reader};
+use std::io;
+use tracing::{debug_span, field, trace};
+
+/// The high bit of the TCP record mark, which is reserved by RFC4120.
+const RECORD_MARK_RESERVED_BIT: u32 = 0x8000_0000;
+
+/// The largest record that a record mark can describe, 2^31 - 1 bytes.
+pub(crate) const RECORD_MAX_LEN: usize = (RECORD_MARK_RESERVED_BIT - 1) as usize;
+
+pub(crate) fn map_der_decode_error(err: der::Error) -> io::Error {
+    match err.kind() {
+        // The outer application tag was valid DER but isn't a message that we
+        // understand. This is distinct to a corrupt frame, so the caller can
+        // decide if they want to retry or drop the connection.
+        der::ErrorKind::TagUnexpected {
+            expected: None,
+            actual,
+        } => io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Unexpected kerberos message type: {}", actual),
+        ),
+        _ => io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+    }
+}
+
+// Each message is traced with a span describing it. Only the message type, length
+// and error code are recorded, never the content, since it carries ciphertexts.
+
+pub(crate) fn decode_response(record: &[u8]) -> Result<KerberosResponse, io::Error> {
+    let span = debug_span!(
+        "decode_response",
+        len = record.len(),
+        msg_type = field::Empty,
+        error_code = field::Empty
+    );
+    let _enter = span.enter();
+
+    let response = KerberosResponse::from_der(record).map_err(map_der_decode_error)?;
+
+    span.record("msg_type", response.message_type());
+    if let Some(error_code) = response.error_code() {
+        span.record("error_code", field::debug(error_code));
+    }
+    trace!("Decoded response");
+
+    Ok(response)
+}
+
+pub(crate) fn decode_request(record: &[u8]) -> Result<KerberosRequest, io::Error> {
+    let span = debug_span!(
+        "decode_request",
+        len = record.len(),
+        msg_type = field::Empty
+    );
+    let _enter = span.enter();
+
+    let request = KerberosRequest::from_der(record).map_err(map_der_decode_error)?;
+
+    span.record("msg_type", request.message_type());
+    trace!("Decoded request");
+
+    Ok(request)
+}
+
+pub(crate) fn request_to_der(msg: &KerberosRequest, max_size: usize) -> io::Result<Vec<u8>> {
+    let span = debug_span!(
+        "encode_request",
+        msg_type = msg.message_type(),
+        len = field::Empty
+    );
+    let _enter = span.enter();
+
+    let der_bytes = msg
+        .to_der()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    span.record("len", der_bytes.len());
+    check_encoded_len(&der_bytes, max_size)?;
+    trace!("Encoded request");
+
+    Ok(der_bytes)
+}
+
+pub(crate) fn response_to_der(msg: &KerberosResponse, max_size: usize) -> io::Result<Vec<u8>> {
+    let span = debug_span!(
+        "encode_response",
+        msg_type = msg.message_type(),
+        len = field::Empty,
+        error_code = field::Empty
+    );
+    let _enter = span.enter();
+
+    if let Some(error_code) = msg.error_code() {
+        span.record("error_code", field::debug(error_code));
+    }
+
+    let der_bytes = msg
+        .to_der()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    span.record("len", der_bytes.len());
+    check_encoded_len(&der_bytes, max_size)?;
+    trace!("Encoded response");
+
+    Ok(der_bytes)
+}
+
+/// Split a complete record from the buffer, returning `None` if the record has not
+/// been completely received yet. The record shares the memory of the buffer, so
+/// the message is never copied before it is DER decoded.
+pub(crate) fn decode_record(
+    buf: &mut BytesMut,
+    max_size: usize,
+) -> Result<Option<Bytes>, io::Error> {
+    // The record mark is a four byte big endian header. Until we have
+    // that we can't know how much more to wait for.
+    let Some(header) = buf.get(..4) else {
+        return Ok(None);
+    };
+
+    let mut header_bytes = [0u8; 4];
+    header_bytes.copy_from_slice(header);
+    let record_mark = u32::from_be_bytes(header_bytes);
+
+    // RFC4120 7.2.2 reserves the high bit for future expansion, and it MUST
+    // be zero. A peer setting it is either speaking a newer protocol or
+    // is using RPC record marking, neither of which we understand.
+    if record_mark & RECORD_MARK_RESERVED_BIT != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Record mark has the reserved high bit set",
+        ));
+    }
+
+    let record_len = record_mark as usize;
+
+    // Check this before we reserve anything, else a peer can make us allocate
+    // up to 2GiB just by sending a header.
+    if record_len > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Record length {} exceeds maximum of {}",
+                record_len, max_size
+            ),
+        ));
+    }
+
+    if buf.len() < 4 + record_len {
+        // The remainder of the record hasn't arrived yet. Leave the buffer
+        // untouched and hint how much more we need.
+        buf.reserve(4 + record_len - buf.len());
+        return Ok(None);
+    }
+
+    // We have a complete frame, so we can consume it now.
+    buf.advance(4);
+    let record = buf.split_to(record_len).freeze();
+
+    check_record_complete(&record)?;
+
+    Ok(Some(record))
+}
+
+/// Ensure that the message in the record isn't longer than the record itself.
+/// This is what happens when a peer splits a message over multiple RPC record
+/// marking fragments, which RFC4120 doesn't permit. Without this check the
+/// caller would only see a confusing DER error about a truncated message.
+fn check_record_complete(record: &[u8]) -> io::Result<()> {
+    // If the header can't be parsed, the DER decode will report why.
+    let Ok(mut reader) = der::SliceReader::new(record) else {
+        return Ok(());
+    };
+    let Ok(header) = der::Header::decode(&mut reader) else {
+        return Ok(());
+    };
+    let Ok(message_len) = reader.position() + header.length else {
+        return Ok(());
+    };
+
+    if message_len > reader.input_len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Message of {} bytes is truncated to a record of {} bytes, multi-fragment records are not supported",
+                message_len,
+                reader.input_len()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_encoded_len(der_bytes: &[u8], max_size: usize) -> io::Result<()> {
+    if der_bytes.len() > max_size {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Encoded message length {} exceeds maximum of {}",
+                der_bytes.len(),
+                max_size
+            ),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn encode_record(der_bytes: &[u8], buf: &mut BytesMut) -> io::Result<()> {
+    /* RFC4120 section 7.2.2
+     *
+     * Each request (KRB_KDC_REQ) and response (KRB_KDC_REP or KRB_ERROR)
+     * sent over the TCP stream is preceded by the length of the request as
+     * 4 octets in network byte order. The high bit of the length is
+     * reserved for future expansion and MUST currently be set to zero.
+     */
+    let record_len = u32::try_from(der_bytes.len())
+        .ok()
+        .filter(|len| len & RECORD_MARK_RESERVED_BIT == 0)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Message is too large to be framed",
+            )
+        })?;
+
+    buf.reserve(4 + der_bytes.len());
+    buf.put_u32(record_len);
+    buf.put_slice(der_bytes);
+    Ok(())
+}
+
+pub(crate) fn encode_request(
+    msg: &KerberosRequest,
+    max_size: usize,
+    buf: &mut BytesMut,
+) -> io::Result<()> {
+    let der_bytes = request_to_der(msg, max_size)?;
+    encode_record(&der_bytes, buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_record, decode_response, request_to_der};
+    use crate::constants::DEFAULT_IO_MAX_SIZE;
+    use crate::proto::KerberosRequest;
+    use crate::tests::{AS_REP_SAMPLE, RESPONSE_TOO_BIG_SAMPLE};
+    use bytes::{BufMut, BytesMut};
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime};
+    use tracing::field::{Field, Visit};
+    use tracing::span;
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    /// Captures the fields of every span as `name=value`.
+    #[derive(Clone, Default)]
+    struct SpanFields(Arc<Mutex<Vec<String>>>);
+
+    impl SpanFields {
+        fn push(&self, record: impl FnOnce(&mut dyn Visit)) {
+            let mut fields = self.0.lock().expect("Poisoned lock");
+            record(&mut FieldVisitor(&mut fields));
+        }
+    }
+
+    struct FieldVisitor<'a>(&'a mut Vec<String>);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    impl<S: tracing::Subscriber> Layer<S> for SpanFields {
+        fn on_new_span(&self, attrs: &span::Attributes<'_>, _id: &span::Id, _ctx: Context<'_, S>) {
+            self.push(|visitor| attrs.record(visitor));
+        }
+
+        fn on_record(&self, _id: &span::Id, values: &span::Record<'_>, _ctx: Context<'_, S>) {
+            self.push(|visitor| values.record(visitor));
+        }
+    }
+
+    #[test]
+    fn test_decode_record_zero_copy() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let mut buf = BytesMut::with_capacity(4 + as_rep.len());
+        buf.put_u32(as_rep.len() as u32);
+        buf.put_slice(&as_rep);
+
+        let message_ptr = buf[4..].as_ptr();
+
+        let record = decode_record(&mut buf, DEFAULT_IO_MAX_SIZE)
+            .expect("Failed to decode")
+            .expect("Incomplete record");
+
+        // The record is a view of the received bytes, not a copy of them.
+        assert_eq!(record.as_ptr(), message_ptr);
+        assert_eq!(record, as_rep);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_message_spans() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let krb_error = hex::decode(RESPONSE_TOO_BIG_SAMPLE).expect("Failed to decode sample");
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build()
+        .expect("Failed to build request");
+
+        let captured = SpanFields::default();
+        let subscriber = tracing_subscriber::registry().with(captured.clone());
+
+        let as_req_len = tracing::subscriber::with_default(subscriber, || {
+            decode_response(&as_rep).expect("Failed to decode AS-REP");
+            decode_response(&krb_error).expect("Failed to decode KRB-ERROR");
+            request_to_der(&as_req, DEFAULT_IO_MAX_SIZE)
+                .expect("Failed to encode AS-REQ")
+                .len()
+        });
+
+        let fields = captured.0.lock().expect("Poisoned lock");
+
+        for expected in [
+            format!("len={}", as_rep.len()),
+            "msg_type=\"AS-REP\"".to_string(),
+            format!("len={}", krb_error.len()),
+            "msg_type=\"KRB-ERROR\"".to_string(),
+            "error_code=KrbErrResponseTooBig".to_string(),
+            "msg_type=\"AS-REQ\"".to_string(),
+            format!("len={}", as_req_len),
+        ] {
+            assert!(
+                fields.contains(&expected),
+                "{} not in {:?}",
+                expected,
+                fields
+            );
+        }
+
+        // Nothing from the content of the messages is recorded.
+        assert!(fields.iter().all(|field| field.starts_with("len=")
+            || field.starts_with("msg_type=")
+            || field.starts_with("error_code=")));
+    }
+}
@@ -0,0 +1,282 @@
+use crate::asn1::kdc_proxy_message::KdcProxyMessage;
+use crate::asn1::kerberos_string::KerberosString;
+use crate::asn1::{Ia5String, OctetString};
+use crate::constants::DEFAULT_IO_MAX_SIZE;
+use crate::error::KrbError;
+use crate::framing::{decode_record, decode_response, encode_request};
+use crate::proto::{KerberosRequest, KerberosResponse};
+use bytes::BytesMut;
+use der::{Decode, Encode};
+use std::future::Future;
+use tracing::debug;
+
+/// The content type of a KDC-PROXY-MESSAGE in both requests and replies.
+pub const KDC_PROXY_CONTENT_TYPE: &str = "application/kerberos";
+
+/// The HTTP client that a [KdcProxyTransport] uses to reach the proxy.
+pub trait KdcProxyHttpClient {
+    /// POST `body` to `url` with a content type of [KDC_PROXY_CONTENT_TYPE],
+    /// returning the body of a successful response.
+    fn post(
+        &self,
+        url: &str,
+        body: Vec<u8>,
+    ) -> impl Future<Output = Result<Vec<u8>, KrbError>> + Send;
+}
+
+/// Exchange kerberos messages with a KDC through an MS-KKDCP proxy, which tunnels
+/// them over HTTPS. The proxy url is commonly of the form `https://host/KdcProxy`.
+pub struct KdcProxyTransport<C> {
+    url: String,
+    client: C,
+}
+
+impl<C: KdcProxyHttpClient> KdcProxyTransport<C> {
+    pub fn new(url: &str, client: C) -> Self {
+        KdcProxyTransport {
+            url: url.to_string(),
+            client,
+        }
+    }
+
+    pub async fn exchange(&self, request: &KerberosRequest) -> Result<KerberosResponse, KrbError> {
+        let body = wrap_request(request)?;
+
+        let reply = self.client.post(&self.url, body).await?;
+
+        unwrap_response(&reply)
+    }
+}
+
+fn wrap_request(request: &KerberosRequest) -> Result<Vec<u8>, KrbError> {
+    // The kerb-message is the request exactly as it would be sent over TCP,
+    // including the length prefix.
+    let mut kerb_message = BytesMut::new();
+    encode_request(request, DEFAULT_IO_MAX_SIZE, &mut kerb_message).map_err(|err| {
+        debug!(?err, "Failed to encode request");
+        KrbError::IoError(err.kind())
+    })?;
+
+    let target_domain = Ia5String::new(request.realm())
+        .map(KerberosString)
+        .map_err(|_| KrbError::DerEncodeKdcProxyMessage)?;
+
+    let kerb_message =
+        OctetString::new(kerb_message.to_vec()).map_err(|_| KrbError::DerEncodeKdcProxyMessage)?;
+
+    KdcProxyMessage {
+        kerb_message,
+        target_domain: Some(target_domain),
+        dclocator_hint: None,
+    }
+    .to_der()
+    .map_err(|_| KrbError::DerEncodeKdcProxyMessage)
+}
+
+fn unwrap_response(reply: &[u8]) -> Result<KerberosResponse, KrbError> {
+    let proxy_message =
+        KdcProxyMessage::from_der(reply).map_err(|_| KrbError::DerDecodeKdcProxyMessage)?;
+
+    let mut kerb_message = BytesMut::from(proxy_message.kerb_message.as_bytes());
+
+    let record = decode_record(&mut kerb_message, DEFAULT_IO_MAX_SIZE)
+        .map_err(|err| {
+            debug!(?err, "Failed to decode proxied response");
+            KrbError::IoError(err.kind())
+        })?
+        // The proxy must return the whole reply in a single message.
+        .ok_or(KrbError::DerDecodeKdcProxyMessage)?;
+
+    decode_response(&record).map_err(|err| {
+        debug!(?err, "Failed to decode proxied response");
+        KrbError::IoError(err.kind())
+    })
+}
+
+#[cfg(feature = "kkdcp-reqwest")]
+impl KdcProxyHttpClient for reqwest::Client {
+    async fn post(&self, url: &str, body: Vec<u8>) -> Result<Vec<u8>, KrbError> {
+        let response = reqwest::Client::post(self, url)
+            .header(reqwest::header::CONTENT_TYPE, KDC_PROXY_CONTENT_TYPE)
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| {
+                debug!(?err, "KDC proxy request failed");
+                KrbError::HttpRequestFailed(err.to_string())
+            })?;
+
+        response
+            .bytes()
+            .await
+            .map(|body| body.to_vec())
+            .map_err(|err| {
+                debug!(?err, "Failed to read KDC proxy response");
+                KrbError::HttpRequestFailed(err.to_string())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{wrap_request, KdcProxyHttpClient, KdcProxyTransport};
+    use crate::asn1::kdc_proxy_message::KdcProxyMessage;
+    use crate::asn1::OctetString;
+    use crate::error::KrbError;
+    use crate::proto::{KerberosRequest, KerberosResponse};
+    use crate::tests::{as_rep_record, as_req};
+    use der::{Decode, Encode};
+
+    fn proxy_reply() -> Vec<u8> {
+        KdcProxyMessage {
+            kerb_message: OctetString::new(as_rep_record()).expect("Failed to build octet string"),
+            target_domain: None,
+            dclocator_hint: None,
+        }
+        .to_der()
+        .expect("Failed to encode proxy message")
+    }
+
+    struct CannedProxy;
+
+    impl KdcProxyHttpClient for CannedProxy {
+        async fn post(&self, url: &str, body: Vec<u8>) -> Result<Vec<u8>, KrbError> {
+            assert_eq!(url, "https://kdc.example.com/KdcProxy");
+
+            let proxy_message = KdcProxyMessage::from_der(&body).expect("Invalid proxy message");
+            let kerb_message = proxy_message.kerb_message.as_bytes();
+            assert!(KerberosRequest::from_der(&kerb_message[4..]).is_ok());
+
+            Ok(proxy_reply())
+        }
+    }
+
+    #[test]
+    fn test_wrap_request() {
+        let body = wrap_request(&as_req()).expect("Failed to wrap request");
+
+        let proxy_message = KdcProxyMessage::from_der(&body).expect("Invalid proxy message");
+
+        let target_domain = proxy_message.target_domain.expect("Missing target domain");
+        assert_eq!(target_domain.as_str(), "EXAMPLE.COM");
+        assert_eq!(proxy_message.dclocator_hint, None);
+
+        // The message retains the TCP length prefix.
+        let kerb_message = proxy_message.kerb_message.as_bytes();
+        let (len, der_bytes) = kerb_message.split_at(4);
+        assert_eq!(len, (der_bytes.len() as u32).to_be_bytes());
+
+        let KerberosRequest::AsReq(as_req) =
+            KerberosRequest::from_der(der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(as_req.client_name, "testuser");
+        assert_eq!(as_req.realm, "EXAMPLE.COM");
+    }
+
+    #[tokio::test]
+    async fn test_proxy_exchange() {
+        let transport = KdcProxyTransport::new("https://kdc.example.com/KdcProxy", CannedProxy);
+
+        let response = transport
+            .exchange(&as_req())
+            .await
+            .expect("Exchange failed");
+
+        assert!(matches!(response, KerberosResponse::AsRep(_)));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_invalid_reply() {
+        struct BrokenProxy;
+
+        impl KdcProxyHttpClient for BrokenProxy {
+            async fn post(&self, _url: &str, _body: Vec<u8>) -> Result<Vec<u8>, KrbError> {
+                Ok(vec![0x30, 0x03, 0x02, 0x01, 0x05])
+            }
+        }
+
+        let transport = KdcProxyTransport::new("https://kdc.example.com/KdcProxy", BrokenProxy);
+
+        let err = transport
+            .exchange(&as_req())
+            .await
+            .expect_err("Invalid reply was accepted");
+
+        assert!(matches!(err, KrbError::DerDecodeKdcProxyMessage));
+    }
+
+    #[cfg(feature = "kkdcp-reqwest")]
+    #[tokio::test]
+    async fn test_reqwest_proxy_exchange() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+        let addr = listener.local_addr().expect("Unable to get local address");
+
+        // Just enough of HTTP/1.1 to answer a single POST with a canned reply.
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("Failed to accept");
+
+            let mut request = Vec::with_capacity(4096);
+            let mut buf = [0; 1024];
+            let body_start = loop {
+                let len = stream.read(&mut buf).await.expect("Failed to read request");
+                assert_ne!(len, 0, "Connection closed");
+                request.extend_from_slice(&buf[..len]);
+                if let Some(idx) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break idx + 4;
+                }
+            };
+
+            let headers = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+            assert!(headers.starts_with("post /kdcproxy "));
+            assert!(headers.contains("content-type: application/kerberos"));
+
+            let content_length: usize = headers
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length: "))
+                .and_then(|len| len.trim().parse().ok())
+                .expect("Missing content length");
+
+            while request.len() < body_start + content_length {
+                let len = stream.read(&mut buf).await.expect("Failed to read body");
+                assert_ne!(len, 0, "Connection closed");
+                request.extend_from_slice(&buf[..len]);
+            }
+
+            KdcProxyMessage::from_der(&request[body_start..]).expect("Invalid proxy message");
+
+            let reply = proxy_reply();
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/kerberos\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                reply.len()
+            );
+            stream
+                .write_all(header.as_bytes())
+                .await
+                .expect("Failed to write header");
+            stream
+                .write_all(&reply)
+                .await
+                .expect("Failed to write body");
+        });
+
+        let url = format!("http://{}/KdcProxy", addr);
+        let transport = KdcProxyTransport::new(&url, reqwest::Client::new());
+
+        let response = transport
+            .exchange(&as_req())
+            .await
+            .expect("Exchange failed");
+
+        assert!(matches!(response, KerberosResponse::AsRep(_)));
+
+        server.await.expect("Server task failed");
+    }
+}
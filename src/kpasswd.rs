@@ -0,0 +1,716 @@
+use crate::asn1::{
+    change_passwd_data::ChangePasswdData,
+    constants::message_types::KrbMessageType,
+    enc_krb_priv_part::{EncKrbPrivPart, TaggedEncKrbPrivPart},
+    encrypted_data::EncryptedData as KdcEncryptedData,
+    host_address::HostAddress as KdcHostAddress,
+    kerberos_time::KerberosTime,
+    krb_error::TaggedKrbError,
+    krb_priv::{KrbPriv, TaggedKrbPriv},
+    OctetString,
+};
+use crate::client::KerberosClient;
+use crate::constants::DEFAULT_IO_MAX_SIZE;
+use crate::error::KrbError;
+use crate::proto::{
+    AuthenticatedReply, BaseKey, EncryptedData, HostAddress, KeyUsage, KrbErrorCode,
+};
+use der::{Decode, Encode};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use rand::{thread_rng, Rng};
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{debug, trace};
+use zeroize::Zeroizing;
+
+/// The port that the kpasswd service listens on, alongside the KDC.
+pub const KPASSWD_PORT: u16 = 464;
+
+/// RFC 3244 - the protocol version of a change password request.
+const KPASSWD_REQUEST_VERSION: u16 = 0xff80;
+/// RFC 3244 - the protocol version of a reply. Some servers echo the request
+/// version instead, so both are accepted.
+const KPASSWD_REPLY_VERSION: u16 = 0x0001;
+
+/// The message length, protocol version and AP-REQ (or AP-REP) length.
+const KPASSWD_HEADER_LEN: usize = 6;
+
+/// The result of a password change, as reported by the kpasswd server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u16)]
+pub enum KpasswdResultCode {
+    Success = 0,
+    Malformed = 1,
+    HardError = 2,
+    AuthError = 3,
+    SoftError = 4,
+    AccessDenied = 5,
+    BadVersion = 6,
+    InitialFlagNeeded = 7,
+}
+
+/// Change the password of `client_name` from `old_password` to `new_password`,
+/// using the kpasswd service that runs on the same host as the client's KDC. The
+/// new password is wiped from memory once it has been sent.
+pub async fn change_password(
+    client: &mut KerberosClient,
+    client_name: &str,
+    old_password: &str,
+    new_password: Zeroizing<String>,
+) -> Result<(), KrbError> {
+    // RFC 3244 - the ticket must be for kadmin/changepw and come from an AS exchange,
+    // so that the server knows the client just proved knowledge of the old password.
+    let until = SystemTime::now() + Duration::from_secs(300);
+    let reply = client
+        .authenticate(client_name, "kadmin/changepw", old_password, until)
+        .await?;
+
+    let kpasswd = SocketAddr::new(client.kdc().ip(), KPASSWD_PORT);
+    change_password_with_ticket(kpasswd, &reply, &new_password).await
+}
+
+async fn change_password_with_ticket(
+    kpasswd: SocketAddr,
+    reply: &AuthenticatedReply,
+    new_password: &str,
+) -> Result<(), KrbError> {
+    let mut stream = TcpStream::connect(kpasswd).await.map_err(|err| {
+        debug!(?err, %kpasswd, "Unable to connect to kpasswd server");
+        KrbError::IoError(err.kind())
+    })?;
+
+    let local_addr = stream.local_addr().map_err(|err| {
+        debug!(?err, "Unable to get local address");
+        KrbError::IoError(err.kind())
+    })?;
+
+    // The top two bits are left clear as some implementations treat the
+    // sequence number as signed.
+    let seq_number = thread_rng().gen::<u32>() & 0x3fff_ffff;
+
+    // RFC 3244 - the KRB-PRIV is encrypted in a subkey of the authenticator, rather
+    // than in the session key of the ticket.
+    let subkey = BaseKey::generate_session_key(reply.session_key().etype())?;
+    let now = SystemTime::now();
+
+    let request = build_request(reply, &subkey, new_password, local_addr, now, seq_number)?;
+
+    // Over TCP the message is prefixed by its length, the same as a KDC request.
+    let mut record = Vec::with_capacity(4 + request.len());
+    record.extend_from_slice(&(request.len() as u32).to_be_bytes());
+    record.extend_from_slice(&request);
+
+    stream.write_all(&record).await.map_err(|err| {
+        debug!(?err, "Failed to send request to kpasswd server");
+        KrbError::IoError(err.kind())
+    })?;
+
+    let mut record_mark = [0u8; 4];
+    stream.read_exact(&mut record_mark).await.map_err(|err| {
+        debug!(?err, "Failed to receive reply from kpasswd server");
+        KrbError::IoError(err.kind())
+    })?;
+
+    let len = u32::from_be_bytes(record_mark) as usize;
+    if len > DEFAULT_IO_MAX_SIZE {
+        debug!(%len, "kpasswd reply is too large");
+        return Err(KrbError::KpasswdInvalidReply);
+    }
+
+    let mut message = vec![0; len];
+    stream.read_exact(&mut message).await.map_err(|err| {
+        debug!(?err, "Failed to receive reply from kpasswd server");
+        KrbError::IoError(err.kind())
+    })?;
+
+    decode_reply(&message, reply, &subkey, now)
+}
+
+fn build_request(
+    reply: &AuthenticatedReply,
+    subkey: &BaseKey,
+    new_password: &str,
+    local_addr: SocketAddr,
+    now: SystemTime,
+    seq_number: u32,
+) -> Result<Vec<u8>, KrbError> {
+    let ap_req = reply
+        .build_ap_req(
+            now,
+            Some(seq_number),
+            None,
+            Some(subkey),
+            KeyUsage::AP_REQ_AUTHENTICATOR,
+        )?
+        .to_der()
+        .map_err(|_| KrbError::DerEncodeApReq)?;
+
+    let user_data = OctetString::new(new_password.as_bytes())
+        .and_then(|new_passwd| {
+            ChangePasswdData {
+                new_passwd,
+                targ_name: None,
+                targ_realm: None,
+            }
+            .to_der()
+        })
+        .and_then(OctetString::new)
+        .map_err(|_| KrbError::DerEncodeKrbPriv)?;
+
+    let since_epoch = now
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| KrbError::DerEncodeKrbPriv)?;
+    let timestamp = KerberosTime::from_unix_duration(Duration::from_secs(since_epoch.as_secs()))
+        .map_err(|_| KrbError::DerEncodeKrbPriv)?;
+
+    let s_address = KdcHostAddress::try_from(&HostAddress::from(local_addr.ip()))
+        .map_err(|_| KrbError::DerEncodeKrbPriv)?;
+
+    let enc_part = TaggedEncKrbPrivPart(EncKrbPrivPart {
+        user_data,
+        timestamp: Some(timestamp),
+        usec: Some(since_epoch.subsec_micros()),
+        seq_number: Some(seq_number),
+        s_address,
+        r_address: None,
+    })
+    .to_der()
+    .map_err(|_| KrbError::DerEncodeKrbPriv)?;
+
+    let enc_part = subkey.encrypt_data(&enc_part, KeyUsage::KRB_PRIV)?;
+
+    let krb_priv = KdcEncryptedData::try_from(&enc_part)
+        .and_then(|enc_part| {
+            TaggedKrbPriv(KrbPriv {
+                pvno: 5,
+                msg_type: KrbMessageType::KrbPriv as u8,
+                enc_part,
+            })
+            .to_der()
+        })
+        .map_err(|_| KrbError::DerEncodeKrbPriv)?;
+
+    let message_len = KPASSWD_HEADER_LEN + ap_req.len() + krb_priv.len();
+    let message_len = u16::try_from(message_len).map_err(|_| KrbError::DerEncodeKrbPriv)?;
+    // The AP-REQ can't be longer than the whole message.
+    let ap_req_len = ap_req.len() as u16;
+
+    let mut message = Vec::with_capacity(message_len as usize);
+    message.extend_from_slice(&message_len.to_be_bytes());
+    message.extend_from_slice(&KPASSWD_REQUEST_VERSION.to_be_bytes());
+    message.extend_from_slice(&ap_req_len.to_be_bytes());
+    message.extend_from_slice(&ap_req);
+    message.extend_from_slice(&krb_priv);
+
+    Ok(message)
+}
+
+fn decode_reply(
+    message: &[u8],
+    reply: &AuthenticatedReply,
+    subkey: &BaseKey,
+    now: SystemTime,
+) -> Result<(), KrbError> {
+    let Some((header, body)) = message.split_first_chunk::<KPASSWD_HEADER_LEN>() else {
+        return Err(KrbError::KpasswdInvalidReply);
+    };
+
+    let message_len = u16::from_be_bytes([header[0], header[1]]) as usize;
+    let version = u16::from_be_bytes([header[2], header[3]]);
+    let ap_rep_len = u16::from_be_bytes([header[4], header[5]]) as usize;
+
+    trace!(%message_len, %version, %ap_rep_len);
+
+    if message_len != message.len() {
+        debug!(%message_len, len = %message.len(), "kpasswd reply length mismatch");
+        return Err(KrbError::KpasswdInvalidReply);
+    }
+
+    // An error is returned as a bare KRB-ERROR, without an AP-REP.
+    if ap_rep_len == 0 {
+        let krb_error = TaggedKrbError::from_der(body)
+            .map_err(|_| KrbError::KpasswdInvalidReply)?
+            .0;
+
+        // RFC 3244 - the e-data of the error carries the result code and string.
+        if let Some(error_data) = krb_error.error_data {
+            let (result_code, message) = decode_result(error_data.as_bytes())?;
+            return Err(KrbError::PasswordChangeFailed(result_code, message));
+        }
+
+        let error_code = KrbErrorCode::try_from(krb_error.error_code)
+            .map_err(|_| KrbError::KpasswdInvalidReply)?;
+        return Err(KrbError::KdcError(error_code));
+    }
+
+    if version != KPASSWD_REPLY_VERSION && version != KPASSWD_REQUEST_VERSION {
+        debug!(%version, "Unsupported kpasswd reply version");
+        return Err(KrbError::KpasswdInvalidReply);
+    }
+
+    let (Some(ap_rep), Some(krb_priv)) = (body.get(..ap_rep_len), body.get(ap_rep_len..)) else {
+        return Err(KrbError::KpasswdInvalidReply);
+    };
+
+    // The AP-REP proves that the server holds the kadmin/changepw key, and that it
+    // answers the authenticator that we just sent, to the microsecond.
+    let ap_reply = reply.decrypt_ap_rep(ap_rep)?;
+    if micros_since_epoch(ap_reply.ctime()) != micros_since_epoch(now) {
+        debug!("kpasswd AP-REP doesn't answer our authenticator");
+        return Err(KrbError::KpasswdInvalidReply);
+    }
+
+    // The server may replace our subkey with one of its own.
+    let key = ap_reply.subkey().unwrap_or(subkey);
+
+    let krb_priv = TaggedKrbPriv::from_der(krb_priv)
+        .map_err(|_| KrbError::DerDecodeKrbPriv)?
+        .0;
+
+    let cleartext =
+        EncryptedData::try_from(krb_priv.enc_part)?.decrypt_data(key, KeyUsage::KRB_PRIV)?;
+
+    let enc_part = TaggedEncKrbPrivPart::from_der(&cleartext)
+        .map_err(|_| KrbError::DerDecodeKrbPriv)?
+        .0;
+
+    match decode_result(enc_part.user_data.as_bytes())? {
+        (KpasswdResultCode::Success, _) => Ok(()),
+        (result_code, message) => Err(KrbError::PasswordChangeFailed(result_code, message)),
+    }
+}
+
+fn micros_since_epoch(time: SystemTime) -> Option<u128> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|since_epoch| since_epoch.as_micros())
+}
+
+fn decode_result(result: &[u8]) -> Result<(KpasswdResultCode, String), KrbError> {
+    let Some((result_code, result_string)) = result.split_first_chunk::<2>() else {
+        return Err(KrbError::KpasswdInvalidReply);
+    };
+
+    let result_code = KpasswdResultCode::try_from(u16::from_be_bytes(*result_code))
+        .map_err(|_| KrbError::KpasswdInvalidReply)?;
+
+    let message = String::from_utf8_lossy(result_string)
+        .trim_end_matches('\0')
+        .to_string();
+
+    Ok((result_code, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        change_password_with_ticket, decode_reply, KpasswdResultCode, KPASSWD_REQUEST_VERSION,
+    };
+    use crate::asn1::{
+        ap_rep::{ApRep, TaggedApRep},
+        ap_req::TaggedApReq,
+        authenticator::{Authenticator, TaggedAuthenticator},
+        change_passwd_data::ChangePasswdData,
+        constants::message_types::KrbMessageType,
+        enc_ap_rep_part::{EncApRepPart, TaggedEncApRepPart},
+        enc_krb_priv_part::{EncKrbPrivPart, TaggedEncKrbPrivPart},
+        encrypted_data::EncryptedData as KdcEncryptedData,
+        encryption_key::EncryptionKey as KdcEncryptionKey,
+        host_address::HostAddress,
+        kerberos_string::KerberosString,
+        kerberos_time::KerberosTime,
+        krb_error::{KrbError as KdcKrbError, TaggedKrbError},
+        krb_priv::{KrbPriv, TaggedKrbPriv},
+        principal_name::PrincipalName,
+        Ia5String, OctetString,
+    };
+    use crate::error::KrbError;
+    use crate::proto::{
+        AuthenticatedReply, BaseKey, EncryptedData, EncryptionType, FlagSet, KerberosResponse,
+        KeyUsage,
+    };
+    use crate::tests::AS_REP_SAMPLE;
+    use der::{Decode, Encode};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    fn session_key() -> BaseKey {
+        BaseKey::from_bytes(EncryptionType::AES256_CTS_HMAC_SHA1_96, &[7; 32]).expect("Invalid key")
+    }
+
+    fn authenticated_reply() -> AuthenticatedReply {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: session_key(),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        }
+    }
+
+    async fn read_request(stream: &mut TcpStream) -> Vec<u8> {
+        let len = stream.read_u32().await.expect("Failed to read record mark");
+        let mut message = vec![0; len as usize];
+        stream
+            .read_exact(&mut message)
+            .await
+            .expect("Failed to read request");
+        message
+    }
+
+    fn reply_message(ap_rep: &[u8], body: &[u8]) -> Vec<u8> {
+        let message_len = 6 + ap_rep.len() + body.len();
+        let mut reply = (message_len as u16).to_be_bytes().to_vec();
+        reply.extend_from_slice(&1u16.to_be_bytes());
+        reply.extend_from_slice(&(ap_rep.len() as u16).to_be_bytes());
+        reply.extend_from_slice(ap_rep);
+        reply.extend_from_slice(body);
+        reply
+    }
+
+    async fn write_reply(stream: &mut TcpStream, ap_rep: &[u8], body: &[u8]) {
+        let message = reply_message(ap_rep, body);
+        let mut reply = (message.len() as u32).to_be_bytes().to_vec();
+        reply.extend_from_slice(&message);
+
+        stream
+            .write_all(&reply)
+            .await
+            .expect("Failed to write reply");
+    }
+
+    /// The authenticator of a request, its subkey and the new password that the
+    /// KRB-PRIV carries in that subkey.
+    fn decode_request(message: &[u8]) -> (Authenticator, BaseKey, Vec<u8>) {
+        let message_len = u16::from_be_bytes([message[0], message[1]]) as usize;
+        assert_eq!(message_len, message.len());
+        assert_eq!(
+            u16::from_be_bytes([message[2], message[3]]),
+            KPASSWD_REQUEST_VERSION
+        );
+
+        let ap_req_len = u16::from_be_bytes([message[4], message[5]]) as usize;
+        let (ap_req, krb_priv) = message[6..].split_at(ap_req_len);
+
+        let ap_req = TaggedApReq::from_der(ap_req).expect("Invalid AP-REQ").0;
+        assert_eq!(ap_req.msg_type, KrbMessageType::KrbApReq as u8);
+
+        let authenticator = EncryptedData::try_from(ap_req.authenticator)
+            .and_then(|authenticator| {
+                authenticator.decrypt_data(&session_key(), KeyUsage::AP_REQ_AUTHENTICATOR)
+            })
+            .expect("Failed to decrypt authenticator");
+        let authenticator = TaggedAuthenticator::from_der(&authenticator)
+            .expect("Invalid authenticator")
+            .0;
+        let subkey = authenticator
+            .subkey
+            .clone()
+            .map(BaseKey::try_from)
+            .expect("Missing subkey")
+            .expect("Invalid subkey");
+
+        let krb_priv = TaggedKrbPriv::from_der(krb_priv)
+            .expect("Invalid KRB-PRIV")
+            .0;
+        let cleartext = EncryptedData::try_from(krb_priv.enc_part)
+            .and_then(|enc_part| enc_part.decrypt_data(&subkey, KeyUsage::KRB_PRIV))
+            .expect("Failed to decrypt KRB-PRIV");
+
+        let enc_part = TaggedEncKrbPrivPart::from_der(&cleartext)
+            .expect("Invalid KRB-PRIV encrypted part")
+            .0;
+        assert!(enc_part.seq_number.is_some());
+
+        let new_password = ChangePasswdData::from_der(enc_part.user_data.as_bytes())
+            .expect("Invalid ChangePasswdData")
+            .new_passwd
+            .as_bytes()
+            .to_vec();
+
+        (authenticator, subkey, new_password)
+    }
+
+    /// The AP-REP that answers `authenticator`, optionally with a subkey of the server.
+    fn ap_rep(authenticator: &Authenticator, subkey: Option<&BaseKey>) -> Vec<u8> {
+        let enc_part = TaggedEncApRepPart(EncApRepPart {
+            ctime: authenticator.ctime,
+            cusec: authenticator.cusec,
+            subkey: subkey
+                .map(KdcEncryptionKey::try_from)
+                .transpose()
+                .expect("Failed to encode subkey"),
+            seq_number: None,
+        })
+        .to_der()
+        .expect("Failed to encode AP-REP encrypted part");
+
+        let enc_part = session_key()
+            .encrypt_data(&enc_part, KeyUsage::AP_REP_ENC_PART)
+            .expect("Failed to encrypt AP-REP");
+
+        TaggedApRep(ApRep {
+            pvno: 5,
+            msg_type: KrbMessageType::KrbApRep as u8,
+            enc_part: KdcEncryptedData::try_from(&enc_part)
+                .expect("Failed to build encrypted data"),
+        })
+        .to_der()
+        .expect("Failed to encode AP-REP")
+    }
+
+    fn krb_priv(key: &BaseKey, result_code: KpasswdResultCode, message: &str) -> Vec<u8> {
+        let enc_part = TaggedEncKrbPrivPart(EncKrbPrivPart {
+            user_data: OctetString::new(result_data(result_code, message))
+                .expect("Failed to build user data"),
+            timestamp: Some(
+                KerberosTime::from_system_time(SystemTime::now())
+                    .expect("Failed to build timestamp"),
+            ),
+            usec: None,
+            seq_number: None,
+            s_address: HostAddress {
+                addr_type: 2,
+                address: OctetString::new(vec![127, 0, 0, 1]).expect("Failed to build address"),
+            },
+            r_address: None,
+        })
+        .to_der()
+        .expect("Failed to encode KRB-PRIV encrypted part");
+
+        let enc_part = key
+            .encrypt_data(&enc_part, KeyUsage::KRB_PRIV)
+            .expect("Failed to encrypt KRB-PRIV");
+
+        TaggedKrbPriv(KrbPriv {
+            pvno: 5,
+            msg_type: KrbMessageType::KrbPriv as u8,
+            enc_part: KdcEncryptedData::try_from(&enc_part)
+                .expect("Failed to build encrypted data"),
+        })
+        .to_der()
+        .expect("Failed to encode KRB-PRIV")
+    }
+
+    fn result_data(result_code: KpasswdResultCode, message: &str) -> Vec<u8> {
+        let mut data = u16::from(result_code).to_be_bytes().to_vec();
+        data.extend_from_slice(message.as_bytes());
+        data
+    }
+
+    #[tokio::test]
+    async fn test_change_password_success() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+        let addr = listener.local_addr().expect("Unable to get local address");
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("Failed to accept");
+            let message = read_request(&mut stream).await;
+
+            let (authenticator, subkey, new_password) = decode_request(&message);
+            assert_eq!(new_password, b"n3w_p4ssw0rd");
+
+            // Without a subkey of its own, the server replies in ours.
+            let ap_rep = ap_rep(&authenticator, None);
+            let krb_priv = krb_priv(&subkey, KpasswdResultCode::Success, "");
+            write_reply(&mut stream, &ap_rep, &krb_priv).await;
+        });
+
+        change_password_with_ticket(addr, &authenticated_reply(), "n3w_p4ssw0rd")
+            .await
+            .expect("Password change failed");
+
+        server.await.expect("Server task failed");
+    }
+
+    #[tokio::test]
+    async fn test_change_password_server_subkey() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+        let addr = listener.local_addr().expect("Unable to get local address");
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("Failed to accept");
+            let message = read_request(&mut stream).await;
+
+            let (authenticator, _, new_password) = decode_request(&message);
+            assert_eq!(new_password, b"n3w_p4ssw0rd");
+
+            let server_subkey =
+                BaseKey::generate_session_key(EncryptionType::AES256_CTS_HMAC_SHA1_96)
+                    .expect("Failed to generate subkey");
+            let ap_rep = ap_rep(&authenticator, Some(&server_subkey));
+            let krb_priv = krb_priv(&server_subkey, KpasswdResultCode::Success, "");
+            write_reply(&mut stream, &ap_rep, &krb_priv).await;
+        });
+
+        change_password_with_ticket(addr, &authenticated_reply(), "n3w_p4ssw0rd")
+            .await
+            .expect("Password change failed");
+
+        server.await.expect("Server task failed");
+    }
+
+    #[tokio::test]
+    async fn test_change_password_error_reply() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind listener");
+        let addr = listener.local_addr().expect("Unable to get local address");
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("Failed to accept");
+            read_request(&mut stream).await;
+
+            let stime = KerberosTime::from_system_time(SystemTime::now())
+                .expect("Failed to build timestamp");
+
+            let krb_error = TaggedKrbError(KdcKrbError {
+                pvno: 5,
+                msg_type: KrbMessageType::KrbError as u8,
+                ctime: None,
+                cusec: None,
+                stime,
+                susec: 0,
+                error_code: 60,
+                crealm: None,
+                cname: None,
+                service_realm: KerberosString(
+                    Ia5String::new("EXAMPLE.COM").expect("Invalid realm"),
+                ),
+                service_name: PrincipalName {
+                    name_type: 2,
+                    name_string: ["kadmin", "changepw"]
+                        .into_iter()
+                        .map(|name| KerberosString(Ia5String::new(name).expect("Invalid name")))
+                        .collect(),
+                },
+                error_text: None,
+                error_data: Some(
+                    OctetString::new(result_data(
+                        KpasswdResultCode::SoftError,
+                        "Password is too short",
+                    ))
+                    .expect("Failed to build error data"),
+                ),
+            })
+            .to_der()
+            .expect("Failed to encode KRB-ERROR");
+
+            write_reply(&mut stream, &[], &krb_error).await;
+        });
+
+        let err = change_password_with_ticket(addr, &authenticated_reply(), "short")
+            .await
+            .expect_err("Password change succeeded");
+
+        match err {
+            KrbError::PasswordChangeFailed(result_code, message) => {
+                assert_eq!(result_code, KpasswdResultCode::SoftError);
+                assert_eq!(message, "Password is too short");
+            }
+            err => unreachable!("Unexpected error {:?}", err),
+        }
+
+        server.await.expect("Server task failed");
+    }
+
+    #[test]
+    fn test_build_request() {
+        let reply = authenticated_reply();
+        let subkey = BaseKey::generate_session_key(EncryptionType::AES256_CTS_HMAC_SHA1_96)
+            .expect("Failed to generate subkey");
+        let message = super::build_request(
+            &reply,
+            &subkey,
+            "n3w_p4ssw0rd",
+            "192.0.2.1:1234".parse().expect("Invalid address"),
+            SystemTime::now() + Duration::from_secs(1),
+            42,
+        )
+        .expect("Failed to build request");
+
+        let (authenticator, sent, new_password) = decode_request(&message);
+        assert_eq!(new_password, b"n3w_p4ssw0rd");
+        assert_eq!(sent.as_slice(), subkey.as_slice());
+        assert_eq!(authenticator.seq_number, Some(42));
+    }
+
+    #[test]
+    fn test_decode_reply_keys() {
+        let reply = authenticated_reply();
+        let subkey = BaseKey::generate_session_key(EncryptionType::AES256_CTS_HMAC_SHA1_96)
+            .expect("Failed to generate subkey");
+        let now = SystemTime::now();
+        let message = super::build_request(
+            &reply,
+            &subkey,
+            "n3w_p4ssw0rd",
+            "192.0.2.1:1234".parse().expect("Invalid address"),
+            now,
+            42,
+        )
+        .expect("Failed to build request");
+        let (authenticator, _, _) = decode_request(&message);
+
+        let success = |key: &BaseKey| krb_priv(key, KpasswdResultCode::Success, "");
+        let server_subkey = BaseKey::generate_session_key(EncryptionType::AES256_CTS_HMAC_SHA1_96)
+            .expect("Failed to generate subkey");
+
+        // The reply is in the subkey of the server when it sends one, otherwise in ours.
+        let message = reply_message(&ap_rep(&authenticator, None), &success(&subkey));
+        assert!(decode_reply(&message, &reply, &subkey, now).is_ok());
+        let message = reply_message(
+            &ap_rep(&authenticator, Some(&server_subkey)),
+            &success(&server_subkey),
+        );
+        assert!(decode_reply(&message, &reply, &subkey, now).is_ok());
+
+        // Never in the session key of the ticket, nor in our subkey once the server
+        // replaced it.
+        let message = reply_message(&ap_rep(&authenticator, None), &success(&session_key()));
+        assert!(matches!(
+            decode_reply(&message, &reply, &subkey, now),
+            Err(KrbError::MessageAuthenticationFailed)
+        ));
+        let message = reply_message(
+            &ap_rep(&authenticator, Some(&server_subkey)),
+            &success(&subkey),
+        );
+        assert!(matches!(
+            decode_reply(&message, &reply, &subkey, now),
+            Err(KrbError::MessageAuthenticationFailed)
+        ));
+
+        // The AP-REP must answer the authenticator that was sent.
+        let message = reply_message(&ap_rep(&authenticator, None), &success(&subkey));
+        assert!(matches!(
+            decode_reply(&message, &reply, &subkey, now + Duration::from_micros(1)),
+            Err(KrbError::KpasswdInvalidReply)
+        ));
+        let message = reply_message(&[0x6f, 0x00], &success(&subkey));
+        assert!(matches!(
+            decode_reply(&message, &reply, &subkey, now),
+            Err(KrbError::DerDecodeApRep)
+        ));
+    }
+}
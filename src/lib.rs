@@ -18,257 +18,59 @@
 #![allow(clippy::unreachable)]
 
 mod asn1;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod checksum;
+#[cfg(feature = "tokio-codec")]
+pub mod client;
+#[cfg(feature = "tokio-codec")]
+mod codec;
 pub(crate) mod constants;
 pub(crate) mod crypto;
 pub mod error;
+pub mod exchange;
+#[cfg(any(feature = "tokio-codec", feature = "blocking", feature = "kkdcp"))]
+mod framing;
+#[cfg(feature = "kkdcp")]
+pub mod kkdcp;
+#[cfg(feature = "tokio-codec")]
+pub mod kpasswd;
+#[cfg(feature = "pkinit")]
+pub mod pkinit;
 pub mod proto;
+#[cfg(feature = "dns")]
+pub mod resolver;
 
-use bytes::Buf;
-use bytes::BufMut;
-use bytes::BytesMut;
-use der::Decode;
-use proto::KerberosResponse;
-use std::io::{self};
-use tokio_util::codec::{Decoder, Encoder};
-use xdr_codec::record::XdrRecordReader;
-use xdr_codec::record::XdrRecordWriter;
-use xdr_codec::Write;
-
-use crate::constants::DEFAULT_IO_MAX_SIZE;
-use crate::proto::KerberosRequest;
-
-pub struct KerberosTcpCodec {
-    max_size: usize,
-}
-
-impl Default for KerberosTcpCodec {
-    fn default() -> Self {
-        KerberosTcpCodec {
-            max_size: DEFAULT_IO_MAX_SIZE,
-        }
-    }
-}
-
-impl Decoder for KerberosTcpCodec {
-    type Item = KerberosResponse;
-    type Error = io::Error;
-
-    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let reader = buf.reader();
-        let mut xdr_reader = XdrRecordReader::new(reader);
-        xdr_reader.set_implicit_eor(true);
-
-        let record = xdr_reader.into_iter().next();
-
-        let record: Vec<u8> = match record {
-            None => {
-                return Err(io::Error::new(
-                    io::ErrorKind::UnexpectedEof,
-                    "XDR reader returned EOF",
-                ))
-            }
-            Some(rr) => match rr {
-                Err(x) => return Err(x),
-                Ok(buf) => buf,
-            },
-        };
-
-        let rep = KerberosResponse::from_der(&record)
-            .map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x.to_string()))
-            .expect("Failed to decode");
-
-        Ok(Some(rep))
-    }
-}
-
-impl Encoder<KerberosRequest> for KerberosTcpCodec {
-    type Error = io::Error;
-
-    fn encode(&mut self, msg: KerberosRequest, buf: &mut BytesMut) -> io::Result<()> {
-        debug_assert!(buf.is_empty());
-
-        let der_bytes = msg
-            .to_der()
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
-
-        debug_assert!(buf.len() <= self.max_size);
-
-        /* RFC1831 section 10
-        *
-        * When RPC messages are passed on top of a byte stream transport
-        * protocol (like TCP), it is necessary to delimit one message from
-        * another in order to detect and possibly recover from protocol errors.
-        * This is called record marking (RM).  One RPC message fits into one RM
-        * record.
-
-        * A record is composed of one or more record fragments.  A record
-        * fragment is a four-byte header followed by 0 to (2**31) - 1 bytes of
-        * fragment data.  The bytes encode an unsigned binary number; as with
-        * XDR integers, the byte order is from highest to lowest.  The number
-        * encodes two values -- a boolean which indicates whether the fragment
-        * is the last fragment of the record (bit value 1 implies the fragment
-        * is the last fragment) and a 31-bit unsigned binary value which is the
-        * length in bytes of the fragment's data.  The boolean value is the
-        * highest-order bit of the header; the length is the 31 low-order bits.
-        * (Note that this record specification is NOT in XDR standard form!)
-        */
-        let mut w = XdrRecordWriter::new(buf.writer());
-        w.set_implicit_eor(true);
-        w.write_all(&der_bytes)
-    }
-}
+#[cfg(feature = "tokio-codec")]
+pub use crate::codec::{KdcTcpCodec, KerberosTcpCodec, KerberosUdpCodec};
 
 #[cfg(test)]
 mod tests {
-    use super::KerberosResponse;
-    use futures::SinkExt;
-    use tokio::net::TcpStream;
-    use tokio_util::codec::Framed;
-
-    use std::time::{Duration, SystemTime};
-
-    use super::KerberosTcpCodec;
-    use crate::asn1::constants::errors::KrbErrorCode;
-    use crate::asn1::constants::PaDataType;
-    use crate::proto::KerberosRequest;
-    use futures::StreamExt;
-    use tracing::trace;
-
-    #[tokio::test]
-    async fn test_localhost_kdc() {
-        let _ = tracing_subscriber::fmt::try_init();
-
-        let stream = TcpStream::connect("127.0.0.1:55000")
-            .await
-            .expect("Unable to connect to localhost:55000");
-
-        let mut krb_stream = Framed::new(stream, KerberosTcpCodec::default());
-
-        let as_req = KerberosRequest::build_asreq(
-            "testuser".to_string(),
-            "krbtgt".to_string(),
-            None,
-            SystemTime::now() + Duration::from_secs(3600),
-            None,
+    // A captured AS-REP from MIT KRB5.
+    pub(crate) const AS_REP_SAMPLE: &str = "6b8203513082034da003020105a10302010ba22d302b3029a103020113a2220420301e301ca003020112a1151b134558414d504c452e434f4d7465737475736572a30d1b0b4558414d504c452e434f4da4153013a003020101a10c300a1b087465737475736572a58201ba618201b6308201b2a003020105a10d1b0b4558414d504c452e434f4da220301ea003020102a11730151b066b72627467741b0b4558414d504c452e434f4da382017830820174a003020112a103020101a282016604820162eac20712018638db059fc4580cb6aad87fbc722c85219b83574df7a6cee9ee5f6d83569c8ddfcd0695bd9ec215540200f905ec11f91353d6724be7fbfe9444606d39b4d85e4ae084a72a14a0f652a922da109e652b68dae1a519d2c2087b07c7d8f738738fe2276ead3c31d83bd3f8cbcc6c6ca8b5133a1cca5f09bfb45489fca80cecfc754d13f93418dc6385475400795d7f06f8ae9a146e21eeccd10f2efaa0bf1d3acde3f8d1c71cb7a555eedb1ce333a32941141c8ed7552a31df706d11be06b21c02178d2ac8bbed10964ff67b0b06e7f56f1c2422be26ac862521bf1be90b3977975a3346f2d2404342bf53b9c45d83a56c45fef0a7386ed82ffc0c4b23e10e9cb51ab18076d8fe9fc3d66d0ad9cd44764f2af929a181fe008d99de0acc44d689874ad433f1b04d129c2bb65f3070aa7c0343d9b07a44c9d031f950119f90744ff0085b0f4c08b29b281d376525736f9dd292eec03c16d2f5a681eb24bb56a682012c30820128a003020112a282011f0482011b602fe69bf3c949b575e0303ebec6975c3921b38a7479c16e68fd18d18972e670296ce1f6d005df8f423f44f9f8efcaafc8a148a141f706ddd24a2ded22f85b85c41ffe6168ba887a85f3b514e4f670818bf0f402c245cd167ef5136a72edd19e0536d0ea1863e27a227dd7207aa0d1c3d13526936636574f604bb57492feb534c1d8b15610bcce035a4de2d259103f9e63968f8b4e3f8b1e7120ef31bd390344bfabacf657ff062c8a50f12ffdf045df03d98bbc5f324b7a7eb48e4e656ceb5ee1325a394de51bb7617d6db4cda242c0aba97612dcf23816e08ca41bea80f4b2dc144422ed832c2395b61fdd9437f08fd2a3a1dd2475d61d61a102d1a38292afaded12f26318a6550328f60addb0542ac8e287d7a1c96f3593ca04";
+
+    // A captured KRB-ERROR with KRB_ERR_RESPONSE_TOO_BIG from Active Directory.
+    pub(crate) const RESPONSE_TOO_BIG_SAMPLE: &str = "7e5a3058a003020105a10302011ea411180f32303234303631323131343830355aa505020301dc66a603020134a90c1b0a41464f524553542e4144aa1f301da003020102a11630141b066b72627467741b0a41464f524553542e4144";
+
+    // The AS exchange that the transports are tested with.
+    #[cfg(any(feature = "blocking", feature = "kkdcp"))]
+    pub(crate) fn as_req() -> crate::proto::KerberosRequest {
+        crate::proto::KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            std::time::SystemTime::now() + std::time::Duration::from_secs(3600),
         )
-        .build();
-
-        // Write a request
-        krb_stream
-            .send(as_req)
-            .await
-            .expect("Failed to transmit request");
-
-        let response = krb_stream.next().await;
-
-        trace!(?response);
-        assert!(response.is_some());
-        let response = response.unwrap();
-        assert!(response.is_ok());
-        let response = response.unwrap();
-        let asrep = match response {
-            KerberosResponse::AsRep(asrep) => asrep,
-            _ => unreachable!(),
-        };
-
-        let base_key = asrep
-            .enc_part
-            .derive_key(b"password", b"EXAMPLE.COM", b"testuser")
-            .unwrap();
-
-        // RFC 4120 The key usage value for encrypting this field is 3 in an AS-REP
-        // message, using the client's long-term key or another key selected
-        // via pre-authentication mechanisms.
-        let cleartext = asrep.enc_part.decrypt_data(&base_key, 3).unwrap();
+        .build()
+        .expect("Failed to build request")
     }
 
-    #[tokio::test]
-    async fn test_localhost_kdc_preauth() {
-        let _ = tracing_subscriber::fmt::try_init();
-
-        let stream = TcpStream::connect("127.0.0.1:55000")
-            .await
-            .expect("Unable to connect to localhost:55000");
-
-        let mut krb_stream = Framed::new(stream, KerberosTcpCodec::default());
-
-        let as_req = KerberosRequest::build_asreq(
-            "testuser_preauth".to_string(),
-            "krbtgt".to_string(),
-            None,
-            SystemTime::now() + Duration::from_secs(3600),
-            None,
-        )
-        .build();
-
-        // Write a request
-        krb_stream
-            .send(as_req)
-            .await
-            .expect("Failed to transmit request");
-
-        let response = krb_stream.next().await;
-
-        trace!(?response);
-        assert!(response.is_some());
-        let response = response.unwrap();
-        assert!(response.is_ok());
-        let response = response.unwrap();
-        let pa_rep = match response {
-            KerberosResponse::PaRep(pa_rep) => pa_rep,
-            _ => unreachable!(),
-        };
-
-        // The PA-ENC-TIMESTAMP method MUST be supported by
-        // clients, but whether it is enabled by default MAY be determined on
-        // a realm-by-realm basis.
-        // If the method is not used in the initial request and the error
-        // KDC_ERR_PREAUTH_REQUIRED is returned specifying PA-ENC-TIMESTAMP
-        // as an acceptable method, the client SHOULD retry the initial
-        // request using the PA-ENC-TIMESTAMP pre- authentication method.
-        //
-        // The ETYPE-INFO2 method MUST be supported; this method is used to
-        // communicate the set of supported encryption types, and
-        // corresponding salt and string to key parameters.
-
-        // Assert returned preauth data contains PA-ENC-TIMESTAMP and PA-ETYPE-INFO2
-        assert!(pa_rep.enc_timestamp);
-
-        // Assert returned preauth data contains PA-ETYPE-INFO2
-        assert!(!pa_rep.etype_info2.is_empty());
-
-        // Compute the pre-authentication.
-        let now = SystemTime::now();
-        let password = "password";
-        let seconds_since_epoch = now.duration_since(SystemTime::UNIX_EPOCH).unwrap();
-
-        let pre_auth = pa_rep
-            .perform_enc_timestamp(
-                password,
-                "EXAMPLE.COM",
-                "testuser_preauth",
-                seconds_since_epoch,
-            )
-            .unwrap();
-
-        let as_req = KerberosRequest::build_asreq(
-            "testuser_preauth".to_string(),
-            "krbtgt".to_string(),
-            None,
-            now + Duration::from_secs(3600),
-            None,
-        )
-        .add_preauthentication(pre_auth)
-        .build();
-
-        // Write a request
-        krb_stream
-            .send(as_req)
-            .await
-            .expect("Failed to transmit request");
-
-        let response = krb_stream.next().await;
-
-        trace!(?response);
+    // The AS_REP_SAMPLE as a TCP record, prefixed by its length.
+    #[cfg(any(feature = "blocking", feature = "kkdcp"))]
+    pub(crate) fn as_rep_record() -> Vec<u8> {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let mut record = (as_rep.len() as u32).to_be_bytes().to_vec();
+        record.extend_from_slice(&as_rep);
+        record
     }
 }
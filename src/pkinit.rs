@@ -0,0 +1,598 @@
+//! PKINIT (RFC 4556) pre-authentication, where the client proves its identity to the
+//! KDC with a certificate rather than a password. The private key of the certificate
+//! is only ever used through [PkinitSigner], so it may be held by a smartcard or other
+//! hardware. Only the Diffie-Hellman key exchange is supported.
+
+use crate::asn1::auth_pack::{
+    AlgorithmIdentifier, AuthPack, DomainParameters, PkAuthenticator, SubjectPublicKeyInfo,
+};
+use crate::asn1::kerberos_time::KerberosTime;
+use crate::asn1::pa_pk_as_rep::{KdcDhKeyInfo, PaPkAsRep};
+use crate::asn1::pa_pk_as_req::PaPkAsReq;
+use crate::asn1::OctetString;
+use crate::constants::{
+    AES_128_KEY_LEN, AES_256_KEY_LEN, PKINIT_DH_MODP_2048_G, PKINIT_DH_MODP_2048_P,
+};
+use crate::crypto::octetstring2key_aes_cts_hmac_sha1_96;
+use crate::error::KrbError;
+use crate::proto::{BaseKey, EncryptedData, EncryptionType};
+use cms::cert::{CertificateChoices, IssuerAndSerialNumber};
+use cms::content_info::{CmsVersion, ContentInfo};
+use cms::signed_data::{
+    CertificateSet, EncapsulatedContentInfo, SignedData, SignerIdentifier, SignerInfo, SignerInfos,
+};
+use der::asn1::{Any, BitString, ObjectIdentifier, Uint};
+use der::{Decode, Encode};
+use num_bigint::BigUint;
+use rand::{thread_rng, Rng};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+use x509_cert::attr::Attribute;
+use x509_cert::der::asn1::{
+    Any as CmsAny, ObjectIdentifier as CmsObjectIdentifier, OctetString as CmsOctetString, SetOfVec,
+};
+use x509_cert::der::{Decode as _, Encode as _, Tag as CmsTag};
+use x509_cert::spki::AlgorithmIdentifierOwned;
+use x509_cert::Certificate;
+
+// RFC 3279 2.3.3
+const DH_PUBLIC_NUMBER: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10046.2.1");
+
+// RFC 4556 3.1
+const ID_PKINIT_AUTH_DATA: CmsObjectIdentifier = CmsObjectIdentifier::new_unwrap("1.3.6.1.5.2.3.1");
+const ID_PKINIT_DHKEY_DATA: CmsObjectIdentifier =
+    CmsObjectIdentifier::new_unwrap("1.3.6.1.5.2.3.2");
+
+// RFC 5652
+const ID_SIGNED_DATA: CmsObjectIdentifier = CmsObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+const ID_CONTENT_TYPE: CmsObjectIdentifier =
+    CmsObjectIdentifier::new_unwrap("1.2.840.113549.1.9.3");
+const ID_MESSAGE_DIGEST: CmsObjectIdentifier =
+    CmsObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4");
+
+// RFC 5754
+const ID_SHA256: CmsObjectIdentifier = CmsObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1");
+
+// A 512 bit exponent is well beyond the strength of the 2048 bit group.
+const DH_PRIVATE_KEY_LEN: usize = 64;
+
+/// Signs the requests of the client with the private key of its certificate.
+pub trait PkinitSigner: Send + Sync {
+    /// The certificate of the client, which the KDC maps to the client principal.
+    fn certificate(&self) -> &Certificate;
+
+    /// The algorithm of the signatures made by [Self::sign], such as
+    /// sha256WithRSAEncryption.
+    fn signature_algorithm(&self) -> AlgorithmIdentifierOwned;
+
+    /// Sign `data` with the private key of the certificate. The data must be hashed
+    /// with SHA-256 as part of the signature algorithm.
+    fn sign(&self, data: &[u8]) -> Result<Vec<u8>, KrbError>;
+}
+
+/// Checks that the reply was signed by a KDC of the realm.
+pub trait KdcCertValidator: Send + Sync {
+    /// Validate the certificate of the KDC that is carried in, or known to, the
+    /// `signed_data`, and the signature of the KDC over its content. This must
+    /// check that the certificate chains to a trusted anchor, and that it is one of a
+    /// KDC for the realm.
+    fn validate(&self, signed_data: &SignedData) -> Result<(), KrbError>;
+}
+
+/// The state of the client in a PKINIT exchange. This holds the ephemeral
+/// Diffie-Hellman key that the reply key is agreed with, so the same value must be
+/// used for the request and its reply.
+#[derive(Clone)]
+pub struct PkinitPreauth {
+    signer: Arc<dyn PkinitSigner>,
+    kdc_cert_validator: Arc<dyn KdcCertValidator>,
+    dh_private_key: BigUint,
+}
+
+impl fmt::Debug for PkinitPreauth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never leak key material into logs.
+        f.debug_struct("PkinitPreauth").finish_non_exhaustive()
+    }
+}
+
+impl PkinitPreauth {
+    pub(crate) fn new(
+        signer: Arc<dyn PkinitSigner>,
+        kdc_cert_validator: Arc<dyn KdcCertValidator>,
+    ) -> Self {
+        let mut dh_private_key = [0u8; DH_PRIVATE_KEY_LEN];
+        thread_rng().fill(&mut dh_private_key[..]);
+
+        PkinitPreauth {
+            signer,
+            kdc_cert_validator,
+            dh_private_key: BigUint::from_bytes_be(&dh_private_key),
+        }
+    }
+
+    fn dh_public_key(&self) -> BigUint {
+        let p = BigUint::from_bytes_be(&PKINIT_DH_MODP_2048_P);
+        BigUint::from(PKINIT_DH_MODP_2048_G).modpow(&self.dh_private_key, &p)
+    }
+
+    /// Build the PA-PK-AS-REQ for the request with `req_body`, which is the DER of its
+    /// KDC-REQ-BODY. The `freshness_token` from the KDC is signed along with it.
+    pub(crate) fn pa_pk_as_req(
+        &self,
+        req_body: &[u8],
+        nonce: u32,
+        now: SystemTime,
+        freshness_token: Option<&[u8]>,
+    ) -> Result<PaPkAsReq, KrbError> {
+        let auth_pack = self
+            .auth_pack(req_body, nonce, now, freshness_token)
+            .and_then(|auth_pack| auth_pack.to_der())
+            .map_err(|_| KrbError::DerEncodePaPkAsReq)?;
+
+        let signed_auth_pack = self.sign_auth_pack(&auth_pack)?;
+
+        Ok(PaPkAsReq {
+            signed_auth_pack: OctetString::new(signed_auth_pack)
+                .map_err(|_| KrbError::DerEncodePaPkAsReq)?,
+            trusted_certifiers: None,
+            kdc_pk_id: None,
+        })
+    }
+
+    fn auth_pack(
+        &self,
+        req_body: &[u8],
+        nonce: u32,
+        now: SystemTime,
+        freshness_token: Option<&[u8]>,
+    ) -> der::Result<AuthPack> {
+        let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        // KerberosTime has no fractional seconds, those are carried in cusec.
+        let ctime = KerberosTime::from_unix_duration(Duration::from_secs(since_epoch.as_secs()))?;
+
+        let pa_checksum = Sha1::digest(req_body);
+
+        let p = BigUint::from_bytes_be(&PKINIT_DH_MODP_2048_P);
+        let q: BigUint = (&p - 1u8) >> 1;
+
+        let parameters = DomainParameters {
+            p: Uint::new(&p.to_bytes_be())?,
+            g: Uint::new(&[PKINIT_DH_MODP_2048_G])?,
+            q: Uint::new(&q.to_bytes_be())?,
+        };
+
+        // The public key is the DER of the INTEGER, inside the BIT STRING.
+        let public_key = Uint::new(&self.dh_public_key().to_bytes_be())?.to_der()?;
+
+        Ok(AuthPack {
+            pk_authenticator: PkAuthenticator {
+                cusec: since_epoch.subsec_micros(),
+                ctime,
+                nonce,
+                pa_checksum: Some(OctetString::new(pa_checksum.to_vec())?),
+                freshness_token: freshness_token.map(OctetString::new).transpose()?,
+            },
+            client_public_value: Some(SubjectPublicKeyInfo {
+                algorithm: AlgorithmIdentifier {
+                    algorithm: DH_PUBLIC_NUMBER,
+                    parameters: Some(Any::encode_from(&parameters)?),
+                },
+                subject_public_key: BitString::from_bytes(&public_key)?,
+            }),
+            supported_cms_types: None,
+            client_dh_nonce: None,
+        })
+    }
+
+    /// Wrap the AuthPack in the CMS SignedData of RFC 4556 3.2.1, signed by the
+    /// signer and carrying its certificate.
+    fn sign_auth_pack(&self, auth_pack: &[u8]) -> Result<Vec<u8>, KrbError> {
+        let certificate = self.signer.certificate();
+
+        let digest_algorithm = AlgorithmIdentifierOwned {
+            oid: ID_SHA256,
+            parameters: None,
+        };
+
+        let signed_attrs = signed_attributes(auth_pack).map_err(|err| {
+            debug!(?err, "Failed to build signed attributes");
+            KrbError::DerEncodePaPkAsReq
+        })?;
+
+        // RFC 5652 5.4 The signature is over the DER of the attributes as a SET OF.
+        let signature = signed_attrs
+            .to_der()
+            .map_err(|_| KrbError::DerEncodePaPkAsReq)
+            .and_then(|signed_attrs| self.signer.sign(&signed_attrs))?;
+
+        let signer_info = SignerInfo {
+            version: CmsVersion::V1,
+            sid: SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+                issuer: certificate.tbs_certificate.issuer.clone(),
+                serial_number: certificate.tbs_certificate.serial_number.clone(),
+            }),
+            digest_alg: digest_algorithm.clone(),
+            signed_attrs: Some(signed_attrs),
+            signature_algorithm: self.signer.signature_algorithm(),
+            signature: CmsOctetString::new(signature).map_err(|_| KrbError::DerEncodePaPkAsReq)?,
+            unsigned_attrs: None,
+        };
+
+        let signed_data = SignedData {
+            // The content is not id-data, so this is version 3.
+            version: CmsVersion::V3,
+            digest_algorithms: SetOfVec::try_from(vec![digest_algorithm])
+                .map_err(|_| KrbError::DerEncodePaPkAsReq)?,
+            encap_content_info: EncapsulatedContentInfo {
+                econtent_type: ID_PKINIT_AUTH_DATA,
+                econtent: Some(
+                    CmsAny::new(CmsTag::OctetString, auth_pack)
+                        .map_err(|_| KrbError::DerEncodePaPkAsReq)?,
+                ),
+            },
+            certificates: Some(CertificateSet(
+                SetOfVec::try_from(vec![CertificateChoices::Certificate(certificate.clone())])
+                    .map_err(|_| KrbError::DerEncodePaPkAsReq)?,
+            )),
+            crls: None,
+            signer_infos: SignerInfos(
+                SetOfVec::try_from(vec![signer_info]).map_err(|_| KrbError::DerEncodePaPkAsReq)?,
+            ),
+        };
+
+        CmsAny::encode_from(&signed_data)
+            .map(|content| ContentInfo {
+                content_type: ID_SIGNED_DATA,
+                content,
+            })
+            .and_then(|content_info| content_info.to_der())
+            .map_err(|_| KrbError::DerEncodePaPkAsReq)
+    }
+
+    /// Derive the reply key from the PA-PK-AS-REP of the KDC, once the KDC has been
+    /// validated. The `enc_part` is that of the reply, which is encrypted in this key.
+    pub(crate) fn reply_key(
+        &self,
+        pk_as_rep: &PaPkAsRep,
+        nonce: u32,
+        enc_part: &EncryptedData,
+    ) -> Result<BaseKey, KrbError> {
+        let dh_rep_info = &pk_as_rep.0;
+
+        // The nonces and KDFs are only used when the client offers them.
+        if dh_rep_info.server_dh_nonce.is_some() || dh_rep_info.kdf.is_some() {
+            return Err(KrbError::PkinitInvalidSignedData);
+        }
+
+        let content_info = ContentInfo::from_der(dh_rep_info.dh_signed_data.as_bytes())
+            .map_err(|_| KrbError::PkinitInvalidSignedData)?;
+        if content_info.content_type != ID_SIGNED_DATA {
+            return Err(KrbError::PkinitInvalidSignedData);
+        }
+
+        let signed_data: SignedData = content_info
+            .content
+            .decode_as()
+            .map_err(|_| KrbError::PkinitInvalidSignedData)?;
+        if signed_data.encap_content_info.econtent_type != ID_PKINIT_DHKEY_DATA {
+            return Err(KrbError::PkinitInvalidSignedData);
+        }
+
+        self.kdc_cert_validator.validate(&signed_data)?;
+
+        let key_info = signed_data
+            .encap_content_info
+            .econtent
+            .as_ref()
+            .ok_or(KrbError::PkinitInvalidSignedData)
+            .and_then(|econtent| {
+                KdcDhKeyInfo::from_der(econtent.value())
+                    .map_err(|_| KrbError::PkinitInvalidSignedData)
+            })?;
+
+        if key_info.nonce != nonce {
+            return Err(KrbError::PkinitNonceMismatch);
+        }
+
+        let public_key = key_info
+            .subject_public_key
+            .as_bytes()
+            .ok_or(KrbError::PkinitInvalidDhKey)
+            .and_then(|public_key| {
+                Uint::from_der(public_key).map_err(|_| KrbError::PkinitInvalidDhKey)
+            })
+            .map(|public_key| BigUint::from_bytes_be(public_key.as_bytes()))?;
+
+        let p = BigUint::from_bytes_be(&PKINIT_DH_MODP_2048_P);
+
+        // Reject the trivial keys that would make the shared secret predictable.
+        if public_key <= BigUint::from(1u8) || public_key >= &p - 1u8 {
+            return Err(KrbError::PkinitInvalidDhKey);
+        }
+
+        // RFC 4556 3.2.3.1 The shared secret is left padded with zeros to the size
+        // of the prime.
+        let shared_secret = public_key.modpow(&self.dh_private_key, &p).to_bytes_be();
+        let mut x = vec![0u8; PKINIT_DH_MODP_2048_P.len() - shared_secret.len()];
+        x.extend_from_slice(&shared_secret);
+
+        match enc_part {
+            EncryptedData::Aes128CtsHmacSha196 { .. } => BaseKey::from_bytes(
+                EncryptionType::AES128_CTS_HMAC_SHA1_96,
+                &octetstring2key_aes_cts_hmac_sha1_96::<AES_128_KEY_LEN>(&x),
+            ),
+            EncryptedData::Aes256CtsHmacSha196 { .. } => BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &octetstring2key_aes_cts_hmac_sha1_96::<AES_256_KEY_LEN>(&x),
+            ),
+            // RFC 8636 derives the reply key of the sha2 enctypes with a different KDF.
+            EncryptedData::Aes128CtsHmacSha256128 { .. }
+            | EncryptedData::Aes256CtsHmacSha384192 { .. }
+            | EncryptedData::Unsupported { .. } => Err(KrbError::UnsupportedEncryption),
+            #[cfg(feature = "camellia")]
+            EncryptedData::Camellia128CtsCmac { .. } | EncryptedData::Camellia256CtsCmac { .. } => {
+                Err(KrbError::UnsupportedEncryption)
+            }
+            #[cfg(feature = "legacy-crypto")]
+            EncryptedData::Des3CbcSha1Kd { .. } => Err(KrbError::UnsupportedEncryption),
+        }
+    }
+}
+
+// RFC 5652 5.3 The signed attributes must carry the content type and the digest of
+// the content.
+fn signed_attributes(content: &[u8]) -> x509_cert::der::Result<SetOfVec<Attribute>> {
+    let message_digest = Sha256::digest(content);
+
+    SetOfVec::try_from(vec![
+        Attribute {
+            oid: ID_CONTENT_TYPE,
+            values: SetOfVec::try_from(vec![CmsAny::encode_from(&ID_PKINIT_AUTH_DATA)?])?,
+        },
+        Attribute {
+            oid: ID_MESSAGE_DIGEST,
+            values: SetOfVec::try_from(vec![CmsAny::new(
+                CmsTag::OctetString,
+                message_digest.as_slice(),
+            )?])?,
+        },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        KdcCertValidator, PkinitPreauth, PkinitSigner, ID_PKINIT_AUTH_DATA, ID_PKINIT_DHKEY_DATA,
+        ID_SIGNED_DATA,
+    };
+    use crate::asn1::auth_pack::{AuthPack, DomainParameters};
+    use crate::asn1::pa_pk_as_rep::{DhRepInfo, KdcDhKeyInfo, PaPkAsRep};
+    use crate::asn1::OctetString;
+    use crate::constants::{AES_256_KEY_LEN, PKINIT_DH_MODP_2048_P};
+    use crate::crypto::octetstring2key_aes_cts_hmac_sha1_96;
+    use crate::error::KrbError;
+    use crate::proto::{EncryptedData, EncryptionType};
+    use cms::content_info::{CmsVersion, ContentInfo};
+    use cms::signed_data::{EncapsulatedContentInfo, SignedData, SignerInfos};
+    use der::asn1::{BitString, Uint};
+    use der::{Decode, Encode};
+    use num_bigint::BigUint;
+    use sha1::{Digest, Sha1};
+    use std::sync::Arc;
+    use std::time::SystemTime;
+    use x509_cert::der::asn1::{Any as CmsAny, SetOfVec};
+    use x509_cert::der::{Decode as _, Encode as _, Tag as CmsTag};
+    use x509_cert::spki::AlgorithmIdentifierOwned;
+    use x509_cert::Certificate;
+
+    // The client certificate of the docker test realm.
+    const TESTUSER_PKINIT_CERT: &[u8] = include_bytes!("../kdc_test/pkinit/testuser_pkinit.der");
+
+    struct TestSigner(Certificate);
+
+    impl PkinitSigner for TestSigner {
+        fn certificate(&self) -> &Certificate {
+            &self.0
+        }
+
+        fn signature_algorithm(&self) -> AlgorithmIdentifierOwned {
+            AlgorithmIdentifierOwned {
+                oid: "1.2.840.113549.1.1.11".parse().expect("Invalid oid"),
+                parameters: None,
+            }
+        }
+
+        fn sign(&self, _data: &[u8]) -> Result<Vec<u8>, KrbError> {
+            Ok(vec![0x5a; 256])
+        }
+    }
+
+    struct TrustAll;
+
+    impl KdcCertValidator for TrustAll {
+        fn validate(&self, _signed_data: &SignedData) -> Result<(), KrbError> {
+            Ok(())
+        }
+    }
+
+    fn pkinit() -> PkinitPreauth {
+        let certificate =
+            Certificate::from_der(TESTUSER_PKINIT_CERT).expect("Failed to decode certificate");
+        PkinitPreauth::new(Arc::new(TestSigner(certificate)), Arc::new(TrustAll))
+    }
+
+    // What the KDC would reply with, for a KDC private key of `kdc_private_key`.
+    fn pk_as_rep(kdc_private_key: &BigUint, nonce: u32) -> PaPkAsRep {
+        let p = BigUint::from_bytes_be(&PKINIT_DH_MODP_2048_P);
+        let public_key = BigUint::from(2u8).modpow(kdc_private_key, &p);
+        let public_key = Uint::new(&public_key.to_bytes_be())
+            .and_then(|public_key| public_key.to_der())
+            .expect("Failed to encode public key");
+
+        let key_info = KdcDhKeyInfo {
+            subject_public_key: BitString::from_bytes(&public_key).expect("Invalid bit string"),
+            nonce,
+            dh_key_expiration: None,
+        }
+        .to_der()
+        .expect("Failed to encode KDCDHKeyInfo");
+
+        let signed_data = SignedData {
+            version: CmsVersion::V3,
+            digest_algorithms: SetOfVec::new(),
+            encap_content_info: EncapsulatedContentInfo {
+                econtent_type: ID_PKINIT_DHKEY_DATA,
+                econtent: Some(
+                    CmsAny::new(CmsTag::OctetString, key_info).expect("Failed to encode content"),
+                ),
+            },
+            certificates: None,
+            crls: None,
+            signer_infos: SignerInfos(SetOfVec::new()),
+        };
+
+        let dh_signed_data = ContentInfo {
+            content_type: ID_SIGNED_DATA,
+            content: CmsAny::encode_from(&signed_data).expect("Failed to encode SignedData"),
+        }
+        .to_der()
+        .expect("Failed to encode ContentInfo");
+
+        PaPkAsRep(DhRepInfo {
+            dh_signed_data: OctetString::new(dh_signed_data).expect("Invalid octet string"),
+            server_dh_nonce: None,
+            kdf: None,
+        })
+    }
+
+    #[test]
+    fn test_pkinit_signed_auth_pack() {
+        let pkinit = pkinit();
+        let req_body = b"a KDC-REQ-BODY";
+
+        let pk_as_req = pkinit
+            .pa_pk_as_req(
+                req_body,
+                0x1234_5678,
+                SystemTime::now(),
+                Some(b"fresh-token"),
+            )
+            .expect("Failed to build PA-PK-AS-REQ");
+
+        let content_info = ContentInfo::from_der(pk_as_req.signed_auth_pack.as_bytes())
+            .expect("Failed to decode ContentInfo");
+        assert_eq!(content_info.content_type, ID_SIGNED_DATA);
+
+        let signed_data: SignedData = content_info
+            .content
+            .decode_as()
+            .expect("Failed to decode SignedData");
+        assert_eq!(
+            signed_data.encap_content_info.econtent_type,
+            ID_PKINIT_AUTH_DATA
+        );
+        assert_eq!(signed_data.signer_infos.0.len(), 1);
+        assert!(signed_data.certificates.is_some());
+
+        let econtent = signed_data
+            .encap_content_info
+            .econtent
+            .expect("Missing content");
+        let auth_pack = AuthPack::from_der(econtent.value()).expect("Failed to decode AuthPack");
+
+        let pk_authenticator = auth_pack.pk_authenticator;
+        assert_eq!(pk_authenticator.nonce, 0x1234_5678);
+        assert_eq!(
+            pk_authenticator.pa_checksum.map(|c| c.into_bytes()),
+            Some(Sha1::digest(req_body).to_vec())
+        );
+        assert_eq!(
+            pk_authenticator.freshness_token.map(|t| t.into_bytes()),
+            Some(b"fresh-token".to_vec())
+        );
+
+        // The public key is offered in the 2048 bit MODP group.
+        let client_public_value = auth_pack
+            .client_public_value
+            .expect("Missing client public value");
+        assert_eq!(
+            client_public_value.algorithm.algorithm.to_string(),
+            "1.2.840.10046.2.1"
+        );
+        let parameters: DomainParameters = client_public_value
+            .algorithm
+            .parameters
+            .expect("Missing domain parameters")
+            .decode_as()
+            .expect("Failed to decode domain parameters");
+        assert_eq!(parameters.p.as_bytes(), PKINIT_DH_MODP_2048_P);
+        assert_eq!(parameters.g.as_bytes(), [2]);
+
+        let public_key = client_public_value
+            .subject_public_key
+            .as_bytes()
+            .and_then(|public_key| Uint::from_der(public_key).ok())
+            .expect("Failed to decode public key");
+        assert_eq!(
+            BigUint::from_bytes_be(public_key.as_bytes()),
+            pkinit.dh_public_key()
+        );
+    }
+
+    #[test]
+    fn test_pkinit_reply_key() {
+        let pkinit = pkinit();
+        let kdc_private_key = BigUint::from_bytes_be(&[0x77; 64]);
+        let enc_part = EncryptedData::Aes256CtsHmacSha196 {
+            kvno: None,
+            data: Vec::new(),
+        };
+
+        let reply_key = pkinit
+            .reply_key(
+                &pk_as_rep(&kdc_private_key, 0x1234_5678),
+                0x1234_5678,
+                &enc_part,
+            )
+            .expect("Failed to derive reply key");
+
+        // The KDC agrees on the same secret from the public key of the client.
+        let p = BigUint::from_bytes_be(&PKINIT_DH_MODP_2048_P);
+        let shared_secret = pkinit.dh_public_key().modpow(&kdc_private_key, &p);
+        let mut x = vec![0u8; PKINIT_DH_MODP_2048_P.len()];
+        let shared_secret = shared_secret.to_bytes_be();
+        x[PKINIT_DH_MODP_2048_P.len() - shared_secret.len()..].copy_from_slice(&shared_secret);
+
+        assert_eq!(reply_key.etype(), EncryptionType::AES256_CTS_HMAC_SHA1_96);
+        assert_eq!(
+            reply_key.as_slice(),
+            octetstring2key_aes_cts_hmac_sha1_96::<AES_256_KEY_LEN>(&x).as_slice()
+        );
+
+        // The reply must be to this request.
+        let err = pkinit
+            .reply_key(
+                &pk_as_rep(&kdc_private_key, 0x0765_4321),
+                0x1234_5678,
+                &enc_part,
+            )
+            .expect_err("Nonce mismatch was accepted");
+        assert!(matches!(err, KrbError::PkinitNonceMismatch));
+
+        // A public key of 1 would make the shared secret known to everyone.
+        let err = pkinit
+            .reply_key(
+                &pk_as_rep(&BigUint::from(0u8), 0x1234_5678),
+                0x1234_5678,
+                &enc_part,
+            )
+            .expect_err("Trivial public key was accepted");
+        assert!(matches!(err, KrbError::PkinitInvalidDhKey));
+    }
+}
@@ -1,39 +1,82 @@
 use crate::asn1::{
-    constants::{
-        encryption_types::EncryptionType, errors::KrbErrorCode, message_types::KrbMessageType,
-        pa_data_types::PaDataType,
-    },
+    ap_options::ApOptions,
+    ap_rep::TaggedApRep,
+    ap_req::{ApReq, TaggedApReq},
+    authenticator::{Authenticator, TaggedAuthenticator},
+    checksum::Checksum,
+    constants::{checksum_types::ChecksumType, message_types::KrbMessageType},
+    enc_ap_rep_part::{EncApRepPart, TaggedEncApRepPart},
+    enc_kdc_rep_part::{EncKdcRepPart, TaggedEncKdcRepPart},
+    enc_ticket_part::{EncTicketPart as KdcEncTicketPart, TaggedEncTicketPart},
     encrypted_data::EncryptedData as KdcEncryptedData,
-    etype_info2::ETypeInfo2 as KdcETypeInfo2,
+    encryption_key::EncryptionKey as KdcEncryptionKey,
+    etype_info::ETypeInfo as KdcETypeInfo,
+    etype_info2::{ETypeInfo2 as KdcETypeInfo2, ETypeInfo2Entry},
+    host_address::HostAddress as KdcHostAddress,
     kdc_rep::KdcRep,
     kdc_req::KdcReq,
     kdc_req_body::KdcReqBody,
     kerberos_flags::KerberosFlags,
     kerberos_string::KerberosString,
     kerberos_time::KerberosTime,
-    krb_error::MethodData,
+    krb_error::{MethodData, TaggedKrbError},
+    krb_fast_armor::KrbFastArmor,
+    krb_fast_rep::{KrbFastArmoredRep, KrbFastResponse, PaFxFastReply},
+    krb_fast_req::{FastOptions, KrbFastArmoredReq, KrbFastReq, PaFxFastRequest},
+    krb_kdc_rep::KrbKdcRep,
     krb_kdc_req::KrbKdcReq,
+    last_req::LastReqItem,
+    microseconds::Microseconds,
     pa_data::PaData,
     pa_enc_ts_enc::PaEncTsEnc,
+    pa_for_user::PaForUser as KdcPaForUser,
+    pa_otp_challenge::{OtpTokenInfo, PaOtpChallenge},
+    pa_otp_request::{PaOtpEncRequest, PaOtpRequest},
+    pa_pac_options::PaPacOptions,
+    pa_pk_as_rep::PaPkAsRep,
+    pa_pk_as_req::PaPkAsReq,
     principal_name::PrincipalName,
+    tagged_ticket::TaggedTicket,
     Ia5String, OctetString,
 };
-use crate::constants::AES_256_KEY_LEN;
+#[cfg(feature = "legacy-crypto")]
+use crate::constants::DES3_KEY_LEN;
+use crate::constants::{AES_128_KEY_LEN, AES_256_KEY_LEN};
+#[cfg(feature = "camellia")]
+use crate::constants::{CAMELLIA_128_KEY_LEN, CAMELLIA_256_KEY_LEN};
 use crate::crypto::{
-    decrypt_aes256_cts_hmac_sha1_96, derive_key_aes256_cts_hmac_sha1_96,
-    derive_key_external_salt_aes256_cts_hmac_sha1_96, encrypt_aes256_cts_hmac_sha1_96,
+    cipher_for, default_salt, derive_key_aes128_cts_hmac_sha1_96,
+    derive_key_aes128_cts_hmac_sha256_128, derive_key_aes256_cts_hmac_sha1_96,
+    derive_key_aes256_cts_hmac_sha384_192,
 };
 use crate::error::KrbError;
-use der::{flagset::FlagSet, Decode, Encode, Tag, TagNumber};
+#[cfg(feature = "pkinit")]
+use crate::pkinit::{KdcCertValidator, PkinitPreauth, PkinitSigner};
+use der::{Decode, Encode, Tag, TagNumber};
 use rand::{thread_rng, Rng};
 
-use std::cmp::Ordering;
-use std::time::{Duration, SystemTime};
-use tracing::trace;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+#[cfg(feature = "pkinit")]
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, trace};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
-#[derive(Debug)]
+pub use crate::asn1::constants::encryption_types::EncryptionType;
+pub use crate::asn1::constants::errors::KrbErrorCode;
+pub use crate::asn1::constants::key_usages::KeyUsage;
+pub use crate::asn1::constants::pa_data_types::PaDataType;
+pub use crate::asn1::kdc_options::KdcOptions;
+pub use crate::asn1::pa_otp_challenge::OtpFlags;
+pub use crate::asn1::pa_pac_options::PacOptions;
+pub use crate::asn1::ticket_flags::TicketFlags;
+pub use der::flagset::FlagSet;
+
+#[derive(Debug, Clone)]
 pub enum KerberosRequest {
     AsReq(KerberosAsReq),
+    TgsReq(KerberosTgsReq),
 }
 
 #[derive(Debug)]
@@ -43,57 +86,717 @@ pub enum KerberosResponse {
     // This is it's own valid state, not an error, so we return it
     // as a valid response instead.
     PaRep(KerberosPaRep),
-    ErrRep(KrbErrorCode),
+    Err(KerberosErrorRep),
 }
 
 #[derive(Debug)]
 pub struct KerberosAsReqBuilder {
     client_name: String,
     service_name: String,
+    realm: String,
     from: Option<SystemTime>,
     until: SystemTime,
     renew: Option<SystemTime>,
     preauth: Option<PreAuth>,
+    kdc_options: FlagSet<KdcOptions>,
+    addresses: Option<Vec<HostAddress>>,
+    enterprise: bool,
+    etypes: Vec<EncryptionType>,
+    nonce: Option<u32>,
+    pac_options: FlagSet<PacOptions>,
+    req_enc_pa_rep: bool,
+    armor: Option<FastArmor>,
+    #[cfg(feature = "pkinit")]
+    pkinit: Option<PkinitPreauth>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct KerberosAsReq {
-    nonce: u32,
-    client_name: String,
+    pub(crate) nonce: u32,
+    pub(crate) kdc_options: FlagSet<KdcOptions>,
+    pub(crate) addresses: Option<Vec<HostAddress>>,
+    pub(crate) enterprise: bool,
+    pub(crate) etypes: Vec<EncryptionType>,
+    // The etypes exactly as the client offered them, including those that aren't
+    // known, so that the request is encoded as it was received.
+    pub(crate) raw_etypes: Vec<i32>,
+    pub(crate) pac_options: Option<FlagSet<PacOptions>>,
+    // Whether the KDC is asked to checksum the request in PA-REQ-ENC-PA-REP.
+    pub(crate) req_enc_pa_rep: bool,
+    // The PA-FX-FAST that carries the padata and req-body of an armored request.
+    pub(crate) fast: Option<KrbFastArmoredReq>,
+    // The signed AuthPack of a PKINIT request.
+    pub(crate) pk_as_req: Option<PaPkAsReq>,
+    // The Diffie-Hellman key that the reply key of a PKINIT request is agreed with.
+    #[cfg(feature = "pkinit")]
+    pub(crate) pkinit: Option<PkinitPreauth>,
+    pub(crate) client_name: String,
+    pub(crate) service_name: String,
+    pub(crate) realm: String,
+    pub(crate) from: Option<SystemTime>,
+    pub(crate) until: SystemTime,
+    pub(crate) renew: Option<SystemTime>,
+    pub(crate) preauth: Option<PreAuth>,
+}
+
+/// A network address that a ticket may be restricted to, as defined by RFC 4120 7.5.3.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostAddress {
+    Ipv4(Ipv4Addr),
+    Ipv6(Ipv6Addr),
+    /// A NetBIOS name of 1 to 15 characters, without its padding.
+    NetBios(String),
+    /// Any other address type, which is preserved as is.
+    Other {
+        addr_type: i32,
+        address: Vec<u8>,
+    },
+}
+
+#[derive(Debug)]
+pub struct KerberosTgsReqBuilder {
     service_name: String,
     from: Option<SystemTime>,
     until: SystemTime,
     renew: Option<SystemTime>,
-    preauth: Option<PreAuth>,
+    kdc_options: FlagSet<KdcOptions>,
+    additional_tickets: Vec<Ticket>,
+    for_user: Option<(String, String)>,
+    evidence_ticket: Option<Ticket>,
+    pac_options: FlagSet<PacOptions>,
+    clock_offset: ClockOffset,
+    subkey: Option<BaseKey>,
+}
+
+#[derive(Debug, Clone)]
+pub struct KerberosTgsReq {
+    pub(crate) body: TgsReqBody,
+    // Presents the TGT to the KDC, sent as the PA-TGS-REQ padata.
+    pub(crate) ap_req: TaggedApReq,
+    // The DER of the req-body exactly as it was sent, which the checksum in the
+    // authenticator covers.
+    pub(crate) req_body: Vec<u8>,
+    pub(crate) for_user: Option<PaForUser>,
+    pub(crate) pac_options: Option<FlagSet<PacOptions>>,
+}
+
+/// The req-body of a TGS-REQ, which the authenticator checksum covers.
+#[derive(Debug, Clone)]
+pub(crate) struct TgsReqBody {
+    pub(crate) nonce: u32,
+    pub(crate) kdc_options: FlagSet<KdcOptions>,
+    pub(crate) service_name: String,
+    pub(crate) realm: String,
+    pub(crate) from: Option<SystemTime>,
+    pub(crate) until: SystemTime,
+    pub(crate) renew: Option<SystemTime>,
+    pub(crate) etypes: Vec<EncryptionType>,
+    pub(crate) additional_tickets: Vec<Ticket>,
+}
+
+/// The authenticator of a PA-TGS-REQ, once the KDC has verified it with the session
+/// key of the TGT that the request presented.
+#[derive(Debug)]
+pub struct TgsAuthenticator {
+    pub(crate) client_realm: String,
+    pub(crate) client_name: String,
+    pub(crate) ctime: SystemTime,
+    pub(crate) subkey: Option<BaseKey>,
 }
 
+/// The AP-REP with which a service answers an AP-REQ that asked for mutual
+/// authentication, once it is decrypted with the session key of the ticket.
 #[derive(Debug)]
+pub struct ApReply {
+    pub(crate) ctime: SystemTime,
+    pub(crate) subkey: Option<BaseKey>,
+    pub(crate) seq_number: Option<u32>,
+}
+
+/// The PA-FOR-USER padata of an S4U2Self request, naming the user on whose behalf a
+/// service requests a ticket to itself.
+#[derive(Debug, Clone)]
+pub struct PaForUser {
+    pub(crate) user_name: PrincipalName,
+    pub(crate) user_realm: String,
+    pub(crate) cksum: Checksum,
+    pub(crate) auth_package: String,
+}
+
+/// How far the clock of the KDC is from ours. The times that we send to the KDC in
+/// preauthentication and authenticators are moved by it, so that they are within
+/// the clock skew that the KDC allows even when our clock is wrong.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClockOffset {
+    // Whether the clock of the KDC is behind ours, rather than ahead of it.
+    behind: bool,
+    offset: Duration,
+}
+
+/// A ticket issued by a KDC. The ticket is encrypted for its service, so beyond
+/// the service it was issued for its contents are opaque to the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ticket(pub(crate) TaggedTicket);
+
+/// The armor of a FAST (RFC 6113) exchange. This is an AP-REQ that presents a TGT,
+/// such as one of the host, to the KDC. The armor key derived from it protects the
+/// preauthentication of the client and the reply of the KDC.
+#[derive(Debug, Clone)]
+pub struct FastArmor {
+    armor: KrbFastArmor,
+    armor_key: BaseKey,
+}
+
+/// The pre-authentication data that a client sends in an AS-REQ. The entries are
+/// sent in order, and a type may appear more than once.
+#[derive(Debug, Clone, Default)]
 pub struct PreAuth {
-    enc_timestamp: Option<Vec<u8>>,
-    pa_fx_cookie: Option<Vec<u8>>,
+    entries: Vec<PreAuthEntry>,
+}
+
+/// A single padata entry of a [PreAuth].
+#[derive(Debug, Clone)]
+pub enum PreAuthEntry {
+    EncTimestamp(EncryptedData),
+    EncChallenge(EncryptedData),
+    FxCookie(Vec<u8>),
+    /// PA-AS-FRESHNESS (RFC 8070), which is empty in the initial request to ask the
+    /// KDC for a freshness token, and otherwise carries the token back to it.
+    AsFreshness(Vec<u8>),
+    /// A padata type that this crate doesn't interpret, which is sent as is.
+    Raw {
+        padata_type: u32,
+        value: Vec<u8>,
+    },
 }
 
-pub enum BaseKey {
+/// A key of one of the supported etypes, such as a session key or a key derived from
+/// a password. The key material is only reachable from within the crate, and a key
+/// is made with [BaseKey::from_bytes] from the raw bytes of its etype.
+#[derive(Clone)]
+pub struct BaseKey(KeyMaterial);
+
+#[derive(Clone)]
+enum KeyMaterial {
+    Aes128 {
+        k: [u8; AES_128_KEY_LEN],
+    },
     Aes256 {
-        // Todo zeroizing.
         k: [u8; AES_256_KEY_LEN],
     },
+    Aes128Sha256 {
+        k: [u8; AES_128_KEY_LEN],
+    },
+    Aes256Sha384 {
+        k: [u8; AES_256_KEY_LEN],
+    },
+    #[cfg(feature = "camellia")]
+    Camellia128 {
+        k: [u8; CAMELLIA_128_KEY_LEN],
+    },
+    #[cfg(feature = "camellia")]
+    Camellia256 {
+        k: [u8; CAMELLIA_256_KEY_LEN],
+    },
+    #[cfg(feature = "legacy-crypto")]
+    Des3 {
+        k: [u8; DES3_KEY_LEN],
+    },
 }
 
-#[derive(Debug)]
+/// A [BaseKey] along with its version number (kvno), such as an entry of a keytab.
+/// Data that names another version of the key fails to decrypt with
+/// [KrbError::KeyVersionMismatch], as it was encrypted in a key we don't have.
+#[derive(Clone, Debug)]
+pub struct KerberosKey {
+    key: BaseKey,
+    kvno: u32,
+}
+
+impl KerberosKey {
+    pub fn new(key: BaseKey, kvno: u32) -> Self {
+        KerberosKey { key, kvno }
+    }
+
+    pub fn key(&self) -> &BaseKey {
+        &self.key
+    }
+
+    pub fn kvno(&self) -> u32 {
+        self.kvno
+    }
+
+    pub fn etype(&self) -> EncryptionType {
+        self.key.etype()
+    }
+}
+
+impl BaseKey {
+    pub(crate) fn encrypt_data(
+        &self,
+        data: &[u8],
+        key_usage: KeyUsage,
+    ) -> Result<EncryptedData, KrbError> {
+        let cipher = cipher_for(self.etype())?;
+        let data = cipher.encrypt(self.as_slice(), key_usage, data)?;
+        Ok(EncryptedData::new(cipher.etype() as i32, None, data))
+    }
+
+    /// The bytes of the key, whatever its etype.
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        match &self.0 {
+            KeyMaterial::Aes128 { k } | KeyMaterial::Aes128Sha256 { k } => k.as_slice(),
+            KeyMaterial::Aes256 { k } | KeyMaterial::Aes256Sha384 { k } => k.as_slice(),
+            #[cfg(feature = "camellia")]
+            KeyMaterial::Camellia128 { k } => k.as_slice(),
+            #[cfg(feature = "camellia")]
+            KeyMaterial::Camellia256 { k } => k.as_slice(),
+            #[cfg(feature = "legacy-crypto")]
+            KeyMaterial::Des3 { k } => k.as_slice(),
+        }
+    }
+
+    /// RFC 6113 KRB-FX-CF2, which combines this key with `other` into a new key. The
+    /// new key has the etype of this key.
+    pub(crate) fn krb_fx_cf2(
+        &self,
+        other: &BaseKey,
+        pepper1: &str,
+        pepper2: &str,
+    ) -> Result<BaseKey, KrbError> {
+        let cipher = cipher_for(self.etype())?;
+        let mut out = Zeroizing::new(vec![0u8; cipher.seed_size()]);
+        let mut prf2 = Zeroizing::new(vec![0u8; out.len()]);
+        cipher.prf_plus(self.as_slice(), pepper1.as_bytes(), &mut out)?;
+        cipher_for(other.etype())?.prf_plus(other.as_slice(), pepper2.as_bytes(), &mut prf2)?;
+
+        out.iter_mut().zip(prf2.iter()).for_each(|(a, b)| *a ^= b);
+        BaseKey::from_bytes(self.etype(), &cipher.random_to_key(&out)?)
+    }
+
+    /// A new random key of `etype`, such as a session key that a KDC issues or the
+    /// subkey of an AP-REQ. The key is made from the output of a CSPRNG with the
+    /// random-to-key of the etype.
+    pub fn generate_session_key(etype: EncryptionType) -> Result<Self, KrbError> {
+        let cipher = cipher_for(etype)?;
+        let mut random = Zeroizing::new(vec![0u8; cipher.seed_size()]);
+        thread_rng().fill(random.as_mut_slice());
+        BaseKey::from_bytes(etype, &cipher.random_to_key(&random)?)
+    }
+
+    /// A key of `etype` from its raw bytes, such as one from a keytab or an HSM,
+    /// which is used as is without any string-to-key. A key of any other length than
+    /// that of the etype fails with [KrbError::InvalidKeyLength]. Along with its
+    /// version, the key makes a [KerberosKey].
+    pub fn from_bytes(etype: EncryptionType, key: &[u8]) -> Result<Self, KrbError> {
+        let invalid_length = |_| KrbError::InvalidKeyLength(etype, key.len());
+        match etype {
+            EncryptionType::AES128_CTS_HMAC_SHA1_96 => key
+                .try_into()
+                .map(|k| BaseKey(KeyMaterial::Aes128 { k }))
+                .map_err(invalid_length),
+            EncryptionType::AES256_CTS_HMAC_SHA1_96 => key
+                .try_into()
+                .map(|k| BaseKey(KeyMaterial::Aes256 { k }))
+                .map_err(invalid_length),
+            EncryptionType::AES128_CTS_HMAC_SHA256_128 => key
+                .try_into()
+                .map(|k| BaseKey(KeyMaterial::Aes128Sha256 { k }))
+                .map_err(invalid_length),
+            EncryptionType::AES256_CTS_HMAC_SHA384_192 => key
+                .try_into()
+                .map(|k| BaseKey(KeyMaterial::Aes256Sha384 { k }))
+                .map_err(invalid_length),
+            #[cfg(feature = "camellia")]
+            EncryptionType::CAMELLIA128_CTS_CMAC => key
+                .try_into()
+                .map(|k| BaseKey(KeyMaterial::Camellia128 { k }))
+                .map_err(invalid_length),
+            #[cfg(feature = "camellia")]
+            EncryptionType::CAMELLIA256_CTS_CMAC => key
+                .try_into()
+                .map(|k| BaseKey(KeyMaterial::Camellia256 { k }))
+                .map_err(invalid_length),
+            #[cfg(feature = "legacy-crypto")]
+            EncryptionType::DES3_CBC_SHA1_KD => key
+                .try_into()
+                .map(|k| BaseKey(KeyMaterial::Des3 { k }))
+                .map_err(invalid_length),
+            _ => Err(KrbError::UnsupportedEncryption),
+        }
+    }
+
+    pub fn etype(&self) -> EncryptionType {
+        match self.0 {
+            KeyMaterial::Aes128 { .. } => EncryptionType::AES128_CTS_HMAC_SHA1_96,
+            KeyMaterial::Aes256 { .. } => EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            KeyMaterial::Aes128Sha256 { .. } => EncryptionType::AES128_CTS_HMAC_SHA256_128,
+            KeyMaterial::Aes256Sha384 { .. } => EncryptionType::AES256_CTS_HMAC_SHA384_192,
+            #[cfg(feature = "camellia")]
+            KeyMaterial::Camellia128 { .. } => EncryptionType::CAMELLIA128_CTS_CMAC,
+            #[cfg(feature = "camellia")]
+            KeyMaterial::Camellia256 { .. } => EncryptionType::CAMELLIA256_CTS_CMAC,
+            #[cfg(feature = "legacy-crypto")]
+            KeyMaterial::Des3 { .. } => EncryptionType::DES3_CBC_SHA1_KD,
+        }
+    }
+
+    /// The mandatory checksum type of the etype of this key (RFC 3961 3), which is
+    /// the only one that a checksum keyed with it is accepted in.
+    pub(crate) fn mandatory_checksum_type(&self) -> Result<ChecksumType, KrbError> {
+        Ok(cipher_for(self.etype())?.checksum_type())
+    }
+
+    /// The checksum of `data` of the checksum type of this etype.
+    pub(crate) fn checksum(&self, data: &[u8], key_usage: KeyUsage) -> Result<Checksum, KrbError> {
+        Checksum::compute(self.mandatory_checksum_type()?, self, key_usage, data)
+    }
+}
+
+impl PreAuth {
+    /// Add `entry` after the entries already present.
+    pub fn push(&mut self, entry: PreAuthEntry) {
+        self.entries.push(entry);
+    }
+
+    /// The entries in the order they are sent.
+    pub fn entries(&self) -> &[PreAuthEntry] {
+        &self.entries
+    }
+}
+
+impl TryFrom<&PreAuthEntry> for PaData {
+    type Error = der::Error;
+
+    fn try_from(entry: &PreAuthEntry) -> Result<Self, Self::Error> {
+        let (padata_type, value) = match entry {
+            // The padata value of PA-ENC-TIMESTAMP is the DER encoded EncryptedData.
+            PreAuthEntry::EncTimestamp(enc_data) => (
+                PaDataType::PaEncTimestamp as u32,
+                KdcEncryptedData::try_from(enc_data)?.to_der()?,
+            ),
+            PreAuthEntry::EncChallenge(enc_data) => (
+                PaDataType::PaEncryptedChallenge as u32,
+                KdcEncryptedData::try_from(enc_data)?.to_der()?,
+            ),
+            PreAuthEntry::FxCookie(fx_cookie) => (PaDataType::PaFxCookie as u32, fx_cookie.clone()),
+            PreAuthEntry::AsFreshness(token) => {
+                (PaDataType::PadataAsFreshness as u32, token.clone())
+            }
+            PreAuthEntry::Raw { padata_type, value } => (*padata_type, value.clone()),
+        };
+
+        Ok(PaData {
+            padata_type,
+            padata_value: OctetString::new(value)?,
+        })
+    }
+}
+
+impl TryFrom<PaData> for PreAuthEntry {
+    type Error = KrbError;
+
+    fn try_from(padata: PaData) -> Result<Self, Self::Error> {
+        let PaData {
+            padata_type,
+            padata_value,
+        } = padata;
+
+        match PaDataType::try_from(padata_type) {
+            Ok(PaDataType::PaEncTimestamp) => KdcEncryptedData::from_der(padata_value.as_bytes())
+                .map_err(|_| KrbError::DerDecodePaData)
+                .and_then(EncryptedData::try_from)
+                .map(PreAuthEntry::EncTimestamp),
+            Ok(PaDataType::PaEncryptedChallenge) => {
+                KdcEncryptedData::from_der(padata_value.as_bytes())
+                    .map_err(|_| KrbError::DerDecodePaData)
+                    .and_then(EncryptedData::try_from)
+                    .map(PreAuthEntry::EncChallenge)
+            }
+            Ok(PaDataType::PaFxCookie) => Ok(PreAuthEntry::FxCookie(padata_value.into_bytes())),
+            Ok(PaDataType::PadataAsFreshness) => {
+                Ok(PreAuthEntry::AsFreshness(padata_value.into_bytes()))
+            }
+            _ => Ok(PreAuthEntry::Raw {
+                padata_type,
+                value: padata_value.into_bytes(),
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for BaseKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never leak key material into logs.
+        let name = match self.0 {
+            KeyMaterial::Aes128 { .. } => "Aes128",
+            KeyMaterial::Aes256 { .. } => "Aes256",
+            KeyMaterial::Aes128Sha256 { .. } => "Aes128Sha256",
+            KeyMaterial::Aes256Sha384 { .. } => "Aes256Sha384",
+            #[cfg(feature = "camellia")]
+            KeyMaterial::Camellia128 { .. } => "Camellia128",
+            #[cfg(feature = "camellia")]
+            KeyMaterial::Camellia256 { .. } => "Camellia256",
+            #[cfg(feature = "legacy-crypto")]
+            KeyMaterial::Des3 { .. } => "Des3",
+        };
+        f.debug_struct(name).finish_non_exhaustive()
+    }
+}
+
+impl Zeroize for BaseKey {
+    fn zeroize(&mut self) {
+        match &mut self.0 {
+            KeyMaterial::Aes128 { k } | KeyMaterial::Aes128Sha256 { k } => k.zeroize(),
+            KeyMaterial::Aes256 { k } | KeyMaterial::Aes256Sha384 { k } => k.zeroize(),
+            #[cfg(feature = "camellia")]
+            KeyMaterial::Camellia128 { k } => k.zeroize(),
+            #[cfg(feature = "camellia")]
+            KeyMaterial::Camellia256 { k } => k.zeroize(),
+            #[cfg(feature = "legacy-crypto")]
+            KeyMaterial::Des3 { k } => k.zeroize(),
+        }
+    }
+}
+
+impl Drop for BaseKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for BaseKey {}
+
+#[derive(Clone)]
 pub enum EncryptedData {
-    Aes256CtsHmacSha196 { kvno: Option<u32>, data: Vec<u8> },
+    Aes128CtsHmacSha196 {
+        kvno: Option<u32>,
+        data: Vec<u8>,
+    },
+    Aes256CtsHmacSha196 {
+        kvno: Option<u32>,
+        data: Vec<u8>,
+    },
+    Aes128CtsHmacSha256128 {
+        kvno: Option<u32>,
+        data: Vec<u8>,
+    },
+    Aes256CtsHmacSha384192 {
+        kvno: Option<u32>,
+        data: Vec<u8>,
+    },
+    #[cfg(feature = "camellia")]
+    Camellia128CtsCmac {
+        kvno: Option<u32>,
+        data: Vec<u8>,
+    },
+    #[cfg(feature = "camellia")]
+    Camellia256CtsCmac {
+        kvno: Option<u32>,
+        data: Vec<u8>,
+    },
+    #[cfg(feature = "legacy-crypto")]
+    Des3CbcSha1Kd {
+        kvno: Option<u32>,
+        data: Vec<u8>,
+    },
+    /// Data in an etype that can't be decrypted by this crate, which is kept as is.
+    Unsupported {
+        etype: i32,
+        kvno: Option<u32>,
+        data: Vec<u8>,
+    },
+}
+
+impl fmt::Debug for EncryptedData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Only describe the ciphertext, so that it never ends up in logs.
+        match self {
+            EncryptedData::Aes128CtsHmacSha196 { kvno, data } => f
+                .debug_struct("Aes128CtsHmacSha196")
+                .field("kvno", kvno)
+                .field("len", &data.len())
+                .finish(),
+            EncryptedData::Aes256CtsHmacSha196 { kvno, data } => f
+                .debug_struct("Aes256CtsHmacSha196")
+                .field("kvno", kvno)
+                .field("len", &data.len())
+                .finish(),
+            EncryptedData::Aes128CtsHmacSha256128 { kvno, data } => f
+                .debug_struct("Aes128CtsHmacSha256128")
+                .field("kvno", kvno)
+                .field("len", &data.len())
+                .finish(),
+            EncryptedData::Aes256CtsHmacSha384192 { kvno, data } => f
+                .debug_struct("Aes256CtsHmacSha384192")
+                .field("kvno", kvno)
+                .field("len", &data.len())
+                .finish(),
+            #[cfg(feature = "camellia")]
+            EncryptedData::Camellia128CtsCmac { kvno, data } => f
+                .debug_struct("Camellia128CtsCmac")
+                .field("kvno", kvno)
+                .field("len", &data.len())
+                .finish(),
+            #[cfg(feature = "camellia")]
+            EncryptedData::Camellia256CtsCmac { kvno, data } => f
+                .debug_struct("Camellia256CtsCmac")
+                .field("kvno", kvno)
+                .field("len", &data.len())
+                .finish(),
+            #[cfg(feature = "legacy-crypto")]
+            EncryptedData::Des3CbcSha1Kd { kvno, data } => f
+                .debug_struct("Des3CbcSha1Kd")
+                .field("kvno", kvno)
+                .field("len", &data.len())
+                .finish(),
+            EncryptedData::Unsupported { etype, kvno, data } => f
+                .debug_struct("Unsupported")
+                .field("etype", etype)
+                .field("kvno", kvno)
+                .field("len", &data.len())
+                .finish(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct KerberosAsRep {
     pub(crate) client_realm: String,
     pub(crate) client_name: String,
+    // The name type of the client, which is only kept to encode the reply.
+    pub(crate) client_name_type: i32,
+    pub(crate) ticket: TaggedTicket,
+    pub(crate) enc_part: EncryptedData,
+    // The armored KrbFastResponse, until the reply is unarmored.
+    pub(crate) fast_rep: Option<EncryptedData>,
+    // The key that the KDC strengthened the reply key with under FAST.
+    pub(crate) strengthen_key: Option<BaseKey>,
+    // The Diffie-Hellman key of the KDC in reply to a PKINIT request.
+    pub(crate) pk_as_rep: Option<PaPkAsRep>,
+    // The ETYPE-INFO2 that the KDC confirmed the reply key with, if it sent one.
+    pub(crate) etype_info2: Vec<EtypeInfo2>,
+    // The padata that isn't interpreted, which is encoded again as it was received.
+    pub(crate) other_padata: Vec<PaData>,
+}
+
+#[derive(Debug)]
+pub struct KerberosTgsRep {
+    pub(crate) client_realm: String,
+    pub(crate) client_name: String,
+    // The name type of the client, which is only kept to encode the reply.
+    pub(crate) client_name_type: i32,
+    pub(crate) ticket: TaggedTicket,
     pub(crate) enc_part: EncryptedData,
+    // The padata that isn't interpreted, which is encoded again as it was received.
+    pub(crate) other_padata: Vec<PaData>,
+}
+
+/// The decrypted enc-part of a [Ticket], as described by RFC 4120 5.3. Only the
+/// service that the ticket was issued for, and the KDC, can decrypt it.
+#[derive(Debug)]
+pub struct EncTicketPart {
+    pub(crate) flags: FlagSet<TicketFlags>,
+    pub(crate) session_key: BaseKey,
+    pub(crate) client_realm: String,
+    pub(crate) client_name: String,
+    pub(crate) transited: (i32, Vec<u8>),
+    pub(crate) auth_time: SystemTime,
+    pub(crate) start_time: Option<SystemTime>,
+    pub(crate) end_time: SystemTime,
+    pub(crate) renew_till: Option<SystemTime>,
+    pub(crate) client_addresses: Option<Vec<HostAddress>>,
+    pub(crate) authorization_data: Vec<(i32, Vec<u8>)>,
+}
+
+/// The kind of a last-req entry, as described by RFC 4120 5.4.2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LastReqType {
+    /// No information is conveyed by the time.
+    None,
+    /// The last initial request for a TGT.
+    InitialTgt,
+    /// The last initial request, for any ticket.
+    Initial,
+    /// The issue of the newest TGT that was used.
+    NewestTgt,
+    /// The last renewal.
+    Renewal,
+    /// The last request of any type.
+    Request,
+    /// When the client's password expires.
+    PasswordExpiration,
+    /// When the client's account expires.
+    AccountExpiration,
+    /// An lr-type that this crate doesn't know, which is preserved as is.
+    Other(i32),
 }
 
+/// A single entry of the last-req of a reply. A negative lr-type means that the
+/// time is only for the KDC that replied, rather than for all KDCs of the realm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastReqEntry {
+    pub(crate) lr_type: LastReqType,
+    pub(crate) local: bool,
+    pub(crate) time: SystemTime,
+}
+
+/// The decrypted enc-part of an AS-REP, as described by RFC 4120 5.4.2. Most
+/// clients only need the [AuthenticatedReply], but this carries every field that the
+/// KDC returned. The EncTGSRepPart of a TGS-REP has the same fields, and is decrypted
+/// into this too.
+#[derive(Debug)]
+pub struct EncAsRepPart {
+    pub(crate) session_key: BaseKey,
+    pub(crate) last_req: Vec<LastReqEntry>,
+    pub(crate) nonce: u32,
+    pub(crate) key_expiration: Option<SystemTime>,
+    pub(crate) flags: FlagSet<TicketFlags>,
+    pub(crate) auth_time: SystemTime,
+    pub(crate) start_time: Option<SystemTime>,
+    pub(crate) end_time: SystemTime,
+    pub(crate) renew_till: Option<SystemTime>,
+    pub(crate) server_realm: String,
+    pub(crate) server_name: String,
+    pub(crate) client_addresses: Option<Vec<HostAddress>>,
+}
+
+/// The outcome of a successful AS exchange. This contains the ticket that was
+/// issued to the client, and the session key that the client must use with it.
 #[derive(Debug)]
-pub struct KerberosTgsRep {}
+pub struct AuthenticatedReply {
+    pub(crate) client_realm: String,
+    pub(crate) client_name: String,
+    pub(crate) ticket: TaggedTicket,
+    pub(crate) session_key: BaseKey,
+    pub(crate) flags: FlagSet<TicketFlags>,
+    pub(crate) auth_time: SystemTime,
+    pub(crate) start_time: Option<SystemTime>,
+    pub(crate) end_time: SystemTime,
+    pub(crate) renew_till: Option<SystemTime>,
+    // Whether the KDC checksummed the request that this replies to, and it matched.
+    pub(crate) request_verified: bool,
+    // Whether the KDC said in the encrypted part that it supports FAST.
+    pub(crate) kdc_supports_fast: bool,
+}
+
+/// A ticket with everything the client needs to use it, as a credentials cache
+/// holds it. It is built from a reply and its decrypted enc-part.
+#[derive(Debug, Clone)]
+pub struct Credential {
+    client_realm: String,
+    client_name: String,
+    server_realm: String,
+    server_name: String,
+    session_key: BaseKey,
+    ticket: TaggedTicket,
+    flags: FlagSet<TicketFlags>,
+    auth_time: SystemTime,
+    start_time: Option<SystemTime>,
+    end_time: SystemTime,
+    renew_till: Option<SystemTime>,
+    addresses: Option<Vec<HostAddress>>,
+}
 
 #[derive(Debug)]
 pub struct PreAuthData {
@@ -103,13 +806,74 @@ pub struct PreAuthData {
 
 #[derive(Debug)]
 pub struct KerberosPaRep {
+    // Every padata type that the KDC sent, in its order, including those that
+    // aren't known.
+    pub(crate) offered: Vec<u32>,
     pub(crate) pa_fx_fast: bool,
     pub(crate) enc_timestamp: bool,
+    pub(crate) enc_challenge: bool,
+    // The armored KrbFastResponse, when this replies to a FAST request.
+    pub(crate) fast_rep: Option<EncryptedData>,
     pub(crate) pa_fx_cookie: Option<Vec<u8>>,
+    // The freshness token of PA-AS-FRESHNESS, which the next request must echo.
+    pub(crate) pa_as_freshness: Option<Vec<u8>>,
+    pub(crate) otp_challenge: Option<OtpChallenge>,
     pub(crate) etype_info2: Vec<EtypeInfo2>,
+    // The canonical client, which the KDC names when the request asked it to
+    // canonicalize. This is the principal that the default salt is built from.
+    pub(crate) client_realm: Option<String>,
+    pub(crate) client_name: Option<String>,
+    // The times of the KRB-ERROR that carried the padata, if it came in one.
+    pub(crate) stime: Option<SystemTime>,
+    pub(crate) ctime: Option<SystemTime>,
+    // The KRB-ERROR that carried the padata, which is encoded again as it was
+    // received.
+    pub(crate) krb_error: Option<KerberosErrorRep>,
+}
+
+/// The PA-OTP-CHALLENGE (RFC 6560) that a KDC sends inside FAST, offering the OTP
+/// tokens that the client may authenticate with.
+#[derive(Debug, Clone)]
+pub struct OtpChallenge {
+    nonce: Vec<u8>,
+    service: Option<String>,
+    tokens: Vec<OtpToken>,
+}
+
+/// An OTP token that the KDC offered in an [OtpChallenge].
+#[derive(Debug, Clone)]
+pub struct OtpToken(pub(crate) OtpTokenInfo);
+
+/// The e-data of a [KerberosErrorRep], as far as it could be interpreted.
+#[derive(Debug, Clone)]
+pub enum ErrorEData {
+    /// A METHOD-DATA, such as the ETYPE-INFO2 that MIT sends along with
+    /// KDC_ERR_PREAUTH_FAILED and KDC_ERR_ETYPE_NOSUPP, in the order it was sent.
+    MethodData(Vec<PreAuthEntry>),
+    /// Anything else, such as the KERB-ERROR-DATA that Active Directory sends with
+    /// an NTSTATUS for policy failures.
+    Raw(Vec<u8>),
 }
 
+/// A KRB-ERROR from the KDC, other than a request for preauthentication which is
+/// returned as [KerberosResponse::PaRep].
 #[derive(Debug)]
+pub struct KerberosErrorRep {
+    pub(crate) error_code: KrbErrorCode,
+    pub(crate) ctime: Option<SystemTime>,
+    pub(crate) stime: SystemTime,
+    pub(crate) client_realm: Option<String>,
+    pub(crate) client_name: Option<String>,
+    pub(crate) service_realm: String,
+    pub(crate) service_name: String,
+    pub(crate) error_text: Option<String>,
+    pub(crate) error_data: Option<Vec<u8>>,
+    // The name types of the principals, which are only kept to encode the error.
+    pub(crate) client_name_type: i32,
+    pub(crate) service_name_type: i32,
+}
+
+#[derive(Debug, Clone)]
 pub struct EtypeInfo2 {
     // The type of encryption for enc ts.
     etype: EncryptionType,
@@ -126,55 +890,324 @@ pub struct EtypeInfo2 {
     s2kparams: Option<Vec<u8>>,
 }
 
-fn sort_cryptographic_strength(a: &EtypeInfo2, b: &EtypeInfo2) -> Ordering {
-    if a.etype == EncryptionType::AES256_CTS_HMAC_SHA384_192 {
-        Ordering::Greater
-    } else if b.etype == EncryptionType::AES256_CTS_HMAC_SHA384_192 {
-        Ordering::Less
-    } else if a.etype == EncryptionType::AES128_CTS_HMAC_SHA256_128 {
-        Ordering::Greater
-    } else if b.etype == EncryptionType::AES128_CTS_HMAC_SHA256_128 {
-        Ordering::Less
-    } else if a.etype == EncryptionType::AES256_CTS_HMAC_SHA1_96 {
-        Ordering::Greater
-    } else if b.etype == EncryptionType::AES256_CTS_HMAC_SHA1_96 {
-        Ordering::Less
-    } else if a.etype == EncryptionType::AES128_CTS_HMAC_SHA1_96 {
-        Ordering::Greater
-    } else if b.etype == EncryptionType::AES128_CTS_HMAC_SHA1_96 {
-        Ordering::Less
-    } else {
-        // Everything else is trash.
-        Ordering::Equal
+/// How the client's key is derived from a passphrase with the string-to-key
+/// parameters that the KDC sent.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StringToKeyOptions {
+    min_iter_count: u32,
+}
+
+impl StringToKeyOptions {
+    /// Refuse to derive a key with fewer than `min_iter_count` iterations, with
+    /// [KrbError::PreAuthIterCountTooLow]. The count is chosen by the KDC, so without
+    /// a floor a malicious KDC can advertise a single iteration to make the key cheap
+    /// to guess. By default any count is accepted.
+    pub fn min_iter_count(mut self, min_iter_count: u32) -> Self {
+        self.min_iter_count = min_iter_count;
+        self
+    }
+
+    fn check_iter_count(&self, iter_count: u32) -> Result<(), KrbError> {
+        if iter_count < self.min_iter_count {
+            return Err(KrbError::PreAuthIterCountTooLow(
+                iter_count,
+                self.min_iter_count,
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl EtypeInfo2 {
+    pub fn etype(&self) -> EncryptionType {
+        self.etype
+    }
+
+    /// The iteration count that the key of this etype is derived with. This is the
+    /// count of the s2kparams, or the default of the etype when the KDC sent none.
+    pub fn iter_count(&self) -> Result<u32, KrbError> {
+        cipher_for(self.etype)?.iter_count(self.s2kparams.as_deref())
+    }
+
+    /// The salt and iteration count of this etype for the client `cname` in `realm`.
+    /// Without a salt, the default salt of `realm` and `cname` is used.
+    pub(crate) fn string_to_key_params(
+        &self,
+        realm: &str,
+        cname: &str,
+    ) -> Result<StringToKeyParams, KrbError> {
+        let salt = match &self.salt {
+            Some(external_salt) => external_salt.as_bytes().to_vec(),
+            None => default_salt(realm.as_bytes(), cname.as_bytes()),
+        };
+
+        Ok(StringToKeyParams {
+            etype: self.etype,
+            salt,
+            iter_count: self.iter_count()?,
+        })
+    }
+
+    /// Derive the client's key from the passphrase with the salt and parameters of
+    /// this etype, as [Self::string_to_key_params].
+    pub(crate) fn derive_key(
+        &self,
+        passphrase: &str,
+        realm: &str,
+        cname: &str,
+        options: &StringToKeyOptions,
+    ) -> Result<BaseKey, KrbError> {
+        self.string_to_key_params(realm, cname)?
+            .derive_key(passphrase, options)
+    }
+
+    /// Derive the key as [Self::derive_key] without blocking the executor.
+    #[cfg(feature = "tokio-codec")]
+    pub(crate) async fn derive_key_async(
+        &self,
+        passphrase: &str,
+        realm: &str,
+        cname: &str,
+        options: &StringToKeyOptions,
+    ) -> Result<BaseKey, KrbError> {
+        self.string_to_key_params(realm, cname)?
+            .derive_key_async(passphrase, options)
+            .await
+    }
+}
+
+/// The inputs of a string-to-key other than the passphrase. Keys derived from the
+/// same passphrase with equal parameters are the same key, so it only needs to be
+/// derived once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StringToKeyParams {
+    etype: EncryptionType,
+    salt: Vec<u8>,
+    iter_count: u32,
+}
+
+impl StringToKeyParams {
+    pub(crate) fn derive_key(
+        &self,
+        passphrase: &str,
+        options: &StringToKeyOptions,
+    ) -> Result<BaseKey, KrbError> {
+        let cipher = cipher_for(self.etype)?;
+        options.check_iter_count(self.iter_count)?;
+
+        debug!(etype = ?self.etype, iter_count = %self.iter_count, "Deriving key from passphrase");
+        let key = cipher.string_to_key(passphrase.as_bytes(), &self.salt, Some(self.iter_count))?;
+        BaseKey::from_bytes(self.etype, &key)
+    }
+
+    /// Derive the key as [Self::derive_key] on the blocking thread pool of tokio. The
+    /// iterations of the string-to-key take long enough that running them on the
+    /// executor would stall every other task on its thread.
+    #[cfg(feature = "tokio-codec")]
+    pub(crate) async fn derive_key_async(
+        &self,
+        passphrase: &str,
+        options: &StringToKeyOptions,
+    ) -> Result<BaseKey, KrbError> {
+        let params = self.clone();
+        let passphrase = Zeroizing::new(passphrase.to_string());
+        let options = *options;
+
+        tokio::task::spawn_blocking(move || params.derive_key(&passphrase, &options))
+            .await
+            .map_err(|_| KrbError::StringToKeyTaskFailed)?
+    }
+}
+
+/// The default salt of the principal `cname` in `realm` (RFC 4120 4), which the
+/// string-to-key uses when the KDC names no salt. This is the realm as given, followed
+/// by each component of the name without the separators, so that
+/// `host/server.example.com@EXAMPLE.COM` is salted with
+/// `EXAMPLE.COMhostserver.example.com`. The realm isn't folded to any case, so a
+/// canonicalized client should pass the realm that the KDC replied with.
+pub fn salt_for_principal(realm: &str, cname: &str) -> String {
+    // Splitting at the separators keeps each component whole, so the salt of two
+    // strings is a string too.
+    String::from_utf8_lossy(&default_salt(realm.as_bytes(), cname.as_bytes())).into_owned()
+}
+
+/// Decode the ETYPE-INFO2 of a padata, keeping the entries of known etypes.
+pub(crate) fn etype_info2_from_der(padata_value: &[u8]) -> Result<Vec<EtypeInfo2>, KrbError> {
+    let einfo2_sequence =
+        KdcETypeInfo2::from_der(padata_value).map_err(|_| KrbError::DerDecodeEtypeInfo2)?;
+
+    Ok(einfo2_sequence
+        .into_iter()
+        .filter_map(|einfo2| {
+            // Invalid etype or we don't support it.
+            let etype = EncryptionType::try_from(einfo2.etype).ok()?;
+            Some(EtypeInfo2 {
+                etype,
+                salt: einfo2.salt.map(|s| s.into()),
+                s2kparams: einfo2.s2kparams.map(|v| v.as_bytes().to_vec()),
+            })
+        })
+        .collect())
+}
+
+/// Add `postdate_options` to `kdc_options` when the request starts at a `from` in the
+/// future, since the KDC otherwise ignores the from time and issues the ticket now.
+fn with_postdate_options(
+    kdc_options: FlagSet<KdcOptions>,
+    from: Option<SystemTime>,
+    postdate_options: FlagSet<KdcOptions>,
+) -> Result<FlagSet<KdcOptions>, KrbError> {
+    match from {
+        Some(from) if from > SystemTime::now() => Ok(kdc_options | postdate_options),
+        Some(_) => Ok(kdc_options),
+        // A postdated ticket must know when it starts.
+        None if kdc_options.contains(KdcOptions::Postdated) => Err(KrbError::MissingStartTime),
+        None => Ok(kdc_options),
     }
 }
 
+/// Whether the crypto of this crate can handle `etype`.
+fn is_supported_etype(etype: EncryptionType) -> bool {
+    cipher_for(etype).is_ok()
+}
+
 #[derive(Debug)]
 enum KerberosErrRep {
-    Err(KrbErrorCode),
+    Err(KerberosErrorRep),
     Pa(KerberosPaRep),
 }
 
 impl KerberosRequest {
+    /// Build an AS-REQ for `client_name` in `realm`, requesting a ticket for
+    /// `service_name` that is valid until `until`. The start and renew times, along
+    /// with everything else the request may carry, are set on the builder.
+    ///
+    /// ```
+    /// use libkrime::proto::KerberosRequest;
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let until = SystemTime::now() + Duration::from_secs(3600);
+    ///
+    /// let as_req =
+    ///     KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+    ///         .renewable(true)
+    ///         .renew(Some(until + Duration::from_secs(86400)))
+    ///         .build()
+    ///         .expect("Failed to build request");
+    /// ```
     pub fn build_asreq(
-        client_name: String,
-        service_name: String,
-        from: Option<SystemTime>,
+        client_name: impl Into<String>,
+        service_name: impl Into<String>,
+        realm: impl Into<String>,
         until: SystemTime,
-        renew: Option<SystemTime>,
     ) -> KerberosAsReqBuilder {
         KerberosAsReqBuilder {
-            client_name,
-            service_name,
-            from,
+            client_name: client_name.into(),
+            service_name: service_name.into(),
+            realm: realm.into(),
+            from: None,
             until,
-            renew,
+            renew: None,
             preauth: None,
+            kdc_options: FlagSet::default(),
+            addresses: None,
+            enterprise: false,
+            // MIT KRB5 claims to support AES128_CTS_HMAC_SHA256_128 and
+            // AES256_CTS_HMAC_SHA384_192, but if they are provided then MIT KDC's will
+            // ignore them.
+            etypes: vec![
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                EncryptionType::AES128_CTS_HMAC_SHA1_96,
+            ],
+            nonce: None,
+            pac_options: FlagSet::default(),
+            req_enc_pa_rep: false,
+            armor: None,
+            #[cfg(feature = "pkinit")]
+            pkinit: None,
+        }
+    }
+
+    /// Build a request to renew the renewable ticket `tgt`, so that it is valid
+    /// until `until`. The KDC won't extend it past its renew-till, and replies with
+    /// the same ticket with new times.
+    pub fn build_renew(
+        tgt: &AuthenticatedReply,
+        until: SystemTime,
+    ) -> Result<KerberosRequest, KrbError> {
+        Self::build_reissue(tgt, until, KdcOptions::Renew)
+    }
+
+    /// Build a request to validate the postdated ticket `tgt`, which was issued with
+    /// the invalid flag. This must be sent once the start time of the ticket has
+    /// arrived, and the KDC replies with the same ticket without the invalid flag.
+    pub fn build_validate(tgt: &AuthenticatedReply) -> Result<KerberosRequest, KrbError> {
+        Self::build_reissue(tgt, tgt.end_time(), KdcOptions::Validate)
+    }
+
+    fn build_reissue(
+        tgt: &AuthenticatedReply,
+        until: SystemTime,
+        option: KdcOptions,
+    ) -> Result<KerberosRequest, KrbError> {
+        // RFC 4120 3.3.3.1 The ticket is renewed or validated by the TGS that issued
+        // it, which is named by its own sname and realm.
+        let service_name: String = tgt.ticket.0.sname.clone().into();
+        let realm: String = tgt.ticket.0.realm.clone().into();
+
+        KerberosRequest::build_tgsreq(format!("{service_name}@{realm}"), until)
+            .kdc_options(FlagSet::from(option))
+            .build(tgt)
+    }
+
+    /// Build a request for a ticket to `service_name`. The request is authenticated
+    /// with a TGT when it is built. The service name may be qualified with its realm,
+    /// as in `krbtgt/REMOTE.REALM@LOCAL.REALM`, otherwise it is in the realm of the
+    /// TGS that the TGT is for.
+    pub fn build_tgsreq(
+        service_name: impl Into<String>,
+        until: SystemTime,
+    ) -> KerberosTgsReqBuilder {
+        KerberosTgsReqBuilder {
+            service_name: service_name.into(),
+            from: None,
+            until,
+            renew: None,
+            kdc_options: FlagSet::default(),
+            additional_tickets: Vec::new(),
+            for_user: None,
+            evidence_ticket: None,
+            pac_options: FlagSet::default(),
+            clock_offset: ClockOffset::default(),
+            subkey: None,
         }
     }
 
-    pub(crate) fn from_der(der: Vec<u8>) -> Result<Self, der::Error> {
-        todo!();
+    pub(crate) fn from_der(der: &[u8]) -> Result<Self, der::Error> {
+        match KrbKdcReq::from_der(der)? {
+            KrbKdcReq::AsReq(kdc_req) => {
+                let tag = Tag::Application {
+                    constructed: true,
+                    number: TagNumber::N10,
+                };
+                let as_req = KerberosAsReq::try_from(kdc_req).map_err(|err| {
+                    trace!(?err, "Failed to parse as req");
+                    tag.value_error()
+                })?;
+                Ok(KerberosRequest::AsReq(as_req))
+            }
+            KrbKdcReq::TgsReq(kdc_req) => {
+                let tag = Tag::Application {
+                    constructed: true,
+                    number: TagNumber::N12,
+                };
+                let tgs_req = KerberosTgsReq::try_from(kdc_req).map_err(|err| {
+                    trace!(?err, "Failed to parse tgs req");
+                    tag.value_error()
+                })?;
+                Ok(KerberosRequest::TgsReq(tgs_req))
+            }
+        }
     }
 
     pub(crate) fn to_der(&self) -> Result<Vec<u8>, der::Error> {
@@ -183,14 +1216,219 @@ impl KerberosRequest {
                 let asn_as_req = as_req.to_asn()?;
                 KrbKdcReq::to_der(&KrbKdcReq::AsReq(asn_as_req))
             }
+            KerberosRequest::TgsReq(tgs_req) => {
+                let asn_tgs_req = tgs_req.to_asn()?;
+                KrbKdcReq::to_der(&KrbKdcReq::TgsReq(asn_tgs_req))
+            }
         }
     }
-}
-
-impl<'a> ::der::Decode<'a> for KerberosResponse {
-    fn decode<R: der::Reader<'a>>(decoder: &mut R) -> der::Result<Self> {
-        let tag: der::Tag = decoder.decode()?;
-        let _len: der::Length = decoder.decode()?;
+
+    /// The realm that this request is addressed to.
+    pub fn realm(&self) -> &str {
+        match self {
+            KerberosRequest::AsReq(as_req) => &as_req.realm,
+            KerberosRequest::TgsReq(tgs_req) => &tgs_req.body.realm,
+        }
+    }
+
+    /// The client that requested an initial ticket. A TGS-REQ doesn't name its
+    /// client in the req-body, as it is identified by the presented TGT.
+    pub fn client_name(&self) -> Option<&str> {
+        match self {
+            KerberosRequest::AsReq(as_req) => Some(&as_req.client_name),
+            KerberosRequest::TgsReq(_) => None,
+        }
+    }
+
+    /// The service that a ticket is requested for.
+    pub fn service_name(&self) -> &str {
+        match self {
+            KerberosRequest::AsReq(as_req) => &as_req.service_name,
+            KerberosRequest::TgsReq(tgs_req) => &tgs_req.body.service_name,
+        }
+    }
+
+    /// The time that the client would like the ticket to be valid from.
+    pub fn from(&self) -> Option<SystemTime> {
+        match self {
+            KerberosRequest::AsReq(as_req) => as_req.from,
+            KerberosRequest::TgsReq(tgs_req) => tgs_req.body.from,
+        }
+    }
+
+    /// The time that the client would like the ticket to expire.
+    pub fn until(&self) -> SystemTime {
+        match self {
+            KerberosRequest::AsReq(as_req) => as_req.until,
+            KerberosRequest::TgsReq(tgs_req) => tgs_req.body.until,
+        }
+    }
+
+    /// The time that the client would like the ticket to be renewable until.
+    pub fn renew_until(&self) -> Option<SystemTime> {
+        match self {
+            KerberosRequest::AsReq(as_req) => as_req.renew,
+            KerberosRequest::TgsReq(tgs_req) => tgs_req.body.renew,
+        }
+    }
+
+    /// The nonce that the KDC must return in the encrypted part of its reply.
+    pub fn nonce(&self) -> u32 {
+        match self {
+            KerberosRequest::AsReq(as_req) => as_req.nonce,
+            KerberosRequest::TgsReq(tgs_req) => tgs_req.body.nonce,
+        }
+    }
+
+    /// The etypes that the client supports for the session key, in its order of
+    /// preference. Etypes that this crate doesn't know are left out.
+    pub fn etypes(&self) -> &[EncryptionType] {
+        match self {
+            KerberosRequest::AsReq(as_req) => &as_req.etypes,
+            KerberosRequest::TgsReq(tgs_req) => &tgs_req.body.etypes,
+        }
+    }
+
+    /// The pre-authentication that an AS-REQ carries. The PA-TGS-REQ of a TGS-REQ
+    /// is instead verified with [KerberosTgsReq::verify_authenticator].
+    pub fn preauth(&self) -> Option<&PreAuth> {
+        match self {
+            KerberosRequest::AsReq(as_req) => as_req.preauth.as_ref(),
+            KerberosRequest::TgsReq(_) => None,
+        }
+    }
+
+    /// The name of this message type as used by RFC4120, for logging.
+    pub(crate) fn message_type(&self) -> &'static str {
+        match self {
+            KerberosRequest::AsReq(_) => "AS-REQ",
+            KerberosRequest::TgsReq(_) => "TGS-REQ",
+        }
+    }
+}
+
+impl KerberosResponse {
+    /// The name of this message type as used by RFC4120, for logging.
+    pub(crate) fn message_type(&self) -> &'static str {
+        match self {
+            KerberosResponse::AsRep(_) => "AS-REP",
+            KerberosResponse::TgsRep(_) => "TGS-REP",
+            KerberosResponse::PaRep(_) | KerberosResponse::Err(_) => "KRB-ERROR",
+        }
+    }
+
+    /// The error code if this response is a KRB-ERROR.
+    pub(crate) fn error_code(&self) -> Option<KrbErrorCode> {
+        match self {
+            KerberosResponse::AsRep(_) | KerberosResponse::TgsRep(_) => None,
+            KerberosResponse::PaRep(_) => Some(KrbErrorCode::KdcErrPreauthRequired),
+            KerberosResponse::Err(err_rep) => Some(err_rep.error_code),
+        }
+    }
+}
+
+impl KerberosErrorRep {
+    pub fn error_code(&self) -> KrbErrorCode {
+        self.error_code
+    }
+
+    /// The client's time from the request, if the KDC echoed it.
+    pub fn ctime(&self) -> Option<SystemTime> {
+        self.ctime
+    }
+
+    /// The KDC's time when it generated the error.
+    pub fn stime(&self) -> SystemTime {
+        self.stime
+    }
+
+    /// How far the clock of the KDC is from ours, going by the time that it sent
+    /// this error. After KRB_AP_ERR_SKEW, the request can be sent again with it.
+    pub fn clock_offset(&self) -> ClockOffset {
+        ClockOffset::new(self.stime, SystemTime::now())
+    }
+
+    pub fn client_realm(&self) -> Option<&str> {
+        self.client_realm.as_deref()
+    }
+
+    pub fn client_name(&self) -> Option<&str> {
+        self.client_name.as_deref()
+    }
+
+    /// The realm that a client referral (RFC 6806 7) sends the client to. The KDC
+    /// answers KDC_ERR_WRONG_REALM with the realm of the client in crealm, which
+    /// the AS exchange must be started again against.
+    pub fn referral_realm(&self) -> Option<&str> {
+        match self.error_code {
+            KrbErrorCode::KdcErrWrongRealm => self.client_realm.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn service_realm(&self) -> &str {
+        &self.service_realm
+    }
+
+    pub fn service_name(&self) -> &str {
+        &self.service_name
+    }
+
+    /// Additional human readable text about the error.
+    pub fn error_text(&self) -> Option<&str> {
+        self.error_text.as_deref()
+    }
+
+    /// The raw e-data. Its contents depend on the error code and the KDC.
+    pub fn error_data(&self) -> Option<&[u8]> {
+        self.error_data.as_deref()
+    }
+
+    /// The e-data, decoded as a METHOD-DATA whatever the error code is, since KDCs
+    /// send one with several errors. When that fails the raw e-data is returned.
+    pub fn e_data(&self) -> Option<ErrorEData> {
+        let error_data = self.error_data.as_deref()?;
+
+        let method_data = MethodData::from_der(error_data)
+            .map_err(|_| KrbError::DerDecodePaData)
+            .and_then(|padata| padata.into_iter().map(PreAuthEntry::try_from).collect());
+
+        Some(match method_data {
+            Ok(entries) => ErrorEData::MethodData(entries),
+            Err(_) => ErrorEData::Raw(error_data.to_vec()),
+        })
+    }
+
+    /// The pre-authentication hints in the e-data, if it is a METHOD-DATA. After
+    /// KDC_ERR_ETYPE_NOSUPP or KDC_ERR_PREAUTH_FAILED this tells which etypes and
+    /// salts the KDC has keys for.
+    pub fn pa_rep(&self) -> Option<KerberosPaRep> {
+        let padata = MethodData::from_der(self.error_data.as_deref()?).ok()?;
+
+        let mut pa_rep = KerberosPaRep::try_from(padata).ok()?;
+        pa_rep.client_realm.clone_from(&self.client_realm);
+        pa_rep.client_name.clone_from(&self.client_name);
+        Some(pa_rep)
+    }
+}
+
+impl TryFrom<KrbKdcRep> for KerberosResponse {
+    type Error = KrbError;
+
+    /// Interpret a reply by its application tag. The pvno and msg_type of the body
+    /// must agree with the tag, so that an AS-REP is never taken for a TGS-REP.
+    fn try_from(rep: KrbKdcRep) -> Result<Self, Self::Error> {
+        match rep {
+            KrbKdcRep::AsRep(kdc_rep) => KerberosAsRep::try_from(kdc_rep).map(Self::AsRep),
+            KrbKdcRep::TgsRep(kdc_rep) => KerberosTgsRep::try_from(kdc_rep).map(Self::TgsRep),
+        }
+    }
+}
+
+impl<'a> ::der::Decode<'a> for KerberosResponse {
+    fn decode<R: der::Reader<'a>>(decoder: &mut R) -> der::Result<Self> {
+        let tag: der::Tag = decoder.decode()?;
+        let _len: der::Length = decoder.decode()?;
 
         match tag {
             Tag::Application {
@@ -198,19 +1436,20 @@ impl<'a> ::der::Decode<'a> for KerberosResponse {
                 number: TagNumber::N11,
             } => {
                 let kdc_rep: KdcRep = decoder.decode()?;
-                //let kdc_rep: KrbKdcRep = KrbKdcRep::AsRep(kdc_rep);
-                let as_rep: KerberosAsRep =
-                    KerberosAsRep::try_from(kdc_rep).expect("Failed to parse as rep");
-                Ok(KerberosResponse::AsRep(as_rep))
+                KerberosResponse::try_from(KrbKdcRep::AsRep(kdc_rep)).map_err(|err| {
+                    trace!(?err, "Failed to parse as rep");
+                    tag.value_error()
+                })
             }
             Tag::Application {
                 constructed: true,
                 number: TagNumber::N13,
             } => {
                 let kdc_rep: KdcRep = decoder.decode()?;
-                let tgs_rep: KerberosTgsRep =
-                    KerberosTgsRep::try_from(kdc_rep).expect("Failed to parse tgs rep");
-                Ok(KerberosResponse::TgsRep(tgs_rep))
+                KerberosResponse::try_from(KrbKdcRep::TgsRep(kdc_rep)).map_err(|err| {
+                    trace!(?err, "Failed to parse tgs rep");
+                    tag.value_error()
+                })
             }
             Tag::Application {
                 constructed: true,
@@ -219,12 +1458,14 @@ impl<'a> ::der::Decode<'a> for KerberosResponse {
                 let kdc_rep: crate::asn1::krb_error::KrbError = decoder.decode()?;
                 // Kerberos encodes state in some error resposes, and so we need to disambiguate
                 // that here.
-                let err_rep: KerberosErrRep =
-                    KerberosErrRep::try_from(kdc_rep).expect("Failed to parse err rep");
+                let err_rep: KerberosErrRep = KerberosErrRep::try_from(kdc_rep).map_err(|err| {
+                    trace!(?err, "Failed to parse err rep");
+                    tag.value_error()
+                })?;
 
                 Ok(match err_rep {
                     KerberosErrRep::Pa(pa_rep) => KerberosResponse::PaRep(pa_rep),
-                    KerberosErrRep::Err(err_code) => KerberosResponse::ErrRep(err_code),
+                    KerberosErrRep::Err(err_rep) => KerberosResponse::Err(err_rep),
                 })
             }
             _ => Err(der::Error::from(der::ErrorKind::TagUnexpected {
@@ -235,419 +1476,8040 @@ impl<'a> ::der::Decode<'a> for KerberosResponse {
     }
 }
 
+/// The encoding of a [KerberosResponse], as a KDC sends it. Whatever the crate
+/// doesn't interpret of a decoded response is encoded again as it was received, so
+/// that a proxy can pass a response on unchanged.
+enum AsnResponse {
+    KdcRep(KrbKdcRep),
+    KrbError(TaggedKrbError),
+}
+
+impl KerberosResponse {
+    fn to_asn(&self) -> Result<AsnResponse, der::Error> {
+        match self {
+            KerberosResponse::AsRep(as_rep) => as_rep
+                .to_asn()
+                .map(|rep| AsnResponse::KdcRep(KrbKdcRep::AsRep(rep))),
+            KerberosResponse::TgsRep(tgs_rep) => tgs_rep
+                .to_asn()
+                .map(|rep| AsnResponse::KdcRep(KrbKdcRep::TgsRep(rep))),
+            KerberosResponse::PaRep(pa_rep) => pa_rep
+                .to_asn()
+                .map(|err| AsnResponse::KrbError(TaggedKrbError(err))),
+            KerberosResponse::Err(err_rep) => err_rep
+                .to_asn()
+                .map(|err| AsnResponse::KrbError(TaggedKrbError(err))),
+        }
+    }
+}
+
+impl ::der::Encode for KerberosResponse {
+    fn encoded_len(&self) -> der::Result<der::Length> {
+        match self.to_asn()? {
+            AsnResponse::KdcRep(rep) => rep.encoded_len(),
+            AsnResponse::KrbError(err) => err.encoded_len(),
+        }
+    }
+
+    fn encode(&self, encoder: &mut impl der::Writer) -> der::Result<()> {
+        match self.to_asn()? {
+            AsnResponse::KdcRep(rep) => rep.encode(encoder),
+            AsnResponse::KrbError(err) => err.encode(encoder),
+        }
+    }
+}
+
 impl KerberosAsReqBuilder {
     pub fn add_preauthentication(mut self, preauth: PreAuth) -> Self {
         self.preauth = Some(preauth);
         self
     }
 
-    pub fn build(self) -> KerberosRequest {
+    /// Set the options requested of the KDC. By default no options are requested.
+    pub fn kdc_options(mut self, kdc_options: FlagSet<KdcOptions>) -> Self {
+        self.kdc_options = kdc_options;
+        self
+    }
+
+    /// Request a ticket that may be forwarded to other hosts.
+    pub fn forwardable(self, forwardable: bool) -> Self {
+        self.set_kdc_option(KdcOptions::Forwardable, forwardable)
+    }
+
+    /// Request a ticket that may be used to obtain proxy tickets.
+    pub fn proxiable(self, proxiable: bool) -> Self {
+        self.set_kdc_option(KdcOptions::Proxiable, proxiable)
+    }
+
+    /// Request a renewable ticket. This requires the renew time to be set.
+    pub fn renewable(self, renewable: bool) -> Self {
+        self.set_kdc_option(KdcOptions::Renewable, renewable)
+    }
+
+    /// Request a ticket that is postdated to start at the from time, which must be
+    /// set. The ticket is issued invalid, and must be validated with
+    /// [KerberosRequest::build_validate] once it starts.
+    pub fn postdated(self, postdated: bool) -> Self {
+        self.set_kdc_option(KdcOptions::Postdated, postdated)
+    }
+
+    /// Set when the ticket starts. A start time in the future requests a postdated
+    /// ticket, as with [Self::postdated].
+    pub fn from(mut self, from: Option<SystemTime>) -> Self {
+        self.from = from;
+        self
+    }
+
+    /// Set until when a renewable ticket may be renewed.
+    pub fn renew(mut self, renew: Option<SystemTime>) -> Self {
+        self.renew = renew;
+        self
+    }
+
+    /// Allow the KDC to canonicalize the client and service names.
+    pub fn canonicalize(self, canonicalize: bool) -> Self {
+        self.set_kdc_option(KdcOptions::Canonicalize, canonicalize)
+    }
+
+    /// Accept a renewable ticket when the requested lifetime can't be granted, whose
+    /// renew time is then the requested end time.
+    pub fn renewable_ok(self, renewable_ok: bool) -> Self {
+        self.set_kdc_option(KdcOptions::RenewableOk, renewable_ok)
+    }
+
+    /// Request an anonymous ticket (RFC 8062), which is sent as the
+    /// [ANONYMOUS_PRINCIPAL] in place of the client name. The KDC only issues one
+    /// when the client is authenticated some other way, such as anonymous PKINIT.
+    pub fn anonymous(mut self, anonymous: bool) -> Self {
+        if anonymous {
+            self.client_name = ANONYMOUS_PRINCIPAL.to_string();
+            self.enterprise = false;
+        }
+        self.set_kdc_option(KdcOptions::RequestAnonymous, anonymous)
+    }
+
+    /// Ask the KDC not to check the transited realms of a cross-realm path against
+    /// its policy.
+    pub fn disable_transited_check(self, disable_transited_check: bool) -> Self {
+        self.set_kdc_option(KdcOptions::DisableTransitedCheck, disable_transited_check)
+    }
+
+    /// Send the client name as an enterprise name (RFC 6806), such as the UPN
+    /// `user@corp.example.com` of an Active Directory user, which the KDC maps to
+    /// the principal of the client. This is usually combined with canonicalize, so
+    /// that the KDC replies with the principal it mapped the name to.
+    pub fn enterprise(mut self, enterprise: bool) -> Self {
+        self.enterprise = enterprise;
+        self
+    }
+
+    /// The etypes that the client supports, in order of preference. The KDC picks
+    /// the etype of the reply from these. At least one of them must be supported by
+    /// this crate. By default aes256-cts-hmac-sha1-96 is offered, then
+    /// aes128-cts-hmac-sha1-96.
+    pub fn etypes(mut self, etypes: Vec<EncryptionType>) -> Self {
+        self.etypes = etypes;
+        self
+    }
+
+    /// Use this nonce rather than a random one, such as to send a request again or
+    /// to produce the same request in tests. Only the low 31 bits are used.
+    pub fn nonce(mut self, nonce: u32) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    /// Set or clear a PAC option, which is sent as PA-PAC-OPTIONS. This is only
+    /// understood by Active Directory, such as to ask for the claims of the client.
+    pub fn pac_option(mut self, option: PacOptions, enabled: bool) -> Self {
+        if enabled {
+            self.pac_options |= option;
+        } else {
+            self.pac_options -= option;
+        }
+        self
+    }
+
+    /// Ask the KDC to return a checksum of this request in the encrypted part of
+    /// the reply, with PA-REQ-ENC-PA-REP (RFC 6806 11). This detects tampering with
+    /// the parts of the request that are sent in the clear, such as the options,
+    /// once the reply is decrypted with [KerberosAsRep::into_verified_reply].
+    pub fn req_enc_pa_rep(mut self, req_enc_pa_rep: bool) -> Self {
+        self.req_enc_pa_rep = req_enc_pa_rep;
+        self
+    }
+
+    /// Armor the request with FAST (RFC 6113), which hides the preauthentication
+    /// from anyone but the KDC. The reply must then be unarmored with the same armor.
+    pub fn armor(mut self, armor: &FastArmor) -> Self {
+        self.armor = Some(armor.clone());
+        self
+    }
+
+    /// Preauthenticate with PKINIT (RFC 4556), signing the request with the
+    /// certificate of `signer`. The reply of the KDC is only trusted once
+    /// `kdc_cert_validator` accepts it, and its reply key is agreed with
+    /// Diffie-Hellman rather than derived from a password.
+    #[cfg(feature = "pkinit")]
+    pub fn preauth_pkinit<S, V>(mut self, signer: S, kdc_cert_validator: V) -> Self
+    where
+        S: PkinitSigner + 'static,
+        V: KdcCertValidator + 'static,
+    {
+        self.pkinit = Some(PkinitPreauth::new(
+            Arc::new(signer),
+            Arc::new(kdc_cert_validator),
+        ));
+        self
+    }
+
+    /// Restrict the issued ticket to be used from these addresses only.
+    pub fn addresses(mut self, addresses: Vec<HostAddress>) -> Self {
+        self.addresses = Some(addresses);
+        self
+    }
+
+    fn set_kdc_option(mut self, option: KdcOptions, enabled: bool) -> Self {
+        if enabled {
+            self.kdc_options |= option;
+        } else {
+            self.kdc_options -= option;
+        }
+        self
+    }
+
+    /// Build the request. This fails if the requested lifetime can't be issued, as
+    /// when it has already ended, ends before it starts, or ends after it may be
+    /// renewed until.
+    pub fn build(self) -> Result<KerberosRequest, KrbError> {
+        check_lifetime(self.from, self.until, self.renew)?;
+        self.build_unchecked()
+    }
+
+    /// Build the request as [Self::build] does, without checking that the lifetime
+    /// can be issued. The KDC is then left to refuse it, which is useful to test how
+    /// a malformed request is handled. The times must still be representable.
+    pub fn build_unchecked(self) -> Result<KerberosRequest, KrbError> {
         let KerberosAsReqBuilder {
             client_name,
             service_name,
+            realm,
             from,
             until,
             renew,
             preauth,
+            kdc_options,
+            addresses,
+            enterprise,
+            etypes,
+            nonce,
+            pac_options,
+            req_enc_pa_rep,
+            armor,
+            #[cfg(feature = "pkinit")]
+            pkinit,
         } = self;
 
-        // let nonce: u32 = thread_rng().gen();
-        // BUG IN MIT KRB5 - If the value is greater than i32 max you get:
-        //
-        // Jun 28 03:47:41 3e79497ab6b5 krb5kdc[1](Error): ASN.1 value too large - while dispatching (tcp)
-        //
-        let nonce = 2_147_483_647;
+        let nonce = request_nonce(nonce);
+
+        // A renewable ticket needs to know how long it may be renewed for.
+        if kdc_options.contains(KdcOptions::Renewable) && renew.is_none() {
+            return Err(KrbError::MissingRenewTime);
+        }
+
+        // Reject what can't be encoded now, rather than when the request is sent.
+        // An enterprise name is a single component, even with an @ in it.
+        check_times(from, until, renew)?;
+        if enterprise {
+            check_name_component(&client_name)?;
+        } else {
+            check_principal_name(&client_name)?;
+        }
+        check_principal_name(&service_name)?;
+        check_name_component(&realm)?;
+
+        // RFC 4120 3.1.3 A ticket starting in the future is issued postdated and
+        // invalid. Allowing postdating also lets this ticket, when it is a TGT, be
+        // used to request postdated service tickets.
+        let kdc_options = with_postdate_options(
+            kdc_options,
+            from,
+            KdcOptions::Postdated | KdcOptions::AllowPostdate,
+        )?;
+
+        // Whatever the KDC picks, we must be able to decrypt at least one of them.
+        if !etypes.iter().any(|etype| is_supported_etype(*etype)) {
+            return Err(KrbError::UnsupportedEncryption);
+        }
 
-        KerberosRequest::AsReq(KerberosAsReq {
+        let mut as_req = KerberosAsReq {
             nonce,
+            kdc_options,
+            addresses,
+            enterprise,
+            raw_etypes: etypes.iter().map(|etype| *etype as i32).collect(),
+            etypes,
+            pac_options: (!pac_options.is_empty()).then_some(pac_options),
+            req_enc_pa_rep,
+            fast: None,
+            pk_as_req: None,
+            #[cfg(feature = "pkinit")]
+            pkinit: None,
             client_name,
             service_name,
+            realm,
             from,
             until,
             renew,
             preauth,
-        })
+        };
+
+        // The AuthPack is signed over the req-body, so it can only be built once the
+        // rest of the request is.
+        #[cfg(feature = "pkinit")]
+        if let Some(pkinit) = pkinit {
+            let req_body = as_req
+                .req_body()
+                .and_then(|req_body| req_body.to_der())
+                .map_err(|_| KrbError::DerEncodeKdcReqBody)?;
+            // RFC 8070 3 The token that the KDC returned is also signed in the
+            // PKAuthenticator.
+            let freshness_token = as_req
+                .preauth
+                .iter()
+                .flat_map(|preauth| preauth.entries())
+                .find_map(|entry| match entry {
+                    PreAuthEntry::AsFreshness(token) if !token.is_empty() => Some(token.as_slice()),
+                    _ => None,
+                });
+            as_req.pk_as_req =
+                Some(pkinit.pa_pk_as_req(&req_body, nonce, SystemTime::now(), freshness_token)?);
+            as_req.pkinit = Some(pkinit);
+        }
+
+        // Under FAST all the padata moves into the armored request.
+        if let Some(armor) = armor {
+            let req_body = as_req
+                .req_body()
+                .map_err(|_| KrbError::DerEncodeKdcReqBody)?;
+            let padata = as_req
+                .padata()
+                .map_err(|_| KrbError::DerEncodeFastReq)?
+                .unwrap_or_default();
+            as_req.fast = Some(armor.armor_request(req_body, padata)?);
+        }
+
+        Ok(KerberosRequest::AsReq(as_req))
     }
 }
 
 impl KerberosAsReq {
-    fn to_asn(&self) -> Result<KdcReq, der::Error> {
-        let padata = if let Some(preauth) = &self.preauth {
-            let mut padata_inner = Vec::with_capacity(2);
-
-            if let Some(enc_data) = &preauth.enc_timestamp {
-                let padata_value = OctetString::new(enc_data.clone())?;
-                padata_inner.push(PaData {
-                    padata_type: PaDataType::PaEncTimestamp as u32,
-                    padata_value,
-                })
-            }
+    /// The nonce that the reply from the KDC must contain.
+    pub fn nonce(&self) -> u32 {
+        self.nonce
+    }
 
-            if let Some(fx_cookie) = &preauth.pa_fx_cookie {
-                let padata_value = OctetString::new(fx_cookie.clone())?;
-                padata_inner.push(PaData {
-                    padata_type: PaDataType::PaFxCookie as u32,
-                    padata_value,
-                })
-            }
+    /// The options that the client requested of the KDC.
+    pub fn kdc_options(&self) -> FlagSet<KdcOptions> {
+        self.kdc_options
+    }
 
-            Some(padata_inner)
-        } else {
-            None
+    /// Whether the client accepts a renewable ticket in place of the lifetime it
+    /// requested.
+    pub fn renewable_ok(&self) -> bool {
+        self.kdc_options.contains(KdcOptions::RenewableOk)
+    }
+
+    /// Whether the client asked for the transited realms not to be checked, which
+    /// the KDC may refuse by policy.
+    pub fn disable_transited_check(&self) -> bool {
+        self.kdc_options.contains(KdcOptions::DisableTransitedCheck)
+    }
+
+    /// Whether the client requested an anonymous ticket.
+    pub fn anonymous(&self) -> bool {
+        self.kdc_options.contains(KdcOptions::RequestAnonymous)
+    }
+
+    /// The etypes that the client offered, in its order of preference. Values
+    /// that aren't a known etype are only given by [Self::raw_etypes].
+    pub fn etypes(&self) -> &[EncryptionType] {
+        &self.etypes
+    }
+
+    /// The whole offer of etypes as it was sent, including values that aren't a
+    /// known etype. A KDC can use this to notice a client that offers only etypes
+    /// it doesn't know, or to log what was offered.
+    pub fn raw_etypes(&self) -> &[i32] {
+        &self.raw_etypes
+    }
+
+    /// The etypes that the client offered which this crate can encrypt a reply
+    /// in, in the client's order of preference. When this is empty, the KDC should
+    /// reply with KDC_ERR_ETYPE_NOSUPP.
+    pub fn supported_etypes(&self) -> Vec<EncryptionType> {
+        self.etypes
+            .iter()
+            .copied()
+            .filter(|etype| is_supported_etype(*etype))
+            .collect()
+    }
+
+    /// The PAC options that the client sent as PA-PAC-OPTIONS, if any.
+    pub fn pac_options(&self) -> Option<FlagSet<PacOptions>> {
+        self.pac_options
+    }
+
+    /// Whether the client sent PA-REQ-ENC-PA-REP, asking for a checksum of the
+    /// request in the reply.
+    pub fn req_enc_pa_rep(&self) -> bool {
+        self.req_enc_pa_rep
+    }
+
+    /// The addresses that the client asked the ticket to be restricted to.
+    pub fn addresses(&self) -> Option<&[HostAddress]> {
+        self.addresses.as_deref()
+    }
+
+    /// Whether the request is armored with FAST.
+    pub fn armored(&self) -> bool {
+        self.fast.is_some()
+    }
+
+    /// The pre-authentication data of the request, other than that of FAST, PKINIT
+    /// and the PAC options which are held on their own.
+    pub fn preauth(&self) -> Option<&PreAuth> {
+        self.preauth.as_ref()
+    }
+
+    /// The PKINIT state of the request, which the reply key is agreed with.
+    #[cfg(feature = "pkinit")]
+    pub fn pkinit(&self) -> Option<&PkinitPreauth> {
+        self.pkinit.as_ref()
+    }
+
+    fn to_asn(&self) -> Result<KdcReq, der::Error> {
+        let padata = match &self.fast {
+            Some(fast) => Some(vec![PaData {
+                padata_type: PaDataType::PaFxFast as u32,
+                padata_value: OctetString::new(PaFxFastRequest(fast.clone()).to_der()?)?,
+            }]),
+            None => self.padata()?,
         };
 
         Ok(KdcReq {
             pvno: 5,
             msg_type: KrbMessageType::KrbAsReq as u8,
             padata,
-            req_body: KdcReqBody {
-                kdc_options: FlagSet::<KerberosFlags>::new(0b0)
-                    .expect("Failed to build kdc_options"),
-                cname: Some(PrincipalName {
-                    // Should be some kind of enum probably?
-                    name_type: 1,
-                    name_string: vec![KerberosString(Ia5String::new(&self.client_name).unwrap())],
-                }),
-                realm: KerberosString(Ia5String::new("EXAMPLE.COM").unwrap()),
-                sname: Some(PrincipalName {
-                    name_type: 2,
-                    name_string: vec![
-                        KerberosString(Ia5String::new(&self.service_name).unwrap()),
-                        KerberosString(Ia5String::new("EXAMPLE.COM").unwrap()),
-                    ],
-                }),
-                from: self.from.map(|t| {
-                    KerberosTime::from_system_time(t)
-                        .expect("Failed to build KerberosTime from SystemTime")
-                }),
-                till: KerberosTime::from_system_time(self.until)
-                    .expect("Failed to build KerberosTime from SystemTime"),
-                rtime: self.renew.map(|t| {
-                    KerberosTime::from_system_time(t)
-                        .expect("Failed to build KerberosTime from SystemTime")
-                }),
-                nonce: self.nonce,
-                etype: vec![
-                    EncryptionType::AES256_CTS_HMAC_SHA1_96 as i32,
-                    // MIT KRB5 claims to support these values, but if they are provided then MIT
-                    // KDC's will ignore them.
-                    // EncryptionType::AES128_CTS_HMAC_SHA256_128 as i32,
-                    // EncryptionType::AES256_CTS_HMAC_SHA384_192 as i32,
-                ],
-                addresses: None,
-                enc_authorization_data: None,
-                additional_tickets: None,
-            },
+            req_body: self.req_body()?,
+        })
+    }
+
+    fn padata(&self) -> Result<Option<Vec<PaData>>, der::Error> {
+        let mut padata = self
+            .preauth
+            .as_ref()
+            .map(|preauth| preauth.entries.iter().map(PaData::try_from).collect())
+            .transpose()?;
+
+        // RFC 6806 11 The padata-value is empty in the request.
+        if self.req_enc_pa_rep {
+            padata.get_or_insert_with(Vec::new).push(PaData {
+                padata_type: PaDataType::EncpadataReqEncPaRep as u32,
+                padata_value: OctetString::new(Vec::new())?,
+            });
+        }
+
+        if let Some(pk_as_req) = &self.pk_as_req {
+            padata.get_or_insert_with(Vec::new).push(PaData {
+                padata_type: PaDataType::PaPkAsReq as u32,
+                padata_value: OctetString::new(pk_as_req.to_der()?)?,
+            });
+        }
+
+        if let Some(pac_options) = self.pac_options {
+            let pac_options = PaPacOptions {
+                flags: KerberosFlags(pac_options),
+            };
+            padata.get_or_insert_with(Vec::new).push(PaData {
+                padata_type: PaDataType::PaPacOptions as u32,
+                padata_value: OctetString::new(pac_options.to_der()?)?,
+            });
+        }
+
+        Ok(padata)
+    }
+
+    fn req_body(&self) -> Result<KdcReqBody, der::Error> {
+        Ok(KdcReqBody {
+            kdc_options: KerberosFlags(self.kdc_options),
+            cname: Some(if self.kdc_options.contains(KdcOptions::RequestAnonymous) {
+                // RFC 8062 4.1 The anonymous principal has two components.
+                principal_name_from_str(NT_WELLKNOWN, ANONYMOUS_PRINCIPAL)?
+            } else if self.enterprise {
+                // An enterprise name is a single component, even with an @ in it.
+                PrincipalName {
+                    name_type: NT_ENTERPRISE,
+                    name_string: vec![KerberosString(Ia5String::new(&self.client_name)?)],
+                }
+            } else {
+                // NT-PRINCIPAL, such as testuser or host/node1.example.com
+                principal_name_from_str(1, &self.client_name)?
+            }),
+            realm: KerberosString(Ia5String::new(&self.realm)?),
+            // NT-SRV-INST, such as krbtgt/REALM or kadmin/changepw
+            sname: Some(principal_name_from_str(2, &self.service_name)?),
+            from: self.from.map(KerberosTime::from_system_time).transpose()?,
+            till: KerberosTime::from_system_time(self.until)?,
+            rtime: self.renew.map(KerberosTime::from_system_time).transpose()?,
+            nonce: self.nonce,
+            etype: self.raw_etypes.clone(),
+            addresses: self
+                .addresses
+                .as_ref()
+                .map(|addresses| {
+                    addresses
+                        .iter()
+                        .map(KdcHostAddress::try_from)
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .transpose()?,
+            enc_authorization_data: None,
+            additional_tickets: None,
         })
     }
 }
 
-impl TryFrom<KdcRep> for KerberosAsRep {
+impl TryFrom<KdcReq> for KerberosAsReq {
     type Error = KrbError;
 
-    fn try_from(rep: KdcRep) -> Result<Self, Self::Error> {
+    fn try_from(req: KdcReq) -> Result<Self, Self::Error> {
         // assert the pvno and msg_type
-        if rep.pvno != 5 {
-            todo!();
+        if req.pvno != 5 {
+            return Err(KrbError::InvalidPvno(req.pvno));
         }
 
-        let msg_type = KrbMessageType::try_from(rep.msg_type).map_err(|_| {
+        let msg_type = KrbMessageType::try_from(req.msg_type).map_err(|_| {
             KrbError::InvalidEnumValue(
                 std::any::type_name::<KrbMessageType>().to_string(),
-                rep.msg_type as i32,
+                req.msg_type as i32,
             )
         })?;
 
         match msg_type {
-            KrbMessageType::KrbAsRep => {
-                let enc_part = EncryptedData::try_from(rep.enc_part)?;
-                trace!(?enc_part);
+            KrbMessageType::KrbAsReq => {
+                let cname = req.req_body.cname.ok_or(KrbError::MissingClientName)?;
+                let enterprise = cname.name_type == NT_ENTERPRISE;
+                let client_name: String = cname.into();
+                let service_name: String = req
+                    .req_body
+                    .sname
+                    .ok_or(KrbError::MissingServiceName)?
+                    .into();
+                let realm: String = req.req_body.realm.into();
 
-                let client_realm: String = rep.crealm.into();
-                let client_name: String = rep.cname.into();
+                let from = req.req_body.from.map(|t| t.to_system_time());
+                let until = req.req_body.till.to_system_time();
+                let renew = req.req_body.rtime.map(|t| t.to_system_time());
 
-                Ok(KerberosAsRep {
-                    client_realm,
+                // Etypes that aren't even known can't be picked, but are kept in the
+                // raw offer.
+                let raw_etypes = req.req_body.etype;
+                let etypes = raw_etypes
+                    .iter()
+                    .filter_map(|etype| EncryptionType::try_from(*etype).ok())
+                    .collect();
+
+                let pac_options = req
+                    .padata
+                    .iter()
+                    .flatten()
+                    .find(|padata| padata.padata_type == PaDataType::PaPacOptions as u32)
+                    .map(|padata| {
+                        PaPacOptions::from_der(padata.padata_value.as_bytes())
+                            .map_err(|_| KrbError::DerDecodePaPacOptions)
+                            .map(|pac_options| pac_options.flags.0)
+                    })
+                    .transpose()?;
+
+                let pk_as_req = req
+                    .padata
+                    .iter()
+                    .flatten()
+                    .find(|padata| padata.padata_type == PaDataType::PaPkAsReq as u32)
+                    .map(|padata| {
+                        PaPkAsReq::from_der(padata.padata_value.as_bytes())
+                            .map_err(|_| KrbError::DerDecodePaData)
+                    })
+                    .transpose()?;
+
+                let req_enc_pa_rep =
+                    req.padata.iter().flatten().any(|padata| {
+                        padata.padata_type == PaDataType::EncpadataReqEncPaRep as u32
+                    });
+
+                // The padata of an armored request can only be read by the KDC.
+                let fast = req
+                    .padata
+                    .iter()
+                    .flatten()
+                    .find(|padata| padata.padata_type == PaDataType::PaFxFast as u32)
+                    .map(|padata| {
+                        PaFxFastRequest::from_der(padata.padata_value.as_bytes())
+                            .map_err(|_| KrbError::DerDecodeFastReq)
+                            .map(|fast| fast.0)
+                    })
+                    .transpose()?;
+
+                // Everything that isn't held by a field of its own is kept in order,
+                // so that padata we don't understand is sent on unchanged.
+                let preauth = req
+                    .padata
+                    .map(|padata| {
+                        padata
+                            .into_iter()
+                            .filter(|padata| {
+                                !matches!(
+                                    PaDataType::try_from(padata.padata_type),
+                                    Ok(PaDataType::PaPacOptions
+                                        | PaDataType::PaPkAsReq
+                                        | PaDataType::PaFxFast
+                                        | PaDataType::EncpadataReqEncPaRep)
+                                )
+                            })
+                            .map(PreAuthEntry::try_from)
+                            .collect::<Result<Vec<_>, _>>()
+                            .map(|entries| PreAuth { entries })
+                    })
+                    .transpose()?;
+
+                let addresses = req
+                    .req_body
+                    .addresses
+                    .map(|addresses| {
+                        addresses
+                            .into_iter()
+                            .map(HostAddress::try_from)
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                    .transpose()?;
+
+                Ok(KerberosAsReq {
+                    nonce: req.req_body.nonce,
+                    kdc_options: req.req_body.kdc_options.0,
+                    addresses,
+                    enterprise,
+                    etypes,
+                    raw_etypes,
+                    pac_options,
+                    req_enc_pa_rep,
+                    fast,
+                    pk_as_req,
+                    #[cfg(feature = "pkinit")]
+                    pkinit: None,
                     client_name,
-                    enc_part,
+                    service_name,
+                    realm,
+                    from,
+                    until,
+                    renew,
+                    preauth,
                 })
             }
             _ => Err(KrbError::InvalidMessageType(
-                rep.msg_type as i32,
-                KrbMessageType::KrbAsRep as i32,
+                req.msg_type as i32,
+                KrbMessageType::KrbAsReq as i32,
             )),
         }
     }
 }
 
-impl TryFrom<KdcRep> for KerberosTgsRep {
-    type Error = KrbError;
+impl KerberosTgsReqBuilder {
+    /// Set the options requested of the KDC. By default no options are requested.
+    pub fn kdc_options(mut self, kdc_options: FlagSet<KdcOptions>) -> Self {
+        self.kdc_options = kdc_options;
+        self
+    }
 
-    fn try_from(rep: KdcRep) -> Result<Self, Self::Error> {
-        // assert the pvno and msg_type
-        if rep.pvno != 5 {
-            todo!();
+    /// Accept a renewable ticket when the requested lifetime can't be granted.
+    pub fn renewable_ok(self, renewable_ok: bool) -> Self {
+        self.set_kdc_option(KdcOptions::RenewableOk, renewable_ok)
+    }
+
+    /// Request a ticket that names the [ANONYMOUS_PRINCIPAL] as its client rather
+    /// than the client of the TGT (RFC 8062 4.2), hiding the identity of the client
+    /// from the service.
+    pub fn anonymous(self, anonymous: bool) -> Self {
+        self.set_kdc_option(KdcOptions::RequestAnonymous, anonymous)
+    }
+
+    /// Ask the KDC not to check the transited realms of a cross-realm path against
+    /// its policy.
+    pub fn disable_transited_check(self, disable_transited_check: bool) -> Self {
+        self.set_kdc_option(KdcOptions::DisableTransitedCheck, disable_transited_check)
+    }
+
+    fn set_kdc_option(mut self, option: KdcOptions, enabled: bool) -> Self {
+        if enabled {
+            self.kdc_options |= option;
+        } else {
+            self.kdc_options -= option;
         }
+        self
+    }
 
-        let msg_type = KrbMessageType::try_from(rep.msg_type).map_err(|_| {
-            KrbError::InvalidEnumValue(
-                std::any::type_name::<KrbMessageType>().to_string(),
-                rep.msg_type as i32,
+    /// Set when the ticket starts. A start time in the future requests a postdated
+    /// ticket, which the TGT must be allowed to issue, and which must be validated
+    /// once it starts.
+    pub fn from(mut self, from: Option<SystemTime>) -> Self {
+        self.from = from;
+        self
+    }
+
+    /// Set until when a renewable ticket may be renewed.
+    pub fn renew(mut self, renew: Option<SystemTime>) -> Self {
+        self.renew = renew;
+        self
+    }
+
+    /// Add a ticket to the additional-tickets of the request. For user-to-user
+    /// authentication this is the TGT of the peer, and [KdcOptions::EncTktInSkey]
+    /// must also be set so that the KDC encrypts the ticket in its session key.
+    pub fn additional_ticket(mut self, ticket: Ticket) -> Self {
+        self.additional_tickets.push(ticket);
+        self
+    }
+
+    /// Request the ticket on behalf of `user_name` in `user_realm` with S4U2Self
+    /// (protocol transition). The service name of the request must be the client
+    /// of the TGT, as the ticket is issued to the service itself.
+    pub fn s4u2self(mut self, user_name: String, user_realm: String) -> Self {
+        self.for_user = Some((user_name, user_realm));
+        self
+    }
+
+    /// Request a ticket to `service_name` in the name of the client of
+    /// `evidence_ticket` with S4U2Proxy (constrained delegation). The evidence is a
+    /// forwardable ticket to this service, from S4U2Self or presented by the user.
+    pub fn delegate(mut self, evidence_ticket: Ticket, service_name: String) -> Self {
+        self.service_name = service_name;
+        self.evidence_ticket = Some(evidence_ticket);
+        self
+    }
+
+    /// Set or clear a PAC option, which is sent as PA-PAC-OPTIONS. This is only
+    /// understood by Active Directory. Resource-based constrained delegation is
+    /// always requested with [Self::delegate].
+    pub fn pac_option(mut self, option: PacOptions, enabled: bool) -> Self {
+        if enabled {
+            self.pac_options |= option;
+        } else {
+            self.pac_options -= option;
+        }
+        self
+    }
+
+    /// Move the ctime of the authenticator by `clock_offset`, so that the KDC
+    /// accepts it when our clock is off.
+    pub fn clock_offset(mut self, clock_offset: ClockOffset) -> Self {
+        self.clock_offset = clock_offset;
+        self
+    }
+
+    /// Send `subkey` in the authenticator, so that the KDC encrypts its reply in it
+    /// rather than in the session key of the TGT. The reply is then decrypted with
+    /// [KerberosTgsRep::into_authenticated_reply_with_subkey]. A fresh subkey is made
+    /// with [BaseKey::generate_session_key].
+    pub fn subkey(mut self, subkey: Option<BaseKey>) -> Self {
+        self.subkey = subkey;
+        self
+    }
+
+    /// Build the request, authenticating it with the TGT and session key from a
+    /// previous AS exchange.
+    pub fn build(self, tgt: &AuthenticatedReply) -> Result<KerberosRequest, KrbError> {
+        let KerberosTgsReqBuilder {
+            service_name,
+            from,
+            until,
+            renew,
+            kdc_options,
+            additional_tickets,
+            for_user,
+            evidence_ticket,
+            pac_options,
+            clock_offset,
+            subkey,
+        } = self;
+
+        // MS-SFU 3.1.5.2.1 The evidence ticket is the first of the additional tickets,
+        // and cname-in-addl-tkt asks the KDC to issue the ticket in the name of its
+        // client. Resource-based delegation is requested too, as the KDC decides
+        // which kind of delegation applies.
+        let (kdc_options, additional_tickets, pac_options) = match evidence_ticket {
+            Some(evidence_ticket) => (
+                kdc_options | KdcOptions::ConstrainedDelegation,
+                std::iter::once(evidence_ticket)
+                    .chain(additional_tickets)
+                    .collect(),
+                pac_options | PacOptions::ResourceBasedConstrainedDelegation,
+            ),
+            None => (kdc_options, additional_tickets, pac_options),
+        };
+        let pac_options = (!pac_options.is_empty()).then_some(pac_options);
+
+        // RFC 4120 3.3.3 A postdated ticket is requested with a TGT that may postdate.
+        check_start_time(from, until)?;
+        let kdc_options = with_postdate_options(kdc_options, from, KdcOptions::Postdated.into())?;
+
+        let nonce = request_nonce(None);
+
+        // The realm of the req-body is that of the service. Unless the service name
+        // says otherwise, the service is in the realm of the TGS that our TGT is for,
+        // which is not the realm that issued the TGT when it is a cross-realm TGT.
+        let (service_name, realm) = match split_principal(&service_name) {
+            (name, Some(realm)) => (name.to_string(), realm.to_string()),
+            (_, None) => {
+                let realm =
+                    tgs_realm(&tgt.ticket).unwrap_or_else(|| tgt.ticket.0.realm.clone().into());
+                (service_name, realm)
+            }
+        };
+
+        check_times(from, until, renew)?;
+        check_principal_name(&service_name)?;
+        check_name_component(&realm)?;
+
+        let body = TgsReqBody {
+            nonce,
+            kdc_options,
+            service_name,
+            realm,
+            from,
+            until,
+            renew,
+            etypes: vec![
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                EncryptionType::AES128_CTS_HMAC_SHA1_96,
+            ],
+            additional_tickets,
+        };
+
+        let req_body = body
+            .to_asn()
+            .and_then(|req_body| req_body.to_der())
+            .map_err(|_| KrbError::DerEncodeKdcReqBody)?;
+
+        // RFC 4120 The authenticator checksum over the req-body is keyed with the
+        // session key with key usage 6, and the authenticator itself is encrypted
+        // with key usage 7.
+        let cksum = tgt
+            .session_key
+            .checksum(&req_body, KeyUsage::TGS_REQ_PA_TGS_REQ_CKSUM)?;
+        let ap_req = tgt.build_ap_req(
+            clock_offset.now(),
+            None,
+            Some(cksum),
+            subkey.as_ref(),
+            KeyUsage::TGS_REQ_PA_TGS_REQ_AUTHENTICATOR,
+        )?;
+
+        // The PA-FOR-USER is bound to the session key of the TGT, not to the req-body.
+        let for_user = for_user
+            .map(|(user_name, user_realm)| PaForUser::new(&user_name, user_realm, &tgt.session_key))
+            .transpose()?;
+
+        Ok(KerberosRequest::TgsReq(KerberosTgsReq {
+            body,
+            ap_req,
+            req_body,
+            for_user,
+            pac_options,
+        }))
+    }
+}
+
+impl TgsReqBody {
+    pub(crate) fn to_asn(&self) -> Result<KdcReqBody, der::Error> {
+        let additional_tickets = if self.additional_tickets.is_empty() {
+            None
+        } else {
+            Some(
+                self.additional_tickets
+                    .iter()
+                    .map(|ticket| ticket.0.clone())
+                    .collect(),
             )
-        })?;
+        };
 
-        match msg_type {
-            KrbMessageType::KrbTgsRep => Ok(KerberosTgsRep {}),
-            _ => Err(KrbError::InvalidMessageType(
-                rep.msg_type as i32,
-                KrbMessageType::KrbTgsRep as i32,
-            )),
+        Ok(KdcReqBody {
+            kdc_options: KerberosFlags(self.kdc_options),
+            // The client is identified by the authenticator, not the req-body.
+            cname: None,
+            realm: KerberosString(Ia5String::new(&self.realm)?),
+            sname: Some(principal_name_from_str(2, &self.service_name)?),
+            from: self.from.map(KerberosTime::from_system_time).transpose()?,
+            till: KerberosTime::from_system_time(self.until)?,
+            rtime: self.renew.map(KerberosTime::from_system_time).transpose()?,
+            nonce: self.nonce,
+            etype: self.etypes.iter().map(|etype| *etype as i32).collect(),
+            addresses: None,
+            enc_authorization_data: None,
+            additional_tickets,
+        })
+    }
+}
+
+impl KerberosTgsReq {
+    /// The options that the client requested of the KDC.
+    pub fn kdc_options(&self) -> FlagSet<KdcOptions> {
+        self.body.kdc_options
+    }
+
+    /// Whether the client accepts a renewable ticket in place of the lifetime it
+    /// requested.
+    pub fn renewable_ok(&self) -> bool {
+        self.body.kdc_options.contains(KdcOptions::RenewableOk)
+    }
+
+    /// Whether the client asked for the transited realms not to be checked, which
+    /// the KDC may refuse by policy.
+    pub fn disable_transited_check(&self) -> bool {
+        self.body
+            .kdc_options
+            .contains(KdcOptions::DisableTransitedCheck)
+    }
+
+    /// Whether the client requested an anonymous ticket.
+    pub fn anonymous(&self) -> bool {
+        self.body.kdc_options.contains(KdcOptions::RequestAnonymous)
+    }
+
+    /// The additional tickets sent with the request, such as the TGT of the peer
+    /// in user-to-user authentication.
+    pub fn additional_tickets(&self) -> &[Ticket] {
+        &self.body.additional_tickets
+    }
+
+    /// The user named by an S4U2Self request. A KDC must [verify](PaForUser::verify)
+    /// it before issuing a ticket on their behalf.
+    pub fn for_user(&self) -> Option<&PaForUser> {
+        self.for_user.as_ref()
+    }
+
+    /// The evidence ticket of an S4U2Proxy request. The KDC issues the ticket in the
+    /// name of its client, if its delegation policy allows the service to do so.
+    pub fn evidence_ticket(&self) -> Option<&Ticket> {
+        if self
+            .body
+            .kdc_options
+            .contains(KdcOptions::ConstrainedDelegation)
+        {
+            self.body.additional_tickets.first()
+        } else {
+            None
+        }
+    }
+
+    /// The PAC options requested by the client, if any.
+    pub fn pac_options(&self) -> Option<FlagSet<PacOptions>> {
+        self.pac_options
+    }
+
+    /// The TGT that the client presented in the PA-TGS-REQ. The KDC decrypts it to
+    /// find the session key that [Self::verify_authenticator] needs.
+    pub fn ticket(&self) -> Ticket {
+        Ticket(self.ap_req.0.ticket.clone())
+    }
+
+    /// Decrypt the authenticator of the PA-TGS-REQ with the `session_key` of the
+    /// presented TGT, and check that its checksum covers the req-body as it was
+    /// sent. The KDC must still check that the client matches the TGT, and that
+    /// the ctime is within the allowed clock skew.
+    pub fn verify_authenticator(
+        &self,
+        session_key: &BaseKey,
+    ) -> Result<TgsAuthenticator, KrbError> {
+        let authenticator = EncryptedData::try_from(self.ap_req.0.authenticator.clone())?
+            .decrypt_authenticator(session_key, KeyUsage::TGS_REQ_PA_TGS_REQ_AUTHENTICATOR)?;
+
+        // RFC 4120 5.5.1 The checksum is required in the authenticator of a
+        // PA-TGS-REQ.
+        let cksum = authenticator
+            .cksum
+            .ok_or(KrbError::MissingAuthenticatorChecksum)?;
+
+        cksum.verify(
+            session_key,
+            KeyUsage::TGS_REQ_PA_TGS_REQ_CKSUM,
+            &self.req_body,
+        )?;
+
+        let ctime = authenticator.ctime.to_system_time()
+            + Duration::from_micros(authenticator.cusec.into());
+
+        Ok(TgsAuthenticator {
+            client_realm: authenticator.crealm.into(),
+            client_name: authenticator.cname.into(),
+            ctime,
+            subkey: authenticator.subkey.map(BaseKey::try_from).transpose()?,
+        })
+    }
+
+    fn to_asn(&self) -> Result<KdcReq, der::Error> {
+        let mut padata = vec![PaData {
+            padata_type: PaDataType::PaTgsReq as u32,
+            padata_value: OctetString::new(self.ap_req.to_der()?)?,
+        }];
+
+        if let Some(for_user) = &self.for_user {
+            padata.push(PaData {
+                padata_type: PaDataType::PaForUser as u32,
+                padata_value: OctetString::new(for_user.to_asn()?.to_der()?)?,
+            });
         }
+
+        if let Some(pac_options) = self.pac_options {
+            let pac_options = PaPacOptions {
+                flags: KerberosFlags(pac_options),
+            };
+            padata.push(PaData {
+                padata_type: PaDataType::PaPacOptions as u32,
+                padata_value: OctetString::new(pac_options.to_der()?)?,
+            });
+        }
+
+        Ok(KdcReq {
+            pvno: 5,
+            msg_type: KrbMessageType::KrbTgsReq as u8,
+            padata: Some(padata),
+            req_body: self.body.to_asn()?,
+        })
     }
 }
 
-impl TryFrom<crate::asn1::krb_error::KrbError> for KerberosErrRep {
+impl TryFrom<KdcReq> for KerberosTgsReq {
     type Error = KrbError;
 
-    fn try_from(rep: crate::asn1::krb_error::KrbError) -> Result<Self, Self::Error> {
+    fn try_from(req: KdcReq) -> Result<Self, Self::Error> {
         // assert the pvno and msg_type
-        if rep.pvno != 5 {
-            todo!();
+        if req.pvno != 5 {
+            return Err(KrbError::InvalidPvno(req.pvno));
         }
 
-        let msg_type = KrbMessageType::try_from(rep.msg_type).map_err(|_| {
+        let msg_type = KrbMessageType::try_from(req.msg_type).map_err(|_| {
             KrbError::InvalidEnumValue(
                 std::any::type_name::<KrbMessageType>().to_string(),
-                rep.msg_type as i32,
+                req.msg_type as i32,
             )
         })?;
 
         match msg_type {
-            KrbMessageType::KrbError => {
-                let error_code = KrbErrorCode::try_from(rep.error_code).map_err(|_| {
-                    KrbError::InvalidEnumValue(
-                        std::any::type_name::<KrbErrorCode>().to_string(),
-                        rep.error_code,
-                    )
-                })?;
+            KrbMessageType::KrbTgsReq => {
+                // The req-body is kept as it was received, as DER has only one
+                // encoding of it.
+                let req_body = req
+                    .req_body
+                    .to_der()
+                    .map_err(|_| KrbError::DerEncodeKdcReqBody)?;
 
-                let rep = match error_code {
-                    KrbErrorCode::KdcErrPreauthRequired => {
-                        let edata = rep.error_data.ok_or(KrbError::MissingPaData)?;
+                let service_name: String = req
+                    .req_body
+                    .sname
+                    .ok_or(KrbError::MissingServiceName)?
+                    .into();
+                let realm: String = req.req_body.realm.into();
 
-                        let pavec: Vec<PaData> = MethodData::from_der(edata.as_bytes())
-                            .map_err(|_| KrbError::DerDecodePaData)?;
+                let from = req.req_body.from.map(|t| t.to_system_time());
+                let until = req.req_body.till.to_system_time();
+                let renew = req.req_body.rtime.map(|t| t.to_system_time());
 
-                        let pa_rep = KerberosPaRep::try_from(pavec)?;
-                        KerberosErrRep::Pa(pa_rep)
-                    }
-                    err_code => KerberosErrRep::Err(err_code),
-                };
+                let etypes = req
+                    .req_body
+                    .etype
+                    .iter()
+                    .filter_map(|etype| EncryptionType::try_from(*etype).ok())
+                    .collect();
 
-                Ok(rep)
+                let additional_tickets = req
+                    .req_body
+                    .additional_tickets
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Ticket)
+                    .collect();
+
+                let padata = req.padata.unwrap_or_default();
+
+                let ap_req = padata
+                    .iter()
+                    .find(|padata| padata.padata_type == PaDataType::PaTgsReq as u32)
+                    .ok_or(KrbError::MissingPaData)
+                    .and_then(|padata| {
+                        TaggedApReq::from_der(padata.padata_value.as_bytes())
+                            .map_err(|_| KrbError::DerDecodeApReq)
+                    })?;
+
+                let for_user = padata
+                    .iter()
+                    .find(|padata| padata.padata_type == PaDataType::PaForUser as u32)
+                    .map(|padata| {
+                        KdcPaForUser::from_der(padata.padata_value.as_bytes())
+                            .map_err(|_| KrbError::DerDecodePaForUser)
+                            .map(PaForUser::from)
+                    })
+                    .transpose()?;
+
+                let pac_options = padata
+                    .iter()
+                    .find(|padata| padata.padata_type == PaDataType::PaPacOptions as u32)
+                    .map(|padata| {
+                        PaPacOptions::from_der(padata.padata_value.as_bytes())
+                            .map_err(|_| KrbError::DerDecodePaPacOptions)
+                            .map(|pac_options| pac_options.flags.0)
+                    })
+                    .transpose()?;
+
+                Ok(KerberosTgsReq {
+                    body: TgsReqBody {
+                        nonce: req.req_body.nonce,
+                        kdc_options: req.req_body.kdc_options.0,
+                        service_name,
+                        realm,
+                        from,
+                        until,
+                        renew,
+                        etypes,
+                        additional_tickets,
+                    },
+                    ap_req,
+                    req_body,
+                    for_user,
+                    pac_options,
+                })
             }
             _ => Err(KrbError::InvalidMessageType(
-                rep.msg_type as i32,
-                KrbMessageType::KrbError as i32,
+                req.msg_type as i32,
+                KrbMessageType::KrbTgsReq as i32,
             )),
         }
     }
 }
 
-impl TryFrom<Vec<PaData>> for KerberosPaRep {
-    type Error = KrbError;
-
-    fn try_from(pavec: Vec<PaData>) -> Result<Self, Self::Error> {
-        // Per https://www.rfc-editor.org/rfc/rfc4120#section-7.5.2
-        // Build up the set of PaRep data
-        let mut pa_fx_fast = false;
-        let mut enc_timestamp = false;
-        let mut pa_fx_cookie = None;
-        let mut etype_info2 = Vec::with_capacity(0);
-
-        for PaData {
-            padata_type,
-            padata_value,
-        } in pavec
-        {
-            let Ok(padt) = padata_type.try_into() else {
-                // padatatype that we don't support
-                continue;
-            };
+// MS-SFU 2.2.1 The auth-package of PA-FOR-USER, which is always "Kerberos".
+const PA_FOR_USER_AUTH_PACKAGE: &str = "Kerberos";
 
-            match padt {
-                PaDataType::PaEncTimestamp => enc_timestamp = true,
-                PaDataType::PaEtypeInfo2 => {
-                    // this is a sequence of etypeinfo2
-                    let einfo2_sequence = KdcETypeInfo2::from_der(padata_value.as_bytes())
-                        .map_err(|_| KrbError::DerDecodeEtypeInfo2)?;
+impl PaForUser {
+    fn new(user_name: &str, user_realm: String, session_key: &BaseKey) -> Result<Self, KrbError> {
+        let user_name =
+            principal_name_from_str(1, user_name).map_err(|_| KrbError::DerEncodePaForUser)?;
 
-                    for einfo2 in einfo2_sequence {
-                        let Ok(etype) = EncryptionType::try_from(einfo2.etype) else {
-                            // Invalid etype or we don't support it.
-                            continue;
-                        };
+        let data = pa_for_user_checksum_data(&user_name, &user_realm, PA_FOR_USER_AUTH_PACKAGE);
+        // MS-SFU 2.2.1 The checksum is keyed with the TGT session key.
+        let cksum = Checksum::compute(
+            ChecksumType::HMAC_MD5,
+            session_key,
+            KeyUsage::KERB_NON_KERB_CKSUM_SALT,
+            &data,
+        )?;
 
-                        // Only proceed with what we support.
-                        match etype {
-                            EncryptionType::AES256_CTS_HMAC_SHA1_96 => {}
-                            _ => continue,
-                        };
+        Ok(PaForUser {
+            user_name,
+            user_realm,
+            cksum,
+            auth_package: PA_FOR_USER_AUTH_PACKAGE.to_string(),
+        })
+    }
 
-                        // I think at this point we should ignore any etypes we don't support.
+    pub fn user_name(&self) -> String {
+        self.user_name.clone().into()
+    }
 
-                        let salt = einfo2.salt.map(|s| s.into());
-                        let s2kparams = einfo2.s2kparams.map(|v| v.as_bytes().to_vec());
+    pub fn user_realm(&self) -> &str {
+        &self.user_realm
+    }
 
-                        etype_info2.push(EtypeInfo2 {
-                            etype,
-                            salt,
-                            s2kparams,
-                        });
-                    }
-                }
-                PaDataType::PaFxFast => pa_fx_fast = true,
-                PaDataType::PaFxCookie => pa_fx_cookie = Some(padata_value.as_bytes().to_vec()),
-                _ => {
-                    // Ignore unsupported pa data types.
-                }
-            };
+    /// Verify the checksum of the PA-FOR-USER with the session key of the TGT that
+    /// authenticated the request.
+    pub fn verify(&self, session_key: &BaseKey) -> Result<(), KrbError> {
+        if !self
+            .auth_package
+            .eq_ignore_ascii_case(PA_FOR_USER_AUTH_PACKAGE)
+        {
+            return Err(KrbError::InvalidAuthPackage(self.auth_package.clone()));
         }
 
-        // Sort the etype_info by cryptographic strength.
-        etype_info2.sort_unstable_by(sort_cryptographic_strength);
+        let data = pa_for_user_checksum_data(&self.user_name, &self.user_realm, &self.auth_package);
+        self.cksum
+            .verify_hmac_md5(session_key, KeyUsage::KERB_NON_KERB_CKSUM_SALT, &data)
+    }
 
-        Ok(KerberosPaRep {
-            pa_fx_fast,
-            pa_fx_cookie,
-            enc_timestamp,
-            etype_info2,
+    fn to_asn(&self) -> Result<KdcPaForUser, der::Error> {
+        Ok(KdcPaForUser {
+            user_name: self.user_name.clone(),
+            user_realm: KerberosString(Ia5String::new(&self.user_realm)?),
+            cksum: self.cksum.clone(),
+            auth_package: KerberosString(Ia5String::new(&self.auth_package)?),
         })
     }
 }
 
-impl EncryptedData {
-    pub fn derive_key(
-        &self,
-        passphrase: &[u8],
-        realm: &[u8],
-        cname: &[u8],
-    ) -> Result<BaseKey, KrbError> {
-        match self {
-            EncryptedData::Aes256CtsHmacSha196 { .. } => {
-                // todo! there is some way to get a number of rounds here
-                // but I can't obviously see it?
-                let iter_count = None;
-                derive_key_aes256_cts_hmac_sha1_96(passphrase, realm, cname, iter_count)
-                    .map(|k| BaseKey::Aes256 { k })
-            }
-        }
+impl TgsAuthenticator {
+    pub fn client_realm(&self) -> &str {
+        &self.client_realm
     }
 
-    pub fn decrypt_data(&self, base_key: &BaseKey, key_usage: i32) -> Result<Vec<u8>, KrbError> {
-        match (self, base_key) {
-            (EncryptedData::Aes256CtsHmacSha196 { kvno: _, data }, BaseKey::Aes256 { k }) => {
-                decrypt_aes256_cts_hmac_sha1_96(&k, &data, key_usage)
-            }
-        }
+    pub fn client_name(&self) -> &str {
+        &self.client_name
+    }
+
+    /// When the client built the request, to the microsecond.
+    pub fn ctime(&self) -> SystemTime {
+        self.ctime
+    }
+
+    /// The subkey that the client asked the KDC to encrypt the reply in, in place of
+    /// the session key of the TGT.
+    pub fn subkey(&self) -> Option<&BaseKey> {
+        self.subkey.as_ref()
     }
 }
 
-impl TryFrom<KdcEncryptedData> for EncryptedData {
-    type Error = KrbError;
+impl ApReply {
+    /// The ctime of the authenticator that the service replies to, to the
+    /// microsecond. This must be that of the AP-REQ that was sent.
+    pub fn ctime(&self) -> SystemTime {
+        self.ctime
+    }
 
-    fn try_from(enc_data: KdcEncryptedData) -> Result<Self, Self::Error> {
-        let etype: EncryptionType = EncryptionType::try_from(enc_data.etype)
-            .map_err(|_| KrbError::UnsupportedEncryption)?;
-        match etype {
-            EncryptionType::AES256_CTS_HMAC_SHA1_96 => {
-                // todo! there is some way to get a number of rounds here
-                // but I can't obviously see it?
-                let kvno = enc_data.kvno;
-                let data = enc_data.cipher.into_bytes();
-                Ok(EncryptedData::Aes256CtsHmacSha196 { kvno, data })
-            }
-            _ => Err(KrbError::UnsupportedEncryption),
-        }
+    /// The subkey of the service, which replaces the subkey of the authenticator
+    /// for the messages that follow.
+    pub fn subkey(&self) -> Option<&BaseKey> {
+        self.subkey.as_ref()
+    }
+
+    /// The initial sequence number of the messages from the service.
+    pub fn seq_number(&self) -> Option<u32> {
+        self.seq_number
     }
 }
 
-impl KerberosPaRep {
-    pub fn perform_enc_timestamp(
-        &self,
-        passphrase: &str,
-        realm: &str,
-        cname: &str,
-        epoch_seconds: Duration,
-    ) -> Result<PreAuth, KrbError> {
-        // Major TODO: Can we actually use a reasonable amount of iterations?
-        if !self.enc_timestamp {
-            return Err(KrbError::PreAuthUnsupported);
+impl From<KdcPaForUser> for PaForUser {
+    fn from(for_user: KdcPaForUser) -> Self {
+        PaForUser {
+            user_name: for_user.user_name,
+            user_realm: for_user.user_realm.into(),
+            cksum: for_user.cksum,
+            auth_package: for_user.auth_package.into(),
         }
+    }
+}
 
-        // This gets the highest encryption strength item.
-        let Some(einfo2) = self.etype_info2.last() else {
-            return Err(KrbError::PreAuthMissingEtypeInfo2);
-        };
+/// MS-SFU 2.2.1 The checksum covers the name-type as a little endian 32-bit integer,
+/// each component of the name, the realm and the auth-package, without separators.
+fn pa_for_user_checksum_data(
+    user_name: &PrincipalName,
+    user_realm: &str,
+    auth_package: &str,
+) -> Vec<u8> {
+    let mut data = user_name.name_type.to_le_bytes().to_vec();
+    for component in user_name.name_string.iter() {
+        data.extend_from_slice(component.as_str().as_bytes());
+    }
+    data.extend_from_slice(user_realm.as_bytes());
+    data.extend_from_slice(auth_package.as_bytes());
+    data
+}
 
-        // https://www.rfc-editor.org/rfc/rfc4120#section-5.2.7.2
-        let key_usage = 1;
+// RFC 6113 5.4.1.1 The armor is an AP-REQ.
+const FX_FAST_ARMOR_AP_REQUEST: i32 = 1;
 
-        let patimestamp = KerberosTime::from_unix_duration(epoch_seconds)
-            .map_err(|_| KrbError::PreAuthInvalidUnixTs)?;
+impl FastArmor {
+    /// Armor with `tgt`, which the KDC must be able to decrypt. The `subkey` is a
+    /// fresh random key that is sent in the authenticator, and which the armor key
+    /// is derived from together with the session key of the TGT.
+    pub fn new(tgt: &AuthenticatedReply, subkey: BaseKey) -> Result<Self, KrbError> {
+        let ap_req = tgt
+            .build_ap_req(
+                SystemTime::now(),
+                None,
+                None,
+                Some(&subkey),
+                KeyUsage::AP_REQ_AUTHENTICATOR,
+            )?
+            .to_der()
+            .map_err(|_| KrbError::DerEncodeApReq)?;
 
-        let paenctsenc = PaEncTsEnc {
-            patimestamp,
-            pausec: None,
+        let armor = KrbFastArmor {
+            armor_type: FX_FAST_ARMOR_AP_REQUEST,
+            armor_value: OctetString::new(ap_req).map_err(|_| KrbError::DerEncodeApReq)?,
         };
 
-        eprintln!("{:?}", paenctsenc);
+        // RFC 6113 5.4.1.1
+        let armor_key = subkey.krb_fx_cf2(&tgt.session_key, "subkeyarmor", "ticketarmor")?;
 
-        let data = paenctsenc
-            .to_der()
-            .map_err(|_| KrbError::DerEncodePaEncTsEnc)?;
+        Ok(FastArmor { armor, armor_key })
+    }
 
-        let enc_timestamp = match einfo2.etype {
-            EncryptionType::AES256_CTS_HMAC_SHA1_96 => {
-                let iter_count = if let Some(s2kparams) = &einfo2.s2kparams {
-                    if s2kparams.len() != 4 {
-                        return Err(KrbError::PreAuthInvalidS2KParams);
-                    };
-                    let mut iter_count = [0u8; 4];
-                    iter_count.copy_from_slice(&s2kparams);
-
-                    Some(u32::from_be_bytes(iter_count))
-                } else {
-                    None
-                };
+    /// The key that armors the exchange. An OTP exchange has no long-term key of the
+    /// client, so the KDC encrypts its reply in this key instead.
+    pub fn armor_key(&self) -> &BaseKey {
+        &self.armor_key
+    }
 
-                let base_key = if let Some(external_salt) = &einfo2.salt {
-                    derive_key_external_salt_aes256_cts_hmac_sha1_96(
-                        passphrase.as_bytes(),
-                        external_salt.as_bytes(),
-                        iter_count,
-                    )?
-                } else {
-                    derive_key_aes256_cts_hmac_sha1_96(
-                        passphrase.as_bytes(),
-                        realm.as_bytes(),
-                        cname.as_bytes(),
-                        iter_count,
-                    )?
-                };
+    /// Wrap the padata and req-body of a request into the PA-FX-FAST. The checksum
+    /// binds the armor to the req-body that is sent in the clear.
+    fn armor_request(
+        &self,
+        req_body: KdcReqBody,
+        padata: Vec<PaData>,
+    ) -> Result<KrbFastArmoredReq, KrbError> {
+        let outer_req_body = req_body
+            .to_der()
+            .map_err(|_| KrbError::DerEncodeKdcReqBody)?;
+        let req_checksum = self
+            .armor_key
+            .checksum(&outer_req_body, KeyUsage::FAST_REQ_CHKSUM)?;
+
+        let fast_req = KrbFastReq {
+            fast_options: KerberosFlags(FlagSet::<FastOptions>::default()),
+            padata,
+            req_body,
+        }
+        .to_der()
+        .map_err(|_| KrbError::DerEncodeFastReq)?;
+
+        let enc_fast_req = self.armor_key.encrypt_data(&fast_req, KeyUsage::FAST_ENC)?;
+        let enc_fast_req =
+            KdcEncryptedData::try_from(&enc_fast_req).map_err(|_| KrbError::DerEncodeFastReq)?;
+
+        Ok(KrbFastArmoredReq {
+            armor: Some(self.armor.clone()),
+            req_checksum,
+            enc_fast_req,
+        })
+    }
+
+    /// Decrypt the KrbFastResponse of a reply to a request with `nonce`.
+    fn unarmor_reply(
+        &self,
+        enc_fast_rep: &EncryptedData,
+        nonce: u32,
+    ) -> Result<KrbFastResponse, KrbError> {
+        let fast_rep = enc_fast_rep.decrypt_data(&self.armor_key, KeyUsage::FAST_REP)?;
+
+        let fast_rep =
+            KrbFastResponse::from_der(&fast_rep).map_err(|_| KrbError::DerDecodeFastRep)?;
+
+        if fast_rep.nonce != nonce {
+            return Err(KrbError::FastNonceMismatch);
+        }
+
+        Ok(fast_rep)
+    }
+}
+
+impl ClockOffset {
+    /// The offset of `kdc_time` from `local_time`, which were read at the same
+    /// moment.
+    pub fn new(kdc_time: SystemTime, local_time: SystemTime) -> Self {
+        match kdc_time.duration_since(local_time) {
+            Ok(offset) => ClockOffset {
+                behind: false,
+                offset,
+            },
+            Err(err) => ClockOffset {
+                behind: true,
+                offset: err.duration(),
+            },
+        }
+    }
+
+    /// The time on the clock of the KDC when ours reads `time`.
+    pub fn apply(&self, time: SystemTime) -> SystemTime {
+        let kdc_time = if self.behind {
+            time.checked_sub(self.offset)
+        } else {
+            time.checked_add(self.offset)
+        };
+        // An offset that doesn't fit isn't one that any KDC could have given us.
+        kdc_time.unwrap_or(time)
+    }
+
+    /// The time now on the clock of the KDC.
+    pub fn now(&self) -> SystemTime {
+        self.apply(SystemTime::now())
+    }
+}
+
+impl From<PaOtpChallenge> for OtpChallenge {
+    fn from(challenge: PaOtpChallenge) -> Self {
+        OtpChallenge {
+            nonce: challenge.nonce.into_bytes(),
+            service: challenge.otp_service,
+            tokens: challenge.otp_token_info.into_iter().map(OtpToken).collect(),
+        }
+    }
+}
+
+impl OtpChallenge {
+    /// The name of the OTP service, to show to the user when asking for the OTP.
+    pub fn service(&self) -> Option<&str> {
+        self.service.as_deref()
+    }
+
+    /// The tokens that the client may authenticate with, of which there is at least
+    /// one.
+    pub fn tokens(&self) -> &[OtpToken] {
+        &self.tokens
+    }
+}
+
+impl OtpToken {
+    pub fn flags(&self) -> FlagSet<OtpFlags> {
+        self.0.flags.0
+    }
+
+    pub fn vendor(&self) -> Option<&str> {
+        self.0.otp_vendor.as_deref()
+    }
+
+    /// A challenge to enter into the token, for tokens that compute the OTP from one.
+    pub fn challenge(&self) -> Option<&[u8]> {
+        self.0
+            .otp_challenge
+            .as_ref()
+            .map(|challenge| challenge.as_bytes())
+    }
+
+    /// The number of digits or characters of the OTP.
+    pub fn length(&self) -> Option<i32> {
+        self.0.otp_length
+    }
+
+    pub fn token_id(&self) -> Option<&[u8]> {
+        self.0.otp_token_id.as_ref().map(|id| id.as_bytes())
+    }
+}
+
+impl Ticket {
+    pub fn from_der(der: &[u8]) -> Result<Self, KrbError> {
+        TaggedTicket::from_der(der)
+            .map(Ticket)
+            .map_err(|_| KrbError::DerDecodeTicket)
+    }
+
+    pub fn to_der(&self) -> Result<Vec<u8>, KrbError> {
+        self.0.to_der().map_err(|_| KrbError::DerEncodeTicket)
+    }
+
+    /// The realm of the service that the ticket was issued for.
+    pub fn service_realm(&self) -> String {
+        self.0 .0.realm.clone().into()
+    }
+
+    pub fn service_name(&self) -> String {
+        self.0 .0.sname.clone().into()
+    }
+
+    /// If this is a TGT, the realm of the TGS that it is for. For a cross-realm TGT
+    /// `krbtgt/REMOTE@LOCAL` this is `REMOTE`, while it was issued by `LOCAL`.
+    pub fn tgs_realm(&self) -> Option<String> {
+        tgs_realm(&self.0)
+    }
+
+    /// Decrypt the enc-part of this ticket with the long-term key of the service it
+    /// was issued for, as a service or the TGS does when a client presents it. The
+    /// version of that key must match the kvno of the ticket when the KDC sent one.
+    pub fn decrypt(&self, service_key: &KerberosKey) -> Result<EncTicketPart, KrbError> {
+        let enc_part = EncryptedData::try_from(self.0 .0.enc_part.clone())?
+            .decrypt_enc_ticket_part(service_key)?;
+
+        let client_addresses = enc_part
+            .cadr
+            .map(|addresses| {
+                addresses
+                    .into_iter()
+                    .map(HostAddress::try_from)
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+
+        Ok(EncTicketPart {
+            flags: enc_part.flags.0,
+            session_key: BaseKey::try_from(enc_part.key)?,
+            client_realm: enc_part.crealm.into(),
+            client_name: enc_part.cname.into(),
+            transited: (
+                enc_part.transited.tr_type,
+                enc_part.transited.contents.into_bytes(),
+            ),
+            auth_time: enc_part.authtime.to_system_time(),
+            start_time: enc_part.starttime.map(|t| t.to_system_time()),
+            end_time: enc_part.endtime.to_system_time(),
+            renew_till: enc_part.till.map(|t| t.to_system_time()),
+            client_addresses,
+            authorization_data: enc_part
+                .authorization_data
+                .unwrap_or_default()
+                .into_iter()
+                .map(|ad| (ad.ad_type, ad.ad_data.into_bytes()))
+                .collect(),
+        })
+    }
+}
+
+impl EncTicketPart {
+    /// The flags that the ticket was issued with.
+    pub fn flags(&self) -> FlagSet<TicketFlags> {
+        self.flags
+    }
+
+    /// The session key that the client shares with the service.
+    pub fn session_key(&self) -> &BaseKey {
+        &self.session_key
+    }
+
+    /// The realm of the client that the ticket was issued to.
+    pub fn client_realm(&self) -> &str {
+        &self.client_realm
+    }
+
+    /// The name of the client that the ticket was issued to.
+    pub fn client_name(&self) -> &str {
+        &self.client_name
+    }
+
+    /// The tr-type and contents of the realms that were transited to issue the
+    /// ticket, as described by RFC 4120 3.3.3.2.
+    pub fn transited(&self) -> (i32, &[u8]) {
+        (self.transited.0, &self.transited.1)
+    }
+
+    /// The time of the initial authentication that the ticket derives from.
+    pub fn auth_time(&self) -> SystemTime {
+        self.auth_time
+    }
+
+    /// The time that the ticket is valid from. This is the authtime when the KDC
+    /// didn't send a starttime.
+    pub fn start_time(&self) -> SystemTime {
+        self.start_time.unwrap_or(self.auth_time)
+    }
+
+    /// The time that the ticket expires.
+    pub fn end_time(&self) -> SystemTime {
+        self.end_time
+    }
+
+    /// The time that a renewable ticket may be renewed until.
+    pub fn renew_till(&self) -> Option<SystemTime> {
+        self.renew_till
+    }
+
+    /// The addresses that the ticket may be used from, if it is restricted.
+    pub fn client_addresses(&self) -> Option<&[HostAddress]> {
+        self.client_addresses.as_deref()
+    }
+
+    /// The ad-type and ad-data of each authorization-data element, such as the PAC.
+    pub fn authorization_data(&self) -> &[(i32, Vec<u8>)] {
+        &self.authorization_data
+    }
+
+    /// Whether the ticket has expired at `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        now >= self.end_time
+    }
+
+    /// Whether the ticket may be used at `now`. It must have started and not yet
+    /// expired, and must not need to be validated first.
+    pub fn is_valid_at(&self, now: SystemTime) -> bool {
+        !self.flags.contains(TicketFlags::Invalid)
+            && now >= self.start_time()
+            && !self.is_expired(now)
+    }
+}
+
+impl Credential {
+    /// The credential of an AS exchange, from the reply and the enc-part that
+    /// [KerberosAsRep::decrypt_enc_part] decrypted from it.
+    pub fn from_as_rep(as_rep: KerberosAsRep, enc_part: EncAsRepPart) -> Self {
+        Self::from_reply(
+            as_rep.client_realm,
+            as_rep.client_name,
+            as_rep.ticket,
+            enc_part,
+        )
+    }
+
+    /// The credential of a TGS exchange, from the reply and the enc-part that
+    /// [KerberosTgsRep::decrypt_enc_part] decrypted from it.
+    pub fn from_tgs_rep(tgs_rep: KerberosTgsRep, enc_part: EncAsRepPart) -> Self {
+        Self::from_reply(
+            tgs_rep.client_realm,
+            tgs_rep.client_name,
+            tgs_rep.ticket,
+            enc_part,
+        )
+    }
+
+    fn from_reply(
+        client_realm: String,
+        client_name: String,
+        ticket: TaggedTicket,
+        enc_part: EncAsRepPart,
+    ) -> Self {
+        Credential {
+            client_realm,
+            client_name,
+            server_realm: enc_part.server_realm,
+            server_name: enc_part.server_name,
+            session_key: enc_part.session_key,
+            ticket,
+            flags: enc_part.flags,
+            auth_time: enc_part.auth_time,
+            start_time: enc_part.start_time,
+            end_time: enc_part.end_time,
+            renew_till: enc_part.renew_till,
+            addresses: enc_part.client_addresses,
+        }
+    }
+
+    pub fn client_realm(&self) -> &str {
+        &self.client_realm
+    }
+
+    pub fn client_name(&self) -> &str {
+        &self.client_name
+    }
+
+    /// The realm of the service that the ticket is for.
+    pub fn server_realm(&self) -> &str {
+        &self.server_realm
+    }
+
+    /// The service that the ticket is for, such as `krbtgt/EXAMPLE.COM`.
+    pub fn server_name(&self) -> &str {
+        &self.server_name
+    }
+
+    pub fn session_key(&self) -> &BaseKey {
+        &self.session_key
+    }
+
+    pub fn ticket(&self) -> Ticket {
+        Ticket(self.ticket.clone())
+    }
+
+    pub fn ticket_flags(&self) -> FlagSet<TicketFlags> {
+        self.flags
+    }
+
+    pub fn auth_time(&self) -> SystemTime {
+        self.auth_time
+    }
+
+    /// When the ticket becomes valid, which is the auth time unless a start time
+    /// was requested.
+    pub fn start_time(&self) -> SystemTime {
+        self.start_time.unwrap_or(self.auth_time)
+    }
+
+    pub fn end_time(&self) -> SystemTime {
+        self.end_time
+    }
+
+    pub fn renew_till(&self) -> Option<SystemTime> {
+        self.renew_till
+    }
+
+    /// The addresses that the ticket may be used from, if it is restricted.
+    pub fn addresses(&self) -> Option<&[HostAddress]> {
+        self.addresses.as_deref()
+    }
+
+    /// Whether the ticket has expired at `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        now >= self.end_time
+    }
+
+    /// How long the ticket remains valid after `now`, which is zero once it has
+    /// expired.
+    pub fn time_remaining(&self, now: SystemTime) -> Duration {
+        self.end_time.duration_since(now).unwrap_or_default()
+    }
+}
+
+fn tgs_realm(ticket: &TaggedTicket) -> Option<String> {
+    match ticket.0.sname.name_string.as_slice() {
+        [service, realm] if service.as_str() == "krbtgt" => Some(realm.as_str().to_string()),
+        _ => None,
+    }
+}
+
+// RFC 6806 5 - The name type of enterprise names.
+const NT_ENTERPRISE: i32 = 10;
+
+// RFC 6111 3 - The name type of well-known names, such as the anonymous principal.
+const NT_WELLKNOWN: i32 = 11;
+
+/// The well-known anonymous principal of RFC 8062, which an anonymous client is
+/// named by.
+pub const ANONYMOUS_PRINCIPAL: &str = "WELLKNOWN/ANONYMOUS";
+
+/// The well-known anonymous realm of RFC 8062, which is the realm of a client that
+/// is fully anonymous.
+pub const ANONYMOUS_REALM: &str = "WELLKNOWN:ANONYMOUS";
+
+/// Split a principal name of the form `name@REALM` into the name and the realm, if
+/// it has one.
+pub(crate) fn split_principal(principal: &str) -> (&str, Option<&str>) {
+    match principal.rsplit_once('@') {
+        Some((name, realm)) if !realm.is_empty() => (name, Some(realm)),
+        _ => (principal, None),
+    }
+}
+
+// RFC 4120 7.5.3 - Address types
+const ADDR_TYPE_IPV4: i32 = 2;
+const ADDR_TYPE_NETBIOS: i32 = 20;
+const ADDR_TYPE_IPV6: i32 = 24;
+
+const NETBIOS_NAME_LEN: usize = 16;
 
-                encrypt_aes256_cts_hmac_sha1_96(&base_key, &data, key_usage)?
+impl From<IpAddr> for HostAddress {
+    fn from(addr: IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(addr) => HostAddress::Ipv4(addr),
+            IpAddr::V6(addr) => HostAddress::Ipv6(addr),
+        }
+    }
+}
+
+impl TryFrom<&HostAddress> for KdcHostAddress {
+    type Error = der::Error;
+
+    fn try_from(addr: &HostAddress) -> Result<Self, Self::Error> {
+        let (addr_type, address) = match addr {
+            HostAddress::Ipv4(addr) => (ADDR_TYPE_IPV4, addr.octets().to_vec()),
+            HostAddress::Ipv6(addr) => (ADDR_TYPE_IPV6, addr.octets().to_vec()),
+            HostAddress::NetBios(name) => {
+                // The name is padded with spaces to 15 octets, and the 16th is NUL.
+                if name.is_empty() || name.len() >= NETBIOS_NAME_LEN || !name.is_ascii() {
+                    return Err(der::Tag::OctetString.value_error());
+                }
+                let mut address = vec![b' '; NETBIOS_NAME_LEN];
+                address[..name.len()].copy_from_slice(name.as_bytes());
+                address[NETBIOS_NAME_LEN - 1] = 0;
+                (ADDR_TYPE_NETBIOS, address)
             }
-            // Shouldn't be possible, we pre-vet all the etypes.
-            _ => return Err(KrbError::UnsupportedEncryption),
+            HostAddress::Other { addr_type, address } => (*addr_type, address.clone()),
         };
 
-        // fx cookie always has to be sent.
-        let pa_fx_cookie = self.pa_fx_cookie.clone();
+        Ok(KdcHostAddress {
+            addr_type,
+            address: OctetString::new(address)?,
+        })
+    }
+}
+
+impl TryFrom<KdcHostAddress> for HostAddress {
+    type Error = KrbError;
 
-        Ok(PreAuth {
-            enc_timestamp: Some(enc_timestamp),
-            pa_fx_cookie,
+    fn try_from(addr: KdcHostAddress) -> Result<Self, Self::Error> {
+        let address = addr.address.as_bytes();
+        match addr.addr_type {
+            ADDR_TYPE_IPV4 => <[u8; 4]>::try_from(address)
+                .map(|octets| HostAddress::Ipv4(Ipv4Addr::from(octets)))
+                .map_err(|_| KrbError::InvalidHostAddress),
+            ADDR_TYPE_IPV6 => <[u8; 16]>::try_from(address)
+                .map(|octets| HostAddress::Ipv6(Ipv6Addr::from(octets)))
+                .map_err(|_| KrbError::InvalidHostAddress),
+            ADDR_TYPE_NETBIOS => {
+                let name = std::str::from_utf8(address)
+                    .map_err(|_| KrbError::InvalidHostAddress)?
+                    .trim_end_matches(['\0', ' ']);
+                if name.is_empty() || address.len() != NETBIOS_NAME_LEN {
+                    return Err(KrbError::InvalidHostAddress);
+                }
+                Ok(HostAddress::NetBios(name.to_string()))
+            }
+            addr_type => Ok(HostAddress::Other {
+                addr_type,
+                address: address.to_vec(),
+            }),
+        }
+    }
+}
+
+// An end time this close to now is accepted, so that a request ending "now" isn't
+// refused for the time it takes to build it.
+const END_TIME_ALLOWANCE: Duration = Duration::from_secs(5);
+
+/// Check that a lifetime can be issued: it must not have ended already, it must
+/// end after it starts, and a renewable ticket must be renewable until at least
+/// when it ends.
+fn check_lifetime(
+    from: Option<SystemTime>,
+    until: SystemTime,
+    renew: Option<SystemTime>,
+) -> Result<(), KrbError> {
+    let now = SystemTime::now();
+    if until
+        .checked_add(END_TIME_ALLOWANCE)
+        .is_some_and(|until| until <= now)
+    {
+        return Err(KrbError::EndTimeInPast);
+    }
+    check_start_time(from, until)?;
+    if renew.is_some_and(|renew| renew < until) {
+        return Err(KrbError::RenewTimeBeforeEndTime);
+    }
+    Ok(())
+}
+
+/// Check that a ticket starting at `from` ends after it starts.
+fn check_start_time(from: Option<SystemTime>, until: SystemTime) -> Result<(), KrbError> {
+    if from.is_some_and(|from| from >= until) {
+        return Err(KrbError::InvalidStartTime);
+    }
+    Ok(())
+}
+
+/// Check that the times of a request can be encoded as a KerberosTime, which is
+/// limited to the years 1970 through 9999.
+fn check_times(
+    from: Option<SystemTime>,
+    until: SystemTime,
+    renew: Option<SystemTime>,
+) -> Result<(), KrbError> {
+    from.into_iter()
+        .chain(std::iter::once(until))
+        .chain(renew)
+        .try_for_each(|time| {
+            KerberosTime::from_system_time(time)
+                .map(|_| ())
+                .map_err(|_| KrbError::InvalidTime)
+        })
+}
+
+/// Check that `component` is a non-empty KerberosString.
+fn check_name_component(component: &str) -> Result<(), KrbError> {
+    if component.is_empty() || Ia5String::new(component).is_err() {
+        return Err(KrbError::InvalidName(component.to_string()));
+    }
+    Ok(())
+}
+
+/// The nonce of a request, which is random unless the caller chose one.
+fn request_nonce(nonce: Option<u32>) -> u32 {
+    // BUG IN MIT KRB5 - If the value is greater than i32 max you get:
+    //
+    // Jun 28 03:47:41 3e79497ab6b5 krb5kdc[1](Error): ASN.1 value too large - while dispatching (tcp)
+    //
+    nonce.unwrap_or_else(|| thread_rng().gen()) & 0x7fff_ffff
+}
+
+/// Check each component of a principal name such as `host/server.example.com`.
+fn check_principal_name(name: &str) -> Result<(), KrbError> {
+    name.split('/')
+        .try_for_each(check_name_component)
+        .map_err(|_| KrbError::InvalidName(name.to_string()))
+}
+
+fn principal_name_from_str(name_type: i32, name: &str) -> Result<PrincipalName, der::Error> {
+    let name_string = name
+        .split('/')
+        .map(|component| Ia5String::new(component).map(KerberosString))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(PrincipalName {
+        name_type,
+        name_string,
+    })
+}
+
+/// Split `time` into a KerberosTime and the microseconds that it has no room for.
+fn kerberos_time_usec(time: SystemTime) -> Result<(KerberosTime, Microseconds), der::Error> {
+    let since_epoch = time
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| der::Error::from(der::ErrorKind::DateTime))?;
+    let seconds = KerberosTime::from_unix_duration(Duration::from_secs(since_epoch.as_secs()))?;
+    Ok((seconds, since_epoch.subsec_micros()))
+}
+
+impl KerberosErrorRep {
+    fn to_asn(&self) -> Result<crate::asn1::krb_error::KrbError, der::Error> {
+        let ctime = self.ctime.map(kerberos_time_usec).transpose()?;
+        let (stime, susec) = kerberos_time_usec(self.stime)?;
+
+        Ok(crate::asn1::krb_error::KrbError {
+            pvno: 5,
+            msg_type: KrbMessageType::KrbError as u8,
+            ctime: ctime.map(|(ctime, _)| ctime),
+            cusec: ctime.map(|(_, cusec)| cusec),
+            stime,
+            susec,
+            error_code: self.error_code.into(),
+            crealm: self
+                .client_realm
+                .as_deref()
+                .map(|crealm| Ia5String::new(crealm).map(KerberosString))
+                .transpose()?,
+            cname: self
+                .client_name
+                .as_deref()
+                .map(|cname| principal_name_from_str(self.client_name_type, cname))
+                .transpose()?,
+            service_realm: KerberosString(Ia5String::new(&self.service_realm)?),
+            service_name: principal_name_from_str(self.service_name_type, &self.service_name)?,
+            error_text: self
+                .error_text
+                .as_deref()
+                .map(|error_text| Ia5String::new(error_text).map(KerberosString))
+                .transpose()?,
+            error_data: self.error_data.clone().map(OctetString::new).transpose()?,
+        })
+    }
+}
+
+impl KerberosPaRep {
+    fn to_asn(&self) -> Result<crate::asn1::krb_error::KrbError, der::Error> {
+        // Padata that didn't come in a KRB-ERROR, such as that of a FAST reply, has
+        // no error to be encoded in.
+        let krb_error = self.krb_error.as_ref().ok_or_else(|| {
+            Tag::Application {
+                constructed: true,
+                number: TagNumber::N30,
+            }
+            .value_error()
+        })?;
+        krb_error.to_asn()
+    }
+}
+
+impl KerberosAsRep {
+    fn to_asn(&self) -> Result<KdcRep, der::Error> {
+        let mut padata = None;
+
+        if let Some(pk_as_rep) = &self.pk_as_rep {
+            padata.get_or_insert_with(Vec::new).push(PaData {
+                padata_type: PaDataType::PaPkAsRep as u32,
+                padata_value: OctetString::new(pk_as_rep.to_der()?)?,
+            });
+        }
+
+        if !self.etype_info2.is_empty() {
+            let einfo2_sequence = self
+                .etype_info2
+                .iter()
+                .map(|einfo2| {
+                    Ok(ETypeInfo2Entry {
+                        etype: einfo2.etype as i32,
+                        salt: einfo2
+                            .salt
+                            .as_deref()
+                            .map(|salt| Ia5String::new(salt).map(KerberosString))
+                            .transpose()?,
+                        s2kparams: einfo2.s2kparams.clone().map(OctetString::new).transpose()?,
+                    })
+                })
+                .collect::<Result<KdcETypeInfo2, der::Error>>()?;
+            padata.get_or_insert_with(Vec::new).push(PaData {
+                padata_type: PaDataType::PaEtypeInfo2 as u32,
+                padata_value: OctetString::new(einfo2_sequence.to_der()?)?,
+            });
+        }
+
+        if let Some(fast_rep) = &self.fast_rep {
+            let fast = PaFxFastReply(KrbFastArmoredRep {
+                enc_fast_rep: KdcEncryptedData::try_from(fast_rep)?,
+            });
+            padata.get_or_insert_with(Vec::new).push(PaData {
+                padata_type: PaDataType::PaFxFast as u32,
+                padata_value: OctetString::new(fast.to_der()?)?,
+            });
+        }
+
+        if !self.other_padata.is_empty() {
+            padata
+                .get_or_insert_with(Vec::new)
+                .extend(self.other_padata.iter().cloned());
+        }
+
+        Ok(KdcRep {
+            pvno: 5,
+            msg_type: KrbMessageType::KrbAsRep as u8,
+            padata,
+            crealm: KerberosString(Ia5String::new(&self.client_realm)?),
+            cname: principal_name_from_str(self.client_name_type, &self.client_name)?,
+            ticket: self.ticket.clone(),
+            enc_part: KdcEncryptedData::try_from(&self.enc_part)?,
+        })
+    }
+}
+
+impl KerberosAsRep {
+    /// Decrypt the enc-part of this reply with the client's key, yielding the ticket
+    /// and the session key that was issued with it.
+    pub fn into_authenticated_reply(
+        self,
+        base_key: &BaseKey,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        self.decrypt_reply(base_key, None, None, None)
+    }
+
+    /// Decrypt the enc-part of this reply as [Self::into_authenticated_reply] does,
+    /// with a version of the client's key. When the reply can't be decrypted and
+    /// names another version of the key, this fails with
+    /// [KrbError::KeyVersionMismatch], as the key has been rotated.
+    pub fn into_authenticated_reply_with_kvno(
+        self,
+        key: &KerberosKey,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        self.decrypt_reply(key.key(), Some(key.kvno()), None, None)
+    }
+
+    /// Decrypt the enc-part of this reply as [Self::into_authenticated_reply] does,
+    /// and check the checksum of the request in PA-REQ-ENC-PA-REP against `request`,
+    /// which must be the DER of the AS-REQ exactly as it was sent. Whether the KDC
+    /// returned a checksum is given by [AuthenticatedReply::request_verified], as a
+    /// KDC that doesn't support it replies without one.
+    pub fn into_verified_reply(
+        self,
+        base_key: &BaseKey,
+        request: &[u8],
+    ) -> Result<AuthenticatedReply, KrbError> {
+        self.decrypt_reply(base_key, None, Some(request), None)
+    }
+
+    /// The ETYPE-INFO2 entry that the KDC sent in the reply for the etype of the
+    /// enc-part. Its salt is the one that the reply key was derived with, which
+    /// takes precedence over the salt of the PREAUTH_REQUIRED error.
+    pub fn reply_etype_info(&self) -> Option<&EtypeInfo2> {
+        let etype = self.enc_part.etype();
+        self.etype_info2
+            .iter()
+            .find(|einfo2| einfo2.etype as i32 == etype)
+    }
+
+    /// The parameters that the reply key was derived with, if the KDC confirmed them
+    /// in the reply.
+    pub(crate) fn reply_string_to_key_params(&self) -> Option<Result<StringToKeyParams, KrbError>> {
+        self.reply_etype_info()
+            .map(|einfo2| einfo2.string_to_key_params(&self.client_realm, &self.client_name))
+    }
+
+    /// Derive the reply key from the passphrase with the salt that the KDC
+    /// confirmed in the reply, if it confirmed one.
+    pub fn derive_reply_key(
+        &self,
+        passphrase: &str,
+        options: &StringToKeyOptions,
+    ) -> Option<Result<BaseKey, KrbError>> {
+        self.reply_etype_info().map(|einfo2| {
+            einfo2.derive_key(passphrase, &self.client_realm, &self.client_name, options)
+        })
+    }
+
+    /// Derive the reply key from the passphrase with the default salt and iteration
+    /// count of the etype of the enc-part, for a KDC that neither required
+    /// preauthentication nor confirmed a salt.
+    pub fn derive_default_reply_key(
+        &self,
+        passphrase: &str,
+        options: &StringToKeyOptions,
+    ) -> Result<BaseKey, KrbError> {
+        self.default_etype_info()?.derive_key(
+            passphrase,
+            &self.client_realm,
+            &self.client_name,
+            options,
+        )
+    }
+
+    /// As [Self::derive_reply_key], without blocking the executor.
+    #[cfg(feature = "tokio-codec")]
+    pub async fn derive_reply_key_async(
+        &self,
+        passphrase: &str,
+        options: &StringToKeyOptions,
+    ) -> Option<Result<BaseKey, KrbError>> {
+        let einfo2 = self.reply_etype_info()?;
+        Some(
+            einfo2
+                .derive_key_async(passphrase, &self.client_realm, &self.client_name, options)
+                .await,
+        )
+    }
+
+    /// As [Self::derive_default_reply_key], without blocking the executor.
+    #[cfg(feature = "tokio-codec")]
+    pub async fn derive_default_reply_key_async(
+        &self,
+        passphrase: &str,
+        options: &StringToKeyOptions,
+    ) -> Result<BaseKey, KrbError> {
+        self.default_etype_info()?
+            .derive_key_async(passphrase, &self.client_realm, &self.client_name, options)
+            .await
+    }
+
+    /// The parameters of the default string-to-key for the etype of the enc-part,
+    /// with the default salt of the client.
+    pub(crate) fn default_string_to_key_params(&self) -> Result<StringToKeyParams, KrbError> {
+        self.default_etype_info()?
+            .string_to_key_params(&self.client_realm, &self.client_name)
+    }
+
+    // The parameters of the default string-to-key for the etype of the enc-part.
+    fn default_etype_info(&self) -> Result<EtypeInfo2, KrbError> {
+        let etype = EncryptionType::try_from(self.enc_part.etype())
+            .map_err(|_| KrbError::UnsupportedEncryption)?;
+
+        Ok(EtypeInfo2 {
+            etype,
+            salt: None,
+            s2kparams: None,
         })
     }
+
+    /// Check that the enc-part is encrypted in one of the `requested` etypes. A KDC
+    /// must pick one of the etypes of the request, so any other one is either a
+    /// misconfigured KDC or an attacker downgrading the reply to a weaker etype.
+    pub fn check_etype(&self, requested: &[EncryptionType]) -> Result<(), KrbError> {
+        let received = self.enc_part.etype();
+        if requested.iter().any(|etype| *etype as i32 == received) {
+            Ok(())
+        } else {
+            Err(KrbError::EtypeDowngrade(requested.to_vec(), received))
+        }
+    }
+
+    /// Decrypt the enc-part of this reply with the client's key, yielding every
+    /// field that the KDC returned in it. Either application tag is accepted, as MIT
+    /// KDCs tag the enc-part of an AS-REP as an EncTGSRepPart.
+    pub fn decrypt_enc_part(&self, base_key: &BaseKey) -> Result<EncAsRepPart, KrbError> {
+        let strengthened_key = self.strengthened_key(base_key)?;
+        let reply_key = strengthened_key.as_ref().unwrap_or(base_key);
+
+        self.decrypt_kdc_rep_part(reply_key)
+            .and_then(EncAsRepPart::try_from)
+    }
+
+    /// Decrypt the enc-part of this reply with version `key_kvno` of the client's
+    /// key, checking the request checksum against `request` and that the reply
+    /// carries the `nonce` of the request, if given.
+    pub(crate) fn decrypt_reply(
+        self,
+        base_key: &BaseKey,
+        key_kvno: Option<u32>,
+        request: Option<&[u8]>,
+        nonce: Option<u32>,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        let strengthened_key = self.strengthened_key(base_key)?;
+        let reply_key = strengthened_key.as_ref().unwrap_or(base_key);
+
+        let enc_part = self
+            .decrypt_kdc_rep_part(reply_key)
+            .map_err(|err| self.enc_part.key_version_error(err, key_kvno))?;
+
+        // RFC 4120 3.1.5 The reply must be to the request that we sent.
+        if nonce.is_some_and(|nonce| nonce != enc_part.nonce) {
+            return Err(KrbError::ReplyNonceMismatch);
+        }
+
+        let request_verified = match request {
+            Some(request) => verify_req_enc_pa_rep(&enc_part, reply_key, request)?,
+            None => false,
+        };
+
+        // RFC 6806 11 A KDC that supports FAST says so in the encrypted part, where it
+        // can't be stripped like the PA-FX-FAST of the PREAUTH_REQUIRED error can.
+        let kdc_supports_fast = enc_part
+            .encrypted_pa_data
+            .iter()
+            .flatten()
+            .any(|padata| padata.padata_type == PaDataType::PaFxFast as u32);
+
+        let session_key = BaseKey::try_from(enc_part.key)?;
+
+        Ok(AuthenticatedReply {
+            client_realm: self.client_realm,
+            client_name: self.client_name,
+            ticket: self.ticket,
+            session_key,
+            flags: enc_part.flags.0,
+            auth_time: enc_part.auth_time.to_system_time(),
+            start_time: enc_part.start_time.map(|t| t.to_system_time()),
+            end_time: enc_part.end_time.to_system_time(),
+            renew_till: enc_part.renew_till.map(|t| t.to_system_time()),
+            request_verified,
+            kdc_supports_fast,
+        })
+    }
+}
+
+impl KerberosAsRep {
+    /// The key that strengthens `base_key` into the reply key, if the KDC sent one.
+    fn strengthened_key(&self, base_key: &BaseKey) -> Result<Option<BaseKey>, KrbError> {
+        // The reply must be checked against the armor before it can be trusted.
+        if self.fast_rep.is_some() {
+            return Err(KrbError::FastReplyArmored);
+        }
+
+        // RFC 6113 5.4.3 A strengthen key replaces the reply key with one derived
+        // from both.
+        self.strengthen_key
+            .as_ref()
+            .map(|strengthen_key| strengthen_key.krb_fx_cf2(base_key, "strengthenkey", "replykey"))
+            .transpose()
+    }
+
+    fn decrypt_kdc_rep_part(&self, reply_key: &BaseKey) -> Result<EncKdcRepPart, KrbError> {
+        // RFC 4120 The key usage value for encrypting this field is 3 in an AS-REP
+        // message, using the client's long-term key or another key selected
+        // via pre-authentication mechanisms.
+        self.enc_part
+            .decrypt_enc_kdc_rep_part(reply_key, KeyUsage::AS_REP_ENC_PART)
+    }
+}
+
+impl TryFrom<EncKdcRepPart> for EncAsRepPart {
+    type Error = KrbError;
+
+    fn try_from(enc_part: EncKdcRepPart) -> Result<Self, KrbError> {
+        let client_addresses = enc_part
+            .client_addresses
+            .map(|addresses| {
+                addresses
+                    .into_iter()
+                    .map(HostAddress::try_from)
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+
+        Ok(EncAsRepPart {
+            session_key: BaseKey::try_from(enc_part.key)?,
+            last_req: enc_part
+                .last_req
+                .into_iter()
+                .map(LastReqEntry::from)
+                .collect(),
+            nonce: enc_part.nonce,
+            key_expiration: enc_part.key_expiration.map(|t| t.to_system_time()),
+            flags: enc_part.flags.0,
+            auth_time: enc_part.auth_time.to_system_time(),
+            start_time: enc_part.start_time.map(|t| t.to_system_time()),
+            end_time: enc_part.end_time.to_system_time(),
+            renew_till: enc_part.renew_till.map(|t| t.to_system_time()),
+            server_realm: enc_part.server_realm.into(),
+            server_name: enc_part.server_name.into(),
+            client_addresses,
+        })
+    }
+}
+
+impl From<LastReqItem> for LastReqEntry {
+    fn from(item: LastReqItem) -> Self {
+        let lr_type = match item.lr_type.unsigned_abs() {
+            0 => LastReqType::None,
+            1 => LastReqType::InitialTgt,
+            2 => LastReqType::Initial,
+            3 => LastReqType::NewestTgt,
+            4 => LastReqType::Renewal,
+            5 => LastReqType::Request,
+            6 => LastReqType::PasswordExpiration,
+            7 => LastReqType::AccountExpiration,
+            _ => LastReqType::Other(item.lr_type),
+        };
+
+        LastReqEntry {
+            lr_type,
+            local: item.lr_type < 0,
+            time: item.lr_value.to_system_time(),
+        }
+    }
+}
+
+impl LastReqEntry {
+    /// What the time of this entry refers to.
+    pub fn lr_type(&self) -> LastReqType {
+        self.lr_type
+    }
+
+    /// Whether the time is only for the KDC that replied.
+    pub fn is_local(&self) -> bool {
+        self.local
+    }
+
+    /// The time of this entry.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+}
+
+impl EncAsRepPart {
+    /// Check that this is the reply to `request` as RFC 4120 3.1.5 requires. The
+    /// nonce must be the one that was sent, and the ticket must be for the service
+    /// that was asked for, unless the client allowed the KDC to canonicalize its
+    /// name. The KDC may shorten the requested lifetime, but never extend it.
+    pub fn validate_against_request(&self, request: &KerberosRequest) -> Result<(), KrbError> {
+        if self.nonce != request.nonce() {
+            return Err(KrbError::ReplyNonceMismatch);
+        }
+
+        let kdc_options = match request {
+            KerberosRequest::AsReq(as_req) => as_req.kdc_options(),
+            KerberosRequest::TgsReq(tgs_req) => tgs_req.kdc_options(),
+        };
+        if !kdc_options.contains(KdcOptions::Canonicalize)
+            && (self.server_name != request.service_name() || self.server_realm != request.realm())
+        {
+            return Err(KrbError::ReplyServiceMismatch(format!(
+                "{}@{}",
+                self.server_name, self.server_realm
+            )));
+        }
+
+        if self.end_time > request.until() {
+            return Err(KrbError::ReplyEndTimeExceedsRequest);
+        }
+
+        Ok(())
+    }
+
+    /// The session key that was issued with the ticket.
+    pub fn session_key(&self) -> &BaseKey {
+        &self.session_key
+    }
+
+    /// The times of the client's last requests, in the order that the KDC sent
+    /// them.
+    pub fn last_req(&self) -> &[LastReqEntry] {
+        &self.last_req
+    }
+
+    /// The nonce of the request that this is the reply to.
+    pub fn nonce(&self) -> u32 {
+        self.nonce
+    }
+
+    /// When the client's key expires, so that the user can be warned.
+    pub fn key_expiration(&self) -> Option<SystemTime> {
+        self.key_expiration
+    }
+
+    /// How long remains until the client's password expires, if that is within
+    /// `threshold` of `now`. KDCs report the expiry in key-expiration, in the
+    /// last-req, or both, and the earliest of them is used. A password that has
+    /// already expired gives a zero duration.
+    pub fn password_expiry_warning(
+        &self,
+        now: SystemTime,
+        threshold: Duration,
+    ) -> Option<Duration> {
+        let expiry = self
+            .last_req
+            .iter()
+            .filter(|entry| entry.lr_type == LastReqType::PasswordExpiration)
+            .map(|entry| entry.time)
+            .chain(self.key_expiration)
+            .min()?;
+
+        let remaining = expiry.duration_since(now).unwrap_or_default();
+        (remaining <= threshold).then_some(remaining)
+    }
+
+    /// The flags of the issued ticket.
+    pub fn flags(&self) -> FlagSet<TicketFlags> {
+        self.flags
+    }
+
+    /// The time of the initial authentication.
+    pub fn auth_time(&self) -> SystemTime {
+        self.auth_time
+    }
+
+    /// The time that the ticket is valid from, if it differs from the authtime.
+    pub fn start_time(&self) -> Option<SystemTime> {
+        self.start_time
+    }
+
+    /// The time that the ticket expires.
+    pub fn end_time(&self) -> SystemTime {
+        self.end_time
+    }
+
+    /// The time that a renewable ticket may be renewed until.
+    pub fn renew_till(&self) -> Option<SystemTime> {
+        self.renew_till
+    }
+
+    /// The realm of the service that the ticket is for.
+    pub fn server_realm(&self) -> &str {
+        &self.server_realm
+    }
+
+    /// The name of the service that the ticket is for.
+    pub fn server_name(&self) -> &str {
+        &self.server_name
+    }
+
+    /// The addresses that the ticket is restricted to, if any.
+    pub fn client_addresses(&self) -> Option<&[HostAddress]> {
+        self.client_addresses.as_deref()
+    }
+}
+
+/// Check the checksum of the AS-REQ that the KDC returned in PA-REQ-ENC-PA-REP,
+/// which is keyed with the reply key. This returns false if there is none.
+fn verify_req_enc_pa_rep(
+    enc_part: &EncKdcRepPart,
+    reply_key: &BaseKey,
+    request: &[u8],
+) -> Result<bool, KrbError> {
+    let Some(padata) = enc_part
+        .encrypted_pa_data
+        .iter()
+        .flatten()
+        .find(|padata| padata.padata_type == PaDataType::EncpadataReqEncPaRep as u32)
+    else {
+        // RFC 6806 11 The KDC sets enc-pa-rep when it sends the checksum, and the
+        // flag is in the encrypted part, so the checksum can't have been stripped.
+        if enc_part.flags.0.contains(TicketFlags::EncPaRep) {
+            return Err(KrbError::ReqEncPaRepMissing);
+        }
+        return Ok(false);
+    };
+
+    let cksum = Checksum::from_der(padata.padata_value.as_bytes())
+        .map_err(|_| KrbError::DerDecodeReqEncPaRep)?;
+
+    cksum
+        .verify(reply_key, KeyUsage::AS_REQ, request)
+        .map_err(|err| match err {
+            KrbError::MessageAuthenticationFailed => KrbError::ReqEncPaRepMismatch,
+            err => err,
+        })?;
+
+    Ok(true)
+}
+
+#[cfg(feature = "pkinit")]
+impl KerberosAsRep {
+    /// Agree on the reply key of the reply to a PKINIT request, with the state of
+    /// that request. The KDC is validated first, and the reply is then decrypted with
+    /// this key by [Self::into_authenticated_reply].
+    pub fn pkinit_reply_key(
+        &self,
+        pkinit: &PkinitPreauth,
+        nonce: u32,
+    ) -> Result<BaseKey, KrbError> {
+        let pk_as_rep = self
+            .pk_as_rep
+            .as_ref()
+            .ok_or(KrbError::PkinitMissingReply)?;
+        pkinit.reply_key(pk_as_rep, nonce, &self.enc_part)
+    }
+}
+
+impl KerberosAsRep {
+    /// Unarmor the reply to a FAST request with `nonce`. This checks that the
+    /// ticket is the one the KDC issued, and takes the client name from the armored
+    /// reply, as the one in the clear may have been tampered with.
+    pub fn unarmor(mut self, armor: &FastArmor, nonce: u32) -> Result<KerberosAsRep, KrbError> {
+        let fast_rep = self.fast_rep.take().ok_or(KrbError::FastMissingReply)?;
+        let fast_rep = armor.unarmor_reply(&fast_rep, nonce)?;
+
+        let finished = fast_rep.finished.ok_or(KrbError::FastMissingFinished)?;
+
+        let ticket = self
+            .ticket
+            .to_der()
+            .map_err(|_| KrbError::DerEncodeTicket)?;
+        finished
+            .ticket_checksum
+            .verify(&armor.armor_key, KeyUsage::FAST_FINISHED, &ticket)?;
+
+        self.client_realm = finished.crealm.into();
+        self.client_name = finished.cname.into();
+        self.strengthen_key = fast_rep.strengthen_key.map(BaseKey::try_from).transpose()?;
+
+        Ok(self)
+    }
+}
+
+impl AuthenticatedReply {
+    pub fn client_realm(&self) -> &str {
+        &self.client_realm
+    }
+
+    pub fn client_name(&self) -> &str {
+        &self.client_name
+    }
+
+    pub fn session_key(&self) -> &BaseKey {
+        &self.session_key
+    }
+
+    /// The issued ticket. A TGT can be given to a peer for user-to-user
+    /// authentication, see [KerberosTgsReqBuilder::additional_ticket].
+    pub fn ticket(&self) -> Ticket {
+        Ticket(self.ticket.clone())
+    }
+
+    /// The flags of the issued ticket, as reported by the KDC.
+    pub fn ticket_flags(&self) -> FlagSet<TicketFlags> {
+        self.flags
+    }
+
+    /// When the client initially authenticated, which is retained when the ticket
+    /// is renewed.
+    pub fn auth_time(&self) -> SystemTime {
+        self.auth_time
+    }
+
+    /// When the ticket becomes valid, which is the auth time unless a start time
+    /// was requested.
+    pub fn start_time(&self) -> SystemTime {
+        self.start_time.unwrap_or(self.auth_time)
+    }
+
+    /// When the ticket expires.
+    pub fn end_time(&self) -> SystemTime {
+        self.end_time
+    }
+
+    /// Until when a renewable ticket may be renewed.
+    pub fn renew_till(&self) -> Option<SystemTime> {
+        self.renew_till
+    }
+
+    /// Whether the ticket is postdated and invalid, so that it must be validated
+    /// once it starts before it can be used.
+    pub fn requires_validation(&self) -> bool {
+        self.flags.contains(TicketFlags::Invalid)
+    }
+
+    /// Whether the KDC returned a checksum of the AS-REQ, which matched the request
+    /// that was sent. A caller that requires this protection must check it, as it is
+    /// false when the KDC doesn't support PA-REQ-ENC-PA-REP.
+    pub fn request_verified(&self) -> bool {
+        self.request_verified
+    }
+
+    /// Whether the KDC said in the encrypted part of the reply that it supports
+    /// FAST. A client that sent its request without armor can tell from this that
+    /// the offer of FAST was removed from the error that asked for preauthentication.
+    pub fn kdc_supports_fast(&self) -> bool {
+        self.kdc_supports_fast
+    }
+
+    /// Whether this TGT may be used to request postdated tickets.
+    pub fn may_postdate(&self) -> bool {
+        self.flags.contains(TicketFlags::MayPostdate)
+    }
+
+    /// Whether the ticket is anonymous, so that it names the [ANONYMOUS_PRINCIPAL]
+    /// rather than the client. The client realm is then either the realm of the
+    /// client or [ANONYMOUS_REALM] when that is hidden too.
+    pub fn is_anonymous(&self) -> bool {
+        self.flags.contains(TicketFlags::Anonymous)
+    }
+
+    /// Build an AP-REQ that presents the ticket of this reply to its service. The
+    /// authenticator is encrypted in the session key with `key_usage`.
+    pub(crate) fn build_ap_req(
+        &self,
+        now: SystemTime,
+        seq_number: Option<u32>,
+        cksum: Option<Checksum>,
+        subkey: Option<&BaseKey>,
+        key_usage: KeyUsage,
+    ) -> Result<TaggedApReq, KrbError> {
+        let since_epoch = now
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| KrbError::DerEncodeAuthenticator)?;
+
+        // KerberosTime has no fractional seconds, those are carried in cusec.
+        let ctime = KerberosTime::from_unix_duration(Duration::from_secs(since_epoch.as_secs()))
+            .map_err(|_| KrbError::DerEncodeAuthenticator)?;
+
+        let crealm = Ia5String::new(&self.client_realm)
+            .map(KerberosString)
+            .map_err(|_| KrbError::DerEncodeAuthenticator)?;
+
+        let cname = principal_name_from_str(1, &self.client_name)
+            .map_err(|_| KrbError::DerEncodeAuthenticator)?;
+
+        let subkey = subkey
+            .map(KdcEncryptionKey::try_from)
+            .transpose()
+            .map_err(|_| KrbError::DerEncodeAuthenticator)?;
+
+        let authenticator = TaggedAuthenticator(Authenticator {
+            authenticator_vno: 5,
+            crealm,
+            cname,
+            cksum,
+            cusec: since_epoch.subsec_micros(),
+            ctime,
+            subkey,
+            seq_number,
+            authorization_data: None,
+        })
+        .to_der()
+        .map_err(|_| KrbError::DerEncodeAuthenticator)?;
+
+        let authenticator = self.session_key.encrypt_data(&authenticator, key_usage)?;
+        let authenticator =
+            KdcEncryptedData::try_from(&authenticator).map_err(|_| KrbError::DerEncodeApReq)?;
+
+        Ok(TaggedApReq(ApReq {
+            pvno: 5,
+            msg_type: KrbMessageType::KrbApReq as u8,
+            ap_options: FlagSet::<ApOptions>::default(),
+            ticket: self.ticket.clone(),
+            authenticator,
+        }))
+    }
+
+    /// Decrypt the AP-REP with which a service answers an AP-REQ for the ticket of
+    /// this reply. The ctime of the [ApReply] must then be checked against that of
+    /// the authenticator that was sent.
+    pub fn decrypt_ap_rep(&self, ap_rep: &[u8]) -> Result<ApReply, KrbError> {
+        let ap_rep = TaggedApRep::from_der(ap_rep)
+            .map_err(|_| KrbError::DerDecodeApRep)?
+            .0;
+
+        if ap_rep.pvno != 5 {
+            return Err(KrbError::InvalidPvno(ap_rep.pvno));
+        }
+        if ap_rep.msg_type != KrbMessageType::KrbApRep as u8 {
+            return Err(KrbError::InvalidMessageType(
+                ap_rep.msg_type as i32,
+                KrbMessageType::KrbApRep as i32,
+            ));
+        }
+
+        let enc_part = EncryptedData::try_from(ap_rep.enc_part)?
+            .decrypt_enc_ap_rep_part(&self.session_key, KeyUsage::AP_REP_ENC_PART)?;
+
+        Ok(ApReply {
+            ctime: enc_part.ctime.to_system_time() + Duration::from_micros(enc_part.cusec.into()),
+            subkey: enc_part.subkey.map(BaseKey::try_from).transpose()?,
+            seq_number: enc_part.seq_number,
+        })
+    }
+}
+
+impl TryFrom<&BaseKey> for KdcEncryptionKey {
+    type Error = der::Error;
+
+    fn try_from(key: &BaseKey) -> Result<Self, Self::Error> {
+        Ok(KdcEncryptionKey {
+            key_type: key.etype() as i32,
+            key_value: OctetString::new(key.as_slice().to_vec())?,
+        })
+    }
+}
+
+impl TryFrom<KdcEncryptionKey> for BaseKey {
+    type Error = KrbError;
+
+    fn try_from(key: KdcEncryptionKey) -> Result<Self, Self::Error> {
+        let key_type =
+            EncryptionType::try_from(key.key_type).map_err(|_| KrbError::UnsupportedEncryption)?;
+        BaseKey::from_bytes(key_type, key.key_value.as_bytes())
+    }
+}
+
+impl KerberosTgsRep {
+    fn to_asn(&self) -> Result<KdcRep, der::Error> {
+        Ok(KdcRep {
+            pvno: 5,
+            msg_type: KrbMessageType::KrbTgsRep as u8,
+            padata: (!self.other_padata.is_empty()).then(|| self.other_padata.clone()),
+            crealm: KerberosString(Ia5String::new(&self.client_realm)?),
+            cname: principal_name_from_str(self.client_name_type, &self.client_name)?,
+            ticket: self.ticket.clone(),
+            enc_part: KdcEncryptedData::try_from(&self.enc_part)?,
+        })
+    }
+
+    /// Decrypt the enc-part of this reply with the session key of the TGT that
+    /// authenticated the request, yielding the service ticket and its session key.
+    pub fn into_authenticated_reply(
+        self,
+        session_key: &BaseKey,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        // RFC 4120 The key usage value for encrypting this field is 8 in a TGS-REP
+        // message, using the TGS session key.
+        self.decrypt_reply(session_key, KeyUsage::TGS_REP_ENC_PART_SESSION, None)
+    }
+
+    /// Decrypt the enc-part of this reply with the session key of the TGT, yielding
+    /// every field that the KDC returned in it.
+    pub fn decrypt_enc_part(&self, session_key: &BaseKey) -> Result<EncAsRepPart, KrbError> {
+        self.enc_part
+            .decrypt_enc_kdc_rep_part(session_key, KeyUsage::TGS_REP_ENC_PART_SESSION)
+            .and_then(EncAsRepPart::try_from)
+    }
+
+    /// Decrypt the enc-part of this reply as [Self::into_authenticated_reply] does,
+    /// for a request whose authenticator carried `subkey`. The KDC then encrypts the
+    /// reply in the subkey in place of the session key of the TGT.
+    pub fn into_authenticated_reply_with_subkey(
+        self,
+        subkey: &BaseKey,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        // RFC 4120 The key usage value is 9 when the TGS authenticator subkey is used.
+        self.decrypt_reply(subkey, KeyUsage::TGS_REP_ENC_PART_SUBKEY, None)
+    }
+
+    /// Decrypt the enc-part of this reply to `request`, in the `subkey` of its
+    /// authenticator if it carried one and otherwise in the session key of the TGT.
+    /// The reply is checked against the request as
+    /// [EncAsRepPart::validate_against_request] does, so that a reply to an earlier
+    /// request under the same session key can't be replayed (RFC 4120 3.3.3.1).
+    pub fn into_authenticated_reply_for_request(
+        self,
+        request: &KerberosRequest,
+        session_key: &BaseKey,
+        subkey: Option<&BaseKey>,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        match subkey {
+            Some(subkey) => {
+                self.decrypt_reply(subkey, KeyUsage::TGS_REP_ENC_PART_SUBKEY, Some(request))
+            }
+            None => self.decrypt_reply(
+                session_key,
+                KeyUsage::TGS_REP_ENC_PART_SESSION,
+                Some(request),
+            ),
+        }
+    }
+
+    fn decrypt_reply(
+        self,
+        key: &BaseKey,
+        key_usage: KeyUsage,
+        request: Option<&KerberosRequest>,
+    ) -> Result<AuthenticatedReply, KrbError> {
+        let enc_part = self
+            .enc_part
+            .decrypt_enc_kdc_rep_part(key, key_usage)
+            .and_then(EncAsRepPart::try_from)?;
+
+        if let Some(request) = request {
+            enc_part.validate_against_request(request)?;
+        }
+
+        Ok(AuthenticatedReply {
+            client_realm: self.client_realm,
+            client_name: self.client_name,
+            ticket: self.ticket,
+            session_key: enc_part.session_key,
+            flags: enc_part.flags,
+            auth_time: enc_part.auth_time,
+            start_time: enc_part.start_time,
+            end_time: enc_part.end_time,
+            renew_till: enc_part.renew_till,
+            request_verified: false,
+            kdc_supports_fast: false,
+        })
+    }
+}
+
+impl TryFrom<KdcRep> for KerberosAsRep {
+    type Error = KrbError;
+
+    fn try_from(rep: KdcRep) -> Result<Self, Self::Error> {
+        // assert the pvno and msg_type
+        if rep.pvno != 5 {
+            return Err(KrbError::InvalidPvno(rep.pvno));
+        }
+
+        // An unknown msg_type is as much a mismatch as a known but wrong one.
+        let msg_type = KrbMessageType::try_from(rep.msg_type).map_err(|_| {
+            KrbError::InvalidMessageType(rep.msg_type as i32, KrbMessageType::KrbAsRep as i32)
+        })?;
+
+        match msg_type {
+            KrbMessageType::KrbAsRep => {
+                let enc_part = EncryptedData::try_from(rep.enc_part)?;
+                trace!(?enc_part);
+
+                let client_realm: String = rep.crealm.into();
+                let client_name_type = rep.cname.name_type;
+                let client_name: String = rep.cname.into();
+
+                let fast_rep = rep
+                    .padata
+                    .iter()
+                    .flatten()
+                    .find(|padata| padata.padata_type == PaDataType::PaFxFast as u32)
+                    .map(|padata| {
+                        PaFxFastReply::from_der(padata.padata_value.as_bytes())
+                            .map_err(|_| KrbError::DerDecodeFastRep)
+                            .and_then(|fast| EncryptedData::try_from(fast.0.enc_fast_rep))
+                    })
+                    .transpose()?;
+
+                let pk_as_rep = rep
+                    .padata
+                    .iter()
+                    .flatten()
+                    .find(|padata| padata.padata_type == PaDataType::PaPkAsRep as u32)
+                    .map(|padata| {
+                        PaPkAsRep::from_der(padata.padata_value.as_bytes())
+                            .map_err(|_| KrbError::DerDecodePaPkAsRep)
+                    })
+                    .transpose()?;
+
+                // RFC 4120 5.2.7.5 The KDC may name the salt of the reply key again.
+                let etype_info2 = rep
+                    .padata
+                    .iter()
+                    .flatten()
+                    .find(|padata| padata.padata_type == PaDataType::PaEtypeInfo2 as u32)
+                    .map(|padata| etype_info2_from_der(padata.padata_value.as_bytes()))
+                    .transpose()?
+                    .unwrap_or_default();
+
+                let other_padata = rep
+                    .padata
+                    .into_iter()
+                    .flatten()
+                    .filter(|padata| {
+                        ![
+                            PaDataType::PaFxFast,
+                            PaDataType::PaPkAsRep,
+                            PaDataType::PaEtypeInfo2,
+                        ]
+                        .iter()
+                        .any(|padata_type| padata.padata_type == *padata_type as u32)
+                    })
+                    .collect();
+
+                Ok(KerberosAsRep {
+                    client_realm,
+                    client_name,
+                    client_name_type,
+                    ticket: rep.ticket,
+                    enc_part,
+                    fast_rep,
+                    strengthen_key: None,
+                    pk_as_rep,
+                    etype_info2,
+                    other_padata,
+                })
+            }
+            _ => Err(KrbError::InvalidMessageType(
+                rep.msg_type as i32,
+                KrbMessageType::KrbAsRep as i32,
+            )),
+        }
+    }
+}
+
+impl TryFrom<KdcRep> for KerberosTgsRep {
+    type Error = KrbError;
+
+    fn try_from(rep: KdcRep) -> Result<Self, Self::Error> {
+        // assert the pvno and msg_type
+        if rep.pvno != 5 {
+            return Err(KrbError::InvalidPvno(rep.pvno));
+        }
+
+        // An unknown msg_type is as much a mismatch as a known but wrong one.
+        let msg_type = KrbMessageType::try_from(rep.msg_type).map_err(|_| {
+            KrbError::InvalidMessageType(rep.msg_type as i32, KrbMessageType::KrbTgsRep as i32)
+        })?;
+
+        match msg_type {
+            KrbMessageType::KrbTgsRep => {
+                let enc_part = EncryptedData::try_from(rep.enc_part)?;
+                trace!(?enc_part);
+
+                let client_realm: String = rep.crealm.into();
+                let client_name_type = rep.cname.name_type;
+                let client_name: String = rep.cname.into();
+
+                Ok(KerberosTgsRep {
+                    client_realm,
+                    client_name,
+                    client_name_type,
+                    ticket: rep.ticket,
+                    enc_part,
+                    other_padata: rep.padata.unwrap_or_default(),
+                })
+            }
+            _ => Err(KrbError::InvalidMessageType(
+                rep.msg_type as i32,
+                KrbMessageType::KrbTgsRep as i32,
+            )),
+        }
+    }
+}
+
+impl TryFrom<crate::asn1::krb_error::KrbError> for KerberosErrRep {
+    type Error = KrbError;
+
+    fn try_from(rep: crate::asn1::krb_error::KrbError) -> Result<Self, Self::Error> {
+        // assert the pvno and msg_type
+        if rep.pvno != 5 {
+            return Err(KrbError::InvalidPvno(rep.pvno));
+        }
+
+        // An unknown msg_type is as much a mismatch as a known but wrong one.
+        let msg_type = KrbMessageType::try_from(rep.msg_type).map_err(|_| {
+            KrbError::InvalidMessageType(rep.msg_type as i32, KrbMessageType::KrbError as i32)
+        })?;
+
+        match msg_type {
+            KrbMessageType::KrbError => {
+                let error_code = KrbErrorCode::try_from(rep.error_code).map_err(|_| {
+                    KrbError::InvalidEnumValue(
+                        std::any::type_name::<KrbErrorCode>().to_string(),
+                        rep.error_code,
+                    )
+                })?;
+
+                // The time fields carry their microseconds separately.
+                let ctime = rep.ctime.map(|ctime| {
+                    ctime.to_system_time()
+                        + Duration::from_micros(rep.cusec.unwrap_or_default().into())
+                });
+                let stime = rep.stime.to_system_time() + Duration::from_micros(rep.susec.into());
+
+                let err_rep = KerberosErrorRep {
+                    error_code,
+                    ctime,
+                    stime,
+                    client_realm: rep.crealm.map(|crealm| crealm.into()),
+                    client_name_type: rep.cname.as_ref().map_or(1, |cname| cname.name_type),
+                    client_name: rep.cname.map(|cname| cname.into()),
+                    service_realm: rep.service_realm.into(),
+                    service_name_type: rep.service_name.name_type,
+                    service_name: rep.service_name.into(),
+                    error_text: rep.error_text.map(|error_text| error_text.into()),
+                    error_data: rep.error_data.map(|edata| edata.as_bytes().to_vec()),
+                };
+
+                let rep = match error_code {
+                    KrbErrorCode::KdcErrPreauthRequired => {
+                        let edata = err_rep
+                            .error_data
+                            .as_deref()
+                            .ok_or(KrbError::MissingPaData)?;
+
+                        let pavec: Vec<PaData> =
+                            MethodData::from_der(edata).map_err(|_| KrbError::DerDecodePaData)?;
+
+                        let mut pa_rep = KerberosPaRep::try_from(pavec)?;
+                        pa_rep.client_realm = err_rep.client_realm.clone();
+                        pa_rep.client_name = err_rep.client_name.clone();
+                        pa_rep.stime = Some(stime);
+                        pa_rep.ctime = ctime;
+                        pa_rep.krb_error = Some(err_rep);
+                        KerberosErrRep::Pa(pa_rep)
+                    }
+                    _ => KerberosErrRep::Err(err_rep),
+                };
+
+                Ok(rep)
+            }
+            _ => Err(KrbError::InvalidMessageType(
+                rep.msg_type as i32,
+                KrbMessageType::KrbError as i32,
+            )),
+        }
+    }
+}
+
+impl TryFrom<Vec<PaData>> for KerberosPaRep {
+    type Error = KrbError;
+
+    fn try_from(pavec: Vec<PaData>) -> Result<Self, Self::Error> {
+        // Per https://www.rfc-editor.org/rfc/rfc4120#section-7.5.2
+        // Build up the set of PaRep data
+        let mut pa_fx_fast = false;
+        let mut enc_timestamp = false;
+        let mut enc_challenge = false;
+        let mut fast_rep = None;
+        let mut pa_fx_cookie = None;
+        let mut pa_as_freshness = None;
+        let mut otp_challenge = None;
+        let mut etype_info2 = Vec::with_capacity(0);
+        let mut etype_info = None;
+        let mut seen_etype_info2 = false;
+        let mut client_realm = None;
+        let mut client_name = None;
+        let mut stime = None;
+        let mut offered = Vec::with_capacity(pavec.len());
+
+        for PaData {
+            padata_type,
+            padata_value,
+        } in pavec
+        {
+            offered.push(padata_type);
+
+            let Ok(padt) = padata_type.try_into() else {
+                // padatatype that we don't support
+                continue;
+            };
+
+            match padt {
+                PaDataType::PaEncTimestamp => enc_timestamp = true,
+                PaDataType::PaEtypeInfo2 => {
+                    seen_etype_info2 = true;
+                    // Keep every etype that was offered, the key is derived from the
+                    // strongest of them that we support.
+                    etype_info2.extend(etype_info2_from_der(padata_value.as_bytes())?);
+                }
+                PaDataType::PaEtypeInfo => {
+                    // The predecessor of ETYPE-INFO2 that older KDCs send, where the
+                    // salt is an octet string and there are no s2kparams.
+                    let einfo_sequence = KdcETypeInfo::from_der(padata_value.as_bytes())
+                        .map_err(|_| KrbError::DerDecodeEtypeInfo)?;
+
+                    let mut entries = Vec::with_capacity(einfo_sequence.len());
+                    for einfo in einfo_sequence {
+                        let Ok(etype) = EncryptionType::try_from(einfo.etype) else {
+                            continue;
+                        };
+
+                        let salt = einfo
+                            .salt
+                            .map(|s| String::from_utf8(s.into_bytes()))
+                            .transpose()
+                            .map_err(|_| KrbError::DerDecodeEtypeInfo)?;
+
+                        entries.push(EtypeInfo2 {
+                            etype,
+                            salt,
+                            s2kparams: None,
+                        });
+                    }
+                    etype_info = Some(entries);
+                }
+                PaDataType::PaFxFast => {
+                    pa_fx_fast = true;
+                    // The KDC only advertises FAST with an empty value, while a reply
+                    // to an armored request carries the armored reply.
+                    if !padata_value.as_bytes().is_empty() {
+                        let fast = PaFxFastReply::from_der(padata_value.as_bytes())
+                            .map_err(|_| KrbError::DerDecodeFastRep)?;
+                        fast_rep = Some(EncryptedData::try_from(fast.0.enc_fast_rep)?);
+                    }
+                }
+                PaDataType::PaFxError => {
+                    // The error that the KDC returned inside the armored reply.
+                    let err = TaggedKrbError::from_der(padata_value.as_bytes())
+                        .map_err(|_| KrbError::DerDecodeFastRep)?;
+                    client_realm = err.0.crealm.map(|crealm| crealm.into());
+                    client_name = err.0.cname.map(|cname| cname.into());
+                    stime = Some(
+                        err.0.stime.to_system_time() + Duration::from_micros(err.0.susec.into()),
+                    );
+                }
+                PaDataType::PaEncryptedChallenge => enc_challenge = true,
+                PaDataType::PaFxCookie => pa_fx_cookie = Some(padata_value.as_bytes().to_vec()),
+                PaDataType::PadataAsFreshness => {
+                    pa_as_freshness = Some(padata_value.as_bytes().to_vec())
+                }
+                PaDataType::PaOtpChallenge => {
+                    let challenge = PaOtpChallenge::from_der(padata_value.as_bytes())
+                        .map_err(|_| KrbError::DerDecodePaOtpChallenge)?;
+                    otp_challenge = Some(OtpChallenge::from(challenge));
+                }
+                _ => {
+                    // Ignore unsupported pa data types.
+                }
+            };
+        }
+
+        // RFC 4120 5.2.7.5 ETYPE-INFO is only used when ETYPE-INFO2 wasn't sent.
+        if !seen_etype_info2 {
+            etype_info2 = etype_info.unwrap_or_default();
+        }
+
+        // The entries are left in the order of the KDC's preference.
+
+        Ok(KerberosPaRep {
+            offered,
+            pa_fx_fast,
+            pa_fx_cookie,
+            pa_as_freshness,
+            otp_challenge,
+            enc_timestamp,
+            enc_challenge,
+            fast_rep,
+            etype_info2,
+            client_realm,
+            client_name,
+            stime,
+            ctime: None,
+            krb_error: None,
+        })
+    }
+}
+
+/// Check that a decrypted `payload` is of one of the `accepted` application tags. A
+/// payload that decrypts with the right key may still be of another type than the
+/// caller expects, which would otherwise only be a decode error.
+fn check_payload_tag(payload: &[u8], expected: u8, accepted: &[u8]) -> Result<(), KrbError> {
+    match payload.first().map(|octet| Tag::try_from(*octet)) {
+        Some(Ok(Tag::Application {
+            constructed: true,
+            number,
+        })) if !accepted.contains(&number.value()) => {
+            Err(KrbError::WrongPayloadTag(number.value(), expected))
+        }
+        // Anything that isn't an application tag fails to decode.
+        _ => Ok(()),
+    }
+}
+
+impl EncryptedData {
+    pub fn derive_key(
+        &self,
+        passphrase: &[u8],
+        realm: &[u8],
+        cname: &[u8],
+    ) -> Result<BaseKey, KrbError> {
+        // todo! there is some way to get a number of rounds here
+        // but I can't obviously see it?
+        let iter_count = None;
+        match self {
+            EncryptedData::Aes128CtsHmacSha196 { .. } => {
+                derive_key_aes128_cts_hmac_sha1_96(passphrase, realm, cname, iter_count)
+                    .and_then(|k| BaseKey::from_bytes(EncryptionType::AES128_CTS_HMAC_SHA1_96, &k))
+            }
+            EncryptedData::Aes256CtsHmacSha196 { .. } => {
+                derive_key_aes256_cts_hmac_sha1_96(passphrase, realm, cname, iter_count)
+                    .and_then(|k| BaseKey::from_bytes(EncryptionType::AES256_CTS_HMAC_SHA1_96, &k))
+            }
+            EncryptedData::Aes128CtsHmacSha256128 { .. } => {
+                derive_key_aes128_cts_hmac_sha256_128(passphrase, realm, cname, iter_count)
+                    .and_then(|k| {
+                        BaseKey::from_bytes(EncryptionType::AES128_CTS_HMAC_SHA256_128, &k)
+                    })
+            }
+            EncryptedData::Aes256CtsHmacSha384192 { .. } => {
+                derive_key_aes256_cts_hmac_sha384_192(passphrase, realm, cname, iter_count)
+                    .and_then(|k| {
+                        BaseKey::from_bytes(EncryptionType::AES256_CTS_HMAC_SHA384_192, &k)
+                    })
+            }
+            #[cfg(feature = "camellia")]
+            EncryptedData::Camellia128CtsCmac { .. } => {
+                let salt = default_salt(realm, cname);
+                cipher_for(EncryptionType::CAMELLIA128_CTS_CMAC)?
+                    .string_to_key(passphrase, &salt, iter_count)
+                    .and_then(|k| BaseKey::from_bytes(EncryptionType::CAMELLIA128_CTS_CMAC, &k))
+            }
+            #[cfg(feature = "camellia")]
+            EncryptedData::Camellia256CtsCmac { .. } => {
+                let salt = default_salt(realm, cname);
+                cipher_for(EncryptionType::CAMELLIA256_CTS_CMAC)?
+                    .string_to_key(passphrase, &salt, iter_count)
+                    .and_then(|k| BaseKey::from_bytes(EncryptionType::CAMELLIA256_CTS_CMAC, &k))
+            }
+            #[cfg(feature = "legacy-crypto")]
+            EncryptedData::Des3CbcSha1Kd { .. } => {
+                let salt = default_salt(realm, cname);
+                cipher_for(EncryptionType::DES3_CBC_SHA1_KD)?
+                    .string_to_key(passphrase, &salt, iter_count)
+                    .and_then(|k| BaseKey::from_bytes(EncryptionType::DES3_CBC_SHA1_KD, &k))
+            }
+            EncryptedData::Unsupported { .. } => Err(KrbError::UnsupportedEncryption),
+        }
+    }
+
+    /// Encrypt `plaintext` with `key_usage` in `key`, such as the DER of an
+    /// EncTicketPart with [KeyUsage::KDC_REP_TICKET] or the enc-part of a KRB-CRED
+    /// with [KeyUsage::KRB_CRED]. `etype` is the etype that the peer expects, which
+    /// must be that of the key. This is the counterpart of [Self::decrypt_data].
+    pub fn encrypt(
+        key: &BaseKey,
+        key_usage: KeyUsage,
+        plaintext: &[u8],
+        etype: EncryptionType,
+    ) -> Result<Self, KrbError> {
+        if key.etype() != etype {
+            return Err(KrbError::InvalidEncryptionKey);
+        }
+        key.encrypt_data(plaintext, key_usage)
+    }
+
+    /// The DER of the data as the EncryptedData of RFC 4120 5.2.9, for a message
+    /// that this crate doesn't build itself.
+    pub fn to_der(&self) -> Result<Vec<u8>, KrbError> {
+        KdcEncryptedData::try_from(self)
+            .and_then(|enc_data| enc_data.to_der())
+            .map_err(|_| KrbError::DerEncodeEncryptedData)
+    }
+
+    /// The data of the DER of an EncryptedData, as [Self::to_der] encodes it.
+    pub fn from_der(der_bytes: &[u8]) -> Result<Self, KrbError> {
+        KdcEncryptedData::from_der(der_bytes)
+            .map_err(|_| KrbError::DerDecodeEncryptedData)
+            .and_then(EncryptedData::try_from)
+    }
+
+    pub fn decrypt_data(
+        &self,
+        base_key: &BaseKey,
+        key_usage: KeyUsage,
+    ) -> Result<Vec<u8>, KrbError> {
+        if let EncryptedData::Unsupported { .. } = self {
+            return Err(KrbError::UnsupportedEncryption);
+        }
+
+        // The data is encrypted in another etype than the key.
+        if self.etype() != base_key.etype() as i32 {
+            return Err(KrbError::InvalidEncryptionKey);
+        }
+
+        cipher_for(base_key.etype())?.decrypt(base_key.as_slice(), key_usage, self.data())
+    }
+
+    /// Decrypt the data as [Self::decrypt_data] with a version of a key. If the data
+    /// names a different version of the key, it was encrypted in a key we don't
+    /// have, which is reported rather than failing to decrypt.
+    pub fn decrypt_data_with_kvno(
+        &self,
+        key: &KerberosKey,
+        key_usage: KeyUsage,
+    ) -> Result<Vec<u8>, KrbError> {
+        match self.kvno() {
+            Some(kvno) if kvno != key.kvno() => Err(KrbError::KeyVersionMismatch(kvno, key.kvno())),
+            _ => self.decrypt_data(key.key(), key_usage),
+        }
+    }
+
+    /// Explain why the data failed to decrypt with version `key_kvno` of a key. Data
+    /// that names another version was encrypted in a key we don't have, which is
+    /// reported as [KrbError::KeyVersionMismatch] rather than as a failed checksum.
+    pub(crate) fn key_version_error(&self, err: KrbError, key_kvno: Option<u32>) -> KrbError {
+        match (err, self.kvno(), key_kvno) {
+            (KrbError::MessageAuthenticationFailed, Some(kvno), Some(key_kvno))
+                if kvno != key_kvno =>
+            {
+                KrbError::KeyVersionMismatch(kvno, key_kvno)
+            }
+            (err, _, _) => err,
+        }
+    }
+
+    /// Decrypt the enc-part of an AS-REP or TGS-REP. Either application tag is
+    /// accepted, as MIT KDCs tag the enc-part of an AS-REP as an EncTGSRepPart.
+    pub(crate) fn decrypt_enc_kdc_rep_part(
+        &self,
+        base_key: &BaseKey,
+        key_usage: KeyUsage,
+    ) -> Result<EncKdcRepPart, KrbError> {
+        let cleartext = Zeroizing::new(self.decrypt_data(base_key, key_usage)?);
+        // The AS-REP is encrypted with key usage 3, every other usage is a TGS-REP.
+        let expected = if key_usage == KeyUsage::AS_REP_ENC_PART {
+            25
+        } else {
+            26
+        };
+        check_payload_tag(&cleartext, expected, &[25, 26])?;
+
+        TaggedEncKdcRepPart::from_der(&cleartext)
+            .map(TaggedEncKdcRepPart::into_inner)
+            .map_err(|_| KrbError::DerDecodeEncKdcRepPart)
+    }
+
+    /// Decrypt the enc-part of a ticket with a version of the service key.
+    pub(crate) fn decrypt_enc_ticket_part(
+        &self,
+        service_key: &KerberosKey,
+    ) -> Result<KdcEncTicketPart, KrbError> {
+        // RFC 4120 The key usage value for encrypting this field is 2, using the
+        // service's long-term key.
+        let cleartext =
+            Zeroizing::new(self.decrypt_data_with_kvno(service_key, KeyUsage::KDC_REP_TICKET)?);
+        check_payload_tag(&cleartext, 3, &[3])?;
+
+        TaggedEncTicketPart::from_der(&cleartext)
+            .map(|enc_part| enc_part.0)
+            .map_err(|_| KrbError::DerDecodeEncTicketPart)
+    }
+
+    /// Decrypt the authenticator of an AP-REQ, which is encrypted with `key_usage`
+    /// in the session key of its ticket.
+    pub(crate) fn decrypt_authenticator(
+        &self,
+        base_key: &BaseKey,
+        key_usage: KeyUsage,
+    ) -> Result<Authenticator, KrbError> {
+        let cleartext = Zeroizing::new(self.decrypt_data(base_key, key_usage)?);
+        check_payload_tag(&cleartext, 2, &[2])?;
+
+        TaggedAuthenticator::from_der(&cleartext)
+            .map(|authenticator| authenticator.0)
+            .map_err(|_| KrbError::DerDecodeAuthenticator)
+    }
+
+    fn decrypt_enc_ap_rep_part(
+        &self,
+        base_key: &BaseKey,
+        key_usage: KeyUsage,
+    ) -> Result<EncApRepPart, KrbError> {
+        let cleartext = Zeroizing::new(self.decrypt_data(base_key, key_usage)?);
+        check_payload_tag(&cleartext, 27, &[27])?;
+
+        TaggedEncApRepPart::from_der(&cleartext)
+            .map(|enc_part| enc_part.0)
+            .map_err(|_| KrbError::DerDecodeApRep)
+    }
+
+    /// The data of `etype` as the variant of that etype, or as
+    /// [EncryptedData::Unsupported] if it's one we don't support.
+    fn new(etype: i32, kvno: Option<u32>, data: Vec<u8>) -> Self {
+        match EncryptionType::try_from(etype) {
+            Ok(EncryptionType::AES128_CTS_HMAC_SHA1_96) => {
+                EncryptedData::Aes128CtsHmacSha196 { kvno, data }
+            }
+            Ok(EncryptionType::AES256_CTS_HMAC_SHA1_96) => {
+                EncryptedData::Aes256CtsHmacSha196 { kvno, data }
+            }
+            Ok(EncryptionType::AES128_CTS_HMAC_SHA256_128) => {
+                EncryptedData::Aes128CtsHmacSha256128 { kvno, data }
+            }
+            Ok(EncryptionType::AES256_CTS_HMAC_SHA384_192) => {
+                EncryptedData::Aes256CtsHmacSha384192 { kvno, data }
+            }
+            #[cfg(feature = "camellia")]
+            Ok(EncryptionType::CAMELLIA128_CTS_CMAC) => {
+                EncryptedData::Camellia128CtsCmac { kvno, data }
+            }
+            #[cfg(feature = "camellia")]
+            Ok(EncryptionType::CAMELLIA256_CTS_CMAC) => {
+                EncryptedData::Camellia256CtsCmac { kvno, data }
+            }
+            #[cfg(feature = "legacy-crypto")]
+            Ok(EncryptionType::DES3_CBC_SHA1_KD) => EncryptedData::Des3CbcSha1Kd { kvno, data },
+            _ => EncryptedData::Unsupported { etype, kvno, data },
+        }
+    }
+
+    /// The ciphertext, whatever its etype.
+    fn data(&self) -> &[u8] {
+        match self {
+            EncryptedData::Aes128CtsHmacSha196 { data, .. }
+            | EncryptedData::Aes256CtsHmacSha196 { data, .. }
+            | EncryptedData::Aes128CtsHmacSha256128 { data, .. }
+            | EncryptedData::Aes256CtsHmacSha384192 { data, .. }
+            | EncryptedData::Unsupported { data, .. } => data,
+            #[cfg(feature = "camellia")]
+            EncryptedData::Camellia128CtsCmac { data, .. }
+            | EncryptedData::Camellia256CtsCmac { data, .. } => data,
+            #[cfg(feature = "legacy-crypto")]
+            EncryptedData::Des3CbcSha1Kd { data, .. } => data,
+        }
+    }
+
+    /// The etype that the data is encrypted in, which needn't be one we support.
+    pub fn etype(&self) -> i32 {
+        match self {
+            EncryptedData::Aes128CtsHmacSha196 { .. } => {
+                EncryptionType::AES128_CTS_HMAC_SHA1_96 as i32
+            }
+            EncryptedData::Aes256CtsHmacSha196 { .. } => {
+                EncryptionType::AES256_CTS_HMAC_SHA1_96 as i32
+            }
+            EncryptedData::Aes128CtsHmacSha256128 { .. } => {
+                EncryptionType::AES128_CTS_HMAC_SHA256_128 as i32
+            }
+            EncryptedData::Aes256CtsHmacSha384192 { .. } => {
+                EncryptionType::AES256_CTS_HMAC_SHA384_192 as i32
+            }
+            #[cfg(feature = "camellia")]
+            EncryptedData::Camellia128CtsCmac { .. } => EncryptionType::CAMELLIA128_CTS_CMAC as i32,
+            #[cfg(feature = "camellia")]
+            EncryptedData::Camellia256CtsCmac { .. } => EncryptionType::CAMELLIA256_CTS_CMAC as i32,
+            #[cfg(feature = "legacy-crypto")]
+            EncryptedData::Des3CbcSha1Kd { .. } => EncryptionType::DES3_CBC_SHA1_KD as i32,
+            EncryptedData::Unsupported { etype, .. } => *etype,
+        }
+    }
+
+    /// The version of the key that the data is encrypted in, if it was given.
+    pub fn kvno(&self) -> Option<u32> {
+        match self {
+            EncryptedData::Aes128CtsHmacSha196 { kvno, .. }
+            | EncryptedData::Aes256CtsHmacSha196 { kvno, .. }
+            | EncryptedData::Aes128CtsHmacSha256128 { kvno, .. }
+            | EncryptedData::Aes256CtsHmacSha384192 { kvno, .. }
+            | EncryptedData::Unsupported { kvno, .. } => *kvno,
+            #[cfg(feature = "camellia")]
+            EncryptedData::Camellia128CtsCmac { kvno, .. }
+            | EncryptedData::Camellia256CtsCmac { kvno, .. } => *kvno,
+            #[cfg(feature = "legacy-crypto")]
+            EncryptedData::Des3CbcSha1Kd { kvno, .. } => *kvno,
+        }
+    }
+
+    pub(crate) fn with_kvno(mut self, kvno: Option<u32>) -> Self {
+        match &mut self {
+            EncryptedData::Aes128CtsHmacSha196 { kvno: k, .. }
+            | EncryptedData::Aes256CtsHmacSha196 { kvno: k, .. }
+            | EncryptedData::Aes128CtsHmacSha256128 { kvno: k, .. }
+            | EncryptedData::Aes256CtsHmacSha384192 { kvno: k, .. }
+            | EncryptedData::Unsupported { kvno: k, .. } => *k = kvno,
+            #[cfg(feature = "camellia")]
+            EncryptedData::Camellia128CtsCmac { kvno: k, .. }
+            | EncryptedData::Camellia256CtsCmac { kvno: k, .. } => *k = kvno,
+            #[cfg(feature = "legacy-crypto")]
+            EncryptedData::Des3CbcSha1Kd { kvno: k, .. } => *k = kvno,
+        }
+        self
+    }
+}
+
+impl TryFrom<KdcEncryptedData> for EncryptedData {
+    type Error = KrbError;
+
+    fn try_from(enc_data: KdcEncryptedData) -> Result<Self, Self::Error> {
+        // The KDC may pick any etype that the client offered. That only becomes an
+        // error if the data actually needs to be decrypted.
+        Ok(EncryptedData::new(
+            enc_data.etype,
+            enc_data.kvno,
+            enc_data.cipher.into_bytes(),
+        ))
+    }
+}
+
+impl TryFrom<&EncryptedData> for KdcEncryptedData {
+    type Error = der::Error;
+
+    fn try_from(enc_data: &EncryptedData) -> Result<Self, Self::Error> {
+        Ok(KdcEncryptedData {
+            etype: enc_data.etype(),
+            kvno: enc_data.kvno(),
+            cipher: OctetString::new(enc_data.data().to_vec())?,
+        })
+    }
+}
+
+impl KerberosPaRep {
+    /// The realm of the client as named by the KDC. This differs from the requested
+    /// realm when the KDC canonicalized the client.
+    pub fn client_realm(&self) -> Option<&str> {
+        self.client_realm.as_deref()
+    }
+
+    /// The name of the client as named by the KDC, such as the principal that an
+    /// enterprise name was mapped to.
+    pub fn client_name(&self) -> Option<&str> {
+        self.client_name.as_deref()
+    }
+
+    /// The padata types that the KDC sent, in the order that it sent them. Types
+    /// that this crate doesn't know are included.
+    pub fn offered(&self) -> &[u32] {
+        &self.offered
+    }
+
+    /// The padata types that the KDC sent, as [Self::offered], leaving out those
+    /// that this crate doesn't know.
+    pub fn offered_types(&self) -> Vec<PaDataType> {
+        self.offered
+            .iter()
+            .filter_map(|padata_type| PaDataType::try_from(*padata_type).ok())
+            .collect()
+    }
+
+    /// Whether the KDC offered `padata_type`, such as [PaDataType::PaFxFast] to
+    /// prefer FAST when it is available.
+    pub fn offers(&self, padata_type: PaDataType) -> bool {
+        self.offered.contains(&(padata_type as u32))
+    }
+
+    /// The KDC's time when it asked for preauthentication, if this came in a
+    /// KRB-ERROR.
+    pub fn stime(&self) -> Option<SystemTime> {
+        self.stime
+    }
+
+    /// The client's time from the request, if the KDC echoed it.
+    pub fn ctime(&self) -> Option<SystemTime> {
+        self.ctime
+    }
+
+    /// The freshness token that the KDC returned in PA-AS-FRESHNESS (RFC 8070),
+    /// which proves to it that a PKINIT request was signed recently.
+    pub fn freshness_token(&self) -> Option<&[u8]> {
+        self.pa_as_freshness.as_deref()
+    }
+
+    /// The entry of the etype info to derive the key with. RFC 4120 5.2.7.5 The
+    /// KDC lists the entries in its order of preference, which isn't necessarily
+    /// by strength, so this takes the first that we support unless the client has
+    /// a preference of its own. Without one, a deprecated etype is only taken when
+    /// the KDC offers nothing else. Its [EtypeInfo2::iter_count] is the iteration
+    /// count that [Self::derive_key_with_preference] uses.
+    pub fn select_etype_info(&self, preference: &[EncryptionType]) -> Option<&EtypeInfo2> {
+        let supported = |einfo2: &&EtypeInfo2| is_supported_etype(einfo2.etype);
+
+        if preference.is_empty() {
+            return self
+                .etype_info2
+                .iter()
+                .filter(supported)
+                .find(|einfo2| !einfo2.etype.is_deprecated())
+                .or_else(|| self.etype_info2.iter().find(supported));
+        }
+
+        preference.iter().find_map(|etype| {
+            self.etype_info2
+                .iter()
+                .filter(supported)
+                .find(|einfo2| einfo2.etype == *etype)
+        })
+    }
+
+    /// Build the PA-ENC-TIMESTAMP with the key derived from the passphrase. The key
+    /// isn't kept, so when the AS-REP is to be decrypted as well, derive it with
+    /// [Self::derive_key] and use [Self::perform_enc_timestamp_with_key] instead.
+    pub fn perform_enc_timestamp(
+        &self,
+        passphrase: &str,
+        realm: &str,
+        cname: &str,
+        epoch_seconds: Duration,
+    ) -> Result<PreAuth, KrbError> {
+        let base_key = self.derive_key(passphrase, realm, cname)?;
+        self.enc_timestamp_with_key(&base_key, None, epoch_seconds)
+    }
+
+    /// Build the PA-ENC-TIMESTAMP as [Self::perform_enc_timestamp], naming the
+    /// version `kvno` of the client's key. When the key has been rotated, this lets
+    /// the KDC pick the key the timestamp was encrypted with.
+    pub fn perform_enc_timestamp_with_kvno(
+        &self,
+        passphrase: &str,
+        realm: &str,
+        cname: &str,
+        kvno: u32,
+        epoch_seconds: Duration,
+    ) -> Result<PreAuth, KrbError> {
+        let base_key = self.derive_key(passphrase, realm, cname)?;
+        self.enc_timestamp_with_key(&base_key, Some(kvno), epoch_seconds)
+    }
+
+    /// Build the PA-ENC-TIMESTAMP with a key that was not derived from a passphrase,
+    /// such as one from a keytab. The KDC must have offered the etype of the key.
+    pub fn perform_enc_timestamp_with_key(
+        &self,
+        base_key: &BaseKey,
+        kvno: Option<u32>,
+        epoch_seconds: Duration,
+    ) -> Result<PreAuth, KrbError> {
+        self.check_key_etype(base_key)?;
+        self.enc_timestamp_with_key(base_key, kvno, epoch_seconds)
+    }
+
+    /// Check that the KDC accepts preauthentication with `base_key`, which was not
+    /// derived from the etype info that it sent.
+    pub(crate) fn check_key_etype(&self, base_key: &BaseKey) -> Result<(), KrbError> {
+        if !self.enc_timestamp && !self.enc_challenge {
+            return Err(KrbError::PreAuthUnsupported);
+        }
+
+        // RFC 4120 5.2.7.5 Without etype info the KDC expects one of the etypes of
+        // the request, and there is no salt to derive anything with anyway.
+        let etype = base_key.etype();
+        if self.etype_info2.is_empty()
+            || self.etype_info2.iter().any(|einfo2| einfo2.etype == etype)
+        {
+            Ok(())
+        } else {
+            Err(KrbError::PreAuthKeyEtypeNotOffered(etype))
+        }
+    }
+
+    /// Derive the client's key from the passphrase, using the parameters of the
+    /// first etype that the KDC offered which we support. The key is both the key of
+    /// the preauthentication, given to [Self::perform_enc_timestamp_with_key], and
+    /// the reply key that the AS-REP is decrypted with, so it only needs to be
+    /// derived once.
+    pub fn derive_key(
+        &self,
+        passphrase: &str,
+        realm: &str,
+        cname: &str,
+    ) -> Result<BaseKey, KrbError> {
+        self.derive_key_with_preference(
+            passphrase,
+            realm,
+            cname,
+            &[],
+            &StringToKeyOptions::default(),
+        )
+    }
+
+    /// Derive the client's key from the passphrase as [Self::derive_key], choosing
+    /// among the etypes that the KDC offered in the order of `preference`. When
+    /// `preference` is empty, the order of the KDC is used. The iteration count that
+    /// the KDC asks for must meet the floor of `options`.
+    pub fn derive_key_with_preference(
+        &self,
+        passphrase: &str,
+        realm: &str,
+        cname: &str,
+        preference: &[EncryptionType],
+        options: &StringToKeyOptions,
+    ) -> Result<BaseKey, KrbError> {
+        self.preferred_etype_info(preference)?
+            .derive_key(passphrase, realm, cname, options)
+    }
+
+    /// As [Self::derive_key], without blocking the executor.
+    #[cfg(feature = "tokio-codec")]
+    pub async fn derive_key_async(
+        &self,
+        passphrase: &str,
+        realm: &str,
+        cname: &str,
+    ) -> Result<BaseKey, KrbError> {
+        self.derive_key_with_preference_async(
+            passphrase,
+            realm,
+            cname,
+            &[],
+            &StringToKeyOptions::default(),
+        )
+        .await
+    }
+
+    /// As [Self::derive_key_with_preference], without blocking the executor.
+    #[cfg(feature = "tokio-codec")]
+    pub async fn derive_key_with_preference_async(
+        &self,
+        passphrase: &str,
+        realm: &str,
+        cname: &str,
+        preference: &[EncryptionType],
+        options: &StringToKeyOptions,
+    ) -> Result<BaseKey, KrbError> {
+        self.preferred_etype_info(preference)?
+            .derive_key_async(passphrase, realm, cname, options)
+            .await
+    }
+
+    /// The etype info that the key is derived with, in the order of `preference`.
+    pub(crate) fn preferred_etype_info(
+        &self,
+        preference: &[EncryptionType],
+    ) -> Result<&EtypeInfo2, KrbError> {
+        if !self.enc_timestamp && !self.enc_challenge {
+            return Err(KrbError::PreAuthUnsupported);
+        }
+
+        if self.etype_info2.is_empty() {
+            return Err(KrbError::PreAuthMissingEtypeInfo2);
+        }
+
+        self.select_etype_info(preference)
+            .ok_or(KrbError::UnsupportedEncryption)
+    }
+
+    /// Build the PA-ENC-TIMESTAMP with an already derived key. The same key is
+    /// later needed to decrypt the AS-REP, so this avoids deriving it twice.
+    pub(crate) fn enc_timestamp_with_key(
+        &self,
+        base_key: &BaseKey,
+        kvno: Option<u32>,
+        epoch_seconds: Duration,
+    ) -> Result<PreAuth, KrbError> {
+        // https://www.rfc-editor.org/rfc/rfc4120#section-5.2.7.2
+        let key_usage = KeyUsage::AS_REQ_PA_ENC_TIMESTAMP;
+
+        let patimestamp = KerberosTime::from_unix_duration(epoch_seconds)
+            .map_err(|_| KrbError::PreAuthInvalidUnixTs)?;
+
+        let paenctsenc = PaEncTsEnc {
+            patimestamp,
+            pausec: None,
+        };
+
+        trace!(?paenctsenc);
+
+        let data = paenctsenc
+            .to_der()
+            .map_err(|_| KrbError::DerEncodePaEncTsEnc)?;
+
+        let enc_timestamp = base_key.encrypt_data(&data, key_usage)?.with_kvno(kvno);
+
+        let mut preauth = PreAuth::default();
+        preauth.push(PreAuthEntry::EncTimestamp(enc_timestamp));
+        self.echo_fx_cookie(&mut preauth);
+        self.echo_freshness_token(&mut preauth);
+        Ok(preauth)
+    }
+
+    /// Unarmor the reply to a FAST request with `nonce`, which yields the
+    /// preauthentication that the KDC requires of the client.
+    pub fn unarmor(self, armor: &FastArmor, nonce: u32) -> Result<KerberosPaRep, KrbError> {
+        let fast_rep = self.fast_rep.ok_or(KrbError::FastMissingReply)?;
+        let fast_rep = armor.unarmor_reply(&fast_rep, nonce)?;
+
+        let mut pa_rep = KerberosPaRep::try_from(fast_rep.padata)?;
+        pa_rep.pa_fx_fast = true;
+        pa_rep.client_realm = pa_rep.client_realm.or(self.client_realm);
+        pa_rep.client_name = pa_rep.client_name.or(self.client_name);
+        Ok(pa_rep)
+    }
+
+    /// Build the PA-ENCRYPTED-CHALLENGE of a FAST exchange, which replaces the
+    /// PA-ENC-TIMESTAMP. The timestamp is encrypted in a key derived from both the
+    /// armor key and the client's key.
+    pub(crate) fn encrypted_challenge_with_key(
+        &self,
+        armor: &FastArmor,
+        base_key: &BaseKey,
+        epoch_seconds: Duration,
+    ) -> Result<PreAuth, KrbError> {
+        if !self.enc_challenge {
+            return Err(KrbError::PreAuthUnsupported);
+        }
+
+        let patimestamp = KerberosTime::from_unix_duration(epoch_seconds)
+            .map_err(|_| KrbError::PreAuthInvalidUnixTs)?;
+
+        let data = PaEncTsEnc {
+            patimestamp,
+            pausec: None,
+        }
+        .to_der()
+        .map_err(|_| KrbError::DerEncodePaEncTsEnc)?;
+
+        // RFC 6113 5.4.6
+        let challenge_key =
+            armor
+                .armor_key
+                .krb_fx_cf2(base_key, "clientchallengearmor", "challengelongterm")?;
+        let enc_challenge = challenge_key.encrypt_data(&data, KeyUsage::ENC_CHALLENGE_CLIENT)?;
+
+        let mut preauth = PreAuth::default();
+        preauth.push(PreAuthEntry::EncChallenge(enc_challenge));
+        self.echo_fx_cookie(&mut preauth);
+        self.echo_freshness_token(&mut preauth);
+        Ok(preauth)
+    }
+
+    /// The OTP tokens that the KDC offered, which is only sent inside FAST.
+    pub fn otp_challenge(&self) -> Option<&OtpChallenge> {
+        self.otp_challenge.as_ref()
+    }
+
+    /// Build the PA-OTP-REQUEST (RFC 6560) of a FAST exchange, authenticating with
+    /// `otp_value` of `token`, which is one of those in [Self::otp_challenge]. The
+    /// `pin` is only given when the token requires it separately. As MIT and FreeIPA
+    /// do, the reply is then decrypted with the [armor key](FastArmor::armor_key).
+    pub fn perform_otp(
+        &self,
+        armor: &FastArmor,
+        token: &OtpToken,
+        otp_value: &str,
+        pin: Option<&str>,
+    ) -> Result<PreAuth, KrbError> {
+        let challenge = self
+            .otp_challenge
+            .as_ref()
+            .ok_or(KrbError::PreAuthUnsupported)?;
+
+        // RFC 6560 4.2 The nonce of the challenge proves that the request was made
+        // with the armor key.
+        let enc_request = OctetString::new(challenge.nonce.clone())
+            .and_then(|nonce| PaOtpEncRequest { nonce }.to_der())
+            .map_err(|_| KrbError::DerEncodePaOtpRequest)?;
+        let enc_data = armor
+            .armor_key
+            .encrypt_data(&enc_request, KeyUsage::PA_OTP_REQUEST)?;
+
+        let otp_request = PaOtpRequest {
+            flags: KerberosFlags(FlagSet::default()),
+            nonce: None,
+            enc_data: KdcEncryptedData::try_from(&enc_data)
+                .map_err(|_| KrbError::DerEncodePaOtpRequest)?,
+            hash_alg: None,
+            iteration_count: None,
+            otp_value: Some(
+                OctetString::new(otp_value.as_bytes())
+                    .map_err(|_| KrbError::DerEncodePaOtpRequest)?,
+            ),
+            otp_pin: pin.map(str::to_string),
+            otp_challenge: None,
+            otp_time: None,
+            otp_counter: None,
+            otp_format: None,
+            // The KDC is told which of its tokens this is.
+            otp_token_id: token.0.otp_token_id.clone(),
+            otp_alg_id: None,
+            otp_vendor: token.0.otp_vendor.clone(),
+        }
+        .to_der()
+        .map_err(|_| KrbError::DerEncodePaOtpRequest)?;
+
+        let mut preauth = PreAuth::default();
+        preauth.push(PreAuthEntry::Raw {
+            padata_type: PaDataType::PaOtpRequest as u32,
+            value: otp_request,
+        });
+        self.echo_fx_cookie(&mut preauth);
+        self.echo_freshness_token(&mut preauth);
+        Ok(preauth)
+    }
+
+    /// RFC 6113 5.2 The fx cookie must always be sent back to the KDC unchanged.
+    fn echo_fx_cookie(&self, preauth: &mut PreAuth) {
+        if let Some(pa_fx_cookie) = &self.pa_fx_cookie {
+            preauth.push(PreAuthEntry::FxCookie(pa_fx_cookie.clone()));
+        }
+    }
+
+    /// RFC 8070 2 The freshness token is sent back in the PA-AS-FRESHNESS of the
+    /// next request.
+    fn echo_freshness_token(&self, preauth: &mut PreAuth) {
+        if let Some(token) = &self.pa_as_freshness {
+            preauth.push(PreAuthEntry::AsFreshness(token.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        etype_info2_from_der, principal_name_from_str, salt_for_principal, AuthenticatedReply,
+        BaseKey, ClockOffset, Credential, EncAsRepPart, EncryptedData, EncryptionType, ErrorEData,
+        EtypeInfo2, FastArmor, FlagSet, HostAddress, KdcEncryptedData, KdcEncryptionKey,
+        KdcHostAddress, KdcOptions, KerberosAsRep, KerberosErrRep, KerberosErrorRep, KerberosKey,
+        KerberosPaRep, KerberosRequest, KerberosResponse, KerberosTgsRep, KeyUsage, KrbErrorCode,
+        KrbMessageType, LastReqEntry, LastReqType, OtpFlags, PaDataType, PacOptions, PreAuth,
+        PreAuthEntry, StringToKeyOptions, Ticket, TicketFlags,
+    };
+    use crate::asn1::ap_rep::{ApRep, TaggedApRep};
+    use crate::asn1::ap_req::TaggedApReq;
+    use crate::asn1::authenticator::TaggedAuthenticator;
+    use crate::asn1::authorization_data::AuthorizationData;
+    use crate::asn1::constants::checksum_types::ChecksumType;
+    use crate::asn1::enc_ap_rep_part::{EncApRepPart, TaggedEncApRepPart};
+    use crate::asn1::enc_kdc_rep_part::{EncKdcRepPart, TaggedEncKdcRepPart};
+    use crate::asn1::enc_ticket_part::{EncTicketPart as KdcEncTicketPart, TaggedEncTicketPart};
+    use crate::asn1::kdc_rep::KdcRep;
+    use crate::asn1::kerberos_flags::KerberosFlags;
+    use crate::asn1::kerberos_string::KerberosString;
+    use crate::asn1::kerberos_time::KerberosTime;
+    use crate::asn1::krb_error::{MethodData, TaggedKrbError};
+    use crate::asn1::krb_fast_rep::{KrbFastFinished, KrbFastResponse};
+    use crate::asn1::krb_fast_req::{KrbFastReq, PaFxFastRequest};
+    use crate::asn1::krb_kdc_rep::KrbKdcRep;
+    use crate::asn1::krb_kdc_req::KrbKdcReq;
+    use crate::asn1::last_req::{LastReq, LastReqItem};
+    use crate::asn1::pa_data::PaData;
+    use crate::asn1::pa_enc_ts_enc::PaEncTsEnc;
+    use crate::asn1::pa_otp_request::{PaOtpEncRequest, PaOtpRequest};
+    use crate::asn1::transited_encoding::TransitedEncoding;
+    use crate::asn1::{Ia5String, OctetString};
+    use crate::constants::AES_256_KEY_LEN;
+    use crate::crypto::{cipher_for, derive_key_external_salt_aes256_cts_hmac_sha1_96};
+    use crate::error::KrbError;
+    use crate::tests::{AS_REP_SAMPLE, RESPONSE_TOO_BIG_SAMPLE};
+    use der::{Decode, Encode};
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    // These only need the message types, so they also run in a build without any of
+    // the transport features.
+
+    #[test]
+    fn test_as_req_round_trip() {
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .kdc_options(KdcOptions::Renewable | KdcOptions::Canonicalize)
+        .build()
+        .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+
+        let KerberosRequest::AsReq(as_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(as_req.client_name, "testuser");
+        assert_eq!(as_req.service_name, "krbtgt/EXAMPLE.COM");
+        assert_eq!(as_req.realm, "EXAMPLE.COM");
+        assert_eq!(
+            as_req.kdc_options(),
+            KdcOptions::Renewable | KdcOptions::Canonicalize
+        );
+    }
+
+    #[test]
+    fn test_request_accessors() {
+        // Whole seconds, as that is all that KerberosTime carries.
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Clock is before the epoch")
+            .as_secs();
+        let from = UNIX_EPOCH + Duration::from_secs(now + 60);
+        let until = from + Duration::from_secs(3600);
+        let renew = until + Duration::from_secs(86400);
+
+        let mut preauth = PreAuth::default();
+        preauth.push(PreAuthEntry::FxCookie(b"cookie".to_vec()));
+
+        let der_bytes =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .from(Some(from))
+                .renewable(true)
+                .renew(Some(renew))
+                .nonce(42)
+                .add_preauthentication(preauth)
+                .build()
+                .and_then(|as_req| as_req.to_der().map_err(|_| KrbError::DerEncodeKdcReq))
+                .expect("Failed to build request");
+
+        let as_req = KerberosRequest::from_der(&der_bytes).expect("Failed to decode request");
+        // The parsed request can be kept while the reply is built.
+        let as_req = as_req.clone();
+        assert_eq!(as_req.client_name(), Some("testuser"));
+        assert_eq!(as_req.service_name(), "krbtgt/EXAMPLE.COM");
+        assert_eq!(as_req.from(), Some(from));
+        assert_eq!(as_req.until(), until);
+        assert_eq!(as_req.renew_until(), Some(renew));
+        assert_eq!(as_req.nonce(), 42);
+        assert_eq!(
+            as_req.etypes(),
+            &[
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                EncryptionType::AES128_CTS_HMAC_SHA1_96
+            ]
+        );
+        let [PreAuthEntry::FxCookie(cookie)] = as_req
+            .preauth()
+            .map(PreAuth::entries)
+            .expect("Missing preauth")
+        else {
+            unreachable!();
+        };
+        assert_eq!(cookie, b"cookie");
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        let der_bytes = KerberosRequest::build_tgsreq("host/localhost", until)
+            .from(Some(from))
+            .renew(Some(renew))
+            .build(&tgt)
+            .and_then(|tgs_req| tgs_req.to_der().map_err(|_| KrbError::DerEncodeKdcReq))
+            .expect("Failed to build request");
+
+        let tgs_req = KerberosRequest::from_der(&der_bytes).expect("Failed to decode request");
+        // The client of a TGS-REQ is only known from its TGT.
+        assert_eq!(tgs_req.client_name(), None);
+        assert_eq!(tgs_req.service_name(), "host/localhost");
+        assert_eq!(tgs_req.from(), Some(from));
+        assert_eq!(tgs_req.until(), until);
+        assert_eq!(tgs_req.renew_until(), Some(renew));
+        assert_eq!(
+            tgs_req.etypes(),
+            &[
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                EncryptionType::AES128_CTS_HMAC_SHA1_96
+            ]
+        );
+        assert!(tgs_req.preauth().is_none());
+    }
+
+    #[test]
+    fn test_as_req_ticket_options() {
+        let until = SystemTime::now() + Duration::from_secs(3600);
+        let renew = Some(until + Duration::from_secs(86400));
+
+        let as_req =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .renew(renew)
+                .forwardable(true)
+                .proxiable(true)
+                .renewable(true)
+                .canonicalize(true)
+                // Options can be cleared again before the request is built.
+                .proxiable(false)
+                .build()
+                .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+
+        // Check the bits that were actually encoded, not just what the request holds.
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(
+            kdc_req.req_body.kdc_options.0,
+            KdcOptions::Forwardable | KdcOptions::Renewable | KdcOptions::Canonicalize
+        );
+
+        // Renewable tickets must say how long they can be renewed for.
+        let err =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .renewable(true)
+                .build()
+                .expect_err("Built a renewable request without a renew time");
+        assert!(matches!(err, KrbError::MissingRenewTime));
+
+        // Postdated tickets must say when they start.
+        let err =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .postdated(true)
+                .build()
+                .expect_err("Built a postdated request without a start time");
+        assert!(matches!(err, KrbError::MissingStartTime));
+    }
+
+    #[test]
+    fn test_req_postdated() {
+        let from = SystemTime::now() + Duration::from_secs(3600);
+        let until = from + Duration::from_secs(3600);
+
+        let as_req =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .from(Some(from))
+                .build()
+                .expect("Failed to build request");
+
+        let KerberosRequest::AsReq(as_req) = as_req else {
+            unreachable!();
+        };
+        assert_eq!(
+            as_req.kdc_options(),
+            KdcOptions::Postdated | KdcOptions::AllowPostdate
+        );
+
+        // A start time that has already passed is just when the ticket starts.
+        let as_req =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .from(Some(SystemTime::now() - Duration::from_secs(60)))
+                .build()
+                .expect("Failed to build request");
+
+        let KerberosRequest::AsReq(as_req) = as_req else {
+            unreachable!();
+        };
+        assert!(as_req.kdc_options().is_empty());
+
+        // The ticket must end after it starts.
+        let err =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .from(Some(until))
+                .build()
+                .expect_err("Built a request that ends before it starts");
+        assert!(matches!(err, KrbError::InvalidStartTime));
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::from(TicketFlags::MayPostdate),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+        assert!(tgt.may_postdate());
+        assert!(!tgt.requires_validation());
+
+        let tgs_req = KerberosRequest::build_tgsreq("host/localhost", until)
+            .from(Some(from))
+            .build(&tgt)
+            .expect("Failed to build request");
+
+        let KerberosRequest::TgsReq(tgs_req) = tgs_req else {
+            unreachable!();
+        };
+        assert_eq!(tgs_req.kdc_options(), FlagSet::from(KdcOptions::Postdated));
+    }
+
+    #[test]
+    fn test_as_req_preauth_entries() {
+        let enc_timestamp = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x42; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key")
+        .encrypt_data(b"timestamp", KeyUsage::AS_REQ_PA_ENC_TIMESTAMP)
+        .expect("Failed to encrypt");
+
+        let mut preauth = PreAuth::default();
+        preauth.push(PreAuthEntry::Raw {
+            padata_type: 151,
+            value: vec![0x30, 0x00],
+        });
+        preauth.push(PreAuthEntry::EncTimestamp(enc_timestamp));
+        preauth.push(PreAuthEntry::FxCookie(b"cookie".to_vec()));
+        preauth.push(PreAuthEntry::Raw {
+            padata_type: 151,
+            value: vec![0x30, 0x03, 0x02, 0x01, 0x05],
+        });
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .add_preauthentication(preauth)
+        .pac_option(PacOptions::Claims, true)
+        .build()
+        .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+
+        let decoded = KerberosRequest::from_der(&der_bytes).expect("Failed to decode request");
+        let KerberosRequest::AsReq(as_req) = &decoded else {
+            unreachable!();
+        };
+
+        // Unknown and repeated types keep their order, and the PAC options are
+        // held apart.
+        let entries = as_req.preauth().expect("Missing preauth").entries();
+        assert_eq!(entries.len(), 4);
+        assert!(matches!(
+            &entries[0],
+            PreAuthEntry::Raw { padata_type: 151, value } if value == &[0x30, 0x00]
+        ));
+        assert!(matches!(&entries[1], PreAuthEntry::EncTimestamp(_)));
+        assert!(matches!(&entries[2], PreAuthEntry::FxCookie(cookie) if cookie == b"cookie"));
+        assert!(matches!(
+            &entries[3],
+            PreAuthEntry::Raw { padata_type: 151, value } if value == &[0x30, 0x03, 0x02, 0x01, 0x05]
+        ));
+        assert_eq!(as_req.pac_options(), Some(PacOptions::Claims.into()));
+
+        // What was decoded is sent on exactly as it was received.
+        assert_eq!(
+            decoded.to_der().expect("Failed to encode request"),
+            der_bytes
+        );
+    }
+
+    #[test]
+    fn test_as_rep_req_enc_pa_rep() {
+        let reply_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x42; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .req_enc_pa_rep(true)
+        .build()
+        .expect("Failed to build request");
+
+        let mut request = as_req.to_der().expect("Failed to encode request");
+
+        // The request carries an empty PA-REQ-ENC-PA-REP.
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&request).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        let padata = kdc_req.padata.expect("Missing padata");
+        assert_eq!(padata.len(), 1);
+        assert_eq!(padata[0].padata_type, 149);
+        assert!(padata[0].padata_value.as_bytes().is_empty());
+
+        let KerberosRequest::AsReq(decoded) =
+            KerberosRequest::from_der(&request).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert!(decoded.req_enc_pa_rep());
+        assert!(decoded
+            .preauth()
+            .is_some_and(|preauth| preauth.entries().is_empty()));
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        // Encrypt an enc-part the way the KDC would, with the checksum of the request.
+        let time = KerberosTime::from_unix_duration(Duration::from_secs(1_700_000_000))
+            .expect("Invalid time");
+        let reply = |encrypted_pa_data: Option<Vec<PaData>>| {
+            let enc_part = TaggedEncKdcRepPart::EncAsRepPart(EncKdcRepPart {
+                key: KdcEncryptionKey::try_from(
+                    &BaseKey::from_bytes(
+                        EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                        &[0x33; AES_256_KEY_LEN],
+                    )
+                    .expect("Invalid key"),
+                )
+                .expect("Failed to encode session key"),
+                last_req: Vec::new(),
+                nonce: decoded.nonce(),
+                key_expiration: None,
+                flags: KerberosFlags(TicketFlags::EncPaRep.into()),
+                auth_time: time,
+                start_time: None,
+                end_time: time,
+                renew_till: None,
+                server_realm: KerberosString(Ia5String::new("EXAMPLE.COM").expect("Invalid realm")),
+                server_name: principal_name_from_str(2, "krbtgt/EXAMPLE.COM")
+                    .expect("Invalid name"),
+                client_addresses: None,
+                encrypted_pa_data,
+            })
+            .to_der()
+            .expect("Failed to encode enc-part");
+
+            KerberosAsRep {
+                client_realm: as_rep.client_realm.clone(),
+                client_name: as_rep.client_name.clone(),
+                client_name_type: as_rep.client_name_type,
+                ticket: as_rep.ticket.clone(),
+                enc_part: reply_key
+                    .encrypt_data(&enc_part, KeyUsage::AS_REP_ENC_PART)
+                    .expect("Failed to encrypt enc-part"),
+                fast_rep: None,
+                strengthen_key: None,
+                pk_as_rep: None,
+                etype_info2: Vec::new(),
+                other_padata: Vec::new(),
+            }
+        };
+
+        let cksum = reply_key
+            .checksum(&request, KeyUsage::AS_REQ)
+            .and_then(|cksum| cksum.to_der().map_err(|_| KrbError::DerEncodeKdcReq))
+            .expect("Failed to checksum request");
+        let encrypted_pa_data = Some(vec![PaData {
+            padata_type: 149,
+            padata_value: OctetString::new(cksum).expect("Invalid checksum"),
+        }]);
+
+        let tgt = reply(encrypted_pa_data.clone())
+            .into_verified_reply(&reply_key, &request)
+            .expect("Failed to verify reply");
+        assert!(tgt.request_verified());
+        assert!(!tgt.kdc_supports_fast());
+
+        // A KDC that supports FAST says so along with the checksum.
+        let mut with_fast = encrypted_pa_data.clone().unwrap_or_default();
+        with_fast.push(PaData {
+            padata_type: PaDataType::PaFxFast as u32,
+            padata_value: OctetString::new(Vec::new()).expect("Invalid padata"),
+        });
+        let tgt = reply(Some(with_fast))
+            .into_verified_reply(&reply_key, &request)
+            .expect("Failed to verify reply");
+        assert!(tgt.request_verified());
+        assert!(tgt.kdc_supports_fast());
+
+        // Not checking the request doesn't verify it.
+        let tgt = reply(encrypted_pa_data.clone())
+            .into_authenticated_reply(&reply_key)
+            .expect("Failed to decrypt reply");
+        assert!(!tgt.request_verified());
+
+        // The checksum no longer matches a request that was tampered with.
+        let last = request.len() - 1;
+        request[last] ^= 0x01;
+        let err = reply(encrypted_pa_data)
+            .into_verified_reply(&reply_key, &request)
+            .expect_err("Modified request was accepted");
+        assert!(matches!(err, KrbError::ReqEncPaRepMismatch));
+
+        // The enc-pa-rep flag promises a checksum.
+        let err = reply(None)
+            .into_verified_reply(&reply_key, &request)
+            .expect_err("Missing checksum was accepted");
+        assert!(matches!(err, KrbError::ReqEncPaRepMissing));
+    }
+
+    #[test]
+    fn test_req_unencodable() {
+        // GeneralizedTime only reaches the year 9999.
+        let year_99999 = UNIX_EPOCH + Duration::from_secs(98_029 * 365 * 86_400);
+        let until = SystemTime::now() + Duration::from_secs(3600);
+
+        let err = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            year_99999,
+        )
+        .build()
+        .expect_err("Built a request ending in the year 99999");
+        assert!(matches!(err, KrbError::InvalidTime));
+
+        let err =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .renew(Some(year_99999))
+                .renewable(true)
+                .build()
+                .expect_err("Built a request renewable until the year 99999");
+        assert!(matches!(err, KrbError::InvalidTime));
+
+        for (client_name, service_name, realm) in [
+            ("", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM"),
+            ("host/", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM"),
+            ("testuser", "krbtgt//EXAMPLE.COM", "EXAMPLE.COM"),
+            ("testuser", "krbtgt/EXAMPLE.COM", ""),
+            ("testüser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM"),
+        ] {
+            let err = KerberosRequest::build_asreq(client_name, service_name, realm, until)
+                .build()
+                .expect_err("Built a request with an invalid name");
+            assert!(matches!(err, KrbError::InvalidName(_)));
+        }
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        let err = KerberosRequest::build_tgsreq("host/localhost", year_99999)
+            .build(&tgt)
+            .expect_err("Built a request ending in the year 99999");
+        assert!(matches!(err, KrbError::InvalidTime));
+
+        let err = KerberosRequest::build_tgsreq("host/@EXAMPLE.COM", until)
+            .build(&tgt)
+            .expect_err("Built a request with an empty name component");
+        assert!(matches!(err, KrbError::InvalidName(_)));
+    }
+
+    #[test]
+    fn test_req_lifetime() {
+        let now = SystemTime::now();
+        let until = now + Duration::from_secs(3600);
+        let build_asreq = |until| {
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+        };
+
+        let err = build_asreq(now - Duration::from_secs(60))
+            .build()
+            .expect_err("Built a request that has already ended");
+        assert!(matches!(err, KrbError::EndTimeInPast));
+
+        // Ending about now is allowed for.
+        build_asreq(now).build().expect("Failed to build request");
+
+        let err = build_asreq(until)
+            .from(Some(until + Duration::from_secs(60)))
+            .build()
+            .expect_err("Built a request that ends before it starts");
+        assert!(matches!(err, KrbError::InvalidStartTime));
+
+        let err = build_asreq(until)
+            .renewable(true)
+            .renew(Some(until - Duration::from_secs(60)))
+            .build()
+            .expect_err("Built a request renewable until before it ends");
+        assert!(matches!(err, KrbError::RenewTimeBeforeEndTime));
+
+        // Renewable until exactly when it ends is the least it may be.
+        build_asreq(until)
+            .renewable(true)
+            .renew(Some(until))
+            .build()
+            .expect("Failed to build request");
+
+        // Unchecked, the KDC is left to refuse these, but what can't be encoded
+        // still fails.
+        build_asreq(now - Duration::from_secs(60))
+            .from(Some(now))
+            .renewable(true)
+            .renew(Some(now - Duration::from_secs(3600)))
+            .build_unchecked()
+            .expect("Failed to build unchecked request");
+
+        let year_99999 = UNIX_EPOCH + Duration::from_secs(98_029 * 365 * 86_400);
+        let err = build_asreq(year_99999)
+            .build_unchecked()
+            .expect_err("Built a request ending in the year 99999");
+        assert!(matches!(err, KrbError::InvalidTime));
+    }
+
+    #[test]
+    fn test_req_renewable_ok_disable_transited_check() {
+        let until = SystemTime::now() + Duration::from_secs(3600);
+
+        let as_req =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .renewable_ok(true)
+                .disable_transited_check(true)
+                .build()
+                .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        // renewable-ok is bit 27 and disable-transited-check bit 26.
+        assert_eq!(
+            kdc_req
+                .req_body
+                .kdc_options
+                .to_der()
+                .expect("Failed to encode options"),
+            [0x03, 0x05, 0x00, 0x00, 0x00, 0x00, 0x30]
+        );
+
+        let KerberosRequest::AsReq(as_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert!(as_req.renewable_ok());
+        assert!(as_req.disable_transited_check());
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        // Only renewable-ok, which is bit 27 alone.
+        let tgs_req = KerberosRequest::build_tgsreq("host/localhost", until)
+            .renewable_ok(true)
+            .disable_transited_check(true)
+            .disable_transited_check(false)
+            .build(&tgt)
+            .expect("Failed to build request");
+
+        let der_bytes = tgs_req.to_der().expect("Failed to encode request");
+
+        let KrbKdcReq::TgsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(
+            kdc_req
+                .req_body
+                .kdc_options
+                .to_der()
+                .expect("Failed to encode options"),
+            [0x03, 0x05, 0x00, 0x00, 0x00, 0x00, 0x10]
+        );
+
+        let KerberosRequest::TgsReq(tgs_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert!(tgs_req.renewable_ok());
+        assert!(!tgs_req.disable_transited_check());
+    }
+
+    #[test]
+    fn test_req_anonymous() {
+        let until = SystemTime::now() + Duration::from_secs(3600);
+
+        let as_req =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .anonymous(true)
+                .build()
+                .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        // request-anonymous is bit 16.
+        assert_eq!(
+            kdc_req
+                .req_body
+                .kdc_options
+                .to_der()
+                .expect("Failed to encode options"),
+            [0x03, 0x05, 0x00, 0x00, 0x00, 0x80, 0x00]
+        );
+        let cname = kdc_req.req_body.cname.expect("Missing cname");
+        assert_eq!(cname.name_type, NT_WELLKNOWN);
+        assert_eq!(cname.name_string.len(), 2);
+
+        let KerberosRequest::AsReq(as_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert!(as_req.anonymous());
+        assert_eq!(as_req.client_name, ANONYMOUS_PRINCIPAL);
+    }
+
+    #[test]
+    fn test_as_req_multi_component_name() {
+        let as_req = KerberosRequest::build_asreq(
+            "host/node1.example.com",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build()
+        .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+
+        // NT-PRINCIPAL, with one component either side of the separator.
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        let cname = kdc_req.req_body.cname.expect("Missing cname");
+        assert_eq!(cname.name_type, 1);
+        let components: Vec<_> = cname.name_string.iter().map(|c| c.as_str()).collect();
+        assert_eq!(components, ["host", "node1.example.com"]);
+
+        let KerberosRequest::AsReq(as_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert!(!as_req.enterprise);
+        assert_eq!(as_req.client_name, "host/node1.example.com");
+    }
+
+    #[test]
+    fn test_as_req_enterprise_name() {
+        let as_req = KerberosRequest::build_asreq(
+            "user@corp.example.com",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .enterprise(true)
+        .canonicalize(true)
+        .build()
+        .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+
+        // NT-ENTERPRISE, with the whole UPN as the only component.
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        let cname = kdc_req.req_body.cname.expect("Missing cname");
+        assert_eq!(cname.name_type, 10);
+        assert_eq!(cname.name_string.len(), 1);
+        assert_eq!(cname.name_string[0].as_str(), "user@corp.example.com");
+
+        let KerberosRequest::AsReq(as_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert!(as_req.enterprise);
+        assert_eq!(as_req.client_name, "user@corp.example.com");
+        assert!(as_req.kdc_options().contains(KdcOptions::Canonicalize));
+    }
+
+    #[test]
+    fn test_as_req_pac_options() {
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .pac_option(PacOptions::Claims, true)
+        .pac_option(PacOptions::BranchAware, true)
+        .pac_option(PacOptions::BranchAware, false)
+        .build()
+        .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        let padata = kdc_req.padata.expect("Missing padata");
+        assert_eq!(padata.len(), 1);
+        assert_eq!(padata[0].padata_type, 167);
+        assert_eq!(
+            padata[0].padata_value.as_bytes(),
+            [0x30, 0x09, 0xa0, 0x07, 0x03, 0x05, 0x00, 0x80, 0x00, 0x00, 0x00]
+        );
+
+        let KerberosRequest::AsReq(as_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(
+            as_req.pac_options(),
+            Some(FlagSet::from(PacOptions::Claims))
+        );
+
+        // Without any options set, no PA-PAC-OPTIONS is sent at all.
+        let KerberosRequest::AsReq(as_req) = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build()
+        .expect("Failed to build request") else {
+            unreachable!();
+        };
+        assert_eq!(as_req.pac_options(), None);
+        assert!(as_req
+            .to_asn()
+            .expect("Failed to encode request")
+            .padata
+            .is_none());
+    }
+
+    #[test]
+    fn test_as_req_nonce() {
+        let build = |nonce: Option<u32>| {
+            let builder = KerberosRequest::build_asreq(
+                "testuser",
+                "krbtgt/EXAMPLE.COM",
+                "EXAMPLE.COM",
+                SystemTime::now() + Duration::from_secs(3600),
+            );
+            let builder = match nonce {
+                Some(nonce) => builder.nonce(nonce),
+                None => builder,
+            };
+            let KerberosRequest::AsReq(as_req) = builder.build().expect("Failed to build request")
+            else {
+                unreachable!();
+            };
+            as_req
+        };
+
+        let as_req = build(Some(0x1234_5678));
+        assert_eq!(as_req.nonce(), 0x1234_5678);
+
+        let der_bytes = KerberosRequest::AsReq(as_req)
+            .to_der()
+            .expect("Failed to encode request");
+        // nonce [7] UInt32
+        let nonce_der = [0xa7, 0x06, 0x02, 0x04, 0x12, 0x34, 0x56, 0x78];
+        assert!(der_bytes.windows(nonce_der.len()).any(|w| w == nonce_der));
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(kdc_req.req_body.nonce, 0x1234_5678);
+
+        // MIT KRB5 rejects nonces that don't fit an i32.
+        assert_eq!(build(Some(u32::MAX)).nonce(), 0x7fff_ffff);
+
+        // Without an override each request gets its own nonce.
+        let (a, b) = (build(None), build(None));
+        assert_ne!(a.nonce(), b.nonce());
+        assert!(a.nonce() <= 0x7fff_ffff);
+        assert!(b.nonce() <= 0x7fff_ffff);
+    }
+
+    #[test]
+    fn test_as_req_etypes() {
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .etypes(vec![
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            EncryptionType::AES128_CTS_HMAC_SHA1_96,
+            EncryptionType::RC4_HMAC,
+        ])
+        .build()
+        .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+
+        // The order of preference is kept on the wire.
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(kdc_req.req_body.etype, [18, 17, 23]);
+
+        // And the KDC sees the whole offer, not only what we can decrypt.
+        let KerberosRequest::AsReq(as_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(
+            as_req.etypes(),
+            [
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                EncryptionType::AES128_CTS_HMAC_SHA1_96,
+                EncryptionType::RC4_HMAC,
+            ]
+        );
+
+        // Nothing that we could decrypt a reply with.
+        let err = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .etypes(vec![EncryptionType::RC4_HMAC])
+        .build()
+        .expect_err("Request without a supported etype was built");
+        assert!(matches!(err, KrbError::UnsupportedEncryption));
+    }
+
+    #[test]
+    fn test_as_req_raw_etypes() {
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .etypes(vec![
+            EncryptionType::RC4_HMAC,
+            EncryptionType::AES128_CTS_HMAC_SHA1_96,
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+        ])
+        .build()
+        .expect("Failed to build request");
+
+        // Offer an etype that isn't known between the others.
+        let KrbKdcReq::AsReq(mut kdc_req) =
+            KrbKdcReq::from_der(&as_req.to_der().expect("Failed to encode request"))
+                .expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(kdc_req.req_body.etype, [23, 17, 18]);
+        kdc_req.req_body.etype = vec![23, -4242, 17, 18];
+        let der_bytes = KrbKdcReq::AsReq(kdc_req)
+            .to_der()
+            .expect("Failed to encode request");
+
+        let KerberosRequest::AsReq(as_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(as_req.raw_etypes(), [23, -4242, 17, 18]);
+        assert_eq!(
+            as_req.etypes(),
+            [
+                EncryptionType::RC4_HMAC,
+                EncryptionType::AES128_CTS_HMAC_SHA1_96,
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            ]
+        );
+        assert_eq!(
+            as_req.supported_etypes(),
+            [
+                EncryptionType::AES128_CTS_HMAC_SHA1_96,
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            ]
+        );
+
+        // Nothing of the offer is lost when the request is encoded again.
+        let reencoded = KerberosRequest::AsReq(as_req)
+            .to_der()
+            .expect("Failed to encode request");
+        assert_eq!(reencoded, der_bytes);
+    }
+
+    #[test]
+    fn test_unsupported_etype() {
+        // The KDC may reply with any etype that was offered, which must still decode.
+        let enc_data = KdcEncryptedData {
+            etype: EncryptionType::RC4_HMAC as i32,
+            kvno: Some(2),
+            cipher: OctetString::new(vec![0xab; 48]).expect("Failed to build cipher"),
+        };
+
+        let decoded = EncryptedData::try_from(enc_data.clone()).expect("Failed to decode");
+        assert!(matches!(
+            decoded,
+            EncryptedData::Unsupported {
+                etype: 23,
+                kvno: Some(2),
+                ..
+            }
+        ));
+        assert_eq!(
+            KdcEncryptedData::try_from(&decoded).expect("Failed to encode"),
+            enc_data
+        );
+
+        let key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x42; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        assert!(matches!(
+            decoded.decrypt_data(&key, KeyUsage::AS_REP_ENC_PART),
+            Err(KrbError::UnsupportedEncryption)
+        ));
+    }
+
+    #[test]
+    fn test_encrypted_data_kvno() {
+        let key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x42; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+
+        for kvno in [None, Some(3)] {
+            let enc_data = key
+                .encrypt_data(b"data", KeyUsage::AS_REP_ENC_PART)
+                .expect("Failed to encrypt")
+                .with_kvno(kvno);
+
+            let der_bytes = KdcEncryptedData::try_from(&enc_data)
+                .and_then(|enc_data| enc_data.to_der())
+                .expect("Failed to encode");
+            let decoded = KdcEncryptedData::from_der(&der_bytes)
+                .map_err(|_| KrbError::DerDecodePaData)
+                .and_then(EncryptedData::try_from)
+                .expect("Failed to decode");
+
+            assert_eq!(decoded.kvno(), kvno);
+            assert_eq!(
+                decoded
+                    .decrypt_data_with_kvno(
+                        &KerberosKey::new(key.clone(), 3),
+                        KeyUsage::AS_REP_ENC_PART,
+                    )
+                    .expect("Failed to decrypt"),
+                b"data"
+            );
+            let other_version = decoded.decrypt_data_with_kvno(
+                &KerberosKey::new(key.clone(), 4),
+                KeyUsage::AS_REP_ENC_PART,
+            );
+            match kvno {
+                Some(_) => assert!(matches!(
+                    other_version,
+                    Err(KrbError::KeyVersionMismatch(3, 4))
+                )),
+                // Without a kvno there is nothing to tell the versions apart.
+                None => assert!(other_version.is_ok()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_encrypted_data_usage_separation() {
+        let key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x42; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        let usages = [
+            KeyUsage::KDC_REP_TICKET,
+            KeyUsage::AS_REP_ENC_PART,
+            KeyUsage::TGS_REQ_PA_TGS_REQ_AUTHENTICATOR,
+            KeyUsage::AP_REQ_AUTHENTICATOR,
+            KeyUsage::AP_REP_ENC_PART,
+            KeyUsage::KRB_PRIV,
+            KeyUsage::KRB_CRED,
+        ];
+        // The same plaintext in each usage.
+        let plaintext = [0x30, 0x03, 0x02, 0x01, 0x05];
+
+        let encrypted: Vec<_> = usages
+            .iter()
+            .map(|key_usage| {
+                EncryptedData::encrypt(
+                    &key,
+                    *key_usage,
+                    &plaintext,
+                    EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                )
+                .expect("Failed to encrypt")
+            })
+            .collect();
+
+        for (i, (key_usage, enc_data)) in usages.iter().zip(&encrypted).enumerate() {
+            // Through the DER of the message, as a peer would receive it.
+            let der_bytes = enc_data.to_der().expect("Failed to encode");
+            let decoded = EncryptedData::from_der(&der_bytes).expect("Failed to decode");
+            assert_eq!(
+                decoded
+                    .decrypt_data(&key, *key_usage)
+                    .expect("Failed to decrypt"),
+                plaintext
+            );
+
+            for (j, other_usage) in usages.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                assert_ne!(enc_data.data(), encrypted[j].data());
+                assert!(matches!(
+                    decoded.decrypt_data(&key, *other_usage),
+                    Err(KrbError::MessageAuthenticationFailed)
+                ));
+            }
+        }
+
+        // The key must be of the etype that the peer expects.
+        assert!(matches!(
+            EncryptedData::encrypt(
+                &key,
+                KeyUsage::KRB_PRIV,
+                &plaintext,
+                EncryptionType::AES128_CTS_HMAC_SHA1_96
+            ),
+            Err(KrbError::InvalidEncryptionKey)
+        ));
+        assert!(matches!(
+            EncryptedData::from_der(&plaintext),
+            Err(KrbError::DerDecodeEncryptedData)
+        ));
+    }
+
+    #[test]
+    fn test_pa_enc_timestamp_kvno() {
+        let pa_rep = KerberosPaRep {
+            offered: Vec::new(),
+            pa_fx_fast: false,
+            enc_timestamp: true,
+            enc_challenge: false,
+            fast_rep: None,
+            pa_fx_cookie: None,
+            pa_as_freshness: None,
+            otp_challenge: None,
+            etype_info2: vec![EtypeInfo2 {
+                etype: EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                salt: Some("EXAMPLE.COMtestuser".to_string()),
+                s2kparams: None,
+            }],
+            client_realm: None,
+            client_name: None,
+            stime: None,
+            ctime: None,
+            krb_error: None,
+        };
+        let epoch_seconds = Duration::from_secs(1_700_000_000);
+
+        let with_kvno = pa_rep
+            .perform_enc_timestamp_with_kvno(
+                "password",
+                "EXAMPLE.COM",
+                "testuser",
+                2,
+                epoch_seconds,
+            )
+            .expect("Failed to build preauth");
+        let without_kvno = pa_rep
+            .perform_enc_timestamp("password", "EXAMPLE.COM", "testuser", epoch_seconds)
+            .expect("Failed to build preauth");
+
+        for (preauth, kvno) in [(with_kvno, Some(2)), (without_kvno, None)] {
+            // The kvno survives encoding the padata and decoding it again.
+            let padata = PaData::try_from(&preauth.entries()[0]).expect("Failed to encode");
+            let PreAuthEntry::EncTimestamp(enc_data) =
+                PreAuthEntry::try_from(padata).expect("Failed to decode")
+            else {
+                unreachable!();
+            };
+            assert_eq!(enc_data.kvno(), kvno);
+        }
+    }
+
+    #[test]
+    fn test_pa_enc_timestamp_with_key() {
+        // An aes128 key given as an aes256 one is refused rather than padded.
+        assert!(matches!(
+            BaseKey::from_bytes(EncryptionType::AES256_CTS_HMAC_SHA1_96, &[0x42; 16]),
+            Err(KrbError::InvalidKeyLength(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                16
+            ))
+        ));
+        assert!(matches!(
+            BaseKey::from_bytes(EncryptionType::AES128_CTS_HMAC_SHA256_128, &[0x42; 32]),
+            Err(KrbError::InvalidKeyLength(
+                EncryptionType::AES128_CTS_HMAC_SHA256_128,
+                32
+            ))
+        ));
+        assert!(matches!(
+            BaseKey::from_bytes(EncryptionType::RC4_HMAC, &[0x42; 16]),
+            Err(KrbError::UnsupportedEncryption)
+        ));
+
+        let mut pa_rep = KerberosPaRep {
+            offered: Vec::new(),
+            pa_fx_fast: false,
+            enc_timestamp: true,
+            enc_challenge: false,
+            fast_rep: None,
+            pa_fx_cookie: None,
+            pa_as_freshness: None,
+            otp_challenge: None,
+            etype_info2: vec![EtypeInfo2 {
+                etype: EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                salt: Some("EXAMPLE.COMtestuser".to_string()),
+                s2kparams: None,
+            }],
+            client_realm: None,
+            client_name: None,
+            stime: None,
+            ctime: None,
+            krb_error: None,
+        };
+        let epoch_seconds = Duration::from_secs(1_700_000_000);
+
+        // The same key as derived from the password, as a keytab would hold it.
+        let derived = pa_rep
+            .derive_key("password", "EXAMPLE.COM", "testuser")
+            .expect("Failed to derive key");
+        let key = BaseKey::from_bytes(EncryptionType::AES256_CTS_HMAC_SHA1_96, derived.as_slice())
+            .expect("Failed to build key");
+
+        let preauth = pa_rep
+            .perform_enc_timestamp_with_key(&key, Some(3), epoch_seconds)
+            .expect("Failed to build preauth");
+        let [PreAuthEntry::EncTimestamp(enc_data)] = preauth.entries() else {
+            unreachable!();
+        };
+        assert_eq!(enc_data.kvno(), Some(3));
+
+        let data = enc_data
+            .decrypt_data(&derived, KeyUsage::AS_REQ_PA_ENC_TIMESTAMP)
+            .expect("Failed to decrypt timestamp");
+        let paenctsenc = PaEncTsEnc::from_der(&data).expect("Failed to decode timestamp");
+        assert_eq!(paenctsenc.patimestamp.to_unix_duration(), epoch_seconds);
+
+        // A KDC that only offers other etypes doesn't have a key of ours.
+        pa_rep.etype_info2[0].etype = EncryptionType::AES128_CTS_HMAC_SHA1_96;
+        assert!(matches!(
+            pa_rep.perform_enc_timestamp_with_key(&key, None, epoch_seconds),
+            Err(KrbError::PreAuthKeyEtypeNotOffered(
+                EncryptionType::AES128_CTS_HMAC_SHA1_96
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_pa_rep_reply_key() {
+        let pa_rep = KerberosPaRep {
+            offered: Vec::new(),
+            pa_fx_fast: false,
+            enc_timestamp: true,
+            enc_challenge: false,
+            fast_rep: None,
+            pa_fx_cookie: None,
+            pa_as_freshness: None,
+            otp_challenge: None,
+            etype_info2: vec![EtypeInfo2 {
+                etype: EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                // An external salt and iteration count, as Active Directory sends.
+                salt: Some("EXAMPLE.COMhostclient.example.com".to_string()),
+                s2kparams: Some(vec![0x00, 0x00, 0x08, 0x00]),
+            }],
+            client_realm: None,
+            client_name: None,
+            stime: None,
+            ctime: None,
+            krb_error: None,
+        };
+        let epoch_seconds = Duration::from_secs(1_700_000_000);
+
+        // The key is derived once, for both the preauthentication and the reply.
+        let reply_key = pa_rep
+            .derive_key("password", "EXAMPLE.COM", "client$")
+            .expect("Failed to derive key");
+
+        let preauth = pa_rep
+            .perform_enc_timestamp_with_key(&reply_key, None, epoch_seconds)
+            .expect("Failed to build preauth");
+        let [PreAuthEntry::EncTimestamp(enc_data)] = preauth.entries() else {
+            unreachable!();
+        };
+        enc_data
+            .decrypt_data(&reply_key, KeyUsage::AS_REQ_PA_ENC_TIMESTAMP)
+            .expect("Failed to decrypt timestamp");
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let time = KerberosTime::from_unix_duration(epoch_seconds).expect("Invalid time");
+        let enc_part = TaggedEncKdcRepPart::EncAsRepPart(EncKdcRepPart {
+            key: KdcEncryptionKey::try_from(
+                &BaseKey::from_bytes(
+                    EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                    &[0x33; AES_256_KEY_LEN],
+                )
+                .expect("Invalid key"),
+            )
+            .expect("Failed to encode session key"),
+            last_req: Vec::new(),
+            nonce: 1,
+            key_expiration: None,
+            flags: KerberosFlags(FlagSet::default()),
+            auth_time: time,
+            start_time: None,
+            end_time: time,
+            renew_till: None,
+            server_realm: KerberosString(Ia5String::new("EXAMPLE.COM").expect("Invalid realm")),
+            server_name: principal_name_from_str(2, "krbtgt/EXAMPLE.COM").expect("Invalid name"),
+            client_addresses: None,
+            encrypted_pa_data: None,
+        })
+        .to_der()
+        .expect("Failed to encode enc-part");
+
+        let as_rep = KerberosAsRep {
+            enc_part: reply_key
+                .encrypt_data(&enc_part, KeyUsage::AS_REP_ENC_PART)
+                .expect("Failed to encrypt enc-part"),
+            ..as_rep
+        };
+
+        let tgt = as_rep
+            .into_authenticated_reply(&reply_key)
+            .expect("Failed to decrypt reply");
+        assert_eq!(
+            tgt.session_key().etype(),
+            EncryptionType::AES256_CTS_HMAC_SHA1_96
+        );
+        assert_eq!(tgt.session_key().as_slice(), [0x33; AES_256_KEY_LEN]);
+    }
+
+    #[test]
+    fn test_as_rep_decrypt_enc_part() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let reply_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x42; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        let auth_time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let key_expiration = auth_time + Duration::from_secs(86400 * 30);
+        let address = HostAddress::Ipv4(Ipv4Addr::new(192, 168, 1, 100));
+
+        // MIT KDCs tag the enc-part of an AS-REP as an EncTGSRepPart.
+        let enc_part = TaggedEncKdcRepPart::EncTgsRepPart(EncKdcRepPart {
+            key: KdcEncryptionKey::try_from(
+                &BaseKey::from_bytes(
+                    EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                    &[0x33; AES_256_KEY_LEN],
+                )
+                .expect("Invalid key"),
+            )
+            .expect("Failed to encode session key"),
+            last_req: vec![LastReqItem {
+                lr_type: 0,
+                lr_value: KerberosTime::from_system_time(auth_time).expect("Invalid time"),
+            }],
+            nonce: 42,
+            key_expiration: Some(
+                KerberosTime::from_system_time(key_expiration).expect("Invalid time"),
+            ),
+            flags: KerberosFlags(TicketFlags::Initial | TicketFlags::PreAuthent),
+            auth_time: KerberosTime::from_system_time(auth_time).expect("Invalid time"),
+            start_time: None,
+            end_time: KerberosTime::from_system_time(auth_time + Duration::from_secs(3600))
+                .expect("Invalid time"),
+            renew_till: None,
+            server_realm: KerberosString(Ia5String::new("EXAMPLE.COM").expect("Invalid realm")),
+            server_name: principal_name_from_str(2, "krbtgt/EXAMPLE.COM").expect("Invalid name"),
+            client_addresses: Some(vec![
+                KdcHostAddress::try_from(&address).expect("Invalid address")
+            ]),
+            encrypted_pa_data: None,
+        })
+        .to_der()
+        .expect("Failed to encode enc-part");
+
+        let as_rep = KerberosAsRep {
+            enc_part: reply_key
+                .encrypt_data(&enc_part, KeyUsage::AS_REP_ENC_PART)
+                .expect("Failed to encrypt enc-part"),
+            ..as_rep
+        };
+
+        let enc_part = as_rep
+            .decrypt_enc_part(&reply_key)
+            .expect("Failed to decrypt enc-part");
+        assert_eq!(
+            enc_part.session_key().etype(),
+            EncryptionType::AES256_CTS_HMAC_SHA1_96
+        );
+        assert_eq!(enc_part.session_key().as_slice(), [0x33; AES_256_KEY_LEN]);
+        assert_eq!(
+            enc_part.last_req(),
+            &[LastReqEntry {
+                lr_type: LastReqType::None,
+                local: false,
+                time: auth_time,
+            }]
+        );
+        assert_eq!(enc_part.nonce(), 42);
+        assert_eq!(enc_part.key_expiration(), Some(key_expiration));
+        let now = auth_time + Duration::from_secs(86400 * 25);
+        assert_eq!(
+            enc_part.password_expiry_warning(now, Duration::from_secs(86400 * 7)),
+            Some(Duration::from_secs(86400 * 5))
+        );
+        assert_eq!(
+            enc_part.password_expiry_warning(now, Duration::from_secs(86400)),
+            None
+        );
+        assert_eq!(
+            enc_part.password_expiry_warning(key_expiration, Duration::ZERO),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            enc_part.flags(),
+            TicketFlags::Initial | TicketFlags::PreAuthent
+        );
+        assert_eq!(enc_part.auth_time(), auth_time);
+        assert_eq!(enc_part.start_time(), None);
+        assert_eq!(enc_part.server_realm(), "EXAMPLE.COM");
+        assert_eq!(enc_part.server_name(), "krbtgt/EXAMPLE.COM");
+        assert_eq!(enc_part.client_addresses(), Some([address].as_slice()));
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            auth_time + Duration::from_secs(3600),
+        )
+        .nonce(42)
+        .build_unchecked()
+        .expect("Failed to build request");
+        enc_part
+            .validate_against_request(&as_req)
+            .expect("Reply doesn't match the request");
+
+        // A replayed reply to another request is refused.
+        let other_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            auth_time + Duration::from_secs(3600),
+        )
+        .nonce(43)
+        .build_unchecked()
+        .expect("Failed to build request");
+        assert!(matches!(
+            enc_part.validate_against_request(&other_req),
+            Err(KrbError::ReplyNonceMismatch)
+        ));
+
+        // As is a ticket for another service, unless its name may be canonicalized.
+        let other_req = |canonicalize| {
+            KerberosRequest::build_asreq(
+                "testuser",
+                "krbtgt/example.com",
+                "EXAMPLE.COM",
+                auth_time + Duration::from_secs(3600),
+            )
+            .nonce(42)
+            .canonicalize(canonicalize)
+            .build_unchecked()
+            .expect("Failed to build request")
+        };
+        assert!(matches!(
+            enc_part.validate_against_request(&other_req(false)),
+            Err(KrbError::ReplyServiceMismatch(_))
+        ));
+        enc_part
+            .validate_against_request(&other_req(true))
+            .expect("Canonicalized name was refused");
+
+        // And a ticket that outlives the requested lifetime.
+        let other_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            auth_time + Duration::from_secs(1800),
+        )
+        .nonce(42)
+        .build_unchecked()
+        .expect("Failed to build request");
+        assert!(matches!(
+            enc_part.validate_against_request(&other_req),
+            Err(KrbError::ReplyEndTimeExceedsRequest)
+        ));
+
+        // The reply can still be decrypted into the ticket afterwards.
+        let wrong_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x43; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        assert!(as_rep.decrypt_enc_part(&wrong_key).is_err());
+        as_rep
+            .into_authenticated_reply(&reply_key)
+            .expect("Failed to decrypt reply");
+    }
+
+    #[test]
+    fn test_pa_rep_derive_key_supported_etype() {
+        let einfo2 = |etype| EtypeInfo2 {
+            etype,
+            salt: Some("EXAMPLE.COMtestuser".to_string()),
+            s2kparams: None,
+        };
+
+        let etype_info2 = vec![
+            einfo2(EncryptionType::AES256_CTS_HMAC_SHA384_192),
+            einfo2(EncryptionType::AES256_CTS_HMAC_SHA1_96),
+            einfo2(EncryptionType::RC4_HMAC),
+        ];
+
+        let mut pa_rep = KerberosPaRep {
+            offered: Vec::new(),
+            pa_fx_fast: false,
+            enc_timestamp: true,
+            enc_challenge: false,
+            fast_rep: None,
+            pa_fx_cookie: None,
+            pa_as_freshness: None,
+            otp_challenge: None,
+            etype_info2,
+            client_realm: None,
+            client_name: None,
+            stime: None,
+            ctime: None,
+            krb_error: None,
+        };
+
+        // The strongest etype is skipped, since we can't derive its key.
+        let key = pa_rep
+            .derive_key("password", "EXAMPLE.COM", "testuser")
+            .expect("Failed to derive key");
+        assert_eq!(key.etype(), EncryptionType::AES256_CTS_HMAC_SHA1_96);
+
+        pa_rep
+            .etype_info2
+            .retain(|einfo2| einfo2.etype != EncryptionType::AES256_CTS_HMAC_SHA1_96);
+        assert!(matches!(
+            pa_rep.derive_key("password", "EXAMPLE.COM", "testuser"),
+            Err(KrbError::UnsupportedEncryption)
+        ));
+    }
+
+    #[test]
+    fn test_pa_rep_etype_info2_order() {
+        // METHOD-DATA offering AES256 and RC4 with distinct salts, in both orders.
+        let aes_first = "304f3009a103020102a20204003042a103020113a23b04393037301ca003020112a1151b134558414d504c452e434f4d74657374757365723017a003020117a1101b0e4558414d504c452e434f4d726334";
+        let rc4_first = "304f3009a103020102a20204003042a103020113a23b043930373017a003020117a1101b0e4558414d504c452e434f4d726334301ca003020112a1151b134558414d504c452e434f4d7465737475736572";
+
+        for method_data in [aes_first, rc4_first] {
+            let method_data = hex::decode(method_data).expect("Failed to decode sample");
+            let padata = MethodData::from_der(&method_data).expect("Failed to decode METHOD-DATA");
+            let pa_rep = KerberosPaRep::try_from(padata).expect("Failed to parse padata");
+
+            // Whichever the KDC lists first, the entry we support is chosen.
+            let einfo2 = pa_rep.select_etype_info(&[]).expect("No etype selected");
+            assert_eq!(einfo2.etype, EncryptionType::AES256_CTS_HMAC_SHA1_96);
+            assert_eq!(einfo2.salt.as_deref(), Some("EXAMPLE.COMtestuser"));
+
+            let einfo2 = pa_rep
+                .select_etype_info(&[
+                    EncryptionType::RC4_HMAC,
+                    EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                ])
+                .expect("No etype selected");
+            assert_eq!(einfo2.etype, EncryptionType::AES256_CTS_HMAC_SHA1_96);
+
+            // Nothing the client prefers is supported.
+            assert!(pa_rep
+                .select_etype_info(&[EncryptionType::RC4_HMAC])
+                .is_none());
+            assert!(matches!(
+                pa_rep.derive_key_with_preference(
+                    "password",
+                    "EXAMPLE.COM",
+                    "testuser",
+                    &[EncryptionType::RC4_HMAC],
+                    &StringToKeyOptions::default()
+                ),
+                Err(KrbError::UnsupportedEncryption)
+            ));
+        }
+    }
+
+    #[cfg(feature = "camellia")]
+    #[test]
+    fn test_pa_rep_camellia() {
+        let pa_rep = KerberosPaRep {
+            offered: Vec::new(),
+            pa_fx_fast: false,
+            enc_timestamp: true,
+            enc_challenge: false,
+            fast_rep: None,
+            pa_fx_cookie: None,
+            pa_as_freshness: None,
+            otp_challenge: None,
+            etype_info2: vec![EtypeInfo2 {
+                etype: EncryptionType::CAMELLIA256_CTS_CMAC,
+                salt: Some("ATHENA.MIT.EDUraeburn".to_string()),
+                s2kparams: Some(vec![0, 0, 0, 1]),
+            }],
+            client_realm: None,
+            client_name: None,
+            stime: None,
+            ctime: None,
+            krb_error: None,
+        };
+
+        // The base key of the key derivation samples of RFC 6803.
+        let key = pa_rep
+            .derive_key("password", "ATHENA.MIT.EDU", "raeburn")
+            .expect("Failed to derive key");
+        assert_eq!(key.etype(), EncryptionType::CAMELLIA256_CTS_CMAC);
+        assert_eq!(
+            key.as_slice(),
+            hex::decode("b9d6828b2056b7be656d88a123b1fac68214ac2b727ecf5f69afe0c4df2a6d2c")
+                .unwrap()
+        );
+
+        let plaintext = [0x61, 0x03, 0x02, 0x01, 0x05];
+        let enc_part = key
+            .encrypt_data(&plaintext, KeyUsage::KDC_REP_TICKET)
+            .expect("Failed to encrypt");
+        assert!(matches!(enc_part, EncryptedData::Camellia256CtsCmac { .. }));
+        assert_eq!(
+            enc_part
+                .decrypt_data(&key, KeyUsage::KDC_REP_TICKET)
+                .expect("Failed to decrypt"),
+            plaintext
+        );
+        assert!(matches!(
+            enc_part.decrypt_data(&key, KeyUsage::AS_REP_ENC_PART),
+            Err(KrbError::MessageAuthenticationFailed)
+        ));
+    }
+
+    #[cfg(feature = "legacy-crypto")]
+    #[test]
+    fn test_pa_rep_des3_only_when_nothing_else() {
+        let einfo2 = |etype| EtypeInfo2 {
+            etype,
+            salt: Some("ATHENA.MIT.EDUraeburn".to_string()),
+            s2kparams: None,
+        };
+
+        let mut pa_rep = KerberosPaRep {
+            offered: Vec::new(),
+            pa_fx_fast: false,
+            enc_timestamp: true,
+            enc_challenge: false,
+            fast_rep: None,
+            pa_fx_cookie: None,
+            pa_as_freshness: None,
+            otp_challenge: None,
+            etype_info2: vec![
+                einfo2(EncryptionType::DES3_CBC_SHA1_KD),
+                einfo2(EncryptionType::AES256_CTS_HMAC_SHA1_96),
+            ],
+            client_realm: None,
+            client_name: None,
+            stime: None,
+            ctime: None,
+            krb_error: None,
+        };
+
+        // des3 is deprecated, so it loses to aes even though the KDC prefers it.
+        let einfo2 = pa_rep.select_etype_info(&[]).expect("No etype selected");
+        assert_eq!(einfo2.etype, EncryptionType::AES256_CTS_HMAC_SHA1_96);
+
+        let einfo2 = pa_rep
+            .select_etype_info(&[EncryptionType::DES3_CBC_SHA1_KD])
+            .expect("No etype selected");
+        assert_eq!(einfo2.etype, EncryptionType::DES3_CBC_SHA1_KD);
+
+        pa_rep
+            .etype_info2
+            .retain(|einfo2| einfo2.etype == EncryptionType::DES3_CBC_SHA1_KD);
+        let key = pa_rep
+            .derive_key("password", "ATHENA.MIT.EDU", "raeburn")
+            .expect("Failed to derive key");
+
+        // RFC 3961 A.4
+        assert_eq!(key.etype(), EncryptionType::DES3_CBC_SHA1_KD);
+        assert_eq!(
+            key.as_slice(),
+            hex::decode("850bb51358548cd05e86768c313e3bfef7511937dcf72c3e").unwrap()
+        );
+
+        // A ticket encrypted in the key of the service decrypts to its DER alone.
+        let plaintext = [0x61, 0x03, 0x02, 0x01, 0x05];
+        let enc_part = key
+            .encrypt_data(&plaintext, KeyUsage::KDC_REP_TICKET)
+            .expect("Failed to encrypt");
+        assert!(matches!(enc_part, EncryptedData::Des3CbcSha1Kd { .. }));
+        assert_eq!(
+            enc_part
+                .decrypt_data(&key, KeyUsage::KDC_REP_TICKET)
+                .expect("Failed to decrypt"),
+            plaintext
+        );
+    }
+
+    #[test]
+    fn test_etype_info2_iter_count() {
+        let iter_count = |etype, s2kparams: &[u8]| {
+            EtypeInfo2 {
+                etype,
+                salt: None,
+                s2kparams: Some(s2kparams.to_vec()),
+            }
+            .iter_count()
+        };
+        let seven = [0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x01];
+
+        // Every PBKDF2 enctype refuses s2kparams that aren't a count, while empty
+        // s2kparams stand for the default count of the enctype.
+        for (etype, default_iter_count) in [
+            (EncryptionType::AES128_CTS_HMAC_SHA1_96, 4096),
+            (EncryptionType::AES256_CTS_HMAC_SHA1_96, 4096),
+            (EncryptionType::AES128_CTS_HMAC_SHA256_128, 32768),
+            (EncryptionType::AES256_CTS_HMAC_SHA384_192, 32768),
+            #[cfg(feature = "camellia")]
+            (EncryptionType::CAMELLIA128_CTS_CMAC, 32768),
+            #[cfg(feature = "camellia")]
+            (EncryptionType::CAMELLIA256_CTS_CMAC, 32768),
+        ] {
+            assert!(matches!(
+                iter_count(etype, &[0x00, 0x00, 0x08, 0x00]),
+                Ok(2048)
+            ));
+            assert!(matches!(
+                iter_count(etype, &[]),
+                Ok(count) if count == default_iter_count
+            ));
+            for malformed in [&[0x00, 0x08, 0x00][..], &seven] {
+                assert!(matches!(
+                    iter_count(etype, malformed),
+                    Err(KrbError::PreAuthInvalidS2KParams(params)) if params == malformed
+                ));
+            }
+        }
+
+        // des3 has no iterations at all, so its s2kparams are ignored.
+        #[cfg(feature = "legacy-crypto")]
+        for s2kparams in [&[0x00, 0x00, 0x08, 0x00][..], &[], &seven] {
+            assert!(matches!(
+                iter_count(EncryptionType::DES3_CBC_SHA1_KD, s2kparams),
+                Ok(0)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_salt_for_principal() {
+        assert_eq!(salt_for_principal("EXAMPLE.COM", "user"), "EXAMPLE.COMuser");
+        // Each component is appended in turn, without the separators.
+        assert_eq!(
+            salt_for_principal("EXAMPLE.COM", "host/server.example.com"),
+            "EXAMPLE.COMhostserver.example.com"
+        );
+        assert_eq!(
+            salt_for_principal("EXAMPLE.COM", "ldap/dc1.example.com/example.com"),
+            "EXAMPLE.COMldapdc1.example.comexample.com"
+        );
+        // The realm is used as given, so a KDC that folds case has to say so with
+        // an explicit salt.
+        assert_eq!(salt_for_principal("example.com", "user"), "example.comuser");
+
+        // RFC 3962 B, where raeburn@ATHENA.MIT.EDU has the salt "ATHENA.MIT.EDUraeburn".
+        let einfo2 = EtypeInfo2 {
+            etype: EncryptionType::AES128_CTS_HMAC_SHA1_96,
+            salt: None,
+            s2kparams: Some(1u32.to_be_bytes().to_vec()),
+        };
+        let key = einfo2
+            .derive_key(
+                "password",
+                "ATHENA.MIT.EDU",
+                "raeburn",
+                &StringToKeyOptions::default(),
+            )
+            .expect("Failed to derive key");
+        assert_eq!(
+            key.as_slice(),
+            hex::decode("42263c6e89f4fc28b8df68ee09799f15").expect("Invalid key")
+        );
+
+        // Without a salt from the KDC, a multi-component name derives the same key
+        // as its default salt given explicitly.
+        let derive = |salt: Option<String>| {
+            EtypeInfo2 {
+                etype: EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                salt,
+                s2kparams: None,
+            }
+            .derive_key(
+                "password",
+                "EXAMPLE.COM",
+                "host/server.example.com",
+                &StringToKeyOptions::default(),
+            )
+            .expect("Failed to derive key")
+        };
+        let explicit = derive(Some(salt_for_principal(
+            "EXAMPLE.COM",
+            "host/server.example.com",
+        )));
+        assert_eq!(derive(None).as_slice(), explicit.as_slice());
+        let separated = derive(Some("EXAMPLE.COMhost/server.example.com".to_string()));
+        assert_ne!(separated.as_slice(), explicit.as_slice());
+    }
+
+    #[test]
+    fn test_string_to_key_min_iter_count() {
+        let einfo2 = |s2kparams: Option<u32>| EtypeInfo2 {
+            etype: EncryptionType::AES128_CTS_HMAC_SHA1_96,
+            salt: None,
+            s2kparams: s2kparams.map(|iter_count| iter_count.to_be_bytes().to_vec()),
+        };
+        let derive = |einfo2: &EtypeInfo2, options: &StringToKeyOptions| {
+            einfo2.derive_key("password", "EXAMPLE.COM", "testuser", options)
+        };
+
+        // Without a floor, whatever the KDC asks for is used.
+        let options = StringToKeyOptions::default();
+        for (s2kparams, iter_count) in [(Some(1), 1), (Some(4096), 4096), (None, 4096)] {
+            let einfo2 = einfo2(s2kparams);
+            assert_eq!(einfo2.iter_count().expect("Invalid s2kparams"), iter_count);
+
+            let key = derive(&einfo2, &options).expect("Failed to derive key");
+            let expect = cipher_for(EncryptionType::AES128_CTS_HMAC_SHA1_96)
+                .and_then(|cipher| {
+                    cipher.string_to_key(b"password", b"EXAMPLE.COMtestuser", Some(iter_count))
+                })
+                .expect("Failed to derive key");
+            assert_eq!(key.as_slice(), expect.as_slice());
+        }
+
+        // A KDC that asks for fewer iterations than the floor is refused.
+        let options = StringToKeyOptions::default().min_iter_count(4096);
+        assert!(matches!(
+            derive(&einfo2(Some(1)), &options),
+            Err(KrbError::PreAuthIterCountTooLow(1, 4096))
+        ));
+        assert!(derive(&einfo2(Some(4096)), &options).is_ok());
+        assert!(derive(&einfo2(None), &options).is_ok());
+
+        // The default of the etype is held to the floor too.
+        let options = StringToKeyOptions::default().min_iter_count(32768);
+        assert!(matches!(
+            derive(&einfo2(None), &options),
+            Err(KrbError::PreAuthIterCountTooLow(4096, 32768))
+        ));
+    }
+
+    #[test]
+    fn test_pa_rep_otp() {
+        // The padata of a KRB-ERROR inside FAST, with a PA-OTP-CHALLENGE for a single
+        // token and a PA-FX-COOKIE.
+        let method_data = "3072305ea1040202008da25604543052a01204100102030405060708090a0b0c0d0e0f10a1110c0f4558414d504c452e434f4d204f5450a22930273025a00703050010000000a1090c0746726565495041a303020106a50a040861316232633364343010a10402020085a2080406636f6f6b6965";
+        let method_data = hex::decode(method_data).expect("Failed to decode sample");
+        let padata = MethodData::from_der(&method_data).expect("Failed to decode METHOD-DATA");
+
+        let pa_rep = KerberosPaRep::try_from(padata).expect("Failed to parse padata");
+        let challenge = pa_rep.otp_challenge().expect("Missing OTP challenge");
+        assert_eq!(challenge.service(), Some("EXAMPLE.COM OTP"));
+
+        let [token] = challenge.tokens() else {
+            unreachable!();
+        };
+        assert_eq!(token.flags(), FlagSet::from(OtpFlags::CollectPin));
+        assert_eq!(token.vendor(), Some("FreeIPA"));
+        assert_eq!(token.length(), Some(6));
+        assert_eq!(token.token_id(), Some(b"a1b2c3d4".as_slice()));
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        let armor = FastArmor::new(
+            &tgt,
+            BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x24; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+        )
+        .expect("Failed to build armor");
+
+        let preauth = pa_rep
+            .perform_otp(&armor, token, "123456", Some("1234"))
+            .expect("Failed to build preauth");
+        let [PreAuthEntry::Raw {
+            padata_type: 142,
+            value,
+        }, PreAuthEntry::FxCookie(cookie)] = preauth.entries()
+        else {
+            unreachable!();
+        };
+        assert_eq!(cookie, b"cookie");
+
+        let otp_request = PaOtpRequest::from_der(value).expect("Failed to decode request");
+        assert_eq!(
+            otp_request.otp_value.map(|value| value.into_bytes()),
+            Some(b"123456".to_vec())
+        );
+        assert_eq!(otp_request.otp_pin.as_deref(), Some("1234"));
+        assert_eq!(otp_request.otp_vendor.as_deref(), Some("FreeIPA"));
+        assert_eq!(
+            otp_request.otp_token_id.map(|id| id.into_bytes()),
+            Some(b"a1b2c3d4".to_vec())
+        );
+
+        // The nonce of the challenge is returned encrypted in the armor key.
+        let enc_request = EncryptedData::try_from(otp_request.enc_data)
+            .and_then(|enc_data| enc_data.decrypt_data(armor.armor_key(), KeyUsage::PA_OTP_REQUEST))
+            .expect("Failed to decrypt request");
+        let enc_request =
+            PaOtpEncRequest::from_der(&enc_request).expect("Failed to decode request");
+        assert_eq!(enc_request.nonce.as_bytes(), (1..=16).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_pa_rep_offered() {
+        // A KDC that offers FAST, PKINIT, encrypted timestamp and a padata type that
+        // we don't know.
+        let method_data = "3042300aa10402020088a20204003009a103020110a20204003009a103020102a2020400300aa104020200c8a20204003012a103020113a20b040930073005a003020112";
+        let method_data = hex::decode(method_data).expect("Failed to decode sample");
+        let padata = MethodData::from_der(&method_data).expect("Failed to decode METHOD-DATA");
+
+        let pa_rep = KerberosPaRep::try_from(padata).expect("Failed to parse padata");
+        assert_eq!(pa_rep.offered(), &[136, 16, 2, 200, 19]);
+        assert_eq!(
+            pa_rep.offered_types(),
+            vec![
+                PaDataType::PaFxFast,
+                PaDataType::PaPkAsReq,
+                PaDataType::PaEncTimestamp,
+                PaDataType::PaEtypeInfo2,
+            ]
+        );
+        assert!(pa_rep.offers(PaDataType::PaFxFast));
+        assert!(pa_rep.offers(PaDataType::PaPkAsReq));
+        assert!(!pa_rep.offers(PaDataType::PaEncryptedChallenge));
+
+        // The specifics are still parsed alongside.
+        assert!(pa_rep.pa_fx_fast);
+        assert!(pa_rep.enc_timestamp);
+        assert_eq!(pa_rep.etype_info2.len(), 1);
+    }
+
+    #[test]
+    fn test_pa_rep_as_freshness() {
+        // METHOD-DATA with PA-ENC-TIMESTAMP, a PA-AS-FRESHNESS token and ETYPE-INFO2.
+        let method_data = "304d3009a103020102a20204003015a10402020096a20d040b66726573682d746f6b656e3029a103020113a2220420301e301ca003020112a1151b134558414d504c452e434f4d7465737475736572";
+        let method_data = hex::decode(method_data).expect("Failed to decode sample");
+        let padata = MethodData::from_der(&method_data).expect("Failed to decode METHOD-DATA");
+
+        let pa_rep = KerberosPaRep::try_from(padata).expect("Failed to parse padata");
+        assert_eq!(pa_rep.freshness_token(), Some(b"fresh-token".as_slice()));
+
+        let until = SystemTime::now() + Duration::from_secs(3600);
+
+        // The initial request asks for a token with an empty PA-AS-FRESHNESS.
+        let mut indicator = PreAuth::default();
+        indicator.push(PreAuthEntry::AsFreshness(Vec::new()));
+        let as_req =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .add_preauthentication(indicator)
+                .build()
+                .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        let padata = kdc_req.padata.expect("Missing padata");
+        assert_eq!(padata.len(), 1);
+        assert_eq!(padata[0].padata_type, 150);
+        assert!(padata[0].padata_value.as_bytes().is_empty());
+
+        // The follow-up request carries the token back, along with the timestamp.
+        let base_key = pa_rep
+            .derive_key("password", "EXAMPLE.COM", "testuser")
+            .expect("Failed to derive key");
+        let preauth = pa_rep
+            .enc_timestamp_with_key(&base_key, None, Duration::from_secs(1_700_000_000))
+            .expect("Failed to build preauth");
+
+        let as_req =
+            KerberosRequest::build_asreq("testuser", "krbtgt/EXAMPLE.COM", "EXAMPLE.COM", until)
+                .add_preauthentication(preauth)
+                .build()
+                .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+        let KerberosRequest::AsReq(as_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        let entries = as_req.preauth().expect("Missing preauth").entries();
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(&entries[0], PreAuthEntry::EncTimestamp(_)));
+        assert!(matches!(
+            &entries[1],
+            PreAuthEntry::AsFreshness(token) if token == b"fresh-token"
+        ));
+    }
+
+    #[test]
+    fn test_pa_rep_etype_info() {
+        // METHOD-DATA of a KDC that only sends the legacy PA-ETYPE-INFO, with an
+        // AES256 entry that has a salt and an RC4 entry that doesn't.
+        let method_data = "303d3009a103020102a20204003030a10302010ba22904273025301ca003020112a11504134558414d504c452e434f4d74657374757365723005a003020117";
+        let method_data = hex::decode(method_data).expect("Failed to decode sample");
+        let padata = MethodData::from_der(&method_data).expect("Failed to decode METHOD-DATA");
+
+        let pa_rep = KerberosPaRep::try_from(padata).expect("Failed to parse padata");
+        assert!(pa_rep.enc_timestamp);
+        assert_eq!(pa_rep.etype_info2.len(), 2);
+
+        let aes = pa_rep
+            .etype_info2
+            .iter()
+            .find(|einfo| einfo.etype == EncryptionType::AES256_CTS_HMAC_SHA1_96)
+            .expect("Missing AES256 entry");
+        assert_eq!(aes.salt.as_deref(), Some("EXAMPLE.COMtestuser"));
+        assert!(aes.s2kparams.is_none());
+
+        let rc4 = pa_rep
+            .etype_info2
+            .iter()
+            .find(|einfo| einfo.etype == EncryptionType::RC4_HMAC)
+            .expect("Missing RC4 entry");
+        assert!(rc4.salt.is_none());
+
+        assert!(pa_rep
+            .derive_key("password", "EXAMPLE.COM", "testuser")
+            .is_ok());
+
+        // METHOD-DATA with both, where ETYPE-INFO has a different salt. Only the
+        // ETYPE-INFO2 may be used.
+        let method_data = "305e3009a103020102a20204003026a10302010ba21f041d301b3019a003020112a11204104558414d504c452e434f4d77726f6e673029a103020113a2220420301e301ca003020112a1151b134558414d504c452e434f4d7465737475736572";
+        let method_data = hex::decode(method_data).expect("Failed to decode sample");
+        let padata = MethodData::from_der(&method_data).expect("Failed to decode METHOD-DATA");
+
+        let pa_rep = KerberosPaRep::try_from(padata).expect("Failed to parse padata");
+        assert_eq!(pa_rep.etype_info2.len(), 1);
+        assert_eq!(
+            pa_rep.etype_info2[0].salt.as_deref(),
+            Some("EXAMPLE.COMtestuser")
+        );
+    }
+
+    #[test]
+    fn test_as_req_addresses() {
+        let addresses = vec![
+            HostAddress::Ipv4(Ipv4Addr::new(192, 168, 1, 100)),
+            HostAddress::Ipv6(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1)),
+            HostAddress::NetBios("WORKSTATION".to_string()),
+            HostAddress::Ipv4(Ipv4Addr::new(10, 149, 214, 90)),
+        ];
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .addresses(addresses.clone())
+        .build()
+        .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        let encoded: Vec<(i32, &[u8])> = kdc_req
+            .req_body
+            .addresses
+            .as_ref()
+            .expect("Missing addresses")
+            .iter()
+            .map(|addr| (addr.addr_type, addr.address.as_bytes()))
+            .collect();
+        let expected: Vec<(i32, &[u8])> = vec![
+            (2, [192, 168, 1, 100].as_slice()),
+            (
+                24,
+                [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1].as_slice(),
+            ),
+            (20, b"WORKSTATION    \0".as_slice()),
+            (2, [10, 149, 214, 90].as_slice()),
+        ];
+        assert_eq!(encoded, expected);
+
+        let KerberosRequest::AsReq(as_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(as_req.addresses(), Some(addresses.as_slice()));
+
+        // NetBIOS names are limited to 15 characters.
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .addresses(vec![HostAddress::NetBios("AVERYLONGHOSTNAME".to_string())])
+        .build()
+        .expect("Failed to build request");
+        assert!(as_req.to_der().is_err());
+    }
+
+    #[test]
+    fn test_tgs_req_round_trip() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        let tgs_req = KerberosRequest::build_tgsreq(
+            "host/localhost",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build(&tgt)
+        .expect("Failed to build request");
+
+        let der_bytes = tgs_req.to_der().expect("Failed to encode request");
+
+        let KerberosRequest::TgsReq(tgs_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(tgs_req.body.service_name, "host/localhost");
+        assert_eq!(tgs_req.body.realm, "EXAMPLE.COM");
+        assert!(tgs_req.additional_tickets().is_empty());
+        assert_eq!(tgs_req.ap_req.0.ticket, tgt.ticket);
+
+        // The authenticator must be encrypted in the session key, and carry a checksum
+        // of the req-body that was sent.
+        let authenticator = EncryptedData::try_from(tgs_req.ap_req.0.authenticator.clone())
+            .and_then(|enc_data| {
+                enc_data.decrypt_data(&tgt.session_key, KeyUsage::TGS_REQ_PA_TGS_REQ_AUTHENTICATOR)
+            })
+            .expect("Failed to decrypt authenticator");
+        let authenticator = TaggedAuthenticator::from_der(&authenticator)
+            .expect("Failed to decode authenticator")
+            .0;
+
+        let cname: String = authenticator.cname.into();
+        assert_eq!(cname, "testuser");
+
+        let req_body = tgs_req
+            .body
+            .to_asn()
+            .and_then(|req_body| req_body.to_der())
+            .expect("Failed to encode req-body");
+        let cksum = tgt
+            .session_key
+            .checksum(&req_body, KeyUsage::TGS_REQ_PA_TGS_REQ_CKSUM)
+            .expect("Failed to checksum req-body");
+        assert_eq!(authenticator.cksum, Some(cksum));
+    }
+
+    #[test]
+    fn test_last_req_entries() {
+        let time = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+        // MIT KDCs send an entry with no information, and the password expiration
+        // when the principal has one.
+        let mit = hex::decode("30343018a003020100a111180f31393730303130313030303030305a3018a003020106a111180f32303235303330313030303030305a")
+            .expect("Failed to decode sample");
+        let last_req: Vec<LastReqEntry> = LastReq::from_der(&mit)
+            .expect("Failed to decode last-req")
+            .into_iter()
+            .map(LastReqEntry::from)
+            .collect();
+        assert_eq!(
+            last_req,
+            vec![
+                LastReqEntry {
+                    lr_type: LastReqType::None,
+                    local: false,
+                    time: UNIX_EPOCH,
+                },
+                LastReqEntry {
+                    lr_type: LastReqType::PasswordExpiration,
+                    local: false,
+                    time: time(1_740_787_200),
+                },
+            ]
+        );
+
+        // Heimdal KDCs also send times that only they know of, with a negative
+        // lr-type. Types that aren't known are kept, in their order.
+        let heimdal = hex::decode("30683018a0030201ffa111180f32303234313131353039333030305a3018a003020106a111180f32303235303330313030303030305a3018a003020107a111180f32303235313233313233353935395a3018a0030201d6a111180f32303234313131353039333030305a")
+            .expect("Failed to decode sample");
+        let last_req: Vec<LastReqEntry> = LastReq::from_der(&heimdal)
+            .expect("Failed to decode last-req")
+            .into_iter()
+            .map(LastReqEntry::from)
+            .collect();
+        assert_eq!(
+            last_req
+                .iter()
+                .map(|entry| (entry.lr_type(), entry.is_local(), entry.time()))
+                .collect::<Vec<_>>(),
+            vec![
+                (LastReqType::InitialTgt, true, time(1_731_663_000)),
+                (LastReqType::PasswordExpiration, false, time(1_740_787_200)),
+                (LastReqType::AccountExpiration, false, time(1_767_225_599)),
+                (LastReqType::Other(-42), true, time(1_731_663_000)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ticket_decrypt() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let service_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x42; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        let auth_time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let end_time = auth_time + Duration::from_secs(3600);
+
+        let enc_part = TaggedEncTicketPart(KdcEncTicketPart {
+            flags: KerberosFlags(TicketFlags::Initial | TicketFlags::PreAuthent),
+            key: KdcEncryptionKey::try_from(
+                &BaseKey::from_bytes(
+                    EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                    &[0x33; AES_256_KEY_LEN],
+                )
+                .expect("Invalid key"),
+            )
+            .expect("Failed to encode session key"),
+            crealm: KerberosString(Ia5String::new("EXAMPLE.COM").expect("Invalid realm")),
+            cname: principal_name_from_str(1, "testuser").expect("Invalid name"),
+            transited: TransitedEncoding {
+                tr_type: 1,
+                contents: OctetString::new(Vec::new()).expect("Invalid contents"),
+            },
+            authtime: KerberosTime::from_system_time(auth_time).expect("Invalid time"),
+            starttime: None,
+            endtime: KerberosTime::from_system_time(end_time).expect("Invalid time"),
+            till: None,
+            cadr: None,
+            authorization_data: Some(vec![AuthorizationData {
+                ad_type: 1,
+                ad_data: OctetString::new(b"pac".as_slice()).expect("Invalid data"),
+            }]),
+        })
+        .to_der()
+        .expect("Failed to encode enc-part");
+
+        let mut ticket = as_rep.ticket.clone();
+        let enc_part = service_key
+            .encrypt_data(&enc_part, KeyUsage::KDC_REP_TICKET)
+            .map(|enc_data| enc_data.with_kvno(Some(3)))
+            .expect("Failed to encrypt enc-part");
+        ticket.0.enc_part =
+            KdcEncryptedData::try_from(&enc_part).expect("Failed to encode enc-part");
+        let ticket = Ticket(ticket);
+
+        let enc_part = ticket
+            .decrypt(&KerberosKey::new(service_key.clone(), 3))
+            .expect("Failed to decrypt ticket");
+        assert_eq!(enc_part.client_realm(), "EXAMPLE.COM");
+        assert_eq!(enc_part.client_name(), "testuser");
+        assert_eq!(
+            enc_part.session_key().etype(),
+            EncryptionType::AES256_CTS_HMAC_SHA1_96
+        );
+        assert_eq!(enc_part.session_key().as_slice(), [0x33; AES_256_KEY_LEN]);
+        assert_eq!(
+            enc_part.flags(),
+            TicketFlags::Initial | TicketFlags::PreAuthent
+        );
+        assert_eq!(enc_part.transited(), (1, [].as_slice()));
+        assert_eq!(enc_part.auth_time(), auth_time);
+        // Without a starttime the ticket is valid from its authtime.
+        assert_eq!(enc_part.start_time(), auth_time);
+        assert_eq!(enc_part.end_time(), end_time);
+        assert_eq!(enc_part.authorization_data(), &[(1, b"pac".to_vec())]);
+
+        assert!(!enc_part.is_valid_at(auth_time - Duration::from_secs(1)));
+        assert!(enc_part.is_valid_at(auth_time));
+        assert!(!enc_part.is_expired(end_time - Duration::from_secs(1)));
+        assert!(enc_part.is_expired(end_time));
+        assert!(!enc_part.is_valid_at(end_time));
+
+        // The ticket was encrypted in another version of the service key.
+        assert!(matches!(
+            ticket.decrypt(&KerberosKey::new(service_key, 4)),
+            Err(KrbError::KeyVersionMismatch(3, 4))
+        ));
+
+        let wrong_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x43; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        assert!(ticket.decrypt(&KerberosKey::new(wrong_key, 3)).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_wrong_payload() {
+        let key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x42; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        // An empty SEQUENCE under the application tags of an EncASRepPart, an
+        // EncTicketPart and an Authenticator.
+        let enc_as_rep_part = [0x79, 0x02, 0x30, 0x00];
+        let enc_ticket_part = [0x63, 0x02, 0x30, 0x00];
+        let authenticator = [0x62, 0x02, 0x30, 0x00];
+
+        let encrypt = |payload: &[u8], key_usage| {
+            key.encrypt_data(payload, key_usage)
+                .expect("Failed to encrypt payload")
+        };
+
+        assert!(matches!(
+            encrypt(&authenticator, KeyUsage::AS_REP_ENC_PART)
+                .decrypt_enc_kdc_rep_part(&key, KeyUsage::AS_REP_ENC_PART),
+            Err(KrbError::WrongPayloadTag(2, 25))
+        ));
+        assert!(matches!(
+            encrypt(&enc_ticket_part, KeyUsage::TGS_REP_ENC_PART_SESSION)
+                .decrypt_enc_kdc_rep_part(&key, KeyUsage::TGS_REP_ENC_PART_SESSION),
+            Err(KrbError::WrongPayloadTag(3, 26))
+        ));
+        assert!(matches!(
+            encrypt(&enc_as_rep_part, KeyUsage::KDC_REP_TICKET)
+                .decrypt_enc_ticket_part(&KerberosKey::new(key.clone(), 1)),
+            Err(KrbError::WrongPayloadTag(25, 3))
+        ));
+        assert!(matches!(
+            encrypt(&enc_ticket_part, KeyUsage::TGS_REQ_PA_TGS_REQ_AUTHENTICATOR)
+                .decrypt_authenticator(&key, KeyUsage::TGS_REQ_PA_TGS_REQ_AUTHENTICATOR),
+            Err(KrbError::WrongPayloadTag(3, 2))
+        ));
+
+        // The right tag gets as far as decoding, which the empty SEQUENCE fails.
+        assert!(matches!(
+            encrypt(&enc_as_rep_part, KeyUsage::AS_REP_ENC_PART)
+                .decrypt_enc_kdc_rep_part(&key, KeyUsage::AS_REP_ENC_PART),
+            Err(KrbError::DerDecodeEncKdcRepPart)
+        ));
+        assert!(matches!(
+            encrypt(&authenticator, KeyUsage::AP_REQ_AUTHENTICATOR)
+                .decrypt_authenticator(&key, KeyUsage::AP_REQ_AUTHENTICATOR),
+            Err(KrbError::DerDecodeAuthenticator)
+        ));
+
+        // As does a payload without an application tag.
+        assert!(matches!(
+            encrypt(&[0x30, 0x00], KeyUsage::AS_REP_ENC_PART)
+                .decrypt_enc_kdc_rep_part(&key, KeyUsage::AS_REP_ENC_PART),
+            Err(KrbError::DerDecodeEncKdcRepPart)
+        ));
+
+        // The wrong key is still a failure to decrypt.
+        let other_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x24; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        assert!(
+            encrypt(&authenticator, KeyUsage::TGS_REQ_PA_TGS_REQ_AUTHENTICATOR)
+                .decrypt_authenticator(&other_key, KeyUsage::TGS_REQ_PA_TGS_REQ_AUTHENTICATOR)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_as_rep_kvno_mismatch() {
+        let as_rep = |kvno| {
+            let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+            let KerberosResponse::AsRep(as_rep) =
+                KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+            else {
+                unreachable!();
+            };
+            KerberosAsRep {
+                enc_part: as_rep.enc_part.clone().with_kvno(kvno),
+                ..as_rep
+            }
+        };
+        let key = KerberosKey::new(
+            BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x24; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            2,
+        );
+
+        // The reply is in a newer version of the key than the one we have.
+        assert!(matches!(
+            as_rep(Some(3)).into_authenticated_reply_with_kvno(&key),
+            Err(KrbError::KeyVersionMismatch(3, 2))
+        ));
+
+        // With the same version, or without one to compare, the key is just wrong.
+        for kvno in [Some(2), None] {
+            assert!(matches!(
+                as_rep(kvno).into_authenticated_reply_with_kvno(&key),
+                Err(KrbError::MessageAuthenticationFailed)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_aes128_etype_info2() {
+        // AD computer accounts often only have an aes128 key.
+        let einfo2 = EtypeInfo2 {
+            etype: EncryptionType::AES128_CTS_HMAC_SHA1_96,
+            salt: Some("EXAMPLE.COMhostclient.example.com".to_string()),
+            s2kparams: None,
+        };
+        let base_key = einfo2
+            .derive_key(
+                "password",
+                "EXAMPLE.COM",
+                "client$",
+                &StringToKeyOptions::default(),
+            )
+            .expect("Failed to derive key");
+        assert_eq!(base_key.etype(), EncryptionType::AES128_CTS_HMAC_SHA1_96);
+
+        // The enc-part of a reply in the aes128 key survives the trip over the wire.
+        let enc_data = base_key
+            .encrypt_data(b"enc-part", KeyUsage::AS_REP_ENC_PART)
+            .expect("Failed to encrypt");
+        let kdc_enc_data = KdcEncryptedData::try_from(&enc_data).expect("Failed to encode");
+        assert_eq!(
+            kdc_enc_data.etype,
+            EncryptionType::AES128_CTS_HMAC_SHA1_96 as i32
+        );
+        let enc_data = EncryptedData::try_from(kdc_enc_data).expect("Failed to decode");
+        assert!(matches!(
+            enc_data,
+            EncryptedData::Aes128CtsHmacSha196 { .. }
+        ));
+        assert_eq!(
+            enc_data
+                .decrypt_data(&base_key, KeyUsage::AS_REP_ENC_PART)
+                .expect("Failed to decrypt"),
+            b"enc-part"
+        );
+
+        // A key of another etype can't decrypt it.
+        let other_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x42; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        assert!(matches!(
+            enc_data.decrypt_data(&other_key, KeyUsage::AS_REP_ENC_PART),
+            Err(KrbError::InvalidEncryptionKey)
+        ));
+    }
+
+    #[cfg(feature = "tokio-codec")]
+    #[tokio::test]
+    async fn test_derive_key_async_yields_executor() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let einfo2 = EtypeInfo2 {
+            etype: EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            salt: Some("EXAMPLE.COMtestuser".to_string()),
+            s2kparams: Some(200_000u32.to_be_bytes().to_vec()),
+        };
+        let options = StringToKeyOptions::default();
+
+        // The test runtime has a single thread, so the ticker only runs while the
+        // derivation is off the executor.
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicBool::new(false));
+        let ticker = {
+            let ticks = ticks.clone();
+            let done = done.clone();
+            tokio::spawn(async move {
+                while !done.load(Ordering::Relaxed) {
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                    tokio::task::yield_now().await;
+                }
+            })
+        };
+
+        let base_key = einfo2
+            .derive_key_async("password", "EXAMPLE.COM", "testuser", &options)
+            .await
+            .expect("Failed to derive key");
+        done.store(true, Ordering::Relaxed);
+        ticker.await.expect("Ticker failed");
+        assert!(ticks.load(Ordering::Relaxed) > 0);
+
+        let expected = einfo2
+            .derive_key("password", "EXAMPLE.COM", "testuser", &options)
+            .expect("Failed to derive key");
+        assert_eq!(base_key.as_slice(), expected.as_slice());
+    }
+
+    #[test]
+    fn test_aes256_sha384_etype_info2() {
+        let einfo2 = EtypeInfo2 {
+            etype: EncryptionType::AES256_CTS_HMAC_SHA384_192,
+            salt: None,
+            s2kparams: None,
+        };
+        let base_key = einfo2
+            .derive_key(
+                "password",
+                "EXAMPLE.COM",
+                "testuser",
+                &StringToKeyOptions::default(),
+            )
+            .expect("Failed to derive key");
+        assert_eq!(base_key.etype(), EncryptionType::AES256_CTS_HMAC_SHA384_192);
+
+        let enc_data = base_key
+            .encrypt_data(b"enc-part", KeyUsage::AS_REP_ENC_PART)
+            .expect("Failed to encrypt");
+        let kdc_enc_data = KdcEncryptedData::try_from(&enc_data).expect("Failed to encode");
+        assert_eq!(
+            kdc_enc_data.etype,
+            EncryptionType::AES256_CTS_HMAC_SHA384_192 as i32
+        );
+        let enc_data = EncryptedData::try_from(kdc_enc_data).expect("Failed to decode");
+        assert_eq!(
+            enc_data
+                .decrypt_data(&base_key, KeyUsage::AS_REP_ENC_PART)
+                .expect("Failed to decrypt"),
+            b"enc-part"
+        );
+
+        // The sha1 key of the same length is a different etype.
+        let other_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x42; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        assert!(matches!(
+            enc_data.decrypt_data(&other_key, KeyUsage::AS_REP_ENC_PART),
+            Err(KrbError::InvalidEncryptionKey)
+        ));
+
+        // KRB-FX-CF2 keeps the etype of the first key, whatever the second.
+        let armor_key = base_key
+            .krb_fx_cf2(&other_key, "subkeyarmor", "ticketarmor")
+            .expect("Failed to combine keys");
+        assert_eq!(
+            armor_key.etype(),
+            EncryptionType::AES256_CTS_HMAC_SHA384_192
+        );
+        let armor_key = other_key
+            .krb_fx_cf2(&base_key, "subkeyarmor", "ticketarmor")
+            .expect("Failed to combine keys");
+        assert_eq!(armor_key.etype(), EncryptionType::AES256_CTS_HMAC_SHA1_96);
+    }
+
+    #[test]
+    fn test_aes128_sha256_etype_info2() {
+        // The sha2 enctypes take the same s2kparams as the sha1 ones.
+        let einfo2 = EtypeInfo2 {
+            etype: EncryptionType::AES128_CTS_HMAC_SHA256_128,
+            salt: None,
+            s2kparams: Some(32768u32.to_be_bytes().to_vec()),
+        };
+        let base_key = einfo2
+            .derive_key(
+                "password",
+                "EXAMPLE.COM",
+                "testuser",
+                &StringToKeyOptions::default(),
+            )
+            .expect("Failed to derive key");
+        assert_eq!(base_key.etype(), EncryptionType::AES128_CTS_HMAC_SHA256_128);
+
+        let enc_data = base_key
+            .encrypt_data(b"enc-part", KeyUsage::AS_REP_ENC_PART)
+            .expect("Failed to encrypt");
+        let kdc_enc_data = KdcEncryptedData::try_from(&enc_data).expect("Failed to encode");
+        assert_eq!(
+            kdc_enc_data.etype,
+            EncryptionType::AES128_CTS_HMAC_SHA256_128 as i32
+        );
+        let enc_data = EncryptedData::try_from(kdc_enc_data).expect("Failed to decode");
+        assert_eq!(
+            enc_data
+                .decrypt_data(&base_key, KeyUsage::AS_REP_ENC_PART)
+                .expect("Failed to decrypt"),
+            b"enc-part"
+        );
+
+        // The sha1 key of the same length is a different etype.
+        let other_key = BaseKey::from_bytes(
+            EncryptionType::AES128_CTS_HMAC_SHA1_96,
+            &[0x42; AES_128_KEY_LEN],
+        )
+        .expect("Invalid key");
+        assert!(matches!(
+            enc_data.decrypt_data(&other_key, KeyUsage::AS_REP_ENC_PART),
+            Err(KrbError::InvalidEncryptionKey)
+        ));
+    }
+
+    #[test]
+    fn test_generate_session_key() {
+        for etype in [
+            EncryptionType::AES128_CTS_HMAC_SHA1_96,
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            EncryptionType::AES128_CTS_HMAC_SHA256_128,
+            EncryptionType::AES256_CTS_HMAC_SHA384_192,
+        ] {
+            let key = BaseKey::generate_session_key(etype).expect("Failed to generate key");
+            assert_eq!(key.etype(), etype);
+
+            let other = BaseKey::generate_session_key(etype).expect("Failed to generate key");
+            assert_ne!(key.as_slice(), other.as_slice());
+
+            let enc_data = key
+                .encrypt_data(b"enc-part", KeyUsage::AS_REP_ENC_PART)
+                .expect("Failed to encrypt");
+            assert_eq!(
+                enc_data
+                    .decrypt_data(&key, KeyUsage::AS_REP_ENC_PART)
+                    .expect("Failed to decrypt"),
+                b"enc-part"
+            );
+        }
+
+        assert!(matches!(
+            BaseKey::generate_session_key(EncryptionType::RC4_HMAC),
+            Err(KrbError::UnsupportedEncryption)
+        ));
+    }
+
+    #[test]
+    fn test_base_key_zeroize() {
+        let mut key = BaseKey::generate_session_key(EncryptionType::AES256_CTS_HMAC_SHA1_96)
+            .expect("Failed to generate key");
+
+        // The key material never appears in the debug output.
+        let debug = format!("{:?}", key);
+        assert_eq!(debug, "Aes256 { .. }");
+        assert!(!debug.contains(&hex::encode(key.as_slice())));
+
+        key.zeroize();
+        assert_eq!(key.etype(), EncryptionType::AES256_CTS_HMAC_SHA1_96);
+        assert_eq!(key.as_slice(), [0u8; AES_256_KEY_LEN]);
+    }
+
+    #[test]
+    fn test_krb_fx_cf2() {
+        // From the MIT krb5 t_cf2 tests, where each key is derived with its password
+        // as the salt. The aes256-cts-hmac-sha384-192 output was computed with an
+        // independent implementation of RFC 8009 that gives the other three.
+        for (etype, expect) in [
+            (
+                EncryptionType::AES128_CTS_HMAC_SHA1_96,
+                "97df97e4b798b29eb31ed7280287a92a",
+            ),
+            (
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                "4d6ca4e629785c1f01baf55e2e548566b9617ae3a96868c337cb93b5e72b1c7b",
+            ),
+            (
+                EncryptionType::AES128_CTS_HMAC_SHA256_128,
+                "edd02a39d2dbde31611c16e610be062c",
+            ),
+            (
+                EncryptionType::AES256_CTS_HMAC_SHA384_192,
+                "67f6ea530aea85a37dcbb23349ea52dcc61ca8493ff557252327fd8304341584",
+            ),
+        ] {
+            let cipher = cipher_for(etype).expect("Unsupported etype");
+            let key1 = cipher
+                .string_to_key(b"key1", b"key1", None)
+                .and_then(|k| BaseKey::from_bytes(etype, &k))
+                .expect("Failed to derive key");
+            let key2 = cipher
+                .string_to_key(b"key2", b"key2", None)
+                .and_then(|k| BaseKey::from_bytes(etype, &k))
+                .expect("Failed to derive key");
+
+            let key = key1
+                .krb_fx_cf2(&key2, "a", "b")
+                .expect("Failed to combine keys");
+            assert_eq!(key.etype(), etype);
+            assert_eq!(
+                key.as_slice(),
+                hex::decode(expect).expect("Invalid key").as_slice()
+            );
+        }
+    }
+
+    #[test]
+    fn test_as_rep_etype_info2() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(mut as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+        assert!(as_rep.reply_etype_info().is_none());
+        assert!(as_rep
+            .derive_reply_key("password", &StringToKeyOptions::default())
+            .is_none());
+
+        // The reply confirms a salt that differs from the default one of the client.
+        let etype_info2 =
+            hex::decode("301e301ca003020112a1151b134558414d504c452e434f4d5465737455736572")
+                .expect("Failed to decode sample");
+        as_rep.etype_info2 =
+            etype_info2_from_der(&etype_info2).expect("Failed to parse ETYPE-INFO2");
+
+        let as_rep = KerberosResponse::AsRep(as_rep)
+            .to_der()
+            .expect("Failed to encode AS-REP");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let einfo2 = as_rep.reply_etype_info().expect("Missing ETYPE-INFO2");
+        assert_eq!(einfo2.etype, EncryptionType::AES256_CTS_HMAC_SHA1_96);
+        assert_eq!(einfo2.salt.as_deref(), Some("EXAMPLE.COMTestUser"));
+
+        let reply_key = as_rep
+            .derive_reply_key("password", &StringToKeyOptions::default())
+            .expect("Missing ETYPE-INFO2")
+            .expect("Failed to derive key");
+        let k = derive_key_external_salt_aes256_cts_hmac_sha1_96(
+            b"password",
+            b"EXAMPLE.COMTestUser",
+            None,
+        )
+        .expect("Failed to derive key");
+        assert_eq!(reply_key.etype(), EncryptionType::AES256_CTS_HMAC_SHA1_96);
+        assert_eq!(reply_key.as_slice(), k);
+    }
+
+    #[test]
+    fn test_credential() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let auth_time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let end_time = auth_time + Duration::from_secs(10 * 3600);
+        let enc_part = |server_name: &str, start_time| EncAsRepPart {
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x33; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            last_req: Vec::new(),
+            nonce: 1,
+            key_expiration: None,
+            flags: TicketFlags::Renewable | TicketFlags::Initial,
+            auth_time,
+            start_time,
+            end_time,
+            renew_till: Some(auth_time + Duration::from_secs(7 * 86400)),
+            server_realm: "EXAMPLE.COM".to_string(),
+            server_name: server_name.to_string(),
+            client_addresses: Some(vec![HostAddress::Ipv4(Ipv4Addr::new(192, 0, 2, 1))]),
+        };
+
+        let tgs_rep = KerberosTgsRep {
+            client_realm: as_rep.client_realm.clone(),
+            client_name: as_rep.client_name.clone(),
+            client_name_type: as_rep.client_name_type,
+            ticket: as_rep.ticket.clone(),
+            enc_part: as_rep.enc_part.clone(),
+            other_padata: Vec::new(),
+        };
+
+        let tgt = Credential::from_as_rep(as_rep, enc_part("krbtgt/EXAMPLE.COM", None));
+        assert_eq!(tgt.client_realm(), "EXAMPLE.COM");
+        assert_eq!(tgt.client_name(), "testuser");
+        assert_eq!(tgt.server_realm(), "EXAMPLE.COM");
+        assert_eq!(tgt.server_name(), "krbtgt/EXAMPLE.COM");
+        assert_eq!(
+            tgt.session_key().etype(),
+            EncryptionType::AES256_CTS_HMAC_SHA1_96
+        );
+        assert_eq!(tgt.session_key().as_slice(), [0x33; AES_256_KEY_LEN]);
+        assert!(tgt.ticket_flags().contains(TicketFlags::Initial));
+        assert_eq!(tgt.start_time(), auth_time);
+        assert_eq!(tgt.end_time(), end_time);
+        assert_eq!(
+            tgt.addresses(),
+            Some([HostAddress::Ipv4(Ipv4Addr::new(192, 0, 2, 1))].as_slice())
+        );
+
+        assert!(!tgt.is_expired(auth_time));
+        assert_eq!(
+            tgt.time_remaining(auth_time + Duration::from_secs(3600)),
+            Duration::from_secs(9 * 3600)
+        );
+        assert!(tgt.is_expired(end_time));
+        assert_eq!(tgt.time_remaining(end_time), Duration::ZERO);
+        assert_eq!(
+            tgt.time_remaining(end_time + Duration::from_secs(60)),
+            Duration::ZERO
+        );
+
+        // A service ticket keeps its own start time.
+        let start_time = auth_time + Duration::from_secs(600);
+        let service_ticket =
+            Credential::from_tgs_rep(tgs_rep, enc_part("host/localhost", Some(start_time)));
+        assert_eq!(service_ticket.server_name(), "host/localhost");
+        assert_eq!(service_ticket.auth_time(), auth_time);
+        assert_eq!(service_ticket.start_time(), start_time);
+        assert_eq!(
+            service_ticket.ticket().service_name(),
+            tgt.ticket().service_name()
+        );
+    }
+
+    #[test]
+    fn test_tgs_rep_decrypt() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let session_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x42; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        let subkey = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x24; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+
+        let time = KerberosTime::from_unix_duration(Duration::from_secs(1_700_000_000))
+            .expect("Invalid time");
+        let enc_part = TaggedEncKdcRepPart::EncTgsRepPart(EncKdcRepPart {
+            key: KdcEncryptionKey::try_from(
+                &BaseKey::from_bytes(
+                    EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                    &[0x33; AES_256_KEY_LEN],
+                )
+                .expect("Invalid key"),
+            )
+            .expect("Failed to encode session key"),
+            last_req: Vec::new(),
+            nonce: 1,
+            key_expiration: None,
+            flags: KerberosFlags(FlagSet::default()),
+            auth_time: time,
+            start_time: None,
+            end_time: time,
+            renew_till: None,
+            server_realm: KerberosString(Ia5String::new("EXAMPLE.COM").expect("Invalid realm")),
+            server_name: principal_name_from_str(2, "host/localhost").expect("Invalid name"),
+            client_addresses: None,
+            encrypted_pa_data: None,
+        })
+        .to_der()
+        .expect("Failed to encode enc-part");
+
+        let tgs_rep = |key: &BaseKey, key_usage| {
+            let tgs_rep = KerberosResponse::TgsRep(KerberosTgsRep {
+                client_realm: as_rep.client_realm.clone(),
+                client_name: as_rep.client_name.clone(),
+                client_name_type: as_rep.client_name_type,
+                ticket: as_rep.ticket.clone(),
+                enc_part: key
+                    .encrypt_data(&enc_part, key_usage)
+                    .expect("Failed to encrypt enc-part"),
+                other_padata: Vec::new(),
+            })
+            .to_der()
+            .expect("Failed to encode TGS-REP");
+
+            let KerberosResponse::TgsRep(tgs_rep) =
+                KerberosResponse::from_der(&tgs_rep).expect("Failed to decode TGS-REP")
+            else {
+                unreachable!();
+            };
+            tgs_rep
+        };
+
+        let service_ticket = tgs_rep(&session_key, KeyUsage::TGS_REP_ENC_PART_SESSION)
+            .into_authenticated_reply(&session_key)
+            .expect("Failed to decrypt reply");
+        assert_eq!(service_ticket.client_name(), "testuser");
+        assert_eq!(
+            service_ticket.session_key().etype(),
+            EncryptionType::AES256_CTS_HMAC_SHA1_96
+        );
+        assert_eq!(
+            service_ticket.session_key().as_slice(),
+            [0x33; AES_256_KEY_LEN]
+        );
+
+        let enc_part = tgs_rep(&session_key, KeyUsage::TGS_REP_ENC_PART_SESSION)
+            .decrypt_enc_part(&session_key)
+            .expect("Failed to decrypt enc-part");
+        assert_eq!(enc_part.server_name, "host/localhost");
+
+        // A reply in the authenticator subkey uses its own key usage.
+        tgs_rep(&subkey, KeyUsage::TGS_REP_ENC_PART_SUBKEY)
+            .into_authenticated_reply_with_subkey(&subkey)
+            .expect("Failed to decrypt reply");
+        assert!(tgs_rep(&subkey, KeyUsage::TGS_REP_ENC_PART_SUBKEY)
+            .into_authenticated_reply(&subkey)
+            .is_err());
+    }
+
+    #[test]
+    fn test_tgs_req_verify_authenticator() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        let der_bytes = KerberosRequest::build_tgsreq(
+            "host/localhost",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build(&tgt)
+        .and_then(|tgs_req| tgs_req.to_der().map_err(|_| KrbError::DerEncodeKdcReq))
+        .expect("Failed to build request");
+
+        let KerberosRequest::TgsReq(tgs_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(tgs_req.ticket(), tgt.ticket());
+
+        let authenticator = tgs_req
+            .verify_authenticator(&tgt.session_key)
+            .expect("Failed to verify authenticator");
+        assert_eq!(authenticator.client_realm(), "EXAMPLE.COM");
+        assert_eq!(authenticator.client_name(), "testuser");
+        assert!(authenticator.subkey().is_none());
+        let skew = SystemTime::now()
+            .duration_since(authenticator.ctime())
+            .expect("Authenticator is from the future");
+        assert!(skew < Duration::from_secs(60));
+
+        // Only the session key of the TGT decrypts the authenticator.
+        let wrong_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x43; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        assert!(tgs_req.verify_authenticator(&wrong_key).is_err());
+
+        // A req-body that was changed after it was checksummed is refused.
+        let KrbKdcReq::TgsReq(mut kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        kdc_req.req_body.nonce ^= 1;
+        let der_bytes = KrbKdcReq::TgsReq(kdc_req)
+            .to_der()
+            .expect("Failed to encode request");
+
+        let KerberosRequest::TgsReq(tgs_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        let err = tgs_req
+            .verify_authenticator(&tgt.session_key)
+            .expect_err("Changed req-body was accepted");
+        assert!(matches!(err, KrbError::MessageAuthenticationFailed));
+    }
+
+    fn tgt_for_tests() -> AuthenticatedReply {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        }
+    }
+
+    #[test]
+    fn test_tgs_req_subkey() {
+        let tgt = tgt_for_tests();
+        let subkey = BaseKey::generate_session_key(tgt.session_key.etype())
+            .expect("Failed to generate subkey");
+
+        let der_bytes = KerberosRequest::build_tgsreq(
+            "host/localhost",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .subkey(Some(subkey.clone()))
+        .build(&tgt)
+        .and_then(|tgs_req| tgs_req.to_der().map_err(|_| KrbError::DerEncodeKdcReq))
+        .expect("Failed to build request");
+
+        let KerberosRequest::TgsReq(tgs_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+
+        // The subkey is carried in the authenticator, which is still encrypted in the
+        // session key of the TGT.
+        let authenticator = tgs_req
+            .verify_authenticator(&tgt.session_key)
+            .expect("Failed to verify authenticator");
+        let sent = authenticator.subkey().expect("Missing subkey");
+        assert_eq!(sent.etype(), subkey.etype());
+        assert_eq!(sent.as_slice(), subkey.as_slice());
+    }
+
+    #[test]
+    fn test_decrypt_ap_rep() {
+        let tgt = tgt_for_tests();
+        let ctime = KerberosTime::from_unix_duration(Duration::from_secs(1_700_000_000))
+            .expect("Invalid time");
+
+        let ap_rep = |subkey: Option<&BaseKey>, key: &BaseKey| {
+            let enc_part = TaggedEncApRepPart(EncApRepPart {
+                ctime,
+                cusec: 1234,
+                subkey: subkey
+                    .map(KdcEncryptionKey::try_from)
+                    .transpose()
+                    .expect("Failed to encode subkey"),
+                seq_number: Some(42),
+            })
+            .to_der()
+            .expect("Failed to encode enc-part");
+
+            let enc_part = key
+                .encrypt_data(&enc_part, KeyUsage::AP_REP_ENC_PART)
+                .expect("Failed to encrypt enc-part");
+            let enc_part =
+                KdcEncryptedData::try_from(&enc_part).expect("Failed to encode enc-part");
+
+            TaggedApRep(ApRep {
+                pvno: 5,
+                msg_type: KrbMessageType::KrbApRep as u8,
+                enc_part,
+            })
+            .to_der()
+            .expect("Failed to encode AP-REP")
+        };
+
+        // Without a subkey the session key of the ticket stays in use.
+        let reply = tgt
+            .decrypt_ap_rep(&ap_rep(None, &tgt.session_key))
+            .expect("Failed to decrypt AP-REP");
+        assert!(reply.subkey().is_none());
+        assert_eq!(reply.seq_number(), Some(42));
+        assert_eq!(
+            reply.ctime(),
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000) + Duration::from_micros(1234)
+        );
+
+        let subkey = BaseKey::generate_session_key(EncryptionType::AES128_CTS_HMAC_SHA1_96)
+            .expect("Failed to generate subkey");
+        let reply = tgt
+            .decrypt_ap_rep(&ap_rep(Some(&subkey), &tgt.session_key))
+            .expect("Failed to decrypt AP-REP");
+        let acceptor_subkey = reply.subkey().expect("Missing subkey");
+        assert_eq!(
+            acceptor_subkey.etype(),
+            EncryptionType::AES128_CTS_HMAC_SHA1_96
+        );
+        assert_eq!(acceptor_subkey.as_slice(), subkey.as_slice());
+
+        // The enc-part is only ever in the session key.
+        assert!(matches!(
+            tgt.decrypt_ap_rep(&ap_rep(None, &subkey)),
+            Err(KrbError::InvalidEncryptionKey)
+        ));
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        // A captured PREAUTH_REQUIRED from Active Directory.
+        let preauth_required = "7e81a93081a6a003020105a10302011ea411180f32303234303631323131343830355aa505020301dc66a603020119a90c1b0a41464f524553542e4144aa1f301da003020102a11630141b066b72627467741b0a41464f524553542e4144ac4c044a30483025a103020113a21e041c301a3018a003020112a1111b0f41464f524553542e414475736572313009a103020102a20204003009a103020110a20204003009a10302010fa2020400";
+
+        for sample in [AS_REP_SAMPLE, RESPONSE_TOO_BIG_SAMPLE, preauth_required] {
+            let der_bytes = hex::decode(sample).expect("Failed to decode sample");
+            let response = KerberosResponse::from_der(&der_bytes).expect("Failed to decode");
+            assert_eq!(response.to_der().expect("Failed to encode"), der_bytes);
+        }
+
+        // Padata that isn't interpreted is passed on as it was received.
+        let unknown = PaData {
+            padata_type: 0x7fff,
+            padata_value: OctetString::new(b"opaque".as_slice()).expect("Invalid padata"),
+        };
+        let kdc_rep = || {
+            let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+            let KrbKdcRep::AsRep(kdc_rep) = KrbKdcRep::from_der(&as_rep).expect("Failed to decode")
+            else {
+                unreachable!();
+            };
+            kdc_rep
+        };
+        let as_rep = kdc_rep();
+        for kdc_rep in [
+            KrbKdcRep::AsRep(KdcRep {
+                padata: as_rep
+                    .padata
+                    .clone()
+                    .map(|padata| [padata, vec![unknown.clone()]].concat()),
+                ..as_rep
+            }),
+            KrbKdcRep::TgsRep(KdcRep {
+                msg_type: KrbMessageType::KrbTgsRep as u8,
+                padata: Some(vec![unknown.clone()]),
+                ..kdc_rep()
+            }),
+        ] {
+            let der_bytes = kdc_rep.to_der().expect("Failed to encode");
+            let response = KerberosResponse::from_der(&der_bytes).expect("Failed to decode");
+            assert_eq!(response.to_der().expect("Failed to encode"), der_bytes);
+        }
+
+        // The name type of the client is kept, whatever it is.
+        for kdc_rep in [
+            KrbKdcRep::AsRep(KdcRep {
+                cname: principal_name_from_str(10, "user@corp.example.com").expect("Invalid name"),
+                ..kdc_rep()
+            }),
+            KrbKdcRep::TgsRep(KdcRep {
+                msg_type: KrbMessageType::KrbTgsRep as u8,
+                cname: principal_name_from_str(3, "host/node1.example.com").expect("Invalid name"),
+                ..kdc_rep()
+            }),
+        ] {
+            let der_bytes = kdc_rep.to_der().expect("Failed to encode");
+            let response = KerberosResponse::from_der(&der_bytes).expect("Failed to decode");
+            assert_eq!(response.to_der().expect("Failed to encode"), der_bytes);
+        }
+
+        // Padata outside of a KRB-ERROR has nothing to be encoded in.
+        let method_data = hex::decode("30363009a103020102a20204003029a103020113a2220420301e301ca003020112a1151b134558414d504c452e434f4d7465737475736572")
+            .expect("Failed to decode sample");
+        let pa_rep = KerberosPaRep::try_from(
+            MethodData::from_der(&method_data).expect("Failed to decode METHOD-DATA"),
+        )
+        .expect("Failed to parse padata");
+        assert!(KerberosResponse::PaRep(pa_rep).to_der().is_err());
+    }
+
+    #[test]
+    fn test_reply_pvno_msg_type() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let kdc_rep = || {
+            let KrbKdcRep::AsRep(kdc_rep) =
+                KrbKdcRep::from_der(&as_rep).expect("Failed to decode AS-REP")
+            else {
+                unreachable!();
+            };
+            kdc_rep
+        };
+
+        let as_rep_type = KrbMessageType::KrbAsRep as u8;
+        let tgs_rep_type = KrbMessageType::KrbTgsRep as u8;
+        let tags: [(fn(KdcRep) -> KrbKdcRep, u8); 2] = [
+            (KrbKdcRep::AsRep, as_rep_type),
+            (KrbKdcRep::TgsRep, tgs_rep_type),
+        ];
+
+        for (tag, expected) in tags {
+            for msg_type in [10, 11, 12, 13, 14, 15, 22, 30, 99] {
+                for pvno in [4, 5, 6] {
+                    let mut rep = kdc_rep();
+                    rep.pvno = pvno;
+                    rep.msg_type = msg_type;
+
+                    match KerberosResponse::try_from(tag(rep)) {
+                        Ok(KerberosResponse::AsRep(_)) => {
+                            assert_eq!(expected, as_rep_type);
+                            assert_eq!((pvno, msg_type), (5, expected));
+                        }
+                        Ok(KerberosResponse::TgsRep(_)) => {
+                            assert_eq!(expected, tgs_rep_type);
+                            assert_eq!((pvno, msg_type), (5, expected));
+                        }
+                        Err(KrbError::InvalidPvno(observed)) => {
+                            assert_ne!(pvno, 5);
+                            assert_eq!(observed, pvno);
+                        }
+                        Err(KrbError::InvalidMessageType(observed, wanted)) => {
+                            assert_eq!(pvno, 5);
+                            assert_ne!(msg_type, expected);
+                            assert_eq!(observed, msg_type as i32);
+                            assert_eq!(wanted, expected as i32);
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+
+        // A KRB-ERROR body claiming to be a reply is refused as well.
+        let err = hex::decode("7e763074a003020105a10302011ea211180f32303234313131353039333030305aa305020301e240a411180f32303234313131353039333730305aa505020309fbf1a603020125a90d1b0b4558414d504c452e434f4daa20301ea003020102a11730151b066b72627467741b0b4558414d504c452e434f4d")
+            .expect("Failed to decode sample");
+        for msg_type in [11, 13, 99] {
+            let mut rep = TaggedKrbError::from_der(&err)
+                .expect("Failed to decode KRB-ERROR")
+                .0;
+            rep.msg_type = msg_type;
+            let Err(KrbError::InvalidMessageType(observed, wanted)) = KerberosErrRep::try_from(rep)
+            else {
+                unreachable!();
+            };
+            assert_eq!(observed, msg_type as i32);
+            assert_eq!(wanted, KrbMessageType::KrbError as i32);
+        }
+
+        // Through the decoder a mismatch is a decode error, never a half-filled reply.
+        let mut as_rep_as_tgs = kdc_rep();
+        as_rep_as_tgs.msg_type = KrbMessageType::KrbTgsRep as u8;
+        let der_bytes = KrbKdcRep::AsRep(as_rep_as_tgs)
+            .to_der()
+            .expect("Failed to encode");
+        assert!(KerberosResponse::from_der(&der_bytes).is_err());
+    }
+
+    #[test]
+    fn test_error_referral() {
+        // KDC_ERR_WRONG_REALM to an enterprise name, as Active Directory refers a
+        // user of a child domain.
+        let err = hex::decode("7e819b308198a003020105a10302011ea411180f32303234313131353039333730305aa505020309fbf1a603020144a7131b114348494c442e4558414d504c452e434f4da8273025a00302010aa11e301c1b1a7465737475736572406368696c642e6578616d706c652e636f6da90d1b0b4558414d504c452e434f4daa20301ea003020102a11730151b066b72627467741b0b4558414d504c452e434f4d")
+            .expect("Failed to decode sample");
+        let KerberosResponse::Err(err) =
+            KerberosResponse::from_der(&err).expect("Failed to decode KRB-ERROR")
+        else {
+            unreachable!();
+        };
+        assert_eq!(err.error_code(), KrbErrorCode::KdcErrWrongRealm);
+        assert_eq!(err.referral_realm(), Some("CHILD.EXAMPLE.COM"));
+        assert_eq!(err.client_name(), Some("testuser@child.example.com"));
+        assert_eq!(err.service_realm(), "EXAMPLE.COM");
+
+        // Only KDC_ERR_WRONG_REALM refers the client elsewhere.
+        let err = KerberosErrorRep {
+            error_code: KrbErrorCode::KdcErrCPrincipalUnknown,
+            ..err
+        };
+        assert_eq!(err.referral_realm(), None);
+    }
+
+    #[test]
+    fn test_error_times() {
+        let client_time = UNIX_EPOCH + Duration::from_micros(1_731_663_000_123_456);
+        let server_time = UNIX_EPOCH + Duration::from_micros(1_731_663_420_654_321);
+
+        // KRB_AP_ERR_SKEW from a KDC whose clock is seven minutes ahead of the client.
+        let err = hex::decode("7e763074a003020105a10302011ea211180f32303234313131353039333030305aa305020301e240a411180f32303234313131353039333730305aa505020309fbf1a603020125a90d1b0b4558414d504c452e434f4daa20301ea003020102a11730151b066b72627467741b0b4558414d504c452e434f4d")
+            .expect("Failed to decode sample");
+        let KerberosResponse::Err(err) =
+            KerberosResponse::from_der(&err).expect("Failed to decode KRB-ERROR")
+        else {
+            unreachable!();
+        };
+        assert_eq!(err.error_code(), KrbErrorCode::KrbApErrSkew);
+        assert_eq!(err.ctime(), Some(client_time));
+        assert_eq!(err.stime(), server_time);
+
+        // The request for preauthentication carries the same times.
+        let err = hex::decode("7e8188308185a003020105a10302011ea211180f32303234313131353039333030305aa305020301e240a411180f32303234313131353039333730305aa505020309fbf1a603020119a90d1b0b4558414d504c452e434f4daa20301ea003020102a11730151b066b72627467741b0b4558414d504c452e434f4dac0f040d300b3009a103020102a2020400")
+            .expect("Failed to decode sample");
+        let KerberosResponse::PaRep(pa_rep) =
+            KerberosResponse::from_der(&err).expect("Failed to decode KRB-ERROR")
+        else {
+            unreachable!();
+        };
+        assert!(pa_rep.enc_timestamp);
+        assert_eq!(pa_rep.ctime(), Some(client_time));
+        assert_eq!(pa_rep.stime(), Some(server_time));
+    }
+
+    #[test]
+    fn test_error_e_data() {
+        let err_rep = |error_code, error_data: &str| KerberosErrorRep {
+            error_code,
+            ctime: None,
+            stime: UNIX_EPOCH,
+            client_realm: Some("EXAMPLE.COM".to_string()),
+            client_name: Some("testuser".to_string()),
+            service_realm: "EXAMPLE.COM".to_string(),
+            service_name: "krbtgt/EXAMPLE.COM".to_string(),
+            error_text: None,
+            error_data: Some(hex::decode(error_data).expect("Failed to decode sample")),
+            client_name_type: 1,
+            service_name_type: 2,
+        };
+
+        // MIT returns the ETYPE-INFO2 in a METHOD-DATA when preauthentication fails.
+        let err = err_rep(
+            KrbErrorCode::KdcErrPreauthFailed,
+            "302b3029a103020113a2220420301e301ca003020112a1151b134558414d504c452e434f4d7465737475736572",
+        );
+        let Some(ErrorEData::MethodData(entries)) = err.e_data() else {
+            unreachable!();
+        };
+        let [PreAuthEntry::Raw {
+            padata_type: 19,
+            value: _,
+        }] = entries.as_slice()
+        else {
+            unreachable!();
+        };
+
+        let pa_rep = err.pa_rep().expect("Failed to parse METHOD-DATA");
+        assert_eq!(pa_rep.client_name(), Some("testuser"));
+        let [einfo2] = pa_rep.etype_info2.as_slice() else {
+            unreachable!();
+        };
+        assert_eq!(einfo2.etype, EncryptionType::AES256_CTS_HMAC_SHA1_96);
+        assert_eq!(einfo2.salt.as_deref(), Some("EXAMPLE.COMtestuser"));
+
+        // Active Directory returns a KERB-ERROR-DATA with an extended error, here
+        // STATUS_PASSWORD_EXPIRED, which is not a METHOD-DATA.
+        let error_data = "3015a103020103a20e040c710000c00000000001000000";
+        let err = err_rep(KrbErrorCode::KdcErrKeyExpired, error_data);
+        let Some(ErrorEData::Raw(raw)) = err.e_data() else {
+            unreachable!();
+        };
+        assert_eq!(hex::encode(raw), error_data);
+        assert!(err.pa_rep().is_none());
+    }
+
+    #[test]
+    fn test_clock_offset() {
+        let local = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let ahead = ClockOffset::new(local + Duration::from_secs(600), local);
+        let behind = ClockOffset::new(local - Duration::from_secs(600), local);
+
+        let time = local + Duration::from_secs(60);
+        assert_eq!(ahead.apply(time), time + Duration::from_secs(600));
+        assert_eq!(behind.apply(time), time - Duration::from_secs(600));
+        assert_eq!(ClockOffset::default().apply(time), time);
+
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        // The authenticator is sent in the time of the KDC, an hour behind us.
+        let clock_offset = ClockOffset::new(
+            SystemTime::now() - Duration::from_secs(3600),
+            SystemTime::now(),
+        );
+        let KerberosRequest::TgsReq(tgs_req) = KerberosRequest::build_tgsreq(
+            "host/localhost",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .clock_offset(clock_offset)
+        .build(&tgt)
+        .expect("Failed to build request") else {
+            unreachable!();
+        };
+
+        let authenticator = tgs_req
+            .verify_authenticator(&tgt.session_key)
+            .expect("Failed to verify authenticator");
+        let skew = SystemTime::now()
+            .duration_since(authenticator.ctime())
+            .expect("Authenticator is from the future");
+        assert!(skew > Duration::from_secs(3540) && skew < Duration::from_secs(3660));
+    }
+
+    #[test]
+    fn test_tgs_req_renew_validate() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        let renew_req =
+            KerberosRequest::build_renew(&tgt, SystemTime::now() + Duration::from_secs(3600))
+                .expect("Failed to build request");
+
+        let der_bytes = renew_req.to_der().expect("Failed to encode request");
+
+        let KerberosRequest::TgsReq(tgs_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+
+        // The request is for the TGS that issued the ticket, presenting the ticket.
+        assert_eq!(tgs_req.kdc_options(), FlagSet::from(KdcOptions::Renew));
+        assert_eq!(tgs_req.body.service_name, "krbtgt/EXAMPLE.COM");
+        assert_eq!(tgs_req.body.realm, "EXAMPLE.COM");
+        assert_eq!(tgs_req.ap_req.0.ticket, tgt.ticket);
+
+        let validate_req = KerberosRequest::build_validate(&tgt).expect("Failed to build request");
+
+        let der_bytes = validate_req.to_der().expect("Failed to encode request");
+
+        let KerberosRequest::TgsReq(tgs_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+
+        assert_eq!(tgs_req.kdc_options(), FlagSet::from(KdcOptions::Validate));
+        assert_eq!(tgs_req.body.service_name, "krbtgt/EXAMPLE.COM");
+        assert_eq!(tgs_req.ap_req.0.ticket, tgt.ticket);
+    }
+
+    #[test]
+    fn test_as_req_fast_armor() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        let armor = FastArmor::new(
+            &tgt,
+            BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x24; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+        )
+        .expect("Failed to build armor");
+
+        let as_req = KerberosRequest::build_asreq(
+            "testuser",
+            "krbtgt/EXAMPLE.COM",
+            "EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .pac_option(PacOptions::Claims, true)
+        .armor(&armor)
+        .build()
+        .expect("Failed to build request");
+
+        let der_bytes = as_req.to_der().expect("Failed to encode request");
+
+        // Only the PA-FX-FAST is sent in the clear, the rest of the padata is armored.
+        let KrbKdcReq::AsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        let padata = kdc_req.padata.expect("Missing padata");
+        assert_eq!(padata.len(), 1);
+        assert_eq!(padata[0].padata_type, 136);
+
+        let fast = PaFxFastRequest::from_der(padata[0].padata_value.as_bytes())
+            .expect("Failed to decode PA-FX-FAST")
+            .0;
+
+        // The armor carries the TGT, with the subkey in the authenticator.
+        let fast_armor = fast.armor.expect("Missing armor");
+        assert_eq!(fast_armor.armor_type, 1);
+        let ap_req = TaggedApReq::from_der(fast_armor.armor_value.as_bytes())
+            .expect("Failed to decode armor")
+            .0;
+        assert_eq!(ap_req.ticket, tgt.ticket);
+
+        let authenticator = EncryptedData::try_from(ap_req.authenticator)
+            .and_then(|enc_data| {
+                enc_data.decrypt_data(&tgt.session_key, KeyUsage::AP_REQ_AUTHENTICATOR)
+            })
+            .expect("Failed to decrypt authenticator");
+        let authenticator = TaggedAuthenticator::from_der(&authenticator)
+            .expect("Failed to decode authenticator")
+            .0;
+        let subkey = authenticator.subkey.expect("Missing subkey");
+        assert_eq!(subkey.key_value.as_bytes(), [0x24; AES_256_KEY_LEN]);
+
+        // The checksum binds the armor to the outer req-body.
+        let req_body = kdc_req
+            .req_body
+            .to_der()
+            .expect("Failed to encode req-body");
+        let cksum = armor
+            .armor_key
+            .checksum(&req_body, KeyUsage::FAST_REQ_CHKSUM)
+            .expect("Failed to checksum req-body");
+        assert_eq!(fast.req_checksum, cksum);
+
+        let fast_req = EncryptedData::try_from(fast.enc_fast_req)
+            .and_then(|enc_data| enc_data.decrypt_data(&armor.armor_key, KeyUsage::FAST_ENC))
+            .expect("Failed to decrypt KrbFastReq");
+        let fast_req = KrbFastReq::from_der(&fast_req).expect("Failed to decode KrbFastReq");
+        assert_eq!(fast_req.req_body, kdc_req.req_body);
+        assert_eq!(fast_req.padata.len(), 1);
+        assert_eq!(fast_req.padata[0].padata_type, 167);
+
+        let KerberosRequest::AsReq(as_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert!(as_req.armored());
+    }
+
+    #[test]
+    fn test_as_rep_fast_unarmor() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm.clone(),
+            client_name: as_rep.client_name.clone(),
+            ticket: as_rep.ticket.clone(),
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        let armor = FastArmor::new(
+            &tgt,
+            BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x24; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+        )
+        .expect("Failed to build armor");
+
+        // Armor the sample reply the way the KDC would.
+        let ticket = as_rep.ticket.to_der().expect("Failed to encode ticket");
+        let fast_rep = KrbFastResponse {
+            padata: Vec::new(),
+            strengthen_key: Some(
+                KdcEncryptionKey::try_from(
+                    &BaseKey::from_bytes(
+                        EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                        &[0x33; AES_256_KEY_LEN],
+                    )
+                    .expect("Invalid key"),
+                )
+                .expect("Failed to encode strengthen key"),
+            ),
+            finished: Some(KrbFastFinished {
+                timestamp: KerberosTime::from_unix_duration(Duration::from_secs(1_700_000_000))
+                    .expect("Invalid timestamp"),
+                usec: 0,
+                crealm: KerberosString(Ia5String::new("EXAMPLE.COM").expect("Invalid realm")),
+                cname: principal_name_from_str(1, "armored").expect("Invalid name"),
+                ticket_checksum: armor
+                    .armor_key
+                    .checksum(&ticket, KeyUsage::FAST_FINISHED)
+                    .expect("Failed to checksum ticket"),
+            }),
+            nonce: 0x1234_5678,
+        }
+        .to_der()
+        .expect("Failed to encode KrbFastResponse");
+
+        let as_rep = KerberosAsRep {
+            fast_rep: Some(
+                armor
+                    .armor_key
+                    .encrypt_data(&fast_rep, KeyUsage::FAST_REP)
+                    .expect("Failed to encrypt KrbFastResponse"),
+            ),
+            ..as_rep
+        };
+
+        let der_bytes = KerberosResponse::AsRep(as_rep)
+            .to_der()
+            .expect("Failed to encode reply");
+        let decode = || {
+            let KerberosResponse::AsRep(as_rep) =
+                KerberosResponse::from_der(&der_bytes).expect("Failed to decode reply")
+            else {
+                unreachable!();
+            };
+            as_rep
+        };
+
+        // An armored reply can't be used until it is checked.
+        let err = decode()
+            .into_authenticated_reply(&tgt.session_key)
+            .expect_err("Armored reply was accepted");
+        assert!(matches!(err, KrbError::FastReplyArmored));
+
+        let err = decode()
+            .unarmor(&armor, 0x0765_4321)
+            .expect_err("Nonce mismatch was accepted");
+        assert!(matches!(err, KrbError::FastNonceMismatch));
+
+        let as_rep = decode()
+            .unarmor(&armor, 0x1234_5678)
+            .expect("Failed to unarmor reply");
+        assert!(as_rep.fast_rep.is_none());
+        assert!(as_rep.strengthen_key.is_some());
+        assert_eq!(as_rep.client_realm, "EXAMPLE.COM");
+        assert_eq!(as_rep.client_name, "armored");
+
+        // The ticket must be the one the KDC armored.
+        let other = FastArmor::new(
+            &tgt,
+            BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x25; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+        )
+        .expect("Failed to build armor");
+        assert!(decode().unarmor(&other, 0x1234_5678).is_err());
+    }
+
+    #[test]
+    fn test_tgs_req_cross_realm() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let mut tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+        assert_eq!(tgt.ticket().tgs_realm().as_deref(), Some("EXAMPLE.COM"));
+
+        // Ask the local TGS for a cross-realm TGT.
+        let KerberosRequest::TgsReq(tgs_req) = KerberosRequest::build_tgsreq(
+            "krbtgt/REMOTE.COM@EXAMPLE.COM",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build(&tgt)
+        .expect("Failed to build request") else {
+            unreachable!();
+        };
+        assert_eq!(tgs_req.body.service_name, "krbtgt/REMOTE.COM");
+        assert_eq!(tgs_req.body.realm, "EXAMPLE.COM");
+
+        // A cross-realm TGT is issued by the local realm for the remote TGS, so
+        // requests made with it are for services in the remote realm.
+        tgt.ticket.0.sname = principal_name_from_str(2, "krbtgt/REMOTE.COM")
+            .expect("Failed to build principal name");
+        let cross_realm_tgt = tgt.ticket();
+        assert_eq!(cross_realm_tgt.service_realm(), "EXAMPLE.COM");
+        assert_eq!(cross_realm_tgt.tgs_realm().as_deref(), Some("REMOTE.COM"));
+
+        let KerberosRequest::TgsReq(tgs_req) = KerberosRequest::build_tgsreq(
+            "host/server.remote.com",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .build(&tgt)
+        .expect("Failed to build request") else {
+            unreachable!();
+        };
+        assert_eq!(tgs_req.body.service_name, "host/server.remote.com");
+        assert_eq!(tgs_req.body.realm, "REMOTE.COM");
+
+        // Only a TGT has a TGS realm.
+        tgt.ticket.0.sname = principal_name_from_str(2, "host/server.remote.com")
+            .expect("Failed to build principal name");
+        assert_eq!(tgt.ticket().tgs_realm(), None);
+    }
+
+    #[test]
+    fn test_tgs_req_user_to_user() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        // A synthetic TGT of the peer, which is only ever carried opaquely.
+        let mut peer_tgt = tgt.ticket.clone();
+        peer_tgt.0.enc_part.cipher =
+            OctetString::new(vec![0xab; 64]).expect("Failed to build cipher");
+        let peer_tgt = Ticket::from_der(&peer_tgt.to_der().expect("Failed to encode ticket"))
+            .expect("Failed to decode ticket");
+        assert_eq!(peer_tgt.service_name(), "krbtgt/EXAMPLE.COM");
+        assert_eq!(peer_tgt.service_realm(), "EXAMPLE.COM");
+
+        let tgs_req = KerberosRequest::build_tgsreq(
+            "testuser",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .kdc_options(KdcOptions::EncTktInSkey.into())
+        .additional_ticket(peer_tgt.clone())
+        .build(&tgt)
+        .expect("Failed to build request");
+
+        let der_bytes = tgs_req.to_der().expect("Failed to encode request");
+
+        let KrbKdcReq::TgsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        let additional_tickets = kdc_req
+            .req_body
+            .additional_tickets
+            .as_deref()
+            .expect("Missing additional tickets");
+        assert_eq!(additional_tickets, [peer_tgt.0.clone()]);
+
+        let KerberosRequest::TgsReq(tgs_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(
+            tgs_req.kdc_options(),
+            FlagSet::from(KdcOptions::EncTktInSkey)
+        );
+        assert_eq!(tgs_req.additional_tickets(), [peer_tgt]);
+    }
+
+    #[test]
+    fn test_tgs_req_s4u2proxy() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        // A synthetic ticket of the user to the service, as from S4U2Self.
+        let mut evidence_ticket = tgt.ticket.clone();
+        evidence_ticket.0.sname =
+            principal_name_from_str(1, "testuser").expect("Failed to build principal name");
+        evidence_ticket.0.enc_part.cipher =
+            OctetString::new(vec![0xcd; 64]).expect("Failed to build cipher");
+        let evidence_ticket = Ticket(evidence_ticket);
+
+        let tgs_req = KerberosRequest::build_tgsreq(
+            "testuser",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .kdc_options(KdcOptions::Forwardable.into())
+        .delegate(evidence_ticket.clone(), "host/localhost".to_string())
+        .build(&tgt)
+        .expect("Failed to build request");
+
+        let der_bytes = tgs_req.to_der().expect("Failed to encode request");
+
+        // MS-KILE PA-PAC-OPTIONS with only resource-based-constrained-delegation.
+        let KrbKdcReq::TgsReq(kdc_req) =
+            KrbKdcReq::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        let pac_options = kdc_req
+            .padata
+            .iter()
+            .flatten()
+            .find(|padata| padata.padata_type == 167)
+            .expect("Missing PA-PAC-OPTIONS");
+        assert_eq!(
+            pac_options.padata_value.as_bytes(),
+            [0x30, 0x09, 0xa0, 0x07, 0x03, 0x05, 0x00, 0x10, 0x00, 0x00, 0x00]
+        );
+
+        let KerberosRequest::TgsReq(tgs_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+        assert_eq!(tgs_req.body.service_name, "host/localhost");
+        assert_eq!(
+            tgs_req.kdc_options(),
+            KdcOptions::Forwardable | KdcOptions::ConstrainedDelegation
+        );
+        assert_eq!(tgs_req.evidence_ticket(), Some(&evidence_ticket));
+        assert_eq!(tgs_req.additional_tickets(), [evidence_ticket]);
+        assert_eq!(
+            tgs_req.pac_options(),
+            Some(PacOptions::ResourceBasedConstrainedDelegation.into())
+        );
+        assert!(tgs_req.for_user().is_none());
+
+        // Other options are kept alongside resource-based delegation.
+        let KerberosRequest::TgsReq(tgs_req) = KerberosRequest::build_tgsreq(
+            "testuser",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .pac_option(PacOptions::Claims, true)
+        .delegate(evidence_ticket, "host/localhost".to_string())
+        .build(&tgt)
+        .expect("Failed to build request") else {
+            unreachable!();
+        };
+        assert_eq!(
+            tgs_req.pac_options(),
+            Some(PacOptions::Claims | PacOptions::ResourceBasedConstrainedDelegation)
+        );
+    }
+
+    #[test]
+    fn test_tgs_req_s4u2self() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+
+        let tgt = AuthenticatedReply {
+            client_realm: as_rep.client_realm,
+            client_name: as_rep.client_name,
+            ticket: as_rep.ticket,
+            session_key: BaseKey::from_bytes(
+                EncryptionType::AES256_CTS_HMAC_SHA1_96,
+                &[0x42; AES_256_KEY_LEN],
+            )
+            .expect("Invalid key"),
+            flags: FlagSet::default(),
+            auth_time: UNIX_EPOCH,
+            start_time: None,
+            end_time: UNIX_EPOCH,
+            renew_till: None,
+            request_verified: false,
+            kdc_supports_fast: false,
+        };
+
+        // The service requests a ticket to itself.
+        let tgs_req = KerberosRequest::build_tgsreq(
+            "testuser",
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .s4u2self("testuser_preauth".to_string(), "EXAMPLE.COM".to_string())
+        .build(&tgt)
+        .expect("Failed to build request");
+
+        let der_bytes = tgs_req.to_der().expect("Failed to encode request");
+
+        let KerberosRequest::TgsReq(tgs_req) =
+            KerberosRequest::from_der(&der_bytes).expect("Failed to decode request")
+        else {
+            unreachable!();
+        };
+
+        let for_user = tgs_req.for_user().expect("Missing PA-FOR-USER");
+        assert_eq!(for_user.user_name(), "testuser_preauth");
+        assert_eq!(for_user.user_realm(), "EXAMPLE.COM");
+        assert_eq!(for_user.auth_package, "Kerberos");
+
+        // HMAC-MD5 keyed with the session key over 01000000 | "testuser_preauth" |
+        // "EXAMPLE.COM" | "Kerberos", with key usage 17.
+        assert_eq!(for_user.cksum.checksum_type, ChecksumType::HMAC_MD5 as i32);
+        assert_eq!(
+            for_user.cksum.checksum.as_bytes(),
+            hex::decode("c645519d12dd9a52417ae93c6f16b7e3").expect("Failed to decode checksum")
+        );
+
+        assert!(for_user.verify(&tgt.session_key).is_ok());
+
+        let wrong_key = BaseKey::from_bytes(
+            EncryptionType::AES256_CTS_HMAC_SHA1_96,
+            &[0x24; AES_256_KEY_LEN],
+        )
+        .expect("Invalid key");
+        assert!(matches!(
+            for_user.verify(&wrong_key),
+            Err(KrbError::MessageAuthenticationFailed)
+        ));
+
+        // The checksum binds the user to the request.
+        let mut for_user = for_user.clone();
+        for_user.user_realm = "EVIL.COM".to_string();
+        assert!(matches!(
+            for_user.verify(&tgt.session_key),
+            Err(KrbError::MessageAuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_decode_responses() {
+        let as_rep = hex::decode(AS_REP_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::AsRep(as_rep) =
+            KerberosResponse::from_der(&as_rep).expect("Failed to decode AS-REP")
+        else {
+            unreachable!();
+        };
+        assert_eq!(as_rep.client_name, "testuser");
+        assert_eq!(as_rep.client_realm, "EXAMPLE.COM");
+
+        let krb_error = hex::decode(RESPONSE_TOO_BIG_SAMPLE).expect("Failed to decode sample");
+        let KerberosResponse::Err(err_rep) =
+            KerberosResponse::from_der(&krb_error).expect("Failed to decode KRB-ERROR")
+        else {
+            unreachable!();
+        };
+        assert_eq!(err_rep.error_code(), KrbErrorCode::KrbErrResponseTooBig);
+        assert_eq!(err_rep.ctime(), None);
+        assert_eq!(
+            err_rep.stime(),
+            UNIX_EPOCH + Duration::from_secs(1718192885) + Duration::from_micros(121958)
+        );
+        assert_eq!(err_rep.client_realm(), None);
+        assert_eq!(err_rep.client_name(), None);
+        assert_eq!(err_rep.service_realm(), "AFOREST.AD");
+        assert_eq!(err_rep.service_name(), "krbtgt/AFOREST.AD");
+        assert_eq!(err_rep.error_text(), None);
+        assert_eq!(err_rep.error_data(), None);
+    }
 }
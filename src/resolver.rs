@@ -0,0 +1,211 @@
+pub use crate::client::{KdcAddress, KdcTransport};
+use crate::constants::DEFAULT_KDC_PORT;
+use crate::error::KrbError;
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::TokioAsyncResolver;
+use rand::{thread_rng, Rng};
+use std::net::SocketAddr;
+use tracing::{debug, trace};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SrvEntry {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: String,
+}
+
+/// Discover the KDCs of a realm from the `_kerberos._tcp.REALM` and `_kerberos._udp.REALM`
+/// SRV records, in the order they should be tried. TCP KDCs are listed before UDP ones.
+/// If the realm has no SRV records, then `kerberos.REALM` is used instead.
+pub async fn resolve_kdcs(realm: &str) -> Result<Vec<KdcAddress>, KrbError> {
+    let resolver = TokioAsyncResolver::tokio_from_system_conf().map_err(|err| {
+        debug!(?err, "Unable to configure DNS resolver");
+        KrbError::DnsLookupFailed(err.to_string())
+    })?;
+
+    let mut kdcs = Vec::with_capacity(4);
+
+    for transport in [KdcTransport::Tcp, KdcTransport::Udp] {
+        let proto = match transport {
+            KdcTransport::Tcp => "_tcp",
+            KdcTransport::Udp => "_udp",
+        };
+        // Fully qualify the name so that search domains aren't applied.
+        let name = format!("_kerberos.{}.{}.", proto, realm);
+
+        let records = match resolver.srv_lookup(name.as_str()).await {
+            Ok(lookup) => lookup
+                .iter()
+                .map(|srv| SrvEntry {
+                    priority: srv.priority(),
+                    weight: srv.weight(),
+                    port: srv.port(),
+                    target: srv.target().to_string(),
+                })
+                .collect(),
+            Err(err) if is_no_records(&err) => {
+                trace!(%name, "No SRV records found");
+                Vec::with_capacity(0)
+            }
+            Err(err) => {
+                debug!(?err, %name, "SRV lookup failed");
+                return Err(KrbError::DnsLookupFailed(err.to_string()));
+            }
+        };
+
+        for srv in order_srv_records(records, &mut thread_rng()) {
+            // RFC 2782 - A target of "." means the service is decidedly not
+            // available at this domain.
+            if srv.target == "." {
+                continue;
+            }
+
+            for ip in lookup_host(&resolver, &srv.target).await? {
+                kdcs.push(KdcAddress {
+                    transport,
+                    addr: SocketAddr::new(ip, srv.port),
+                });
+            }
+        }
+    }
+
+    if kdcs.is_empty() {
+        let host = format!("kerberos.{}.", realm);
+        for ip in lookup_host(&resolver, &host).await? {
+            for transport in [KdcTransport::Tcp, KdcTransport::Udp] {
+                kdcs.push(KdcAddress {
+                    transport,
+                    addr: SocketAddr::new(ip, DEFAULT_KDC_PORT),
+                });
+            }
+        }
+        kdcs.sort_by_key(|kdc| kdc.transport == KdcTransport::Udp);
+    }
+
+    if kdcs.is_empty() {
+        Err(KrbError::NoKdcFound)
+    } else {
+        Ok(kdcs)
+    }
+}
+
+async fn lookup_host(
+    resolver: &TokioAsyncResolver,
+    host: &str,
+) -> Result<Vec<std::net::IpAddr>, KrbError> {
+    match resolver.lookup_ip(host).await {
+        Ok(lookup) => Ok(lookup.iter().collect()),
+        Err(err) if is_no_records(&err) => {
+            trace!(%host, "No address records found");
+            Ok(Vec::with_capacity(0))
+        }
+        Err(err) => {
+            debug!(?err, %host, "Address lookup failed");
+            Err(KrbError::DnsLookupFailed(err.to_string()))
+        }
+    }
+}
+
+fn is_no_records(err: &ResolveError) -> bool {
+    matches!(err.kind(), ResolveErrorKind::NoRecordsFound { .. })
+}
+
+/// Order SRV records as described by RFC 2782. Records are tried in order of
+/// lowest priority first. Within a priority, records are selected at random
+/// in proportion to their weight.
+fn order_srv_records<R: Rng>(mut records: Vec<SrvEntry>, rng: &mut R) -> Vec<SrvEntry> {
+    records.sort_by_key(|srv| srv.priority);
+
+    let mut ordered = Vec::with_capacity(records.len());
+
+    for group in records.chunk_by(|a, b| a.priority == b.priority) {
+        let mut group = group.to_vec();
+        // Zero weight records are placed first, so that they have a very
+        // small chance of being selected.
+        group.sort_by_key(|srv| srv.weight != 0);
+
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|srv| srv.weight as u32).sum();
+            let selection = rng.gen_range(0..=total);
+
+            let mut running_sum = 0;
+            let idx = group
+                .iter()
+                .position(|srv| {
+                    running_sum += srv.weight as u32;
+                    running_sum >= selection
+                })
+                .unwrap_or(0);
+
+            ordered.push(group.remove(idx));
+        }
+    }
+
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{order_srv_records, SrvEntry};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn srv(priority: u16, weight: u16, target: &str) -> SrvEntry {
+        SrvEntry {
+            priority,
+            weight,
+            port: 88,
+            target: target.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_order_srv_records_priority() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let records = vec![
+            srv(20, 0, "kdc3.example.com."),
+            srv(0, 50, "kdc1.example.com."),
+            srv(10, 100, "kdc2.example.com."),
+        ];
+
+        let ordered = order_srv_records(records, &mut rng);
+        let targets: Vec<_> = ordered.iter().map(|srv| srv.target.as_str()).collect();
+        assert_eq!(
+            targets,
+            [
+                "kdc1.example.com.",
+                "kdc2.example.com.",
+                "kdc3.example.com."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order_srv_records_weight() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // Every record must be returned exactly once, within its priority group.
+        for _ in 0..100 {
+            let records = vec![
+                srv(0, 0, "a."),
+                srv(0, 10, "b."),
+                srv(0, 90, "c."),
+                srv(1, 0, "d."),
+                srv(1, 0, "e."),
+            ];
+
+            let ordered = order_srv_records(records, &mut rng);
+            assert_eq!(ordered.len(), 5);
+
+            let mut first: Vec<_> = ordered[..3].iter().map(|srv| srv.target.as_str()).collect();
+            first.sort_unstable();
+            assert_eq!(first, ["a.", "b.", "c."]);
+
+            // With no weights the order of the records is retained.
+            let last: Vec<_> = ordered[3..].iter().map(|srv| srv.target.as_str()).collect();
+            assert_eq!(last, ["d.", "e."]);
+        }
+    }
+}